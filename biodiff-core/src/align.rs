@@ -1,20 +1,23 @@
+pub mod anchor;
+pub mod runs;
 mod rustbio;
 use std::{
     ops::Range,
     sync::{
         atomic::{AtomicBool, AtomicU16, Ordering},
-        mpsc::{Sender, SyncSender},
-        Arc,
+        mpsc::{channel, Sender, SyncSender},
+        Arc, Mutex, RwLock,
     },
     thread::available_parallelism,
 };
 
-use crate::{file::FileContent, view::AlignedMessage};
+use crate::file::{FileBytes, FileContent};
+pub use bio::alignment::AlignmentOperation;
 use bio::alignment::AlignmentOperation as Op;
 use realfft::{num_complex::Complex64, RealFftPlanner, RealToComplex};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use self::rustbio::{align_banded, RustBio};
+use self::rustbio::RustBio;
 
 pub const DEFAULT_BLOCKSIZE: usize = 8192;
 pub const DEFAULT_KMER: usize = 8;
@@ -48,8 +51,111 @@ impl From<AlignMode> for InternalMode {
     }
 }
 
-trait Align {
-    fn align(&self, algo: &AlignAlgorithm, mode: InternalMode, x: &[u8], y: &[u8]) -> Vec<Op>;
+/// An algorithm that aligns two byte sequences, which is used for each block in the
+/// blockwise mode and for the whole files in the local and global modes.
+///
+/// Another one is added by implementing this trait and passing a static reference to it to
+/// [`register_aligner`], after which the algorithm settings list it by its name, which is
+/// also how the settings store it, so it should be unique.
+///
+/// The aligner only aligns the bytes it gets, and the alignment threads around it take care
+/// of the rest: after each block, they send its elements to the view, which shows them as
+/// they arrive, and they stop before the next block once the view is gone and sending fails.
+/// The local and global modes align the whole files in a single call, which therefore
+/// can not be cancelled, so slow aligners are better used in the blockwise mode.
+///
+/// An aligner that can not align some bytes, for example because it runs out of memory,
+/// returns an error instead of aborting, and the bytes are then aligned with rust-bio,
+/// while the view shows a message about it.
+pub trait Aligner: Sync {
+    /// the name shown in the algorithm settings and stored in the settings file
+    fn name(&self) -> &'static str;
+    /// Aligns `x` to `y` with the scores of `algo`, where the clips of the operations
+    /// are allowed as described by `mode`
+    fn align(
+        &self,
+        algo: &AlignAlgorithm,
+        mode: InternalMode,
+        x: &[u8],
+        y: &[u8],
+    ) -> Result<Vec<AlignmentOperation>, AlignError>;
+}
+
+/// The reason an aligner failed to align some bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlignError(pub String);
+
+impl std::fmt::Display for AlignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AlignError {}
+
+/// The aligners that were registered in addition to the built-in ones
+static REGISTERED_ALIGNERS: RwLock<Vec<&'static dyn Aligner>> = RwLock::new(Vec::new());
+
+/// Adds an aligner to the ones that can be chosen in the settings
+pub fn register_aligner(aligner: &'static dyn Aligner) {
+    REGISTERED_ALIGNERS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(aligner);
+}
+
+/// The built-in aligners followed by the registered ones
+pub fn aligners() -> Vec<&'static dyn Aligner> {
+    let mut aligners: Vec<&'static dyn Aligner> = vec![&RustBio];
+    aligners.extend(
+        REGISTERED_ALIGNERS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter(),
+    );
+    aligners
+}
+
+/// The aligner that an `AlignAlgorithm` uses, which is stored by its name
+#[derive(Clone, Copy)]
+pub struct AlignerChoice(pub &'static dyn Aligner);
+
+impl AlignerChoice {
+    /// The aligner with the given name, if it is built-in or registered
+    pub fn by_name(name: &str) -> Option<Self> {
+        aligners()
+            .into_iter()
+            .find(|aligner| aligner.name() == name)
+            .map(AlignerChoice)
+    }
+}
+
+impl Default for AlignerChoice {
+    fn default() -> Self {
+        AlignerChoice(&RustBio)
+    }
+}
+
+impl std::fmt::Debug for AlignerChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AlignerChoice")
+            .field(&self.0.name())
+            .finish()
+    }
+}
+
+impl Serialize for AlignerChoice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.name())
+    }
+}
+
+/// Aligners that are not registered in this run fall back to the default one
+impl<'de> Deserialize<'de> for AlignerChoice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(AlignerChoice::by_name(&name).unwrap_or_default())
+    }
 }
 
 /// Determines whether to use the banded variant of the algorithm with given k-mer length
@@ -70,6 +176,7 @@ pub struct AlignAlgorithm {
     pub match_score: i32,
     pub mode: AlignMode,
     pub band: Banded,
+    pub aligner: AlignerChoice,
 }
 
 impl Default for AlignAlgorithm {
@@ -81,28 +188,122 @@ impl Default for AlignAlgorithm {
             match_score: 1,
             mode: AlignMode::Blockwise(DEFAULT_BLOCKSIZE),
             band: Banded::Normal,
+            aligner: AlignerChoice::default(),
+        }
+    }
+}
+
+/// A part of the alignment that the alignment threads send while they are running.
+/// The parts of each direction arrive in order, which means the prepended ones
+/// arrive from the back to the front.
+#[derive(Clone, Debug)]
+pub enum AlignedBlock {
+    /// elements after the ones that arrived so far
+    Append(Vec<AlignElement>),
+    /// elements before the ones that arrived so far
+    Prepend(Vec<AlignElement>),
+    /// the chosen aligner failed, so the bytes of the next block were aligned with rust-bio
+    Fallback {
+        aligner: &'static str,
+        error: AlignError,
+    },
+    /// all threads of the alignment are finished, which is the last block they send
+    Done,
+}
+
+/// Sends `AlignedBlock::Done` when the last of the alignment threads sharing it drops it,
+/// which also happens if one of them panics
+struct DoneGuard<M: From<AlignedBlock>>(Mutex<Sender<M>>);
+
+impl<M: From<AlignedBlock>> DoneGuard<M> {
+    fn new(sender: &Sender<M>) -> Arc<Self> {
+        Arc::new(DoneGuard(Mutex::new(sender.clone())))
+    }
+}
+
+impl<M: From<AlignedBlock>> Drop for DoneGuard<M> {
+    fn drop(&mut self) {
+        if let Ok(sender) = self.0.get_mut() {
+            let _ = sender.send(AlignedBlock::Done.into());
         }
     }
 }
 
 impl AlignAlgorithm {
-    /// This function starts the threads for the alignment, which send the data over the sender.
+    /// Aligns the bytes of `x` and `y` as a whole and waits for the result.
+    /// The blockwise mode starts at the start of both, and for showing the alignment
+    /// while it is still running, `start_align` sends the blocks over a channel instead.
+    ///
+    /// ```
+    /// use biodiff_core::align::AlignAlgorithm;
+    ///
+    /// let alignment = AlignAlgorithm::default().align_bytes(b"a bc", b"abc");
+    /// let addresses: Vec<_> = alignment.iter().map(|e| e.byte_addresses()).collect();
+    /// // the space is only in the first one
+    /// assert_eq!(
+    ///     addresses,
+    ///     [
+    ///         [Some(0), Some(0)],
+    ///         [Some(1), None],
+    ///         [Some(2), Some(1)],
+    ///         [Some(3), Some(2)],
+    ///     ]
+    /// );
+    /// ```
+    pub fn align_bytes(&self, x: &[u8], y: &[u8]) -> Vec<AlignElement> {
+        let [x, y] = [x, y].map(|bytes| Arc::new(FileBytes::from(bytes.to_vec())));
+        let (sender, receiver) = channel::<AlignedBlock>();
+        self.start_align(x, y, (0, 0), sender);
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        // the channel closes when all alignment threads are done
+        for block in receiver {
+            match block {
+                AlignedBlock::Append(elements) => back.extend(elements),
+                AlignedBlock::Prepend(elements) => front.push(elements),
+                AlignedBlock::Fallback { .. } | AlignedBlock::Done => {}
+            }
+        }
+        front.into_iter().rev().flatten().chain(back).collect()
+    }
+    /// This function starts the threads for the alignment, which send the data over the sender,
+    /// which can be a `Sender<AlignedBlock>` or one of messages that `AlignedBlock`s turn into.
     /// It should then immediately return.
-    pub fn start_align(
+    /// Once all of the threads are finished, they send `AlignedBlock::Done`.
+    pub fn start_align<M: From<AlignedBlock> + Send + 'static>(
+        &self,
+        x: FileContent,
+        y: FileContent,
+        addr: (usize, usize),
+        sender: Sender<M>,
+    ) {
+        self.start_align_cancellable(x, y, addr, sender, Arc::default())
+    }
+    /// Like `start_align`, but the blockwise threads stop before their next block
+    /// once `cancel` is set, for when the receiver already knows enough
+    pub fn start_align_cancellable<M: From<AlignedBlock> + Send + 'static>(
         &self,
         x: FileContent,
         y: FileContent,
         addr: (usize, usize),
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
+        cancel: Arc<AtomicBool>,
     ) {
         let algo = *self;
+        let done = DoneGuard::new(&sender);
         match self.mode {
             AlignMode::Local => {
                 // we only need one thread
-                std::thread::spawn(move || algo.align_whole(x, y, InternalMode::Local, sender));
+                std::thread::spawn(move || {
+                    let _done = done;
+                    algo.align_whole(x, y, InternalMode::Local, sender)
+                });
             }
             AlignMode::Global => {
-                std::thread::spawn(move || algo.align_whole(x, y, InternalMode::Global, sender));
+                std::thread::spawn(move || {
+                    let _done = done;
+                    algo.align_whole(x, y, InternalMode::Global, sender)
+                });
             }
             AlignMode::Blockwise(blocksize) => {
                 // for Blockwise, we need one thread for each direction from the cursor
@@ -110,19 +311,25 @@ impl AlignAlgorithm {
                 let x_cp = x.clone();
                 let y_cp = y.clone();
                 let sender_cp = sender.clone();
-                std::thread::spawn(move || algo.align_end(x, y, addr, blocksize, sender));
+                let done_cp = done.clone();
+                let cancel_cp = cancel.clone();
                 std::thread::spawn(move || {
-                    algo.align_front(x_cp, y_cp, addr, blocksize, sender_cp)
+                    let _done = done;
+                    algo.align_end_cancellable(x, y, addr, blocksize, sender, &cancel)
+                });
+                std::thread::spawn(move || {
+                    let _done = done_cp;
+                    algo.align_front_cancellable(x_cp, y_cp, addr, blocksize, sender_cp, &cancel_cp)
                 });
             }
         }
     }
-    pub fn start_align_with_selection(
+    pub fn start_align_with_selection<M: From<AlignedBlock> + Send + 'static>(
         &self,
         files: [FileContent; 2],
         selection: [Option<Range<usize>>; 2],
         addr: [usize; 2],
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
     ) {
         let (selected, right, end) = match selection.clone() {
             [None, None] | [Some(_), Some(_)] => {
@@ -145,48 +352,62 @@ impl AlignAlgorithm {
             }
         };
         let algo = *self;
+        let done = DoneGuard::new(&sender);
         std::thread::spawn(move || {
-            algo.align_with_selection(files, (selected, right), end, sender)
+            algo.align_with_selection(files, (selected, right), end, sender, done)
         });
     }
 
-    fn align(&self, x: &[u8], y: &[u8], mode: InternalMode) -> Vec<Op> {
+    /// Aligns with the chosen aligner, or with rust-bio if that one fails, which is then
+    /// reported over the sender
+    fn align<M: From<AlignedBlock>>(
+        &self,
+        x: &[u8],
+        y: &[u8],
+        mode: InternalMode,
+        sender: &Sender<M>,
+    ) -> Vec<Op> {
         if x[..] == y[..] {
             return vec![Op::Match; x.len()];
         }
-        if self.band == Banded::Normal {
-            RustBio.align(self, mode, x, y)
-        } else {
-            align_banded(self, mode, x, y)
+        match self.aligner.0.align(self, mode, x, y) {
+            Ok(ops) => ops,
+            Err(error) => {
+                let aligner = self.aligner.0.name();
+                let _ = sender.send(AlignedBlock::Fallback { aligner, error }.into());
+                RustBio
+                    .align(self, mode, x, y)
+                    .expect("rust-bio always aligns")
+            }
         }
     }
 
     /// Aligns x to y as a whole
-    fn align_whole(
+    fn align_whole<M: From<AlignedBlock> + Send + 'static>(
         &self,
         x: FileContent,
         y: FileContent,
         mode: InternalMode,
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
     ) {
-        let alignment = self.align(&x, &y, mode);
-        let _ = sender.send(AlignedMessage::Append(
-            AlignElement::from_array(&alignment, &x, &y, 0, 0).0,
-        ));
+        let alignment = self.align(&x, &y, mode, &sender);
+        let elements = AlignElement::from_array(&alignment, &x, &y, 0, 0).0;
+        let _ = sender.send(AlignedBlock::Append(elements).into());
     }
 
-    fn align_with_selection(
+    fn align_with_selection<M: From<AlignedBlock> + Send + 'static>(
         &self,
         files: [FileContent; 2],
         selection: (Range<usize>, bool),
         end: bool,
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
+        done: Arc<DoneGuard<M>>,
     ) {
         let (select, right) = selection;
         let full_pattern = &files[right as usize].clone();
         let pattern = &files[right as usize].clone()[select.clone()];
         let text = &files[(!right) as usize].clone()[..];
-        let alignment = self.align(pattern, text, InternalMode::Semiglobal);
+        let alignment = self.align(pattern, text, InternalMode::Semiglobal, &sender);
         let (alignment, textaddr) = ops_pattern_subrange(&alignment);
         let (mut array, pattern_end, text_end) =
             AlignElement::from_array(alignment, full_pattern, text, select.start, textaddr);
@@ -202,10 +423,10 @@ impl AlignAlgorithm {
         } else {
             (Vec::new(), array)
         };
-        if sender.send(AlignedMessage::Append(append)).is_err() {
+        if sender.send(AlignedBlock::Append(append).into()).is_err() {
             return;
         }
-        if sender.send(AlignedMessage::Prepend(prepend)).is_err() {
+        if sender.send(AlignedBlock::Prepend(prepend).into()).is_err() {
             return;
         }
         let blocksize = if let AlignMode::Blockwise(s) = self.mode {
@@ -215,8 +436,10 @@ impl AlignAlgorithm {
         };
         let files2 = files.clone();
         let sender2 = sender.clone();
+        let done2 = done.clone();
         let algo = *self;
         std::thread::spawn(move || {
+            let _done = done2;
             algo.align_end(
                 files2[0].clone(),
                 files2[1].clone(),
@@ -235,23 +458,39 @@ impl AlignAlgorithm {
     }
 
     /// Blockwise alignment in the ascending address direction
-    pub fn align_end(
+    pub fn align_end<M: From<AlignedBlock> + Send + 'static>(
         &self,
         x: FileContent,
         y: FileContent,
         addr: (usize, usize),
         block_size: usize,
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
+    ) {
+        self.align_end_cancellable(x, y, addr, block_size, sender, &AtomicBool::new(false))
+    }
+    /// Same as align_end, but stops without sending anything more once `cancel` is set
+    fn align_end_cancellable<M: From<AlignedBlock> + Send + 'static>(
+        &self,
+        x: FileContent,
+        y: FileContent,
+        addr: (usize, usize),
+        block_size: usize,
+        sender: Sender<M>,
+        cancel: &AtomicBool,
     ) {
         let (mut xaddr, mut yaddr) = addr;
         // we want to have the beginning of our two arrays aligned at the same place
         // since we start from a previous alignment or a cursor
         while xaddr < x.len() && yaddr < y.len() {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
             // align at most block_size bytes from each sequence
             let end_aligned = self.align(
                 &x[xaddr..(xaddr + block_size).min(x.len())],
                 &y[yaddr..(yaddr + block_size).min(y.len())],
                 self.mode.into(),
+                &sender,
             );
             // we only actually append at most half of the block size since we make sure gaps crossing
             // block boundaries are better detected
@@ -261,7 +500,7 @@ impl AlignAlgorithm {
                 break;
             }
             let (end, new_xaddr, new_yaddr) = AlignElement::from_array(ops, &x, &y, xaddr, yaddr);
-            if sender.send(AlignedMessage::Append(end)).is_err() {
+            if sender.send(AlignedBlock::Append(end).into()).is_err() {
                 return;
             }
             xaddr = new_xaddr;
@@ -274,26 +513,45 @@ impl AlignAlgorithm {
         } else {
             return;
         };
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
         let leftover = AlignElement::from_array(&[clip], &x, &y, xaddr, yaddr).0;
-        let _ = sender.send(AlignedMessage::Append(leftover));
+        let _ = sender.send(AlignedBlock::Append(leftover).into());
     }
     /// Same as align_end, but in the other direction
-    pub fn align_front(
+    pub fn align_front<M: From<AlignedBlock> + Send + 'static>(
+        &self,
+        x: FileContent,
+        y: FileContent,
+        addr: (usize, usize),
+        block_size: usize,
+        sender: Sender<M>,
+    ) {
+        self.align_front_cancellable(x, y, addr, block_size, sender, &AtomicBool::new(false))
+    }
+    /// Same as align_front, but stops without sending anything more once `cancel` is set
+    fn align_front_cancellable<M: From<AlignedBlock> + Send + 'static>(
         &self,
         x: FileContent,
         y: FileContent,
         addr: (usize, usize),
         block_size: usize,
-        sender: Sender<AlignedMessage>,
+        sender: Sender<M>,
+        cancel: &AtomicBool,
     ) {
         let (mut xaddr, mut yaddr) = addr;
         while xaddr > 0 && yaddr > 0 {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
             let lower_xaddr = xaddr.saturating_sub(block_size);
             let lower_yaddr = yaddr.saturating_sub(block_size);
             let aligned = self.align(
                 &x[lower_xaddr..xaddr],
                 &y[lower_yaddr..yaddr],
                 self.mode.into(),
+                &sender,
             );
             // unlike in align_end, we create the Alignelement from the whole array and then cut it
             // in half. This is because the addresses returned from from_array are at the end, which
@@ -307,7 +565,7 @@ impl AlignAlgorithm {
             let first = real_end.first().unwrap();
             xaddr = first.xaddr;
             yaddr = first.yaddr;
-            if sender.send(AlignedMessage::Prepend(real_end)).is_err() {
+            if sender.send(AlignedBlock::Prepend(real_end).into()).is_err() {
                 return;
             }
         }
@@ -318,8 +576,11 @@ impl AlignAlgorithm {
         } else {
             return;
         };
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
         let leftover = AlignElement::from_array(&[clip], &x, &y, 0, 0).0;
-        let _ = sender.send(AlignedMessage::Prepend(leftover));
+        let _ = sender.send(AlignedBlock::Prepend(leftover).into());
     }
 }
 
@@ -343,6 +604,14 @@ impl AlignElement {
             ybyte: self.xbyte,
         }
     }
+    /// The addresses of the bytes of both files, or None for a gap, whose address
+    /// is the one of the next byte of its file and would otherwise be looked up twice
+    pub fn byte_addresses(&self) -> [Option<usize>; 2] {
+        [
+            self.xbyte.map(|_| self.xaddr),
+            self.ybyte.map(|_| self.yaddr),
+        ]
+    }
     /// Creates a vector out of `AlignElement`s from the operations outputted by rust-bio.
     /// Also outputs the addresses at the end of the array.
     fn from_array(
@@ -426,6 +695,8 @@ fn ops_pattern_subrange(mut ops: &[Op]) -> (&[Op], usize) {
 pub enum FlatAlignProgressMessage {
     Incomplete(u16),
     Complete(isize),
+    /// the offset found by `detect_offset`, if any windows were found
+    Detected(Option<anchor::DetectedOffset>),
 }
 pub struct FlatAlignmentContext {
     is_running: Arc<AtomicBool>,
@@ -476,7 +747,7 @@ impl FlatAlignmentContext {
                 return;
             }
             // add the vectors together in the frequency domain
-            for (a, b) in sum.iter_mut().zip(vec.into_iter()) {
+            for (a, b) in sum.iter_mut().zip(vec) {
                 *a += b;
             }
             progress += 1;
@@ -564,3 +835,130 @@ fn correlation_thread(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// puts all bytes of the first sequence before the ones of the second one
+    struct Separate;
+
+    impl Aligner for Separate {
+        fn name(&self) -> &'static str {
+            "separate"
+        }
+        fn align(
+            &self,
+            _: &AlignAlgorithm,
+            _: InternalMode,
+            x: &[u8],
+            y: &[u8],
+        ) -> Result<Vec<Op>, AlignError> {
+            Ok(vec![Op::Xclip(x.len()), Op::Yclip(y.len())])
+        }
+    }
+
+    /// runs out of memory on everything
+    struct Failing;
+
+    impl Aligner for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+        fn align(
+            &self,
+            _: &AlignAlgorithm,
+            _: InternalMode,
+            _: &[u8],
+            _: &[u8],
+        ) -> Result<Vec<Op>, AlignError> {
+            Err(AlignError(String::from("out of memory")))
+        }
+    }
+
+    #[test]
+    fn registered_aligner() {
+        assert!(AlignerChoice::by_name("separate").is_none());
+        register_aligner(&Separate);
+        let names: Vec<_> = aligners().iter().map(|aligner| aligner.name()).collect();
+        assert_eq!(names, ["rust-bio", "separate"]);
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            aligner: AlignerChoice::by_name("separate").unwrap(),
+            ..AlignAlgorithm::default()
+        };
+        let alignment = algo.align_bytes(b"abc", b"abd");
+        assert_eq!(alignment.len(), 6);
+        assert!(alignment[..3].iter().all(|e| e.ybyte.is_none()));
+        assert!(alignment[3..].iter().all(|e| e.xbyte.is_none()));
+    }
+
+    #[test]
+    fn failing_aligner_falls_back() {
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            aligner: AlignerChoice(&Failing),
+            ..AlignAlgorithm::default()
+        };
+        let [x, y] =
+            [&b"a bc"[..], &b"abc"[..]].map(|bytes| Arc::new(FileBytes::from(bytes.to_vec())));
+        let (sender, receiver) = channel::<AlignedBlock>();
+        algo.start_align(x, y, (0, 0), sender);
+        let blocks: Vec<_> = receiver.into_iter().collect();
+        assert!(matches!(
+            &blocks[0],
+            AlignedBlock::Fallback { aligner: "failing", error } if error.0 == "out of memory"
+        ));
+        assert!(matches!(&blocks[1], AlignedBlock::Append(elements) if elements.len() == 4));
+        // the thread is done after that
+        assert!(matches!(&blocks[2..], [AlignedBlock::Done]));
+        // the bytes are still aligned, so the result is the same as with rust-bio
+        let fallback = algo.align_bytes(b"a bc", b"abc");
+        let rustbio = AlignAlgorithm::default().align_bytes(b"a bc", b"abc");
+        let addresses = |alignment: Vec<AlignElement>| -> Vec<_> {
+            alignment.iter().map(|e| e.byte_addresses()).collect()
+        };
+        assert_eq!(addresses(fallback), addresses(rustbio));
+    }
+    #[test]
+    fn cancel_blockwise() {
+        // a message type that cancels the alignment as soon as the first block is sent
+        static CANCEL: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+        struct Cancelling(AlignedBlock);
+        impl From<AlignedBlock> for Cancelling {
+            fn from(block: AlignedBlock) -> Self {
+                if let (AlignedBlock::Append(_), Some(cancel)) = (&block, &*CANCEL.lock().unwrap())
+                {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                Cancelling(block)
+            }
+        }
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Blockwise(64),
+            ..AlignAlgorithm::default()
+        };
+        let mut x = vec![0u8; 1 << 16];
+        let y = x.clone();
+        x[0] = 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        *CANCEL.lock().unwrap() = Some(cancel.clone());
+        let (sender, receiver) = channel::<Cancelling>();
+        algo.start_align_cancellable(
+            Arc::new(x.into()),
+            Arc::new(y.into()),
+            (0, 0),
+            sender,
+            cancel,
+        );
+        let blocks: Vec<_> = receiver.into_iter().map(|block| block.0).collect();
+        // no more blocks arrive after the first one with the difference, while the
+        // other thread only sends the empty bytes in front of the start
+        let appended = blocks
+            .iter()
+            .filter(|block| matches!(block, AlignedBlock::Append(_)))
+            .count();
+        assert_eq!(appended, 1);
+        assert!(matches!(blocks.last(), Some(AlignedBlock::Done)));
+    }
+}
@@ -0,0 +1,167 @@
+//! Detecting the offset between the files by looking for windows of the first file
+//! in the second one, which only reads both files once and is much faster
+//! on large files than the cross-correlation of `align_flat`.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{FlatAlignProgressMessage, FlatAlignmentContext};
+
+/// the length of the windows that are looked for
+const WINDOW: usize = 32;
+/// how many windows are taken from the first file
+const ANCHORS: usize = 256;
+/// the base of the polynomial rolling hash of the windows
+const HASH_BASE: u64 = 0x0100_0000_01b3;
+
+/// An offset between the files found by matching windows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectedOffset {
+    /// the relative offset like the one of `align_flat`, which is the address
+    /// in the first file minus the one in the second file
+    pub offset: isize,
+    /// the share of the windows of the first file that are found at the offset
+    pub confidence: f32,
+}
+
+fn window_hash(window: &[u8]) -> u64 {
+    window.iter().fold(0, |hash, &byte| {
+        hash.wrapping_mul(HASH_BASE).wrapping_add(byte as u64)
+    })
+}
+
+/// The addresses of evenly spread windows of the first file by their hash, leaving out
+/// windows of a single repeated byte, which are found nearly everywhere
+fn anchors(first: &[u8]) -> HashMap<u64, Vec<usize>> {
+    let mut anchors = HashMap::<u64, Vec<usize>>::new();
+    let last = match first.len().checked_sub(WINDOW) {
+        Some(last) => last,
+        None => return anchors,
+    };
+    let step = (last / ANCHORS).max(1);
+    for start in (0..=last).step_by(step).take(ANCHORS) {
+        let window = &first[start..start + WINDOW];
+        if window.iter().all(|&byte| byte == window[0]) {
+            continue;
+        }
+        anchors.entry(window_hash(window)).or_default().push(start);
+    }
+    anchors
+}
+
+/// Looks for the windows of the first file in the whole second file and returns the offset
+/// at which most of them are found, or None if none are found or `is_running` is cleared.
+/// `progress` gets how much of the second file was searched, from 0 to 256.
+pub fn find_offset(
+    first: &[u8],
+    second: &[u8],
+    is_running: &AtomicBool,
+    mut progress: impl FnMut(u16),
+) -> Option<DetectedOffset> {
+    let anchors = anchors(first);
+    let anchor_count = anchors.values().map(Vec::len).sum::<usize>();
+    let last = second.len().checked_sub(WINDOW)?;
+    if anchor_count == 0 {
+        return None;
+    }
+    // the factor of the byte that leaves the window
+    let out_factor = (1..WINDOW).fold(1u64, |factor, _| factor.wrapping_mul(HASH_BASE));
+    let chunk = (second.len() / 256).max(1);
+    let mut votes = HashMap::<isize, usize>::new();
+    let mut hash = window_hash(&second[..WINDOW]);
+    for start in 0..=last {
+        if start > 0 {
+            hash = hash
+                .wrapping_sub((second[start - 1] as u64).wrapping_mul(out_factor))
+                .wrapping_mul(HASH_BASE)
+                .wrapping_add(second[start + WINDOW - 1] as u64);
+        }
+        if let Some(positions) = anchors.get(&hash) {
+            let window = &second[start..start + WINDOW];
+            for &pos in positions {
+                if first[pos..pos + WINDOW] == *window {
+                    *votes.entry(pos as isize - start as isize).or_default() += 1;
+                }
+            }
+        }
+        if start % chunk == 0 {
+            if !is_running.load(Ordering::Relaxed) {
+                return None;
+            }
+            progress((start / chunk).min(256) as u16);
+        }
+    }
+    // the smallest offset wins a tie
+    let (offset, count) = votes
+        .into_iter()
+        .max_by_key(|&(offset, count)| (count, Reverse(offset.unsigned_abs())))?;
+    Some(DetectedOffset {
+        offset,
+        confidence: count as f32 / anchor_count as f32,
+    })
+}
+
+impl FlatAlignmentContext {
+    /// Finds the offset at which the most windows of the first file are found in the second
+    /// one, which is quicker than `align_flat`, but needs bytes that are the same in both files
+    pub fn detect_offset(mut self) {
+        let detected = find_offset(&self.vecs[0], &self.vecs[1], &self.is_running, |i| {
+            (self.update_progress)(FlatAlignProgressMessage::Incomplete(i))
+        });
+        if self.is_running.load(Ordering::Relaxed) {
+            (self.update_progress)(FlatAlignProgressMessage::Detected(detected))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// bytes without windows that repeat
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shifted() {
+        let running = AtomicBool::new(true);
+        let first = noise(0x4000, 1);
+        let mut second = noise(0x800, 2);
+        second.extend_from_slice(&first);
+        let detected = find_offset(&first, &second, &running, |_| ()).unwrap();
+        assert_eq!(detected.offset, -0x800);
+        assert_eq!(detected.confidence, 1.0);
+        // some changed bytes only lower the confidence
+        for byte in second.iter_mut().skip(0x1000).step_by(0x100) {
+            *byte ^= 0xff;
+        }
+        let detected = find_offset(&first, &second, &running, |_| ()).unwrap();
+        assert_eq!(detected.offset, -0x800);
+        assert!(detected.confidence < 1.0);
+        assert_eq!(
+            find_offset(&first, &noise(0x4000, 3), &running, |_| ()),
+            None
+        );
+        assert_eq!(
+            find_offset(&[0; 0x100], &[0; 0x100], &running, |_| ()),
+            None
+        );
+    }
+
+    #[test]
+    fn cancelled() {
+        let running = AtomicBool::new(false);
+        let first = noise(0x1000, 1);
+        assert_eq!(find_offset(&first, &first, &running, |_| ()), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! A compact form of a finished alignment, which only keeps how long the runs of bytes of
+//! both files and of gaps are, for storing the alignment without its bytes.
+
+use serde::{Deserialize, Serialize};
+
+use super::AlignElement;
+
+/// Consecutive elements of an alignment that have bytes of the same files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Run {
+    /// bytes of both files next to each other
+    Both(usize),
+    /// bytes of the first file with a gap in the second one
+    First(usize),
+    /// bytes of the second file with a gap in the first one
+    Second(usize),
+}
+
+impl Run {
+    fn new(bytes: [bool; 2], len: usize) -> Option<Self> {
+        match bytes {
+            [true, true] => Some(Run::Both(len)),
+            [true, false] => Some(Run::First(len)),
+            [false, true] => Some(Run::Second(len)),
+            [false, false] => None,
+        }
+    }
+    /// Which of the files have bytes in the run, and how many elements it has
+    fn parts(self) -> ([bool; 2], usize) {
+        match self {
+            Run::Both(len) => ([true, true], len),
+            Run::First(len) => ([true, false], len),
+            Run::Second(len) => ([false, true], len),
+        }
+    }
+}
+
+/// An alignment without its bytes, which are taken from the files again when it is turned
+/// back into elements. Every element follows the one before it, so only the addresses of
+/// the first one are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlignRuns {
+    /// the addresses of the first element
+    pub start: [usize; 2],
+    pub runs: Vec<Run>,
+}
+
+impl AlignRuns {
+    /// The runs of the elements, or None if an element does not follow the one before it
+    pub fn from_elements(elements: &[AlignElement]) -> Option<Self> {
+        let start = elements
+            .first()
+            .map_or([0, 0], |first| [first.xaddr, first.yaddr]);
+        let mut runs = AlignRuns {
+            start,
+            runs: Vec::new(),
+        };
+        let mut next = start;
+        for element in elements {
+            if [element.xaddr, element.yaddr] != next {
+                return None;
+            }
+            let bytes = [element.xbyte.is_some(), element.ybyte.is_some()];
+            runs.push(Run::new(bytes, 1)?);
+            next = [next[0] + bytes[0] as usize, next[1] + bytes[1] as usize];
+        }
+        Some(runs)
+    }
+    /// Adds a run after the last one, which is extended if it has bytes of the same files
+    fn push(&mut self, run: Run) {
+        let (bytes, len) = run.parts();
+        match self.runs.last_mut() {
+            Some(last) if last.parts().0 == bytes => {
+                *last = Run::new(bytes, last.parts().1 + len).expect("run has bytes");
+            }
+            _ => self.runs.push(run),
+        }
+    }
+    /// The addresses after the last element
+    pub fn end(&self) -> [usize; 2] {
+        self.runs.iter().fold(self.start, |[x, y], run| {
+            let (bytes, len) = run.parts();
+            [x + bytes[0] as usize * len, y + bytes[1] as usize * len]
+        })
+    }
+    /// The number of elements
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|run| run.parts().1).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+    /// Appends the runs of `other` if it starts where these end, and returns whether it did
+    pub fn append(&mut self, other: AlignRuns) -> bool {
+        if other.is_empty() {
+            return true;
+        }
+        if self.is_empty() {
+            *self = other;
+            return true;
+        }
+        if other.start != self.end() {
+            return false;
+        }
+        for run in other.runs {
+            self.push(run);
+        }
+        true
+    }
+    /// The elements of the alignment with the bytes of the files, or None if they end
+    /// after the end of one of the files
+    pub fn elements<'a>(
+        &'a self,
+        x: &'a [u8],
+        y: &'a [u8],
+    ) -> Option<impl Iterator<Item = AlignElement> + 'a> {
+        let end = self.end();
+        if end[0] > x.len() || end[1] > y.len() {
+            return None;
+        }
+        let mut next = self.start;
+        Some(self.runs.iter().flat_map(move |run| {
+            let (bytes, len) = run.parts();
+            let start = next;
+            next = [
+                start[0] + bytes[0] as usize * len,
+                start[1] + bytes[1] as usize * len,
+            ];
+            (0..len).map(move |i| {
+                let [xaddr, yaddr] = [
+                    start[0] + bytes[0] as usize * i,
+                    start[1] + bytes[1] as usize * i,
+                ];
+                AlignElement {
+                    xaddr,
+                    xbyte: bytes[0].then(|| x[xaddr]),
+                    yaddr,
+                    ybyte: bytes[1].then(|| y[yaddr]),
+                }
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::align::AlignAlgorithm;
+
+    #[test]
+    fn runs() {
+        let (x, y) = (&b"a bcdef"[..], &b"abcxdef"[..]);
+        let elements = AlignAlgorithm::default().align_bytes(x, y);
+        let runs = AlignRuns::from_elements(&elements).unwrap();
+        assert_eq!(runs.start, [0, 0]);
+        assert_eq!(runs.end(), [x.len(), y.len()]);
+        assert_eq!(runs.len(), elements.len());
+        let restored = runs.elements(x, y).unwrap().collect::<Vec<_>>();
+        let addresses = |elements: &[AlignElement]| {
+            elements
+                .iter()
+                .map(|e| (e.byte_addresses(), e.xbyte, e.ybyte))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(addresses(&restored), addresses(&elements));
+        // the alignment can be put together from its blocks
+        let (front, back) = elements.split_at(3);
+        let mut joined = AlignRuns::from_elements(front).unwrap();
+        assert!(!AlignRuns::from_elements(back)
+            .unwrap()
+            .append(AlignRuns::from_elements(front).unwrap()));
+        assert!(joined.append(AlignRuns::from_elements(back).unwrap()));
+        assert_eq!(joined, runs);
+        // elements that skip bytes are not runs, and runs do not go past the files
+        assert!(AlignRuns::from_elements(&[elements[0], elements[2]]).is_none());
+        assert!(runs.elements(&x[1..], y).is_none());
+    }
+}
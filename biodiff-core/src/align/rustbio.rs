@@ -3,7 +3,7 @@ use bio::alignment::{
     AlignmentOperation,
 };
 
-use super::{Align, AlignAlgorithm, InternalMode};
+use super::{AlignAlgorithm, AlignError, Aligner, Banded, InternalMode};
 
 fn scorer(
     algo: &AlignAlgorithm,
@@ -26,35 +26,32 @@ fn scorer(
     }
 }
 
+/// The pairwise aligners of rust-bio, which use the banded one if it is enabled
 pub struct RustBio;
 
-impl Align for RustBio {
+impl Aligner for RustBio {
+    fn name(&self) -> &'static str {
+        "rust-bio"
+    }
     fn align(
         &self,
         algo: &AlignAlgorithm,
         mode: InternalMode,
         x: &[u8],
         y: &[u8],
-    ) -> Vec<AlignmentOperation> {
+    ) -> Result<Vec<AlignmentOperation>, AlignError> {
         let scoring = scorer(algo, mode);
-        pairwise::Aligner::with_scoring(scoring)
-            .custom(x, y)
-            .operations
+        Ok(match algo.band {
+            Banded::Normal => {
+                pairwise::Aligner::with_scoring(scoring)
+                    .custom(x, y)
+                    .operations
+            }
+            Banded::Banded { kmer, window } => {
+                pairwise::banded::Aligner::with_scoring(scoring, kmer, window)
+                    .custom(x, y)
+                    .operations
+            }
+        })
     }
 }
-
-pub fn align_banded(
-    algo: &AlignAlgorithm,
-    mode: InternalMode,
-    x: &[u8],
-    y: &[u8],
-) -> Vec<AlignmentOperation> {
-    let (kmer, window) = match algo.band {
-        super::Banded::Normal => return RustBio.align(algo, mode, x, y),
-        super::Banded::Banded { kmer, window } => (kmer, window),
-    };
-    let scoring = scorer(algo, mode);
-    pairwise::banded::Aligner::with_scoring(scoring, kmer, window)
-        .custom(x, y)
-        .operations
-}
@@ -0,0 +1,534 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    ops::{Deref, Range},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::{search::SearchResults, util::ilog2};
+
+mod decompress;
+#[cfg(unix)]
+mod mmap;
+
+pub type FileContent = Arc<FileBytes>;
+
+/// Files at least this big are mapped into memory instead of being read,
+/// so that huge files can be compared of which only small parts are looked at
+#[cfg(unix)]
+const MMAP_THRESHOLD: u64 = 64 << 20;
+
+/// The file argument for reading stdin
+pub const STDIN: &str = "-";
+
+/// The most bytes of a file that are split into bits or nibbles,
+/// since the split bytes take up to eight times as much memory and alignment time
+pub const MAX_SPLIT_LEN: usize = 16 << 20;
+
+/// What the compared units are, which are bytes unless the bytes are split into smaller units
+/// so that a single inserted bit does not shift all following bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unit {
+    #[default]
+    Byte,
+    Nibble,
+    Bit,
+}
+
+impl Unit {
+    /// The number of units in a byte
+    pub fn per_byte(self) -> usize {
+        match self {
+            Unit::Byte => 1,
+            Unit::Nibble => 2,
+            Unit::Bit => 8,
+        }
+    }
+    /// The byte of an address in units and the position of the unit in the byte
+    pub fn split_addr(self, addr: usize) -> (usize, usize) {
+        (addr / self.per_byte(), addr % self.per_byte())
+    }
+    /// Splits each byte into its units, starting with the most significant one,
+    /// so that the units of a byte are in the order of its binary digits
+    pub fn split(self, bytes: &[u8]) -> Vec<u8> {
+        let per_byte = self.per_byte();
+        let bits = 8 / per_byte;
+        let mask = ((1u16 << bits) - 1) as u8;
+        bytes
+            .iter()
+            .flat_map(|&byte| {
+                (0..per_byte)
+                    .rev()
+                    .map(move |i| (byte >> (i * bits)) & mask)
+            })
+            .collect()
+    }
+}
+
+/// The compared bytes of a file, which are the whole file unless a window of it was chosen
+#[derive(Debug)]
+pub struct FileBytes {
+    storage: Storage,
+    /// the address of the first byte in the file
+    base: usize,
+    /// the state of the file on disk when it was read, if it is a regular file
+    disk: Option<DiskState>,
+    /// what each of the bytes stands for, which are the bits or nibbles of the file
+    /// if its bytes were split
+    unit: Unit,
+}
+
+/// The size and modification time of a regular file, which tell whether it changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl DiskState {
+    fn new(metadata: &Metadata) -> Option<Self> {
+        metadata.is_file().then(|| DiskState {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+    /// The current state of the file at the path
+    fn of(path: &OsStr) -> Option<Self> {
+        std::fs::metadata(path).ok().as_ref().and_then(Self::new)
+    }
+}
+
+/// The bytes, either read into memory or mapped from the file
+#[derive(Debug)]
+enum Storage {
+    Read(Vec<u8>),
+    #[cfg(unix)]
+    Mapped(mmap::Mapping),
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.storage {
+            Storage::Read(vec) => vec,
+            #[cfg(unix)]
+            Storage::Mapped(mapping) => mapping,
+        }
+    }
+}
+
+impl From<Vec<u8>> for FileBytes {
+    fn from(vec: Vec<u8>) -> Self {
+        FileBytes {
+            storage: Storage::Read(vec),
+            base: 0,
+            disk: None,
+            unit: Unit::Byte,
+        }
+    }
+}
+
+impl FileBytes {
+    /// Maps big regular files into memory and reads all others
+    fn from_file(mut file: File) -> Result<Self, std::io::Error> {
+        let metadata = file.metadata()?;
+        #[cfg(unix)]
+        if metadata.is_file() && metadata.len() >= MMAP_THRESHOLD {
+            return mmap::Mapping::new(&file).map(|mapping| FileBytes {
+                storage: Storage::Mapped(mapping),
+                base: 0,
+                disk: DiskState::new(&metadata),
+                unit: Unit::Byte,
+            });
+        }
+        // while the filesize might change between the metadata call and the read_to_end call,
+        // in most cases it will make sure that the vec does not have too much capacity.
+        // pipes (like from process substitution) have no meaningful size, so they are
+        // read without preallocating
+        let capacity = if metadata.is_file() {
+            metadata.len() as usize
+        } else {
+            0
+        };
+        let mut vec = Vec::with_capacity(capacity);
+        file.read_to_end(&mut vec)?;
+        Ok(FileBytes {
+            storage: Storage::Read(vec),
+            base: 0,
+            disk: DiskState::new(&metadata),
+            unit: Unit::Byte,
+        })
+    }
+    /// The address of the first byte in the file, which is added to the shown addresses
+    pub fn base(&self) -> usize {
+        self.base
+    }
+    /// What the bytes stand for, see `Unit`
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+    /// A copy of the bytes in memory with some of them replaced, like the ones
+    /// overwritten in edit mode
+    pub fn patched(&self, patches: impl IntoIterator<Item = (usize, u8)>) -> Self {
+        let mut vec = self.to_vec();
+        for (index, byte) in patches {
+            vec[index] = byte;
+        }
+        FileBytes {
+            storage: Storage::Read(vec),
+            base: self.base,
+            disk: self.disk,
+            unit: self.unit,
+        }
+    }
+    /// Restricts the bytes to the window, without copying them
+    fn into_window(self, window: Window) -> Result<Self, std::io::Error> {
+        let range = window.range(self.len())?;
+        let storage = match self.storage {
+            Storage::Read(mut vec) => {
+                vec.truncate(range.end);
+                vec.drain(..range.start);
+                Storage::Read(vec)
+            }
+            #[cfg(unix)]
+            Storage::Mapped(mapping) => Storage::Mapped(mapping.narrow(range.clone())),
+        };
+        Ok(FileBytes {
+            storage,
+            base: self.base + range.start,
+            disk: self.disk,
+            unit: self.unit,
+        })
+    }
+    /// Splits the bytes into smaller units, which fails if there are more than `MAX_SPLIT_LEN`.
+    /// The addresses then count the units.
+    fn into_units(self, unit: Unit) -> Result<Self, std::io::Error> {
+        if unit == Unit::Byte {
+            return Ok(self);
+        }
+        if self.len() > MAX_SPLIT_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{:#x} bytes are too many to split, at most {MAX_SPLIT_LEN:#x} can be \
+                     (--skip and --length choose a part)",
+                    self.len()
+                ),
+            ));
+        }
+        Ok(FileBytes {
+            storage: Storage::Read(unit.split(&self)),
+            base: self.base * unit.per_byte(),
+            disk: self.disk,
+            unit,
+        })
+    }
+}
+
+/// The part of a file that is compared, given by `--skip` and `--length`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Window {
+    /// the number of bytes at the start that are left out
+    pub skip: usize,
+    /// the maximal number of bytes after them, or the rest of the file if not given
+    pub length: Option<usize>,
+}
+
+impl Window {
+    /// The range of the window in bytes of the given length, which fails if it
+    /// starts outside of them. The length is cut off at the end.
+    fn range(self, len: usize) -> Result<Range<usize>, std::io::Error> {
+        // like for the other addresses, only an empty file can be shown at its end
+        if self.skip >= len && self.skip > 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "skipping {:#x} bytes leaves nothing of the {len:#x} bytes",
+                    self.skip
+                ),
+            ));
+        }
+        let rest = len - self.skip;
+        Ok(self.skip..self.skip + self.length.map_or(rest, |length| length.min(rest)))
+    }
+}
+
+/// How a file given on the command line is read, so that it can be read again
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub path: OsString,
+    /// shown instead of the path
+    pub label: Option<String>,
+    /// whether compressed files are decompressed
+    pub decompress: bool,
+    /// the part of the (decompressed) file that is compared
+    pub window: Window,
+    /// the units that the bytes of the window are split into
+    pub unit: Unit,
+}
+
+impl Source {
+    /// Reads the file, returning it along with a warning if it could not be decompressed
+    pub fn read(&self) -> Result<(FileState, Option<String>), std::io::Error> {
+        let mut file = FileState::from_file(&self.path)?;
+        if let Some(label) = &self.label {
+            file.name = label.clone();
+        }
+        let warning = match self.decompress.then(|| file.decompress()) {
+            Some(Err(e)) => Some(format!(
+                "Could not decompress {}, comparing the raw bytes: {e}",
+                file.name
+            )),
+            _ => None,
+        };
+        if self.window != Window::default() || self.unit != Unit::Byte {
+            // the content was just created, so it is only copied in the unlikely case that
+            // it is shared
+            let content = Arc::try_unwrap(file.content)
+                .unwrap_or_else(|content| FileBytes::from(content.to_vec()));
+            file.content = Arc::new(content.into_window(self.window)?.into_units(self.unit)?);
+        }
+        Ok((file, warning))
+    }
+    /// Whether reading the file again gives the current bytes, which is not the case for stdin
+    pub fn can_reread(&self) -> bool {
+        self.path != STDIN
+    }
+    /// Whether the file on disk changed since the content was read from it.
+    /// Files that are not regular files (like stdin or pipes) never change.
+    pub fn modified(&self, content: &FileBytes) -> bool {
+        self.can_reread() && DiskState::of(&self.path) != content.disk
+    }
+    /// Reads the bytes appended to the file since it had the old content, returning
+    /// the grown content or `None` if it did not grow.
+    /// Decompressed and split files are not followed, as they would have to be decompressed
+    /// or split again. Bytes after the end of the window are not read.
+    pub fn read_grown(&self, old: &FileContent) -> Result<Option<FileContent>, std::io::Error> {
+        if !self.can_reread() || self.decompress || self.unit != Unit::Byte {
+            return Ok(None);
+        }
+        let mut file = File::open(&self.path)?;
+        let metadata = file.metadata()?;
+        let old_end = (old.base() + old.len()) as u64;
+        let window_end = self
+            .window
+            .length
+            .map(|length| (self.window.skip + length) as u64);
+        let end = window_end.map_or(metadata.len(), |end| end.min(metadata.len()));
+        if !metadata.is_file() || end <= old_end {
+            return Ok(None);
+        }
+        // big files are mapped again, which does not read anything
+        #[cfg(unix)]
+        if metadata.len() >= MMAP_THRESHOLD {
+            let content = FileBytes::from_file(file)?.into_window(self.window)?;
+            return Ok(Some(Arc::new(content)));
+        }
+        let mut vec = Vec::with_capacity((end - old.base() as u64) as usize);
+        vec.extend_from_slice(old);
+        file.seek(SeekFrom::Start(old_end))?;
+        file.take(end - old_end).read_to_end(&mut vec)?;
+        let grown = vec.len() > old.len();
+        Ok(grown.then(|| {
+            Arc::new(FileBytes {
+                storage: Storage::Read(vec),
+                base: old.base(),
+                disk: DiskState::new(&metadata),
+                unit: Unit::Byte,
+            })
+        }))
+    }
+}
+
+/// The bytes of a file along with its filename and an index pointing at a byte of the file
+#[derive(Debug)]
+pub struct FileState {
+    pub name: String,
+    pub content: FileContent,
+    pub index: usize,
+    pub search: Vec<SearchResults>,
+}
+
+impl FileState {
+    /// Reads a PointedFile from a path, with index 0.
+    /// The path `-` reads stdin to its end.
+    pub fn from_file(name: &OsStr) -> Result<Self, std::io::Error> {
+        if name == STDIN {
+            let mut vec = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut vec)?;
+            return Ok(FileState::from_bytes("<stdin>", vec));
+        }
+        // git passes this for added or deleted files, which does not exist on windows
+        if name == "/dev/null" {
+            return Ok(FileState::from_bytes(&name.to_string_lossy(), Vec::new()));
+        }
+        let content = Arc::new(FileBytes::from_file(File::open(name)?)?);
+        Ok(FileState {
+            name: name.to_string_lossy().to_string(),
+            content,
+            index: 0,
+            search: Vec::new(),
+        })
+    }
+    /// Bytes in memory that are not from a file on disk, with index 0
+    pub fn from_bytes(name: &str, bytes: Vec<u8>) -> Self {
+        FileState {
+            name: name.to_string(),
+            content: Arc::new(bytes.into()),
+            index: 0,
+            search: Vec::new(),
+        }
+    }
+    /// The same bytes at the same index, without the search results.
+    /// This is the hidden second file when only one file is viewed.
+    pub fn shared(&self) -> Self {
+        FileState {
+            name: self.name.clone(),
+            content: self.content.clone(),
+            index: self.index,
+            search: Vec::new(),
+        }
+    }
+    /// Replaces gzip, zstd or xz compressed content by the decompressed bytes and
+    /// notes the format and decompressed size after the name.
+    /// If decompressing fails, the raw bytes are kept and the error is returned.
+    pub fn decompress(&mut self) -> Result<(), String> {
+        let compression = match decompress::Compression::detect(&self.content) {
+            Some(compression) => compression,
+            None => return Ok(()),
+        };
+        let bytes = compression.decompress(&self.content)?;
+        self.name = format!(
+            "{} ({}, {} bytes)",
+            self.name,
+            compression.indicator(),
+            bytes.len()
+        );
+        // the decompressed bytes change when the compressed file changes
+        self.content = Arc::new(FileBytes {
+            storage: Storage::Read(bytes),
+            base: 0,
+            disk: self.content.disk,
+            unit: self.content.unit,
+        });
+        Ok(())
+    }
+    /// gets the number of digits used to represent the file addresses
+    /// (rounded up to be in pairs
+    pub fn address_digits(&self) -> u8 {
+        (self.short_address_digits() + 1) / 2 * 2
+    }
+    /// gets the number of hex digits of the last address of the file,
+    /// which is the address of the byte if the bytes are split into units
+    pub fn short_address_digits(&self) -> u8 {
+        if self.content.is_empty() {
+            return 1;
+        }
+        let last = (self.content.base() + self.content.len() - 1) / self.content.unit.per_byte();
+        ilog2(last.max(1)) / 4 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path in the temporary directory for the test with the name,
+    /// which is unique to the process running the tests
+    pub(super) fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("biodiff-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn read_grown() {
+        let path = temp_path("grown");
+        std::fs::write(&path, b"first").unwrap();
+        let source = Source {
+            path: path.clone().into_os_string(),
+            label: None,
+            decompress: false,
+            window: Window::default(),
+            unit: Unit::Byte,
+        };
+        let (file, _) = source.read().unwrap();
+        assert!(source.read_grown(&file.content).unwrap().is_none());
+        assert!(!source.modified(&file.content));
+        std::fs::write(&path, b"first, second").unwrap();
+        let grown = source.read_grown(&file.content).unwrap().unwrap();
+        // the grown content is the one on disk now
+        let modified = [&file.content, &grown].map(|content| source.modified(content));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(modified, [true, false]);
+        assert_eq!(&grown[..], b"first, second");
+    }
+
+    #[test]
+    fn windows() {
+        let path = temp_path("window");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let read = |skip, length| {
+            Source {
+                path: path.clone().into_os_string(),
+                label: None,
+                decompress: false,
+                window: Window { skip, length },
+                unit: Unit::Byte,
+            }
+            .read()
+            .map(|(file, _)| file)
+        };
+        let file = read(2, Some(3)).unwrap();
+        assert_eq!(&file.content[..], b"234");
+        assert_eq!(file.content.base(), 2);
+        // the length is cut off at the end, but skipping has to leave some bytes
+        assert_eq!(&read(8, Some(10)).unwrap().content[..], b"89");
+        let error = read(10, None).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    #[test]
+    fn units() {
+        assert_eq!(Unit::Bit.split(&[0xa5]), [1, 0, 1, 0, 0, 1, 0, 1]);
+        assert_eq!(Unit::Nibble.split(&[0xa5, 0x0f]), [0xa, 0x5, 0x0, 0xf]);
+        assert_eq!(Unit::Byte.split(&[0xa5]), [0xa5]);
+        assert_eq!(Unit::Bit.split_addr(0x95), (0x12, 5));
+        let path = temp_path("units");
+        std::fs::write(&path, b"\x00\x01\xf0").unwrap();
+        let (file, _) = Source {
+            path: path.clone().into_os_string(),
+            label: None,
+            decompress: false,
+            window: Window {
+                skip: 1,
+                length: None,
+            },
+            unit: Unit::Nibble,
+        }
+        .read()
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // the addresses count the nibbles, but the shown digits are those of the bytes
+        assert_eq!(&file.content[..], [0x0, 0x1, 0xf, 0x0]);
+        assert_eq!(file.content.base(), 2);
+        assert_eq!(file.content.unit(), Unit::Nibble);
+        assert_eq!(file.short_address_digits(), 1);
+        let too_big = FileBytes::from(vec![0; MAX_SPLIT_LEN + 1]).into_units(Unit::Bit);
+        assert_eq!(
+            too_big.unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+    #[test]
+    fn patched() {
+        let content: FileContent = Arc::new(FileBytes {
+            base: 2,
+            ..b"abc".to_vec().into()
+        });
+        let patched = content.patched([(1, b'x'), (2, b'y')]);
+        assert_eq!((&patched[..], patched.base()), (&b"axy"[..], 2));
+        assert_eq!(&content[..], b"abc");
+    }
+}
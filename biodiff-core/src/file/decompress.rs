@@ -0,0 +1,110 @@
+use flate2::read::MultiGzDecoder;
+use std::io::Read;
+use xz2::read::XzDecoder;
+
+/// The compression formats that are recognized by their magic bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    const MAGIC: &'static [(&'static [u8], Compression)] = &[
+        (&[0x1f, 0x8b], Compression::Gzip),
+        (&[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+        (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Compression::Xz),
+    ];
+    /// Determines the format from the start of the bytes
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        Self::MAGIC
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|(_, compression)| *compression)
+    }
+    /// Shown after the file name
+    pub fn indicator(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gunzipped",
+            Compression::Zstd => "unzstd'd",
+            Compression::Xz => "unxz'd",
+        }
+    }
+    /// The name of the format in error messages
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        }
+    }
+    /// Decompresses all of the concatenated streams in the bytes,
+    /// which fails for truncated or corrupt data
+    pub fn decompress(self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let mut output = Vec::new();
+        let read = match self {
+            Compression::Gzip => MultiGzDecoder::new(bytes).read_to_end(&mut output),
+            Compression::Zstd => zstd::stream::read::Decoder::new(bytes)
+                .and_then(|mut decoder| decoder.read_to_end(&mut output)),
+            Compression::Xz => XzDecoder::new_multi_decoder(bytes).read_to_end(&mut output),
+        };
+        match read {
+            Ok(_) => Ok(output),
+            Err(e) => Err(format!(
+                "{} data could not be decompressed: {e}",
+                self.name()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn magic_bytes() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08\0rest"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::detect(b"(\xb5/\xfd"), Some(Compression::Zstd));
+        assert_eq!(Compression::detect(b"\xfd7zXZ\0\0"), Some(Compression::Xz));
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn corrupt_data_fails() {
+        // a gzip header with nothing after it
+        assert!(Compression::Gzip
+            .decompress(b"\x1f\x8b\x08\0\0\0\0\0")
+            .is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = (0..100000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&bytes).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::stream::encode_all(&bytes[..], 0).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(&bytes).unwrap();
+        let xz = xz.finish().unwrap();
+        for compressed in [gzip, zstd, xz] {
+            let compression = Compression::detect(&compressed).unwrap();
+            assert_eq!(compression.decompress(&compressed).unwrap(), bytes);
+            // concatenated streams are decompressed one after another
+            let twice = [&compressed[..], &compressed[..]].concat();
+            assert_eq!(
+                compression.decompress(&twice).unwrap(),
+                [&bytes[..], &bytes[..]].concat()
+            );
+            let truncated = &compressed[..compressed.len() / 2];
+            assert!(compression.decompress(truncated).is_err());
+        }
+    }
+}
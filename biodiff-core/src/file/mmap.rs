@@ -0,0 +1,64 @@
+use std::{fs::File, io, ops::Range};
+
+use memmap2::Mmap;
+
+/// A read-only mapping of a whole file into memory.
+///
+/// If the file is truncated while it is mapped, reading the bytes past its new end
+/// raises SIGBUS on unix, which can not be turned into a panic, so programs that
+/// have to restore the terminal should handle that signal themselves.
+#[derive(Debug)]
+pub struct Mapping {
+    map: Mmap,
+    /// the part of the mapping that is visible through `Deref`
+    window: Range<usize>,
+}
+
+impl Mapping {
+    /// Maps the file, which has to be non-empty
+    pub fn new(file: &File) -> io::Result<Self> {
+        // SAFETY: the mapping is only read from, and other processes changing the file
+        // only change the bytes we see, except for truncating it, which is documented above
+        let map = unsafe { Mmap::map(file)? };
+        let window = 0..map.len();
+        Ok(Mapping { map, window })
+    }
+    /// Restricts the visible bytes to the range of the currently visible ones
+    pub fn narrow(mut self, range: Range<usize>) -> Self {
+        assert!(
+            range.end <= self.window.len(),
+            "narrowed mapping out of bounds"
+        );
+        let start = self.window.start;
+        self.window = start + range.start..start + range.end;
+        self
+    }
+}
+
+impl std::ops::Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map[self.window.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+
+    #[test]
+    fn mapped_bytes() {
+        let path = temp_path("mmap");
+        let bytes: Vec<u8> = (0..10000u32).map(|i| (i * 7) as u8).collect();
+        std::fs::write(&path, &bytes).unwrap();
+        let mapping = Mapping::new(&File::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        // the mapping stays valid after the file is unlinked
+        let mapping = mapping.unwrap();
+        assert_eq!(&mapping[..], &bytes[..]);
+        let narrowed = mapping.narrow(100..5000).narrow(10..20);
+        assert_eq!(&narrowed[..], &bytes[110..120]);
+    }
+}
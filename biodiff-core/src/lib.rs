@@ -0,0 +1,35 @@
+//! The alignment of binary files behind biodiff, along with reading the files and searching
+//! them, without the terminal interface.
+//!
+//! An [`align::AlignAlgorithm`] holds the parameters of the alignment. It either aligns two byte
+//! slices and waits for the result with [`align::AlignAlgorithm::align_bytes`], or starts threads
+//! that send the alignment in blocks over a channel while it is running with
+//! [`align::AlignAlgorithm::start_align`]:
+//!
+//! ```
+//! use std::sync::{mpsc::channel, Arc};
+//!
+//! use biodiff_core::align::{AlignAlgorithm, AlignMode, AlignedBlock};
+//! use biodiff_core::file::FileBytes;
+//!
+//! let algo = AlignAlgorithm {
+//!     mode: AlignMode::Global,
+//!     ..AlignAlgorithm::default()
+//! };
+//! let x = Arc::new(FileBytes::from(b"some bytes".to_vec()));
+//! let y = Arc::new(FileBytes::from(b"some other bytes".to_vec()));
+//! let (sender, receiver) = channel::<AlignedBlock>();
+//! algo.start_align(x, y, (0, 0), sender);
+//! let mut inserted = 0;
+//! // the channel closes when the alignment is done
+//! for block in receiver {
+//!     if let AlignedBlock::Append(elements) | AlignedBlock::Prepend(elements) = block {
+//!         inserted += elements.iter().filter(|e| e.xbyte.is_none()).count();
+//!     }
+//! }
+//! assert_eq!(inserted, "other ".len());
+//! ```
+pub mod align;
+pub mod file;
+pub mod search;
+pub mod util;
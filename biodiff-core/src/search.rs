@@ -0,0 +1,1285 @@
+use std::io::Write;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{collections::BTreeMap, sync::Arc};
+
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::file::{FileBytes, FileContent};
+use crate::util::Finalable;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The four query types, which are all compiled to a regex, but with
+/// different options
+pub enum QueryType {
+    /// plain unescaped text
+    Text,
+    /// a normal regex
+    Regex,
+    /// a regex using hex characters
+    Hexagex,
+    /// hex bytes with `?` wildcards for single nibbles
+    HexPattern,
+}
+
+/// The compiled form of a query
+#[derive(Clone, Debug)]
+enum Matcher {
+    /// a regex with the maximum length of its matches, if that is bounded
+    Regex(Arc<Regex>, Option<usize>),
+    Approximate(Arc<ApproximateMatcher>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Query {
+    text: String,
+    query_type: QueryType,
+    mismatches: usize,
+    matcher: Matcher,
+}
+
+impl PartialEq for Query {
+    fn eq(&self, other: &Self) -> bool {
+        // we do not compare the compiled regex, since it is already uniquely determined
+        // by text, query_type and mismatches
+        self.text == other.text
+            && self.query_type == other.query_type
+            && self.mismatches == other.mismatches
+    }
+}
+
+impl Eq for Query {}
+
+impl Query {
+    /// Compiles a query, which matches with at most `mismatches` substituted bytes.
+    /// Approximate matching is only supported for text and hex queries.
+    pub fn new(
+        query_type: QueryType,
+        text: &str,
+        mismatches: usize,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let matcher = if mismatches > 0 {
+            let pattern = match query_type {
+                QueryType::Text => text.as_bytes().to_vec(),
+                QueryType::Hexagex => parse_hex_bytes(text)?,
+                QueryType::HexPattern => {
+                    let pattern = parse_hex_pattern(text)?;
+                    let matcher = ApproximateMatcher::with_wildcards(&pattern, mismatches)?;
+                    return Ok(Query {
+                        text: text.to_owned(),
+                        query_type,
+                        mismatches,
+                        matcher: Matcher::Approximate(Arc::new(matcher)),
+                    });
+                }
+                QueryType::Regex => {
+                    return Err(
+                        "Approximate search is only supported for text and hex queries".into(),
+                    )
+                }
+            };
+            Matcher::Approximate(Arc::new(ApproximateMatcher::new(&pattern, mismatches)?))
+        } else {
+            let regex = match query_type {
+                // unicode is disabled because it is likely that one wants to search for non-unicode
+                // in a hex viewer
+                QueryType::Text => RegexBuilder::new(&regex::escape(text))
+                    .multi_line(true)
+                    .unicode(true)
+                    .build()?,
+                QueryType::Regex => RegexBuilder::new(text)
+                    .multi_line(true)
+                    .unicode(false)
+                    .build()?,
+                QueryType::Hexagex => hexagex::hexagex(text)?,
+                QueryType::HexPattern => hex_pattern_regex(&parse_hex_pattern(text)?)?,
+            };
+            let max_len = match query_type {
+                QueryType::Text => Some(text.len()),
+                _ => max_match_len(&regex),
+            };
+            Matcher::Regex(Arc::new(regex), max_len)
+        };
+        Ok(Query {
+            text: text.to_owned(),
+            query_type,
+            mismatches,
+            matcher,
+        })
+    }
+    pub fn query_type(&self) -> QueryType {
+        self.query_type.clone()
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+    /// Iterates over the matches of the query in `data`
+    #[cfg(test)]
+    fn find_iter<'a>(&'a self, data: &'a [u8]) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        Box::new(self.search_iter(data).filter_map(|message| match message {
+            SearchMessage::Match(m) => Some(m),
+            _ => None,
+        }))
+    }
+    /// Iterates over the matches of the query in `data`, interspersed with
+    /// progress messages after every chunk without matches.
+    /// Regexes are searched in chunks overlapping by their maximum match length,
+    /// so that matches starting in one chunk are found completely; regexes with
+    /// unbounded matches search the rest of the file and keep the match found
+    /// there until the chunk containing it is reached.
+    fn search_iter<'a>(&'a self, data: &'a [u8]) -> Box<dyn Iterator<Item = SearchMessage> + 'a> {
+        match &self.matcher {
+            Matcher::Regex(regex, max_len) => {
+                let mut pos = 0;
+                // the next match of an unbounded regex behind the current chunk,
+                // Some(None) if there are no matches left
+                let mut next_match: Option<Option<Range<usize>>> = None;
+                Box::new(std::iter::from_fn(move || {
+                    if pos >= data.len() {
+                        return None;
+                    }
+                    let end = (pos + PROGRESS_STEP).min(data.len());
+                    let found = match max_len {
+                        Some(max_len) => {
+                            // one more byte, so that assertions at the end of a match see what follows
+                            let window = (end + max_len + 1).min(data.len());
+                            regex.find_at(&data[..window], pos).map(|m| m.range())
+                        }
+                        None => next_match
+                            .take()
+                            .unwrap_or_else(|| regex.find_at(data, pos).map(|m| m.range())),
+                    };
+                    Some(match found {
+                        Some(m) if m.start < end => {
+                            // empty matches would otherwise be found again
+                            pos = m.end + m.is_empty() as usize;
+                            SearchMessage::Match(m)
+                        }
+                        found => {
+                            if max_len.is_none() {
+                                next_match = Some(found);
+                            }
+                            pos = end;
+                            SearchMessage::Progress(end)
+                        }
+                    })
+                }))
+            }
+            Matcher::Approximate(approx) => {
+                let mut matches = approx.find_iter(data);
+                Box::new(std::iter::from_fn(move || {
+                    if matches.pos >= data.len() {
+                        return None;
+                    }
+                    let end = (matches.pos + PROGRESS_STEP).min(data.len());
+                    Some(match matches.next_before(end) {
+                        Some(m) => SearchMessage::Match(m),
+                        None => SearchMessage::Progress(matches.pos),
+                    })
+                }))
+            }
+        }
+    }
+}
+
+/// How many bytes are searched between progress reports
+const PROGRESS_STEP: usize = 1 << 20;
+
+/// The maximum length of the matches of a regex, or None if it is unbounded
+fn max_match_len(regex: &Regex) -> Option<usize> {
+    regex_syntax::ParserBuilder::new()
+        .unicode(false)
+        .utf8(false)
+        .build()
+        .parse(regex.as_str())
+        .ok()?
+        .properties()
+        .maximum_len()
+}
+
+/// parses a sequence of hex bytes, ignoring whitespace
+fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            c.to_digit(16)
+                .map(|d| d as u8)
+                .ok_or_else(|| format!("Invalid hex digit '{c}' in approximate search query"))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(String::from(
+            "Approximate hex search query must consist of whole bytes",
+        ));
+    }
+    Ok(digits.chunks(2).map(|x| x[0] << 4 | x[1]).collect())
+}
+
+/// A byte of a hex pattern, where only the bits set in `mask` have to be equal to `value`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct HexPatternByte {
+    value: u8,
+    mask: u8,
+}
+
+impl HexPatternByte {
+    fn matches(self, byte: u8) -> bool {
+        byte & self.mask == self.value
+    }
+}
+
+/// Parses a hex pattern of whitespace-separated bytes, where `??` matches
+/// any byte and a `?` in place of a single digit matches any nibble
+fn parse_hex_pattern(text: &str) -> Result<Vec<HexPatternByte>, String> {
+    let pattern = text
+        .split_whitespace()
+        .map(|token| {
+            // the offset of the token in the query, for error messages
+            let offset = token.as_ptr() as usize - text.as_ptr() as usize;
+            let invalid = |reason: &str| {
+                format!("Invalid token \"{token}\" at position {offset} of hex pattern: {reason}")
+            };
+            let digits = token.chars().collect::<Vec<_>>();
+            if digits.len() != 2 {
+                return Err(invalid(
+                    "every byte must consist of exactly two hex digits or '?'",
+                ));
+            }
+            let mut byte = HexPatternByte { value: 0, mask: 0 };
+            for (digit, shift) in digits.into_iter().zip([4, 0]) {
+                if digit == '?' {
+                    continue;
+                }
+                let nibble = digit
+                    .to_digit(16)
+                    .ok_or_else(|| invalid(&format!("'{digit}' is neither a hex digit nor '?'")))?;
+                byte.value |= (nibble as u8) << shift;
+                byte.mask |= 0xf << shift;
+            }
+            Ok(byte)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if pattern.is_empty() {
+        return Err(String::from("Hex pattern must not be empty"));
+    }
+    Ok(pattern)
+}
+
+/// Compiles a hex pattern into an equivalent byte regex
+fn hex_pattern_regex(pattern: &[HexPatternByte]) -> Result<Regex, regex::Error> {
+    let mut regex = String::new();
+    for byte in pattern {
+        match byte.mask {
+            0xff => regex.push_str(&format!("\\x{:02x}", byte.value)),
+            0 => regex.push('.'),
+            _ => {
+                regex.push('[');
+                for x in (0..=255u8).filter(|x| byte.matches(*x)) {
+                    regex.push_str(&format!("\\x{x:02x}"));
+                }
+                regex.push(']');
+            }
+        }
+    }
+    RegexBuilder::new(&regex)
+        .unicode(false)
+        .dot_matches_new_line(true)
+        .build()
+}
+
+/// Maximum pattern length for approximate search, since the
+/// bitap state of one pattern has to fit into an u64
+pub const MAX_APPROXIMATE_LEN: usize = 64;
+
+/// Finds matches of a pattern with at most `k` substitutions
+/// using the bitap (shift-and) algorithm
+#[derive(Clone, Debug)]
+pub struct ApproximateMatcher {
+    /// for each byte value, the bit i is set if the pattern has that value at position i
+    masks: Box<[u64; 256]>,
+    len: usize,
+    k: usize,
+}
+
+impl ApproximateMatcher {
+    pub fn new(pattern: &[u8], k: usize) -> Result<Self, String> {
+        let pattern = pattern
+            .iter()
+            .map(|&value| HexPatternByte { value, mask: 0xff })
+            .collect::<Vec<_>>();
+        Self::with_wildcards(&pattern, k)
+    }
+    /// Like `new`, but with bytes that only have to match partially
+    fn with_wildcards(pattern: &[HexPatternByte], k: usize) -> Result<Self, String> {
+        if pattern.is_empty() {
+            return Err(String::from("Approximate search query must not be empty"));
+        }
+        if pattern.len() > MAX_APPROXIMATE_LEN {
+            return Err(format!(
+                "Approximate search query must be at most {MAX_APPROXIMATE_LEN} bytes long"
+            ));
+        }
+        if k >= pattern.len() {
+            return Err(format!(
+                "Number of mismatches ({k}) must be smaller than the query length ({})",
+                pattern.len()
+            ));
+        }
+        let mut masks = Box::new([0u64; 256]);
+        for (i, byte) in pattern.iter().enumerate() {
+            for x in 0..=255u8 {
+                if byte.matches(x) {
+                    masks[x as usize] |= 1 << i;
+                }
+            }
+        }
+        Ok(ApproximateMatcher {
+            masks,
+            len: pattern.len(),
+            k,
+        })
+    }
+    /// Iterates over the non-overlapping matches in `data`, leftmost first
+    pub fn find_iter<'a>(&'a self, data: &'a [u8]) -> ApproximateMatches<'a> {
+        ApproximateMatches {
+            matcher: self,
+            data,
+            pos: 0,
+            states: vec![0; self.k + 1],
+        }
+    }
+}
+
+pub struct ApproximateMatches<'a> {
+    matcher: &'a ApproximateMatcher,
+    data: &'a [u8],
+    pos: usize,
+    /// states[d] has bit i set if the pattern prefix of length i + 1 matches
+    /// the text ending at the current position with at most d mismatches
+    states: Vec<u64>,
+}
+
+impl<'a> ApproximateMatches<'a> {
+    /// returns the next match that ends before `end`, stopping at `end` if there is none
+    fn next_before(&mut self, end: usize) -> Option<Range<usize>> {
+        let accept = 1u64 << (self.matcher.len - 1);
+        while self.pos < end {
+            let mask = self.matcher.masks[self.data[self.pos] as usize];
+            self.pos += 1;
+            let mut prev_old = 0u64;
+            for d in 0..self.states.len() {
+                let old = self.states[d];
+                let mut new = (old << 1 | 1) & mask;
+                if d > 0 {
+                    // substitute the current byte
+                    new |= prev_old << 1 | 1;
+                }
+                self.states[d] = new;
+                prev_old = old;
+            }
+            if self.states[self.matcher.k] & accept != 0 {
+                // reset the states so that the next match starts after this one
+                self.states.iter_mut().for_each(|x| *x = 0);
+                return Some(self.pos - self.matcher.len..self.pos);
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for ApproximateMatches<'a> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_before(self.data.len())
+    }
+}
+
+/// The messages sent from a running search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchMessage {
+    /// a match was found
+    Match(Range<usize>),
+    /// everything before this offset was searched
+    Progress(usize),
+    /// the whole file was searched
+    Done,
+    /// the search was stopped before reaching the end of the file
+    Cancelled,
+}
+
+impl Finalable for SearchMessage {
+    fn is_final(&self) -> bool {
+        matches!(self, SearchMessage::Done | SearchMessage::Cancelled)
+    }
+}
+
+/// How far a search on one file has come
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStatus {
+    /// still searching, with everything before the offset being searched
+    Running(usize),
+    Finished,
+    Cancelled,
+}
+
+#[derive(Debug)]
+/// contains a query and its results
+pub struct SearchResults {
+    /// maps the start address of a match to its end
+    starts: BTreeMap<usize, usize>,
+    /// maps the end address of a match to its start
+    ends: BTreeMap<usize, usize>,
+    /// the query this belongs to
+    query: Query,
+    /// the length of the searched file
+    len: usize,
+    status: SearchStatus,
+    /// whether only matches where the aligned files differ are kept
+    only_differences: bool,
+    /// maps the start address of matches of a difference-only search that
+    /// could not be checked yet to their end
+    pending: BTreeMap<usize, usize>,
+    /// whether the search filling these results goes on, see `SearchContext`
+    is_running: Arc<AtomicBool>,
+}
+
+/// Results that are replaced or removed (like the oldest ones when there are too many)
+/// stop their search, which would otherwise go on for nothing
+impl Drop for SearchResults {
+    fn drop(&mut self) {
+        self.is_running.store(false, Ordering::Relaxed);
+    }
+}
+
+fn map_both<T, S>(r: Result<T, T>, f: impl FnOnce(T) -> S) -> Result<S, S> {
+    match r {
+        Ok(s) => Ok(f(s)),
+        Err(s) => Err(f(s)),
+    }
+}
+
+fn transpose_both<T>(r: Result<Option<T>, Option<T>>) -> Option<Result<T, T>> {
+    match r {
+        Ok(Some(s)) => Some(Ok(s)),
+        Err(Some(s)) => Some(Err(s)),
+        Ok(None) | Err(None) => None,
+    }
+}
+
+fn unwrap_both<T>(r: Result<T, T>) -> T {
+    match r {
+        Ok(s) | Err(s) => s,
+    }
+}
+
+impl SearchResults {
+    /// Get a new empty search result store for a given query on a file of length `len`
+    pub fn new(query: Query, len: usize) -> Self {
+        SearchResults {
+            starts: BTreeMap::new(),
+            ends: BTreeMap::new(),
+            query,
+            len,
+            status: SearchStatus::Running(0),
+            only_differences: false,
+            pending: BTreeMap::new(),
+            is_running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+    /// get the query associated with this SearchResults set
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+    /// add a match range to the set, keeping the matches non-empty and disjoint so that
+    /// every start and end belongs to exactly one match: empty matches (like the ones
+    /// of a regex like `a*`) are dropped and overlapping ones are merged into one match,
+    /// while adjacent matches stay separate
+    pub fn add_match(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        // since the matches are disjoint, the ones overlapping the range are the last
+        // ones starting before its end which also end after its start
+        let overlapping = self
+            .starts
+            .range(..range.end)
+            .rev()
+            .take_while(|(_, &end)| end > range.start)
+            .map(|(&start, &end)| start..end)
+            .collect::<Vec<_>>();
+        let mut merged = range;
+        for old in overlapping {
+            self.starts.remove(&old.start);
+            self.ends.remove(&old.end);
+            merged = merged.start.min(old.start)..merged.end.max(old.end);
+        }
+        self.starts.insert(merged.start, merged.end);
+        self.ends.insert(merged.end, merged.start);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+    /// iterates over all matches, ordered by address
+    pub fn matches(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.starts.iter().map(|(start, end)| *start..*end)
+    }
+    /// update the results with a message from the search process
+    pub fn update(&mut self, message: SearchMessage) {
+        match message {
+            SearchMessage::Match(range) => {
+                if let SearchStatus::Running(offset) = &mut self.status {
+                    *offset = (*offset).max(range.end);
+                }
+                if self.only_differences {
+                    self.pending.insert(range.start, range.end);
+                } else {
+                    self.add_match(range)
+                }
+            }
+            SearchMessage::Progress(pos) => {
+                if let SearchStatus::Running(offset) = &mut self.status {
+                    *offset = (*offset).max(pos);
+                }
+            }
+            SearchMessage::Done => self.status = SearchStatus::Finished,
+            SearchMessage::Cancelled => self.status = SearchStatus::Cancelled,
+        }
+    }
+    pub fn status(&self) -> SearchStatus {
+        self.status
+    }
+    /// Tells how many results were found in the file `name` once the search is finished,
+    /// or None before that and without results, which the search status already shows
+    pub fn finished_summary(&self, name: &str) -> Option<String> {
+        if self.status != SearchStatus::Finished || self.is_empty() {
+            return None;
+        }
+        let count = self.starts.len();
+        let plural = if count == 1 { "" } else { "s" };
+        Some(format!(
+            "Found {count} result{plural} for {} in {name}",
+            self.query.text()
+        ))
+    }
+    /// Decides for the pending matches of a difference-only search that overlap `block`
+    /// whether they are kept. `decide` returns None if this cannot be decided yet,
+    /// in which case they stay pending.
+    pub fn filter_pending(
+        &mut self,
+        block: Range<usize>,
+        mut decide: impl FnMut(&Range<usize>) -> Option<bool>,
+    ) {
+        // the matches of a search do not overlap, so only the last one starting
+        // before the block can reach into it
+        let before =
+            (self.pending.range(..block.start).next_back()).filter(|(_, &end)| end > block.start);
+        let candidates = before
+            .into_iter()
+            .chain(self.pending.range(block))
+            .map(|(&start, &end)| start..end)
+            .collect::<Vec<_>>();
+        for range in candidates {
+            if let Some(keep) = decide(&range) {
+                self.pending.remove(&range.start);
+                if keep {
+                    self.add_match(range)
+                }
+            }
+        }
+    }
+    /// Drops the pending matches that could not be decided, once nothing more is aligned
+    pub fn drop_pending(&mut self) {
+        self.pending.clear()
+    }
+    /// the percentage of the file that was searched, if the search is still running
+    pub fn progress(&self) -> Option<usize> {
+        match self.status {
+            SearchStatus::Running(offset) => {
+                Some((offset * 100).checked_div(self.len).unwrap_or(0))
+            }
+            _ => None,
+        }
+    }
+    /// calculates whether the given address is inside a result
+    pub fn is_in_result(&self, addr: Option<usize>) -> bool {
+        self.result_at(addr).is_some()
+    }
+    /// returns the range of the result that contains the given address
+    pub fn result_at(&self, addr: Option<usize>) -> Option<Range<usize>> {
+        let addr = addr?;
+        self.starts
+            .range(..=addr)
+            .next_back()
+            .map(|(x, y)| *x..*y)
+            .filter(|range| range.contains(&addr))
+    }
+    /// get the next result after addr
+    /// Returns None if there is no result, and Some(Err) if the result is after wraparound
+    pub fn next_result(&self, addr: usize) -> Option<Result<Range<usize>, Range<usize>>> {
+        match self
+            .starts
+            .range(addr + 1..)
+            .map(|(a, b)| *a..*b)
+            .next()
+            .ok_or_else(|| self.starts.range(..).map(|(a, b)| *a..*b).next())
+        {
+            Ok(o) => Some(Ok(o)),
+            Err(Some(e)) => Some(Err(e)),
+            Err(None) => None,
+        }
+    }
+    /// from a list of search results, find the next result from any of them,
+    /// along with whether the search wrapped around to the start to find it.
+    /// the T is supposed to be data to disambiguate between the multiple
+    /// usize addresses from different search results
+    pub fn nearest_next_result<T: Ord + Copy>(
+        list: &[(&Self, usize, T)],
+        to_index: impl Fn(usize, T) -> Option<isize>,
+    ) -> Option<(isize, bool)> {
+        // note that Ok(_) < Err(_), so by using min here,
+        // we prioritize results that are not wraparound
+        let next = list
+            .iter()
+            .flat_map(|&(search, addr, right)| {
+                search
+                    .next_result(addr)
+                    .and_then(|x| transpose_both(map_both(x, |y| to_index(y.start, right))))
+            })
+            .min()?;
+        Some((unwrap_both(next), next.is_err()))
+    }
+    /// get the previous result before addr
+    /// Returns None if there is no result, and Some(Err) if the result is after wraparound
+    pub fn prev_result(&self, addr: usize) -> Option<Result<Range<usize>, Range<usize>>> {
+        match self
+            .ends
+            .range(..=addr)
+            .rev()
+            .map(|(a, b)| *b..*a)
+            .next()
+            .ok_or_else(|| self.ends.range(..).rev().map(|(a, b)| *b..*a).next())
+        {
+            Ok(o) => Some(Ok(o)),
+            Err(Some(e)) => Some(Err(e)),
+            Err(None) => None,
+        }
+    }
+    /// from a list of search results, find the previous result from any of them,
+    /// along with whether the search wrapped around to the end to find it.
+    /// the T is supposed to be data to disambiguate between the multiple
+    /// usize addresses from different search results
+    pub fn nearest_prev_result<T: Ord + Copy>(
+        list: &[(&Self, usize, T)],
+        to_index: impl Fn(usize, T) -> Option<isize>,
+    ) -> Option<(isize, bool)> {
+        // note that Ok(_) < Err(_), so by using min here,
+        // we prioritize results that are not wraparound
+        let next = list
+            .iter()
+            .flat_map(|&(search, addr, right)| {
+                search
+                    .prev_result(addr)
+                    .and_then(|x| transpose_both(map_both(x, |y| to_index(y.start, right))))
+                    // since we use min to prioritize Ok over Err, reverse the order of the addresses to prioritize later addresses
+                    // (those that are nearer to the original address)
+                    .map(|x| map_both(x, std::cmp::Reverse))
+            })
+            .min()?;
+        Some((unwrap_both(next).0, next.is_err()))
+    }
+}
+
+/// Options for how a new search is set up in the view
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchOptions {
+    /// add the query to the existing ones instead of replacing them
+    pub keep: bool,
+    /// only keep matches where the aligned files differ, which only works in the aligned view
+    pub only_differences: bool,
+}
+
+/// The maximum number of queries that can be active on one file at the same time.
+/// Each query gets its own highlight color, depending on its slot.
+pub const MAX_SEARCH_SLOTS: usize = 4;
+
+/// An enum for keeping track which views a cursor is enabled in,
+/// which is also the files that searches apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorActive {
+    Both,
+    First,
+    Second,
+    None,
+}
+impl CursorActive {
+    /// Cursor is enabled on the first view
+    pub fn is_first(&self) -> bool {
+        match self {
+            Self::Both | Self::First => true,
+            Self::Second | Self::None => false,
+        }
+    }
+    /// Cursor is enabled on the second view
+    pub fn is_second(&self) -> bool {
+        match self {
+            Self::Both | Self::Second => true,
+            Self::First | Self::None => false,
+        }
+    }
+    /// The same cursors after swapping the views
+    pub fn swapped(self) -> Self {
+        match self {
+            Self::First => Self::Second,
+            Self::Second => Self::First,
+            both_or_none => both_or_none,
+        }
+    }
+    /// List of whether view is enabled
+    pub fn is_active(&self) -> [bool; 2] {
+        match self {
+            Self::Both => [true, true],
+            Self::First => [true, false],
+            Self::Second => [false, true],
+            Self::None => [false, false],
+        }
+    }
+}
+
+/// The search results of both files, each being a list of the active queries
+/// with the oldest query first
+pub struct SearchPair(pub Vec<SearchResults>, pub Vec<SearchResults>);
+
+impl SearchPair {
+    /// Exchanges the searches of both files
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.0, &mut self.1);
+    }
+    /// returns the slot of the first query that has a result at the given address, for both files
+    pub fn is_in_result(&self, addr: [Option<usize>; 2]) -> [Option<u8>; 2] {
+        [(&self.0, addr[0]), (&self.1, addr[1])].map(|(list, addr)| {
+            list.iter()
+                .position(|search| search.is_in_result(addr))
+                .map(|slot| slot as u8)
+        })
+    }
+    /// returns the results under the cursor addresses of both files
+    pub fn current_results(&self, cursor: [Option<usize>; 2]) -> CurrentResults {
+        CurrentResults(
+            [(&self.0, cursor[0]), (&self.1, cursor[1])]
+                .map(|(list, addr)| list.iter().find_map(|search| search.result_at(addr))),
+        )
+    }
+    /// removes all queries of the active cursors
+    pub fn clear(&mut self, cursor_act: CursorActive) {
+        if cursor_act.is_first() {
+            self.0.clear();
+        }
+        if cursor_act.is_second() {
+            self.1.clear();
+        }
+    }
+    /// removes the most recently added query of the active cursors
+    pub fn drop_last(&mut self, cursor_act: CursorActive) {
+        if cursor_act.is_first() {
+            self.0.pop();
+        }
+        if cursor_act.is_second() {
+            self.1.pop();
+        }
+    }
+    /// returns the search results belonging to `query` on one of the files
+    pub fn results_mut(&mut self, first: bool, query: &Query) -> Option<&mut SearchResults> {
+        if first { &mut self.0 } else { &mut self.1 }
+            .iter_mut()
+            .find(|search| search.query() == query)
+    }
+    /// returns all search results of the active cursors, along with whether
+    /// they are on the second file
+    pub fn active(&self, cursor_act: CursorActive) -> Vec<(&SearchResults, bool)> {
+        let first = self.0.iter().filter(|_| cursor_act.is_first());
+        let second = self.1.iter().filter(|_| cursor_act.is_second());
+        first
+            .map(|x| (x, false))
+            .chain(second.map(|x| (x, true)))
+            .collect()
+    }
+    /// returns whether any search is still running
+    pub fn is_running(&self) -> bool {
+        self.0
+            .iter()
+            .chain(self.1.iter())
+            .any(|s| matches!(s.status(), SearchStatus::Running(_)))
+    }
+    /// marks the searches that are still running as cancelled, for when their
+    /// results can not arrive anymore
+    pub fn cancel_running(&mut self) {
+        for search in self.0.iter_mut().chain(self.1.iter_mut()) {
+            if search.progress().is_some() {
+                search.update(SearchMessage::Cancelled);
+            }
+        }
+    }
+    /// describes the state of the searches of the active cursors for the bottom bar:
+    /// the progress while they are still running, or whether the newest
+    /// search was cancelled or found nothing
+    pub fn status_text(&self, cursor_act: CursorActive) -> Option<String> {
+        let active = self.active(cursor_act);
+        if let Some(progress) = active.iter().filter_map(|(s, _)| s.progress()).min() {
+            return Some(format!("Searching... {progress}%"));
+        }
+        let newest = [
+            (&self.0, cursor_act.is_first()),
+            (&self.1, cursor_act.is_second()),
+        ]
+        .into_iter()
+        .filter(|(_, is_active)| *is_active)
+        .filter_map(|(list, _)| list.last())
+        .collect::<Vec<_>>();
+        if newest.iter().any(|s| s.status() == SearchStatus::Cancelled) {
+            Some(String::from("Search cancelled"))
+        } else if !newest.is_empty() && newest.iter().all(|s| s.is_empty()) {
+            Some(String::from("No search results"))
+        } else {
+            None
+        }
+    }
+    /// returns the most recently added query of the active cursors
+    pub fn current_search_query(&self, cursor_act: CursorActive) -> Option<&Query> {
+        if cursor_act.is_first() {
+            [&self.0, &self.1]
+        } else {
+            [&self.1, &self.0]
+        }
+        .iter()
+        .flat_map(|list| list.last())
+        .map(|x| x.query())
+        .next()
+    }
+    /// Initializes the empty search results for the search query
+    /// on the currently active cursors.
+    pub fn setup_search(
+        &mut self,
+        query: Query,
+        cursor_act: CursorActive,
+        files: [FileContent; 2],
+        options: SearchOptions,
+    ) -> (
+        (SearchContext, FileContent),
+        Option<(SearchContext, FileContent)>,
+    ) {
+        let [ffirst, fsecond] = files;
+        // the searches of both files have their own flags, since their results
+        // are dropped on their own
+        let mut insert = |first: bool, len| {
+            let context = SearchContext {
+                first,
+                query: query.clone(),
+                is_running: Arc::new(AtomicBool::new(true)),
+            };
+            let list = if first { &mut self.0 } else { &mut self.1 };
+            insert_query(list, &context, len, options);
+            context
+        };
+        match cursor_act {
+            CursorActive::None | CursorActive::Both => {
+                let first = insert(true, ffirst.len());
+                let second = insert(false, fsecond.len());
+                ((first, ffirst), Some((second, fsecond)))
+            }
+            CursorActive::First => ((insert(true, ffirst.len()), ffirst), None),
+            CursorActive::Second => ((insert(false, fsecond.len()), fsecond), None),
+        }
+    }
+}
+
+/// quotes a CSV field if necessary
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the results of all queries on both files as CSV, with the file name, query, start and end
+/// (exclusive) of every match in the whole file, ordered by file, query and address.
+/// If `with_bytes` is set, the matched bytes are added as hex.
+pub fn write_results_csv<W: Write>(
+    out: &mut W,
+    files: [(&str, &FileBytes, &[SearchResults]); 2],
+    with_bytes: bool,
+) -> std::io::Result<()> {
+    write!(out, "file,query,start,end")?;
+    if with_bytes {
+        write!(out, ",bytes")?;
+    }
+    writeln!(out)?;
+    for (name, content, searches) in files {
+        let name = csv_field(name);
+        for search in searches {
+            let query = csv_field(search.query().text());
+            for range in search.matches() {
+                let base = content.base();
+                write!(
+                    out,
+                    "{name},{query},{:#x},{:#x}",
+                    range.start + base,
+                    range.end + base
+                )?;
+                if with_bytes {
+                    write!(out, ",")?;
+                    for byte in content.get(range).unwrap_or_default() {
+                        write!(out, "{byte:02x}")?;
+                    }
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The ranges of the search results the cursor is currently in, for both files
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CurrentResults(pub [Option<Range<usize>>; 2]);
+
+impl CurrentResults {
+    /// returns whether the given addresses are inside the current results
+    pub fn contains(&self, addr: [Option<usize>; 2]) -> [bool; 2] {
+        [0, 1].map(|i| match (&self.0[i], addr[i]) {
+            (Some(range), Some(addr)) => range.contains(&addr),
+            _ => false,
+        })
+    }
+}
+
+/// adds empty results for a query to the list, replacing the results of
+/// the same query if it already exists and dropping the oldest query
+/// when there are too many.
+/// If `options.keep` is not set, the other queries are removed.
+/// The searches of the dropped results are stopped.
+fn insert_query(
+    list: &mut Vec<SearchResults>,
+    context: &SearchContext,
+    len: usize,
+    options: SearchOptions,
+) {
+    if !options.keep {
+        list.clear();
+    }
+    let mut results = SearchResults::new(context.query.clone(), len);
+    results.only_differences = options.only_differences;
+    results.is_running = context.is_running.clone();
+    if let Some(existing) = list.iter_mut().find(|x| x.query() == results.query()) {
+        *existing = results;
+        return;
+    }
+    if list.len() >= MAX_SEARCH_SLOTS {
+        list.remove(0);
+    }
+    list.push(results);
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchContext {
+    /// what hexpanel this is on (effectively an identifier for the search process)
+    pub first: bool,
+    /// original query
+    pub query: Query,
+    /// bool for cancelling the search
+    pub is_running: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl SearchContext {
+    pub fn start_search<Sender>(self, mut send: Sender, file: FileContent)
+    where
+        Sender: FnMut(SearchMessage) -> bool + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            for message in self.query.search_iter(&file) {
+                if !self.is_running.load(Ordering::Relaxed) {
+                    send(SearchMessage::Cancelled);
+                    return;
+                }
+                if !send(message) {
+                    return;
+                }
+            }
+            send(SearchMessage::Done);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn approximate_matches() {
+        let matcher = ApproximateMatcher::new(b"abcd", 1).unwrap();
+        let matches = matcher
+            .find_iter(b"xabcdxabxdxxbcdxaxxd")
+            .collect::<Vec<_>>();
+        assert_eq!(matches, vec![1..5, 6..10, 11..15]);
+        let exact = ApproximateMatcher::new(b"ab", 0).unwrap();
+        assert_eq!(
+            exact.find_iter(b"abab").collect::<Vec<_>>(),
+            vec![0..2, 2..4]
+        );
+        let any = ApproximateMatcher::new(b"ab", 1).unwrap();
+        assert_eq!(any.find_iter(b"xxxx").next(), None);
+        assert_eq!(any.find_iter(b"xbxa").collect::<Vec<_>>(), vec![0..2]);
+        assert!(ApproximateMatcher::new(b"ab", 2).is_err());
+        assert!(ApproximateMatcher::new(&[0; 65], 1).is_err());
+        let long = (0..64u8).collect::<Vec<_>>();
+        let mut text = vec![0xffu8; 10];
+        text.extend(long.iter().map(|x| x ^ (*x == 30) as u8));
+        let long_matcher = ApproximateMatcher::new(&long, 1).unwrap();
+        assert_eq!(
+            long_matcher.find_iter(&text).collect::<Vec<_>>(),
+            vec![10..74]
+        );
+    }
+    #[test]
+    fn approximate_query() {
+        let query = Query::new(QueryType::Hexagex, "de ad be ef", 1).unwrap();
+        let data = [0u8, 0xde, 0xad, 0x00, 0xef];
+        assert_eq!(query.find_iter(&data).collect::<Vec<_>>(), vec![1..5]);
+        assert!(Query::new(QueryType::Regex, "abc", 1).is_err());
+        assert!(Query::new(QueryType::Hexagex, "abc", 1).is_err());
+    }
+    #[test]
+    fn multiple_queries() {
+        let query = |text| Query::new(QueryType::Text, text, 0).unwrap();
+        let mut pair = SearchPair(vec![], vec![]);
+        let keep = SearchOptions {
+            keep: true,
+            ..SearchOptions::default()
+        };
+        let files = || {
+            [
+                Arc::new(b"abcd".to_vec().into()),
+                Arc::new(b"abcd".to_vec().into()),
+            ]
+        };
+        pair.setup_search(
+            query("a"),
+            CursorActive::Both,
+            files(),
+            SearchOptions::default(),
+        );
+        let ((oldest, _), _) = pair.setup_search(query("c"), CursorActive::First, files(), keep);
+        pair.results_mut(true, &query("a")).unwrap().add_match(0..1);
+        pair.results_mut(true, &query("c")).unwrap().add_match(2..3);
+        pair.results_mut(false, &query("a"))
+            .unwrap()
+            .add_match(0..1);
+        assert!(pair.results_mut(false, &query("c")).is_none());
+        assert_eq!(pair.is_in_result([Some(0), Some(0)]), [Some(0), Some(0)]);
+        assert_eq!(pair.is_in_result([Some(2), Some(2)]), [Some(1), None]);
+        let current = pair.current_results([Some(2), Some(0)]);
+        assert_eq!(current, CurrentResults([Some(2..3), Some(0..1)]));
+        assert_eq!(current.contains([Some(3), Some(0)]), [false, true]);
+        assert_eq!(
+            pair.current_search_query(CursorActive::First),
+            Some(&query("c"))
+        );
+        // searching for an existing query again keeps its slot
+        pair.setup_search(query("a"), CursorActive::First, files(), keep);
+        assert_eq!(pair.is_in_result([Some(0), None]), [None, None]);
+        assert_eq!(pair.is_in_result([Some(2), None]), [Some(1), None]);
+        for text in ["e", "f", "g"] {
+            pair.setup_search(query(text), CursorActive::First, files(), keep);
+        }
+        assert_eq!(pair.0.len(), MAX_SEARCH_SLOTS);
+        assert_eq!(pair.0[0].query(), &query("c"));
+        assert!(oldest.is_running.load(Ordering::Relaxed));
+        // the search of the oldest query stops when it is dropped for a new one
+        let ((newest, _), _) = pair.setup_search(query("h"), CursorActive::First, files(), keep);
+        assert!(!oldest.is_running.load(Ordering::Relaxed));
+        assert_eq!(pair.0[0].query(), &query("e"));
+        pair.drop_last(CursorActive::Both);
+        assert!(!newest.is_running.load(Ordering::Relaxed));
+        assert_eq!(pair.0.len(), MAX_SEARCH_SLOTS - 1);
+        assert!(pair.1.is_empty());
+        pair.setup_search(
+            query("h"),
+            CursorActive::First,
+            files(),
+            SearchOptions::default(),
+        );
+        assert_eq!(pair.0.len(), 1);
+    }
+    #[test]
+    fn search_progress() {
+        let query = Query::new(QueryType::Text, "abc", 1).unwrap();
+        let mut data = vec![0u8; PROGRESS_STEP * 2 + 10];
+        data[PROGRESS_STEP + 5..PROGRESS_STEP + 8].copy_from_slice(b"abd");
+        let messages = query.search_iter(&data).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                SearchMessage::Progress(PROGRESS_STEP),
+                SearchMessage::Match(PROGRESS_STEP + 5..PROGRESS_STEP + 8),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 8),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 10),
+            ]
+        );
+        let mut results = SearchResults::new(query, data.len());
+        results.update(messages[0].clone());
+        assert_eq!(results.progress(), Some(49));
+        assert_eq!(results.finished_summary("first"), None);
+        results.update(messages[1].clone());
+        results.update(SearchMessage::Done);
+        // exact matches crossing the end of a chunk are still found
+        let exact = Query::new(QueryType::Text, "abc", 0).unwrap();
+        data[PROGRESS_STEP - 1..PROGRESS_STEP + 2].copy_from_slice(b"abc");
+        assert_eq!(
+            exact.search_iter(&data).collect::<Vec<_>>(),
+            vec![
+                SearchMessage::Match(PROGRESS_STEP - 1..PROGRESS_STEP + 2),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 2),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 10),
+            ]
+        );
+        let unbounded = Query::new(QueryType::Regex, "ab[cd]+", 0).unwrap();
+        assert_eq!(
+            unbounded.search_iter(&data).collect::<Vec<_>>(),
+            vec![
+                SearchMessage::Match(PROGRESS_STEP - 1..PROGRESS_STEP + 2),
+                SearchMessage::Match(PROGRESS_STEP + 5..PROGRESS_STEP + 8),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 8),
+                SearchMessage::Progress(PROGRESS_STEP * 2 + 10),
+            ]
+        );
+        // a match behind the first chunk is only reported once its chunk is reached
+        let later = Query::new(QueryType::Regex, "abd+", 0).unwrap();
+        assert_eq!(later.search_iter(&data).collect::<Vec<_>>(), messages);
+        assert_eq!(results.progress(), None);
+        assert_eq!(results.status(), SearchStatus::Finished);
+        assert_eq!(
+            results.finished_summary("first").as_deref(),
+            Some("Found 1 result for abc in first")
+        );
+    }
+    #[test]
+    fn wraparound() {
+        let query = Query::new(QueryType::Text, "ab", 0).unwrap();
+        let mut results = SearchResults::new(query, 16);
+        results.add_match(2..4);
+        results.add_match(8..10);
+        let index = |addr, _: bool| Some(addr as isize);
+        let next = |addr| SearchResults::nearest_next_result(&[(&results, addr, false)], index);
+        let prev = |addr| SearchResults::nearest_prev_result(&[(&results, addr, false)], index);
+        assert_eq!(next(2), Some((8, false)));
+        assert_eq!(next(8), Some((2, true)));
+        assert_eq!(prev(9), Some((2, false)));
+        assert_eq!(prev(3), Some((8, true)));
+    }
+    #[test]
+    fn match_boundaries() {
+        let query = Query::new(QueryType::Regex, "ab|b*", 0).unwrap();
+        let mut results = SearchResults::new(query, 12);
+        // adjacent, overlapping and empty matches, and ones at both ends of the file
+        for range in [0..2, 2..4, 6..8, 7..9, 5..5, 10..12, 12..12] {
+            results.add_match(range);
+        }
+        let matches = results.matches().collect::<Vec<_>>();
+        assert_eq!(matches, vec![0..2, 2..4, 6..9, 10..12]);
+        for (i, m) in matches.iter().enumerate() {
+            let next = &matches[(i + 1) % matches.len()];
+            let prev = &matches[(i + matches.len() - 1) % matches.len()];
+            assert_eq!(
+                results.next_result(m.start).map(unwrap_both),
+                Some(next.clone())
+            );
+            assert_eq!(
+                results.prev_result(m.start).map(unwrap_both),
+                Some(prev.clone())
+            );
+            for addr in m.clone() {
+                assert_eq!(results.result_at(Some(addr)), Some(m.clone()));
+            }
+        }
+        for addr in [4, 5, 9, 12] {
+            assert!(!results.is_in_result(Some(addr)));
+        }
+        // only jumping past the ends of the file wraps around
+        assert_eq!(results.next_result(4), Some(Ok(6..9)));
+        assert_eq!(results.next_result(10), Some(Err(0..2)));
+        assert_eq!(results.prev_result(1), Some(Err(10..12)));
+        assert_eq!(results.prev_result(12), Some(Ok(10..12)));
+    }
+    #[test]
+    fn export_csv() {
+        let query = Query::new(QueryType::Text, "a,b", 0).unwrap();
+        let mut results = SearchResults::new(query, 8);
+        results.add_match(5..8);
+        results.add_match(0..3);
+        let mut out = Vec::new();
+        write_results_csv(
+            &mut out,
+            [
+                ("first", &b"a,bxxa,b".to_vec().into(), &[results]),
+                ("second", &Vec::new().into(), &[]),
+            ],
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "file,query,start,end,bytes\n\
+             first,\"a,b\",0x0,0x3,612c62\n\
+             first,\"a,b\",0x5,0x8,612c62\n"
+        );
+    }
+    #[test]
+    fn hex_pattern() {
+        let query = Query::new(QueryType::HexPattern, "de ad ?? 4? ?f", 0).unwrap();
+        let data = [0xde, 0xad, 0x0a, 0x41, 0x3f, 0xde, 0xad, 0x00, 0x51, 0x0f];
+        assert_eq!(query.find_iter(&data).collect::<Vec<_>>(), vec![0..5]);
+        let approx = Query::new(QueryType::HexPattern, "de ad ?? 4? ?f", 1).unwrap();
+        assert_eq!(
+            approx.find_iter(&data).collect::<Vec<_>>(),
+            vec![0..5, 5..10]
+        );
+        let err = parse_hex_pattern("de  adx ?").unwrap_err();
+        assert!(err.contains("\"adx\" at position 4"), "{err}");
+        let err = parse_hex_pattern("de g?").unwrap_err();
+        assert!(err.contains("\"g?\" at position 3"), "{err}");
+        assert!(parse_hex_pattern(" ").is_err());
+    }
+    #[test]
+    fn pending_results() {
+        let query = Query::new(QueryType::Text, "a", 0).unwrap();
+        let mut results = SearchResults::new(query, 10);
+        results.only_differences = true;
+        for m in [0..1, 4..5, 7..9] {
+            results.update(SearchMessage::Match(m));
+        }
+        assert!(results.is_empty());
+        results.filter_pending(0..10, |range| match range.start {
+            0 => Some(true),
+            4 => Some(false),
+            _ => None,
+        });
+        assert_eq!(results.matches().collect::<Vec<_>>(), vec![0..1]);
+        // only the matches overlapping the block are looked at
+        results.filter_pending(5..7, |_| Some(true));
+        assert_eq!(results.matches().collect::<Vec<_>>(), vec![0..1]);
+        // as well as the one reaching into it from before
+        results.filter_pending(8..10, |_| Some(true));
+        assert_eq!(results.matches().collect::<Vec<_>>(), vec![0..1, 7..9]);
+        // matches that are still pending when the alignment is done are dropped
+        results.update(SearchMessage::Match(9..10));
+        results.drop_pending();
+        results.filter_pending(9..10, |_| Some(true));
+        assert_eq!(results.matches().collect::<Vec<_>>(), vec![0..1, 7..9]);
+    }
+}
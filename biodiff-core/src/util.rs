@@ -0,0 +1,380 @@
+use core::panic;
+use std::{
+    sync::mpsc::{sync_channel, Receiver},
+    time::{Duration, Instant},
+};
+
+use realfft::{num_complex::Complex64, RealFftPlanner};
+
+/// a channel that bunches up data into vectors if there is too much data at once
+/// (to avoid too many callbacks to cursive)
+/// used by the rate_limit_channel function
+struct RateLimitedChannel<T: Finalable + Send, F: FnMut(Vec<T>) -> bool + Send + 'static> {
+    /// the receiver of the input
+    input: Receiver<Option<T>>,
+    /// the function where batched up data is sent to
+    receiver: F,
+    /// maximum size of a batch
+    size: usize,
+    /// how long to wait until the batch is sent if it did not fill up
+    refresh_period: Duration,
+    /// last time a batch was sent
+    last_refresh: Option<Instant>,
+    /// buffer for current batch
+    element_buffer: Vec<T>,
+}
+
+impl<T: Finalable + Send + 'static, F: FnMut(Vec<T>) -> bool + Send + 'static>
+    RateLimitedChannel<T, F>
+{
+    fn spawn(mut self) {
+        std::thread::spawn(move || {
+            let period = self.refresh_period;
+            for i in self.input {
+                // if we reached our buffer size, we clear it
+                if self.element_buffer.len() >= self.size {
+                    if !(self.receiver)(self.element_buffer) {
+                        break;
+                    }
+                    self.last_refresh = Some(Instant::now());
+                    self.element_buffer = vec![];
+                }
+                if let Some(x) = i {
+                    self.element_buffer.push(x);
+                }
+                let is_final = self.element_buffer.is_final();
+                let do_refresh = self
+                    .last_refresh
+                    .map(|x| Instant::now() - x >= period || is_final)
+                    .unwrap_or(true);
+                if !do_refresh || self.element_buffer.is_empty() {
+                    continue;
+                }
+                if !(self.receiver)(self.element_buffer) {
+                    break;
+                }
+                self.last_refresh = Some(Instant::now());
+                self.element_buffer = vec![];
+                if is_final {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// trait for types which can have a final value that is sent last
+/// for the purpose of hanging up the RateLimitedChannel since there
+/// is a timer thread that would not get deleted otherwise
+pub trait Finalable {
+    fn is_final(&self) -> bool;
+}
+
+impl<T> Finalable for Option<T> {
+    fn is_final(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T> Finalable for Vec<T>
+where
+    T: Finalable,
+{
+    fn is_final(&self) -> bool {
+        self.last().map(|x| x.is_final()).unwrap_or(false)
+    }
+}
+
+/// construct a rate limited channel of maximum buffer size `size`, refresh period `period`
+/// and a receiver where data is pushed to, that returns a function that accepts data
+pub fn rate_limit_channel<T: Finalable + Send + 'static>(
+    size: usize,
+    period: Duration,
+    receiver: impl FnMut(Vec<T>) -> bool + Send + 'static,
+) -> impl FnMut(T) -> bool + Send + 'static {
+    let (input_channel_send, input_channel_recv) = sync_channel::<Option<T>>(2);
+    let timer_send = input_channel_send.clone();
+    let timer = move || loop {
+        std::thread::sleep(period);
+        if timer_send.send(None).is_err() {
+            break;
+        }
+    };
+    let relay = RateLimitedChannel {
+        input: input_channel_recv,
+        receiver,
+        size,
+        refresh_period: period,
+        last_refresh: None,
+        element_buffer: vec![],
+    };
+    relay.spawn();
+    std::thread::spawn(timer);
+    move |t| input_channel_send.send(Some(t)).is_ok()
+}
+
+/// returns the entropy of a blob of data
+pub fn entropy(data: &[u8]) -> f32 {
+    let mut counts = vec![0usize; 256];
+    for byte in data {
+        counts[usize::from(*byte)] += 1;
+    }
+    counts
+        .into_iter()
+        .map(|x| {
+            if x == 0 {
+                return 0.0f32;
+            };
+            let freq = x as f32 / data.len() as f32;
+            -freq * freq.log2() / 8.0
+        })
+        .sum::<f32>()
+}
+
+/// The byte counts of a window that moves over the data
+#[derive(Default)]
+struct WindowCounts {
+    counts: Vec<usize>,
+    len: usize,
+    /// the number of different bytes in the window
+    distinct: usize,
+    /// the sum of c * log2(c) over the counts c, which picks up rounding errors
+    /// as the window moves
+    sum: f64,
+}
+
+impl WindowCounts {
+    fn change(&mut self, byte: Option<u8>, add: bool) {
+        let byte = match byte {
+            Some(byte) => byte,
+            None => return,
+        };
+        if self.counts.is_empty() {
+            self.counts = vec![0; 256];
+        }
+        let c_log = |c: usize| {
+            if c == 0 {
+                0.0
+            } else {
+                c as f64 * (c as f64).log2()
+            }
+        };
+        let count = &mut self.counts[usize::from(byte)];
+        self.sum -= c_log(*count);
+        if add {
+            self.distinct += (*count == 0) as usize;
+            *count += 1;
+            self.len += 1;
+        } else {
+            *count -= 1;
+            self.distinct -= (*count == 0) as usize;
+            self.len -= 1;
+        }
+        self.sum += c_log(*count);
+    }
+    /// the entropy of the window in bits per byte
+    fn entropy(&self) -> f64 {
+        // windows of a single byte value have no entropy, even if the sum drifted
+        if self.distinct <= 1 {
+            return 0.0;
+        }
+        let len = self.len as f64;
+        (len.log2() - self.sum / len).max(0.0)
+    }
+}
+
+/// returns the entropy of the window of `window` bytes around each byte of the data,
+/// relative to the highest entropy such a window can have, so from 0 to 1.
+/// The windows are cut off at the ends of the data and missing bytes are left out.
+/// Takes linear time regardless of the window size.
+pub fn sliding_entropy(data: &[Option<u8>], window: usize) -> Vec<f32> {
+    let window = window.max(2);
+    let half = window / 2;
+    let max_entropy = (window.min(256) as f64).log2();
+    let mut counts = WindowCounts::default();
+    let (mut start, mut end) = (0, 0);
+    let mut ret = Vec::with_capacity(data.len());
+    for i in 0..data.len() {
+        let new_end = (i + window - half).min(data.len());
+        for byte in &data[end..new_end] {
+            counts.change(*byte, true);
+        }
+        end = new_end;
+        let new_start = i.saturating_sub(half);
+        for byte in &data[start..new_start] {
+            counts.change(*byte, false);
+        }
+        start = new_start;
+        ret.push((counts.entropy() / max_entropy).min(1.0) as f32);
+    }
+    ret
+}
+
+pub fn autocorrelation(data: &[u8]) -> Vec<f64> {
+    let padded_len = data.len() * 2;
+    let avg = data.iter().copied().map(u64::from).sum::<u64>() as f64 / data.len() as f64;
+    let mut a = vec![0.0f64; padded_len];
+    let mut a_out = vec![Complex64::new(0.0, 0.0); padded_len / 2 + 1];
+    let mut fft_planner = RealFftPlanner::new();
+    let fft_forward = fft_planner.plan_fft_forward(padded_len);
+    let fft_inverse = fft_planner.plan_fft_inverse(padded_len);
+    for (i, x) in data.iter().enumerate() {
+        a[i] = *x as f64 - avg;
+    }
+    let square_sum = a.iter().map(|x| x * x).sum::<f64>();
+    if square_sum == 0.0 {
+        return vec![0.0f64; padded_len];
+    }
+    fft_forward.process(&mut a, &mut a_out).unwrap();
+    for x in a_out.iter_mut() {
+        *x = *x * x.conj() / padded_len as f64 / square_sum;
+    }
+    fft_inverse.process(&mut a_out, &mut a).unwrap();
+    a.truncate(data.len());
+    a
+}
+
+/// The autocorrelation of `data` if it has at most `budget` bytes, and otherwise
+/// the average of the autocorrelations of `windows` evenly spread parts of it
+/// which together have `budget` bytes
+pub fn sampled_autocorrelation(data: &[u8], budget: usize, windows: usize) -> Vec<f64> {
+    if data.len() <= budget || windows < 2 {
+        return autocorrelation(data);
+    }
+    let window_len = budget / windows;
+    let mut sum = vec![0.0f64; window_len];
+    for i in 0..windows {
+        let start = i * (data.len() - window_len) / (windows - 1);
+        let window = autocorrelation(&data[start..start + window_len]);
+        for (total, x) in sum.iter_mut().zip(window) {
+            *total += x / windows as f64;
+        }
+    }
+    sum
+}
+
+/// alternative ilog2 implementation because
+/// i don't want to require rust 1.67 for now
+pub fn ilog2(mut n: usize) -> u8 {
+    let mut bit = usize::BITS as u8 / 2;
+    let mut res = 0;
+    if n == 0 {
+        panic!("ilog2(0) is undefined");
+    }
+    while bit > 0 {
+        if n & !((1 << bit) - 1) != 0 {
+            res += bit;
+            n >>= bit;
+        }
+        bit >>= 1;
+    }
+    res
+}
+
+/// Formats a number of bytes with binary prefixes and one decimal, like `1.5 MiB`
+pub fn human_size(len: usize) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if len < 1024 {
+        return format!("{len} B");
+    }
+    let mut size = len as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Formats the offset from an address of the first file to one of the second file
+/// in hex and decimal with its sign, like `+0x800 (+2048)`
+pub fn signed_offset(offset: isize) -> String {
+    match offset {
+        0 => String::from("0"),
+        offset if offset < 0 => format!("-{:#x} ({offset})", offset.unsigned_abs()),
+        offset => format!("+{offset:#x} (+{offset})"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::entropy;
+    #[test]
+    fn signed_offset() {
+        assert_eq!(super::signed_offset(0), "0");
+        assert_eq!(super::signed_offset(0x800), "+0x800 (+2048)");
+        assert_eq!(super::signed_offset(-16), "-0x10 (-16)");
+    }
+    #[test]
+    fn human_size() {
+        assert_eq!(super::human_size(0), "0 B");
+        assert_eq!(super::human_size(1023), "1023 B");
+        assert_eq!(super::human_size(1536), "1.5 KiB");
+        assert_eq!(super::human_size(64 << 20), "64.0 MiB");
+        assert_eq!(super::human_size(usize::MAX), "16.0 EiB");
+    }
+    #[test]
+    fn sampled_autocorrelation() {
+        let periodic = |len: usize| (0..len).map(|i| (i % 24 * 37 % 256) as u8);
+        let small: Vec<u8> = periodic(1000).collect();
+        assert_eq!(
+            super::sampled_autocorrelation(&small, 4096, 4),
+            super::autocorrelation(&small)
+        );
+        // a large zero region at the start does not hide the period
+        let large: Vec<u8> = std::iter::repeat(0)
+            .take(20000)
+            .chain(periodic(80000))
+            .collect();
+        let sampled = super::sampled_autocorrelation(&large, 8192, 8);
+        assert_eq!(sampled.len(), 1024);
+        let peak = (6..sampled.len())
+            .max_by(|a, b| sampled[*a].total_cmp(&sampled[*b]))
+            .unwrap();
+        assert_eq!(peak, 24);
+    }
+    #[test]
+    fn sliding_ent() {
+        let data: Vec<_> = [vec![0u8; 32], (32..64).collect()]
+            .concat()
+            .into_iter()
+            .map(Some)
+            .collect();
+        let sliding = super::sliding_entropy(&data, 16);
+        assert_eq!(sliding.len(), 64);
+        assert!(sliding[..24].iter().all(|e| *e == 0.0));
+        assert!(sliding[40..=56].iter().all(|e| (e - 1.0).abs() < 0.001));
+        // half of the window is zeros, the other half distinct bytes
+        assert!((sliding[32] - 2.5 / 4.0).abs() < 0.001);
+        // the same as computing each window on its own
+        for (i, e) in sliding.iter().enumerate() {
+            let window: Vec<u8> = data[i.saturating_sub(8)..(i + 8).min(64)]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            assert!((entropy(&window) * 8.0 / 4.0 - e).abs() < 0.001);
+        }
+        assert_eq!(super::sliding_entropy(&[None, None], 4), [0.0, 0.0]);
+    }
+    #[test]
+    fn ent() {
+        let all = (0..=255u8).collect::<Vec<u8>>();
+        assert!((entropy(&all) - 1.0).abs() < 0.001);
+        let none = vec![0u8; 256];
+        assert!(entropy(&none).abs() < 0.001);
+    }
+    #[test]
+    fn ilog2() {
+        for i in 0..usize::BITS {
+            assert_eq!(super::ilog2(1 << i), i as u8);
+        }
+        let mut log = 0;
+        for i in 2..1024 {
+            if i & (i - 1) == 0 {
+                log += 1;
+            }
+            assert_eq!(super::ilog2(i), log);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{fs::read_to_string, ops::Range, path::Path};
+
+use crate::{
+    file::Unit,
+    theme::{ColorDepth, ThemeColor},
+};
+
+/// A labeled range of addresses of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    /// the addresses in the whole file, not only in the compared bytes
+    pub range: Range<usize>,
+    pub label: String,
+    /// the background of the annotated bytes, or None for the one of the theme
+    pub color: Option<ThemeColor>,
+}
+
+/// The annotations of a file, read from a sidecar file with one annotation per line
+/// in the form `start,end,label[,color]`, where the addresses are hexadecimal
+/// (with or without `0x`) and the end is inclusive, so that `0x0,0x3f,header`
+/// labels the first 64 bytes. Labels containing commas can be quoted like in CSV,
+/// and empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Annotations {
+    /// sorted by the start of their ranges
+    list: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Reads the annotations from a file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = read_to_string(path).map_err(|e| e.to_string())?;
+        text.parse()
+    }
+    /// Converts the colors to ones the terminal can display
+    pub fn degrade(mut self, depth: ColorDepth) -> Self {
+        for annotation in &mut self.list {
+            annotation.color = annotation.color.map(|color| color.degrade(depth));
+        }
+        self
+    }
+    /// Stretches the ranges over all units of their bytes, for files whose bytes are split
+    pub fn split(mut self, unit: Unit) -> Self {
+        let per_byte = unit.per_byte();
+        for annotation in &mut self.list {
+            let range = &annotation.range;
+            annotation.range = range.start * per_byte..range.end * per_byte;
+        }
+        self
+    }
+    /// The innermost annotation containing the address, which is the shortest one
+    /// (or the one listed last of the shortest ones)
+    pub fn at(&self, addr: usize) -> Option<&Annotation> {
+        let starting_before = self.list.partition_point(|a| a.range.start <= addr);
+        self.list[..starting_before]
+            .iter()
+            .rev()
+            .filter(|a| a.range.end > addr)
+            .min_by_key(|a| a.range.len())
+    }
+}
+
+impl std::str::FromStr for Annotations {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut list = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| parse_line(line).map_err(|e| format!("line {}: {e}", i + 1)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // the sort is stable, so annotations with the same start stay in the order of the file
+        list.sort_by_key(|a| a.range.start);
+        Ok(Annotations { list })
+    }
+}
+
+/// Parses a line of the form `start,end,label[,color]`
+fn parse_line(line: &str) -> Result<Annotation, String> {
+    let fields = split_fields(line)?;
+    let (start, end, label, color) = match &fields[..] {
+        [start, end, label] => (start, end, label, None),
+        [start, end, label, color] => (start, end, label, Some(color)),
+        _ => return Err(String::from("expected start,end,label[,color]")),
+    };
+    let address = |field: &str| {
+        let hex = field.strip_prefix("0x").unwrap_or(field);
+        usize::from_str_radix(hex, 16).map_err(|_| format!("invalid address '{field}'"))
+    };
+    let (start, end) = (address(start)?, address(end)?);
+    if end < start {
+        return Err(format!("the end {end:#x} is before the start {start:#x}"));
+    }
+    let color = match color.filter(|color| !color.is_empty()) {
+        Some(color) => Some(color.parse()?),
+        None => None,
+    };
+    Ok(Annotation {
+        range: start..end.saturating_add(1),
+        label: label.clone(),
+        color,
+    })
+}
+
+/// Splits a line at its commas and trims the fields, where fields in double quotes
+/// can contain commas and `""` for a quote
+fn split_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut rest = line.trim_start();
+    loop {
+        let field = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut field = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) if quoted[i + 1..].starts_with('"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    Some((i, '"')) => break i + 1,
+                    Some((_, c)) => field.push(c),
+                    None => return Err(String::from("missing closing quote")),
+                }
+            };
+            rest = quoted[end..].trim_start();
+            if !rest.is_empty() && !rest.starts_with(',') {
+                return Err(String::from("unexpected text after a closing quote"));
+            }
+            field
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let field = rest[..end].trim().to_string();
+            rest = &rest[end..];
+            field
+        };
+        fields.push(field);
+        match rest.strip_prefix(',') {
+            Some(next) => rest = next.trim_start(),
+            None => return Ok(fields),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let annotations: Annotations = "# format header\n\
+            0x40, 0x1ff, \"key blob, \"\"encrypted\"\"\", darkcyan\n\
+            \n\
+            0,3f,header\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            annotations.list,
+            [
+                Annotation {
+                    range: 0..0x40,
+                    label: String::from("header"),
+                    color: None,
+                },
+                Annotation {
+                    range: 0x40..0x200,
+                    label: String::from("key blob, \"encrypted\""),
+                    color: Some(ThemeColor::Basic(6)),
+                },
+            ]
+        );
+        for (invalid, error) in [
+            ("0,10", "line 1: expected start,end,label[,color]"),
+            ("0,x,a", "line 1: invalid address 'x'"),
+            ("10,0,a", "line 1: the end 0x0 is before the start 0x10"),
+            ("0,1,\"a", "line 1: missing closing quote"),
+            (
+                "0,1,\"a\"b",
+                "line 1: unexpected text after a closing quote",
+            ),
+        ] {
+            assert_eq!(invalid.parse::<Annotations>(), Err(error.to_string()));
+        }
+        assert!("#\n0,1,a,nocolor".parse::<Annotations>().is_err());
+    }
+
+    #[test]
+    fn innermost() {
+        let annotations: Annotations =
+            "0,ff,file\n10,1f,header\n10,13,magic\n20,2f,same\n20,2f,later"
+                .parse()
+                .unwrap();
+        let label = |addr| annotations.at(addr).map(|a| a.label.as_str());
+        assert_eq!(label(0x0f), Some("file"));
+        assert_eq!(label(0x10), Some("magic"));
+        assert_eq!(label(0x14), Some("header"));
+        assert_eq!(label(0x20), Some("later"));
+        assert_eq!(label(0xff), Some("file"));
+        assert_eq!(label(0x100), None);
+        // split into bits, the ranges cover all bits of their bytes
+        let bits = annotations.clone().split(Unit::Bit);
+        let label = |addr| bits.at(addr).map(|a| a.label.as_str());
+        assert_eq!(label(0x9f), Some("magic"));
+        assert_eq!(label(0xa0), Some("header"));
+    }
+}
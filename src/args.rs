@@ -0,0 +1,438 @@
+use std::{ffi::OsString, ops::Range, path::Path, str::FromStr};
+
+use crate::{
+    align::{AlignMode, DEFAULT_BLOCKSIZE},
+    control::{Frontend, Presets},
+    file::{Unit, Window},
+    ignore::{parse_ranges, Ignore},
+    report::patch::PatchFormat,
+    script::Replay,
+    style::ColumnSetting,
+};
+
+/// Flags which take the next argument as their value, like `--flag value`
+const VALUE_FLAGS: &[&str] = &[
+    "--max-report",
+    "--elide",
+    "--label-a",
+    "--label-b",
+    "--offset-a",
+    "--offset-b",
+    "--algorithm",
+    "--gap-open",
+    "--gap-extend",
+    "--columns",
+    "--goto",
+    "--frontend",
+    "--decompress",
+    "--skip",
+    "--skip-a",
+    "--skip-b",
+    "--length",
+    "--length-a",
+    "--length-b",
+    "--annotations-a",
+    "--annotations-b",
+    "--ignore",
+    "--ignore-a",
+    "--ignore-b",
+    "--ignore-bits",
+    "--unit",
+    "--session",
+    "--jobs",
+    "--json-dir",
+    "--html",
+    "--collapse",
+    "--emit-patch",
+    "--patch-format",
+    "--apply-patch",
+    "--record",
+    "--replay",
+    "--replay-speed",
+];
+
+/// Flags which can only be given a value as `--flag=value`
+const EQUALS_FLAGS: &[&str] = &["--color", "--unified"];
+
+/// Flags without a value
+const SWITCHES: &[&str] = &[
+    "--no-scroll",
+    "--report",
+    "--json",
+    "--unified",
+    "--quiet",
+    "--start-aligned",
+    "--no-cache",
+    "--self",
+    "--batch",
+];
+
+/// The command line arguments, split into flags and positional arguments
+pub struct Args {
+    /// flags in the form `--flag` or `--flag=value`
+    flags: Vec<String>,
+    pub positional: Vec<OsString>,
+}
+
+impl Args {
+    /// Splits the arguments into flags and positional arguments,
+    /// joining flags given as `--flag value` into `--flag=value`
+    pub fn parse(args: impl Iterator<Item = OsString>) -> Self {
+        let mut flags = Vec::new();
+        let mut positional = Vec::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.to_str() {
+                Some(name) if VALUE_FLAGS.contains(&name) => {
+                    let value = args.next_if(|v| v.to_str().is_some());
+                    let value = value.as_ref().and_then(|v| v.to_str()).unwrap_or_default();
+                    flags.push(format!("{name}={value}"))
+                }
+                Some(flag) if is_flag(flag) => flags.push(flag.to_string()),
+                _ => positional.push(arg),
+            }
+        }
+        Args { flags, positional }
+    }
+    /// Whether the flag is given without a value
+    pub fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|x| x == flag)
+    }
+    /// Whether the flag is given, with or without a value
+    pub fn has_any(&self, flag: &str) -> bool {
+        self.has(flag) || self.value(flag).is_some()
+    }
+    /// The value of the last occurence of the flag
+    pub fn value(&self, flag: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find_map(|x| x.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')))
+    }
+    /// The value of the flag, parsed with `parse`
+    fn parse_value<T, E: ToString>(
+        &self,
+        flag: &str,
+        parse: impl FnOnce(&str) -> Result<T, E>,
+    ) -> Result<Option<T>, String> {
+        self.value(flag)
+            .map(|value| {
+                parse(value)
+                    .map_err(|e| format!("Invalid value '{value}' for {flag}: {}", e.to_string()))
+            })
+            .transpose()
+    }
+    /// The value of the flag as a number
+    pub fn number<T: FromStr>(&self, flag: &str) -> Result<Option<T>, String>
+    where
+        T::Err: ToString,
+    {
+        self.parse_value(flag, str::parse)
+    }
+    /// The value of the flag as a hexadecimal address, like in the goto dialog
+    fn address(&self, flag: &str) -> Result<Option<usize>, String> {
+        self.parse_value(flag, |value| {
+            usize::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16)
+        })
+    }
+    /// Whether compressed files should be decompressed, from `--decompress auto|off`
+    pub fn decompress(&self) -> Result<bool, String> {
+        let decompress = self.parse_value("--decompress", |value| match value {
+            "auto" => Ok(true),
+            "off" => Ok(false),
+            _ => Err("expected auto or off"),
+        })?;
+        Ok(decompress.unwrap_or(false))
+    }
+    /// The compared parts of both files, from `--skip-a`, `--length-a`, `--skip-b` and `--length-b`,
+    /// which default to `--skip` and `--length` for both files
+    pub fn windows(&self) -> Result<[Window; 2], String> {
+        let window = |suffix: &str| -> Result<Window, String> {
+            let value = |flag: &str| -> Result<Option<usize>, String> {
+                Ok(self
+                    .address(&format!("{flag}{suffix}"))?
+                    .or(self.address(flag)?))
+            };
+            Ok(Window {
+                skip: value("--skip")?.unwrap_or(0),
+                length: value("--length")?,
+            })
+        };
+        Ok([window("-a")?, window("-b")?])
+    }
+    /// What the bytes of the files are split into, from `--unit bytes|nibbles|bits`
+    pub fn unit(&self) -> Result<Unit, String> {
+        let unit = self.parse_value("--unit", |value| match value {
+            "bytes" => Ok(Unit::Byte),
+            "nibbles" => Ok(Unit::Nibble),
+            "bits" => Ok(Unit::Bit),
+            _ => Err("expected bytes, nibbles or bits"),
+        })?;
+        Ok(unit.unwrap_or_default())
+    }
+    /// The format of the patch of `--emit-patch`, from `--patch-format simple|vcdiff`
+    pub fn patch_format(&self) -> Result<PatchFormat, String> {
+        let format = self.parse_value("--patch-format", |value| match value {
+            "simple" => Ok(PatchFormat::Simple),
+            "vcdiff" => Ok(PatchFormat::Vcdiff),
+            _ => Err("expected simple or vcdiff"),
+        })?;
+        Ok(format.unwrap_or_default())
+    }
+    /// The actions to replay from the log of `--replay`, as many times faster as `--replay-speed`
+    pub fn replay(&self) -> Result<Option<Replay>, String> {
+        let speed = self.parse_value("--replay-speed", |value| match value.parse::<f64>() {
+            Ok(speed) if speed >= 0.0 => Ok(speed),
+            _ => Err("expected a factor like 2 or 0.5, or 0 for not waiting"),
+        })?;
+        self.value("--replay")
+            .map(|path| Replay::load(Path::new(path), speed.unwrap_or(1.0)))
+            .transpose()
+    }
+    /// The differences that are not counted, from the ranges of `--ignore-a` and `--ignore-b`
+    /// (which default to `--ignore`) and the bits of `--ignore-bits`.
+    /// The ranges are given in bytes, which cover all of their units if the bytes are split.
+    pub fn ignore(&self) -> Result<Ignore, String> {
+        let per_byte = self.unit()?.per_byte();
+        let ranges = |flag: &str| -> Result<Vec<Range<usize>>, String> {
+            Ok(self
+                .parse_value(flag, parse_ranges)?
+                .or(self.parse_value("--ignore", parse_ranges)?)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|range| range.start * per_byte..range.end * per_byte)
+                .collect())
+        };
+        let bits = self.parse_value("--ignore-bits", |value| {
+            u8::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16)
+        })?;
+        if bits.is_some() && per_byte > 1 {
+            return Err(String::from(
+                "--ignore-bits only works with whole bytes, not with --unit",
+            ));
+        }
+        Ok(Ignore::new(
+            [ranges("--ignore-a")?, ranges("--ignore-b")?],
+            bits.unwrap_or(0),
+        ))
+    }
+    /// The settings given on the command line which replace the configured ones
+    pub fn presets(&self) -> Result<Presets, String> {
+        let mode = self.parse_value("--algorithm", |value| match value {
+            "global" => Ok(AlignMode::Global),
+            "local" => Ok(AlignMode::Local),
+            "blockwise" => Ok(AlignMode::Blockwise(DEFAULT_BLOCKSIZE)),
+            _ => Err("expected global, local or blockwise"),
+        })?;
+        // penalties are given as positive numbers, but stored as negative scores
+        let penalty = |flag| -> Result<Option<i32>, String> {
+            Ok(self.number::<i32>(flag)?.map(|n| -n.abs()))
+        };
+        let columns = self.parse_value("--columns", |value| match value.parse() {
+            Ok(ColumnSetting::Fixed(0) | ColumnSetting::Multiple(0)) => {
+                Err("must not be zero".to_string())
+            }
+            Ok(columns) => Ok(columns),
+            Err(e) => Err(e.to_string()),
+        })?;
+        let frontend = self.parse_value("--frontend", |value| match value {
+            "crossterm" => Ok(Frontend::Crossterm),
+            "cursive" => Ok(Frontend::Cursive),
+            "auto" => Ok(Frontend::Auto),
+            _ => Err("expected crossterm, cursive or auto"),
+        })?;
+        Ok(Presets {
+            mode,
+            gap_open: penalty("--gap-open")?,
+            gap_extend: penalty("--gap-extend")?,
+            columns,
+            frontend,
+        })
+    }
+    /// The indexes of both files to start at, given the addresses of the compared bytes
+    /// in the files.
+    /// `--offset-a` and `--offset-b` set the addresses which are initially next to each other,
+    /// and `--goto` moves to an address of the first file, keeping the distance between them.
+    pub fn start_addresses(&self, windows: [Range<usize>; 2]) -> Result<[usize; 2], String> {
+        let in_bounds = |flag: &str, addr: usize, window: &Range<usize>| {
+            // an empty file can only be shown at its start
+            if window.contains(&addr) || addr == window.start {
+                Ok(addr - window.start)
+            } else {
+                Err(format!(
+                    "Address {addr:#x} of {flag} is not in bounds ({:#x}..{:#x})",
+                    window.start, window.end
+                ))
+            }
+        };
+        let offset = |flag, window| match self.address(flag)? {
+            Some(addr) => in_bounds(flag, addr, window),
+            None => Ok(0),
+        };
+        let (first, second) = (
+            offset("--offset-a", &windows[0])?,
+            offset("--offset-b", &windows[1])?,
+        );
+        let goto = match self.address("--goto")? {
+            Some(goto) => in_bounds("--goto", goto, &windows[0])?,
+            None => return Ok([first, second]),
+        };
+        match (goto + second).checked_sub(first) {
+            Some(goto_second) => Ok([goto, goto_second]),
+            None => Err(format!(
+                "--goto {goto:#x} would be before the start of the second file with the given offsets"
+            )),
+        }
+    }
+}
+
+/// Whether the argument is one of the flags (in the `--flag=value` form for ones with values)
+fn is_flag(arg: &str) -> bool {
+    match arg.split_once('=') {
+        Some((name, _)) => VALUE_FLAGS.contains(&name) || EQUALS_FLAGS.contains(&name),
+        None => SWITCHES.contains(&arg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn split_flags() {
+        let args = args(&[
+            "biodiff",
+            "--goto",
+            "0x10",
+            "a",
+            "--unified=2",
+            "--columns=8",
+            "--report",
+            "b",
+            "--quiet=1",
+        ]);
+        assert_eq!(args.positional, ["biodiff", "a", "b", "--quiet=1"]);
+        assert_eq!(args.value("--goto"), Some("0x10"));
+        assert_eq!(args.value("--unified"), Some("2"));
+        assert!(args.has("--report"));
+        assert!(args.has_any("--unified"));
+        assert!(!args.has("--unified"));
+        assert_eq!(args.number::<u8>("--columns"), Ok(Some(8)));
+        assert!(args.number::<u8>("--goto").is_err());
+        assert_eq!(args.number::<u8>("--elide"), Ok(None));
+    }
+
+    #[test]
+    fn presets() {
+        let presets = args(&[
+            "--algorithm",
+            "global",
+            "--gap-open",
+            "5",
+            "--gap-extend=-1",
+        ])
+        .presets()
+        .unwrap();
+        assert!(matches!(presets.mode, Some(AlignMode::Global)));
+        assert_eq!(presets.gap_open, Some(-5));
+        assert_eq!(presets.gap_extend, Some(-1));
+        assert!(presets.columns.is_none());
+        assert!(presets.frontend.is_none());
+        let frontend = args(&["--frontend", "cursive"]).presets().unwrap().frontend;
+        assert_eq!(frontend, Some(Frontend::Cursive));
+        let columns = args(&["--columns", "16"]).presets().unwrap().columns;
+        assert!(matches!(columns, Some(ColumnSetting::Fixed(16))));
+        for invalid in [
+            &["--algorithm", "wfa"][..],
+            &["--columns", "0"],
+            &["--columns", "many"],
+            &["--gap-open", "x"],
+            &["--frontend", "ncurses"],
+        ] {
+            assert!(args(invalid).presets().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn windows() {
+        let windows = args(&["--skip", "0x1000", "--length", "4000", "--skip-b=800"])
+            .windows()
+            .unwrap();
+        let window = |skip, length| Window { skip, length };
+        assert_eq!(
+            windows,
+            [window(0x1000, Some(0x4000)), window(0x800, Some(0x4000))]
+        );
+        assert_eq!(args(&[]).windows(), Ok([Window::default(); 2]));
+        assert!(args(&["--length-a", "x"]).windows().is_err());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn ignore() {
+        let ignore = args(&[
+            "--ignore",
+            "10-1f",
+            "--ignore-b=20-2f",
+            "--ignore-bits",
+            "0x0f",
+        ])
+        .ignore()
+        .unwrap();
+        assert_eq!(
+            ignore,
+            Ignore::new([vec![0x10..0x20], vec![0x20..0x30]], 0x0f)
+        );
+        assert_eq!(args(&[]).ignore(), Ok(Ignore::default()));
+        assert!(args(&["--ignore-bits", "100"]).ignore().is_err());
+        assert!(args(&["--ignore-a", "x"]).ignore().is_err());
+        // the ranges cover the bits of the bytes
+        assert_eq!(
+            args(&["--ignore", "1-2", "--unit", "bits"]).ignore(),
+            Ok(Ignore::new([vec![8..24], vec![8..24]], 0))
+        );
+        assert!(args(&["--ignore-bits", "1", "--unit=bits"])
+            .ignore()
+            .is_err());
+        assert!(args(&["--unit", "bytes"]).ignore().is_ok());
+        assert!(args(&["--unit", "words"]).unit().is_err());
+    }
+
+    #[test]
+    fn start_addresses() {
+        let lens = [0..0x5000, 0..0x5000];
+        assert_eq!(args(&[]).start_addresses(lens.clone()), Ok([0, 0]));
+        let offsets = ["--offset-a", "0x200", "--offset-b", "0"];
+        assert_eq!(args(&offsets).start_addresses(lens.clone()), Ok([0x200, 0]));
+        let goto = [&offsets[..], &["--goto", "4000"]].concat();
+        assert_eq!(
+            args(&goto).start_addresses(lens.clone()),
+            Ok([0x4000, 0x3e00])
+        );
+        // the second file would need to start before its beginning
+        let goto = [&offsets[..], &["--goto", "0x100"]].concat();
+        assert!(args(&goto).start_addresses(lens.clone()).is_err());
+        assert!(args(&["--goto", "0x5000"])
+            .start_addresses(lens.clone())
+            .is_err());
+        assert!(args(&["--offset-b", "0x6000"])
+            .start_addresses(lens.clone())
+            .is_err());
+        assert_eq!(
+            args(&["--goto", "0"]).start_addresses([0..0, 0..0]),
+            Ok([0, 0])
+        );
+        // addresses are the ones of the whole files
+        let windows = [0x1000..0x5000, 0x2000..0x3000];
+        let offsets = ["--offset-a", "0x1100", "--offset-b", "0x2000"];
+        assert_eq!(
+            args(&offsets).start_addresses(windows.clone()),
+            Ok([0x100, 0])
+        );
+        assert!(args(&["--goto", "0x800"]).start_addresses(windows).is_err());
+    }
+}
@@ -1,22 +1,52 @@
 use crossterm::{
     cursor,
-    event::{read, Event, KeyCode, KeyModifiers},
+    event::{
+        poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     style,
     style::Attribute,
     style::Color as CrossColor,
     terminal,
 };
-use crossterm::{execute, queue};
-use cursive::{reexports::enumset::EnumSet, theme, Printer};
+use crossterm::{execute, queue, Command};
+use cursive::{
+    event::{Event as CursiveEvent, Key as CursiveKey, MouseEvent as CursiveMouseEvent},
+    reexports::enumset::EnumSet,
+    theme, Printer,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::io::{Cursor, Write};
 use std::{
-    convert::{TryFrom, TryInto},
-    io::{Cursor, Write},
+    io::Stdout,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Once,
+    },
+    time::Duration,
 };
-use std::{io::Stdout, sync::mpsc::Sender};
 use unicode_width::UnicodeWidthStr;
 
+#[cfg(test)]
+pub mod capture;
+
+use crate::{
+    capabilities,
+    keymap::KeyMap,
+    prompt::PromptKey,
+    script::Script,
+    suspend,
+    theme::{Palette, ThemeColor},
+};
+
+/// How long no event is read before the views get an `Action::Tick`, which is rarely
+/// enough to not keep the CPU busy
+pub const TICK_INTERVAL: Duration = Duration::from_millis(250);
+
 /// A wrapper for events coming from crossterm
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Action {
     Up,
     Down,
@@ -28,10 +58,14 @@ pub enum Action {
     RightAlt,
     PgUp,
     PgDown,
+    HalfPgUp,
+    HalfPgDown,
     NextDifference,
     PrevDifference,
     NextInsertion,
     PrevInsertion,
+    /// go on to the next kind of differences the jumps stop at in a three-way comparison
+    ChangeFilter,
     Quit,
     Unalign,
     Align,
@@ -39,10 +73,23 @@ pub enum Action {
     Algorithm,
     Refresh,
     Goto,
+    FollowPointer,
     Search,
     SetOffset,
+    /// move both cursors together at their current offset
+    OffsetLock,
+    /// set the offset to where windows of the first file are found in the second one
+    AutoOffset,
     NextSearch,
     PrevSearch,
+    /// to the next byte group on the left, see `DoubleHexContext::word_move`
+    WordLeft,
+    /// to the next byte group on the right
+    WordRight,
+    /// the first byte of the row, which is on the right in right-to-left mode
+    RowStart,
+    /// the last byte of the row, which is on the left in right-to-left mode
+    RowEnd,
     Top,
     Bottom,
     CursorFirst,
@@ -51,106 +98,401 @@ pub enum Action {
     AddColumn,
     RemoveColumn,
     AutoColumn,
+    MultipleColumn,
     ResetColumn,
+    CycleGroup,
+    ToggleText,
+    ToggleSplit,
+    NextBase,
+    ByteClasses,
+    EntropyColors,
+    /// move the cursor while extending the selection
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectRows,
     StartSelection,
     ClearSelection,
+    DropSearch,
+    ClearSearches,
+    ExportSearch,
+    ExportHtml,
+    Hash,
+    ByteStats,
+    ReloadFiles,
+    Follow,
+    SwapFiles,
+    SetMark,
+    Marks,
+    FoldIdentical,
+    ToggleFold,
+    DiffMap,
+    JumpPercent,
+    DeltaMode,
+    RelativeAddresses,
+    JumpBack,
+    JumpForward,
+    Undo,
+    /// start overwriting the bytes at the cursor
+    Edit,
+    SaveAs,
+    /// overwrite the selected bytes with the same byte
+    Fill,
+    UndoEdit,
+    /// a character typed in edit mode
+    Type(char),
+    /// switch between typing hex digits and text in edit mode
+    SwitchInput,
+    StopEditing,
+    /// open the command line, in which commands like `set columns=16` are typed
+    Command,
+    /// a key pressed while the command line is open
+    Prompt(PromptKey),
+    /// stop the process until it is continued from the shell
+    Suspend,
+    /// place the cursor at the byte at the screen position (column, row)
+    SetCursorTo(usize, usize),
+    /// the count prefix typed so far, or None if it was cancelled
+    PendingCount(Option<usize>),
+    /// no event was read for `TICK_INTERVAL`, so the view turns its busy indicator and
+    /// hides a notice that was shown long enough, without using up the count prefix
+    Tick,
 }
 
-impl TryFrom<Event> for Action {
-    // unknown event for now unit
-    type Error = ();
+impl Action {
+    /// Whether the action compares or aligns the files, which is not possible
+    /// when only one file is shown
+    pub fn compares_files(&self) -> bool {
+        matches!(
+            self,
+            Action::NextDifference
+                | Action::PrevDifference
+                | Action::NextInsertion
+                | Action::PrevInsertion
+                | Action::ChangeFilter
+                | Action::Unalign
+                | Action::Align
+                | Action::SetOffset
+                | Action::OffsetLock
+                | Action::AutoOffset
+                | Action::CursorBoth
+                | Action::CursorSecond
+                | Action::SwapFiles
+                | Action::ToggleSplit
+                | Action::DeltaMode
+        )
+    }
+    /// Whether the action changes the style of the view, so that the state before it
+    /// is remembered for undoing it
+    pub fn can_be_undone(&self) -> bool {
+        matches!(
+            self,
+            Action::AddColumn
+                | Action::RemoveColumn
+                | Action::AutoColumn
+                | Action::MultipleColumn
+                | Action::ResetColumn
+                | Action::CycleGroup
+                | Action::NextBase
+                | Action::ByteClasses
+                | Action::EntropyColors
+                | Action::FoldIdentical
+                | Action::DiffMap
+                | Action::DeltaMode
+                | Action::ToggleSplit
+                | Action::ToggleText
+                | Action::RelativeAddresses
+        )
+    }
+    /// Whether the action moves by a step that the count prefix multiplies, so that
+    /// repeated ones that pile up can be done at once
+    pub fn is_step(&self) -> bool {
+        matches!(
+            self,
+            Action::Up
+                | Action::Down
+                | Action::Left
+                | Action::Right
+                | Action::UpAlt
+                | Action::DownAlt
+                | Action::LeftAlt
+                | Action::RightAlt
+                | Action::SelectUp
+                | Action::SelectDown
+                | Action::SelectLeft
+                | Action::SelectRight
+        )
+    }
+    /// Whether the action jumps away from the cursor, so that the position before it
+    /// is remembered for jumping back
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Action::NextDifference
+                | Action::PrevDifference
+                | Action::NextInsertion
+                | Action::PrevInsertion
+                | Action::NextSearch
+                | Action::PrevSearch
+                | Action::Top
+                | Action::Bottom
+                | Action::JumpPercent
+        )
+    }
+}
+
+/// Converts a crossterm event into an action, using the keymap for key events
+fn event_action(event: Event, keymap: &KeyMap) -> Option<Action> {
+    match event {
+        Event::Resize(_, _) => Some(Action::Refresh),
+        Event::Key(key) => keymap.action(key),
+        Event::Mouse(mouse) => match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => Some(Action::SetCursorTo(
+                mouse.column as usize,
+                mouse.row as usize,
+            )),
+            MouseEventKind::ScrollUp => Some(Action::UpAlt),
+            MouseEventKind::ScrollDown => Some(Action::DownAlt),
+            _ => None,
+        },
+        Event::FocusGained | Event::FocusLost | Event::Paste(_) => None,
+    }
+}
 
-    fn try_from(value: Event) -> Result<Self, Self::Error> {
-        Ok(
-            match match value {
-                Event::Resize(_, _) => return Ok(Action::Refresh),
-                // ignore modifiers for now
-                Event::Key(x) => (x.code, x.modifiers),
-                Event::Mouse(_) | Event::FocusGained | Event::FocusLost | Event::Paste(_) => {
-                    return Err(())
+/// The largest count prefix, which is plenty for moving around
+const MAX_COUNT: usize = 1 << 24;
+
+/// Translates events into actions while keeping track of a vim-style count
+/// prefix (like the 40 in `40j`) typed before an action.
+pub struct CountingReader<K> {
+    keymap: K,
+    count: Option<usize>,
+    /// whether the view is in edit mode, in which typed characters are not looked up
+    /// in the keymap
+    editing: Arc<AtomicBool>,
+    /// whether the command line of the view is open, which gets all keys
+    prompting: Arc<AtomicBool>,
+}
+
+impl<K: Borrow<KeyMap>> CountingReader<K> {
+    pub fn new(keymap: K, editing: Arc<AtomicBool>, prompting: Arc<AtomicBool>) -> Self {
+        CountingReader {
+            keymap,
+            count: None,
+            editing,
+            prompting,
+        }
+    }
+    /// Returns the action of an event, which is `Action::PendingCount` for changes
+    /// of the count prefix.
+    /// Any other action ends the count prefix, so the receiver should apply the pending count
+    /// to the next action it gets.
+    pub fn action(&mut self, event: Event) -> Option<Action> {
+        // a stop signal caught since the last event suspends instead of the event
+        if suspend::take_request() {
+            return Some(Action::Suspend);
+        }
+        let action = match self.prompting.load(Ordering::Relaxed) {
+            true => self.prompt_action(event),
+            false => self.action_or_edit(event),
+        };
+        match action {
+            // the view turns edit mode off again if it can not edit
+            Some(Action::Edit) => self.editing.store(true, Ordering::Relaxed),
+            // the following keys already go to the command line before the view opens it
+            Some(Action::Command) => self.prompting.store(true, Ordering::Relaxed),
+            _ => (),
+        }
+        action
+    }
+    /// Returns the keys for the command line, which is closed by Enter and Esc.
+    /// Other events like resizing still do their actions.
+    fn prompt_action(&mut self, event: Event) -> Option<Action> {
+        let key = match event {
+            Event::Key(key) => key,
+            otherwise => return event_action(otherwise, self.keymap.borrow()),
+        };
+        let key = match key.code {
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                PromptKey::Char(c)
+            }
+            KeyCode::Backspace => PromptKey::Backspace,
+            KeyCode::Delete => PromptKey::Delete,
+            KeyCode::Left => PromptKey::Left,
+            KeyCode::Right => PromptKey::Right,
+            KeyCode::Home => PromptKey::Home,
+            KeyCode::End => PromptKey::End,
+            KeyCode::Up => PromptKey::Up,
+            KeyCode::Down => PromptKey::Down,
+            KeyCode::Enter => PromptKey::Enter,
+            KeyCode::Esc => PromptKey::Esc,
+            _ => return None,
+        };
+        if matches!(key, PromptKey::Enter | PromptKey::Esc) {
+            self.prompting.store(false, Ordering::Relaxed);
+        }
+        Some(Action::Prompt(key))
+    }
+    /// Returns the typed characters in edit mode, and the action of the event otherwise
+    fn action_or_edit(&mut self, event: Event) -> Option<Action> {
+        if let (true, Event::Key(key)) = (self.editing.load(Ordering::Relaxed), &event) {
+            match key.code {
+                KeyCode::Esc => {
+                    self.editing.store(false, Ordering::Relaxed);
+                    return Some(Action::StopEditing);
                 }
-            } {
-                (KeyCode::Char(' ') | KeyCode::Down | KeyCode::Char('j'), m)
-                    if m.contains(KeyModifiers::CONTROL) =>
+                KeyCode::Tab => return Some(Action::SwitchInput),
+                KeyCode::Char(c)
+                    if !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
                 {
-                    Action::NextInsertion
+                    return Some(Action::Type(c))
                 }
-                (KeyCode::Char(' '), _) => Action::NextDifference,
-                (KeyCode::Down, m) if m.contains(KeyModifiers::SHIFT) => Action::NextDifference,
-                (KeyCode::Char('J'), _) => Action::NextDifference,
-                (KeyCode::Up | KeyCode::Char('k'), m) if m.contains(KeyModifiers::CONTROL) => {
-                    Action::PrevInsertion
+                _ => (),
+            }
+        }
+        let keymap = self.keymap.borrow();
+        let key = match event {
+            Event::Key(key) => key,
+            otherwise => {
+                let action = event_action(otherwise, keymap);
+                if action.is_some() {
+                    self.count = None;
                 }
-                (KeyCode::Up, m) if m.contains(KeyModifiers::SHIFT) => Action::PrevDifference,
-                (KeyCode::Char('K'), _) => Action::PrevDifference,
-                (KeyCode::Up, _) => Action::Up,
-                (KeyCode::Down, _) => Action::Down,
-                (KeyCode::Left, _) => Action::Left,
-                (KeyCode::Right, _) => Action::Right,
-                (KeyCode::PageDown, _) => Action::PgDown,
-                (KeyCode::PageUp, _) => Action::PgUp,
-                (KeyCode::Char('q'), _) => Action::Quit,
-                (KeyCode::Esc, _) => Action::Quit,
-                (KeyCode::Char('?'), _) => Action::Help,
-                (KeyCode::Char('r'), _) => Action::Refresh,
-                (KeyCode::Char('a'), _) => Action::CursorFirst,
-                (KeyCode::Char('s'), _) => Action::CursorBoth,
-                (KeyCode::Char('d'), _) => Action::CursorSecond,
-                (KeyCode::Char('h'), _) => Action::LeftAlt,
-                (KeyCode::Char('j'), _) => Action::DownAlt,
-                (KeyCode::Char('k'), _) => Action::UpAlt,
-                (KeyCode::Char('l'), _) => Action::RightAlt,
-                (KeyCode::Char('n'), _) => Action::NextSearch,
-                (KeyCode::Char('N'), _) => Action::PrevSearch,
-                (KeyCode::Char('o'), _) => Action::SetOffset,
-                (KeyCode::F(1), _) => Action::Help,
-                (KeyCode::Char('1'), _) => Action::Help,
-                (KeyCode::F(2), _) => Action::Unalign,
-                (KeyCode::Char('2'), _) => Action::Unalign,
-                (KeyCode::F(3), _) => Action::Align,
-                (KeyCode::Char('3'), _) => Action::Align,
-                (KeyCode::F(4), _) => Action::Algorithm,
-                (KeyCode::Char('4'), _) => Action::Algorithm,
-                (KeyCode::F(5), _) => Action::Refresh,
-                (KeyCode::Char('5'), _) => Action::Refresh,
-                (KeyCode::F(6), _) => Action::Goto,
-                (KeyCode::Char('6'), _) => Action::Goto,
-                (KeyCode::F(7), _) => Action::Search,
-                (KeyCode::Char('7'), _) => Action::Search,
-                (KeyCode::Home, _) => Action::Top,
-                (KeyCode::End, _) => Action::Bottom,
-                (KeyCode::Char(']'), _) => Action::AddColumn,
-                (KeyCode::Char('['), _) => Action::RemoveColumn,
-                (KeyCode::Char('='), _) => Action::AutoColumn,
-                (KeyCode::Char('0'), _) => Action::ResetColumn,
-                (KeyCode::Char('v'), _) => Action::StartSelection,
-                (KeyCode::Char('c'), _) => Action::ClearSelection,
-                _ => return Err(()),
-            },
-        )
+                return action;
+            }
+        };
+        let digit = match key.code {
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                c.to_digit(10)
+            }
+            _ => None,
+        };
+        match (digit, self.count) {
+            (Some(digit), Some(count)) => {
+                self.count = Some((count * 10 + digit as usize).min(MAX_COUNT));
+                return Some(Action::PendingCount(self.count));
+            }
+            // digits that are bound to something else (like '0') only
+            // count when they are not at the start of the count
+            (Some(digit), None) if digit != 0 && keymap.action(key).is_none() => {
+                self.count = Some(digit as usize);
+                return Some(Action::PendingCount(self.count));
+            }
+            _ => (),
+        }
+        let action = keymap.action(key);
+        match (self.count.take(), action) {
+            // escape only cancels the count prefix instead of quitting
+            (Some(_), _) if key.code == KeyCode::Esc => Some(Action::PendingCount(None)),
+            (Some(_), None) => Some(Action::PendingCount(None)),
+            (_, action) => action,
+        }
     }
 }
 
-/// Reads crossterm events and sends them into a sender that understands them
-pub fn send_cross_actions<F, A: From<Action>>(quit_predicate: F, sender: &mut Sender<A>)
-where
+/// Converts an event of cursive into the crossterm event it was read from,
+/// so that the cursive frontend can use the same keymap
+pub fn cross_event(event: &CursiveEvent) -> Option<Event> {
+    let key = |code, modifiers| Some(Event::Key(KeyEvent::new(code, modifiers)));
+    let (cursive_key, modifiers) = match *event {
+        CursiveEvent::Char(c) => return key(KeyCode::Char(c), KeyModifiers::NONE),
+        CursiveEvent::CtrlChar(c) => return key(KeyCode::Char(c), KeyModifiers::CONTROL),
+        CursiveEvent::AltChar(c) => return key(KeyCode::Char(c), KeyModifiers::ALT),
+        CursiveEvent::Key(k) => (k, KeyModifiers::NONE),
+        CursiveEvent::Shift(CursiveKey::Tab) => return key(KeyCode::BackTab, KeyModifiers::SHIFT),
+        CursiveEvent::Shift(k) => (k, KeyModifiers::SHIFT),
+        CursiveEvent::Alt(k) => (k, KeyModifiers::ALT),
+        CursiveEvent::AltShift(k) => (k, KeyModifiers::ALT | KeyModifiers::SHIFT),
+        CursiveEvent::Ctrl(k) => (k, KeyModifiers::CONTROL),
+        CursiveEvent::CtrlShift(k) => (k, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+        CursiveEvent::CtrlAlt(k) => (k, KeyModifiers::CONTROL | KeyModifiers::ALT),
+        CursiveEvent::Mouse {
+            position, event, ..
+        } => {
+            let kind = match event {
+                CursiveMouseEvent::Press(cursive::event::MouseButton::Left) => {
+                    MouseEventKind::Down(MouseButton::Left)
+                }
+                CursiveMouseEvent::WheelUp => MouseEventKind::ScrollUp,
+                CursiveMouseEvent::WheelDown => MouseEventKind::ScrollDown,
+                _ => return None,
+            };
+            return Some(Event::Mouse(MouseEvent {
+                kind,
+                column: position.x.try_into().ok()?,
+                row: position.y.try_into().ok()?,
+                modifiers: KeyModifiers::NONE,
+            }));
+        }
+        // cursive lays out the views itself on resizes
+        _ => return None,
+    };
+    let code = match cursive_key {
+        CursiveKey::Enter => KeyCode::Enter,
+        CursiveKey::Tab => KeyCode::Tab,
+        CursiveKey::Backspace => KeyCode::Backspace,
+        CursiveKey::Esc => KeyCode::Esc,
+        CursiveKey::Left => KeyCode::Left,
+        CursiveKey::Right => KeyCode::Right,
+        CursiveKey::Up => KeyCode::Up,
+        CursiveKey::Down => KeyCode::Down,
+        CursiveKey::Ins => KeyCode::Insert,
+        CursiveKey::Del => KeyCode::Delete,
+        CursiveKey::Home => KeyCode::Home,
+        CursiveKey::End => KeyCode::End,
+        CursiveKey::PageUp => KeyCode::PageUp,
+        CursiveKey::PageDown => KeyCode::PageDown,
+        function_key => (1..=12)
+            .find(|&n| CursiveKey::from_f(n) == function_key)
+            .map(KeyCode::F)?,
+    };
+    key(code, modifiers)
+}
+
+/// Reads crossterm events and sends them into a sender that understands them,
+/// or the actions of the script while it replays them, and records the sent actions.
+/// `editing` is the edit mode of the view and `prompting` whether its command line is open,
+/// see `CountingReader`.
+pub fn send_cross_actions<F, A: From<Action>>(
+    quit_predicate: F,
+    keymap: &KeyMap,
+    editing: Arc<AtomicBool>,
+    prompting: Arc<AtomicBool>,
+    sender: &mut Sender<A>,
+    script: &mut Script,
+) where
     F: Fn(Action) -> bool,
 {
+    let mut reader = CountingReader::new(keymap, editing, prompting);
     loop {
-        match read()
-            .unwrap_or_else(quit_with_error("Could not get key event"))
-            .try_into()
-            .map(|action| sender.send(A::from(action)).map(|()| action))
-        {
-            Ok(Ok(action)) => {
-                if quit_predicate(action) {
-                    return;
-                }
+        let action = script.replayed().or_else(|| {
+            // reading blocks until the next event, so the views are ticked while waiting
+            if !poll(TICK_INTERVAL).unwrap_or_else(quit_with_error("Could not get key event")) {
+                return Some(Action::Tick);
             }
+            let event = read().unwrap_or_else(quit_with_error("Could not get key event"));
+            reader.action(event)
+        });
+        if let Some(action) = action {
+            script.record(action);
+        }
+        match action.map(|action| sender.send(A::from(action)).map(|()| action)) {
+            Some(Ok(action)) if quit_predicate(action) => return,
             // quit when other end has disconnected
-            Ok(Err(_)) => return,
+            Some(Err(_)) => return,
             // drop unknown event
-            Err(()) => (),
+            Some(Ok(_)) | None => (),
         }
     }
 }
@@ -166,12 +508,15 @@ pub trait Backend {
     fn can_scroll(&self) -> bool;
     /// scrolls amount (positive moves content of terminal up)
     fn scroll(&mut self, amount: isize);
-    /// refreshes the screen after content has been queued
-    fn refresh(&mut self);
+    /// refreshes the screen after content has been queued,
+    /// returning any error that happened since the last refresh
+    fn refresh(&mut self) -> std::io::Result<()>;
     /// gets the dimensions of the display
     fn size(&mut self) -> (usize, usize);
     /// clears display
     fn clear(&mut self);
+    /// sets the title of the terminal window
+    fn set_title(&mut self, title: &str);
 }
 
 /// Wrapper for crossterm and cursive colors
@@ -184,33 +529,58 @@ pub enum Color {
     HexDiffSecondary,
     HexOneside,
     HexOnesideSecondary,
+    /// the classes of bytes when coloring by class
+    ByteNull,
+    BytePrintable,
+    ByteControl,
+    ByteHighBit,
+    ByteFf,
+    /// the level of the entropy when coloring by entropy
+    Entropy(u8),
+    /// gaps in the alignment
+    Gap,
+    /// differences that are ignored
+    Ignored,
+    /// bytes that were overwritten in edit mode
+    Edited,
+    /// differences of a three-way comparison that only ours, only theirs or both changed
+    Ours,
+    Theirs,
+    Conflict,
 }
 
 impl Color {
-    /// Converts to a crossterm color
-    fn to_cross(self) -> CrossColor {
+    /// Returns the color of the palette
+    pub fn theme_color(self, palette: &Palette) -> ThemeColor {
         match self {
-            Color::Unimportant => CrossColor::DarkGrey,
-            Color::HexSame => CrossColor::White,
-            Color::HexDiff => CrossColor::Red,
-            Color::HexOneside => CrossColor::Green,
-            Color::HexSameSecondary => CrossColor::Yellow,
-            Color::HexDiffSecondary => CrossColor::DarkRed,
-            Color::HexOnesideSecondary => CrossColor::DarkGreen,
-        }
-    }
-    /// Converts to a cursive color (with black background)
-    fn to_cursiv(self, bg: BackgroundColor) -> theme::ColorStyle {
-        let col = match self {
-            Color::Unimportant => theme::Color::Light(theme::BaseColor::Black),
-            Color::HexSame => theme::Color::Light(theme::BaseColor::White),
-            Color::HexDiff => theme::Color::Light(theme::BaseColor::Red),
-            Color::HexOneside => theme::Color::Light(theme::BaseColor::Green),
-            Color::HexSameSecondary => theme::Color::Light(theme::BaseColor::Yellow),
-            Color::HexDiffSecondary => theme::Color::Dark(theme::BaseColor::Red),
-            Color::HexOnesideSecondary => theme::Color::Dark(theme::BaseColor::Green),
-        };
-        theme::ColorStyle::new(col, bg.to_cursiv())
+            Color::Unimportant => palette.unimportant,
+            Color::HexSame => palette.same,
+            Color::HexDiff => palette.diff,
+            Color::HexOneside => palette.oneside,
+            Color::HexSameSecondary => palette.same_secondary,
+            Color::HexDiffSecondary => palette.diff_secondary,
+            Color::HexOnesideSecondary => palette.oneside_secondary,
+            Color::ByteNull => palette.null,
+            Color::BytePrintable => palette.printable,
+            Color::ByteControl => palette.control,
+            Color::ByteHighBit => palette.high_bit,
+            Color::ByteFf => palette.ff,
+            Color::Entropy(level) => palette.entropy[level as usize % 8],
+            Color::Gap => palette.gap,
+            Color::Ignored => palette.ignored,
+            Color::Edited => palette.edited,
+            Color::Ours => palette.ours,
+            Color::Theirs => palette.theirs,
+            Color::Conflict => palette.conflict,
+        }
+    }
+    /// Converts to a crossterm color
+    fn to_cross(self, palette: &Palette) -> CrossColor {
+        self.theme_color(palette).to_cross()
+    }
+    /// Converts to a cursive color (with the given background)
+    fn to_cursiv(self, bg: BackgroundColor, palette: &Palette) -> theme::ColorStyle {
+        theme::ColorStyle::new(self.theme_color(palette).to_cursiv(), bg.to_cursiv(palette))
     }
 }
 
@@ -218,20 +588,33 @@ impl Color {
 pub enum BackgroundColor {
     Blank,
     Highlight,
+    /// results of additional search queries, by their slot
+    Search(u8),
+    /// the search result the cursor is in
+    CurrentResult,
+    /// rows with differences, if they are tinted
+    DiffRow,
+    /// annotated bytes, in the color of their annotation
+    Annotation(ThemeColor),
 }
 
 impl BackgroundColor {
-    fn to_cross(self) -> CrossColor {
+    /// Returns the color of the palette
+    pub fn theme_color(self, palette: &Palette) -> ThemeColor {
         match self {
-            BackgroundColor::Blank => CrossColor::Black,
-            BackgroundColor::Highlight => CrossColor::DarkGrey,
+            BackgroundColor::Blank => palette.background,
+            BackgroundColor::Highlight => palette.highlight,
+            BackgroundColor::CurrentResult => palette.current_result,
+            BackgroundColor::DiffRow => palette.diff_row,
+            BackgroundColor::Search(slot) => palette.search[slot as usize % 3],
+            BackgroundColor::Annotation(color) => color,
         }
     }
-    fn to_cursiv(self) -> theme::Color {
-        match self {
-            BackgroundColor::Blank => theme::Color::Dark(theme::BaseColor::Black),
-            BackgroundColor::Highlight => theme::Color::Light(theme::BaseColor::Black),
-        }
+    fn to_cross(self, palette: &Palette) -> CrossColor {
+        self.theme_color(palette).to_cross()
+    }
+    fn to_cursiv(self, palette: &Palette) -> theme::Color {
+        self.theme_color(palette).to_cursiv()
     }
 }
 
@@ -283,176 +666,317 @@ pub struct Cross {
     prev_color: Option<CrossColor>,
     prev_bg: Option<CrossColor>,
     prev_effect: Option<style::Attributes>,
+    palette: Palette,
+    /// no colors are written at all, only attributes
+    monochrome: bool,
+    /// the first error since the last refresh
+    error: Option<std::io::Error>,
+    /// the last known size of the terminal
+    size: (usize, usize),
+    /// whether frames are wrapped in synchronized update sequences to avoid flicker
+    synchronized: bool,
+    /// whether the terminal can scroll its content
+    scroll: bool,
+    /// the size the views are drawn in instead of the one of the terminal, see `fix_size`
+    fixed_size: Option<(usize, usize)>,
 }
 
 impl Cross {
     /// Private API for creating a new object and not yet initializing the terminal
-    fn new_uninit() -> Self {
+    fn new_uninit(palette: Palette, monochrome: bool) -> Self {
         Cross {
+            palette,
             stdout: std::io::stdout(),
             buffer: Cursor::new(Vec::new()),
             prev_color: None,
             prev_bg: None,
             prev_effect: None,
+            monochrome,
+            error: None,
+            size: FALLBACK_SIZE,
+            synchronized: false,
+            scroll: true,
+            fixed_size: None,
         }
     }
     /// init the crossterm backend, places the screen into raw mode and the alternative buffer
     /// and hides the cursor etc.
-    pub fn init() -> Self {
-        let mut ret = Self::new_uninit();
+    /// Scrolling is only used if `scroll` is true.
+    pub fn init(palette: Palette, monochrome: bool, scroll: bool) -> Self {
+        let mut ret = Self::new_uninit(palette, monochrome);
+        ret.scroll = scroll;
+        install_panic_hook();
+        // set before the first change, so that failures in between also restore the terminal
+        TERMINAL_ACTIVE.store(true, Ordering::SeqCst);
         execute!(ret.stdout, terminal::EnterAlternateScreen,)
             .unwrap_or_else(quit_with_error("Could not get terminal size"));
         terminal::enable_raw_mode().unwrap_or_else(quit_with_error("Could not enable raw mode"));
+        // the title is restored from here on exit
+        let _ = execute!(ret.stdout, PushTitle);
+        // the terminal can only be asked in raw mode
+        ret.synchronized = capabilities::synchronized_output();
         execute!(
             ret.stdout,
             style::ResetColor,
             style::SetAttribute(style::Attribute::Reset),
-            style::SetBackgroundColor(CrossColor::Black),
+        )
+        .unwrap_or_else(quit_with_error("Could not initialize crossterm"));
+        if !monochrome {
+            execute!(
+                ret.stdout,
+                style::SetBackgroundColor(BackgroundColor::Blank.to_cross(&palette))
+            )
+            .unwrap_or_else(quit_with_error("Could not initialize crossterm"));
+        }
+        execute!(
+            ret.stdout,
             terminal::Clear(terminal::ClearType::All),
             terminal::DisableLineWrap,
             cursor::MoveTo(0, 0),
             cursor::Hide,
+            EnableMouseCapture,
         )
         .unwrap_or_else(quit_with_error("Could not initialize crossterm"));
         ret
     }
+    /// Makes `size` return the size instead of the one of the terminal, like when replaying
+    /// actions that were recorded in a terminal of that size
+    pub fn fix_size(&mut self, size: (usize, usize)) {
+        self.fixed_size = Some(size);
+    }
     /// uninitializes everything we initialized and goes back to the normal screen
-    pub fn uninit(mut self) {
-        let _ = execute!(
-            self.stdout,
-            style::ResetColor,
-            terminal::EnableLineWrap,
-            cursor::Show,
-            terminal::LeaveAlternateScreen,
-        );
-        let _ = terminal::disable_raw_mode();
+    pub fn uninit(self) {
+        restore_terminal();
+    }
+}
+
+/// Whether the terminal is currently set up by `Cross::init` and needs to be restored
+static TERMINAL_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal back into its normal state, unless that has already been done
+/// (both `Cross::uninit` and the panic hook end up here)
+fn restore_terminal() {
+    if !TERMINAL_ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let _ = execute!(
+        std::io::stdout(),
+        DisableMouseCapture,
+        style::ResetColor,
+        terminal::EnableLineWrap,
+        cursor::Show,
+        // terminals without a title stack at least do not keep our title
+        terminal::SetTitle(""),
+        PopTitle,
+        terminal::LeaveAlternateScreen,
+    );
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Saves the window title on the title stack of the terminal (ignored if there is none)
+struct PushTitle;
+
+impl Command for PushTitle {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        f.write_str("\x1b[22;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        Ok(())
     }
 }
 
+/// Restores the window title saved by `PushTitle`
+struct PopTitle;
+
+impl Command for PopTitle {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        f.write_str("\x1b[23;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        Ok(())
+    }
+}
+
+/// Installs a panic hook that restores the terminal before the previous hook
+/// prints the panic message, as it would not be readable otherwise.
+/// The hook stays installed, but only does anything while the terminal is active.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info)
+        }));
+    });
+}
+
 /// Convenience function for quitting and uninitializing the terminal before it
 pub fn quit_with_error<E: std::error::Error, Out>(premsg: &'static str) -> impl Fn(E) -> Out {
     move |err| {
-        let tmp = Cross::new_uninit();
-        tmp.uninit();
+        restore_terminal();
         eprintln!("{premsg}: {err}");
         std::process::exit(1)
     }
 }
 
+/// The size assumed when the terminal size could not be determined yet
+const FALLBACK_SIZE: (usize, usize) = (80, 24);
+
+/// Converts a screen coordinate to the type crossterm uses
+fn coordinate(x: usize, what: &str) -> std::io::Result<u16> {
+    u16::try_from(x).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{what} {x} out of range"),
+        )
+    })
+}
+
+impl Cross {
+    /// Writes out a frame to the terminal, as one synchronized update if supported
+    fn write_frame(&mut self, frame: &[u8]) -> std::io::Result<()> {
+        if self.synchronized {
+            self.stdout
+                .write_all(capabilities::BEGIN_SYNCHRONIZED_UPDATE)?;
+        }
+        self.stdout.write_all(frame)?;
+        if self.synchronized {
+            self.stdout
+                .write_all(capabilities::END_SYNCHRONIZED_UPDATE)?;
+        }
+        self.stdout.flush()
+    }
+    /// Remembers the first error until the next refresh
+    fn record<T>(&mut self, result: std::io::Result<T>) -> Option<T> {
+        match result {
+            Ok(x) => Some(x),
+            Err(e) => {
+                self.error.get_or_insert(e);
+                None
+            }
+        }
+    }
+    /// sets the background color, unless in monochrome mode
+    fn set_background(&mut self, color: CrossColor) {
+        if self.monochrome {
+            return;
+        }
+        let result = queue!(self.buffer, style::SetBackgroundColor(color));
+        self.record(result);
+    }
+}
+
+/// Errors while drawing are not reported right away, but returned by the next
+/// `refresh`, so that drawing code does not need to check every single call.
 impl Backend for Cross {
     fn set_line(&mut self, line: usize) {
-        queue!(
-            self.buffer,
-            cursor::MoveTo(
-                0,
-                u16::try_from(line).unwrap_or_else(quit_with_error("line out of range"))
-            ),
-            // i haven't check whether this is actually needed
-            style::SetBackgroundColor(CrossColor::Black),
-        )
-        .unwrap_or_else(quit_with_error("Could not move cursor"));
+        let result =
+            coordinate(line, "line").and_then(|line| queue!(self.buffer, cursor::MoveTo(0, line)));
+        self.record(result);
+        // i haven't check whether this is actually needed
+        self.set_background(BackgroundColor::Blank.to_cross(&self.palette));
     }
 
     fn set_pos(&mut self, column: usize, line: usize) {
-        queue!(
-            self.buffer,
-            cursor::MoveTo(
-                u16::try_from(column).unwrap_or_else(quit_with_error("column out of range")),
-                u16::try_from(line).unwrap_or_else(quit_with_error("line out of range"))
-            ),
-        )
-        .unwrap_or_else(quit_with_error("Could not move cursor"));
+        let result = match (coordinate(column, "column"), coordinate(line, "line")) {
+            (Ok(column), Ok(line)) => queue!(self.buffer, cursor::MoveTo(column, line)),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        self.record(result);
     }
 
     fn append_text(&mut self, text: &str, color: Color, bg: BackgroundColor, effect: Effect) {
         let attribute = effect.to_cross();
         // try to optimize by not printing the color if it hasn't changed
         if Some(attribute) != self.prev_effect {
-            queue!(
+            let result = queue!(
                 self.buffer,
                 style::SetAttribute(Attribute::Reset),
                 style::SetAttributes(attribute),
-                style::SetBackgroundColor(bg.to_cross())
-            )
-            .unwrap_or_else(quit_with_error("Could not write out text"));
+            );
+            self.record(result);
             self.prev_effect = Some(attribute);
             // because the attribute is Reset, then we also need to set the color again
             self.prev_color = None;
             self.prev_bg = None;
         }
-        let cross_color = color.to_cross();
-        if Some(cross_color) != self.prev_color {
-            queue!(self.buffer, style::SetForegroundColor(cross_color),)
-                .unwrap_or_else(quit_with_error("Could not write out text"));
+        let cross_color = color.to_cross(&self.palette);
+        if !self.monochrome && Some(cross_color) != self.prev_color {
+            let result = queue!(self.buffer, style::SetForegroundColor(cross_color));
+            self.record(result);
             self.prev_color = Some(cross_color);
         }
-        let bg_color = bg.to_cross();
+        let bg_color = bg.to_cross(&self.palette);
         if Some(bg_color) != self.prev_bg {
-            queue!(self.buffer, style::SetBackgroundColor(bg_color),)
-                .unwrap_or_else(quit_with_error("Could not write out text"));
+            self.set_background(bg_color);
             self.prev_bg = Some(bg_color);
         }
-        queue!(self.buffer, style::Print(text))
-            .unwrap_or_else(quit_with_error("Could not write out text"));
+        let result = queue!(self.buffer, style::Print(text));
+        self.record(result);
     }
 
     fn can_scroll(&self) -> bool {
-        // this doesn't work on linux's native terminal and i would like to know
-        // how to feature detect this (also, i'm pretty sure there are some other
-        // scroll sequences that work there?) but for now just pretend it works
-        true
+        self.scroll
     }
 
     fn scroll(&mut self, amount: isize) {
-        match amount {
-            isize::MIN..=-1 => {
-                queue!(
-                    self.buffer,
-                    terminal::ScrollDown(
-                        u16::try_from(-amount)
-                            .unwrap_or_else(quit_with_error("scroll out of range"))
-                    )
-                )
-                .unwrap_or_else(quit_with_error("Could not scroll"));
-            }
-            1..=isize::MAX => {
-                queue!(
-                    self.buffer,
-                    terminal::ScrollUp(
-                        u16::try_from(amount)
-                            .unwrap_or_else(quit_with_error("scroll out of range"))
-                    )
-                )
-                .unwrap_or_else(quit_with_error("Could not scroll"));
-            }
-            _ => (),
-        }
+        let result = match amount {
+            isize::MIN..=-1 => coordinate(amount.unsigned_abs(), "scroll amount")
+                .and_then(|amount| queue!(self.buffer, terminal::ScrollDown(amount))),
+            1..=isize::MAX => coordinate(amount as usize, "scroll amount")
+                .and_then(|amount| queue!(self.buffer, terminal::ScrollUp(amount))),
+            _ => Ok(()),
+        };
+        self.record(result);
     }
 
-    fn refresh(&mut self) {
-        let _ = self.buffer.flush();
-        let mut buffer = Cursor::new(Vec::new());
-        std::mem::swap(&mut buffer, &mut self.buffer);
-        self.stdout
-            .write_all(&buffer.into_inner())
-            .unwrap_or_else(quit_with_error("Could not write to stdout"));
-        let _ = self.stdout.flush();
+    fn refresh(&mut self) -> std::io::Result<()> {
+        let buffer = std::mem::take(self.buffer.get_mut());
+        self.buffer.set_position(0);
+        let result = match self.error.take() {
+            Some(e) => Err(e),
+            None => self.write_frame(&buffer),
+        };
+        if result.is_err() {
+            // we do not know what made it to the terminal, so everything has to be set again
+            self.prev_color = None;
+            self.prev_bg = None;
+            self.prev_effect = None;
+        }
+        result
     }
 
     fn size(&mut self) -> (usize, usize) {
-        let (a, b) =
-            terminal::size().unwrap_or_else(quit_with_error("Could not get terminal size"));
-        (usize::from(a), usize::from(b))
+        if let Some(size) = self.fixed_size {
+            return size;
+        }
+        let result = terminal::size();
+        if let Some((a, b)) = self.record(result) {
+            self.size = (usize::from(a), usize::from(b));
+        }
+        self.size
     }
 
     fn clear(&mut self) {
         self.prev_effect = Some(Attribute::NoReverse.into());
-        queue!(
+        let result = queue!(
             self.buffer,
             style::SetAttribute(Attribute::NoReverse),
             terminal::Clear(terminal::ClearType::All),
-        )
-        .unwrap_or_else(quit_with_error("Could not clear screen"))
+        );
+        self.record(result);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        // control characters in file names could end the sequence early
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        let result = queue!(self.buffer, terminal::SetTitle(title));
+        self.record(result);
     }
 }
 
@@ -461,13 +985,15 @@ impl Backend for Cross {
 pub struct Cursiv<'a, 'b, 'c> {
     current_pos: (usize, usize),
     printer: &'c Printer<'a, 'b>,
+    palette: Palette,
 }
 
 impl<'a, 'b, 'c> Cursiv<'a, 'b, 'c> {
-    pub fn from_printer(printer: &'c Printer<'a, 'b>) -> Self {
+    pub fn from_printer(printer: &'c Printer<'a, 'b>, palette: Palette) -> Self {
         Cursiv {
             current_pos: (0, 0),
             printer,
+            palette,
         }
     }
 }
@@ -483,7 +1009,7 @@ impl<'a, 'b, 'c> Backend for Cursiv<'a, 'b, 'c> {
 
     fn append_text(&mut self, text: &str, color: Color, bg: BackgroundColor, effects: Effect) {
         let len = text.width();
-        let mut style = theme::Style::none().combine(color.to_cursiv(bg));
+        let mut style = theme::Style::none().combine(color.to_cursiv(bg, &self.palette));
         for effect in effects.to_cursiv() {
             style = style.combine(effect)
         }
@@ -500,7 +1026,9 @@ impl<'a, 'b, 'c> Backend for Cursiv<'a, 'b, 'c> {
     fn scroll(&mut self, _amount: isize) {}
 
     // this is not necessary since cursive itself does the refreshing
-    fn refresh(&mut self) {}
+    fn refresh(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 
     fn size(&mut self) -> (usize, usize) {
         (self.printer.size.x, self.printer.size.y)
@@ -509,8 +1037,12 @@ impl<'a, 'b, 'c> Backend for Cursiv<'a, 'b, 'c> {
     fn clear(&mut self) {
         self.printer.clear()
     }
+
+    fn set_title(&mut self, _: &str) {}
 }
 
+/// A backend that does not draw anything (and can therefore not fail),
+/// used for updating views that are not visible
 pub struct Dummy;
 
 impl Backend for Dummy {
@@ -526,11 +1058,263 @@ impl Backend for Dummy {
 
     fn scroll(&mut self, _: isize) {}
 
-    fn refresh(&mut self) {}
+    fn refresh(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 
     fn size(&mut self) -> (usize, usize) {
         (0, 0)
     }
 
     fn clear(&mut self) {}
+
+    fn set_title(&mut self, _: &str) {}
+}
+
+/// A backend that collects the text of each line without colors or effects,
+/// used for output that is not going to a terminal.
+/// Columns are ignored, text is always appended to the end of the current line.
+#[derive(Default)]
+pub struct PlainText {
+    lines: Vec<String>,
+    line: usize,
+}
+
+impl PlainText {
+    /// The text of the lines, without trailing spaces
+    pub fn into_lines(self) -> Vec<String> {
+        self.lines
+            .into_iter()
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+}
+
+impl Backend for PlainText {
+    fn set_line(&mut self, line: usize) {
+        if self.lines.len() <= line {
+            self.lines.resize(line + 1, String::new());
+        }
+        self.line = line;
+    }
+
+    fn set_pos(&mut self, _: usize, line: usize) {
+        self.set_line(line)
+    }
+
+    fn append_text(&mut self, text: &str, _: Color, _: BackgroundColor, _: Effect) {
+        self.set_line(self.line);
+        self.lines[self.line].push_str(text)
+    }
+
+    fn can_scroll(&self) -> bool {
+        false
+    }
+
+    fn scroll(&mut self, _: isize) {}
+
+    fn refresh(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&mut self) -> (usize, usize) {
+        (usize::MAX, self.lines.len())
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear()
+    }
+
+    fn set_title(&mut self, _: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cursor::CursorActive,
+        doublehex::{CursorRow, DoubleHexContext, DoubleHexLine},
+        style::ByteData,
+    };
+    /// renders a line with a difference and a one-sided byte, a search result,
+    /// the cursor and the bottom line
+    fn render(monochrome: bool) -> String {
+        let mut dh = DoubleHexContext::new((3, 3));
+        dh.style.monochrome = monochrome;
+        dh.style.ascii_col = true;
+        let byte = |byte, slot| ByteData {
+            byte,
+            search_slot: slot,
+            ..ByteData::default()
+        };
+        let line = DoubleHexLine {
+            address: [Some(0), Some(0)],
+            bytes: vec![
+                (byte(Some(1), Some(1)), byte(Some(1), None)),
+                (byte(Some(2), None), byte(Some(3), None)),
+                (byte(Some(4), None), byte(None, None)),
+            ],
+            fold: None,
+        };
+        let mut cross = Cross::new_uninit(Palette::default(), monochrome);
+        dh.print_doublehex_screen(&[line.clone(), line], &mut cross);
+        dh.set_doublehex_cursor(
+            &mut cross,
+            CursorActive::Both,
+            (byte(Some(1), Some(1)), byte(Some(1), None)),
+            CursorRow {
+                text: ['.', '.'],
+                is_different: true,
+                second_column: None,
+            },
+        );
+        dh.print_bottom_line(
+            &mut cross,
+            [Some(0), Some(0)],
+            None,
+            [3, 3],
+            [None, None],
+            Some("status"),
+        );
+        String::from_utf8(cross.buffer.into_inner()).unwrap()
+    }
+    /// returns the parameters of all SGR sequences
+    fn sgr_parameters(output: &str) -> Vec<u32> {
+        output
+            .split("\x1b[")
+            .skip(1)
+            .filter_map(|seq| {
+                let end = seq.find(|c: char| !c.is_ascii_digit() && c != ';')?;
+                (seq[end..].starts_with('m')).then(|| &seq[..end])
+            })
+            .flat_map(|params| params.split(';').filter_map(|p| p.parse().ok()))
+            .collect()
+    }
+    fn is_color(param: &u32) -> bool {
+        matches!(param, 30..=49 | 90..=107)
+    }
+    #[test]
+    fn monochrome_output() {
+        let colored = render(false);
+        assert!(sgr_parameters(&colored).iter().any(is_color));
+        assert!(!colored.contains('*'));
+        let monochrome = render(true);
+        let params = sgr_parameters(&monochrome);
+        assert!(!params.iter().any(is_color), "{monochrome:?}");
+        // differences are shown in reverse video and marked in front of the row
+        assert!(params.contains(&7));
+        assert!(monochrome.contains('*'));
+    }
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+    #[test]
+    fn count_prefix() {
+        let keymap = KeyMap::default();
+        let mut reader = CountingReader::new(&keymap, Arc::default(), Arc::default());
+        let mut count = |keys: &str| keys.chars().map(|c| reader.action(key(c))).last().flatten();
+        assert!(matches!(count("4"), Some(Action::PendingCount(Some(4)))));
+        assert!(matches!(count("0"), Some(Action::PendingCount(Some(40)))));
+        assert!(matches!(count("j"), Some(Action::DownAlt)));
+        // the count has been used up
+        assert!(matches!(count("0"), Some(Action::ResetColumn)));
+        assert!(matches!(count("2"), Some(Action::PendingCount(Some(2)))));
+        assert!(matches!(count("g"), Some(Action::PendingCount(None))));
+        assert!(matches!(
+            count("9999999999"),
+            Some(Action::PendingCount(Some(MAX_COUNT)))
+        ));
+        let esc = Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(
+            reader.action(esc.clone()),
+            Some(Action::PendingCount(None))
+        ));
+        assert!(matches!(reader.action(esc), Some(Action::Quit)));
+    }
+    #[test]
+    fn edit_mode() {
+        let keymap = KeyMap::default();
+        let editing = Arc::new(AtomicBool::new(false));
+        let mut reader = CountingReader::new(&keymap, editing.clone(), Arc::default());
+        assert!(matches!(reader.action(key('i')), Some(Action::Edit)));
+        assert!(editing.load(Ordering::Relaxed));
+        // keys of actions are typed instead
+        assert!(matches!(reader.action(key('q')), Some(Action::Type('q'))));
+        assert!(matches!(reader.action(key('4')), Some(Action::Type('4'))));
+        // while keys with ctrl still do their actions
+        let ctrl_z = Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL));
+        assert!(matches!(reader.action(ctrl_z), Some(Action::Suspend)));
+        let esc = Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(reader.action(esc), Some(Action::StopEditing)));
+        assert!(!editing.load(Ordering::Relaxed));
+        assert!(matches!(reader.action(key('q')), Some(Action::Quit)));
+    }
+    #[test]
+    fn prompt_mode() {
+        let keymap = KeyMap::default();
+        let prompting = Arc::new(AtomicBool::new(false));
+        let mut reader = CountingReader::new(&keymap, Arc::default(), prompting.clone());
+        assert!(matches!(reader.action(key(':')), Some(Action::Command)));
+        assert!(prompting.load(Ordering::Relaxed));
+        // all keys go to the command line, including the digits of a count
+        assert_eq!(
+            reader.action(key('4')),
+            Some(Action::Prompt(PromptKey::Char('4')))
+        );
+        let up = Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(reader.action(up), Some(Action::Prompt(PromptKey::Up)));
+        let f1 = Event::Key(KeyEvent::new(KeyCode::F(1), KeyModifiers::NONE));
+        assert_eq!(reader.action(f1), None);
+        let enter = Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(reader.action(enter), Some(Action::Prompt(PromptKey::Enter)));
+        assert!(!prompting.load(Ordering::Relaxed));
+        assert!(matches!(reader.action(key('q')), Some(Action::Quit)));
+    }
+    #[test]
+    fn cursive_events() {
+        let keymap = KeyMap::default();
+        let action = |event| cross_event(&event).and_then(|ev| event_action(ev, &keymap));
+        assert!(matches!(
+            action(CursiveEvent::Char('j')),
+            Some(Action::DownAlt)
+        ));
+        assert!(matches!(
+            action(CursiveEvent::Key(CursiveKey::F3)),
+            Some(Action::Align)
+        ));
+        assert!(matches!(
+            action(CursiveEvent::Key(CursiveKey::Esc)),
+            Some(Action::Quit)
+        ));
+        assert!(matches!(
+            action(CursiveEvent::Shift(CursiveKey::Up)),
+            Some(Action::SelectUp)
+        ));
+        let click = CursiveEvent::Mouse {
+            offset: (0, 0).into(),
+            position: (5, 7).into(),
+            event: CursiveMouseEvent::Press(cursive::event::MouseButton::Left),
+        };
+        assert!(matches!(action(click), Some(Action::SetCursorTo(5, 7))));
+        assert!(cross_event(&CursiveEvent::WindowResize).is_none());
+        assert!(cross_event(&CursiveEvent::Key(CursiveKey::F0)).is_none());
+    }
+    #[test]
+    fn window_title() {
+        let mut cross = Cross::new_uninit(Palette::default(), false);
+        cross.set_title("biodiff: a\x07b \u{2194} c");
+        let output = String::from_utf8(cross.buffer.into_inner()).unwrap();
+        assert_eq!(output, "\x1b]0;biodiff: ab \u{2194} c\x07");
+    }
+    #[test]
+    fn drawing_errors() {
+        let mut cross = Cross::new_uninit(Palette::default(), false);
+        cross.set_line(1 << 20);
+        cross.set_pos(3, 1 << 17);
+        let error = cross.refresh().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        // the error is only reported once and the drawing can go on
+        assert!(cross.buffer.get_ref().is_empty());
+        assert!(cross.refresh().is_ok());
+    }
 }
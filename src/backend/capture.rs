@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+
+use unicode_width::UnicodeWidthChar;
+
+use super::{Backend, BackgroundColor, Color, Effect};
+
+/// The content of a single screen cell
+pub type Cell = (char, Color, BackgroundColor, Effect);
+
+/// Marks the second half of a double width character
+const CONTINUATION: char = '\0';
+
+/// A backend that records the rendered screen into a grid of cells,
+/// for comparing it against snapshots in tests
+pub struct Capture {
+    grid: Vec<Vec<Cell>>,
+    columns: usize,
+    pos: (usize, usize),
+    scroll: bool,
+}
+
+impl Capture {
+    /// Creates an empty screen of the given size
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Capture {
+            grid: vec![vec![BLANK; columns]; rows],
+            columns,
+            pos: (0, 0),
+            scroll: true,
+        }
+    }
+    /// Makes `can_scroll` return false, so that every change is redrawn completely
+    pub fn without_scroll(self) -> Self {
+        Capture {
+            scroll: false,
+            ..self
+        }
+    }
+    /// The characters on the screen, without trailing spaces
+    pub fn text(&self) -> String {
+        let mut ret = String::new();
+        for row in &self.grid {
+            let line: String = chars(row).map(|cell| cell.0).collect();
+            ret.push_str(line.trim_end());
+            ret.push('\n');
+        }
+        ret
+    }
+    /// The characters on the screen, each line followed by lines annotating the
+    /// foreground color, the background (only if not blank) and effects (only if there are any)
+    /// of every character
+    pub fn annotated(&self) -> String {
+        let mut ret = String::new();
+        for row in &self.grid {
+            let line = |code: fn(&Cell) -> char| chars(row).map(code).collect::<String>();
+            ret += &format!("   |{}|\n", line(|cell| cell.0));
+            ret += &format!("fg |{}|\n", line(|cell| fg_code(cell.1)));
+            for (name, layer) in [("bg", line(|cell| bg_code(cell.2))), ("fx", line(fx_code))] {
+                if layer.chars().any(|c| c != ' ') {
+                    ret += &format!("{name} |{layer}|\n");
+                }
+            }
+        }
+        ret
+    }
+    fn write(&mut self, cell: Cell) {
+        let (column, line) = self.pos;
+        let width = match cell.0.width() {
+            Some(width @ 1..) => width,
+            _ => return,
+        };
+        if let Some(row) = self.grid.get_mut(line) {
+            for (i, target) in row.iter_mut().skip(column).take(width).enumerate() {
+                *target = if i == 0 {
+                    cell
+                } else {
+                    (CONTINUATION, cell.1, cell.2, cell.3)
+                };
+            }
+        }
+        self.pos.0 += width;
+    }
+}
+
+const BLANK: Cell = (
+    ' ',
+    Color::HexSame,
+    BackgroundColor::Blank,
+    Effect {
+        inverted: false,
+        bold: false,
+    },
+);
+
+/// The cells of a row that start a character
+fn chars(row: &[Cell]) -> impl Iterator<Item = &Cell> {
+    row.iter().filter(|cell| cell.0 != CONTINUATION)
+}
+
+fn fg_code(color: Color) -> char {
+    match color {
+        Color::Unimportant => '.',
+        Color::HexSame => 's',
+        Color::HexSameSecondary => 'S',
+        Color::HexDiff => 'd',
+        Color::HexDiffSecondary => 'D',
+        Color::HexOneside => 'o',
+        Color::HexOnesideSecondary => 'O',
+        Color::ByteNull => '0',
+        Color::BytePrintable => 'p',
+        Color::ByteControl => 'c',
+        Color::ByteHighBit => 'H',
+        Color::ByteFf => 'f',
+        Color::Gap => 'g',
+        Color::Ignored => 'x',
+        Color::Edited => 'e',
+        Color::Ours => '<',
+        Color::Theirs => '>',
+        Color::Conflict => '!',
+        Color::Entropy(level) => char::from_digit(level as u32 % 8 + 1, 10).unwrap(),
+    }
+}
+
+fn bg_code(color: BackgroundColor) -> char {
+    match color {
+        BackgroundColor::Blank => ' ',
+        BackgroundColor::Highlight => 'h',
+        BackgroundColor::CurrentResult => 'c',
+        BackgroundColor::DiffRow => 'r',
+        BackgroundColor::Search(slot) => char::from_digit(slot as u32 % 10, 10).unwrap(),
+        BackgroundColor::Annotation(_) => 'a',
+    }
+}
+
+fn fx_code(cell: &Cell) -> char {
+    match (cell.3.inverted, cell.3.bold) {
+        (false, false) => ' ',
+        (true, false) => 'i',
+        (false, true) => 'b',
+        (true, true) => 'B',
+    }
+}
+
+impl Backend for Capture {
+    fn set_line(&mut self, line: usize) {
+        self.pos = (0, line);
+    }
+
+    fn set_pos(&mut self, column: usize, line: usize) {
+        self.pos = (column, line);
+    }
+
+    fn append_text(&mut self, text: &str, color: Color, bg: BackgroundColor, effect: Effect) {
+        for c in text.chars() {
+            self.write((c, color, bg, effect));
+        }
+    }
+
+    fn can_scroll(&self) -> bool {
+        self.scroll
+    }
+
+    fn scroll(&mut self, amount: isize) {
+        let rows = self.grid.len();
+        let shift = amount.unsigned_abs().min(rows);
+        let blank = vec![BLANK; self.columns];
+        if amount > 0 {
+            self.grid.drain(..shift);
+            self.grid.resize(rows, blank);
+        } else {
+            self.grid.truncate(rows - shift);
+            self.grid.splice(0..0, std::iter::repeat(blank).take(shift));
+        }
+    }
+
+    fn refresh(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&mut self) -> (usize, usize) {
+        (self.columns, self.grid.len())
+    }
+
+    fn clear(&mut self) {
+        for row in &mut self.grid {
+            row.fill(BLANK);
+        }
+    }
+
+    fn set_title(&mut self, _: &str) {}
+}
+
+/// Compares `actual` against the snapshot file `src/snapshots/{name}.txt`.
+/// If the environment variable `UPDATE_SNAPSHOTS` is set, the snapshot is written instead.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "src",
+        "snapshots",
+        &format!("{name}.txt"),
+    ]
+    .iter()
+    .collect();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).expect("Could not write snapshot");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Could not read snapshot {}: {e} (run with UPDATE_SNAPSHOTS=1 to create it)",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "snapshot {name} does not match, expected:\n{expected}\nactual:\n{actual}"
+    );
+}
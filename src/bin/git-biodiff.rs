@@ -4,7 +4,13 @@ use std::process::Command;
 
 fn main() {
     let status = Command::new("git")
-        .args(["difftool", "--no-prompt", "--extcmd=biodiff"])
+        // git evaluates the command in a shell with the path of the compared file in $BASE,
+        // so the temporary files it passes are shown with their real names
+        .args([
+            "difftool",
+            "--no-prompt",
+            r#"--extcmd=biodiff --label-a="a/$BASE" --label-b="b/$BASE""#,
+        ])
         .args(env::args_os().skip(1))
         .status()
         .expect("Failed to run git");
@@ -0,0 +1,288 @@
+use std::{
+    fs::read_to_string,
+    ops::Range,
+    path::PathBuf,
+    sync::mpsc::{channel, Sender},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    align::{runs::AlignRuns, AlignAlgorithm, AlignElement, AlignMode, AlignedBlock},
+    file::FileContent,
+};
+
+/// The size of all cached alignments in bytes unless `"align_cache_size"` is configured
+pub const DEFAULT_CACHE_SIZE: u64 = 256 << 20;
+/// The number of elements sent in each block when showing a cached alignment
+const CACHED_BLOCK: usize = 1 << 16;
+
+/// A finished alignment in the cache
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedAlignment {
+    runs: AlignRuns,
+    /// how many of the elements were prepended before the ones at the start addresses
+    prepended: usize,
+}
+
+/// The cached alignments, with the ones used least recently first
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    /// the size of the file of the alignment in bytes
+    size: u64,
+}
+
+/// The alignments that were finished before, which are shown again instead of aligning
+/// the same bytes with the same parameters once more.
+/// They are kept in a directory with a file for each alignment and an index of them,
+/// and the ones that were not used for the longest time are removed once they take up more
+/// than `max_size` bytes.
+#[derive(Clone, Debug)]
+pub struct AlignCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl AlignCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        AlignCache { dir, max_size }
+    }
+    /// Starts the alignment like `AlignAlgorithm::start_align_with_selection`, but sends the
+    /// cached one if the files were aligned with the same parameters before.
+    /// Otherwise, the blocks are passed on and the alignment is cached once it is finished.
+    /// Hashing the files is done in the background, before aligning them.
+    pub fn start_align<M: From<AlignedBlock> + Send + 'static>(
+        &self,
+        algo: &AlignAlgorithm,
+        files: [FileContent; 2],
+        selection: [Option<Range<usize>>; 2],
+        addr: [usize; 2],
+        sender: Sender<M>,
+    ) {
+        let cache = self.clone();
+        let algo = *algo;
+        std::thread::spawn(move || {
+            let key = key(&algo, &files, &selection, addr);
+            if let Some(cached) = cache.load(&key) {
+                if send_cached(&cached, &files, &sender) {
+                    return;
+                }
+            }
+            let (inner, blocks) = channel::<AlignedBlock>();
+            algo.start_align_with_selection(files.clone(), selection, addr, inner);
+            let mut front = Vec::new();
+            let mut back = AlignRuns::default();
+            let mut cacheable = true;
+            // the channel closes once all threads of the alignment are done
+            for block in blocks {
+                match &block {
+                    AlignedBlock::Append(elements) => {
+                        cacheable &= AlignRuns::from_elements(elements)
+                            .map_or(false, |runs| back.append(runs))
+                    }
+                    AlignedBlock::Prepend(elements) => match AlignRuns::from_elements(elements) {
+                        Some(runs) => front.push(runs),
+                        None => cacheable = false,
+                    },
+                    // the alignment of the chosen aligner is tried again the next time
+                    AlignedBlock::Fallback { .. } => cacheable = false,
+                    AlignedBlock::Done => (),
+                }
+                // the view was closed before the alignment was finished
+                if sender.send(block.into()).is_err() {
+                    return;
+                }
+            }
+            let prepended = front.iter().map(AlignRuns::len).sum();
+            let mut runs = AlignRuns::default();
+            for block in front.into_iter().rev().chain([back]) {
+                cacheable &= runs.append(block);
+            }
+            // only whole alignments are cached, not ones whose threads stopped early
+            let whole = runs.start == [0, 0] && runs.end() == [files[0].len(), files[1].len()];
+            if cacheable && whole {
+                // the cache only saves time, so the alignment is not cached if it can not be written
+                let _ = cache.store(&key, &CachedAlignment { runs, prepended });
+            }
+        });
+    }
+    fn index_file(&self) -> PathBuf {
+        self.dir.join("index.json")
+    }
+    fn entry_file(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+    fn read_index(&self) -> Index {
+        read_to_string(self.index_file())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+    /// Moves the entry to the end of the index as the one used last, and removes the ones
+    /// used least recently while the entries are too big
+    fn use_entry(&self, key: &str, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = self.read_index();
+        index.entries.retain(|entry| entry.key != key);
+        index.entries.push(Entry {
+            key: key.to_string(),
+            size,
+        });
+        let mut total = index.entries.iter().map(|entry| entry.size).sum::<u64>();
+        while total > self.max_size && !index.entries.is_empty() {
+            let oldest = index.entries.remove(0);
+            total -= oldest.size;
+            let _ = std::fs::remove_file(self.entry_file(&oldest.key));
+        }
+        std::fs::write(self.index_file(), serde_json::to_string(&index)?)?;
+        Ok(())
+    }
+    fn load(&self, key: &str) -> Option<CachedAlignment> {
+        let text = read_to_string(self.entry_file(key)).ok()?;
+        let cached = serde_json::from_str(&text).ok()?;
+        let _ = self.use_entry(key, text.len() as u64);
+        Some(cached)
+    }
+    fn store(&self, key: &str, cached: &CachedAlignment) -> Result<(), Box<dyn std::error::Error>> {
+        let text = serde_json::to_string(cached)?;
+        if text.len() as u64 > self.max_size {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.entry_file(key), &text)?;
+        self.use_entry(key, text.len() as u64)
+    }
+}
+
+/// The key of an alignment, which is the hash of the bytes of both files and everything
+/// the alignment depends on
+fn key(
+    algo: &AlignAlgorithm,
+    files: &[FileContent; 2],
+    selection: &[Option<Range<usize>>; 2],
+    addr: [usize; 2],
+) -> String {
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(Sha256::digest(&file[..]));
+    }
+    hasher.update(serde_json::to_string(algo).unwrap_or_default());
+    // the whole files are aligned at once without a selection, no matter where the cursors are
+    let whole = matches!(algo.mode, AlignMode::Global | AlignMode::Local)
+        && matches!(selection, [None, None] | [Some(_), Some(_)]);
+    if !whole {
+        hasher.update(format!("{selection:?} {addr:?}"));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Sends the blocks of a cached alignment like the alignment threads do, returning false if it
+/// does not fit the files
+fn send_cached<M: From<AlignedBlock>>(
+    cached: &CachedAlignment,
+    files: &[FileContent; 2],
+    sender: &Sender<M>,
+) -> bool {
+    let mut elements = match cached.runs.elements(&files[0], &files[1]) {
+        Some(elements) => elements,
+        None => return false,
+    };
+    let front = elements
+        .by_ref()
+        .take(cached.prepended)
+        .collect::<Vec<AlignElement>>();
+    loop {
+        let block = elements.by_ref().take(CACHED_BLOCK).collect::<Vec<_>>();
+        if block.is_empty() {
+            break;
+        }
+        if sender.send(AlignedBlock::Append(block).into()).is_err() {
+            return true;
+        }
+    }
+    for block in front.rchunks(CACHED_BLOCK) {
+        if sender
+            .send(AlignedBlock::Prepend(block.to_vec()).into())
+            .is_err()
+        {
+            return true;
+        }
+    }
+    let _ = sender.send(AlignedBlock::Done.into());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{file::FileBytes, testing::temp_path};
+
+    fn receive(
+        cache: &AlignCache,
+        algo: &AlignAlgorithm,
+        files: &[FileContent; 2],
+    ) -> Vec<AlignElement> {
+        let (sender, receiver) = channel::<AlignedBlock>();
+        cache.start_align(algo, files.clone(), [None, None], [2, 2], sender);
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        for block in receiver {
+            match block {
+                AlignedBlock::Append(elements) => back.extend(elements),
+                AlignedBlock::Prepend(elements) => front.push(elements),
+                AlignedBlock::Fallback { .. } => (),
+                // the cache is written after the last block is passed on
+                AlignedBlock::Done => break,
+            }
+        }
+        front.into_iter().rev().flatten().chain(back).collect()
+    }
+
+    #[test]
+    fn cached_alignment() {
+        let dir = temp_path("cache");
+        let cache = AlignCache::new(dir.clone(), DEFAULT_CACHE_SIZE);
+        let files = [&b"some bytes here"[..], &b"some other bytes here"[..]]
+            .map(|bytes| Arc::new(FileBytes::from(bytes.to_vec())));
+        let algo = AlignAlgorithm::default();
+        let key = key(&algo, &files, &[None, None], [2, 2]);
+        let aligned = receive(&cache, &algo, &files);
+        // wait for the thread writing the cache
+        for _ in 0..100 {
+            if cache.entry_file(&key).exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let cached = cache.load(&key).unwrap();
+        assert_eq!(cached.runs, AlignRuns::from_elements(&aligned).unwrap());
+        let addresses = |elements: Vec<AlignElement>| {
+            elements
+                .into_iter()
+                .map(|e| e.byte_addresses())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            addresses(receive(&cache, &algo, &files)),
+            addresses(aligned)
+        );
+        // other bytes or parameters have other keys, and too big alignments are removed
+        let other = AlignAlgorithm {
+            gap_open: -3,
+            ..algo
+        };
+        assert_ne!(key, super::key(&other, &files, &[None, None], [2, 2]));
+        assert_ne!(key, super::key(&algo, &files, &[None, None], [0, 0]));
+        AlignCache::new(dir.clone(), 1).use_entry(&key, 2).unwrap();
+        assert!(!cache.entry_file(&key).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
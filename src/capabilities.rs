@@ -0,0 +1,186 @@
+use std::{
+    env,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    time::Duration,
+};
+
+/// How long to wait for the terminal to answer a query, in case it never does
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Starts a synchronized update, during which the terminal does not display anything
+pub const BEGIN_SYNCHRONIZED_UPDATE: &[u8] = b"\x1b[?2026h";
+/// Ends a synchronized update and displays everything written since it began
+pub const END_SYNCHRONIZED_UPDATE: &[u8] = b"\x1b[?2026l";
+
+/// Puts the text into the clipboard with the OSC 52 sequence, which is
+/// understood by most terminals (also over ssh) and ignored by the others
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+/// Encodes the bytes in base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| {
+            acc | ((*byte as u32) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Terminals (going by the part of TERM before the first dash) which do not support
+/// the sequences for scrolling the screen content
+const NO_SCROLL_TERMS: &[&str] = &["linux", "dumb", "cons25", "vt52", "vt100", "vt102"];
+
+/// Guesses from the TERM environment variable whether the terminal can scroll
+pub fn scroll_supported() -> bool {
+    term_can_scroll(&env::var("TERM").unwrap_or_default())
+}
+
+fn term_can_scroll(term: &str) -> bool {
+    let name = term.split('-').next().unwrap_or_default();
+    !NO_SCROLL_TERMS.contains(&name)
+}
+
+/// Returns whether the terminal supports synchronized updates (mode 2026).
+/// The terminal is only asked the first time, which has to happen in raw mode
+/// so that the answer is not echoed.
+pub fn synchronized_output() -> bool {
+    static PROBE: Once = Once::new();
+    static SUPPORTED: AtomicBool = AtomicBool::new(false);
+    PROBE.call_once(|| {
+        // the DECRQM query for the mode is followed by a query for the primary device
+        // attributes, which every terminal answers, so that we know when to stop waiting
+        let answer = query_terminal(b"\x1b[?2026$p\x1b[c", has_device_attributes);
+        SUPPORTED.store(mode_supported(&answer, 2026), Ordering::Relaxed)
+    });
+    SUPPORTED.load(Ordering::Relaxed)
+}
+
+/// Returns whether the answers of the terminal contain the primary device attributes,
+/// which have the form `ESC [ ? <params> c`
+fn has_device_attributes(answer: &[u8]) -> bool {
+    answer.windows(3).enumerate().any(|(i, start)| {
+        start == b"\x1b[?"
+            && answer[i + 3..]
+                .iter()
+                .find(|b| !b.is_ascii_digit() && **b != b';')
+                == Some(&b'c')
+    })
+}
+
+/// Looks for the DECRPM report of a private mode in the answers of the terminal,
+/// which has the form `ESC [ ? <mode> ; <status> $ y`, and returns whether
+/// the mode is known and can be set
+fn mode_supported(answer: &[u8], mode: u16) -> bool {
+    let prefix = format!("\x1b[?{mode};");
+    let start = match answer
+        .windows(prefix.len())
+        .position(|w| w == prefix.as_bytes())
+    {
+        Some(pos) => pos + prefix.len(),
+        None => return false,
+    };
+    // 1 and 2 mean set and reset, 3 is permanently set while 0 (unknown)
+    // and 4 (permanently reset) mean it can not be used
+    matches!(&answer[start..], [b'1' | b'2' | b'3', b'$', b'y', ..])
+}
+
+/// Writes a query to the terminal and reads the answer until `done` returns true
+/// or the time runs out, so terminals that do not answer do not make us hang.
+#[cfg(unix)]
+fn query_terminal(query: &[u8], done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    use std::{fs::OpenOptions, io::Read, os::unix::io::AsRawFd, time::Instant};
+    let mut answer = Vec::new();
+    let mut tty = match OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        Ok(tty) => tty,
+        Err(_) => return answer,
+    };
+    if tty.write_all(query).and_then(|()| tty.flush()).is_err() {
+        return answer;
+    }
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    while !done(&answer) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: we pass exactly one valid pollfd
+        let ready = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        let mut buf = [0u8; 64];
+        match tty.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => answer.extend_from_slice(&buf[..n]),
+        }
+    }
+    answer
+}
+
+/// Without a way to wait for input with a timeout, the terminal is not asked
+#[cfg(not(unix))]
+fn query_terminal(_query: &[u8], _done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn scroll_terms() {
+        assert!(term_can_scroll("xterm-256color"));
+        assert!(term_can_scroll("tmux-256color"));
+        assert!(term_can_scroll("screen.linux"));
+        // windows terminals do not set TERM
+        assert!(term_can_scroll(""));
+        assert!(!term_can_scroll("linux"));
+        assert!(!term_can_scroll("linux-16color"));
+        assert!(!term_can_scroll("dumb"));
+    }
+    #[test]
+    fn base64_encoding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+    #[test]
+    fn synchronized_output_answers() {
+        let device_attributes = b"\x1b[?62;22c";
+        assert!(has_device_attributes(device_attributes));
+        assert!(!has_device_attributes(b"\x1b[?2026;2$y"));
+        assert!(!has_device_attributes(b"\x1b[?62;22"));
+        let supported = b"\x1b[?2026;2$y\x1b[?62;22c";
+        assert!(has_device_attributes(supported));
+        assert!(mode_supported(supported, 2026));
+        assert!(!mode_supported(supported, 2027));
+        assert!(!mode_supported(b"\x1b[?2026;0$y\x1b[?62;22c", 2026));
+        assert!(!mode_supported(b"\x1b[?2026;4$y", 2026));
+        // terminals without support only answer the device attributes query
+        assert!(!mode_supported(device_attributes, 2026));
+        assert!(!mode_supported(b"", 2026));
+    }
+}
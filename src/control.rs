@@ -2,7 +2,7 @@ use crossbeam_utils::thread::scope;
 use cursive::{
     backend::Backend as CursiveBackend,
     backends::crossterm,
-    event::Key,
+    event::{Event as CursiveEvent, EventResult, Key},
     traits::Nameable,
     view::ViewWrapper,
     views::{LayerPosition, NamedView},
@@ -10,66 +10,419 @@ use cursive::{
 };
 use cursive::{traits::Resizable, views::ResizedView, Cursive};
 use cursive_buffered_backend::BufferedBackend;
-use dirs::config_dir;
+use dirs::{cache_dir, config_dir, data_dir};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     align::{AlignAlgorithm, AlignMode},
-    backend::{send_cross_actions, Action, Cross, Dummy},
-    cursor::CursorState,
+    annotation::Annotations,
+    backend::{
+        cross_event, send_cross_actions, Action, Backend, CountingReader, Cross, Dummy,
+        TICK_INTERVAL,
+    },
+    cache::{AlignCache, DEFAULT_CACHE_SIZE},
+    capabilities,
+    cursor::{CursorActive, CursorState},
     dialog,
     doublehex::DoubleHexContext,
-    file::FileState,
-    style::Style,
-    view::{self, Aligned, AlignedMessage},
+    file::{FileContent, FileState, Source},
+    ignore::Ignore,
+    keymap::KeyMap,
+    marks::{MarkMap, Marks},
+    notice::{Notice, Severity},
+    pointer::Pointer,
+    prompt::PromptKey,
+    script::{Recorder, Replay, Script},
+    search::Query,
+    session::{SavedQuery, Session, Stamp},
+    sigbus,
+    style::{ColumnSetting, Style},
+    suspend,
+    theme::{ColorDepth, Palette},
+    threeway::ThreeWay,
+    view::{self, Aligned, AlignedMessage, Unaligned, UnalignedMessage},
 };
 use std::{
     error::Error,
     fs::read_to_string,
     ops::Range,
     path::PathBuf,
-    sync::mpsc::{channel, Receiver, Sender},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::Duration,
 };
 
 type CursiveCallback = Box<dyn Fn(&mut Cursive) + 'static + Send>;
 
 /// This is the main loop, here we switch between our custom backend and the cursive backend
 /// when opening dialog boxes. This is done because initially, the cursive backend was too flickery.
-/// However, this was fixed by using cursive_buffered_backend, so now this is only a minor optimization.
-pub fn run(x: FileState, y: FileState) {
+/// However, this was fixed by using cursive_buffered_backend, so now this is only a minor optimization,
+/// and the views can also be shown with cursive by choosing `Frontend::Cursive`.
+///
+/// Returns an error if drawing to the terminal keeps failing.
+pub fn run(mut x: FileState, mut y: FileState, mut options: Options) -> std::io::Result<()> {
     let mut settings = Settings::from_config().unwrap_or_default();
+    options.presets.apply(&mut settings);
     let digits = x.address_digits().max(y.address_digits());
     settings.style.addr_width = digits;
-    let mut hv = HexView::new(x, y);
+    settings.style.short_addr_width = x.short_address_digits().max(y.short_address_digits());
+    settings.style.unit = x.content.unit();
+    settings.style.monochrome = options.monochrome;
+    settings.style.color_depth = ColorDepth::detect();
+    settings.style.can_scroll = !options.no_scroll && capabilities::scroll_supported();
+    settings.style.single = options.single;
+    let (keymap, keymap_errors) = match Settings::keymap_file() {
+        Ok(path) => KeyMap::from_file(&path),
+        Err(_) => (KeyMap::default(), Vec::new()),
+    };
+    settings.keymap = keymap;
+    let files = [(&x, &options.sources[0]), (&y, &options.sources[1])]
+        .map(|(file, source)| Marks::file_key(source.as_ref(), &file.name));
+    // the hidden second file of a single one is the same as the first one,
+    // while the halves of a file compared with itself have marks of their own
+    let mut files = if options.single {
+        [files[0].clone(), files[0].clone()]
+    } else if options.self_diff {
+        let [first, second] = files;
+        [format!("{first} @A"), format!("{second} @B")]
+    } else {
+        files
+    };
+    let (saved_marks, marks_error) = match settings.save_marks.then(Settings::marks_file) {
+        Some(Ok(path)) => match Marks::load(&path) {
+            Ok(marks) => (marks, None),
+            Err(e) => (
+                MarkMap::new(),
+                Some(format!("Could not read the marks: {e}")),
+            ),
+        },
+        _ => (MarkMap::new(), None),
+    };
+    settings.marks = Marks::new(files.clone(), saved_marks);
+    // files that can not be read again (like stdin) are only kept in explicit session files
+    let rereadable =
+        options.sources[0].is_some() && (options.single || options.sources[1].is_some());
+    let session_file = match &options.session {
+        Some(path) => Some(Ok(path.clone())),
+        None => (settings.save_sessions && rereadable).then(Settings::sessions_file),
+    };
+    let (session, session_error) = match &session_file {
+        Some(Ok(path)) => match Session::load(path, &files) {
+            Ok(session) => (session, None),
+            Err(e) => (None, Some(format!("Could not read the session: {e}"))),
+        },
+        _ => (None, None),
+    };
+    let (recorder, record_error) = match options.record.as_deref().map(Recorder::create) {
+        Some(Ok(recorder)) => (Some(recorder), None),
+        Some(Err(e)) => (None, Some(format!("Could not record the actions: {e}"))),
+        None => (None, None),
+    };
+    let mut script = Script {
+        recorder,
+        replay: options.replay.take(),
+    };
+    let mut notice = marks_error
+        .or(session_error)
+        .or(record_error)
+        .map(|error| (Severity::Error, error));
+    if let Some(session) = &session {
+        session.restore(&mut settings);
+        // the columns given on the command line take precedence over the ones of the session
+        options.presets.apply(&mut settings);
+        let stamps = [0, 1].map(|i| Stamp::of(options.sources[i].as_ref()));
+        let changed = [&x, &y]
+            .into_iter()
+            .zip(session.changed(&stamps))
+            .filter_map(|(file, changed)| changed.then_some(file.name.as_str()))
+            .collect::<Vec<_>>();
+        if !changed.is_empty() && notice.is_none() {
+            let warning = format!(
+                "{} changed since the session was saved",
+                changed.join(" and ")
+            );
+            notice = Some((Severity::Warning, warning));
+        }
+    }
+    // the start addresses on the command line take precedence over the cursors of the session
+    let restored = session.filter(|_| !options.start_given);
+    if let Some(session) = &restored {
+        // the alignment starts at the cursors
+        for (file, position) in [&mut x, &mut y].into_iter().zip(session.positions) {
+            file.index = (position.max(0) as usize).min(file.content.len().saturating_sub(1));
+        }
+    }
+    // invalid lines of the keymap are reported before showing the files
+    let mut startup_dialog = (!keymap_errors.is_empty())
+        .then(|| Box::new(dialog::keymap_errors(keymap_errors)) as CursiveCallback);
+    // both files are aligned to their base before showing them, which blocks until it is done
+    let three_way = options
+        .base
+        .take()
+        .map(|base| ThreeWay::new(&settings.algo, base, [&x, &y].map(|f| f.content.clone())));
+    // a single file has nothing to be aligned to, and a three-way comparison starts aligned
+    let start_aligned = (options.start_aligned
+        || settings.start_aligned
+        || three_way.is_some()
+        || restored.as_ref().map_or(false, |session| session.aligned))
+        && !options.single;
+    let align_cache = Settings::cache_path()
+        .ok()
+        .filter(|_| !options.no_cache)
+        .map(|dir| AlignCache::new(dir, settings.align_cache_size.unwrap_or(DEFAULT_CACHE_SIZE)));
+    let mut hv = HexView::starting(x, y, start_aligned.then_some(&settings.algo), align_cache);
+    match (&restored, &mut hv) {
+        (Some(session), HexView::Aligned(v, _, _)) => v.restore_cursor(session.cursor),
+        (Some(session), HexView::Unaligned(v)) => {
+            v.set_positions(session.positions);
+            v.dh.offset_lock = session.offset_lock;
+        }
+        (None, _) => (),
+    }
+    if let (true, HexView::Unaligned(v)) = (options.single, &mut hv) {
+        v.dh.cursor_act = CursorActive::First;
+    }
+    if let Some((severity, text)) = notice {
+        match hv {
+            HexView::Aligned(ref mut v, _, _) => v.dh.set_notice(severity, text),
+            HexView::Unaligned(ref mut v) => v.dh.set_notice(severity, text),
+        }
+    }
+    let annotations = std::mem::take(&mut options.annotations)
+        .map(|annotations| annotations.degrade(settings.style.color_depth));
+    let dh = match hv {
+        HexView::Aligned(ref mut v, _, _) => &mut v.dh,
+        HexView::Unaligned(ref mut v) => &mut v.dh,
+    };
+    dh.annotations = annotations;
+    dh.ignore = std::mem::take(&mut options.ignore);
+    dh.three_way = three_way;
+    let mut frontend = settings.frontend;
+    // only the event loop of the crossterm frontend records and replays the actions
+    if script.recorder.is_some() || script.replay.is_some() {
+        frontend = Frontend::Crossterm;
+    }
+    suspend::catch_stop_signal();
+    sigbus::catch_bus_error();
     loop {
         *match hv {
             HexView::Aligned(ref mut v, _, _) => &mut v.dh.style,
             HexView::Unaligned(ref mut v) => &mut v.dh.style,
         } = settings.style;
-        let mut cross = Cross::init();
-        let (hv_new, quit) = hv.process_cross(&mut cross, &settings);
-        hv = hv_new;
-        cross.uninit();
-        // the column setting can be changed during the non-dialog,
-        // so we need to keep it updated here
-        settings.style = match &hv {
-            HexView::Aligned(v, _, _) => v.dh.style,
-            HexView::Unaligned(v) => v.dh.style,
+        let quit = match startup_dialog.take() {
+            Some(dia) => DelegateEvent::OpenDialog(dia),
+            None => {
+                let (hv_new, quit) = frontend.process(hv, &settings, &options.sources, &mut script);
+                hv = hv_new;
+                // the column setting can be changed during the non-dialog,
+                // so we need to keep it updated here
+                settings.style = match &hv {
+                    HexView::Aligned(v, _, _) => v.dh.style,
+                    HexView::Unaligned(v) => v.dh.style,
+                };
+                quit
+            }
+        };
+        // unsaved edits are saved or discarded before they are lost
+        let quit = match quit {
+            DelegateEvent::Quit | DelegateEvent::ReloadFiles if hv.has_unsaved_edits() => {
+                (hv, settings) = hv.show_dialog(Box::new(dialog::unsaved_edits), settings);
+                if hv.has_unsaved_edits() {
+                    continue;
+                }
+                quit
+            }
+            otherwise => otherwise,
         };
         let (hv_new, settings_new) = match quit {
-            DelegateEvent::Quit => break,
+            DelegateEvent::Quit => {
+                if let Some(Ok(path)) = &session_file {
+                    let session = hv.session(files, &options.sources, &settings);
+                    if let Err(e) = session.store(path) {
+                        eprintln!("Could not save the session: {e}");
+                    }
+                }
+                break;
+            }
+            DelegateEvent::TerminalError(e) => return Err(e),
             DelegateEvent::OpenDialog(dia) => hv.show_dialog(dia, settings),
+            DelegateEvent::RunCommand => hv.show_dialog(Box::new(dialog::command), settings),
+            DelegateEvent::ReloadFiles => (hv.reload(&options.sources, &settings.algo), settings),
+            DelegateEvent::SwapFiles => {
+                // the sources are in the order of the shown files, for reloading and watching them
+                options.sources.swap(0, 1);
+                files.swap(0, 1);
+                settings.marks.swap_files();
+                (hv.swap_files(), settings)
+            }
+            // the frontend has already restored the terminal, and sets it up again
+            // and redraws the whole view when the process is continued
+            DelegateEvent::Suspend => {
+                suspend::stop();
+                (hv, settings)
+            }
             _ => (hv, settings),
         };
         hv = hv_new;
         settings = settings_new;
     }
+    Ok(())
+}
+
+/// The event loop and `Backend` used for the views outside of dialogs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frontend {
+    /// our own crossterm backend, which only redraws the parts of the view that changed
+    #[default]
+    Crossterm,
+    /// a cursive session like the ones of the dialogs, which redraws the whole view
+    Cursive,
+    /// cursive, falling back to crossterm if the cursive backend can not be started
+    Auto,
+}
+
+impl Frontend {
+    /// Runs the event loop of the frontend on the view until the main loop has to handle something
+    fn process(
+        &mut self,
+        hv: HexView,
+        settings: &Settings,
+        sources: &[Option<Source>; 2],
+        script: &mut Script,
+    ) -> (HexView, DelegateEvent) {
+        match self {
+            Frontend::Crossterm => {
+                let mut cross = Cross::init(
+                    settings.style.palette(),
+                    settings.style.monochrome,
+                    settings.style.can_scroll,
+                );
+                if let Some(size) = script.size() {
+                    cross.fix_size(size);
+                }
+                let result = hv.process_cross(&mut cross, settings, sources, script);
+                cross.uninit();
+                result
+            }
+            Frontend::Cursive => hv.process_cursive(settings, sources),
+            Frontend::Auto => match hv.process_cursive(settings, sources) {
+                // the only error of the cursive frontend is that its backend could not be started
+                (hv, DelegateEvent::TerminalError(_)) => {
+                    *self = Frontend::Crossterm;
+                    self.process(hv, settings, sources, script)
+                }
+                result => {
+                    *self = Frontend::Cursive;
+                    result
+                }
+            },
+        }
+    }
+}
+
+/// Options given on the command line, which are not saved in the config
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// no colors, from NO_COLOR or `--color=never`
+    pub monochrome: bool,
+    /// never scroll the terminal, from `--no-scroll`
+    pub no_scroll: bool,
+    /// align the files at the start, from `--start-aligned`
+    pub start_aligned: bool,
+    /// only one file was given, which is shown as the first file and hidden as the second
+    pub single: bool,
+    /// one file is compared with itself, with both halves sharing its bytes, from `--self`
+    pub self_diff: bool,
+    pub presets: Presets,
+    /// how the files are read again when reloading them, or None if they can not be
+    pub sources: [Option<Source>; 2],
+    /// the labeled ranges of the files, from `--annotations-a` and `--annotations-b`
+    pub annotations: [Annotations; 2],
+    /// the differences that are not counted, from `--ignore` and `--ignore-bits`
+    pub ignore: Ignore,
+    /// neither use nor fill the alignment cache, from `--no-cache`
+    pub no_cache: bool,
+    /// the file the session is kept in, from `--session`
+    pub session: Option<PathBuf>,
+    /// whether `--offset-a`, `--offset-b` or `--goto` were given, which take precedence
+    /// over the cursors of the session
+    pub start_given: bool,
+    /// the common ancestor of the files in a three-way comparison, given before them
+    pub base: Option<FileContent>,
+    /// the file the actions are written to, from `--record`
+    pub record: Option<PathBuf>,
+    /// the actions that are done instead of reading the keyboard, from `--replay`
+    pub replay: Option<Replay>,
+}
+
+/// Settings given on the command line, which replace the configured ones
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Presets {
+    /// from `--algorithm`
+    pub mode: Option<AlignMode>,
+    /// from `--gap-open`
+    pub gap_open: Option<i32>,
+    /// from `--gap-extend`
+    pub gap_extend: Option<i32>,
+    /// from `--columns`
+    pub columns: Option<ColumnSetting>,
+    /// from `--frontend`
+    pub frontend: Option<Frontend>,
+}
+
+impl Presets {
+    pub fn apply(&self, settings: &mut Settings) {
+        let algo = &mut settings.algo;
+        match (self.mode, algo.mode) {
+            // keep the configured block size
+            (Some(AlignMode::Blockwise(_)), AlignMode::Blockwise(_)) => (),
+            (Some(mode), _) => algo.mode = mode,
+            (None, _) => (),
+        }
+        algo.gap_open = self.gap_open.unwrap_or(algo.gap_open);
+        algo.gap_extend = self.gap_extend.unwrap_or(algo.gap_extend);
+        if let Some(columns) = self.columns {
+            settings.style.column_count = columns;
+        }
+        settings.frontend = self.frontend.unwrap_or(settings.frontend);
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Settings {
     pub algo: AlignAlgorithm,
     pub style: Style,
+    /// whether to align the files right away instead of starting in the unaligned view
+    #[serde(default)]
+    pub start_aligned: bool,
+    #[serde(default)]
+    pub frontend: Frontend,
+    /// read from its own file, see `Settings::keymap_file`
+    #[serde(skip)]
+    pub keymap: KeyMap,
+    /// whether marks are written to their own file whenever they change and read on startup
+    #[serde(default)]
+    pub save_marks: bool,
+    /// the marks of the shown files, see `Settings::marks_file`
+    #[serde(skip)]
+    pub marks: Marks,
+    /// how the last followed pointer was read
+    #[serde(default)]
+    pub pointer: Pointer,
+    /// whether the session of every comparison is written to its own file on exit and
+    /// restored when the same files are compared again
+    #[serde(default)]
+    pub save_sessions: bool,
+    /// the last query that was searched, which the search dialog starts with
+    #[serde(skip)]
+    pub last_query: Option<Query>,
+    /// how many bytes the cached alignments may take up, or the default size if None
+    #[serde(default)]
+    pub align_cache_size: Option<u64>,
 }
 
 impl Settings {
@@ -88,11 +441,69 @@ impl Settings {
             },
         }
     }
+    /// Sessions are data rather than configuration, so they have their own directory
+    fn data_path() -> Result<PathBuf, std::io::Error> {
+        match std::env::var_os("BIODIFF_DATA_DIR") {
+            Some(p) => Ok(PathBuf::from(p)),
+            None => match data_dir() {
+                Some(mut p) => {
+                    p.push("biodiff");
+                    Ok(p)
+                }
+                None => Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Could not find data directory",
+                )),
+            },
+        }
+    }
+    /// Cached alignments can be removed at any time, so they are not kept with the other data
+    fn cache_path() -> Result<PathBuf, std::io::Error> {
+        match std::env::var_os("BIODIFF_CACHE_DIR") {
+            Some(p) => Ok(PathBuf::from(p)),
+            None => match cache_dir() {
+                Some(mut p) => {
+                    p.push("biodiff");
+                    p.push("alignments");
+                    Ok(p)
+                }
+                None => Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Could not find cache directory",
+                )),
+            },
+        }
+    }
+    fn sessions_file() -> Result<PathBuf, std::io::Error> {
+        let mut path = Self::data_path()?;
+        path.push("sessions.json");
+        Ok(path)
+    }
     fn settings_file() -> Result<PathBuf, std::io::Error> {
         let mut path = Self::config_path()?;
         path.push("config.json");
         Ok(path)
     }
+    /// The keymap is not stored in the json config, as it is meant to be edited by hand
+    fn keymap_file() -> Result<PathBuf, std::io::Error> {
+        let mut path = Self::config_path()?;
+        path.push("keymap.conf");
+        Ok(path)
+    }
+    /// Marks are not stored in the json config, as they change more often than the settings
+    fn marks_file() -> Result<PathBuf, std::io::Error> {
+        let mut path = Self::config_path()?;
+        path.push("marks.json");
+        Ok(path)
+    }
+    /// Writes the marks if they are kept between runs
+    pub fn store_marks(&self) -> Result<(), Box<dyn Error + 'static>> {
+        if !self.save_marks {
+            return Ok(());
+        }
+        std::fs::create_dir_all(Self::config_path()?)?;
+        self.marks.save(&Self::marks_file()?)
+    }
     pub fn from_config() -> Option<Self> {
         let config = read_to_string(Self::settings_file().ok()?).ok()?;
         serde_json::from_str(&config).ok()
@@ -117,6 +528,7 @@ impl Settings {
 /// The aligned view also contains a channel for messages, as the alignment
 /// algorithms need to dynamically append/prepend new blocks to the view
 /// and the crossbeam backend also sends user events over that.
+#[allow(clippy::large_enum_variant)]
 pub enum HexView {
     Aligned(
         view::Aligned,
@@ -136,6 +548,71 @@ impl HexView {
             DoubleHexContext::new((16, 16)),
         ))
     }
+    /// Creates the view shown at startup, which is aligned with the given algorithm if there is one.
+    /// If one of the files is empty, there is nothing to align and the view stays unaligned
+    /// with a notice. The alignments of the view are looked up in and added to the cache
+    /// if there is one.
+    fn starting(
+        left: FileState,
+        right: FileState,
+        align: Option<&AlignAlgorithm>,
+        cache: Option<AlignCache>,
+    ) -> Self {
+        let empty = [&left, &right]
+            .into_iter()
+            .find(|file| file.content.is_empty())
+            .map(|file| file.name.clone());
+        let mut view = HexView::new(left, right);
+        if let HexView::Unaligned(v) = &mut view {
+            v.dh.align_cache = cache;
+        }
+        match (align, empty) {
+            (None, _) => view,
+            (Some(algo), None) => view.into_aligned(algo, [None, None]),
+            (Some(_), Some(name)) => match view {
+                HexView::Unaligned(mut v) => {
+                    let notice = format!("Not aligning at the start, {name} is empty");
+                    v.dh.set_notice(Severity::Warning, notice);
+                    HexView::Unaligned(v)
+                }
+                aligned => aligned,
+            },
+        }
+    }
+    /// Where the comparison of the files with the given keys was left, for restoring it
+    fn session(
+        &self,
+        files: [String; 2],
+        sources: &[Option<Source>; 2],
+        settings: &Settings,
+    ) -> Session {
+        let (positions, cursor, dh, query) = match self {
+            HexView::Aligned(v, _, _) => (
+                v.positions(),
+                v.cursor_addresses(),
+                &v.dh,
+                v.current_search_query(),
+            ),
+            HexView::Unaligned(v) => (
+                v.positions(),
+                v.cursor_addresses(),
+                &v.dh,
+                v.current_search_query(),
+            ),
+        };
+        Session {
+            files,
+            stamps: [0, 1].map(|i| Stamp::of(sources[i].as_ref())),
+            positions,
+            cursor,
+            aligned: matches!(self, HexView::Aligned(..)),
+            offset_lock: dh.offset_lock,
+            style: settings.style,
+            second_column_count: settings.style.second_column_count,
+            marks: settings.marks.list(),
+            query: query.or(settings.last_query.as_ref()).map(SavedQuery::from),
+        }
+    }
     /// Turns a hexview into an aligned view using the given algorithm parameters
     fn into_aligned(self, algo: &AlignAlgorithm, select: [Option<Range<usize>>; 2]) -> HexView {
         let (send, recv) = channel();
@@ -174,13 +651,108 @@ impl HexView {
             HexView::Unaligned(_) => self,
         }
     }
+    /// Reads the files again at the addresses of the cursor, which are moved to the last byte
+    /// of files that are shorter now, and realigns them if the view is aligned.
+    /// Files that can not be read again are kept as they are, and search results are dropped.
+    fn reload(self, sources: &[Option<Source>; 2], algo: &AlignAlgorithm) -> HexView {
+        let aligned = matches!(self, HexView::Aligned(..));
+        let (left, right, mut dh) = match self {
+            HexView::Aligned(a, send, recv) => match a.destruct() {
+                Ok(parts) => parts,
+                Err(mut a) => {
+                    a.dh.set_notice(Severity::Error, "Can not reload outside of the files");
+                    return HexView::Aligned(a, send, recv);
+                }
+            },
+            HexView::Unaligned(u) => match u.destruct() {
+                Ok(parts) => parts,
+                Err(u) => return HexView::Unaligned(u),
+            },
+        };
+        let mut notices = Vec::new();
+        let old = [left.content.clone(), right.content.clone()];
+        let [left, right] = if dh.style.single {
+            let left = reload_file(left, sources[0].as_ref(), &mut notices);
+            let right = left.shared();
+            [left, right]
+        } else if Arc::ptr_eq(&old[0], &old[1]) {
+            // a file compared with itself is only read once, and its halves keep sharing it
+            let left = reload_file(left, sources[0].as_ref(), &mut notices);
+            let len = left.content.len();
+            let right = FileState {
+                name: right.name,
+                index: right.index.min(len.saturating_sub(1)),
+                ..left.shared()
+            };
+            [left, right]
+        } else {
+            [(left, &sources[0]), (right, &sources[1])]
+                .map(|(file, source)| reload_file(file, source.as_ref(), &mut notices))
+        };
+        let empty = [&left, &right]
+            .into_iter()
+            .find(|file| file.content.is_empty())
+            .map(|file| file.name.clone());
+        if let (true, Some(name)) = (aligned, &empty) {
+            notices.push(format!("Not aligning, {name} is empty"));
+        }
+        // files that could not be read again are noticed as modified again
+        dh.modified = [false, false];
+        // the edits were saved or discarded before reloading
+        dh.edits = Default::default();
+        dh.editor.set_active(false);
+        if notices.is_empty() {
+            dh.set_notice(Severity::Info, "Reloaded the files");
+        } else {
+            dh.set_notice(Severity::Warning, notices.join(", "));
+        }
+        let view = HexView::Unaligned(view::Unaligned::new(left, right, dh));
+        match (aligned, empty) {
+            (true, None) => view.into_aligned(algo, [None, None]),
+            _ => view,
+        }
+    }
+    /// Exchanges the two files, keeping the alignment if the view is aligned
+    fn swap_files(mut self) -> HexView {
+        match &mut self {
+            HexView::Aligned(a, _, _) => a.swap_files(),
+            HexView::Unaligned(u) => u.swap_files(),
+        }
+        self
+    }
+    /// Whether one of the files has edits that were not saved yet
+    fn has_unsaved_edits(&self) -> bool {
+        match self {
+            HexView::Aligned(a, _, _) => a.dh.has_unsaved_edits(),
+            HexView::Unaligned(u) => u.dh.has_unsaved_edits(),
+        }
+    }
     /// Call the relevant event processing functions for the crossterm backend
-    fn event_proc(&mut self, cross: &mut Cross) -> DelegateEvent {
+    fn event_proc(
+        &mut self,
+        cross: &mut Cross,
+        keymap: &KeyMap,
+        sources: &[Option<Source>; 2],
+        script: &mut Script,
+    ) -> DelegateEvent {
         match self {
             HexView::Aligned(ref mut a, ref mut send, ref mut recv) => {
-                aligned_cross(a, cross, send, recv)
+                aligned_cross(a, cross, keymap, sources, send, recv, script)
             }
-            HexView::Unaligned(ref mut u) => unaligned_cross(u, cross),
+            HexView::Unaligned(ref mut u) => unaligned_cross(u, cross, keymap, sources, script),
+        }
+    }
+    fn window_title(&self) -> String {
+        match self {
+            HexView::Aligned(a, _, _) => a.window_title(),
+            HexView::Unaligned(u) => u.window_title(),
+        }
+    }
+    /// Marks the searches of the view that are still running as cancelled
+    fn cancel_running_searches(&mut self) {
+        match self {
+            HexView::Aligned(a, _, _) => a.cancel_running_searches(),
+            HexView::Unaligned(u) => u.cancel_running_searches(),
         }
     }
     fn selection(&self) -> [Option<Range<usize>>; 2] {
@@ -191,19 +763,28 @@ impl HexView {
     }
     /// control loop for crossbeam backend, switches the view between aligned and unaligned when
     /// requested and runs event loops
-    fn process_cross(self, cross: &mut Cross, settings: &Settings) -> (Self, DelegateEvent) {
+    fn process_cross(
+        self,
+        cross: &mut Cross,
+        settings: &Settings,
+        sources: &[Option<Source>; 2],
+        script: &mut Script,
+    ) -> (Self, DelegateEvent) {
         let mut view = self;
+        // written out with the first refresh
+        cross.set_title(&view.window_title());
         let mut quit;
         let quit_reason = loop {
-            let q = view.event_proc(cross);
+            let q = view.event_proc(cross, &settings.keymap, sources, script);
             view = match q {
                 // delegate to top-level control loop
                 DelegateEvent::Quit | DelegateEvent::OpenDialog(_) => {
-                    quit = match &mut view {
-                        HexView::Aligned(v, _, _) => !v.process_escape(cross),
-                        HexView::Unaligned(v) => !v.process_escape(cross),
-                    }
-                    .then_some(q);
+                    let escaped = match &mut view {
+                        HexView::Aligned(v, _, _) => v.process_escape(cross),
+                        HexView::Unaligned(v) => v.process_escape(cross),
+                    };
+                    // if drawing failed, we stay and let the event loop redraw the view
+                    quit = matches!(escaped, Ok(false)).then_some(q);
                     view
                 }
                 DelegateEvent::SwitchToAlign => {
@@ -215,6 +796,11 @@ impl HexView {
                     quit = None;
                     view.into_unaligned()
                 }
+                DelegateEvent::ReloadFiles
+                | DelegateEvent::SwapFiles
+                | DelegateEvent::Suspend
+                | DelegateEvent::RunCommand
+                | DelegateEvent::TerminalError(_) => break q,
             };
             if let Some(q) = quit {
                 break q;
@@ -222,6 +808,115 @@ impl HexView {
         };
         (view, quit_reason)
     }
+    /// control loop for the cursive frontend, which starts a new cursive session
+    /// after switching between aligned and unaligned views
+    fn process_cursive(
+        self,
+        settings: &Settings,
+        sources: &[Option<Source>; 2],
+    ) -> (Self, DelegateEvent) {
+        let mut view = self;
+        loop {
+            let (view_new, quit) = view.cursive_session(settings, sources);
+            view = match quit {
+                DelegateEvent::SwitchToAlign => {
+                    let select = view_new.selection();
+                    view_new.into_aligned(&settings.algo, select)
+                }
+                DelegateEvent::SwitchToUnalign => view_new.into_unaligned(),
+                otherwise => return (view_new, otherwise),
+            };
+        }
+    }
+    /// Runs a cursive instance showing only the view, until an action has to be delegated.
+    ///
+    /// Returns `DelegateEvent::TerminalError` if the cursive backend could not be started.
+    fn cursive_session(
+        self,
+        settings: &Settings,
+        sources: &[Option<Source>; 2],
+    ) -> (Self, DelegateEvent) {
+        let mut siv = cursive::default();
+        siv.set_theme(cursiv_theme(&settings.style.palette()));
+        siv.set_window_title(self.window_title());
+        let run = |siv: &mut Cursive| {
+            siv.try_run_with(|| {
+                crossterm::Backend::init()
+                    .map(|x| Box::new(BufferedBackend::new(x)) as Box<dyn CursiveBackend>)
+            })
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+        };
+        let keymap = &settings.keymap;
+        match self {
+            HexView::Aligned(a, send, mut recv) => {
+                let (following, files, modified) = (a.dh.follow.clone(), a.files(), a.dh.modified);
+                let watch_send = send.clone();
+                let (stop, stopped) = channel();
+                siv.add_fullscreen_layer(CursiveControl::new(a, "aligned", keymap));
+                let mut sink = siv.cb_sink().clone();
+                // the alignment threads keep sending new blocks while the view is shown
+                let result = scope(|s| {
+                    s.spawn(move |_| {
+                        watch_files(sources, files, modified, &following, stopped, |change| {
+                            watch_send.send(change.aligned(&watch_send)).is_ok()
+                        })
+                    });
+                    let join_handle = s.spawn(|_| cursiv_align_relay(&mut recv, &mut sink));
+                    let result = run(&mut siv);
+                    drop(stop);
+                    // misuse the Action::Quit as a signal for the thread to exit
+                    send.send(AlignedMessage::UserEvent(Action::Quit))
+                        .expect("Could not tell align relay thread to quit");
+                    join_handle
+                        .join()
+                        .expect("Could not join align relay thread");
+                    result
+                })
+                .expect("Could not join align relay thread");
+                match CursiveControl::peel(&mut siv) {
+                    Some((x, quit)) => (
+                        HexView::Aligned(x, send, recv),
+                        result.err().map_or(quit, DelegateEvent::TerminalError),
+                    ),
+                    None => panic!("Internal error, could not downcast view"),
+                }
+            }
+            HexView::Unaligned(u) => {
+                let (following, files, modified) = (u.dh.follow.clone(), u.files(), u.dh.modified);
+                let (stop, stopped) = channel();
+                siv.add_fullscreen_layer(CursiveControl::new(u, "unaligned", keymap));
+                let sink = siv.cb_sink().clone();
+                let result = scope(|s| {
+                    s.spawn(move |_| {
+                        watch_files(sources, files, modified, &following, stopped, |change| {
+                            sink.send(Box::new(|siv: &mut Cursive| {
+                                siv.call_on_name("unaligned", |view: &mut Unaligned| {
+                                    let _ = match change {
+                                        FileChange::Grown(files) => view.grow(&mut Dummy, files),
+                                        FileChange::Modified(modified) => {
+                                            view.set_modified(&mut Dummy, modified)
+                                        }
+                                    };
+                                });
+                            }))
+                            .is_ok()
+                        })
+                    });
+                    let result = run(&mut siv);
+                    drop(stop);
+                    result
+                })
+                .expect("Could not join file watching thread");
+                match CursiveControl::peel(&mut siv) {
+                    Some((v, quit)) => (
+                        HexView::Unaligned(v),
+                        result.err().map_or(quit, DelegateEvent::TerminalError),
+                    ),
+                    None => panic!("Internal error, could not downcast view"),
+                }
+            }
+        }
+    }
     /// Setup a cursive instance and shows a dialog constructed through the callback given in `dialog`.
     ///
     /// Note that the settings are placed into the user_data of the cursive instace and can be modified
@@ -229,10 +924,14 @@ impl HexView {
     fn show_dialog(self, dialog: CursiveCallback, settings: Settings) -> (Self, Settings) {
         let mut siv = cursive::default();
         // this theme is the default theme except that the background color is black
-        siv.set_theme(cursiv_theme());
+        siv.set_theme(cursiv_theme(&settings.style.palette()));
         siv.add_global_callback(Key::Esc, dialog::close_top_maybe_quit);
+        // like the crossterm frontend, the view is ticked when there are no events,
+        // which cursive does at its refresh rate
+        siv.set_fps((1000 / TICK_INTERVAL.as_millis()) as u32);
+        siv.add_global_callback(CursiveEvent::Refresh, tick_view);
         siv.set_user_data(settings);
-        match self {
+        let (mut hv, settings) = match self {
             HexView::Aligned(a, send, mut recv) => {
                 siv.add_fullscreen_layer(a.with_name("aligned").full_screen());
                 let mut sink = siv.cb_sink().clone();
@@ -279,8 +978,44 @@ impl HexView {
                     None => panic!("Internal error, could not downcast view"),
                 }
             }
+        };
+        // the searches send their results to this session, in which the view can not
+        // change, so the results of the ones that were cancelled are lost with it
+        hv.cancel_running_searches();
+        (hv, settings)
+    }
+}
+
+/// Reads the file again from the source, keeping its address unless the file is shorter now.
+/// If the file can not be read, the old one is kept and the reason is added to the notices.
+fn reload_file(old: FileState, source: Option<&Source>, notices: &mut Vec<String>) -> FileState {
+    let source = match source {
+        Some(source) => source,
+        None => {
+            notices.push(format!("{} can not be read again", old.name));
+            return old;
+        }
+    };
+    let mut file = match source.read() {
+        Ok((file, warning)) => {
+            notices.extend(warning);
+            file
         }
+        Err(e) => {
+            notices.push(format!("Could not read {} again: {e}", old.name));
+            return old;
+        }
+    };
+    let len = file.content.len();
+    file.index = old.index;
+    if file.index >= len && file.index > 0 {
+        file.index = len.saturating_sub(1);
+        notices.push(format!(
+            "{} is shorter than the cursor address now",
+            file.name
+        ));
     }
+    file
 }
 
 // this one causes tears to come from my eyes
@@ -293,11 +1028,129 @@ fn peel_onion<V: View>(siv: &mut Cursive) -> Option<V> {
         .and_then(|view| view.into_inner().ok())
 }
 
-/// Default Cursive theme except that the background color is black
-fn cursiv_theme() -> cursive::theme::Theme {
-    use cursive::theme::{BaseColor::*, Color::*, PaletteColor::*};
+/// The views that can be controlled by actions in the cursive frontend
+trait ActionView: View {
+    fn process(&mut self, action: Action);
+    /// The event the action is delegated as, if it is not processed by the view
+    fn delegate(&self, action: Action) -> Option<DelegateEvent> {
+        delegate_action(action)
+    }
+    /// Returns whether the escape was used up by the view, like for clearing the selection
+    fn escape(&mut self) -> bool;
+    /// Whether the view is in edit mode, shared with the event reader
+    fn editing(&self) -> Arc<AtomicBool>;
+    /// Whether the command line of the view is open, shared with the event reader
+    fn prompting(&self) -> Arc<AtomicBool>;
+}
+
+impl ActionView for Aligned {
+    fn process(&mut self, action: Action) {
+        // cursive draws the whole view afterwards, so there is nothing to draw here
+        let _ = self.process_action(&mut Dummy, AlignedMessage::UserEvent(action));
+    }
+    fn escape(&mut self) -> bool {
+        matches!(self.process_escape(&mut Dummy), Ok(true))
+    }
+    fn editing(&self) -> Arc<AtomicBool> {
+        self.dh.editor.active.clone()
+    }
+    fn prompting(&self) -> Arc<AtomicBool> {
+        self.dh.prompt.active.clone()
+    }
+}
+
+impl ActionView for view::Unaligned {
+    fn process(&mut self, action: Action) {
+        let _ = self.process_action(&mut Dummy, action);
+    }
+    fn delegate(&self, action: Action) -> Option<DelegateEvent> {
+        delegate_unaligned_action(action, self.dh.style.single)
+    }
+    fn escape(&mut self) -> bool {
+        matches!(self.process_escape(&mut Dummy), Ok(true))
+    }
+    fn editing(&self) -> Arc<AtomicBool> {
+        self.dh.editor.active.clone()
+    }
+    fn prompting(&self) -> Arc<AtomicBool> {
+        self.dh.prompt.active.clone()
+    }
+}
+
+/// Wraps a view shown by the cursive frontend, translating cursive's events into
+/// actions for the view and quitting the cursive instance when one has to be delegated
+struct CursiveControl<V> {
+    view: ResizedView<NamedView<V>>,
+    reader: CountingReader<KeyMap>,
+    delegated: Option<DelegateEvent>,
+}
+
+impl<V: ActionView> CursiveControl<V> {
+    /// The view can be found by its name like in dialogs, and starts without a count prefix
+    fn new(mut view: V, name: &str, keymap: &KeyMap) -> Self {
+        view.process(Action::PendingCount(None));
+        let (editing, prompting) = (view.editing(), view.prompting());
+        CursiveControl {
+            view: view.with_name(name).full_screen(),
+            reader: CountingReader::new(keymap.clone(), editing, prompting),
+            delegated: None,
+        }
+    }
+    /// Extracts the view and the delegated event from the cursive instance,
+    /// which is `DelegateEvent::Quit` if cursive was quit otherwise (like by ctrl+c)
+    fn peel(siv: &mut Cursive) -> Option<(V, DelegateEvent)> {
+        let control = siv
+            .screen_mut()
+            .remove_layer(LayerPosition::FromBack(0))
+            .downcast::<Self>()
+            .ok()?;
+        let view = control.view.into_inner().ok()?.into_inner().ok()?;
+        Some((view, control.delegated.unwrap_or(DelegateEvent::Quit)))
+    }
+}
+
+impl<V: ActionView> ViewWrapper for CursiveControl<V> {
+    cursive::wrap_impl!(self.view: ResizedView<NamedView<V>>);
+
+    fn wrap_on_event(&mut self, event: CursiveEvent) -> EventResult {
+        let action = match cross_event(&event).and_then(|ev| self.reader.action(ev)) {
+            Some(action) => action,
+            None => return EventResult::Ignored,
+        };
+        let mut view = self.view.get_inner_mut().get_mut();
+        match view.delegate(action) {
+            // like in the crossterm frontend, escape first clears the selection
+            Some(DelegateEvent::Quit | DelegateEvent::OpenDialog(_)) if view.escape() => {
+                EventResult::Consumed(None)
+            }
+            Some(q) => {
+                self.delegated = Some(q);
+                EventResult::with_cb(Cursive::quit)
+            }
+            None => {
+                view.process(action);
+                EventResult::Consumed(None)
+            }
+        }
+    }
+}
+
+/// Ticks the view of the cursive instance at its refresh rate, since neither the view
+/// nor the dialogs above it use the refresh events
+fn tick_view(siv: &mut Cursive) {
+    siv.call_on_name("aligned", |view: &mut Aligned| view.process(Action::Tick))
+        .or_else(|| {
+            siv.call_on_name("unaligned", |view: &mut Unaligned| {
+                view.process(Action::Tick)
+            })
+        });
+}
+
+/// Default Cursive theme except that the background color is the one of our palette
+fn cursiv_theme(palette: &Palette) -> cursive::theme::Theme {
+    use cursive::theme::PaletteColor::*;
     let mut cursiv_theme = cursive::theme::load_default();
-    cursiv_theme.palette[Background] = Dark(Black);
+    cursiv_theme.palette[Background] = palette.background.to_cursiv();
     cursiv_theme
 }
 
@@ -309,7 +1162,7 @@ fn cursiv_align_relay(recv: &mut Receiver<AlignedMessage>, sink: &mut cursive::C
             otherwise => {
                 sink.send(Box::new(|siv: &mut Cursive| {
                     siv.call_on_name("aligned", |view: &mut Aligned| {
-                        view.process_action(&mut Dummy, otherwise);
+                        let _ = view.process_action(&mut Dummy, otherwise);
                     })
                     .expect("Could not send new data to view");
                 }))
@@ -319,12 +1172,113 @@ fn cursiv_align_relay(recv: &mut Receiver<AlignedMessage>, sink: &mut cursive::C
     }
 }
 
+/// How often the files are checked for changes on disk
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Changes of the shown files on disk
+enum FileChange {
+    /// the files have grown in follow mode
+    Grown([FileContent; 2]),
+    /// whether the files changed since they were read
+    Modified([bool; 2]),
+}
+
+impl FileChange {
+    /// The message for the aligned view, which continues aligning grown files over `send`
+    fn aligned(self, send: &Sender<AlignedMessage>) -> AlignedMessage {
+        match self {
+            FileChange::Grown(files) => AlignedMessage::Grown(files, send.clone()),
+            FileChange::Modified(modified) => AlignedMessage::Modified(modified),
+        }
+    }
+}
+
+impl From<FileChange> for UnalignedMessage {
+    fn from(change: FileChange) -> Self {
+        match change {
+            FileChange::Grown(files) => UnalignedMessage::Grown(files),
+            FileChange::Modified(modified) => UnalignedMessage::Modified(modified),
+        }
+    }
+}
+
+/// Checks whether the files changed on disk since the shown contents were read
+/// and, while they are followed, reads the bytes appended to them.
+/// The changes are passed to `changed` until `stop` is disconnected or it returns false.
+/// Files that can not be read at the moment are tried again the next time.
+fn watch_files(
+    sources: &[Option<Source>; 2],
+    mut files: [FileContent; 2],
+    mut modified: [bool; 2],
+    following: &AtomicBool,
+    stop: Receiver<()>,
+    mut changed: impl FnMut(FileChange) -> bool,
+) {
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(WATCH_INTERVAL) {
+        if following.load(Ordering::Relaxed) {
+            let mut grown = false;
+            for (file, source) in files.iter_mut().zip(sources) {
+                if let Some(Ok(Some(content))) = source.as_ref().map(|s| s.read_grown(file)) {
+                    *file = content;
+                    grown = true;
+                }
+            }
+            if grown && !changed(FileChange::Grown(files.clone())) {
+                return;
+            }
+        }
+        let now = [0, 1].map(|i| {
+            sources[i]
+                .as_ref()
+                .map_or(false, |source| source.modified(&files[i]))
+        });
+        if now != modified {
+            modified = now;
+            if !changed(FileChange::Modified(modified)) {
+                return;
+            }
+        }
+    }
+}
+
 /// This enum is used for delegating actions to higher level event loops.
 enum DelegateEvent {
     Quit,
     SwitchToAlign,
     SwitchToUnalign,
     OpenDialog(CursiveCallback),
+    ReloadFiles,
+    SwapFiles,
+    /// the terminal is restored while the process is stopped from the shell
+    Suspend,
+    /// the command typed into the command line is run, which is done in a cursive session
+    /// like the dialogs, since a search shows its progress there
+    RunCommand,
+    /// drawing to the terminal failed even after redrawing a few times
+    TerminalError(std::io::Error),
+}
+
+/// How often the whole view is redrawn after drawing failed before giving up
+const REDRAW_ATTEMPTS: usize = 3;
+
+/// Checks the result of drawing to the terminal, and on failure retries by redrawing
+/// the whole view with `redraw`, which gets a message about the error for the bottom line.
+/// Returns the last error if none of the attempts succeed.
+fn retry_redraw(
+    result: std::io::Result<()>,
+    mut redraw: impl FnMut(Option<String>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut error = match result {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    for _ in 0..REDRAW_ATTEMPTS {
+        match redraw(Some(format!("Drawing failed: {error}"))) {
+            Ok(()) => return Ok(()),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
 }
 
 /// Converts an event to a delegation
@@ -333,46 +1287,144 @@ fn delegate_action(action: Action) -> Option<DelegateEvent> {
         Action::Quit => Some(DelegateEvent::Quit),
         Action::Align => Some(DelegateEvent::SwitchToAlign),
         Action::Unalign => Some(DelegateEvent::SwitchToUnalign),
+        Action::ReloadFiles => Some(DelegateEvent::ReloadFiles),
+        Action::SwapFiles => Some(DelegateEvent::SwapFiles),
+        Action::Suspend => Some(DelegateEvent::Suspend),
+        Action::Prompt(PromptKey::Enter) => Some(DelegateEvent::RunCommand),
         Action::Algorithm => Some(DelegateEvent::OpenDialog(Box::new(dialog::settings))),
         Action::Goto => Some(DelegateEvent::OpenDialog(Box::new(dialog::goto))),
+        Action::FollowPointer => Some(DelegateEvent::OpenDialog(Box::new(dialog::follow_pointer))),
+        Action::SaveAs => Some(DelegateEvent::OpenDialog(Box::new(dialog::save_as))),
+        Action::Fill => Some(DelegateEvent::OpenDialog(Box::new(dialog::fill))),
+        Action::SetMark => Some(DelegateEvent::OpenDialog(Box::new(dialog::set_mark))),
+        Action::Marks => Some(DelegateEvent::OpenDialog(Box::new(dialog::marks))),
         Action::Search => Some(DelegateEvent::OpenDialog(Box::new(dialog::search))),
+        Action::ExportSearch => Some(DelegateEvent::OpenDialog(Box::new(dialog::export_search))),
+        Action::ExportHtml => Some(DelegateEvent::OpenDialog(Box::new(dialog::export_html))),
+        Action::Hash => Some(DelegateEvent::OpenDialog(Box::new(dialog::hash))),
+        Action::ByteStats => Some(DelegateEvent::OpenDialog(Box::new(dialog::byte_stats))),
         Action::SetOffset => Some(DelegateEvent::OpenDialog(Box::new(dialog::set_offset))),
-        Action::Help => Some(DelegateEvent::OpenDialog(Box::new(dialog::help_window(
-            dialog::MAIN_HELP,
-        )))),
+        Action::AutoOffset => Some(DelegateEvent::OpenDialog(Box::new(dialog::auto_offset))),
+        Action::Help => Some(DelegateEvent::OpenDialog(Box::new(dialog::main_help))),
         _otherwise => None,
     }
 }
 
+/// Converts an event of the unaligned view to a delegation. When only one file is shown,
+/// the actions comparing the files are left to the view, which tells that they are not available.
+fn delegate_unaligned_action(action: Action, single: bool) -> Option<DelegateEvent> {
+    if single && action.compares_files() {
+        None
+    } else {
+        delegate_action(action)
+    }
+}
+
+/// Takes the messages waiting in `recv` that repeat the step `action`, like the ones that
+/// pile up while an arrow key is held down over a slow connection, so that they are
+/// drawn once. Returns how often the step is done in total and the first message
+/// that is something else, if any.
+fn coalesce_steps<M>(
+    recv: &Receiver<M>,
+    action: Action,
+    user_event: fn(&M) -> Option<Action>,
+) -> (usize, Option<M>) {
+    let mut steps = 1;
+    while let Ok(msg) = recv.try_recv() {
+        if user_event(&msg) != Some(action) {
+            return (steps, Some(msg));
+        }
+        steps += 1;
+    }
+    (steps, None)
+}
+
 /// This function is the one that processes actions sent by the event reader loop
 /// setup in `unaligned_cross`. Note that the event reader loop has to stay in the same
 /// thread, so this process is chosen to not be in the main thread instead.
-fn unaligned_cross_recv(
+fn unaligned_cross_recv<B: Backend>(
     unaligned: &mut view::Unaligned,
-    cross: &mut Cross,
-    recv: Receiver<Action>,
+    cross: &mut B,
+    recv: Receiver<UnalignedMessage>,
 ) -> DelegateEvent {
-    unaligned.refresh(cross);
-    for action in recv.iter() {
-        if let Some(q) = delegate_action(action) {
-            return q;
+    let mut next = None;
+    while let Some(msg) = next.take().or_else(|| recv.recv().ok()) {
+        let result = match msg {
+            UnalignedMessage::UserEvent(action) => {
+                if let Some(q) = delegate_unaligned_action(action, unaligned.dh.style.single) {
+                    return q;
+                }
+                // a count prefix is not combined with the repetitions
+                if action.is_step() && unaligned.dh.pending_count.is_none() {
+                    let (steps, rest) = coalesce_steps(&recv, action, |msg| match msg {
+                        UnalignedMessage::UserEvent(action) => Some(*action),
+                        _ => None,
+                    });
+                    unaligned.dh.pending_count = (steps > 1).then_some(steps);
+                    next = rest;
+                }
+                unaligned.process_action(cross, action)
+            }
+            UnalignedMessage::Grown(files) => unaligned.grow(cross, files),
+            UnalignedMessage::Modified(modified) => unaligned.set_modified(cross, modified),
+        };
+        if let Err(e) = retry_redraw(result, |notice| {
+            unaligned.dh.notice = notice.map(|text| Notice::new(Severity::Error, text));
+            unaligned.resize(cross.size());
+            unaligned.redraw(cross, true)
+        }) {
+            return DelegateEvent::TerminalError(e);
         }
-        unaligned.process_action(cross, action);
     }
     DelegateEvent::Quit
 }
 
 /// This setups the event processing thread for the crossterm backend and reads crossterm's events
-fn unaligned_cross(unaligned: &mut view::Unaligned, cross: &mut Cross) -> DelegateEvent {
-    unaligned.refresh(cross);
+fn unaligned_cross(
+    unaligned: &mut view::Unaligned,
+    cross: &mut Cross,
+    keymap: &KeyMap,
+    sources: &[Option<Source>; 2],
+    script: &mut Script,
+) -> DelegateEvent {
+    // the new event reader starts without a count prefix
+    unaligned.dh.pending_count = None;
+    let result = unaligned.refresh(cross);
+    if let Err(e) = retry_redraw(result, |notice| {
+        unaligned.dh.notice = notice.map(|text| Notice::new(Severity::Error, text));
+        unaligned.resize(cross.size());
+        unaligned.redraw(cross, true)
+    }) {
+        return DelegateEvent::TerminalError(e);
+    }
     let (mut send, recv) = channel();
+    let following = unaligned.dh.follow.clone();
+    let editing = unaligned.dh.editor.active.clone();
+    let prompting = unaligned.dh.prompt.active.clone();
+    let (files, modified) = (unaligned.files(), unaligned.dh.modified);
+    let single = unaligned.dh.style.single;
+    let watch_send = send.clone();
+    let (stop, stopped) = channel();
     let mut quit = DelegateEvent::Quit;
     scope(|s| {
+        s.spawn(move |_| {
+            watch_files(sources, files, modified, &following, stopped, |change| {
+                watch_send.send(change.into()).is_ok()
+            })
+        });
         // both this thread and the send_cross_actions function determine when to quit by
-        // checking the output of delegate_action, so make sure this is the same
+        // checking the output of delegate_unaligned_action, so make sure this is the same
         let receiver_thread = s.spawn(|_| unaligned_cross_recv(unaligned, cross, recv));
-        send_cross_actions(|action| delegate_action(action).is_some(), &mut send);
+        send_cross_actions(
+            |action| delegate_unaligned_action(action, single).is_some(),
+            keymap,
+            editing,
+            prompting,
+            &mut send,
+            script,
+        );
         quit = receiver_thread.join().unwrap();
+        drop(stop);
     })
     .unwrap();
     quit
@@ -387,17 +1439,34 @@ fn aligned_cross_recv(
     cross: &mut Cross,
     recv: &mut Receiver<AlignedMessage>,
 ) -> DelegateEvent {
-    for msg in recv.iter() {
+    let mut next = None;
+    while let Some(msg) = next.take().or_else(|| recv.recv().ok()) {
         let msg = match msg {
             AlignedMessage::UserEvent(action) => {
                 if let Some(q) = delegate_action(action) {
                     return q;
                 }
+                // a count prefix is not combined with the repetitions
+                if action.is_step() && aligned.dh.pending_count.is_none() {
+                    let (steps, rest) = coalesce_steps(recv, action, |msg| match msg {
+                        AlignedMessage::UserEvent(action) => Some(*action),
+                        _ => None,
+                    });
+                    aligned.dh.pending_count = (steps > 1).then_some(steps);
+                    next = rest;
+                }
                 msg
             }
             _ => msg,
         };
-        aligned.process_action(cross, msg);
+        let result = aligned.process_action(cross, msg);
+        if let Err(e) = retry_redraw(result, |notice| {
+            aligned.dh.notice = notice.map(|text| Notice::new(Severity::Error, text));
+            aligned.resize(cross.size());
+            aligned.redraw(cross, true)
+        }) {
+            return DelegateEvent::TerminalError(e);
+        }
     }
     DelegateEvent::Quit
 }
@@ -408,18 +1477,283 @@ fn aligned_cross_recv(
 fn aligned_cross(
     aligned: &mut view::Aligned,
     cross: &mut Cross,
+    keymap: &KeyMap,
+    sources: &[Option<Source>; 2],
     send: &mut Sender<AlignedMessage>,
     recv: &mut Receiver<AlignedMessage>,
+    script: &mut Script,
 ) -> DelegateEvent {
-    aligned.refresh(cross);
+    // the new event reader starts without a count prefix
+    aligned.dh.pending_count = None;
+    let result = aligned.refresh(cross);
+    if let Err(e) = retry_redraw(result, |notice| {
+        aligned.dh.notice = notice.map(|text| Notice::new(Severity::Error, text));
+        aligned.resize(cross.size());
+        aligned.redraw(cross, true)
+    }) {
+        return DelegateEvent::TerminalError(e);
+    }
+    let following = aligned.dh.follow.clone();
+    let editing = aligned.dh.editor.active.clone();
+    let prompting = aligned.dh.prompt.active.clone();
+    let (files, modified) = (aligned.files(), aligned.dh.modified);
+    let watch_send = send.clone();
+    let (stop, stopped) = channel();
     let mut quit = DelegateEvent::Quit;
     scope(|s| {
+        s.spawn(move |_| {
+            watch_files(sources, files, modified, &following, stopped, |change| {
+                watch_send.send(change.aligned(&watch_send)).is_ok()
+            })
+        });
         // both the thread and the send_cross_actions function determine when to quit by
         // checking the output of delegate_action, so make sure this is the same.
         let receiver_thread = s.spawn(|_| aligned_cross_recv(aligned, cross, recv));
-        send_cross_actions(|action| delegate_action(action).is_some(), send);
+        send_cross_actions(
+            |action| delegate_action(action).is_some(),
+            keymap,
+            editing,
+            prompting,
+            send,
+            script,
+        );
         quit = receiver_thread.join().unwrap();
+        drop(stop);
     })
     .unwrap();
     quit
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        backend::capture::Capture,
+        search::{Query, QueryType, SearchMessage, SearchOptions, SearchStatus},
+        testing::{file, temp_path},
+    };
+
+    #[test]
+    fn coalesce_steps() {
+        let (send, recv) = channel();
+        for action in [Action::Down, Action::Down, Action::Down, Action::Up] {
+            send.send(UnalignedMessage::UserEvent(action)).unwrap();
+        }
+        send.send(UnalignedMessage::UserEvent(Action::Tick))
+            .unwrap();
+        let user_event = |msg: &UnalignedMessage| match msg {
+            UnalignedMessage::UserEvent(action) => Some(*action),
+            _ => None,
+        };
+        // the first down was already taken by the loop
+        recv.recv().unwrap();
+        let (steps, rest) = super::coalesce_steps(&recv, Action::Down, user_event);
+        assert_eq!(steps, 3);
+        assert!(matches!(
+            rest,
+            Some(UnalignedMessage::UserEvent(Action::Up))
+        ));
+        let (steps, rest) = super::coalesce_steps(&recv, Action::Up, user_event);
+        assert_eq!(steps, 1);
+        assert!(matches!(
+            rest,
+            Some(UnalignedMessage::UserEvent(Action::Tick))
+        ));
+        assert_eq!(super::coalesce_steps(&recv, Action::Up, user_event).0, 1);
+    }
+
+    #[test]
+    fn replay_session() {
+        let log = concat!(
+            "{\"columns\":80,\"rows\":10}\n",
+            "{\"ms\":0,\"action\":\"Down\"}\n",
+            "{\"ms\":20,\"action\":{\"PendingCount\":3}}\n",
+            "{\"ms\":40,\"action\":\"Right\"}\n",
+            "{\"ms\":60,\"action\":\"CursorFirst\"}\n",
+            "{\"ms\":80,\"action\":\"Right\"}\n",
+            "{\"ms\":100,\"action\":\"Quit\"}\n",
+        );
+        let mut script = Script {
+            recorder: None,
+            replay: Some(Replay::parse(log, 0.0).unwrap()),
+        };
+        let size = script.size().unwrap();
+        let mut dh = DoubleHexContext::new(size);
+        dh.style.addr_width = 2;
+        dh.style.ascii_col = true;
+        let content = b"0123456789abcdef".repeat(8);
+        let (first, second) = (file("first", &content), file("second", &content[1..]));
+        let mut view = view::Unaligned::new(first, second, dh);
+        view.resize(size);
+        let mut capture = Capture::new(size.0, size.1);
+        view.refresh(&mut capture).unwrap();
+        // the log ends with quitting, so the keyboard is never read
+        let (mut send, recv) = channel();
+        send_cross_actions(
+            |action| delegate_unaligned_action(action, false).is_some(),
+            &KeyMap::default(),
+            Arc::default(),
+            Arc::default(),
+            &mut send,
+            &mut script,
+        );
+        let quit = unaligned_cross_recv(&mut view, &mut capture, recv);
+        assert!(matches!(quit, DelegateEvent::Quit));
+        // a row down and three bytes right, then one more with only the first cursor active,
+        // which moves the cursor on the screen and so the addresses of both files
+        let row = view.dh.cursor.bytes_per_row();
+        assert_eq!(view.cursor_addresses(), [Some(row + 4), Some(row + 4)]);
+        assert_eq!(view.dh.cursor_act, CursorActive::First);
+        // the screen drawn step by step is the one of the final state
+        let mut redrawn = Capture::new(size.0, size.1).without_scroll();
+        view.redraw(&mut redrawn, true).unwrap();
+        assert_eq!(capture.annotated(), redrawn.annotated());
+    }
+
+    #[test]
+    fn start_aligned() {
+        let algo = AlignAlgorithm::default();
+        let start = |second: &[u8], align| {
+            HexView::starting(
+                file("first", b"some bytes"),
+                file("second", second),
+                align,
+                None,
+            )
+        };
+        assert!(matches!(
+            start(b"some other bytes", None),
+            HexView::Unaligned(_)
+        ));
+        assert!(matches!(
+            start(b"some other bytes", Some(&algo)),
+            HexView::Aligned(..)
+        ));
+        match start(b"", Some(&algo)) {
+            HexView::Unaligned(v) => assert_eq!(
+                v.dh.notice_text(),
+                Some("Not aligning at the start, second is empty")
+            ),
+            HexView::Aligned(..) => panic!("empty file was aligned"),
+        }
+    }
+
+    #[test]
+    fn searches_across_views() {
+        let query = Query::new(QueryType::Text, "bytes", 0).unwrap();
+        let view = HexView::new(
+            file("first", b"some bytes, more bytes"),
+            file("second", b"other bytes"),
+        );
+        let add = |view: &mut HexView, first, results: Vec<SearchMessage>| match view {
+            HexView::Aligned(v, _, _) => v.add_search_results(query.clone(), results, first),
+            HexView::Unaligned(v) => v.add_search_results(query.clone(), results, first),
+        };
+        let current = |view: &HexView| match view {
+            HexView::Aligned(v, _, _) => v.current_search_query().cloned(),
+            HexView::Unaligned(v) => v.current_search_query().cloned(),
+        };
+        let mut view = match view {
+            HexView::Unaligned(mut v) => {
+                v.setup_search(query.clone(), SearchOptions::default());
+                HexView::Unaligned(v)
+            }
+            HexView::Aligned(..) => panic!("new view is aligned"),
+        };
+        // the results keep arriving in whichever view is shown
+        add(&mut view, true, vec![SearchMessage::Match(5..10)]);
+        let mut view = view.into_aligned(&AlignAlgorithm::default(), [None, None]);
+        assert!(matches!(view, HexView::Aligned(..)));
+        assert_eq!(current(&view).as_ref(), Some(&query));
+        add(&mut view, false, vec![SearchMessage::Match(6..11)]);
+        let mut view = view.into_unaligned();
+        add(&mut view, true, vec![SearchMessage::Match(17..22)]);
+        add(&mut view, true, vec![SearchMessage::Done]);
+        let mut view = view.into_aligned(&AlignAlgorithm::default(), [None, None]);
+        // the session the second search sends its results to ends before it is done
+        view.cancel_running_searches();
+        let mut view = view.into_unaligned();
+        assert_eq!(current(&view).as_ref(), Some(&query));
+        view.cancel_running_searches();
+        let (first, second, _) = match view {
+            HexView::Unaligned(v) => v.destruct().ok().unwrap(),
+            HexView::Aligned(..) => panic!("view was not unaligned"),
+        };
+        let matches = |file: &FileState| file.search[0].matches().collect::<Vec<_>>();
+        assert_eq!(matches(&first), vec![5..10, 17..22]);
+        assert_eq!(matches(&second), vec![6..11]);
+        assert_eq!(first.search[0].status(), SearchStatus::Finished);
+        assert_eq!(second.search[0].status(), SearchStatus::Cancelled);
+    }
+
+    #[test]
+    fn reload() {
+        let path = temp_path("reload");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let source = Source {
+            path: path.clone().into(),
+            label: Some(String::from("second")),
+            decompress: false,
+            window: crate::file::Window::default(),
+            unit: crate::file::Unit::Byte,
+        };
+        let (mut second, _) = source.read().unwrap();
+        second.index = 8;
+        let view = HexView::starting(file("first", b"some bytes"), second, None, None);
+        std::fs::write(&path, b"01234").unwrap();
+        let view = view.reload(&[None, Some(source)], &AlignAlgorithm::default());
+        std::fs::remove_file(&path).unwrap();
+        let view = match view {
+            HexView::Unaligned(v) => v,
+            HexView::Aligned(..) => panic!("unaligned view was aligned"),
+        };
+        assert_eq!(
+            view.dh.notice_text(),
+            Some("first can not be read again, second is shorter than the cursor address now")
+        );
+        let (first, second, _) = match view.destruct() {
+            Ok(parts) => parts,
+            Err(_) => panic!("could not destruct view"),
+        };
+        assert_eq!(&first.content[..], b"some bytes");
+        assert_eq!(&second.content[..], b"01234");
+        assert_eq!(second.index, 4);
+    }
+
+    #[test]
+    fn reload_self_diff() {
+        let path = temp_path("self");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let sources = ["file @A", "file @B"].map(|label| Source {
+            path: path.clone().into(),
+            label: Some(String::from(label)),
+            decompress: false,
+            window: crate::file::Window::default(),
+            unit: crate::file::Unit::Byte,
+        });
+        let (first, _) = sources[0].read().unwrap();
+        let second = FileState {
+            name: String::from("file @B"),
+            index: 8,
+            ..first.shared()
+        };
+        let view = HexView::starting(first, second, None, None);
+        std::fs::write(&path, b"01234").unwrap();
+        let view = view.reload(&sources.map(Some), &AlignAlgorithm::default());
+        std::fs::remove_file(&path).unwrap();
+        let (first, second, _) = match view {
+            HexView::Unaligned(v) => v.destruct().ok().unwrap(),
+            HexView::Aligned(..) => panic!("unaligned view was aligned"),
+        };
+        // the file is read once, and both halves keep their names and share its bytes
+        assert_eq!(&first.content[..], b"01234");
+        assert!(Arc::ptr_eq(&first.content, &second.content));
+        assert_eq!(
+            (first.name.as_str(), second.name.as_str()),
+            ("file @A", "file @B")
+        );
+        assert_eq!([first.index, second.index], [0, 4]);
+    }
+}
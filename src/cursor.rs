@@ -1,6 +1,12 @@
-use std::ops::Range;
+use std::{collections::VecDeque, ops::Range};
+
+pub use biodiff_core::search::CursorActive;
 
 const VERTICAL_CURSOR_PAD: usize = 2;
+/// The fewest rows of bytes that leave room for the cursor between the pads
+pub const MIN_ROWS: usize = 2 * VERTICAL_CURSOR_PAD + 1;
+/// The number of positions that are remembered for jumping back
+const JUMP_HISTORY_LEN: usize = 100;
 
 /// Keeps track of display dimensions and cursor position
 #[derive(Debug, Clone)]
@@ -9,6 +15,9 @@ pub struct CursorState {
     size: (usize, usize),
     cursor_pos: (usize, usize),
     bytes_per_row: usize,
+    /// the column that vertical moves go back to when a row is wide enough again,
+    /// if they had to leave it in a row that is partially out of bounds
+    sticky_x: Option<usize>,
 }
 
 impl CursorState {
@@ -17,13 +26,14 @@ impl CursorState {
             size,
             cursor_pos: (0, VERTICAL_CURSOR_PAD),
             bytes_per_row: size.0,
+            sticky_x: None,
         }
     }
     /// Updates the screen size, changing the cursor position if neccessary.
     /// Returns the difference of the base address of the cursor view.
     pub fn resize(&mut self, size: (usize, usize), bytes_per_row: usize) -> isize {
         // refuse to resize too small and just keep the old size and draw nonsense instead
-        if size.0 < 1 || size.1 < 2 * VERTICAL_CURSOR_PAD + 1 {
+        if size.0 < 1 || size.1 < MIN_ROWS {
             return 0;
         }
         let prev_index = self.get_index();
@@ -32,6 +42,10 @@ impl CursorState {
         // will keep addresses mostly aligned with 8 (or 4 for smaller sizes)
         self.cursor_pos.0 %= self.get_size_x();
         self.cursor_pos.1 = self.get_y().clamp(self.min_row(), self.max_row());
+        // the columns mean something else with other rows
+        if self.bytes_per_row != bytes_per_row {
+            self.sticky_x = None;
+        }
         self.bytes_per_row = bytes_per_row;
         prev_index as isize - self.get_index() as isize
     }
@@ -78,6 +92,7 @@ impl CursorState {
     /// Returns the change of position of the underlying view into
     /// the grid
     pub fn move_cursor_unbounded(&mut self, xdiff: isize, ydiff: isize) -> isize {
+        self.sticky_x = None;
         let new_x = self.get_x() as isize + xdiff;
         let new_y = self.get_y() as isize + ydiff;
         let actual_x = new_x.clamp(0, self.size.0 as isize - 1);
@@ -115,12 +130,32 @@ impl CursorState {
         }
         self.move_cursor_unbounded(self.restrict_xdiff(xdiff, bounds), 0)
     }
-    /// move cursor in y direction without going out of bounds
+    /// move cursor in y direction without going out of bounds.
+    /// Rows that are partially out of bounds are entered at their nearest byte,
+    /// and the column of the cursor is restored when a row is wide enough again.
     pub fn move_cursor_y_bounded(&mut self, ydiff: isize, bounds: Range<isize>) -> isize {
         if bounds.is_empty() {
             return 0;
         }
-        self.move_cursor_unbounded(0, self.restrict_ydiff(ydiff, bounds))
+        let width = self.bytes_per_row as isize;
+        let last_column = self.size.0.min(self.bytes_per_row) as isize - 1;
+        let y = self.get_y() as isize;
+        // the rows that have at least one byte within bounds
+        let first_row = -(last_column - bounds.start).div_euclid(width);
+        let last_row = (bounds.end - 1).div_euclid(width);
+        let new_y = (y + ydiff).clamp(first_row, last_row.max(first_row));
+        let row_start = new_y * width;
+        let column = self.sticky_x.unwrap_or_else(|| self.get_x());
+        let new_x = (column as isize)
+            .min(last_column)
+            .min(bounds.end - 1 - row_start)
+            .max(bounds.start - row_start)
+            .max(0);
+        let diff = self.move_cursor_unbounded(new_x - self.get_x() as isize, new_y - y);
+        if new_x != column as isize {
+            self.sticky_x = Some(column);
+        }
+        diff
     }
     /// move the view in x direction without going out of bounds
     pub fn move_view_x_bounded(&mut self, xdiff: isize, bounds: Range<isize>) -> isize {
@@ -135,6 +170,7 @@ impl CursorState {
             + old_cursor_x
             - new_cursor_x;
         self.cursor_pos = (new_cursor_x as usize, self.get_y());
+        self.sticky_x = None;
 
         actual_xdiff
     }
@@ -154,6 +190,14 @@ impl CursorState {
 
         actual_ydiff * self.bytes_per_row as isize
     }
+    /// move the view and the cursor on it by whole rows without going out of bounds,
+    /// keeping the cursor in its row on the screen
+    pub fn move_page_bounded(&mut self, ydiff: isize, bounds: Range<isize>) -> isize {
+        if bounds.is_empty() {
+            return 0;
+        }
+        self.restrict_ydiff(ydiff, bounds) * self.bytes_per_row as isize
+    }
     /// moves according to the information in the move struct without going
     /// out of bounds
     pub fn mov(&mut self, movement: Move, bounds: Range<isize>) -> isize {
@@ -163,6 +207,12 @@ impl CursorState {
             Move::CursorY(ydiff) => self.move_cursor_y_bounded(ydiff, bounds),
             Move::ViewX(xdiff) => self.move_view_x_bounded(xdiff, bounds),
             Move::ViewY(ydiff) => self.move_view_y_bounded(ydiff, bounds),
+            Move::Page(ydiff) => self.move_page_bounded(ydiff, bounds),
+            Move::RowStart => self.move_cursor_x_bounded(-(self.get_x() as isize), bounds),
+            Move::RowEnd => {
+                let last = self.size.0.min(self.bytes_per_row) - 1;
+                self.move_cursor_x_bounded(last as isize - self.get_x() as isize, bounds)
+            }
         }
     }
     /// returns Some(amount of rows) if the difference given as argument
@@ -184,37 +234,52 @@ impl CursorState {
     }
 }
 
-/// An enum for keeping track which views a cursor is enabled in
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CursorActive {
-    Both,
-    First,
-    Second,
-    None,
+/// The shown addresses of the cursors before jumps, for going back and forth
+/// between them like with Ctrl-O and Ctrl-I in vim
+#[derive(Debug, Clone, Default)]
+pub struct JumpHistory {
+    positions: VecDeque<[Option<usize>; 2]>,
+    /// the entry that was last gone back or forward to, which is the length
+    /// of the history if the last jump was not one of those
+    current: usize,
 }
-impl CursorActive {
-    /// Cursor is enabled on the first view
-    pub fn is_first(&self) -> bool {
-        match self {
-            Self::Both | Self::First => true,
-            Self::Second | Self::None => false,
+
+impl JumpHistory {
+    /// Remembers the position before a jump, forgetting the positions that were gone back over
+    pub fn push(&mut self, position: [Option<usize>; 2]) {
+        self.positions.truncate(self.current);
+        if self.positions.back() != Some(&position) {
+            self.positions.push_back(position);
+        }
+        if self.positions.len() > JUMP_HISTORY_LEN {
+            self.positions.pop_front();
         }
+        self.current = self.positions.len();
     }
-    /// Cursor is enabled on the second view
-    pub fn is_second(&self) -> bool {
-        match self {
-            Self::Both | Self::Second => true,
-            Self::First | Self::None => false,
+    /// The position to go back to from `position`, which is remembered for going forward again
+    pub fn back(&mut self, position: [Option<usize>; 2]) -> Option<[Option<usize>; 2]> {
+        if self.current == self.positions.len() {
+            if self.positions.back() != Some(&position) {
+                self.positions.push_back(position);
+            }
+            self.current = self.positions.len().saturating_sub(1);
         }
+        self.current = self.current.checked_sub(1)?;
+        Some(self.positions[self.current])
     }
-    /// List of whether view is enabled
-    pub fn is_active(&self) -> [bool; 2] {
-        match self {
-            Self::Both => [true, true],
-            Self::First => [true, false],
-            Self::Second => [false, true],
-            Self::None => [false, false],
+    /// The position to go forward to after going back
+    pub fn forward(&mut self) -> Option<[Option<usize>; 2]> {
+        if self.current + 1 >= self.positions.len() {
+            return None;
         }
+        self.current += 1;
+        Some(self.positions[self.current])
+    }
+    /// Exchanges the addresses of the files, for when they are swapped
+    pub fn swap_files(&mut self) {
+        self.positions
+            .iter_mut()
+            .for_each(|position| position.swap(0, 1));
     }
 }
 
@@ -225,6 +290,12 @@ pub enum Move {
     CursorY(isize),
     ViewX(isize),
     ViewY(isize),
+    /// a page of rows, which moves the view and the cursor together
+    Page(isize),
+    /// to the first byte of the row that is within bounds
+    RowStart,
+    /// to the last byte of the row that is within bounds
+    RowEnd,
 }
 
 impl Move {
@@ -236,9 +307,91 @@ impl Move {
             // unbounded are used by internal functions,
             // who do not have a sense of right or left
             // so we do not have to invert x in this case
-            Move::Unbounded(_, _) | Move::CursorY(_) | Move::ViewY(_) => self,
+            Move::Unbounded(_, _) | Move::CursorY(_) | Move::ViewY(_) | Move::Page(_) => self,
             Move::CursorX(x) => Move::CursorX(-x),
             Move::ViewX(x) => Move::ViewX(-x),
+            // the start of the row is the first byte, wherever it is shown
+            Move::RowStart | Move::RowEnd => self,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_history() {
+        let mut history = JumpHistory::default();
+        assert_eq!(history.back([Some(0), Some(0)]), None);
+        history.push([Some(1), Some(2)]);
+        history.push([Some(3), None]);
+        assert_eq!(history.back([Some(5), Some(5)]), Some([Some(3), None]));
+        assert_eq!(history.back([Some(3), None]), Some([Some(1), Some(2)]));
+        assert_eq!(history.back([Some(1), Some(2)]), None);
+        assert_eq!(history.forward(), Some([Some(3), None]));
+        assert_eq!(history.forward(), Some([Some(5), Some(5)]));
+        assert_eq!(history.forward(), None);
+        // a new jump forgets the positions after the current one
+        history.back([Some(5), Some(5)]);
+        history.push([Some(3), None]);
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back([Some(7), Some(7)]), Some([Some(3), None]));
+        assert_eq!(history.back([Some(3), None]), Some([Some(1), Some(2)]));
+    }
+
+    #[test]
+    fn row_start_end() {
+        let mut cursor = CursorState::new((8, 10));
+        cursor.mov(Move::CursorX(3), 0..100);
+        assert_eq!(cursor.mov(Move::RowEnd, 0..100), 0);
+        assert_eq!(cursor.get_x(), 7);
+        assert_eq!(cursor.mov(Move::RowStart, 0..100), 0);
+        assert_eq!(cursor.get_x(), 0);
+        // rows that are partially outside the bounds stop at the bounds
+        cursor.mov(Move::CursorX(4), 0..100);
+        cursor.mov(Move::RowStart, 18..100);
+        assert_eq!(cursor.get_x(), 2);
+        cursor.mov(Move::RowEnd, 0..20);
+        assert_eq!(cursor.get_x(), 3);
+        // only the bytes of the row when there are more columns than bytes
+        cursor.resize((8, 10), 6);
+        cursor.mov(Move::RowEnd, 0..100);
+        assert_eq!(cursor.get_x(), 5);
+        assert_eq!(Move::RowStart.reflect_rtl(), Move::RowStart);
+    }
+
+    #[test]
+    fn sticky_column() {
+        let mut cursor = CursorState::new((8, 10));
+        cursor.mov(Move::CursorX(6), 0..100);
+        // the last row only has 3 bytes
+        cursor.mov(Move::CursorY(2), 0..35);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (2, 4));
+        assert_eq!(cursor.mov(Move::CursorY(1), 0..35), 0);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (2, 4));
+        cursor.mov(Move::CursorY(-1), 0..35);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (6, 3));
+        // moving sideways forgets the column
+        cursor.mov(Move::CursorY(1), 0..35);
+        cursor.mov(Move::CursorX(-1), 0..35);
+        cursor.mov(Move::CursorY(-1), 0..35);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (1, 3));
+
+        let mut cursor = CursorState::new((8, 10));
+        cursor.mov(Move::CursorY(2), 0..100);
+        // the first row only has the last 3 bytes
+        cursor.mov(Move::CursorY(-1), 29..100);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (5, 3));
+        cursor.mov(Move::CursorY(-1), 29..100);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (5, 3));
+        // resizes keep the column as long as the rows stay the same
+        cursor.resize((8, 12), 8);
+        cursor.mov(Move::CursorY(1), 0..100);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (0, 4));
+        cursor.mov(Move::CursorY(-1), 29..100);
+        cursor.resize((8, 12), 6);
+        cursor.mov(Move::CursorY(1), 0..100);
+        assert_eq!((cursor.get_x(), cursor.get_y()), (5, 4));
+    }
+}
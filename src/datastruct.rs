@@ -60,6 +60,11 @@ impl<T: Clone> DoubleVec<T> {
     pub fn last(&self) -> Option<&T> {
         self.end.last().or_else(|| self.front.first())
     }
+
+    /// Changes every element in place
+    pub fn for_each_mut(&mut self, f: impl FnMut(&mut T)) {
+        self.front.iter_mut().chain(self.end.iter_mut()).for_each(f);
+    }
 }
 
 impl<T: Clone> SignedArray for DoubleVec<T> {
@@ -119,6 +124,13 @@ impl CompVec {
     pub fn add_second_shift(&mut self, relative_shift: isize) -> isize {
         -relative_shift + self.modify_shift(relative_shift)
     }
+    /// Swaps the two arrays, returns the change of index to the same element
+    pub fn swap(&mut self) -> isize {
+        std::mem::swap(&mut self.xvec, &mut self.yvec);
+        let old_shift = self.shift;
+        self.shift = -old_shift;
+        -old_shift
+    }
     /// Gets the address of the left vector at a given index
     pub fn get_first_addr(&self, index: isize) -> Option<usize> {
         if index < 0 || self.xvec.len() <= index as usize {
@@ -294,6 +306,21 @@ mod tests {
         );
     }
     #[test]
+    fn compvec_swap() {
+        let mut cv = CompVec::new(
+            std::sync::Arc::new(b"abcd".to_vec().into()),
+            std::sync::Arc::new(b"xy".to_vec().into()),
+        );
+        cv.shift = 1;
+        assert_eq!(cv.get(2), (Some(b'c'), Some(b'y')));
+        // the same bytes are at the returned change of index
+        let change = cv.swap();
+        assert_eq!(cv.get(2 + change), (Some(b'y'), Some(b'c')));
+        assert_eq!(cv.bounds(), -1..3);
+        assert_eq!(cv.swap() + change, 0);
+        assert_eq!(cv.shift, 1);
+    }
+    #[test]
     fn bsearch() {
         let mut v = DoubleVec::new();
         v.extend_front(&[1, 2, 3, 7, 10, 13]);
@@ -1,19 +1,32 @@
 mod algorithm_settings;
+mod command;
+mod export_html;
+mod export_search;
+mod fill;
 mod goto;
+mod hash;
+mod marks;
+mod pointer;
+mod save_as;
 mod search;
 mod set_offset;
 mod settings;
+mod stats;
 mod style_settings;
 use crate::{
     align::{
-        AlignAlgorithm, AlignMode, Banded, FlatAlignProgressMessage, FlatAlignmentContext,
-        DEFAULT_BLOCKSIZE, DEFAULT_KMER, DEFAULT_WINDOW,
+        aligners, AlignAlgorithm, AlignMode, AlignerChoice, Banded, FlatAlignProgressMessage,
+        FlatAlignmentContext, DEFAULT_BLOCKSIZE, DEFAULT_KMER, DEFAULT_WINDOW,
     },
     backend::Dummy,
+    capabilities,
     control::Settings,
-    file::FileContent,
-    search::{Query, QueryType, SearchContext},
-    style::{ColumnSetting, DisplayMode, Style},
+    hash::{Digests, Hasher},
+    notice::Severity,
+    search::{Query, QueryType, SearchContext, SearchMessage, SearchOptions},
+    selection::{process_selected, Accumulator, ChunkMessage, SelectedBytes},
+    stats::Histogram,
+    style::{AddressColumn, ColumnSetting, DeltaMode, DisplayMode, Style, TextEncoding},
     util::{self, Finalable},
     view::{Aligned, Unaligned},
 };
@@ -28,8 +41,7 @@ use cursive::{
 };
 use std::{
     fmt::Display,
-    num::ParseIntError,
-    ops::Range,
+    path::PathBuf,
     str::FromStr,
     sync::{
         atomic::{AtomicBool, AtomicUsize},
@@ -40,10 +52,19 @@ use std::{
 const TEXT_WIDTH: usize = 6;
 
 pub use algorithm_settings::algorithm;
+pub use command::command;
+pub use export_html::export_html;
+pub use export_search::export_search;
+pub use fill::fill;
 pub use goto::goto;
+pub use hash::hash;
+pub use marks::{marks, set_mark};
+pub use pointer::follow_pointer;
+pub use save_as::{save_as, unsaved_edits};
 pub use search::search;
-pub use set_offset::set_offset;
+pub use set_offset::{auto_offset, set_offset};
 pub use settings::settings;
+pub use stats::byte_stats;
 pub use style_settings::style;
 
 /// A box that changes color when the content is invalid
@@ -96,6 +117,66 @@ where
         .expect("Could not find aligned or unaligned view in cursive stack")
 }
 
+const PROCESS_PROGRESS: &str = "process progress";
+
+/// Processes the selected bytes of the active files (or the whole files without
+/// a selection) with the accumulator on another thread, showing the progress
+/// until `done` is called with the results, unless it was cancelled
+fn process_with_progress<A>(
+    siv: &mut Cursive,
+    what: &str,
+    done: fn(&mut Cursive, &[SelectedBytes], Vec<A::Output>),
+) where
+    A: Accumulator + 'static,
+    A::Output: Send + 'static,
+{
+    let selected = on_hexview(siv, |v| v.selected_bytes(), |v| v.selected_bytes());
+    let total = selected.iter().map(|part| part.range.len()).sum::<usize>();
+    let is_running = Arc::new(AtomicBool::new(true));
+    let counter = Counter(Arc::new(AtomicUsize::new(0)));
+    let stop = is_running.clone();
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(format!(
+                    "{what} {}...",
+                    util::human_size(total)
+                )))
+                .child(
+                    ProgressBar::new()
+                        .min(0)
+                        .max(total.max(1))
+                        .with_value(counter.clone())
+                        .min_width(16),
+                ),
+        )
+        .button("Cancel", move |s| {
+            stop.store(false, std::sync::atomic::Ordering::Relaxed);
+            close_top_maybe_quit(s)
+        })
+        .with_name(PROCESS_PROGRESS),
+    );
+    let sink = siv.cb_sink().clone();
+    let parts = selected.clone();
+    let send = move |message: ChunkMessage<A::Output>| {
+        let parts = parts.clone();
+        let counter = counter.clone();
+        sink.send(Box::new(move |siv: &mut Cursive| match message {
+            ChunkMessage::Progress(processed) => counter.set(processed),
+            ChunkMessage::Done(results) => {
+                // it was cancelled if the progress is not shown anymore
+                if siv.find_name::<Dialog>(PROCESS_PROGRESS).is_some() {
+                    siv.pop_layer();
+                    done(siv, &parts, results)
+                }
+            }
+            ChunkMessage::Cancelled => (),
+        }))
+        .is_ok()
+    };
+    process_selected::<A, _>(selected, is_running, send)
+}
+
 /// We only want to quit cursive and return to our crossterm native implementation
 /// when no other windows are open. This function wraps that behaviour.
 pub fn close_top_maybe_quit(siv: &mut Cursive) {
@@ -111,11 +192,86 @@ pub fn close_top_maybe_quit(siv: &mut Cursive) {
 
 /// A help window that displays a fixed text.
 pub fn help_window(help_text: &'static str) -> impl Fn(&mut Cursive) {
+    move |siv| show_help(siv, help_text)
+}
+
+fn show_help(siv: &mut Cursive, help_text: &str) {
+    siv.add_layer(
+        Dialog::around(ScrollView::new(TextView::new(help_text)))
+            .title("Help")
+            .button("Close", close_top_maybe_quit),
+    )
+}
+
+const HELP_TEXT: &str = "help text";
+const HELP_SCROLL: &str = "help scroll";
+const HELP_FILTER: &str = "help filter";
+
+/// The text of the main help window, with the actions of the current keymap.
+/// With a filter, only the actions containing it are shown.
+fn main_help_text(siv: &mut Cursive, filter: &str) -> String {
+    let keys = siv
+        .user_data::<Settings>()
+        .map(|settings| settings.keymap.help_text(filter))
+        .unwrap_or_default();
+    match (filter.trim().is_empty(), keys.is_empty()) {
+        (true, _) => MAIN_HELP.replace("{keys}", &keys),
+        (false, true) => format!("No action contains \"{}\".", filter.trim()),
+        (false, false) => format!("Actions containing \"{}\":\n{keys}", filter.trim()),
+    }
+}
+
+/// The main help window, which lists the actions with the keys of the current keymap.
+/// '/' moves to a filter below the text for finding an action by its name or description.
+pub fn main_help(siv: &mut Cursive) {
+    let text = main_help_text(siv, "");
+    let filter = EditView::new()
+        .on_edit_mut(|siv, filter, _| {
+            let text = main_help_text(siv, filter);
+            siv.call_on_name(HELP_TEXT, |v: &mut TextView| v.set_content(text));
+            siv.call_on_name(HELP_SCROLL, |v: &mut ScrollView<NamedView<TextView>>| {
+                v.scroll_to_top()
+            });
+        })
+        // enter goes back to scrolling through the filtered actions
+        .on_submit(|siv, _| {
+            let _ = siv.focus_name(HELP_SCROLL);
+        })
+        .with_name(HELP_FILTER)
+        .full_width();
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(
+                        ScrollView::new(TextView::new(text).with_name(HELP_TEXT))
+                            .with_name(HELP_SCROLL),
+                    )
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(TextView::new("Filter (/): "))
+                            .child(filter),
+                    ),
+            )
+            .title("Help")
+            .button("Close", close_top_maybe_quit),
+        )
+        .on_event('/', |siv| {
+            let _ = siv.focus_name(HELP_FILTER);
+        }),
+    )
+}
+
+/// A window listing the lines of the keymap file that could not be used
+pub fn keymap_errors(errors: Vec<String>) -> impl Fn(&mut Cursive) {
     move |siv| {
         siv.add_layer(
-            Dialog::around(ScrollView::new(TextView::new(help_text)))
-                .title("Help")
-                .button("Close", close_top_maybe_quit),
+            Dialog::around(ScrollView::new(TextView::new(format!(
+                "The following lines of the keymap were ignored:\n\n{}",
+                errors.join("\n")
+            ))))
+            .title("Invalid key bindings")
+            .button("Continue", close_top_maybe_quit),
         )
     }
 }
@@ -124,5 +280,8 @@ pub const MAIN_HELP: &str = include_str!("help/main.txt");
 pub const ALGORITHM_HELP: &str = include_str!("help/algorithm.txt");
 pub const STYLE_HELP: &str = include_str!("help/style.txt");
 pub const GOTO_HELP: &str = include_str!("help/goto.txt");
+pub const MARKS_HELP: &str = include_str!("help/marks.txt");
+pub const POINTER_HELP: &str = include_str!("help/pointer.txt");
+pub const SAVE_AS_HELP: &str = include_str!("help/save_as.txt");
 pub const SEARCH_HELP: &str = include_str!("help/search.txt");
 pub const SET_OFFSET_HELP: &str = include_str!("help/set_offset.txt");
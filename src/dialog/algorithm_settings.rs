@@ -21,6 +21,12 @@ fn apply_algorithm(siv: &mut Cursive) {
         &mut errors,
     );
     parse_box(siv, "match score", &mut algorithm.match_score, &mut errors);
+    if let Some(aligner) = siv
+        .call_on_name("aligner", |v: &mut SelectView<AlignerChoice>| v.selection())
+        .flatten()
+    {
+        algorithm.aligner = *aligner;
+    }
 
     // read band settings
     if siv
@@ -82,8 +88,26 @@ pub fn algorithm(siv: &mut Cursive) -> impl View {
     // * gap extend penalty
     // * mismatch score
     // * match score
+    // * the aligner
     // * whether the banded algorithm is used
+    let aligners = aligners();
+    let aligner_index = aligners
+        .iter()
+        .position(|aligner| aligner.name() == algorithm.aligner.0.name())
+        .unwrap_or(0);
     let right_always_list = ListView::new()
+        .child(
+            "Aligner:",
+            SelectView::new()
+                .popup()
+                .with_all(
+                    aligners
+                        .into_iter()
+                        .map(|aligner| (aligner.name(), AlignerChoice(aligner))),
+                )
+                .selected(aligner_index)
+                .with_name("aligner"),
+        )
         .child(
             "Gap Open:",
             validated_box(
@@ -0,0 +1,438 @@
+use super::*;
+use crate::theme::Theme;
+use std::ffi::OsStr;
+
+/// A command typed into the command line
+#[derive(Debug, PartialEq, Eq)]
+enum Command<'a> {
+    /// addresses like the ones of the goto dialog
+    Goto(&'a str),
+    /// settings of the style with their values, like `columns=16`
+    Set(Vec<(&'a str, Option<&'a str>)>),
+    /// the mode or aligner of the algorithm with parameters like `gap_open=-5`
+    Algo(&'a str, Vec<&'a str>),
+    /// a regex between slashes, or text
+    Search(QueryType, &'a str),
+    /// a file the view is exported to, which is chosen by its extension
+    Write(&'a str),
+}
+
+/// Splits the command line into the command and its arguments
+fn parse(line: &str) -> Result<Command<'_>, String> {
+    let line = line.trim();
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let needs = |what: &str| Err(format!("{name} needs {what}"));
+    match name {
+        "goto" if rest.is_empty() => needs("an address, like goto 0x4000"),
+        "goto" => Ok(Command::Goto(rest)),
+        "set" if rest.is_empty() => needs("a setting, like set columns=16"),
+        "set" => Ok(Command::Set(
+            rest.split_whitespace()
+                .map(|setting| match setting.split_once('=') {
+                    Some((key, value)) => (key, Some(value)),
+                    None => (setting, None),
+                })
+                .collect(),
+        )),
+        "algo" => {
+            let mut words = rest.split_whitespace();
+            match words.next() {
+                Some(algorithm) => Ok(Command::Algo(algorithm, words.collect())),
+                None => needs("an algorithm, like algo global"),
+            }
+        }
+        "search" => match rest.strip_prefix('/').map(|regex| regex.strip_suffix('/')) {
+            Some(Some(regex)) if !regex.is_empty() => Ok(Command::Search(QueryType::Regex, regex)),
+            Some(_) => Err(String::from(
+                "A regex goes between slashes, like search /ab+c/",
+            )),
+            None if rest.is_empty() => needs("a /regex/ or a text"),
+            None => Ok(Command::Search(QueryType::Text, rest)),
+        },
+        "w" if rest.is_empty() => needs("a file, like w diff.html"),
+        "w" => Ok(Command::Write(rest)),
+        _ => Err(format!(
+            "Unknown command \"{name}\", try goto, set, algo, search or w"
+        )),
+    }
+}
+
+/// The value of the choices with the name, ignoring the case
+fn choice<T: Copy>(key: &str, value: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    choices
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, choice)| *choice)
+        .ok_or_else(|| {
+            let names = choices.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            format!("{key} is one of {}", names.join(", "))
+        })
+}
+
+/// The switches of the style by their names in config.json
+fn switch<'a>(style: &'a mut Style, key: &str) -> Option<&'a mut bool> {
+    Some(match key {
+        "ascii_col" => &mut style.ascii_col,
+        "bars_col" => &mut style.bars_col,
+        "vertical" => &mut style.vertical,
+        "right_to_left" => &mut style.right_to_left,
+        "no_scroll" => &mut style.no_scroll,
+        "show_index" => &mut style.show_index,
+        "ruler" => &mut style.ruler,
+        "diff_rows" => &mut style.diff_rows,
+        "fold" => &mut style.fold,
+        "diff_map" => &mut style.diff_map,
+        "byte_classes" => &mut style.byte_classes,
+        "entropy" => &mut style.entropy,
+        "word_wrap" => &mut style.word_wrap,
+        _ => return None,
+    })
+}
+
+/// Changes a setting of the style, which has the name of config.json except for `columns`.
+/// Like in vim, a switch is turned on by its name alone and off with `no` in front of it.
+fn set_style(style: &mut Style, key: &str, value: Option<&str>) -> Result<(), String> {
+    if let Some(on) = switch(style, key) {
+        *on = match value {
+            None | Some("on" | "true") => true,
+            Some("off" | "false") => false,
+            Some(value) => return Err(format!("{key} is on or off, not \"{value}\"")),
+        };
+        return Ok(());
+    }
+    if let (Some(key), None) = (key.strip_prefix("no"), value) {
+        if let Some(on) = switch(style, key) {
+            *on = false;
+            return Ok(());
+        }
+    }
+    let value = match value {
+        Some(value) => value,
+        None if key == "columns" => "",
+        None => return Err(format!("{key} needs a value, like {key}=...")),
+    };
+    match key {
+        "columns" => {
+            style.column_count = match value {
+                "fit" => ColumnSetting::Fit,
+                value => match value.parse() {
+                    Ok(ColumnSetting::Fixed(0) | ColumnSetting::Multiple(0)) => {
+                        return Err(String::from("columns must not be zero"))
+                    }
+                    Ok(columns) => columns,
+                    Err(e) => return Err(format!("Could not parse columns: {e}")),
+                },
+            };
+            // the columns set here are the ones of both halves
+            style.second_column_count = None;
+        }
+        "group" => style.group = choice(key, value, &[("1", 1), ("2", 2), ("4", 4), ("8", 8)])?,
+        "mode" => {
+            style.mode = choice(
+                key,
+                value,
+                &[
+                    ("hex", DisplayMode::Hex),
+                    ("binary", DisplayMode::Binary),
+                    ("decimal", DisplayMode::Decimal),
+                    ("octal", DisplayMode::Octal),
+                    ("mixed", DisplayMode::HexAsciiMix),
+                    ("braille", DisplayMode::Braille),
+                    ("roman", DisplayMode::Roman),
+                ],
+            )?
+        }
+        "encoding" => {
+            style.encoding = choice(
+                key,
+                value,
+                &[
+                    ("ascii", TextEncoding::Ascii),
+                    ("latin1", TextEncoding::Latin1),
+                    ("ebcdic", TextEncoding::Ebcdic),
+                    ("utf8", TextEncoding::Utf8),
+                ],
+            )?
+        }
+        "address_column" => {
+            style.address_column = choice(
+                key,
+                value,
+                &[
+                    ("full", AddressColumn::Full),
+                    ("short", AddressColumn::Short),
+                    ("hidden", AddressColumn::Hidden),
+                ],
+            )?
+        }
+        "delta" => {
+            style.delta = choice(
+                key,
+                value,
+                &[
+                    ("off", DeltaMode::Off),
+                    ("xor", DeltaMode::Xor),
+                    ("delta", DeltaMode::Delta),
+                ],
+            )?
+        }
+        "theme" => {
+            style.theme = choice(
+                key,
+                value,
+                &[("dark", Theme::Dark), ("light", Theme::Light)],
+            )?
+        }
+        _ => return Err(format!("Unknown setting \"{key}\"")),
+    }
+    Ok(())
+}
+
+/// Changes the algorithm to the mode (global, local or blockwise) or the aligner of the name,
+/// along with parameters like `gap_open=-5`
+fn set_algorithm(algo: &mut AlignAlgorithm, name: &str, params: &[&str]) -> Result<(), String> {
+    match name {
+        "global" => algo.mode = AlignMode::Global,
+        "local" => algo.mode = AlignMode::Local,
+        "blockwise" if !matches!(algo.mode, AlignMode::Blockwise(_)) => {
+            algo.mode = AlignMode::Blockwise(DEFAULT_BLOCKSIZE)
+        }
+        "blockwise" => (),
+        name => match AlignerChoice::by_name(name) {
+            Some(aligner) => algo.aligner = aligner,
+            None => {
+                let aligners = aligners()
+                    .iter()
+                    .map(|aligner| aligner.name())
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "Unknown algorithm \"{name}\", try global, local, blockwise or {}",
+                    aligners.join(", ")
+                ));
+            }
+        },
+    }
+    for param in params {
+        let (key, value) = param
+            .split_once('=')
+            .ok_or_else(|| format!("The parameter \"{param}\" is not like gap_open=-5"))?;
+        let score = || {
+            value
+                .parse::<i32>()
+                .map_err(|e| format!("Could not parse {key}: {e}"))
+        };
+        let penalty = || match score()? {
+            penalty if penalty > 0 => Err(format!("{key} must not be positive")),
+            penalty => Ok(penalty),
+        };
+        match key {
+            "gap_open" => algo.gap_open = penalty()?,
+            "gap_extend" => algo.gap_extend = penalty()?,
+            "match_score" => algo.match_score = score()?,
+            "mismatch_score" => algo.mismatch_score = score()?,
+            "block_size" => match value.parse::<usize>() {
+                Ok(size) if size > 0 => algo.mode = AlignMode::Blockwise(size),
+                _ => return Err(format!("{key} is a positive number")),
+            },
+            "banded" => {
+                algo.band = match choice(key, value, &[("on", true), ("off", false)])? {
+                    true => Banded::Banded {
+                        kmer: DEFAULT_KMER,
+                        window: DEFAULT_WINDOW,
+                    },
+                    false => Banded::Normal,
+                }
+            }
+            _ => return Err(format!("Unknown parameter \"{key}\" of the algorithm")),
+        }
+    }
+    Ok(())
+}
+
+/// Exports the view to the path, as an HTML page of the alignment or the search results as CSV
+fn write_file(siv: &mut Cursive, path: &str) -> Result<String, String> {
+    let path = PathBuf::from(path);
+    match path.extension().and_then(OsStr::to_str) {
+        Some("html" | "htm") => on_hexview(siv, |v| v.export_html(&path), |v| v.export_html(&path))
+            .map_err(|e| format!("Could not write the page: {e}")),
+        Some("csv") => on_hexview(
+            siv,
+            |v| v.export_search(&path, false),
+            |v| v.export_search(&path, false),
+        )
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                format!("{} already exists, choose a new file", path.display())
+            }
+            _ => format!("Could not write search results: {e}"),
+        }),
+        _ => Err(String::from(
+            "w writes an HTML page (.html) or the search results (.csv)",
+        )),
+    }?;
+    Ok(format!("Wrote {}", path.display()))
+}
+
+/// Runs the command, returning a message for the bottom line
+fn run(siv: &mut Cursive, command: Command) -> Result<Option<String>, String> {
+    match command {
+        Command::Goto(addresses) => {
+            // goto shows its own notes and errors
+            goto::goto_addresses(siv, addresses);
+            Ok(None)
+        }
+        Command::Set(settings) => {
+            let mut style = siv.user_data::<Settings>().unwrap().style;
+            for (key, value) in settings {
+                set_style(&mut style, key, value)?;
+            }
+            siv.user_data::<Settings>().unwrap().style = style;
+            on_hexview(siv, |v| v.dh.style = style, |v| v.dh.style = style);
+            Ok(None)
+        }
+        Command::Algo(name, params) => {
+            let mut algo = siv.user_data::<Settings>().unwrap().algo;
+            set_algorithm(&mut algo, name, &params)?;
+            siv.user_data::<Settings>().unwrap().algo = algo;
+            Ok(Some(String::from(
+                "The next alignment uses the new algorithm (F3 aligns)",
+            )))
+        }
+        Command::Search(query_type, text) => {
+            let query = Query::new(query_type, text, 0).map_err(|e| e.to_string())?;
+            search::run_search(siv, query, SearchOptions::default());
+            Ok(None)
+        }
+        Command::Write(path) => write_file(siv, path).map(Some),
+    }
+}
+
+/// Runs the command typed into the command line of the view, with errors shown in its
+/// bottom line. The cursive session only stays for the progress of a search.
+pub fn command(siv: &mut Cursive) {
+    let line = on_hexview(siv, |v| v.dh.prompt.submit(), |v| v.dh.prompt.submit());
+    // an empty command just closes the command line
+    let result = match line.trim().is_empty() {
+        true => Ok(None),
+        false => parse(&line).and_then(|command| run(siv, command)),
+    };
+    let notice = match result {
+        Ok(message) => message.map(|message| (Severity::Info, message)),
+        Err(e) => Some((Severity::Error, e)),
+    };
+    if let Some((severity, text)) = notice {
+        on_hexview(
+            siv,
+            |v| v.dh.set_notice(severity, text.clone()),
+            |v| v.dh.set_notice(severity, text.clone()),
+        );
+    }
+    if siv.screen().len() <= 1 {
+        siv.quit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse(" goto  0x4000 "), Ok(Command::Goto("0x4000")));
+        assert_eq!(
+            parse("set columns=24 noascii_col"),
+            Ok(Command::Set(vec![
+                ("columns", Some("24")),
+                ("noascii_col", None)
+            ]))
+        );
+        assert_eq!(
+            parse("algo blockwise block_size=512"),
+            Ok(Command::Algo("blockwise", vec!["block_size=512"]))
+        );
+        assert_eq!(
+            parse("search /a b+/"),
+            Ok(Command::Search(QueryType::Regex, "a b+"))
+        );
+        assert_eq!(
+            parse("search PK"),
+            Ok(Command::Search(QueryType::Text, "PK"))
+        );
+        assert_eq!(parse("w out.html"), Ok(Command::Write("out.html")));
+        for bad in [
+            "goto",
+            "set",
+            "algo",
+            "search /ab",
+            "search //",
+            "w",
+            "q",
+            "",
+        ] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn style() {
+        let mut style = Style::default();
+        for (key, value) in [
+            ("columns", Some("24")),
+            ("ascii_col", Some("off")),
+            ("vertical", None),
+            ("nodiff_map", None),
+            ("mode", Some("Binary")),
+            ("group", Some("4")),
+            ("theme", Some("light")),
+        ] {
+            set_style(&mut style, key, value).unwrap();
+        }
+        assert_eq!(style.column_count, ColumnSetting::Fixed(24));
+        assert!(!style.ascii_col && style.vertical && !style.diff_map);
+        assert!(matches!(style.mode, DisplayMode::Binary));
+        assert_eq!((style.group, style.theme), (4, Theme::Light));
+        set_style(&mut style, "columns", Some("4x")).unwrap();
+        assert_eq!(style.column_count, ColumnSetting::Multiple(4));
+        set_style(&mut style, "columns", None).unwrap();
+        assert_eq!(style.column_count, ColumnSetting::Fit);
+        for (key, value) in [
+            ("columns", Some("many")),
+            ("columns", Some("0")),
+            ("ascii_col", Some("maybe")),
+            ("group", Some("3")),
+            ("mode", None),
+            ("nomode", None),
+            ("colour", Some("red")),
+        ] {
+            assert!(set_style(&mut style, key, value).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn algorithm() {
+        let mut algo = AlignAlgorithm::default();
+        set_algorithm(&mut algo, "global", &["gap_open=-8", "banded=on"]).unwrap();
+        assert!(matches!(algo.mode, AlignMode::Global));
+        assert_eq!(algo.gap_open, -8);
+        assert!(matches!(algo.band, Banded::Banded { .. }));
+        set_algorithm(&mut algo, "blockwise", &["block_size=512"]).unwrap();
+        assert!(matches!(algo.mode, AlignMode::Blockwise(512)));
+        // the block size stays when only the mode is given
+        set_algorithm(&mut algo, "blockwise", &[]).unwrap();
+        assert!(matches!(algo.mode, AlignMode::Blockwise(512)));
+        set_algorithm(&mut algo, "rust-bio", &[]).unwrap();
+        assert_eq!(algo.aligner.0.name(), "rust-bio");
+        for bad in [
+            "fast",
+            "global gap_open=5",
+            "global gap_open",
+            "local speed=2",
+        ] {
+            let mut words = bad.split_whitespace();
+            let name = words.next().unwrap();
+            let params = words.collect::<Vec<_>>();
+            assert!(set_algorithm(&mut algo, name, &params).is_err(), "{bad}");
+        }
+    }
+}
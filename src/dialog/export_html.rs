@@ -0,0 +1,37 @@
+use super::*;
+const EXPORT_PATH: &str = "html export path";
+
+/// A dialog for writing the aligned files as an HTML page with their colors
+pub fn export_html(siv: &mut Cursive) {
+    let dialog = Dialog::around(
+        ListView::new().child(
+            "File:",
+            EditView::new()
+                .content("diff.html")
+                .on_submit(|s, _| on_export(s))
+                .with_name(EXPORT_PATH)
+                .min_width(32),
+        ),
+    )
+    .title("Export as HTML")
+    .button("Export", on_export)
+    .button("Cancel", close_top_maybe_quit);
+    siv.add_layer(dialog)
+}
+
+/// writes the page and closes the export dialog, or shows an error
+fn on_export(siv: &mut Cursive) {
+    let path = siv
+        .call_on_name(EXPORT_PATH, |view: &mut EditView| {
+            PathBuf::from(view.get_content().as_ref())
+        })
+        .unwrap();
+    match on_hexview(siv, |v| v.export_html(&path), |v| v.export_html(&path)) {
+        Ok(()) => close_top_maybe_quit(siv),
+        Err(e) => siv.add_layer(
+            Dialog::text(format!("Could not write the page: {e}"))
+                .title("Error in export!")
+                .button("Continue", close_top_maybe_quit),
+        ),
+    }
+}
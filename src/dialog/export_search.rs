@@ -0,0 +1,84 @@
+use super::*;
+const EXPORT_PATH: &str = "export path";
+const EXPORT_BYTES: &str = "export bytes";
+
+/// A dialog for writing the search results of both files into a CSV file
+pub fn export_search(siv: &mut Cursive) {
+    let dialog = OnEventView::new(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "File:",
+                    EditView::new()
+                        .content("search_results.csv")
+                        .on_submit(|s, _| on_export(s))
+                        .with_name(EXPORT_PATH)
+                        .min_width(32),
+                )
+                .child("Matched Bytes:", Checkbox::new().with_name(EXPORT_BYTES)),
+        )
+        .title("Export Search Results")
+        .button("Export", on_export)
+        .button("Cancel", close_top_maybe_quit)
+        .button("Help", help_window(SEARCH_HELP)),
+    )
+    .on_event(Key::F1, help_window(SEARCH_HELP));
+    siv.add_layer(dialog)
+}
+
+/// Action to execute when submitting the export,
+/// asking for confirmation if the results are not complete yet
+fn on_export(siv: &mut Cursive) {
+    let path = siv
+        .call_on_name(EXPORT_PATH, |view: &mut EditView| {
+            view.get_content().as_ref().clone()
+        })
+        .unwrap();
+    let with_bytes = siv
+        .find_name::<Checkbox>(EXPORT_BYTES)
+        .unwrap()
+        .is_checked();
+    let is_running = on_hexview(siv, |v| v.is_search_running(), |v| v.is_search_running());
+    if !is_running {
+        write_export(siv, &path, with_bytes);
+        return;
+    }
+    siv.add_layer(
+        Dialog::text(
+            "The search is still running, only the results found until now would be exported.",
+        )
+        .title("Search still running")
+        .button("Export anyway", move |s| {
+            s.pop_layer();
+            write_export(s, &path, with_bytes)
+        })
+        .button("Cancel", close_top_maybe_quit),
+    )
+}
+
+/// writes the export file and closes the export dialog, or shows an error
+fn write_export(siv: &mut Cursive, path: &str, with_bytes: bool) {
+    let file = PathBuf::from(path);
+    let f1 = file.clone();
+    match on_hexview(
+        siv,
+        move |v| v.export_search(&f1, with_bytes),
+        move |v| v.export_search(&file, with_bytes),
+    ) {
+        Ok(()) => close_top_maybe_quit(siv),
+        Err(e) => {
+            // like saving the edits, existing files are never overwritten
+            let text = match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    format!("{path} already exists, choose a new file")
+                }
+                _ => format!("Could not write search results: {e}"),
+            };
+            siv.add_layer(
+                Dialog::text(text)
+                    .title("Error in export!")
+                    .button("Continue", close_top_maybe_quit),
+            )
+        }
+    }
+}
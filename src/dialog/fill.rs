@@ -0,0 +1,64 @@
+use super::*;
+
+const FILL_BYTE: &str = "fill byte";
+const ONLY_UNALIGNED: &str = "Editing is only possible in the unaligned view (F2)";
+
+/// A dialog for overwriting the selected bytes of the active file with the same byte,
+/// like zeros or NOPs
+pub fn fill(siv: &mut Cursive) {
+    let range = on_hexview(
+        siv,
+        |_| Err(String::from(ONLY_UNALIGNED)),
+        |v| v.fill_range(),
+    );
+    let len = match range {
+        Ok((_, range)) => range.len(),
+        Err(e) => return error_dialog(siv, e),
+    };
+    siv.add_layer(
+        Dialog::around(
+            ListView::new().child(
+                "Byte (hex):",
+                EditView::new()
+                    .content("00")
+                    .on_submit(|siv, _| on_fill(siv))
+                    .with_name(FILL_BYTE)
+                    .fixed_width(4),
+            ),
+        )
+        .title(format!("Fill {len} bytes"))
+        .button("Fill", on_fill)
+        .button("Cancel", close_top_maybe_quit),
+    );
+}
+
+/// Fills the selected bytes with the byte of the dialog
+fn on_fill(siv: &mut Cursive) {
+    let text = siv
+        .call_on_name(FILL_BYTE, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let text = text.trim();
+    let byte = match u8::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16) {
+        Ok(byte) => byte,
+        Err(e) => return error_dialog(siv, format!("The byte '{text}' is invalid: {e}")),
+    };
+    let result = on_hexview(
+        siv,
+        |_| Err(String::from(ONLY_UNALIGNED)),
+        |v| {
+            let (second, range) = v.fill_range()?;
+            let notice = format!("Filled {} bytes with {byte:02x}", range.len());
+            v.dh.set_notice(Severity::Info, notice);
+            v.fill(second, range, byte);
+            Ok(())
+        },
+    );
+    match result {
+        Ok(()) => close_top_maybe_quit(siv),
+        Err(e) => error_dialog(siv, e),
+    }
+}
+
+fn error_dialog(siv: &mut Cursive, text: String) {
+    siv.add_layer(Dialog::text(text).button("Continue", close_top_maybe_quit));
+}
@@ -1,64 +1,446 @@
 use super::*;
+use crate::{cursor::CursorActive, doublehex::DoubleHexContext, file::Unit};
+use std::ops::Range;
 
-fn parse_hex(s: &str) -> Result<(usize, Option<usize>), ParseIntError> {
-    let mut split = s.splitn(2, '|');
-    let to_int = |s: &str| usize::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16);
-    let first = to_int(split.next().unwrap())?;
-    let second = split.next().map(to_int).transpose()?;
-    Ok((first, second))
+const GOTO_ADDRESS: &str = "goto address";
+const GOTO_ERROR: &str = "goto error";
+
+/// What the addresses typed into goto can refer to in one of the files,
+/// or what the indexes can refer to in the alignment
+#[derive(Clone, Debug)]
+struct GotoFile {
+    /// the shown address of the cursor, if it is inside of the file
+    cursor: Option<i128>,
+    /// the shown addresses of the file
+    range: Range<i128>,
+    /// what the addresses are in, for the notes about clamping
+    place: &'static str,
+    /// the number of units in a byte if the bytes of the file are split, where the typed
+    /// numbers are bytes that can be followed by the unit in them, like `0x12:5`
+    per_byte: usize,
 }
 
-/// A dialog to go to a given position in the hexview
-pub fn goto(siv: &mut Cursive) {
-    let call_goto = move |siv: &mut Cursive, s: &str| {
-        let result = parse_hex(s)
-            .map_err(|e| e.to_string())
-            .and_then(|(first, second)| {
-                on_hexview(
+impl GotoFile {
+    fn file(cursor: Option<usize>, range: Range<usize>, unit: Unit) -> Self {
+        GotoFile {
+            cursor: cursor.map(|cursor| cursor as i128),
+            range: range.start as i128..range.end as i128,
+            place: "file",
+            per_byte: unit.per_byte(),
+        }
+    }
+    fn alignment(index: isize, bounds: Range<isize>) -> Self {
+        GotoFile {
+            cursor: Some(index as i128),
+            range: bounds.start as i128..bounds.end as i128,
+            place: "alignment",
+            per_byte: 1,
+        }
+    }
+    /// Shows an address for the notes, with the unit in the byte for split bytes
+    fn show(&self, value: i128) -> String {
+        let per_byte = self.per_byte as i128;
+        match per_byte {
+            1 => signed_hex(value),
+            _ => format!(
+                "{}:{}",
+                signed_hex(value.div_euclid(per_byte)),
+                value.rem_euclid(per_byte)
+            ),
+        }
+    }
+}
+
+/// Where goto jumps to
+#[derive(Debug, PartialEq, Eq)]
+enum GotoTarget {
+    /// addresses of the files, like the arguments of `goto` of the views
+    Addresses(usize, Option<usize>),
+    /// an index into the alignment of the aligned view
+    Index(isize),
+}
+
+/// Everything the input of goto can refer to
+#[derive(Clone, Debug)]
+struct GotoContext {
+    files: [GotoFile; 2],
+    /// the alignment, if the aligned view is active
+    alignment: Option<GotoFile>,
+    cursor_act: CursorActive,
+}
+
+/// An error in the typed addresses, at the position of a character (counting from 1)
+#[derive(Debug, PartialEq, Eq)]
+struct GotoError {
+    pos: usize,
+    msg: String,
+}
+
+impl Display for GotoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.msg, self.pos)
+    }
+}
+
+/// A recursive descent parser for the address expressions of one file
+struct AddressParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    /// the position of the first character in the whole input
+    offset: usize,
+    file: &'a GotoFile,
+}
+
+impl<'a> AddressParser<'a> {
+    fn error<T>(&self, msg: &str) -> Result<T, GotoError> {
+        Err(GotoError {
+            pos: self.offset + self.pos + 1,
+            msg: msg.to_string(),
+        })
+    }
+    /// The next character that is not whitespace
+    fn peek(&mut self) -> Option<char> {
+        while self
+            .chars
+            .get(self.pos)
+            .map_or(false, |c| c.is_whitespace())
+        {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+    fn checked(&self, value: Option<i128>) -> Result<i128, GotoError> {
+        match value {
+            Some(value) => Ok(value),
+            None => self.error("The result is too big"),
+        }
+    }
+    /// Parses the whole input, which is relative to the cursor if it starts with a sign
+    fn address(&mut self) -> Result<i128, GotoError> {
+        let start = match self.peek() {
+            Some('+' | '-') => match self.file.cursor {
+                Some(cursor) => cursor,
+                None => return self.error("The cursor is not inside of the file"),
+            },
+            None => return self.error("Missing address"),
+            Some(_) => 0,
+        };
+        let value = self.sum()?;
+        match self.peek() {
+            None => self.checked(start.checked_add(value)),
+            Some(')') => self.error("Unmatched ')'"),
+            Some(c) => self.error(&format!("Unexpected '{c}'")),
+        }
+    }
+    fn sum(&mut self) -> Result<i128, GotoError> {
+        let mut value = match self.peek() {
+            Some('+' | '-') => 0,
+            _ => self.product()?,
+        };
+        loop {
+            let negative = match self.peek() {
+                Some('+') => false,
+                Some('-') => true,
+                _ => return Ok(value),
+            };
+            self.pos += 1;
+            let operand = self.product()?;
+            value = self.checked(match negative {
+                false => value.checked_add(operand),
+                true => value.checked_sub(operand),
+            })?;
+        }
+    }
+    fn product(&mut self) -> Result<i128, GotoError> {
+        let mut value = self.factor()?;
+        loop {
+            let operator = match self.peek() {
+                Some(c @ ('*' | '/')) => c,
+                _ => return Ok(value),
+            };
+            self.pos += 1;
+            let operand_pos = self.pos;
+            let operand = self.factor()?;
+            value = match operator {
+                '*' => self.checked(value.checked_mul(operand))?,
+                _ if operand == 0 => {
+                    self.pos = operand_pos;
+                    return self.error("Division by zero");
+                }
+                _ => value / operand,
+            };
+        }
+    }
+    fn factor(&mut self) -> Result<i128, GotoError> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.sum()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => self.error("Missing ')'"),
+                }
+            }
+            Some(c) if c.is_ascii_alphanumeric() => self.number(),
+            Some(c) => self.error(&format!("Unexpected '{c}'")),
+            None => self.error("Missing number"),
+        }
+    }
+    /// Parses a number, which is hexadecimal unless it starts with 0d or is a percentage.
+    /// For split bytes, numbers other than percentages are bytes, which can be followed
+    /// by a colon and the decimal position of a unit in the byte.
+    fn number(&mut self) -> Result<i128, GotoError> {
+        let start = self.pos;
+        let end = self.chars[start..]
+            .iter()
+            .position(|c| !c.is_ascii_alphanumeric())
+            .map_or(self.chars.len(), |len| start + len);
+        let word: String = self.chars[start..end].iter().collect();
+        self.pos = end;
+        let percent = self.chars.get(end) == Some(&'%');
+        let (digits, radix) = if percent {
+            self.pos += 1;
+            (word.as_str(), 10)
+        } else if let Some(hex) = word.strip_prefix("0x") {
+            (hex, 16)
+        } else if let Some(dec) = word.strip_prefix("0d") {
+            (dec, 10)
+        } else {
+            (word.as_str(), 16)
+        };
+        let value = match u64::from_str_radix(digits, radix) {
+            Ok(value) => value as i128,
+            Err(e) => {
+                self.pos = start;
+                return self.error(&format!("Invalid number \"{word}\" ({e})"));
+            }
+        };
+        if percent {
+            let range = &self.file.range;
+            let len = range.end - range.start;
+            return self.checked(value.checked_mul(len).map(|x| range.start + x / 100));
+        }
+        let per_byte = self.file.per_byte as i128;
+        let value = self.checked(value.checked_mul(per_byte))?;
+        if self.chars.get(self.pos) != Some(&':') {
+            return Ok(value);
+        }
+        if per_byte == 1 {
+            return self.error("Only split bytes have units after a colon");
+        }
+        self.pos += 1;
+        let start = self.pos;
+        let end = self.chars[start..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .map_or(self.chars.len(), |len| start + len);
+        let digits: String = self.chars[start..end].iter().collect();
+        match digits.parse::<i128>() {
+            Ok(unit) if unit < per_byte => {
+                self.pos = end;
+                Ok(value + unit)
+            }
+            _ => self.error(&format!("Expected a unit below {per_byte} after the colon")),
+        }
+    }
+}
+
+/// Shows a possibly negative number in hex
+fn signed_hex(value: i128) -> String {
+    match value < 0 {
+        true => format!("-{:#x}", -value),
+        false => format!("{value:#x}"),
+    }
+}
+
+/// Computes the typed address in one of the files (or index in the alignment),
+/// clamping it and returning a note in that case
+fn parse_address(
+    s: &str,
+    offset: usize,
+    file: &GotoFile,
+) -> Result<(i128, Option<String>), GotoError> {
+    let mut parser = AddressParser {
+        chars: s.chars().collect(),
+        pos: 0,
+        offset,
+        file,
+    };
+    let value = parser.address()?;
+    let range = &file.range;
+    if range.is_empty() {
+        return Ok((range.start, None));
+    }
+    let clamped = value.clamp(range.start, range.end - 1);
+    let note = (clamped != value).then(|| {
+        format!(
+            "{} is outside of the {}, went to {} instead",
+            file.show(value),
+            file.place,
+            file.show(clamped)
+        )
+    });
+    Ok((clamped, note))
+}
+
+impl GotoContext {
+    /// Computes where the input of goto jumps to, which is either one address for the
+    /// active cursors, two addresses separated by a bar or an index into the alignment
+    /// after an @, along with notes about clamping them
+    fn parse(&self, s: &str) -> Result<(GotoTarget, Vec<String>), GotoError> {
+        if let Some(index) = s.trim_start().strip_prefix('@') {
+            let offset = s.chars().count() - index.chars().count();
+            let alignment = match &self.alignment {
+                Some(alignment) => alignment,
+                None => {
+                    return Err(GotoError {
+                        pos: offset,
+                        msg: String::from("Indexes into the alignment need the aligned view"),
+                    })
+                }
+            };
+            let (index, note) = parse_address(index, offset, alignment)?;
+            return Ok((
+                GotoTarget::Index(index as isize),
+                note.into_iter().collect(),
+            ));
+        }
+        let (first, second) = match s.split_once('|') {
+            Some((first, second)) => (first, Some(second)),
+            None => (s, None),
+        };
+        // a single address is one of the second file only if its cursor is the only active one
+        let first_file = match (second, self.cursor_act) {
+            (None, CursorActive::Second) => &self.files[1],
+            _ => &self.files[0],
+        };
+        let (first, first_note) = parse_address(first, 0, first_file)?;
+        let second = second
+            .map(|second| {
+                let offset = s.chars().count() - second.chars().count();
+                parse_address(second, offset, &self.files[1])
+            })
+            .transpose()?;
+        let notes = first_note
+            .into_iter()
+            .chain(second.clone().and_then(|(_, note)| note))
+            .collect();
+        let target =
+            GotoTarget::Addresses(first as usize, second.map(|(second, _)| second as usize));
+        Ok((target, notes))
+    }
+}
+
+/// Goes to the addresses typed into the dialog, showing the notes about clamping
+/// them in the bottom line. Errors are shown there too, while the dialog stays open
+/// for correcting the addresses.
+fn call_goto(siv: &mut Cursive, s: &str, context: &GotoContext) {
+    let result = context
+        .parse(s)
+        .map_err(|e| e.to_string())
+        .and_then(|(target, notes)| {
+            // we pass the dummy printer because we do not need
+            // to draw when we are in the cursive backend
+            match target {
+                GotoTarget::Addresses(first, second) => on_hexview(
                     siv,
-                    // we pass the dummy printer because we do not need
-                    // to draw when we are in the cursive backend
-                    move |v| v.goto(&mut crate::backend::Dummy, first, second),
-                    move |v| v.goto(&mut crate::backend::Dummy, first, second),
-                )
-            });
+                    move |v| v.goto(&mut Dummy, first, second),
+                    move |v| v.goto(&mut Dummy, first, second),
+                ),
+                GotoTarget::Index(index) => on_hexview(
+                    siv,
+                    move |v| v.goto_alignment_index(&mut Dummy, index),
+                    |_| Err(String::from("The view is not aligned anymore")),
+                ),
+            }?;
+            Ok(notes)
+        });
 
-        match result {
-            Err(e) => siv.add_layer(
-                Dialog::text(format!("Error in goto: {e}"))
-                    .button("Continue", close_top_maybe_quit),
-            ),
-            Ok(()) => close_top_maybe_quit(siv),
+    let (severity, notice) = match &result {
+        Err(e) => (Severity::Error, format!("Error in goto: {e}")),
+        Ok(notes) => (Severity::Warning, notes.join(", ")),
+    };
+    // a successful goto hides the error of a previous try
+    let set_notice = |dh: &mut DoubleHexContext| {
+        if notice.is_empty() {
+            dh.notice = None
+        } else {
+            dh.set_notice(severity, notice.clone())
         }
     };
+    on_hexview(siv, |v| set_notice(&mut v.dh), |v| set_notice(&mut v.dh));
+    if result.is_ok() {
+        close_top_maybe_quit(siv)
+    }
+}
+
+/// What the addresses typed into goto refer to in the view
+fn goto_context(siv: &mut Cursive) -> GotoContext {
+    let (files, cursors, cursor_act, alignment) = on_hexview(
+        siv,
+        |v| {
+            let (index, bounds) = v.alignment_index();
+            let alignment = Some(GotoFile::alignment(index, bounds));
+            (v.files(), v.cursor_addresses(), v.dh.cursor_act, alignment)
+        },
+        |v| (v.files(), v.cursor_addresses(), v.dh.cursor_act, None),
+    );
+    GotoContext {
+        files: [0, 1].map(|i| {
+            let base = files[i].base();
+            GotoFile::file(cursors[i], base..base + files[i].len(), files[i].unit())
+        }),
+        alignment,
+        cursor_act,
+    }
+}
+
+/// Goes to the addresses like the dialog does, for the goto command of the command line
+pub fn goto_addresses(siv: &mut Cursive, s: &str) {
+    let context = goto_context(siv);
+    call_goto(siv, s, &context)
+}
+
+/// A dialog to go to a given position in the hexview
+pub fn goto(siv: &mut Cursive) {
+    let context = goto_context(siv);
+    let (edit_context, submit_context) = (context.clone(), context.clone());
     let find_s_and_call_goto = move |siv: &mut Cursive| {
         let s = siv
-            .call_on_name("goto address", |v: &mut EditView| v.get_content())
+            .call_on_name(GOTO_ADDRESS, |v: &mut EditView| v.get_content())
             .unwrap();
-        call_goto(siv, &s)
+        call_goto(siv, &s, &context)
     };
-    let name = "goto address";
     let textbox = EditView::new()
         .content(String::new())
         .on_edit_mut(move |siv, s, _| {
-            match parse_hex(s).is_ok() {
-                true => siv.call_on_name(name, |v: &mut EditView| {
-                    v.set_style(StyleType::from(PaletteColor::Secondary))
-                }),
-                false => siv.call_on_name(name, |v: &mut EditView| {
-                    v.set_style(StyleType::from(PaletteColor::Highlight))
-                }),
+            let (style, error) = match edit_context.parse(s) {
+                Ok(_) => (PaletteColor::Secondary, String::new()),
+                // nothing typed yet is not worth complaining about
+                Err(_) if s.trim().is_empty() => (PaletteColor::Highlight, String::new()),
+                Err(e) => (PaletteColor::Highlight, e.to_string()),
             };
+            siv.call_on_name(GOTO_ADDRESS, |v: &mut EditView| {
+                v.set_style(StyleType::from(style))
+            });
+            siv.call_on_name(GOTO_ERROR, |v: &mut TextView| v.set_content(error));
         })
-        .on_submit(call_goto)
-        .with_name(name)
+        .on_submit(move |siv, s| call_goto(siv, s, &submit_context))
+        .with_name(GOTO_ADDRESS)
         .fixed_width(33);
     siv.add_layer(
         OnEventView::new(
             Dialog::around(
-                LinearLayout::horizontal()
-                    .child(TextView::new("Address: "))
-                    .child(textbox),
+                LinearLayout::vertical()
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(TextView::new("Address: "))
+                            .child(textbox),
+                    )
+                    .child(TextView::new("").with_name(GOTO_ERROR).fixed_width(42)),
             )
             .button("Goto", find_s_and_call_goto)
             .button("Cancel", close_top_maybe_quit)
@@ -67,3 +449,154 @@ pub fn goto(siv: &mut Cursive) {
         .on_event(Key::F1, help_window(GOTO_HELP)),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(cursor_act: CursorActive) -> GotoContext {
+        GotoContext {
+            files: [
+                GotoFile::file(Some(0x100), 0..0x1000, Unit::Byte),
+                GotoFile::file(None, 0x10..0x210, Unit::Byte),
+            ],
+            alignment: Some(GotoFile::alignment(5, -0x20..0x30)),
+            cursor_act,
+        }
+    }
+
+    fn goto(s: &str) -> Result<(GotoTarget, Vec<String>), GotoError> {
+        context(CursorActive::Both).parse(s)
+    }
+
+    fn addresses(
+        first: usize,
+        second: Option<usize>,
+    ) -> Result<(GotoTarget, Vec<String>), GotoError> {
+        Ok((GotoTarget::Addresses(first, second), vec![]))
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(goto("0234|0x20"), addresses(0x234, Some(0x20)));
+        assert_eq!(goto("0x100+3*0d16"), addresses(0x130, None));
+        assert_eq!(goto("10*2"), addresses(0x20, None));
+        assert_eq!(goto("0d100 / (1+1)"), addresses(50, None));
+        assert_eq!(goto("+0x200"), addresses(0x300, None));
+        assert_eq!(goto("-0d16"), addresses(0xf0, None));
+        assert_eq!(goto("50%|50%"), addresses(0x800, Some(0x110)));
+        // the second file is the one of the address when its cursor is the only active one
+        assert_eq!(
+            context(CursorActive::Second).parse("100%"),
+            Ok((
+                GotoTarget::Addresses(0x20f, None),
+                vec![String::from(
+                    "0x210 is outside of the file, went to 0x20f instead"
+                )]
+            ))
+        );
+        assert_eq!(
+            goto("-0x200|0"),
+            Ok((
+                GotoTarget::Addresses(0, Some(0x10)),
+                vec![
+                    String::from("-0x100 is outside of the file, went to 0x0 instead"),
+                    String::from("0x0 is outside of the file, went to 0x10 instead")
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn alignment_index() {
+        assert_eq!(goto("@10"), Ok((GotoTarget::Index(0x10), vec![])));
+        assert_eq!(goto(" @ -10"), Ok((GotoTarget::Index(-0xb), vec![])));
+        assert_eq!(goto("@0%"), Ok((GotoTarget::Index(-0x20), vec![])));
+        assert_eq!(
+            goto("@100"),
+            Ok((
+                GotoTarget::Index(0x2f),
+                vec![String::from(
+                    "0x100 is outside of the alignment, went to 0x2f instead"
+                )]
+            ))
+        );
+        let mut unaligned = context(CursorActive::Both);
+        unaligned.alignment = None;
+        assert_eq!(
+            unaligned.parse("@10"),
+            Err(GotoError {
+                pos: 1,
+                msg: String::from("Indexes into the alignment need the aligned view")
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |pos, msg: &str| {
+            Err(GotoError {
+                pos,
+                msg: msg.to_string(),
+            })
+        };
+        assert_eq!(goto(""), error(1, "Missing address"));
+        assert_eq!(goto("12+"), error(4, "Missing number"));
+        assert_eq!(goto("(1+2"), error(5, "Missing ')'"));
+        assert_eq!(goto("1+2)"), error(4, "Unmatched ')'"));
+        assert_eq!(goto("4/(2-2)"), error(3, "Division by zero"));
+        assert_eq!(
+            goto("10|+1"),
+            error(4, "The cursor is not inside of the file")
+        );
+        assert_eq!(
+            goto("1+xyz"),
+            error(3, "Invalid number \"xyz\" (invalid digit found in string)")
+        );
+        assert_eq!(goto("1 $"), error(3, "Unexpected '$'"));
+        assert_eq!(goto("@2*"), error(4, "Missing number"));
+        assert_eq!(
+            goto("12:5"),
+            error(3, "Only split bytes have units after a colon")
+        );
+    }
+
+    #[test]
+    fn split_units() {
+        let file = GotoFile::file(Some(0x80), 0..0x100, Unit::Bit);
+        let context = GotoContext {
+            files: [file.clone(), file],
+            alignment: None,
+            cursor_act: CursorActive::Both,
+        };
+        assert_eq!(
+            context.parse("0x12:5"),
+            Ok((GotoTarget::Addresses(0x95, None), vec![]))
+        );
+        // relative moves go by bytes too, and percentages by units
+        assert_eq!(
+            context.parse("+2|50%"),
+            Ok((GotoTarget::Addresses(0x90, Some(0x80)), vec![]))
+        );
+        assert_eq!(
+            context.parse("1f:7"),
+            Ok((GotoTarget::Addresses(0xff, None), vec![]))
+        );
+        assert_eq!(
+            context.parse("20:1"),
+            Ok((
+                GotoTarget::Addresses(0xff, None),
+                vec![String::from(
+                    "0x20:1 is outside of the file, went to 0x1f:7 instead"
+                )]
+            ))
+        );
+        assert_eq!(
+            context.parse("1:8"),
+            Err(GotoError {
+                pos: 3,
+                msg: String::from("Expected a unit below 8 after the colon")
+            })
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use super::*;
+const HASH_LIST: &str = "hash list";
+
+/// Computes the CRC32 and SHA-256 of the selected bytes (or of the whole files
+/// without a selection) and lists them
+pub fn hash(siv: &mut Cursive) {
+    process_with_progress::<Hasher>(siv, "Hashing", show_digests)
+}
+
+/// Lists the digests, which are copied to the clipboard when they are chosen
+fn show_digests(siv: &mut Cursive, selected: &[SelectedBytes], digests: Vec<Digests>) {
+    let name_width = selected
+        .iter()
+        .map(|part| part.name.len())
+        .max()
+        .unwrap_or(0);
+    let descriptions = selected
+        .iter()
+        .map(SelectedBytes::describe)
+        .collect::<Vec<_>>();
+    let mut list = SelectView::new();
+    for (name, digests) in selected.iter().map(|part| &part.name).zip(digests) {
+        list.add_item(
+            format!("{name:name_width$} CRC32   {:08x}", digests.crc32),
+            (format!("CRC32 of {name}"), format!("{:08x}", digests.crc32)),
+        );
+        list.add_item(
+            format!("{name:name_width$} SHA-256 {}", digests.sha256),
+            (format!("SHA-256 of {name}"), digests.sha256),
+        );
+    }
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(descriptions.join("\n")))
+                .child(DummyView)
+                .child(list.on_submit(copy_digest).with_name(HASH_LIST)),
+        )
+        .title("Hashes")
+        .button("Copy", |s| {
+            let selected = s
+                .call_on_name(HASH_LIST, |v: &mut SelectView<(String, String)>| {
+                    v.selection()
+                })
+                .flatten();
+            if let Some(selected) = selected {
+                copy_digest(s, &selected)
+            }
+        })
+        .button("Close", close_top_maybe_quit),
+    )
+}
+
+/// Puts a digest into the clipboard, closes the list and tells
+/// in the bottom line of the hexview what was copied
+fn copy_digest(siv: &mut Cursive, (what, digest): &(String, String)) {
+    let (severity, notice) = match capabilities::copy_to_clipboard(digest) {
+        Ok(()) => (Severity::Info, format!("Copied the {what}")),
+        Err(e) => (Severity::Error, format!("Could not copy the {what}: {e}")),
+    };
+    let n1 = notice.clone();
+    on_hexview(
+        siv,
+        move |v| v.dh.set_notice(severity, n1),
+        move |v| v.dh.set_notice(severity, notice),
+    );
+    close_top_maybe_quit(siv)
+}
@@ -0,0 +1,191 @@
+use super::*;
+use crate::cursor::CursorActive;
+
+const MARK_NAME: &str = "mark name";
+const MARK_LIST: &str = "mark list";
+
+/// The addresses to pass to goto for jumping to a mark with the active cursors
+fn mark_target(
+    cursor_act: CursorActive,
+    addresses: [Option<usize>; 2],
+) -> Result<Option<(usize, Option<usize>)>, String> {
+    let not_in_file = || String::from("The mark is not set in the file of the active cursor");
+    match (cursor_act, addresses) {
+        (CursorActive::None, _) => Ok(None),
+        (CursorActive::First, [first, _]) => first.map(|x| Some((x, None))).ok_or_else(not_in_file),
+        (CursorActive::Second, [_, second]) => {
+            second.map(|x| Some((x, None))).ok_or_else(not_in_file)
+        }
+        (CursorActive::Both, [Some(first), Some(second)]) => Ok(Some((first, Some(second)))),
+        (CursorActive::Both, _) => Err(String::from(
+            "The mark is only set in one file, activate the cursor of that file to jump to it",
+        )),
+    }
+}
+
+fn error_dialog(siv: &mut Cursive, text: String) {
+    siv.add_layer(Dialog::text(text).button("Continue", close_top_maybe_quit));
+}
+
+/// A dialog to set a named mark at the addresses of the cursors
+pub fn set_mark(siv: &mut Cursive) {
+    let name = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive")
+        .marks
+        .free_name();
+    let textbox = EditView::new()
+        .content(name)
+        .on_submit(|siv, _| on_set_mark(siv))
+        .with_name(MARK_NAME)
+        .fixed_width(16);
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::horizontal()
+                    .child(TextView::new("Name: "))
+                    .child(textbox),
+            )
+            .button("Set", on_set_mark)
+            .button("Cancel", close_top_maybe_quit)
+            .title("Set Mark"),
+        )
+        .on_event(Key::F1, help_window(MARKS_HELP)),
+    );
+}
+
+fn on_set_mark(siv: &mut Cursive) {
+    let name = siv
+        .call_on_name(MARK_NAME, |v: &mut EditView| {
+            v.get_content().trim().to_string()
+        })
+        .unwrap();
+    if name.is_empty() {
+        return error_dialog(siv, String::from("The name of a mark cannot be empty"));
+    }
+    let addresses = on_hexview(siv, |v| v.cursor_addresses(), |v| v.cursor_addresses());
+    if addresses == [None, None] {
+        return error_dialog(siv, String::from("The cursors are not inside of the files"));
+    }
+    let settings = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive");
+    settings.marks.set(&name, addresses);
+    match settings.store_marks() {
+        Err(e) => error_dialog(siv, format!("Could not save the marks: {e}")),
+        Ok(()) => close_top_maybe_quit(siv),
+    }
+}
+
+/// A dialog listing the marks of the shown files, for jumping to them or deleting them
+pub fn marks(siv: &mut Cursive) {
+    let settings = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive");
+    let style = settings.style;
+    let width = style.addr_width as usize;
+    let list = settings.marks.list();
+    if list.is_empty() {
+        siv.add_layer(
+            Dialog::text("There are no marks in the shown files yet")
+                .title("Marks")
+                .button("Close", close_top_maybe_quit),
+        );
+        return;
+    }
+    let show_address = |address: Option<usize>| match address {
+        Some(address) => style.hex_addr(address, width),
+        None => "-".repeat(style.hex_addr(0, width).len()),
+    };
+    let mut select = SelectView::new();
+    for (name, [first, second]) in list {
+        let label = format!("{name:<8} {}|{}", show_address(first), show_address(second));
+        select.add_item(label, name);
+    }
+    select.set_on_submit(|siv, name: &String| jump_to_mark(siv, name));
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(select.with_name(MARK_LIST).scrollable())
+                .button("Jump", |siv| {
+                    if let Some(name) = selected_mark(siv) {
+                        jump_to_mark(siv, &name)
+                    }
+                })
+                .button("Delete", delete_mark)
+                .button("Close", close_top_maybe_quit)
+                .title("Marks"),
+        )
+        .on_event(Key::F1, help_window(MARKS_HELP)),
+    );
+}
+
+fn selected_mark(siv: &mut Cursive) -> Option<String> {
+    siv.call_on_name(MARK_LIST, |v: &mut SelectView<String>| v.selection())
+        .flatten()
+        .map(|name| (*name).clone())
+}
+
+fn jump_to_mark(siv: &mut Cursive, name: &str) {
+    let addresses = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive")
+        .marks
+        .get(name);
+    // we pass the dummy printer because we do not need
+    // to draw when we are in the cursive backend
+    let result = on_hexview(
+        siv,
+        |v| match mark_target(v.dh.cursor_act, addresses)? {
+            Some((first, second)) => v.goto(&mut Dummy, first, second),
+            None => Ok(()),
+        },
+        |v| match mark_target(v.dh.cursor_act, addresses)? {
+            Some((first, second)) => v.goto(&mut Dummy, first, second),
+            None => Ok(()),
+        },
+    );
+    match result {
+        Err(e) => error_dialog(siv, format!("Could not jump to mark {name}: {e}")),
+        Ok(()) => close_top_maybe_quit(siv),
+    }
+}
+
+fn delete_mark(siv: &mut Cursive) {
+    let name = match selected_mark(siv) {
+        Some(name) => name,
+        None => return,
+    };
+    let settings = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive");
+    settings.marks.remove(&name);
+    let stored = settings.store_marks();
+    siv.call_on_name(MARK_LIST, |v: &mut SelectView<String>| {
+        if let Some(id) = v.selected_id() {
+            v.remove_item(id);
+        }
+    });
+    if let Err(e) = stored {
+        error_dialog(siv, format!("Could not save the marks: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_targets() {
+        assert_eq!(
+            mark_target(CursorActive::Both, [Some(1), Some(2)]),
+            Ok(Some((1, Some(2))))
+        );
+        assert_eq!(
+            mark_target(CursorActive::Second, [Some(1), Some(2)]),
+            Ok(Some((2, None)))
+        );
+        assert!(mark_target(CursorActive::First, [None, Some(2)]).is_err());
+        assert!(mark_target(CursorActive::Both, [None, Some(2)]).is_err());
+        assert_eq!(mark_target(CursorActive::None, [None, None]), Ok(None));
+    }
+}
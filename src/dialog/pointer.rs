@@ -0,0 +1,111 @@
+use super::*;
+use crate::{cursor::CursorActive, pointer::Pointer};
+
+const POINTER_WIDTH: &str = "pointer width";
+const POINTER_ORDER: &str = "pointer byte order";
+const POINTER_BASE: &str = "pointer base";
+
+/// A dialog for reading the bytes at the active cursor as an offset and going there,
+/// which starts with the choices of the last time
+pub fn follow_pointer(siv: &mut Cursive) {
+    let pointer = siv
+        .user_data::<Settings>()
+        .expect("Could not get settings from cursive")
+        .pointer;
+    let width_index = Pointer::WIDTHS
+        .iter()
+        .position(|&width| width == pointer.width)
+        .unwrap_or(2);
+    let options = ListView::new()
+        .child(
+            "Width:",
+            SelectView::new()
+                .popup()
+                .with_all(Pointer::WIDTHS.map(|width| (format!("{width} bytes"), width)))
+                .selected(width_index)
+                .with_name(POINTER_WIDTH),
+        )
+        .child(
+            "Byte Order:",
+            SelectView::new()
+                .popup()
+                .with_all([("Little Endian", false), ("Big Endian", true)])
+                .selected(pointer.big_endian as usize)
+                .with_name(POINTER_ORDER),
+        )
+        .child(
+            "Base:",
+            EditView::new()
+                .content(format!("{:x}", pointer.base))
+                .on_submit(|siv, _| on_follow(siv))
+                .with_name(POINTER_BASE)
+                .fixed_width(18),
+        );
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(options)
+                .title("Follow Pointer")
+                .button("Follow", on_follow)
+                .button("Cancel", close_top_maybe_quit)
+                .button("Help", help_window(POINTER_HELP)),
+        )
+        .on_event(Key::F1, help_window(POINTER_HELP)),
+    );
+}
+
+/// Remembers the choices of the dialog and goes to the offset at the active cursor
+fn on_follow(siv: &mut Cursive) {
+    let width = siv
+        .call_on_name(POINTER_WIDTH, |v: &mut SelectView<usize>| v.selection())
+        .flatten()
+        .map_or(4, |width| *width);
+    let big_endian = siv
+        .call_on_name(POINTER_ORDER, |v: &mut SelectView<bool>| v.selection())
+        .flatten()
+        .map_or(false, |big_endian| *big_endian);
+    let base = siv
+        .call_on_name(POINTER_BASE, |v: &mut EditView| v.get_content())
+        .unwrap();
+    let base = base.trim();
+    let base = match usize::from_str_radix(base.strip_prefix("0x").unwrap_or(base), 16) {
+        Ok(base) => base,
+        Err(e) => return error_dialog(siv, format!("The base '{base}' is invalid: {e}")),
+    };
+    let pointer = Pointer {
+        width,
+        big_endian,
+        base,
+    };
+    siv.user_data::<Settings>()
+        .expect("Could not get settings from cursive")
+        .pointer = pointer;
+    let (files, cursors, cursor_act) = on_hexview(
+        siv,
+        |v| (v.edited_files(), v.cursor_addresses(), v.dh.cursor_act),
+        |v| (v.edited_files(), v.cursor_addresses(), v.dh.cursor_act),
+    );
+    // like with goto, the first file is meant unless only the second cursor is active
+    let second = cursor_act == CursorActive::Second;
+    let file = &files[second as usize];
+    let result = match cursors[second as usize] {
+        Some(cursor) => pointer.target(&file[cursor - file.base()..]),
+        None => Err(String::from("The cursor is not inside of the file")),
+    }
+    .and_then(|target| {
+        // we pass the dummy printer because we do not need
+        // to draw when we are in the cursive backend
+        on_hexview(
+            siv,
+            move |v| v.goto(&mut Dummy, target, None),
+            move |v| v.goto(&mut Dummy, target, None),
+        )
+    });
+    match result {
+        Err(e) => error_dialog(siv, format!("Error in goto: {e}")),
+        Ok(()) => close_top_maybe_quit(siv),
+    }
+}
+
+fn error_dialog(siv: &mut Cursive, text: String) {
+    siv.add_layer(Dialog::text(text).button("Continue", close_top_maybe_quit));
+}
@@ -0,0 +1,158 @@
+use super::*;
+use crate::doublehex::DoubleHexContext;
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+};
+
+const SAVE_PATHS: [&str; 2] = ["save path first", "save path second"];
+const UNSAVED_EDITS: &str = "unsaved edits";
+
+/// A dialog for writing the edited files to new files, with a path for each edited one
+pub fn save_as(siv: &mut Cursive) {
+    let (names, edited) = on_hexview(
+        siv,
+        |v| (v.filenames().map(String::from), edited_files(&v.dh)),
+        |v| (v.filenames().map(String::from), edited_files(&v.dh)),
+    );
+    let edited = (0..2).filter(|&i| edited[i][0]).collect::<Vec<_>>();
+    if edited.is_empty() {
+        return error_dialog(
+            siv,
+            String::from("Nothing was edited yet, 'i' starts editing at the cursor"),
+        );
+    }
+    let mut paths = ListView::new();
+    for i in edited {
+        paths.add_child(
+            &format!("{}:", names[i]),
+            EditView::new()
+                .content(format!("{}.edited", names[i]))
+                .on_submit(|siv, _| on_save(siv))
+                .with_name(SAVE_PATHS[i])
+                .min_width(32),
+        );
+    }
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(paths)
+                    .child(TextView::new("Existing files are never overwritten.")),
+            )
+            .title("Save As")
+            .button("Save", on_save)
+            .button("Cancel", close_top_maybe_quit)
+            .button("Help", help_window(SAVE_AS_HELP)),
+        )
+        .on_event(Key::F1, help_window(SAVE_AS_HELP)),
+    );
+}
+
+/// Asks whether to save or discard the unsaved edits before they are lost by quitting
+/// or reloading, which only happens if none are left unsaved afterwards
+pub fn unsaved_edits(siv: &mut Cursive) {
+    let (names, edited) = on_hexview(
+        siv,
+        |v| (v.filenames().map(String::from), edited_files(&v.dh)),
+        |v| (v.filenames().map(String::from), edited_files(&v.dh)),
+    );
+    let names = (0..2)
+        .filter(|&i| edited[i][1])
+        .map(|i| names[i].as_str())
+        .collect::<Vec<_>>()
+        .join(" and ");
+    siv.add_layer(
+        Dialog::text(format!("The edits of {names} are not saved yet."))
+            .title("Unsaved Edits")
+            .button("Save As", save_as)
+            .button("Discard", |siv| {
+                mark_saved(siv, &[0, 1]);
+                close_top_maybe_quit(siv)
+            })
+            .button("Cancel", close_top_maybe_quit)
+            .with_name(UNSAVED_EDITS),
+    );
+}
+
+/// Writes the files to the paths of the dialog, closing it (and the question about the
+/// unsaved edits) if all of them were saved
+fn on_save(siv: &mut Cursive) {
+    let (names, files) = on_hexview(
+        siv,
+        |v| (v.filenames().map(String::from), v.edited_files()),
+        |v| (v.filenames().map(String::from), v.edited_files()),
+    );
+    let mut saved = Vec::new();
+    let mut notices = Vec::new();
+    for i in 0..2 {
+        // the paths of files that were saved before an error are disabled
+        let path = siv.call_on_name(SAVE_PATHS[i], |v: &mut EditView| {
+            v.is_enabled().then(|| v.get_content())
+        });
+        let path = match path.flatten() {
+            Some(path) if !path.is_empty() => path,
+            _ => continue,
+        };
+        if let Err(e) = write_new(&path, &files[i]) {
+            // the files written before are saved nonetheless
+            mark_saved(siv, &saved);
+            let text = match e.kind() {
+                ErrorKind::AlreadyExists => format!("{path} already exists, choose a new file"),
+                _ => format!("Could not save {} to {path}: {e}", names[i]),
+            };
+            return error_dialog(siv, text);
+        }
+        siv.call_on_name(SAVE_PATHS[i], |v: &mut EditView| v.disable());
+        saved.push(i);
+        notices.push(format!("Saved {} to {path}", names[i]));
+    }
+    if saved.is_empty() {
+        return error_dialog(siv, String::from("There is no path to save to"));
+    }
+    mark_saved(siv, &saved);
+    let notice = notices.join(", ");
+    let unsaved = on_hexview(
+        siv,
+        |v| {
+            v.dh.set_notice(Severity::Info, notice.clone());
+            v.dh.has_unsaved_edits()
+        },
+        |v| {
+            v.dh.set_notice(Severity::Info, notice.clone());
+            v.dh.has_unsaved_edits()
+        },
+    );
+    close_top_maybe_quit(siv);
+    if !unsaved && siv.find_name::<Dialog>(UNSAVED_EDITS).is_some() {
+        close_top_maybe_quit(siv);
+    }
+}
+
+/// Whether each file has edits and whether they are unsaved
+fn edited_files(dh: &DoubleHexContext) -> [[bool; 2]; 2] {
+    [0, 1].map(|i| [!dh.edits[i].is_empty(), dh.edits[i].unsaved])
+}
+
+/// Marks the edits of the files as saved, so that they are not asked about anymore
+fn mark_saved(siv: &mut Cursive, files: &[usize]) {
+    let mark = |dh: &mut DoubleHexContext| {
+        for &i in files {
+            dh.edits[i].unsaved = false;
+        }
+    };
+    on_hexview(siv, |v| mark(&mut v.dh), |v| mark(&mut v.dh));
+}
+
+/// Writes the bytes into a new file, which fails if there already is one at the path
+fn write_new(path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(bytes)
+}
+
+fn error_dialog(siv: &mut Cursive, text: String) {
+    siv.add_layer(Dialog::text(text).button("Continue", close_top_maybe_quit));
+}
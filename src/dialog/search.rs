@@ -2,6 +2,9 @@ use super::*;
 const SEARCH_DIALOG: &str = "search dialog";
 const SEARCH_BOX: &str = "search box";
 const SEARCH_MODE: &str = "search mode";
+const SEARCH_MISMATCHES: &str = "mismatches";
+const SEARCH_KEEP: &str = "keep queries";
+const SEARCH_DIFFERENCES: &str = "only differences";
 
 /// A dialog for searching bytes in the hexview
 pub fn search(siv: &mut Cursive) {
@@ -9,13 +12,35 @@ pub fn search(siv: &mut Cursive) {
         siv,
         |v| v.current_search_query().cloned(),
         |v| v.current_search_query().cloned(),
-    );
+    )
+    // like the one of a restored session
+    .or_else(|| {
+        siv.user_data::<Settings>()
+            .and_then(|settings| settings.last_query.clone())
+    });
     let query_kind = match query.as_ref().map_or(QueryType::Text, |x| x.query_type()) {
         QueryType::Text => 0,
         QueryType::Regex => 1,
         QueryType::Hexagex => 2,
+        QueryType::HexPattern => 3,
     };
     let query_text = query.as_ref().map_or("", |x| x.text());
+    let mismatches = query.as_ref().map_or(0, |x| x.mismatches());
+    let is_aligned = on_hexview(siv, |_| true, |_| false);
+    let mut options = ListView::new()
+        .child(
+            "Mismatches:",
+            validated_box(SEARCH_MISMATCHES, mismatches.to_string(), TEXT_WIDTH, |s| {
+                s.parse::<usize>().is_ok()
+            }),
+        )
+        .child("Keep Others:", Checkbox::new().with_name(SEARCH_KEEP));
+    if is_aligned {
+        options.add_child(
+            "Only Differences:",
+            Checkbox::new().with_name(SEARCH_DIFFERENCES),
+        );
+    }
     // this pops up on regex compilation errors
     let do_search = |s: &mut Cursive| {
         if let Err(e) = on_search(s) {
@@ -40,12 +65,21 @@ pub fn search(siv: &mut Cursive) {
                         .with_name(SEARCH_BOX)
                         .min_width(24),
                 ))
-                .child(Panel::new(
-                    SelectView::new()
-                        .with_all([("Text", "text"), ("Regex", "regex"), ("Hexagex", "hexagex")])
-                        .selected(query_kind)
-                        .with_name(SEARCH_MODE),
-                )),
+                .child(
+                    LinearLayout::vertical()
+                        .child(Panel::new(
+                            SelectView::new()
+                                .with_all([
+                                    ("Text", "text"),
+                                    ("Regex", "regex"),
+                                    ("Hexagex", "hexagex"),
+                                    ("Hex Pattern", "hexpattern"),
+                                ])
+                                .selected(query_kind)
+                                .with_name(SEARCH_MODE),
+                        ))
+                        .child(options),
+                ),
         )
         .title("Search")
         .button("Search", do_search)
@@ -82,34 +116,62 @@ fn on_search(siv: &mut Cursive) -> Result<(), String> {
         "text" => QueryType::Text,
         "regex" => QueryType::Regex,
         "hexagex" => QueryType::Hexagex,
+        "hexpattern" => QueryType::HexPattern,
         otherwise => return Err(format!("Invaild search mode: {otherwise}")),
     };
-    let query = Query::new(query_type, &content).map_err(|e| e.to_string())?;
+    let mut mismatches = 0usize;
+    let mut errors = String::new();
+    parse_box(siv, SEARCH_MISMATCHES, &mut mismatches, &mut errors);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let options = SearchOptions {
+        keep: siv.find_name::<Checkbox>(SEARCH_KEEP).unwrap().is_checked(),
+        // this option only exists in the aligned view
+        only_differences: siv
+            .find_name::<Checkbox>(SEARCH_DIFFERENCES)
+            .map_or(false, |x| x.is_checked()),
+    };
+    let query = Query::new(query_type, &content, mismatches).map_err(|e| e.to_string())?;
+    // the search dialog is replaced by the progress of the search
+    siv.pop_layer();
+    run_search(siv, query, options);
+    Ok(())
+}
+
+/// Starts searching the query in the view and shows a window with the progress
+/// of the search, which closes itself when the search is done
+pub fn run_search(siv: &mut Cursive, query: Query, options: SearchOptions) {
+    if let Some(settings) = siv.user_data::<Settings>() {
+        settings.last_query = Some(query.clone());
+    }
     let q1 = query.clone();
     let ((context1, file1), second) = on_hexview(
         siv,
-        move |v| v.setup_search(q1),
-        move |v| v.setup_search(query),
+        move |v| v.setup_search(q1, options),
+        move |v| v.setup_search(query, options),
     );
-    // close the search dialog and show a status window showing progress
-    // of the current search
-    siv.pop_layer();
     search_result_status(siv, 1 + second.is_some() as usize);
 
-    let start_search = |context: SearchContext, content: FileContent| {
+    // both halves showing the same bytes (like a file compared with itself) are searched once,
+    // with the results going to both of them
+    let (second, mirror) = match second {
+        Some((context2, file2)) if Arc::ptr_eq(&file1, &file2) => (None, Some(context2)),
+        second => (second, None),
+    };
+    let start_search = |context: SearchContext, mirror: Option<SearchContext>, content| {
         let sink = siv.cb_sink().clone();
         let send = util::rate_limit_channel(
             SEARCH_BUFFER_SIZE,
             Duration::from_millis(200),
-            search_result_receiver(sink, context.clone()),
+            search_result_receiver(sink, context.clone(), mirror),
         );
         context.start_search(send, content)
     };
-    start_search(context1, file1);
+    start_search(context1, mirror, file1);
     if let Some((context2, file2)) = second {
-        start_search(context2, file2)
+        start_search(context2, None, file2)
     }
-    Ok(())
 }
 
 const SEARCH_STATS: &str = "search stats";
@@ -151,27 +213,32 @@ fn search_result_status(siv: &mut Cursive, usage_count: usize) {
     siv.add_layer(search_result_stats.with_name(SEARCH_STATS))
 }
 
-/// creates an adapter to put the search results into
+/// creates an adapter to put the search results into, and also into the mirrored
+/// results of the other half if it shows the same bytes
 fn search_result_receiver(
     cb: cursive::CbSink,
     context: SearchContext,
-) -> impl FnMut(Vec<Option<Range<usize>>>) -> bool + Send + 'static {
+    mirror: Option<SearchContext>,
+) -> impl FnMut(Vec<SearchMessage>) -> bool + Send + 'static {
     move |v| {
-        let context = context.clone();
-        cb.send(Box::new(move |siv| add_search_results(siv, v, context)))
-            .is_ok()
+        let contexts = [Some(context.clone()), mirror.clone()];
+        cb.send(Box::new(move |siv| {
+            for context in contexts.into_iter().flatten() {
+                add_search_results(siv, v.clone(), context)
+            }
+        }))
+        .is_ok()
     }
 }
 
 /// use a list of search results to update the progress window and
 /// maybe close it and jump the hexview to the next result when
 /// finished.
-fn add_search_results(
-    siv: &mut Cursive,
-    results: Vec<Option<Range<usize>>>,
-    context: SearchContext,
-) {
-    let count = results.iter().flatten().count();
+fn add_search_results(siv: &mut Cursive, results: Vec<SearchMessage>, context: SearchContext) {
+    let count = results
+        .iter()
+        .filter(|x| matches!(x, SearchMessage::Match(_)))
+        .count();
     let is_final = results.is_final();
     let SearchContext {
         query,
@@ -193,7 +260,7 @@ fn add_search_results(
         view.usage_count == 0
     }) {
         Some(true) => {
-            on_hexview(
+            let _ = on_hexview(
                 siv,
                 |v| v.jump_next_search_result(&mut Dummy),
                 |v| v.jump_next_search_result(&mut Dummy),
@@ -14,28 +14,45 @@ pub fn set_offset(siv: &mut Cursive) {
     };
     let execute_align = |s: &mut Cursive, which: &i32| match *which {
         0 => {
-            on_hexview(s, |_| (), |v| v.align_start(&mut Dummy));
+            let _ = on_hexview(s, |_| Ok(()), |v| v.align_start(&mut Dummy));
             close_top_maybe_quit(s);
         }
         1 => {
-            on_hexview(s, |_| (), |v| v.align_end(&mut Dummy));
+            let _ = on_hexview(s, |_| Ok(()), |v| v.align_end(&mut Dummy));
             close_top_maybe_quit(s);
         }
         2 => {
             s.pop_layer();
-            FlatAlignmentProgress::make_new(s);
+            FlatAlignmentProgress::make_new(s, "Aligning...", FlatAlignmentContext::align_flat);
+        }
+        3 => {
+            s.pop_layer();
+            auto_offset(s);
         }
         _ => (),
     };
+    // the offsets from the first file to the second one now and at the start and end
+    let [current, start, end] = siv
+        .call_on_name("unaligned", |v: &mut Unaligned| v.offset_choices())
+        .unwrap_or_default()
+        .map(util::signed_offset);
     let dialog = OnEventView::new(
         Dialog::around(
-            SelectView::new()
-                .with_all([
-                    ("Align offset at start", 0),
-                    ("Align offset at end", 1),
-                    ("Align offset at biggest overlap", 2),
-                ])
-                .on_submit(execute_align),
+            LinearLayout::vertical()
+                .child(TextView::new(format!(
+                    "Offset between the cursors: {current}"
+                )))
+                .child(DummyView)
+                .child(
+                    SelectView::new()
+                        .with_all([
+                            (format!("Align offset at start: {start}"), 0),
+                            (format!("Align offset at end: {end}"), 1),
+                            (String::from("Align offset at biggest overlap"), 2),
+                            (String::from("Detect offset from matching windows"), 3),
+                        ])
+                        .on_submit(execute_align),
+                ),
         )
         .title("Set offset")
         .button("Cancel", close_top_maybe_quit)
@@ -45,6 +62,16 @@ pub fn set_offset(siv: &mut Cursive) {
     siv.add_layer(dialog);
 }
 
+/// detects the offset of an unaligned view from windows of the first file
+/// that are found in the second one, showing the progress while searching
+pub fn auto_offset(siv: &mut Cursive) {
+    FlatAlignmentProgress::make_new(
+        siv,
+        "Detecting offset...",
+        FlatAlignmentContext::detect_offset,
+    );
+}
+
 const FLAT_ALIGNMENT_PROGRESS: &str = "flat alignment progress";
 /// Shows the progress of the flat alignment from the "set offset" dialog
 pub struct FlatAlignmentProgress {
@@ -60,9 +87,9 @@ impl ViewWrapper for FlatAlignmentProgress {
 }
 
 impl FlatAlignmentProgress {
-    /// opens a new  flat alignment progress dialog and starts the alignment process
-    /// which calls back with the results later
-    pub fn make_new(siv: &mut Cursive) {
+    /// opens a new  flat alignment progress dialog with the `text` and starts the alignment
+    /// process `job` in another thread, which calls back with the results later
+    pub fn make_new(siv: &mut Cursive, text: &str, job: fn(FlatAlignmentContext)) {
         let content = match siv.call_on_name("unaligned", |s: &mut Unaligned| s.data.clone()) {
             Some(c) => [c.xvec, c.yvec],
             None => {
@@ -80,15 +107,13 @@ impl FlatAlignmentProgress {
         let update_progress = aligned_callback(sink, is_running.clone());
         let counter = Counter(Arc::new(AtomicUsize::new(0)));
         let dialog = Dialog::around(
-            LinearLayout::vertical()
-                .child(TextView::new("Aligning..."))
-                .child(
-                    ProgressBar::new()
-                        .min(0)
-                        .max(256)
-                        .with_value(counter.clone())
-                        .min_width(16),
-                ),
+            LinearLayout::vertical().child(TextView::new(text)).child(
+                ProgressBar::new()
+                    .min(0)
+                    .max(256)
+                    .with_value(counter.clone())
+                    .min_width(16),
+            ),
         )
         .button("Cancel", |s| {
             s.call_on_name(FLAT_ALIGNMENT_PROGRESS, |fa: &mut FlatAlignmentProgress| {
@@ -106,7 +131,11 @@ impl FlatAlignmentProgress {
             .with_name(FLAT_ALIGNMENT_PROGRESS),
         );
         std::thread::spawn(move || {
-            FlatAlignmentContext::new(is_running, content, update_progress).align_flat()
+            job(FlatAlignmentContext::new(
+                is_running,
+                content,
+                update_progress,
+            ))
         });
     }
     fn update_count(&mut self, new_count: u16) {
@@ -118,8 +147,9 @@ impl FlatAlignmentProgress {
 /// the alignment process sends back messages of two kinds:
 ///  * incomplete messages, which include the current progress (from 0 to 256)
 ///  * an complete message, which is the last message and contains the result offset
-///  this callback either updates the progress bar or applies the result to the
-///  unaligned hexview
+///
+/// this callback either updates the progress bar or applies the result to the
+/// unaligned hexview
 fn aligned_callback(
     sink: CbSink,
     is_running: Arc<AtomicBool>,
@@ -141,7 +171,27 @@ fn aligned_callback(
                 }
                 FlatAlignProgressMessage::Complete(c) => {
                     let _ = siv.call_on_name("unaligned", |s: &mut Unaligned| {
-                        s.align_custom(&mut Dummy, c);
+                        let _ = s.align_custom(&mut Dummy, c);
+                    });
+                    close_top_maybe_quit(siv);
+                }
+                FlatAlignProgressMessage::Detected(detected) => {
+                    let _ = siv.call_on_name("unaligned", |s: &mut Unaligned| match detected {
+                        Some(detected) => {
+                            let _ = s.align_custom(&mut Dummy, detected.offset);
+                            let offset = util::signed_offset(s.offset_between_cursors());
+                            s.dh.set_notice(
+                                Severity::Info,
+                                format!(
+                                    "Detected offset {offset}, {:.0}% of the windows match",
+                                    detected.confidence * 100.0
+                                ),
+                            );
+                        }
+                        None => s.dh.set_notice(
+                            Severity::Warning,
+                            "No windows of the first file were found in the second one",
+                        ),
                     });
                     close_top_maybe_quit(siv);
                 }
@@ -0,0 +1,30 @@
+use super::*;
+
+/// Counts the values of the selected bytes (or of the whole files without
+/// a selection) and shows their histograms and statistics
+pub fn byte_stats(siv: &mut Cursive) {
+    process_with_progress::<Histogram>(siv, "Counting", show_histograms)
+}
+
+/// Shows a bar chart and the statistics of every part next to each other
+fn show_histograms(siv: &mut Cursive, selected: &[SelectedBytes], histograms: Vec<Histogram>) {
+    let mut parts = LinearLayout::horizontal();
+    for (i, (part, histogram)) in selected.iter().zip(histograms).enumerate() {
+        if i > 0 {
+            parts.add_child(DummyView.fixed_width(2));
+        }
+        parts.add_child(
+            LinearLayout::vertical()
+                .child(TextView::new(part.describe()))
+                .child(DummyView)
+                .child(TextView::new(histogram.chart(64, 8).join("\n")))
+                .child(DummyView)
+                .child(TextView::new(histogram.statistics())),
+        );
+    }
+    siv.add_layer(
+        Dialog::around(parts.scrollable().scroll_x(true))
+            .title("Byte Statistics")
+            .button("Close", close_top_maybe_quit),
+    )
+}
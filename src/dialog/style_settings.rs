@@ -21,12 +21,47 @@ fn apply_style(siv: &mut Cursive) {
     let ascii_col = siv.find_name::<Checkbox>("ascii_col").unwrap().is_checked();
     let bars_col = siv.find_name::<Checkbox>("bars_col").unwrap().is_checked();
     let vertical = siv.find_name::<Checkbox>("vertical").unwrap().is_checked();
-    let spacer = siv.find_name::<Checkbox>("spacer").unwrap().is_checked();
+    let group = siv
+        .find_name::<SelectView<u8>>("group")
+        .unwrap()
+        .selection()
+        .map_or(1, |group| *group);
     let right_to_left = siv
         .find_name::<Checkbox>("right_to_left")
         .unwrap()
         .is_checked();
     let no_scroll = siv.find_name::<Checkbox>("no_scroll").unwrap().is_checked();
+    let show_index = siv
+        .find_name::<Checkbox>("show_index")
+        .unwrap()
+        .is_checked();
+    let ruler = siv.find_name::<Checkbox>("ruler").unwrap().is_checked();
+    let diff_rows = siv.find_name::<Checkbox>("diff_rows").unwrap().is_checked();
+    let fold = siv.find_name::<Checkbox>("fold").unwrap().is_checked();
+    let diff_map = siv.find_name::<Checkbox>("diff_map").unwrap().is_checked();
+    let byte_classes = siv
+        .find_name::<Checkbox>("byte_classes")
+        .unwrap()
+        .is_checked();
+    let entropy = siv.find_name::<Checkbox>("entropy").unwrap().is_checked();
+    let encoding = number_to_encoding(
+        &siv.find_name::<SelectView<usize>>("encoding")
+            .expect("Could not find text encoding select view")
+            .selected_id()
+            .expect("Text encoding select view appears to be empty"),
+    );
+    let address_column = number_to_address_column(
+        &siv.find_name::<SelectView<usize>>("address_column")
+            .expect("Could not find address column select view")
+            .selected_id()
+            .expect("Address column select view appears to be empty"),
+    );
+    let delta = number_to_delta_mode(
+        &siv.find_name::<SelectView<usize>>("delta")
+            .expect("Could not find second half select view")
+            .selected_id()
+            .expect("Second half select view appears to be empty"),
+    );
     let mode = number_to_stylemode(
         &siv.find_name::<SelectView<usize>>("display mode")
             .expect("Could not find display mode select view")
@@ -37,16 +72,59 @@ fn apply_style(siv: &mut Cursive) {
         .user_data::<Settings>()
         .expect("Could not get align algorithm info from cursive");
     let addr_width = settings.style.addr_width;
+    let Style {
+        entropy_window,
+        word_stride,
+        word_wrap,
+        page_overlap,
+        gap_char,
+        gap_text_char,
+        short_addr_width,
+        relative_to,
+        theme,
+        monochrome,
+        color_depth,
+        can_scroll,
+        single,
+        unit,
+        ..
+    } = settings.style;
     let new_style = Style {
         mode,
         ascii_col,
         bars_col,
         vertical,
-        spacer,
+        encoding,
+        gap_char,
+        gap_text_char,
+        group,
         right_to_left,
         column_count,
+        // the columns set here are the ones of both halves
+        second_column_count: None,
+        address_column,
+        delta,
         addr_width,
+        short_addr_width,
+        relative_to,
         no_scroll,
+        show_index,
+        ruler,
+        diff_rows,
+        fold,
+        diff_map,
+        byte_classes,
+        entropy,
+        entropy_window,
+        word_stride,
+        word_wrap,
+        page_overlap,
+        theme,
+        monochrome,
+        color_depth,
+        can_scroll,
+        single,
+        unit,
     };
     settings.style = new_style;
     on_hexview(
@@ -70,6 +148,34 @@ fn number_to_stylemode(x: &usize) -> DisplayMode {
     }
 }
 
+fn number_to_address_column(x: &usize) -> AddressColumn {
+    match x {
+        0 => AddressColumn::Full,
+        1 => AddressColumn::Short,
+        2 => AddressColumn::Hidden,
+        otherwise => panic!("Unknown item number {otherwise} for style address column setting"),
+    }
+}
+
+fn number_to_delta_mode(x: &usize) -> DeltaMode {
+    match x {
+        0 => DeltaMode::Off,
+        1 => DeltaMode::Xor,
+        2 => DeltaMode::Delta,
+        otherwise => panic!("Unknown item number {otherwise} for style second half setting"),
+    }
+}
+
+fn number_to_encoding(x: &usize) -> TextEncoding {
+    match x {
+        0 => TextEncoding::Ascii,
+        1 => TextEncoding::Latin1,
+        2 => TextEncoding::Ebcdic,
+        3 => TextEncoding::Utf8,
+        otherwise => panic!("Unknown item number {otherwise} for style text encoding setting"),
+    }
+}
+
 pub fn style(siv: &mut Cursive) -> impl View {
     let on_quit = |s: &mut Cursive| {
         let old_style = s
@@ -118,8 +224,8 @@ pub fn style(siv: &mut Cursive) -> impl View {
     // checkboxes for:
     // * ascii column
     // * vertical split
-    // * hex spacers
     // * right to left mode
+    // and a selection for the byte groups
     let left_side = ListView::new()
         .child("Column Count:", column_box)
         .child(
@@ -136,17 +242,57 @@ pub fn style(siv: &mut Cursive) -> impl View {
                 .with_name("vertical"),
         )
         .child(
-            "Hex Spacer:",
-            Checkbox::new()
-                .with_checked(style_settings.spacer)
-                .on_change(|s, check| {
+            "Byte Groups:",
+            SelectView::new()
+                .popup()
+                .with_all([("None", 1u8), ("2", 2), ("4", 4), ("8", 8)])
+                .selected(match style_settings.group {
+                    2 => 1,
+                    4 => 2,
+                    8 => 3,
+                    _ => 0,
+                })
+                .on_submit(|s, group: &u8| {
+                    let group = *group;
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.group = group,
+                        move |v| v.dh.style.group = group,
+                    )
+                })
+                .with_name("group"),
+        )
+        .child(
+            "Addresses:",
+            SelectView::new()
+                .popup()
+                .with_all([("Full", 0usize), ("Short", 1), ("Hidden", 2)])
+                .selected(style_settings.address_column as usize)
+                .on_submit(|s, a| {
+                    let address_column = number_to_address_column(a);
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.address_column = address_column,
+                        move |v| v.dh.style.address_column = address_column,
+                    )
+                })
+                .with_name("address_column"),
+        )
+        .child(
+            "Second Half:",
+            SelectView::new()
+                .popup()
+                .with_all([("Bytes", 0usize), ("XOR", 1), ("Delta", 2)])
+                .selected(style_settings.delta as usize)
+                .on_submit(|s, a| {
+                    let delta = number_to_delta_mode(a);
                     on_hexview(
                         s,
-                        move |v| v.dh.style.spacer = check,
-                        move |v| v.dh.style.spacer = check,
+                        move |v| v.dh.style.delta = delta,
+                        move |v| v.dh.style.delta = delta,
                     )
                 })
-                .with_name("spacer"),
+                .with_name("delta"),
         )
         .child(
             "Right to Left:",
@@ -174,6 +320,27 @@ pub fn style(siv: &mut Cursive) -> impl View {
                 })
                 .with_name("ascii_col"),
         )
+        .child(
+            "Text Encoding:",
+            SelectView::new()
+                .popup()
+                .with_all([
+                    ("ASCII", 0usize),
+                    ("Latin-1", 1),
+                    ("EBCDIC", 2),
+                    ("UTF-8", 3),
+                ])
+                .selected(style_settings.encoding as usize)
+                .on_submit(|s, t| {
+                    let encoding = number_to_encoding(t);
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.encoding = encoding,
+                        move |v| v.dh.style.encoding = encoding,
+                    )
+                })
+                .with_name("encoding"),
+        )
         .child(
             "Bar Column:",
             Checkbox::new()
@@ -199,6 +366,97 @@ pub fn style(siv: &mut Cursive) -> impl View {
                     );
                 })
                 .with_name("no_scroll"),
+        )
+        .child(
+            "Alignment Index:",
+            Checkbox::new()
+                .with_checked(style_settings.show_index)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.show_index = check,
+                        move |v| v.dh.style.show_index = check,
+                    );
+                })
+                .with_name("show_index"),
+        )
+        .child(
+            "Column Ruler:",
+            Checkbox::new()
+                .with_checked(style_settings.ruler)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.ruler = check,
+                        move |v| v.dh.style.ruler = check,
+                    );
+                })
+                .with_name("ruler"),
+        )
+        .child(
+            "Difference Rows:",
+            Checkbox::new()
+                .with_checked(style_settings.diff_rows)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.diff_rows = check,
+                        move |v| v.dh.style.diff_rows = check,
+                    );
+                })
+                .with_name("diff_rows"),
+        )
+        .child(
+            "Fold Identical:",
+            Checkbox::new()
+                .with_checked(style_settings.fold)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.fold = check,
+                        move |v| v.dh.style.fold = check,
+                    );
+                })
+                .with_name("fold"),
+        )
+        .child(
+            "Diff Map:",
+            Checkbox::new()
+                .with_checked(style_settings.diff_map)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.diff_map = check,
+                        move |v| v.dh.style.diff_map = check,
+                    );
+                })
+                .with_name("diff_map"),
+        )
+        .child(
+            "Byte Classes:",
+            Checkbox::new()
+                .with_checked(style_settings.byte_classes)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.byte_classes = check,
+                        move |v| v.dh.style.byte_classes = check,
+                    );
+                })
+                .with_name("byte_classes"),
+        )
+        .child(
+            "Entropy Colors:",
+            Checkbox::new()
+                .with_checked(style_settings.entropy)
+                .on_change(|s, check| {
+                    on_hexview(
+                        s,
+                        move |v| v.dh.style.entropy = check,
+                        move |v| v.dh.style.entropy = check,
+                    );
+                })
+                .with_name("entropy"),
         );
     let right_side = SelectView::new()
         .with_all([
@@ -0,0 +1,460 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyModifiers};
+use sha2::{Digest, Sha256};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    backend::{Backend, BackgroundColor, Color, Cross, Effect},
+    control::Settings,
+    doublehex::elide_middle,
+    notice::{Notice, Severity},
+    theme::ColorDepth,
+};
+
+const BOTTOM_TEXT: &str = "Enter: Open | /: Filter | q: Quit";
+
+/// How the files of the same path in both directories compare
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// same size and hash
+    Same,
+    /// a different size or hash, or one of them could not be read
+    Different,
+    /// only in the first directory
+    OnlyFirst,
+    /// only in the second directory
+    OnlySecond,
+}
+
+impl Status {
+    /// The tag in front of the path in the list, all of the same width
+    fn tag(self) -> &'static str {
+        match self {
+            Status::Same => "same   ",
+            Status::Different => "differs",
+            Status::OnlyFirst => "only A ",
+            Status::OnlySecond => "only B ",
+        }
+    }
+    fn color(self) -> Color {
+        match self {
+            Status::Same => Color::HexSame,
+            Status::Different => Color::HexDiff,
+            Status::OnlyFirst | Status::OnlySecond => Color::HexOneside,
+        }
+    }
+}
+
+/// A file in at least one of the directories
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// relative to the directories
+    pub path: PathBuf,
+    pub status: Status,
+}
+
+/// Adds the paths of all files in the directory and the ones below it to `files`,
+/// with the prefix in front of them
+fn files_below(dir: &Path, prefix: &Path, files: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = prefix.join(entry.file_name());
+        // symlinks to directories are not followed, so that they can not loop
+        if entry.file_type()?.is_dir() {
+            files_below(&entry.path(), &path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+fn hash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Compares the files by their sizes, and only hashes them if those are the same
+fn compare(paths: &[PathBuf; 2]) -> Status {
+    let sizes = [&paths[0], &paths[1]].map(|path| std::fs::metadata(path).map(|m| m.len()));
+    match sizes {
+        [Ok(first), Ok(second)] if first == second => match (hash(&paths[0]), hash(&paths[1])) {
+            (Ok(first), Ok(second)) if first == second => Status::Same,
+            _ => Status::Different,
+        },
+        _ => Status::Different,
+    }
+}
+
+/// The text cut off or padded with spaces to exactly `width` columns
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            break;
+        }
+        used += char_width;
+        fitted.push(c);
+    }
+    fitted + &" ".repeat(width - used)
+}
+
+/// The paths of the files below both directories, relative to them
+pub fn relative_paths(dirs: &[PathBuf; 2]) -> io::Result<[BTreeSet<PathBuf>; 2]> {
+    let mut files = [BTreeSet::new(), BTreeSet::new()];
+    for (dir, files) in dirs.iter().zip(&mut files) {
+        files_below(dir, Path::new(""), files)?;
+    }
+    Ok(files)
+}
+
+/// The files of both directories sorted by their paths, with the ones of the same path
+/// in both of them compared
+pub fn scan(dirs: &[PathBuf; 2]) -> io::Result<Vec<Entry>> {
+    let files = relative_paths(dirs)?;
+    let entries = files[0].union(&files[1]).map(|path| {
+        let status = match (files[0].contains(path), files[1].contains(path)) {
+            (true, false) => Status::OnlyFirst,
+            (false, true) => Status::OnlySecond,
+            _ => compare(&[dirs[0].join(path), dirs[1].join(path)]),
+        };
+        Entry {
+            path: path.clone(),
+            status,
+        }
+    });
+    Ok(entries.collect())
+}
+
+/// What the list was left for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListEvent {
+    /// compare the files of the entry with this index
+    Open(usize),
+    Quit,
+}
+
+/// The list of the files of two directories, of which the ones in both can be chosen
+/// to compare them
+pub struct DirList {
+    dirs: [PathBuf; 2],
+    entries: Vec<Entry>,
+    /// only the entries whose paths contain this are shown
+    filter: String,
+    /// whether typed characters go into the filter
+    filtering: bool,
+    /// the index of the cursor in the shown entries
+    selected: usize,
+    /// the index of the first shown entry in the top row
+    scroll: usize,
+    /// the number of rows of entries when the list was drawn last, for paging
+    rows: usize,
+    notice: Option<Notice>,
+}
+
+impl DirList {
+    pub fn new(dirs: [PathBuf; 2], entries: Vec<Entry>) -> Self {
+        DirList {
+            dirs,
+            entries,
+            filter: String::new(),
+            filtering: false,
+            selected: 0,
+            scroll: 0,
+            rows: 1,
+            notice: None,
+        }
+    }
+    /// The indexes of the entries that match the filter
+    fn shown(&self) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|&i| {
+                let path = self.entries[i].path.to_string_lossy();
+                path.contains(self.filter.as_str())
+            })
+            .collect()
+    }
+    /// The files of an entry in both directories
+    pub fn paths(&self, index: usize) -> [PathBuf; 2] {
+        let path = &self.entries[index].path;
+        [self.dirs[0].join(path), self.dirs[1].join(path)]
+    }
+    /// Compares the files of an entry again, since they can be edited in the view
+    pub fn rescan(&mut self, index: usize) {
+        self.entries[index].status = compare(&self.paths(index));
+    }
+    pub fn set_notice(&mut self, severity: Severity, text: impl Into<String>) {
+        self.notice = Some(Notice::new(severity, text));
+    }
+    /// Moves the cursor by `amount` entries, stopping at the first and the last one
+    fn move_cursor(&mut self, amount: isize) {
+        let last = self.shown().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(amount).min(last);
+    }
+    /// Handles a key, returning what the list was left for if it was
+    pub fn key(&mut self, key: KeyEvent) -> Option<ListEvent> {
+        // the notice is hidden by the next key, like the ones of the views
+        self.notice = None;
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(ListEvent::Quit);
+        }
+        if self.filtering {
+            match key.code {
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.filter.push(c);
+                    self.selected = 0;
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.selected = 0;
+                }
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                _ => (),
+            }
+            return None;
+        }
+        let page = self.rows as isize;
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-page),
+            KeyCode::PageDown => self.move_cursor(page),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.move_cursor(isize::MAX),
+            KeyCode::Char('/') => self.filtering = true,
+            // a filter is cleared before leaving
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.selected = 0;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Some(ListEvent::Quit),
+            KeyCode::Enter => {
+                let index = *self.shown().get(self.selected)?;
+                let side = match self.entries[index].status {
+                    Status::Same | Status::Different => return Some(ListEvent::Open(index)),
+                    Status::OnlyFirst => &self.dirs[0],
+                    Status::OnlySecond => &self.dirs[1],
+                };
+                let text = format!("Only in {}", side.to_string_lossy());
+                self.set_notice(Severity::Warning, text);
+            }
+            _ => (),
+        }
+        None
+    }
+    /// Draws the list with the title in the first line and the filter or key hints
+    /// in the last one
+    pub fn draw<B: Backend>(&mut self, printer: &mut B) {
+        let (width, height) = printer.size();
+        let shown = self.shown();
+        self.rows = height.saturating_sub(2).max(1);
+        // the cursor is kept in the shown rows
+        self.selected = self.selected.min(shown.len().saturating_sub(1));
+        self.scroll = self
+            .scroll
+            .min(self.selected)
+            .max((self.selected + 1).saturating_sub(self.rows));
+        let [first, second] = [0, 1].map(|i| self.dirs[i].to_string_lossy().to_string());
+        let title = format!("biodiff: {first} <-> {second}");
+        printer.set_title(&title);
+        printer.set_line(0);
+        printer.append_text(
+            &fit(&elide_middle(&title, width), width),
+            Color::HexSame,
+            BackgroundColor::Blank,
+            Effect::inverted(),
+        );
+        for row in 0..height.saturating_sub(2) {
+            printer.set_line(row + 1);
+            let entry = match shown.get(self.scroll + row) {
+                Some(&index) => &self.entries[index],
+                None => {
+                    printer.append_text(
+                        &fit("", width),
+                        Color::HexSame,
+                        BackgroundColor::Blank,
+                        Effect::none(),
+                    );
+                    continue;
+                }
+            };
+            let bg = if self.scroll + row == self.selected {
+                BackgroundColor::Highlight
+            } else {
+                BackgroundColor::Blank
+            };
+            let tag = entry.status.tag();
+            printer.append_text(tag, entry.status.color(), bg, Effect::none());
+            // long paths lose their middle, which keeps the file name
+            let path_width = width.saturating_sub(tag.len());
+            let path = format!(" {}", entry.path.to_string_lossy());
+            let path = fit(&elide_middle(&path, path_width), path_width);
+            printer.append_text(&path, Color::HexSame, bg, Effect::none());
+        }
+        let count = |status| {
+            let count = self.entries.iter().filter(|e| e.status == status).count();
+            format!("{count} {}", status.tag().trim_end())
+        };
+        let counts = [
+            Status::Same,
+            Status::Different,
+            Status::OnlyFirst,
+            Status::OnlySecond,
+        ]
+        .map(count)
+        .join(", ");
+        let filter = match (self.filtering, self.filter.is_empty()) {
+            (true, _) => Some(format!("Filter: {}", self.filter)),
+            (false, false) => Some(format!("Filtered by {}", self.filter)),
+            (false, true) => None,
+        };
+        // the key hints are cut off first when the line is too long
+        let fields = [filter.as_deref(), Some(counts.as_str()), Some(BOTTOM_TEXT)];
+        let mut rest = fields.into_iter().flatten().collect::<Vec<_>>().join(" | ");
+        printer.set_line(height.saturating_sub(1));
+        // warnings and errors are shown in the colors of the differences, like in the views
+        let rest_width = match &self.notice {
+            Some(notice) => {
+                let color = match notice.severity {
+                    Severity::Info => Color::HexSame,
+                    Severity::Warning => Color::HexOneside,
+                    Severity::Error => Color::HexDiff,
+                };
+                let text = elide_middle(&notice.text, width);
+                printer.append_text(&text, color, BackgroundColor::Blank, Effect::inverted());
+                rest = format!(" | {rest}");
+                width.saturating_sub(text.width())
+            }
+            None => width,
+        };
+        printer.append_text(
+            &fit(&rest, rest_width),
+            Color::HexSame,
+            BackgroundColor::Blank,
+            Effect::inverted(),
+        );
+    }
+    /// Draws the list and handles the keys until it is left
+    fn interact<B: Backend>(&mut self, printer: &mut B) -> io::Result<ListEvent> {
+        loop {
+            self.draw(printer);
+            printer.refresh()?;
+            // everything else, like resizing the terminal, only draws the list again
+            if let Event::Key(key) = read()? {
+                if let Some(event) = self.key(key) {
+                    return Ok(event);
+                }
+            }
+        }
+    }
+}
+
+/// Shows the list of the files of both directories, from which the chosen files are compared
+/// with `open`. The list is shown again after the comparison, and also shows the error
+/// if there was one.
+pub fn run(
+    dirs: [PathBuf; 2],
+    monochrome: bool,
+    mut open: impl FnMut([PathBuf; 2]) -> Result<(), String>,
+) -> io::Result<()> {
+    let entries = scan(&dirs)?;
+    let mut list = DirList::new(dirs, entries);
+    let mut style = Settings::from_config().unwrap_or_default().style;
+    style.monochrome = monochrome;
+    style.color_depth = ColorDepth::detect();
+    let palette = style.palette();
+    loop {
+        // the terminal is left to the view while it is open
+        let mut cross = Cross::init(palette, monochrome, false);
+        let event = list.interact(&mut cross);
+        cross.uninit();
+        match event? {
+            ListEvent::Open(index) => {
+                if let Err(e) = open(list.paths(index)) {
+                    list.set_notice(Severity::Error, e);
+                }
+                list.rescan(index);
+            }
+            ListEvent::Quit => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::capture::Capture, testing::temp_path};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn directories() {
+        let root = temp_path("dirlist");
+        let dirs = [root.join("a"), root.join("b")];
+        for dir in &dirs {
+            std::fs::create_dir_all(dir.join("sub")).unwrap();
+        }
+        let files: [(&str, [Option<&[u8]>; 2]); 4] = [
+            ("same", [Some(b"123"), Some(b"123")]),
+            ("sub/changed", [Some(b"123"), Some(b"124")]),
+            ("first", [Some(b"1"), None]),
+            ("second", [None, Some(b"1")]),
+        ];
+        for (path, contents) in files {
+            for (dir, content) in dirs.iter().zip(contents) {
+                if let Some(content) = content {
+                    std::fs::write(dir.join(path), content).unwrap();
+                }
+            }
+        }
+        let entries = scan(&dirs).unwrap();
+        let statuses = entries
+            .iter()
+            .map(|e| (e.path.clone(), e.status))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("first", Status::OnlyFirst),
+            ("same", Status::Same),
+            ("second", Status::OnlySecond),
+            ("sub/changed", Status::Different),
+        ]
+        .map(|(path, status)| (PathBuf::from(path), status));
+        assert_eq!(statuses, expected);
+        let mut list = DirList::new(dirs.clone(), entries);
+        let mut capture = Capture::new(60, 6);
+        list.draw(&mut capture);
+        let text = capture.text();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert!(lines[1].starts_with("only A  first"));
+        assert!(lines[5].starts_with("1 same, 1 differs, 1 only A, 1 only B"));
+        // files only in one directory can not be opened
+        assert_eq!(list.key(key(KeyCode::Enter)), None);
+        assert!(list.notice.is_some());
+        // the filter only leaves the changed file, which is opened
+        list.key(key(KeyCode::Char('/')));
+        for c in "chan".chars() {
+            list.key(key(KeyCode::Char(c)));
+        }
+        list.key(key(KeyCode::Enter));
+        assert_eq!(list.key(key(KeyCode::Enter)), Some(ListEvent::Open(3)));
+        assert_eq!(list.paths(3), dirs.map(|dir| dir.join("sub/changed")));
+        assert_eq!(list.key(key(KeyCode::Esc)), None);
+        assert_eq!(list.key(key(KeyCode::Esc)), Some(ListEvent::Quit));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
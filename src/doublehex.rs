@@ -1,39 +1,68 @@
-use std::iter::repeat;
+use std::{
+    iter::repeat,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use crate::{
-    backend::{Backend, BackgroundColor, Color, Effect},
-    cursor::{CursorActive, CursorState},
+    annotation::Annotations,
+    backend::{Backend, BackgroundColor, Color, Effect, PlainText},
+    cache::AlignCache,
+    cursor::{CursorActive, CursorState, JumpHistory, Move},
+    edit::{Editor, Edits},
+    file::FileContent,
+    ignore::Ignore,
+    notice::{Notice, Severity},
+    prompt::Prompt,
     style::{
-        background_color, byte, byte_effect, disp_addr, disp_ascii, disp_bottom_addr,
-        disp_column_blocks, spacer_background_color, ByteData, ColumnSetting, Style, FRONT_PAD,
-        MIDDLE_PAD,
+        background_color, byte, byte_effect, disp_column_blocks, spacer_background_color, ByteData,
+        ColumnSetting, Style, DIFF_MAP_WIDTH, DIFF_MARKER, ENTROPY_LEVELS, FRONT_PAD, MIDDLE_PAD,
     },
-    util::autocorrelation,
+    theme::ThemeColor,
+    threeway::{Change, ThreeWay},
+    util::{human_size, sampled_autocorrelation, sliding_entropy},
 };
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The width that the names of the files keep at least in the title line
+/// when it shows more than the name of the view
+const MIN_NAME_WIDTH: usize = 16;
+
+/// The frames of the busy indicator in the title line, which shows the next one every tick
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
 
 /// A line that can be printed using a backend for two hex views next to each other
 #[derive(Debug, Clone)]
 pub struct DoubleHexLine {
     pub address: [Option<usize>; 2],
     pub bytes: Vec<(ByteData, ByteData)>,
+    /// the number of identical bytes folded into this line, which are summarized
+    /// instead of showing the bytes
+    pub fold: Option<usize>,
 }
 
 impl DoubleHexLine {
-    fn print_unimportant<B: Backend>(&self, printer: &mut B, text: &str) {
-        printer.append_text(
-            text,
-            Color::Unimportant,
-            BackgroundColor::Blank,
-            Effect::none(),
-        );
+    fn print_unimportant<B: Backend>(&self, printer: &mut B, text: &str, bg: BackgroundColor) {
+        printer.append_text(text, Color::Unimportant, bg, Effect::none());
     }
     /// Prints one side of the line
     fn print_half<B>(&self, printer: &mut B, line: usize, style: Style, first: bool)
     where
         B: Backend,
     {
-        self.print_unimportant(printer, FRONT_PAD);
+        // without colors, rows with differences are marked in front
+        let is_different = self.bytes.iter().any(|(a, b)| a.differs(*b));
+        let row_bg = |bg| style.row_background(bg, is_different);
+        let blank = row_bg(BackgroundColor::Blank);
+        if style.monochrome && is_different {
+            self.print_unimportant(printer, DIFF_MARKER, blank);
+        } else {
+            self.print_unimportant(printer, FRONT_PAD, blank);
+        }
         let address = self.address[(!first) as usize];
         let mut bytes = vec![(ByteData::default(), ByteData::default()); self.bytes.len()];
         for (i, (a, b)) in self.bytes.iter().enumerate() {
@@ -42,48 +71,110 @@ impl DoubleHexLine {
             } else {
                 &mut bytes[i]
             };
-            *target = if first { (*a, *b) } else { (*b, *a) }
+            *target = if first {
+                (*a, *b)
+            } else {
+                style.second_half(*b, *a)
+            }
         }
         if !style.right_to_left {
-            self.print_unimportant(printer, &disp_addr(address, style.addr_width));
+            self.print_unimportant(printer, &style.line_addr(address, !first), blank);
         }
         let width = self.bytes.len();
-        for (i, (a, b)) in bytes.iter().enumerate() {
-            let s = style.mode.disp(byte(*a), false);
-            let color = style.mode.color(*a, *b, line);
-            let effect = byte_effect(*a);
-            let bg = background_color(*a);
-            printer.append_text(&s, color, bg, effect);
-            if style.spacer && i + 1 != width && i % 8 == 7 {
-                let spacer_bg = spacer_background_color(*a, style.right_to_left);
-                printer.append_text(" ", color, spacer_bg, effect);
+        if let Some(folded) = self.fold {
+            self.print_fold(printer, folded, style, blank);
+        } else {
+            for (i, (a, b)) in bytes.iter().enumerate() {
+                let s = style.disp_byte(*a, false);
+                let color = style.half_color(*a, *b, line, first);
+                let effect = byte_effect(*a, *b, style.inverted_differences());
+                let bg = row_bg(background_color(*a));
+                printer.append_text(&s, color, bg, effect);
+                if i + 1 != width && style.ends_group(i) {
+                    let spacer_bg = row_bg(spacer_background_color(*a, style.right_to_left));
+                    printer.append_text(" ", color, spacer_bg, effect);
+                }
             }
         }
         if style.right_to_left {
-            self.print_unimportant(printer, &disp_addr(address, style.addr_width));
+            self.print_unimportant(printer, &style.line_addr(address, !first), blank);
         }
-        for col_disp in [
-            (style.ascii_col, disp_ascii as fn(_) -> _),
-            (style.bars_col, disp_column_blocks),
-        ]
-        .iter()
-        .filter_map(|(c, d)| c.then(|| d))
-        {
-            self.print_unimportant(printer, MIDDLE_PAD);
-            for (a, b) in &bytes {
-                let s = col_disp(byte(*a));
-                let color = style.mode.color(*a, *b, line);
-                let effect = byte_effect(*a);
-                let bg = background_color(*a);
-                printer.append_text(&s, color, bg, effect);
+        let columns = [
+            (style.ascii_col, self.text(style, first)),
+            (
+                style.bars_col,
+                bytes
+                    .iter()
+                    .map(|(a, _)| disp_column_blocks(byte(*a)))
+                    .collect(),
+            ),
+        ];
+        for (_, column) in columns.iter().filter(|(enabled, _)| *enabled) {
+            self.print_unimportant(printer, MIDDLE_PAD, blank);
+            if self.fold.is_some() {
+                self.print_unimportant(printer, &" ".repeat(column.len()), blank);
+                continue;
+            }
+            for (s, (a, b)) in column.iter().zip(&bytes) {
+                let color = style.half_color(*a, *b, line, first);
+                let effect = byte_effect(*a, *b, style.inverted_differences());
+                let bg = row_bg(background_color(*a));
+                printer.append_text(s, color, bg, effect);
             }
         }
     }
+    /// Prints the summary of the folded bytes in place of the hex columns
+    fn print_fold<B: Backend>(
+        &self,
+        printer: &mut B,
+        folded: usize,
+        style: Style,
+        bg: BackgroundColor,
+    ) {
+        let width = style.n_column_width(self.bytes.len());
+        let summary: String = format!("\u{2261} {folded:#x} identical bytes")
+            .chars()
+            .take(width)
+            .collect();
+        printer.append_text(
+            &format!("{summary:width$}"),
+            Color::HexSame,
+            bg,
+            Effect::none(),
+        );
+    }
+    /// The characters of one side in the ascii column, in the order they are shown
+    fn text(&self, style: Style, first: bool) -> Vec<String> {
+        let side: Vec<_> = self
+            .bytes
+            .iter()
+            .map(|(a, b)| byte(if first { *a } else { *b }))
+            .collect();
+        let mut text: Vec<_> = style
+            .encoding
+            .decode(&side)
+            .into_iter()
+            .zip(&self.bytes)
+            .map(|(c, (a, b))| {
+                let is_gap = if first { a.is_gap } else { b.is_gap };
+                String::from(if is_gap { style.gap_text_char } else { c })
+            })
+            .collect();
+        if style.right_to_left {
+            text.reverse();
+        }
+        text
+    }
     /// Prints the DoubleHexLine using the given backend at the line given in `line`
-    /// with the views being on the left and right
-    fn print_hor<B: Backend>(&self, printer: &mut B, line: usize, style: Style) {
+    /// with the views being on the left and right, or only the first one in single-file mode.
+    /// The second half is taken from `second`, which is the line itself unless the
+    /// second half has its own columns.
+    fn print_hor<B: Backend>(&self, second: &Self, printer: &mut B, line: usize, style: Style) {
         printer.set_line(line);
         self.print_half(printer, line, style, true);
+        if style.single {
+            return;
+        }
 
         printer.append_text(
             MIDDLE_PAD,
@@ -91,22 +182,197 @@ impl DoubleHexLine {
             BackgroundColor::Blank,
             Effect::none(),
         );
-        self.print_half(printer, line, style, false);
+        second.print_half(printer, line, style, false);
     }
 
-    fn print_vert<B: Backend>(&self, printer: &mut B, lines: [usize; 2], style: Style) {
+    fn print_vert<B: Backend>(
+        &self,
+        second: &Self,
+        printer: &mut B,
+        lines: [usize; 2],
+        style: Style,
+    ) {
         printer.set_line(lines[0]);
         self.print_half(printer, lines[0], style, true);
 
         printer.set_line(lines[1]);
-        self.print_half(printer, lines[1], style, false);
+        second.print_half(printer, lines[1], style, false);
     }
+    /// Prints the halves of the line in the lines 0 and 1, as in the vertical split
+    pub fn print_halves<B: Backend>(&self, printer: &mut B, style: Style) {
+        self.print_vert(self, printer, [0, 1], style);
+    }
+    /// The text of both halves of the line, as they would be printed in the vertical split
+    pub fn plain_text(&self, style: Style) -> [String; 2] {
+        let mut printer = PlainText::default();
+        self.print_halves(&mut printer, style);
+        let mut lines = printer.into_lines().into_iter();
+        [(); 2].map(|()| lines.next().unwrap_or_default())
+    }
+}
+
+/// What painting the cursor needs to know about the other bytes of its row
+#[derive(Clone, Copy, Debug)]
+pub struct CursorRow {
+    /// the characters at the cursor in the ascii column of both files
+    pub text: [char; 2],
+    /// whether any byte of the row differs between the files
+    pub is_different: bool,
+    /// the column of the cursor in the second half if it has its own columns,
+    /// or None if the column is clipped there (see `SecondGrid`)
+    pub second_column: Option<usize>,
+}
+
+/// How the second half shows its bytes when it has its own column setting, which only
+/// the unaligned view allows. Its rows start at multiples of its bytes per row in the
+/// second file, and the cursor is in the same row as in the first half.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecondGrid {
+    /// the number of columns that are shown
+    pub columns: usize,
+    /// the number of bytes in a row, which is more than `columns` if the rows are clipped
+    pub bytes_per_row: usize,
+}
+
+/// How far an offset is through `len` bytes in percent, counting the byte at the
+/// offset so that the last byte is at 100%, padded to the same width for every offset
+fn percent(offset: Option<isize>, len: usize) -> String {
+    match offset {
+        Some(offset) if len > 0 => {
+            let through = (offset + 1).clamp(0, len as isize) as usize;
+            format!("{:>3}%", through * 100 / len)
+        }
+        _ => String::from("    "),
+    }
+}
+
+/// A cell of the diff map, which summarizes a slice of the compared bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MapCell {
+    pub different: bool,
+    /// whether there are bytes in only one of the files
+    pub inserted: bool,
+    /// whether some of the bytes are in view
+    pub in_view: bool,
+}
+
+/// What the title line shows about one of the files
+#[derive(Clone, Copy, Debug)]
+pub struct FileTitle<'a> {
+    pub name: &'a str,
+    /// the number of compared bytes
+    pub len: usize,
+    /// whether the file changed on disk since it was read
+    pub modified: bool,
+    /// whether bytes were overwritten in edit mode and not saved yet
+    pub edited: bool,
+}
+
+impl FileTitle<'_> {
+    /// The size and state shown after the name, like `[1.5 KiB, 1536 bytes, modified, edited]`
+    fn details(&self) -> String {
+        let size = if self.len < 1024 {
+            format!("{} bytes", self.len)
+        } else {
+            format!("{}, {} bytes", human_size(self.len), self.len)
+        };
+        let modified = if self.modified { ", modified" } else { "" };
+        let edited = if self.edited { ", edited" } else { "" };
+        format!("[{size}{modified}{edited}]")
+    }
+    /// The name and details in at most `width` columns, shortening the name in the middle.
+    /// If there is not even space for a few characters of the name, the details are left out
+    /// and a modified file is marked with a `*` and an edited one with a `+`.
+    fn fit(&self, width: usize) -> String {
+        const MIN_NAME_WIDTH: usize = 8;
+        let details = self.details();
+        let name_width = width.saturating_sub(details.width() + 1);
+        let marks = match (self.modified, self.edited) {
+            (true, true) => "*+",
+            (true, false) => "*",
+            (false, true) => "+",
+            (false, false) => "",
+        };
+        if name_width >= MIN_NAME_WIDTH.min(self.name.width()) {
+            format!("{} {details}", elide_middle(self.name, name_width))
+        } else {
+            let name = elide_middle(self.name, width.saturating_sub(marks.len()));
+            format!("{name}{marks}")
+        }
+    }
+}
+
+/// Shortens the text to at most `width` columns by replacing its middle with an ellipsis
+pub fn elide_middle(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    // the ellipsis takes one column, and the end gets the rest of an odd width
+    let front_width = (width - 1) / 2;
+    let back_width = width - 1 - front_width;
+    let take_width = |chars: &mut dyn Iterator<Item = char>, max_width: usize| {
+        let mut width = 0;
+        chars
+            .take_while(|c| {
+                width += c.width().unwrap_or(0);
+                width <= max_width
+            })
+            .collect::<Vec<_>>()
+    };
+    let front = take_width(&mut text.chars(), front_width);
+    let mut back = take_width(&mut text.chars().rev(), back_width);
+    back.reverse();
+    front
+        .into_iter()
+        .chain(std::iter::once('…'))
+        .chain(back)
+        .collect()
 }
 
 pub struct DoubleHexContext {
     pub cursor: CursorState,
     pub style: Style,
     pub cursor_act: CursorActive,
+    /// the count prefix typed before the next action
+    pub pending_count: Option<usize>,
+    /// a message shown in the bottom line until the next action or for a few seconds
+    pub notice: Option<Notice>,
+    /// whether the files are followed as they grow, shared with the thread checking them
+    pub follow: Arc<AtomicBool>,
+    /// the addresses of the first compared bytes in the files, which are added
+    /// to the shown addresses when only a window of them is compared
+    pub bases: [usize; 2],
+    /// whether the files changed on disk since they were read
+    pub modified: [bool; 2],
+    /// the positions before jumps, for going back to them
+    pub history: JumpHistory,
+    /// the labeled ranges of the files, which are looked up with the shown addresses
+    pub annotations: [Annotations; 2],
+    /// the differences that are not counted, which are looked up with the shown addresses
+    pub ignore: Ignore,
+    /// the bytes of the files that were overwritten in edit mode
+    pub edits: [Edits; 2],
+    pub editor: Editor,
+    /// the command line, which replaces the bottom line while it is open
+    pub prompt: Prompt,
+    /// the layout of the second half if it has other columns than the first one
+    pub second_grid: Option<SecondGrid>,
+    /// whether both cursors of the unaligned view move together at their current offset,
+    /// without leaving the bytes where both files overlap
+    pub offset_lock: bool,
+    /// where finished alignments are kept, or None if the files are always aligned again
+    pub align_cache: Option<AlignCache>,
+    /// which of the files changed the differences when they are compared with their base,
+    /// which is looked up with the shown addresses
+    pub three_way: Option<ThreeWay>,
+    /// the frame of the busy indicator, which turns with every tick while something runs
+    spinner: usize,
+    /// the smallest screen the layout fits on while the screen is smaller than that,
+    /// in which case a message is shown instead of the bytes
+    pub too_small: Option<(usize, usize)>,
 }
 
 impl DoubleHexContext {
@@ -117,73 +383,332 @@ impl DoubleHexContext {
             cursor,
             style: Style::default(),
             cursor_act: CursorActive::Both,
+            pending_count: None,
+            notice: None,
+            follow: Arc::new(AtomicBool::new(false)),
+            bases: [0, 0],
+            modified: [false, false],
+            history: JumpHistory::default(),
+            annotations: Default::default(),
+            ignore: Ignore::default(),
+            edits: Default::default(),
+            editor: Editor::default(),
+            prompt: Prompt::default(),
+            second_grid: None,
+            offset_lock: false,
+            align_cache: None,
+            three_way: None,
+            spinner: 0,
+            too_small: None,
+        }
+    }
+    /// The addresses in the files as they are shown, which includes the bases
+    pub fn shown_addresses(&self, addresses: [Option<usize>; 2]) -> [Option<usize>; 2] {
+        [0, 1].map(|i| addresses[i].map(|addr| addr + self.bases[i]))
+    }
+    /// Converts a shown address of one of the files (like one that was typed in) to
+    /// the address in the compared bytes
+    pub fn file_address(&self, second: bool, addr: usize) -> Result<usize, String> {
+        let base = self.bases[second as usize];
+        addr.checked_sub(base).ok_or_else(|| {
+            format!("Target address {addr:#x} is before the compared bytes (>= {base:#x})")
+        })
+    }
+    /// Exchanges everything about the two files, for when they are swapped
+    pub fn swap_files(&mut self) {
+        self.bases.swap(0, 1);
+        if let Some(base) = &mut self.style.relative_to {
+            base.swap(0, 1);
+        }
+        self.modified.swap(0, 1);
+        self.history.swap_files();
+        self.annotations.swap(0, 1);
+        self.ignore.swap_files();
+        if let Some(three_way) = &mut self.three_way {
+            three_way.swap_files();
+        }
+        self.edits.swap(0, 1);
+        self.cursor_act = self.cursor_act.swapped();
+    }
+    /// Whether the bytes at the addresses (of the compared bytes) of both files count as
+    /// different, see `Ignore::differ`
+    pub fn differ(&self, addresses: [Option<usize>; 2], bytes: [Option<u8>; 2]) -> bool {
+        self.ignore.differ(self.shown_addresses(addresses), bytes)
+    }
+    /// Whether the bytes are different, but the difference is ignored
+    pub fn is_ignored(&self, addresses: [Option<usize>; 2], bytes: [Option<u8>; 2]) -> bool {
+        bytes[0] != bytes[1] && !self.differ(addresses, bytes)
+    }
+    /// Which of the files changed the different bytes in a three-way comparison,
+    /// or None if there is no base or the bytes do not count as different
+    pub fn change(&self, addresses: [Option<usize>; 2], bytes: [Option<u8>; 2]) -> Option<Change> {
+        let three_way = self.three_way.as_ref()?;
+        if !self.differ(addresses, bytes) {
+            return None;
+        }
+        three_way.change(self.shown_addresses(addresses))
+    }
+    /// Whether the jumps to the next and previous difference stop at the bytes, which are
+    /// the differences of the kind that the three-way comparison is filtered by
+    pub fn is_jump_target(&self, addresses: [Option<usize>; 2], bytes: [Option<u8>; 2]) -> bool {
+        match &self.three_way {
+            Some(three_way) => {
+                self.differ(addresses, bytes)
+                    && three_way.is_jump_target(three_way.change(self.shown_addresses(addresses)))
+            }
+            None => self.differ(addresses, bytes),
+        }
+    }
+    /// Goes on to the next kind of differences the jumps stop at in a three-way comparison
+    pub fn cycle_change_filter(&mut self) {
+        match &mut self.three_way {
+            Some(three_way) => {
+                three_way.cycle_filter();
+                let text = three_way
+                    .filter
+                    .map_or("Jumping to all differences", Change::describe);
+                self.set_notice(Severity::Info, text)
+            }
+            None => self.set_notice(
+                Severity::Warning,
+                "Filtering the differences needs a base file (biodiff BASE OURS THEIRS)",
+            ),
+        }
+    }
+    /// Whether the bytes at the addresses (of the compared bytes) of both files
+    /// were overwritten in edit mode
+    pub fn is_edited(&self, addresses: [Option<usize>; 2]) -> [bool; 2] {
+        [0, 1].map(|i| addresses[i].map_or(false, |addr| self.edits[i].contains(addr)))
+    }
+    /// The bytes at the addresses of both files as they are shown, which are
+    /// the edited ones where they were overwritten
+    pub fn edited_bytes(
+        &self,
+        addresses: [Option<usize>; 2],
+        bytes: [Option<u8>; 2],
+    ) -> [Option<u8>; 2] {
+        [0, 1].map(|i| match (addresses[i], bytes[i]) {
+            (Some(addr), Some(byte)) => Some(self.edits[i].get(addr).unwrap_or(byte)),
+            (_, byte) => byte,
+        })
+    }
+    /// The contents of both files with their edits, see `Edits::apply`
+    pub fn edited_files(&self, files: [FileContent; 2]) -> [FileContent; 2] {
+        [0, 1].map(|i| self.edits[i].apply(&files[i]))
+    }
+    /// Whether one of the files has edits that were not saved yet
+    pub fn has_unsaved_edits(&self) -> bool {
+        self.edits.iter().any(|edits| edits.unsaved)
+    }
+    /// The backgrounds of the bytes at the addresses (of the compared bytes) of both files,
+    /// which are the colors of their innermost annotations or `default` for ones without
+    pub fn annotation_tints(
+        &self,
+        addresses: [Option<usize>; 2],
+        default: ThemeColor,
+    ) -> [Option<ThemeColor>; 2] {
+        [0, 1].map(|i| {
+            let annotation = self.annotations[i].at(addresses[i]? + self.bases[i])?;
+            Some(annotation.color.unwrap_or(default))
+        })
+    }
+    /// Whether the cursor follows the end of files that grow
+    pub fn following(&self) -> bool {
+        self.follow.load(Ordering::Relaxed)
+    }
+    /// Starts or stops following the files
+    pub fn toggle_follow(&self) {
+        self.follow.fetch_xor(true, Ordering::Relaxed);
+    }
+    /// Shows a message in the bottom line, replacing the previous one
+    pub fn set_notice(&mut self, severity: Severity, text: impl Into<String>) {
+        self.notice = Some(Notice::new(severity, text));
+    }
+    pub fn notice_text(&self) -> Option<&str> {
+        self.notice.as_ref().map(|notice| notice.text.as_str())
+    }
+    /// Hides the notice if it was shown long enough at `now`, returning whether it was
+    pub fn expire_notice(&mut self, now: Instant) -> bool {
+        let expired = self.notice.as_ref().map_or(false, |n| n.is_expired(now));
+        if expired {
+            self.notice = None;
+        }
+        expired
+    }
+    /// Turns the busy indicator if something is running and hides the notice if it was shown
+    /// long enough at `now`, returning whether the bars have to be redrawn
+    pub fn tick(&mut self, now: Instant, busy: bool) -> bool {
+        if busy {
+            self.spinner = (self.spinner + 1) % SPINNER.len();
+        }
+        self.expire_notice(now) || busy
+    }
+    /// The busy indicator of the title line with what is running, like "/ searching",
+    /// or nothing if nothing is running.
+    /// Since it turns with every tick, a stalled thread can be told apart from a finished one.
+    fn busy_indicator(&self, busy: &[&str]) -> String {
+        match busy.is_empty() {
+            true => String::new(),
+            false => format!(" {} {}", SPINNER[self.spinner], busy.join(", ")),
+        }
+    }
+    /// Checks whether the layout of the style fits on a screen of the given size,
+    /// returning whether that changed
+    pub fn check_size(&mut self, (columns, rows): (usize, usize)) -> bool {
+        let (min_columns, min_rows) = self.style.min_size();
+        let too_small =
+            (columns < min_columns || rows < min_rows).then_some((min_columns, min_rows));
+        std::mem::replace(&mut self.too_small, too_small) != too_small
+    }
+    /// Blanks the screen with a message about it being too small in the middle,
+    /// see `check_size`
+    pub fn print_too_small<B: Backend>(&self, printer: &mut B) {
+        let (min_columns, min_rows) = match self.too_small {
+            Some(size) => size,
+            None => return,
+        };
+        let (width, height) = printer.size();
+        let message = format!("terminal too small (need at least {min_columns}x{min_rows})");
+        // the message is all ascii, so it can be cut off at any byte
+        let message = &message[..message.len().min(width)];
+        for line in 0..height {
+            let text = if line == height / 2 { message } else { "" };
+            printer.set_line(line);
+            printer.append_text(
+                &format!("{text:^width$}"),
+                Color::HexSame,
+                BackgroundColor::Blank,
+                Effect::none(),
+            );
         }
     }
     /// width of a screen half when in horizontal split
     fn hor_half_width(&self) -> usize {
         self.style.half_width(self.cursor.get_size_x())
     }
+    /// the number of columns shown in the second half
+    fn second_columns(&self) -> usize {
+        self.second_grid
+            .map_or(self.cursor.get_size_x(), |grid| grid.columns)
+    }
+    /// width of the second half, which differs from the first one if it has its own columns
+    fn second_half_width(&self) -> usize {
+        self.style.half_width(self.second_columns())
+    }
     /// height of a screen half when in vertical split
     fn vert_half_height(&self) -> usize {
-        self.cursor.get_size_y() + 1
+        self.cursor.get_size_y() + self.style.header_height()
     }
     fn full_width(&self) -> usize {
-        if self.style.vertical {
+        if self.style.single {
             self.hor_half_width()
+        } else if self.style.vertical {
+            self.hor_half_width().max(self.second_half_width())
         } else {
-            2 * self.hor_half_width() + MIDDLE_PAD.width()
+            self.hor_half_width() + MIDDLE_PAD.width() + self.second_half_width()
+        }
+    }
+    /// the column of the diff map, right of the hex views
+    fn map_column(&self) -> usize {
+        self.full_width() + DIFF_MAP_WIDTH - 1
+    }
+    /// the rows of the diff map, which reaches from the first row of bytes to the bottom line
+    fn map_rows(&self) -> Range<usize> {
+        self.style.header_height()..self.full_height() - 1
+    }
+    /// The number of cells of the diff map, or zero if it is not shown
+    pub fn map_cells(&self) -> usize {
+        match self.style.diff_map {
+            true => self.map_rows().len(),
+            false => 0,
+        }
+    }
+    /// The cell of the diff map at a position on the screen, if there is one
+    pub fn map_cell_at(&self, column: usize, row: usize) -> Option<usize> {
+        let rows = self.map_rows();
+        (self.style.diff_map && column == self.map_column() && rows.contains(&row))
+            .then(|| row - rows.start)
+    }
+    /// Prints the diff map, which has a cell for each of the `map_cells` slices of the
+    /// compared bytes, with the slices in view highlighted
+    pub fn print_diff_map<B: Backend>(&self, printer: &mut B, cells: &[MapCell]) {
+        if !self.style.diff_map {
+            return;
+        }
+        for (cell, row) in cells.iter().zip(self.map_rows()) {
+            let (text, color) = match cell {
+                MapCell { inserted: true, .. } => ("\u{2593}", Color::HexOneside),
+                MapCell {
+                    different: true, ..
+                } => ("\u{2592}", Color::HexDiff),
+                _ => (" ", Color::HexSame),
+            };
+            let bg = match cell.in_view {
+                true => BackgroundColor::Highlight,
+                false => BackgroundColor::Blank,
+            };
+            printer.set_pos(self.map_column(), row);
+            printer.append_text(text, color, bg, Effect::none());
         }
     }
     fn full_height(&self) -> usize {
-        if self.style.vertical {
-            self.cursor.get_size_y() * 2 + 3
+        if self.style.stacked() {
+            self.vert_half_height() * 2 + 1
         } else {
-            self.cursor.get_size_y() + 2
+            self.vert_half_height() + 1
         }
     }
     /// Prints a whole screen of hex data
     pub fn print_doublehex_screen<B: Backend>(&self, content: &[DoubleHexLine], backend: &mut B) {
-        for (i, line) in content.iter().enumerate() {
-            if self.style.vertical {
+        self.print_split_screen(content, content, backend)
+    }
+    /// Prints a whole screen of hex data, with the second half having its own lines
+    /// since it has its own columns (see `SecondGrid`)
+    pub fn print_split_screen<B: Backend>(
+        &self,
+        first: &[DoubleHexLine],
+        second: &[DoubleHexLine],
+        backend: &mut B,
+    ) {
+        let header = self.style.header_height();
+        for (i, (line, second)) in first.iter().zip(second).enumerate() {
+            if self.style.stacked() {
                 line.print_vert(
+                    second,
                     backend,
-                    [i + 1, self.vert_half_height() + i + 1],
+                    [i + header, self.vert_half_height() + i + header],
                     self.style,
                 );
             } else {
                 // we offset because of the title bar
-                line.print_hor(backend, i + 1, self.style);
+                line.print_hor(second, backend, i + header, self.style);
             }
         }
     }
-    /// returns the logical column of the cursor
-    fn col(&self) -> usize {
-        if self.style.right_to_left {
-            self.cursor.get_size_x() - 1 - self.cursor.get_x()
+    /// returns the positions of a cursor in the column `x` of a half with `columns` columns
+    /// in the hex, ascii and bars views of the first half, as far as they are shown
+    #[allow(clippy::type_complexity)]
+    fn cursor_positions(
+        &self,
+        x: usize,
+        columns: usize,
+    ) -> (
+        (usize, usize),
+        Option<(usize, usize)>,
+        Option<(usize, usize)>,
+    ) {
+        // the logical column, which is mirrored in right-to-left mode
+        let col = if self.style.right_to_left {
+            columns - 1 - x
         } else {
-            self.cursor.get_x()
-        }
-    }
-    /// returns the position of the first cursor on the hex view
-    fn first_cursor(&self) -> (usize, usize) {
-        let ret_x = self.style.nth_column_pos(self.col());
-        let ret_y = self.cursor.get_y() + 1;
-        (ret_x, ret_y)
-    }
-
-    /// returns the position of the first cursor on the ascii view
-    fn first_cursor_ascii(&self) -> Option<(usize, usize)> {
-        let pos = self.style.ascii_start(self.cursor.get_size_x())?;
-        let ret_x = pos + self.col();
-        let ret_y = self.cursor.get_y() + 1;
-        Some((ret_x, ret_y))
-    }
-    /// returns the position of the first cursor on the bars view
-    fn first_cursor_bars(&self) -> Option<(usize, usize)> {
-        let pos = self.style.bars_start(self.cursor.get_size_x())?;
-        let ret_x = pos + self.col();
-        let ret_y = self.cursor.get_y() + 1;
-        Some((ret_x, ret_y))
+            x
+        };
+        let y = self.cursor.get_y() + self.style.header_height();
+        (
+            (self.style.nth_column_pos(col), y),
+            self.style.ascii_start(columns).map(|pos| (pos + col, y)),
+            self.style.bars_start(columns).map(|pos| (pos + col, y)),
+        )
     }
     /// converts a position in the first half into one of the second half
     fn shift_to_second(&self, pos: (usize, usize)) -> (usize, usize) {
@@ -193,22 +718,59 @@ impl DoubleHexContext {
             (pos.0 + self.hor_half_width() + MIDDLE_PAD.width(), pos.1)
         }
     }
-    /// returns the position of the second cursor on the hex view
-    fn second_cursor(&self) -> (usize, usize) {
-        let first = self.first_cursor();
-        self.shift_to_second(first)
-    }
-
-    /// returns the position of the second cursor on the ascii view
-    fn second_cursor_ascii(&self) -> Option<(usize, usize)> {
-        let first = self.first_cursor_ascii()?;
-        Some(self.shift_to_second(first))
-    }
 
-    /// returns the position of the second cursor on the bars view
-    fn second_cursor_bars(&self) -> Option<(usize, usize)> {
-        let first = self.first_cursor_bars()?;
-        Some(self.shift_to_second(first))
+    /// Converts a position on the screen into the position of the cursor
+    /// pointing at the byte displayed there, and whether it is on the second half.
+    /// Returns None if there is no byte at that position.
+    pub fn position_at(&self, column: usize, row: usize) -> Option<(usize, usize, bool)> {
+        let rows = self.cursor.get_size_y();
+        let header = self.style.header_height();
+        let second_start = self.hor_half_width() + MIDDLE_PAD.width();
+        // position inside of one of the halves, with the title bar and ruler removed
+        let (x, y, second) = if self.style.stacked() {
+            let second_row = self.vert_half_height() + header;
+            if (header..header + rows).contains(&row) {
+                (column, row - header, false)
+            } else if (second_row..second_row + rows).contains(&row) {
+                (column, row - second_row, true)
+            } else {
+                return None;
+            }
+        } else if !(header..header + rows).contains(&row) {
+            return None;
+        } else if column < self.hor_half_width() {
+            (column, row - header, false)
+        } else if column >= second_start && !self.style.single {
+            (column - second_start, row - header, true)
+        } else {
+            return None;
+        };
+        let (columns, width) = if second {
+            (self.second_columns(), self.second_half_width())
+        } else {
+            (self.cursor.get_size_x(), self.hor_half_width())
+        };
+        if x >= width {
+            return None;
+        }
+        let in_column = |start: Option<usize>| {
+            let start = start?;
+            (start..start + columns).contains(&x).then(|| x - start)
+        };
+        // the logical column, which is mirrored in right-to-left mode
+        let col = (0..columns)
+            .find(|n| {
+                let pos = self.style.nth_column_pos(*n);
+                (pos..pos + self.style.cell_width()).contains(&x)
+            })
+            .or_else(|| in_column(self.style.ascii_start(columns)))
+            .or_else(|| in_column(self.style.bars_start(columns)))?;
+        let x = if self.style.right_to_left {
+            columns - 1 - col
+        } else {
+            col
+        };
+        Some((x, y, second))
     }
 
     /// Scrolls the hex view and rewrites the missing content, which should be more efficient
@@ -239,19 +801,25 @@ impl DoubleHexContext {
             // note that the lines where the previous bars were scrolled to
             // are overwritten in the first iteration of this loop to further
             // reduce flickering
-            let line = if scroll_amount > 0 {
+            let line_number = if scroll_amount > 0 {
                 line
             } else {
                 rows - line - 1
             };
-            if self.style.vertical {
-                content[line].print_vert(
+            let header = self.style.header_height();
+            let line = &content[line_number];
+            if self.style.stacked() {
+                line.print_vert(
+                    line,
                     backend,
-                    [line + 1, self.vert_half_height() + line + 1],
+                    [
+                        line_number + header,
+                        self.vert_half_height() + line_number + header,
+                    ],
                     self.style,
                 )
             } else {
-                content[line].print_hor(backend, line + 1, self.style)
+                line.print_hor(line, backend, line_number + header, self.style)
             }
         }
     }
@@ -262,136 +830,317 @@ impl DoubleHexContext {
     /// Note: The old cursor needs to be deleted first
     /// by calling this function with CursorActive::None
     /// with the old position.
+    /// The rest of the row of the cursor is described by `row`, see `cursor_row`.
+    /// The addresses of the cursors are shown by `print_bottom_line`.
     pub fn set_doublehex_cursor<B: Backend>(
         &self,
         backend: &mut B,
         active: CursorActive,
         at_cursor: (ByteData, ByteData),
-        cursor_addr: [Option<usize>; 2],
+        row: CursorRow,
     ) {
-        // the cursor is displayed with reverse video
-        let effect = |is_active, byte: ByteData| {
-            if is_active {
-                Effect {
-                    inverted: is_active,
-                    bold: byte.is_search_result,
-                }
-            } else {
-                byte_effect(byte)
+        // the cursor is displayed with reverse video, or without it on differences
+        // in monochrome mode and when coloring by byte class
+        let effect = |is_active: bool, byte: ByteData, other: ByteData| {
+            let effect = byte_effect(byte, other, self.style.inverted_differences());
+            Effect {
+                inverted: effect.inverted != is_active,
+                bold: effect.bold,
             }
         };
 
         // first cursor
-        let (first_x, first_y) = self.first_cursor();
-        let first_effect = effect(active.is_first(), at_cursor.0);
-        let first_color = self.style.mode.color(at_cursor.0, at_cursor.1, first_y);
-        let first_bg = background_color(at_cursor.0);
-        let first_text = self.style.mode.disp(byte(at_cursor.0), true);
+        let ((first_x, first_y), first_ascii, first_bars) =
+            self.cursor_positions(self.cursor.get_x(), self.cursor.get_size_x());
+        let first_effect = effect(active.is_first(), at_cursor.0, at_cursor.1);
+        let first_color = self.style.byte_color(at_cursor.0, at_cursor.1, first_y);
+        // the row tint is left out under the cursor so that it stays visible
+        let first_bg = self.style.row_background(
+            background_color(at_cursor.0),
+            row.is_different && !active.is_first(),
+        );
+        let first_text = self.style.disp_byte(at_cursor.0, true);
         // note again that the title bar is skipped
         backend.set_pos(first_x, first_y);
         // we cut of the last byte of the disp_hex so that the space is not reverse video'd
         backend.append_text(&first_text, first_color, first_bg, first_effect);
         // first ascii and bars column
-        for (fx, fy, disp_col) in [
-            (self.first_cursor_ascii(), disp_ascii as fn(_) -> _),
-            (self.first_cursor_bars(), disp_column_blocks),
+        for (fx, fy, col_text) in [
+            (first_ascii, self.cursor_char(at_cursor.0, row.text[0])),
+            (first_bars, disp_column_blocks(byte(at_cursor.0))),
         ]
-        .iter()
+        .into_iter()
         .filter_map(|(a, b)| a.map(|(a0, a1)| (a0, a1, b)))
         {
             backend.set_pos(fx, fy);
-            backend.append_text(
-                &disp_col(byte(at_cursor.0)),
-                first_color,
-                first_bg,
-                first_effect,
-            );
+            backend.append_text(&col_text, first_color, first_bg, first_effect);
+        }
+
+        // second cursor, unless only the first file is shown
+        if !self.style.single {
+            self.set_second_cursor(backend, active, at_cursor, row, effect);
+        }
+    }
+
+    /// The text of the cursor in the ascii column, which is the gap character on gaps
+    fn cursor_char(&self, at_cursor: ByteData, text: char) -> String {
+        if at_cursor.is_gap {
+            self.style.gap_text_char.to_string()
+        } else {
+            text.to_string()
         }
+    }
 
-        // second cursor
-        let (second_x, second_y) = self.second_cursor();
-        let second_effect = effect(active.is_second(), at_cursor.1);
-        let second_color = self.style.mode.color(at_cursor.1, at_cursor.0, second_y);
-        let second_bg = background_color(at_cursor.1);
-        let second_text = self.style.mode.disp(byte(at_cursor.1), true);
+    /// Paints the cursor of the second half, with the same arguments as `set_doublehex_cursor`
+    fn set_second_cursor<B: Backend>(
+        &self,
+        backend: &mut B,
+        active: CursorActive,
+        at_cursor: (ByteData, ByteData),
+        row: CursorRow,
+        effect: impl Fn(bool, ByteData, ByteData) -> Effect,
+    ) {
+        let (x, columns) = match (self.second_grid, row.second_column) {
+            (None, _) => (self.cursor.get_x(), self.cursor.get_size_x()),
+            (Some(grid), Some(x)) => (x, grid.columns),
+            // the cursor is in a column of the second half that is clipped
+            (Some(_), None) => return,
+        };
+        let (hex, ascii, bars) = self.cursor_positions(x, columns);
+        let (second_x, second_y) = self.shift_to_second(hex);
+        let (second, first) = self.style.second_half(at_cursor.1, at_cursor.0);
+        let second_effect = effect(active.is_second(), second, first);
+        let second_color = self.style.half_color(second, first, second_y, false);
+        let second_bg = self.style.row_background(
+            background_color(second),
+            row.is_different && !active.is_second(),
+        );
+        let second_text = self.style.disp_byte(second, true);
         backend.set_pos(second_x, second_y);
         backend.append_text(&second_text, second_color, second_bg, second_effect);
         // second ascii and bars column
-        for (sx, sy, disp_col) in [
-            (self.second_cursor_ascii(), disp_ascii as fn(_) -> _),
-            (self.second_cursor_bars(), disp_column_blocks),
+        for ((sx, sy), col_text) in [
+            (ascii, self.cursor_char(second, row.text[1])),
+            (bars, disp_column_blocks(byte(second))),
         ]
-        .iter()
-        .filter_map(|(a, b)| a.map(|(a0, a1)| (a0, a1, b)))
+        .into_iter()
+        .filter_map(|(a, b)| a.map(|pos| (self.shift_to_second(pos), b)))
         {
             backend.set_pos(sx, sy);
-            backend.append_text(
-                &disp_col(byte(at_cursor.1)),
-                second_color,
-                second_bg,
-                second_effect,
-            );
+            backend.append_text(&col_text, second_color, second_bg, second_effect);
         }
+    }
 
-        // status bar address
-        let addr_print = disp_bottom_addr(cursor_addr, self.style.addr_width);
-        let addr_print = &addr_print[..addr_print.len().min(self.full_width())];
-        if self.style.right_to_left {
-            backend.set_pos(0, self.full_height() - 1);
+    /// Describes the column setting unless the columns fit the screen,
+    /// with how many of them are shown if the rows are cut off.
+    /// Both halves are described like "12|16" if the second one has its own columns.
+    fn column_rule(&self) -> Option<String> {
+        let describe = |setting| match setting {
+            ColumnSetting::Fit => None,
+            ColumnSetting::Fixed(n) => Some(n.to_string()),
+            ColumnSetting::Multiple(n) => Some(format!("{n}x")),
+        };
+        let first = describe(self.style.column_count);
+        let shown = self.cursor.get_size_x();
+        let clipped = shown < self.cursor.bytes_per_row();
+        let (rule, shown, clipped) = match (self.second_grid, self.style.second_column_count) {
+            (Some(grid), Some(second)) => {
+                let [first, second] = [first, describe(second)]
+                    .map(|rule| rule.unwrap_or_else(|| String::from("fit")));
+                (
+                    format!("{first}|{second}"),
+                    format!("{shown}|{}", grid.columns),
+                    clipped || grid.columns < grid.bytes_per_row,
+                )
+            }
+            _ => (first?, shown.to_string(), clipped),
+        };
+        if clipped {
+            Some(format!("Columns: {rule} (clipped to {shown})"))
         } else {
-            backend.set_pos(
-                self.full_width().saturating_sub(addr_print.len()),
-                self.full_height() - 1,
-            );
+            Some(format!("Columns: {rule}"))
         }
-        backend.append_text(
-            addr_print,
-            Color::HexSame,
-            BackgroundColor::Blank,
-            Effect::inverted(),
-        );
     }
 
-    /// prints the line at the top containing the filenames and status
+    /// The addresses of the cursors for the bottom line, with the distance between them
+    /// if both files are shown and the index in the alignment if it is enabled
+    fn bottom_address(&self, addresses: [Option<usize>; 2], index: Option<isize>) -> String {
+        let [first, second] = self.shown_addresses(addresses);
+        let index = match index.filter(|_| self.style.show_index) {
+            Some(index) if index < 0 => format!(" @-{:x}", -index),
+            Some(index) => format!(" @{index:x}"),
+            None => String::new(),
+        };
+        if self.style.single {
+            format!("{index} {} ", self.style.format_addr(first, false))
+        } else {
+            format!("{index}{}", self.style.bottom_addr([first, second]))
+        }
+    }
+    /// How far the cursors are through the files for the bottom line, in percent of `lens`
+    fn bottom_percents(&self, addresses: [Option<usize>; 2], lens: [usize; 2]) -> String {
+        let [first, second] =
+            [0, 1].map(|i| percent(addresses[i].map(|addr| addr as isize), lens[i]));
+        if self.style.single {
+            format!(" {first}")
+        } else {
+            format!(" {first}|{second}")
+        }
+    }
+
+    /// The size and the shown addresses of the selected bytes of the files for the
+    /// bottom line, which are only given once if they are the same in both files
+    fn selected_text(&self, selected: [Option<Range<usize>>; 2]) -> Option<String> {
+        let [first, second] = [0, 1].map(|i| {
+            selected[i].clone().map(|range| {
+                let range = (range.start + self.bases[i])..(range.end + self.bases[i]);
+                let addresses = if range.is_empty() {
+                    String::from("none")
+                } else {
+                    format!("{:#x}-{:#x}", range.start, range.end - 1)
+                };
+                (range.len(), addresses)
+            })
+        });
+        let (len, addresses) = match (first, second) {
+            (None, None) => return None,
+            (Some(first), Some(second)) if first != second => (
+                format!("{}|{}", first.0, second.0),
+                format!("{}|{}", first.1, second.1),
+            ),
+            (Some((len, addresses)), _) | (None, Some((len, addresses))) => {
+                (len.to_string(), addresses)
+            }
+        };
+        let bytes = if len == "1" { "byte" } else { "bytes" };
+        Some(format!("{len} {bytes} selected ({addresses})"))
+    }
+
+    /// The labels of the innermost annotations at the cursors for the bottom line,
+    /// which are only given once if they are the same in both files
+    fn annotation_text(&self, addresses: [Option<usize>; 2]) -> Option<String> {
+        let [first, second] = [0, 1].map(|i| {
+            let annotation = self.annotations[i].at(addresses[i]? + self.bases[i])?;
+            Some(annotation.label.as_str())
+        });
+        match (first, second) {
+            (None, None) => None,
+            (Some(first), _) if self.style.single => Some(first.to_string()),
+            (first, second) if first == second => first.map(String::from),
+            (first, second) => Some(format!(
+                "{}|{}",
+                first.unwrap_or("-"),
+                second.unwrap_or("-")
+            )),
+        }
+    }
+
+    /// The text of the column ruler of one half, which labels the hex columns
+    /// with their offsets and the ascii and bars columns with the last hex digit
+    fn ruler(&self, columns: usize) -> String {
+        let mut ruler = vec![' '; self.style.half_width(columns)];
+        let digits = |n: usize| format!("{n:x}").len();
+        let label_width = digits(columns.saturating_sub(1)).max(2);
+        // label only every step-th column so that there is a space between labels,
+        // and at least the start of every group
+        let mut step = self.style.group_size();
+        while step * self.style.cell_width() < label_width + 1 {
+            step *= 2;
+        }
+        let physical = |col: usize| {
+            if self.style.right_to_left {
+                columns - 1 - col
+            } else {
+                col
+            }
+        };
+        for col in (0..columns).step_by(step) {
+            let pos = self.style.nth_column_pos(physical(col));
+            let label = format!("{col:0label_width$x}");
+            for (i, c) in label.chars().enumerate() {
+                if let Some(target) = ruler.get_mut(pos + i) {
+                    *target = c;
+                }
+            }
+        }
+        for start in [
+            self.style.ascii_start(columns),
+            self.style.bars_start(columns),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            for col in 0..columns {
+                let digit = std::char::from_digit((col % 16) as u32, 16).unwrap_or(' ');
+                if let Some(target) = ruler.get_mut(start + physical(col)) {
+                    *target = digit;
+                }
+            }
+        }
+        ruler.into_iter().collect()
+    }
+
+    /// prints the column rulers below the title lines, if they are enabled
+    fn print_ruler<B: Backend>(&self, printer: &mut B) {
+        if !self.style.ruler {
+            return;
+        }
+        let print = |printer: &mut B, text: &str| {
+            printer.append_text(
+                text,
+                Color::Unimportant,
+                BackgroundColor::Blank,
+                Effect::none(),
+            )
+        };
+        printer.set_line(1);
+        print(printer, &self.ruler(self.cursor.get_size_x()));
+        if self.style.single {
+            return;
+        }
+        if self.style.vertical {
+            printer.set_line(self.vert_half_height() + 1);
+        } else {
+            print(printer, MIDDLE_PAD);
+        }
+        print(printer, &self.ruler(self.second_columns()));
+    }
+
+    /// prints the line at the top containing the filenames, sizes and status,
+    /// together with the column ruler below it.
+    /// The title is followed by the busy indicator if anything in `busy` is running, and
+    /// the `detail` follows them if the halves are wide enough for it and a name.
     pub fn print_title_line<B: Backend>(
         &self,
         printer: &mut B,
         title: &str,
-        first: &str,
-        second: &str,
+        busy: &[&str],
+        detail: Option<&str>,
+        first: FileTitle,
+        second: FileTitle,
     ) {
-        let title = &title[..title.len().min(self.hor_half_width() - 2)];
-        let namewidth = self.hor_half_width().saturating_sub(title.len() + 2);
-        // title is all ascii so just count bytes
-        // function for truncating the string on the left when it is too long
-        // also inserts an < to indicate that it was truncated
-        let shorten = |s: &str| -> String {
-            if namewidth < 2 {
-                String::new()
-            } else if s.width() > namewidth {
-                s.chars()
-                    .rev()
-                    .take(namewidth - 2)
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .chain(std::iter::once('<'))
-                    .rev()
-                    .collect()
-            } else {
-                s.to_string()
-            }
-        };
-        let short_first = shorten(first);
-        let short_second = shorten(second);
-
-        let format_title = |text| {
+        let title = format!("{title}{}", self.busy_indicator(busy));
+        // the halves only differ in width if the second one has its own columns
+        let format_title = |file: FileTitle, width: usize| {
+            let title = match detail {
+                Some(detail) if title.len() + detail.len() + 2 + MIN_NAME_WIDTH <= width => {
+                    format!("{title}{detail}")
+                }
+                _ => title.to_string(),
+            };
+            let title = &title[..title.len().min(width - 2)];
+            // title is all ascii so just count bytes
+            let namewidth = width.saturating_sub(title.len() + 2);
+            let text = file.fit(namewidth);
+            let pad = " ".repeat(namewidth.saturating_sub(text.width()));
             if self.style.right_to_left {
-                format!("{text:<namewidth$} {title} ")
+                format!("{text}{pad} {title} ")
             } else {
-                format!("{title} {text:>namewidth$} ")
+                format!("{title} {pad}{text} ")
             }
         };
-        let first_title = format_title(short_first);
+        self.print_ruler(printer);
+        let first_title = format_title(first, self.hor_half_width());
         printer.set_line(0);
         printer.append_text(
             &first_title,
@@ -399,6 +1148,9 @@ impl DoubleHexContext {
             BackgroundColor::Blank,
             Effect::inverted(),
         );
+        if self.style.single {
+            return;
+        }
         if self.style.vertical {
             printer.set_line(self.vert_half_height())
         } else {
@@ -409,7 +1161,7 @@ impl DoubleHexContext {
                 Effect::inverted(),
             );
         }
-        let second_title = format_title(short_second);
+        let second_title = format_title(second, self.second_half_width());
         printer.append_text(
             &second_title,
             Color::HexSame,
@@ -418,27 +1170,116 @@ impl DoubleHexContext {
         );
     }
 
-    /// Prints the bottom text containing key information
-    pub fn print_bottom_line<B: Backend>(&self, printer: &mut B, addresses: [Option<usize>; 2]) {
-        const BOTTOM_TEXT: &str =
-            "F1/1: Help F2: Unalign F3: Align F4: Settings F6: Goto F7: Search";
-        let print_addr = disp_bottom_addr(addresses, self.style.addr_width);
+    /// Prints the bottom text containing key information.
+    /// `lens` are the lengths of the files and `index` is the index of the cursor
+    /// in the alignment along with all its indexes, which are also shown in percent.
+    pub fn print_bottom_line<B: Backend>(
+        &self,
+        printer: &mut B,
+        addresses: [Option<usize>; 2],
+        index: Option<(isize, Range<isize>)>,
+        lens: [usize; 2],
+        selected: [Option<Range<usize>>; 2],
+        status: Option<&str>,
+    ) {
+        const BOTTOM_TEXT: &str = "F1: Help F2: Unalign F3: Align F4: Settings F6: Goto F7: Search";
+        const SINGLE_BOTTOM_TEXT: &str = "F1: Help F4: Settings F6: Goto F7: Search";
+        // the count prefix, notices and status (like search progress)
+        // are more important than the key hints
+        let count = self.pending_count.map(|count| count.to_string());
+        let column_rule = self.column_rule();
+        let selected = self.selected_text(selected);
+        let annotation = self.annotation_text(addresses);
+        let lock = self.offset_lock.then(|| match addresses {
+            [Some(first), Some(second)] if second < first => {
+                format!("Offset locked: -{:#x}", first - second)
+            }
+            [Some(first), Some(second)] => format!("Offset locked: +{:#x}", second - first),
+            _ => String::from("Offset locked"),
+        });
+        let mut fields = [
+            count.as_deref(),
+            self.notice_text(),
+            self.editor.describe(),
+            status,
+            selected.as_deref(),
+            annotation.as_deref(),
+            column_rule.as_deref(),
+            lock.as_deref(),
+            self.style.delta.describe(),
+            self.three_way
+                .as_ref()
+                .and_then(|three_way| three_way.filter)
+                .map(Change::describe),
+            self.following().then_some("Following"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        let important_width = fields.join(" | ").len();
+        let print_addr = self.bottom_address(addresses, index.as_ref().map(|(index, _)| *index));
+        let index_percent = index
+            .map(|(index, bounds)| {
+                let len = (bounds.end - bounds.start).max(0) as usize;
+                format!(" {}", percent(Some(index - bounds.start), len))
+            })
+            .unwrap_or_default();
+        let file_percents = self.bottom_percents(addresses, lens);
+        // the percentages are left out (the one of the alignment first)
+        // if they do not fit next to the more important fields
+        let candidates = [
+            format!("{index_percent}{print_addr}{file_percents}"),
+            format!("{print_addr}{file_percents}"),
+            print_addr,
+        ];
+        let print_addr = candidates
+            .iter()
+            .find(|text| text.len() + important_width <= self.full_width())
+            .unwrap_or(&candidates[2]);
         let print_addr = &print_addr[..print_addr.len().min(self.full_width())];
         let info_width = self.full_width().saturating_sub(print_addr.len());
-        let bottom_text = &BOTTOM_TEXT[..BOTTOM_TEXT.len().min(info_width)];
+        fields.push(if self.style.single {
+            SINGLE_BOTTOM_TEXT
+        } else {
+            BOTTOM_TEXT
+        });
+        let bottom_text = fields.join(" | ");
+        let bottom_text = &bottom_text[..bottom_text.len().min(info_width)];
         let info_text = if self.style.right_to_left {
             format!("{print_addr}{bottom_text:>info_width$}")
         } else {
             format!("{bottom_text:<info_width$}{print_addr}")
         };
+        // warnings and errors are shown in the colors of the differences
+        let parts = self.notice.as_ref().and_then(|notice| {
+            let color = match notice.severity {
+                Severity::Info => return None,
+                Severity::Warning => Color::HexOneside,
+                Severity::Error => Color::HexDiff,
+            };
+            // the notice comes right after the count prefix
+            let bottom_start = match self.style.right_to_left {
+                true => info_text.len() - bottom_text.len(),
+                false => 0,
+            };
+            let start = bottom_start + count.as_ref().map_or(0, |count| count.len() + " | ".len());
+            let end = (start + notice.text.len()).min(bottom_start + bottom_text.len());
+            Some(vec![
+                (info_text.get(..start)?, Color::HexSame),
+                (info_text.get(start..end)?, color),
+                (info_text.get(end..)?, Color::HexSame),
+            ])
+        });
+        let parts = parts.unwrap_or_else(|| vec![(info_text.as_str(), Color::HexSame)]);
         let line = self.full_height() - 1;
-        printer.set_line(line);
-        printer.append_text(
-            &info_text,
-            Color::HexSame,
-            BackgroundColor::Blank,
-            Effect::inverted(),
-        );
+        if self.prompt.is_open() {
+            self.print_prompt(printer, line);
+        } else {
+            printer.set_line(line);
+            for (text, color) in parts {
+                printer.append_text(text, color, BackgroundColor::Blank, Effect::inverted());
+            }
+        }
         for line in self.full_height()..printer.size().1 {
             printer.set_line(line);
             printer.append_text(
@@ -450,35 +1291,235 @@ impl DoubleHexContext {
         }
     }
 
-    /// decrease the amount of columns by one
-    pub fn dec_columns(&mut self) {
-        let default = self.cursor.bytes_per_row() as u16;
-        self.style.column_count = ColumnSetting::Fixed(
-            self.style
-                .column_count
-                .fixed()
-                .unwrap_or(default)
-                .saturating_sub(1)
-                .max(1),
-        );
+    /// Prints the open command line in place of the bottom line, with the character
+    /// at its cursor inverted
+    fn print_prompt<B: Backend>(&self, printer: &mut B, line: usize) {
+        let width = self.full_width();
+        let (before, at, after) = self.prompt.parts(width);
+        let rest = width.saturating_sub(before.chars().count() + 2);
+        let after = after.chars().take(rest).collect::<String>();
+        let pad = rest - after.chars().count();
+        printer.set_line(line);
+        let parts = [
+            (format!(":{before}"), Effect::none()),
+            (at.to_string(), Effect::inverted()),
+            (format!("{after}{:pad$}", ""), Effect::none()),
+        ];
+        for (text, effect) in parts {
+            printer.append_text(&text, Color::HexSame, BackgroundColor::Blank, effect);
+        }
     }
-    /// increase the amount of columns by one
-    pub fn inc_columns(&mut self) {
-        let default = self.cursor.bytes_per_row() as u16;
-        self.style.column_count = ColumnSetting::Fixed(
-            self.style
-                .column_count
-                .fixed()
-                .unwrap_or(default)
-                .saturating_add(1),
-        );
+
+    /// The characters of both files in the ascii column at the cursor, which can depend on
+    /// the other bytes of its row in UTF-8, and whether the row has differences.
+    /// `row_byte` returns the bytes of a column of the row and whether they count as different.
+    pub fn cursor_row(
+        &self,
+        row_byte: impl Fn(usize) -> (Option<u8>, Option<u8>, bool),
+    ) -> CursorRow {
+        let mut is_different = false;
+        let (first, second): (Vec<_>, Vec<_>) = (0..self.cursor.get_size_x())
+            .map(|col| {
+                let (first, second, differ) = row_byte(col);
+                is_different |= differ;
+                (first, second)
+            })
+            .unzip();
+        let x = self.cursor.get_x();
+        let text = [first, second].map(|bytes| {
+            let text = self.style.encoding.decode(&bytes);
+            text.get(x).copied().unwrap_or(' ')
+        });
+        CursorRow {
+            text,
+            is_different,
+            second_column: None,
+        }
+    }
+    /// The entropy levels of `len` bytes in view of both files when coloring by entropy,
+    /// starting at the index `start` of the view, and nothing otherwise.
+    /// `bytes` returns the bytes of both files at a range of indices, which is the view
+    /// extended by half a window on each side, so that the bytes at its edges get whole windows.
+    pub fn entropy_levels(
+        &self,
+        start: isize,
+        len: usize,
+        bytes: impl Fn(Range<isize>) -> Vec<(Option<u8>, Option<u8>)>,
+    ) -> [Vec<u8>; 2] {
+        if !self.style.entropy {
+            return [Vec::new(), Vec::new()];
+        }
+        let window = (self.style.entropy_window as usize).max(2);
+        let half = window / 2;
+        let (first, second): (Vec<_>, Vec<_>) =
+            bytes(start - half as isize..start + (len + half) as isize)
+                .into_iter()
+                .unzip();
+        [first, second].map(|side| {
+            sliding_entropy(&side, window)
+                .into_iter()
+                .skip(half)
+                .take(len)
+                .map(|e| ((e * ENTROPY_LEVELS as f32) as u8).min(ENTROPY_LEVELS - 1))
+                .collect()
+        })
+    }
+    /// The number of rows that a full page moves, keeping `page_overlap` rows
+    /// of the previous page on the screen, but moving at least one row
+    pub fn page_rows(&self) -> isize {
+        let rows = self.cursor.get_size_y();
+        rows.saturating_sub(self.style.page_overlap as usize).max(1) as isize
+    }
+    /// The number of rows that a half page moves
+    pub fn half_page_rows(&self) -> isize {
+        (self.cursor.get_size_y() / 2).max(1) as isize
+    }
+    /// The number of bytes that a word moves, which is `word_stride` if it is set
+    /// and otherwise the byte group, or 8 bytes if they are not grouped
+    pub fn word_stride(&self) -> usize {
+        match (self.style.word_stride, self.style.group_size()) {
+            (0, 1) => 8,
+            (0, group) => group,
+            (stride, _) => stride as usize,
+        }
+    }
+    /// The move of the cursor to the start of the next word in the direction of
+    /// `right` on the screen, which goes to the start of the row in the other direction
+    /// instead of leaving it unless `word_wrap` is set
+    pub fn word_move(&self, right: bool) -> Move {
+        let stride = self.word_stride();
+        let x = self.cursor.get_x();
+        let last = self.cursor.get_size_x().min(self.cursor.bytes_per_row()) - 1;
+        // the x moves are mirrored back in right-to-left mode
+        let forward = right != self.style.right_to_left;
+        let diff = if forward {
+            let diff = stride - x % stride;
+            if self.style.word_wrap {
+                diff
+            } else {
+                diff.min(last - x)
+            }
+        } else {
+            let diff = match x % stride {
+                0 => stride,
+                into_word => into_word,
+            };
+            if self.style.word_wrap {
+                diff
+            } else {
+                diff.min(x)
+            }
+        };
+        if right {
+            Move::CursorX(diff as isize)
+        } else {
+            Move::CursorX(-(diff as isize))
+        }
+    }
+    /// switches to the next grouping of the hex columns, going from
+    /// no grouping over groups of 2, 4 and 8 columns back to no grouping
+    pub fn cycle_group(&mut self) {
+        self.style.group = match self.style.group {
+            1 => 2,
+            2 => 4,
+            4 => 8,
+            _ => 1,
+        };
+    }
+    /// changes the column setting of the halves of `active` with `change`, which also
+    /// gets the current number of bytes per row of the half.
+    /// Only the second half can have its own columns, so changing the first one
+    /// alone keeps the columns of the second one.
+    fn change_columns(
+        &mut self,
+        active: CursorActive,
+        change: impl Fn(ColumnSetting, u16) -> ColumnSetting,
+    ) {
+        let first_default = self.cursor.bytes_per_row() as u16;
+        let second_default = self
+            .second_grid
+            .map_or(first_default, |grid| grid.bytes_per_row as u16);
+        let first = self.style.column_count;
+        let second = self.style.second_column_count.unwrap_or(first);
+        let active = if self.style.single {
+            CursorActive::Both
+        } else {
+            active
+        };
+        match active {
+            CursorActive::First => {
+                self.style.column_count = change(first, first_default);
+                self.style.second_column_count = Some(second);
+            }
+            CursorActive::Second => {
+                self.style.second_column_count = Some(change(second, second_default));
+            }
+            CursorActive::Both | CursorActive::None => {
+                self.style.column_count = change(first, first_default);
+                self.style.second_column_count = self
+                    .style
+                    .second_column_count
+                    .map(|second| change(second, second_default));
+            }
+        }
+        if self.style.second_column_count == Some(self.style.column_count) {
+            self.style.second_column_count = None;
+        }
+    }
+    /// decrease the amount of columns of the halves of `active` by one
+    pub fn dec_columns(&mut self, active: CursorActive) {
+        self.change_columns(active, |setting, default| {
+            ColumnSetting::Fixed(setting.fixed().unwrap_or(default).saturating_sub(1).max(1))
+        });
+    }
+    /// increase the amount of columns of the halves of `active` by one
+    pub fn inc_columns(&mut self, active: CursorActive) {
+        self.change_columns(active, |setting, default| {
+            ColumnSetting::Fixed(setting.fixed().unwrap_or(default).saturating_add(1))
+        });
+    }
+    /// lets the columns of both halves fit the screen again
+    pub fn reset_columns(&mut self) {
+        self.style.column_count = ColumnSetting::Fit;
+        self.style.second_column_count = None;
     }
-    /// looks at where the autocorrelation peaks are and sets the column count
-    pub fn auto_columns(&mut self, bytes: [&[u8]; 2]) {
+    /// shows the addresses relative to the given addresses of the files,
+    /// or the actual addresses again if they were relative
+    pub fn toggle_relative_addresses(&mut self, addresses: [Option<usize>; 2]) {
+        self.style.relative_to = match self.style.relative_to {
+            Some(_) => {
+                self.set_notice(Severity::Info, "Absolute addresses");
+                None
+            }
+            None => {
+                self.set_notice(Severity::Info, "Addresses relative to the cursor");
+                Some(addresses.map(|addr| addr.unwrap_or(0)))
+            }
+        };
+    }
+    /// makes the column count a multiple of `n`, or of the current
+    /// column count if `n` is 1, so the rows line up with records of that size
+    pub fn multiple_columns(&mut self, n: usize) {
+        let n = if n > 1 {
+            n
+        } else {
+            self.cursor.bytes_per_row()
+        };
+        self.style.column_count = ColumnSetting::Multiple(n.min(u16::MAX as usize) as u16);
+    }
+    /// looks at where the autocorrelation peaks are and sets the column count,
+    /// which stays fixed if it was fixed before and is rounded to the nearest
+    /// multiple if the columns are kept at a multiple.
+    /// Large inputs are only sampled at a few places spread over them.
+    pub fn auto_columns(&mut self, bytes: [&[u8]; 2], active: CursorActive) {
         const MIN_AUTOCOR_WIDTH: usize = 6;
         const MAX_AUTOCOR_WIDTH: usize = 65535;
         const AUTOCOR_THRESHOLD: f64 = 0.2;
-        let [first, second] = bytes.map(autocorrelation);
+        const AUTOCOR_SAMPLE_BYTES: usize = 256 << 10;
+        const AUTOCOR_SAMPLE_WINDOWS: usize = 8;
+        let [first, second] = bytes.map(|bytes| {
+            sampled_autocorrelation(bytes, AUTOCOR_SAMPLE_BYTES, AUTOCOR_SAMPLE_WINDOWS)
+        });
         let max_len = first.len().max(second.len());
         let ratio = if !second.is_empty() {
             first.len() as f64 / second.len() as f64
@@ -508,8 +1549,266 @@ impl DoubleHexContext {
         let max_index = if let Some(index) = max_index {
             index
         } else {
+            self.set_notice(Severity::Warning, "No repetitions found");
             return;
         };
-        self.style.column_count = ColumnSetting::Multiple(max_index as u16);
+        self.set_notice(Severity::Info, format!("Repeats every {max_index} bytes"));
+        self.change_columns(active, |setting, _| match setting {
+            ColumnSetting::Fixed(_) => ColumnSetting::Fixed(max_index as u16),
+            ColumnSetting::Multiple(n) if n > 1 => {
+                let n = n as usize;
+                let snapped = ((max_index + n / 2) / n).max(1) * n;
+                ColumnSetting::Multiple(snapped.min(u16::MAX as usize) as u16)
+            }
+            _ => ColumnSetting::Multiple(max_index as u16),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::capture::Capture, notice::NOTICE_DURATION};
+    #[test]
+    fn file_titles() {
+        assert_eq!(elide_middle("abcdefgh", 8), "abcdefgh");
+        assert_eq!(elide_middle("abcdefgh", 6), "ab…fgh");
+        assert_eq!(elide_middle("ab中文gh", 6), "ab…gh");
+        assert_eq!(elide_middle("abcdefgh", 0), "");
+        let title = |name, modified| FileTitle {
+            name,
+            len: 1536,
+            modified,
+            edited: false,
+        };
+        let details = "[1.5 KiB, 1536 bytes, modified]";
+        assert_eq!(
+            title("first.bin", true).fit(60),
+            format!("first.bin {details}")
+        );
+        let name = "some/long/directory/name/first.bin";
+        assert_eq!(
+            title(name, true).fit(details.len() + 16),
+            format!("some/lo…rst.bin {details}")
+        );
+        // without space for the details, a modified file is marked
+        assert_eq!(title(name, true).fit(20), "some/long…first.bin*");
+        assert_eq!(title(name, false).fit(20), "some/long…/first.bin");
+        assert_eq!(title("x", false).fit(9), "x");
+        let edited = FileTitle {
+            edited: true,
+            ..title(name, true)
+        };
+        assert_eq!(edited.fit(20), "some/lon…first.bin*+");
+        assert!(edited.fit(60).ends_with("bytes, modified, edited]"));
+    }
+    #[test]
+    fn notices() {
+        let mut dh = DoubleHexContext::new((16, 3));
+        dh.pending_count = Some(4);
+        dh.set_notice(Severity::Error, "No such address");
+        let mut capture = Capture::new(dh.full_width(), dh.full_height());
+        let addresses = [Some(0), Some(0)];
+        dh.print_bottom_line(&mut capture, addresses, None, [16, 16], [None, None], None);
+        let annotated = capture.annotated();
+        let lines = annotated.lines().collect::<Vec<_>>();
+        // the last row is the bottom line, followed by its colors and effects
+        assert!(lines[lines.len() - 3].starts_with("   |4 | No such address | "));
+        assert!(lines[lines.len() - 2].starts_with(&format!("fg |ssss{}s", "d".repeat(15))));
+        let now = Instant::now();
+        assert!(!dh.expire_notice(now));
+        assert!(dh.expire_notice(now + NOTICE_DURATION));
+        assert_eq!(dh.notice_text(), None);
+    }
+    #[test]
+    fn position_at() {
+        let mut dh = DoubleHexContext::new((4, 3));
+        dh.style.addr_width = 2;
+        // front pad and address come before the hex columns
+        assert_eq!(dh.position_at(4, 1), Some((0, 0, false)));
+        assert_eq!(dh.position_at(11, 2), Some((2, 1, false)));
+        assert_eq!(dh.position_at(3, 1), None);
+        assert_eq!(dh.position_at(4, 0), None);
+        assert_eq!(dh.position_at(4, 4), None);
+        let second = dh.hor_half_width() + MIDDLE_PAD.width();
+        assert_eq!(dh.position_at(second + 4, 3), Some((0, 2, true)));
+        assert_eq!(dh.position_at(second - 1, 3), None);
+        dh.style.ascii_col = true;
+        let ascii = dh.style.ascii_start(4).unwrap();
+        assert_eq!(dh.position_at(ascii + 1, 1), Some((1, 0, false)));
+        dh.style.right_to_left = true;
+        assert_eq!(dh.position_at(1, 1), Some((3, 0, false)));
+        dh.style.right_to_left = false;
+        dh.style.vertical = true;
+        let second_row = dh.vert_half_height() + 1;
+        assert_eq!(dh.position_at(4, second_row), Some((0, 0, true)));
+        // the ruler moves the bytes one row down
+        dh.style.ruler = true;
+        assert_eq!(dh.position_at(4, 1), None);
+        assert_eq!(dh.position_at(4, 2), Some((0, 0, false)));
+        let second_row = dh.vert_half_height() + 2;
+        assert_eq!(dh.position_at(4, second_row), Some((0, 0, true)));
+    }
+    #[test]
+    fn percents() {
+        assert_eq!(percent(Some(0), 200), "  0%");
+        assert_eq!(percent(Some(99), 200), " 50%");
+        assert_eq!(percent(Some(199), 200), "100%");
+        assert_eq!(percent(Some(-5), 200), "  0%");
+        assert_eq!(percent(None, 200), "    ");
+        assert_eq!(percent(Some(0), 0), "    ");
+        let dh = DoubleHexContext::new((4, 3));
+        assert_eq!(dh.bottom_percents([Some(9), None], [10, 10]), " 100%|    ");
+    }
+    #[test]
+    fn selected_text() {
+        let mut dh = DoubleHexContext::new((4, 3));
+        assert_eq!(dh.selected_text([None, None]), None);
+        assert_eq!(
+            dh.selected_text([Some(2..11), Some(2..11)]).as_deref(),
+            Some("9 bytes selected (0x2-0xa)")
+        );
+        assert_eq!(
+            dh.selected_text([None, Some(4..5)]).as_deref(),
+            Some("1 byte selected (0x4-0x4)")
+        );
+        // gaps in the aligned view make the selected bytes differ
+        dh.bases = [0x100, 0];
+        assert_eq!(
+            dh.selected_text([Some(2..11), Some(2..2)]).as_deref(),
+            Some("9|0 bytes selected (0x102-0x10a|none)")
+        );
+    }
+    #[test]
+    fn annotations() {
+        let mut dh = DoubleHexContext::new((4, 3));
+        assert_eq!(dh.annotation_text([Some(0), Some(0)]), None);
+        dh.annotations = [
+            "0,3f,header\n40,1ff,key blob,red".parse().unwrap(),
+            "0,3f,header".parse().unwrap(),
+        ];
+        assert_eq!(
+            dh.annotation_text([Some(0x10), Some(0x10)]).as_deref(),
+            Some("header")
+        );
+        // annotations are in the addresses of the whole files
+        dh.bases = [0, 0x30];
+        assert_eq!(
+            dh.annotation_text([Some(0x10), Some(0x10)]).as_deref(),
+            Some("header|-")
+        );
+        let default = ThemeColor::Basic(2);
+        assert_eq!(
+            dh.annotation_tints([Some(0x40), Some(0x0f)], default),
+            [Some(ThemeColor::Basic(9)), Some(default)]
+        );
+        assert_eq!(
+            dh.annotation_tints([None, Some(0x10)], default),
+            [None, None]
+        );
+        dh.style.single = true;
+        assert_eq!(
+            dh.annotation_text([Some(0x40), Some(0x40)]).as_deref(),
+            Some("key blob")
+        );
+    }
+    #[test]
+    fn diff_map() {
+        let mut dh = DoubleHexContext::new((4, 3));
+        assert_eq!(dh.map_cells(), 0);
+        dh.style.diff_map = true;
+        let column = dh.full_width() + 1;
+        // the map goes from the first row of bytes to the bottom line
+        assert_eq!(dh.map_cells(), 3);
+        assert_eq!(dh.map_cell_at(column, 1), Some(0));
+        assert_eq!(dh.map_cell_at(column, 3), Some(2));
+        assert_eq!(dh.map_cell_at(column, 4), None);
+        assert_eq!(dh.map_cell_at(column - 1, 1), None);
+        dh.style.vertical = true;
+        assert_eq!(dh.map_cells(), 2 * dh.vert_half_height() - 1);
+    }
+    #[test]
+    fn ruler() {
+        let mut dh = DoubleHexContext::new((4, 3));
+        dh.style.addr_width = 2;
+        assert_eq!(dh.ruler(4).trim_end(), "    00 01 02 03");
+        dh.style.right_to_left = true;
+        assert_eq!(dh.ruler(4).trim_end(), " 03 02 01 00");
+        dh.style.right_to_left = false;
+        dh.style.group = 2;
+        assert_eq!(dh.ruler(4).trim_end(), "    00     02");
+        // the space between the groups belongs to no column
+        assert_eq!(dh.position_at(10, 1), None);
+        assert_eq!(dh.position_at(11, 1), Some((2, 0, false)));
+    }
+    #[test]
+    fn word_move() {
+        let mut dh = DoubleHexContext::new((16, 8));
+        assert_eq!(dh.word_stride(), 8);
+        dh.style.group = 4;
+        dh.cursor.mov(Move::CursorX(5), 0..100);
+        assert_eq!(dh.word_move(true), Move::CursorX(3));
+        assert_eq!(dh.word_move(false), Move::CursorX(-1));
+        dh.cursor.mov(Move::CursorX(-1), 0..100);
+        assert_eq!(dh.word_move(false), Move::CursorX(-4));
+        // the mirrored moves of right-to-left mode go to the same words
+        dh.style.right_to_left = true;
+        assert_eq!(dh.word_move(false), Move::CursorX(-4));
+        assert_eq!(dh.word_move(true), Move::CursorX(4));
+        dh.style.right_to_left = false;
+        // the row ends stop the moves unless they wrap
+        dh.style.word_stride = 6;
+        dh.cursor.mov(Move::CursorX(10), 0..100);
+        assert_eq!(dh.word_move(true), Move::CursorX(1));
+        dh.style.word_wrap = true;
+        assert_eq!(dh.word_move(true), Move::CursorX(4));
+    }
+    #[test]
+    fn column_rule() {
+        let mut dh = DoubleHexContext::new((4, 8));
+        assert_eq!(dh.column_rule(), None);
+        dh.multiple_columns(24);
+        assert_eq!(dh.column_rule().as_deref(), Some("Columns: 24x"));
+        dh.multiple_columns(1);
+        assert!(matches!(dh.style.column_count, ColumnSetting::Multiple(4)));
+        dh.style.column_count = ColumnSetting::Fixed(300);
+        dh.cursor.resize((4, 8), 300);
+        assert_eq!(
+            dh.column_rule().as_deref(),
+            Some("Columns: 300 (clipped to 4)")
+        );
+        dh.inc_columns(CursorActive::Both);
+        assert!(matches!(dh.style.column_count, ColumnSetting::Fixed(301)));
+        let (dims, bytes_per_row) = dh.style.get_doublehex_dims(80, 10);
+        assert!(dims.0 < 301);
+        assert_eq!(bytes_per_row, 301);
+    }
+    #[test]
+    fn second_columns() {
+        let mut dh = DoubleHexContext::new((4, 8));
+        dh.style.addr_width = 2;
+        dh.dec_columns(CursorActive::Second);
+        assert_eq!(dh.style.column_count, ColumnSetting::Fit);
+        assert_eq!(dh.style.second_column_count, Some(ColumnSetting::Fixed(3)));
+        dh.second_grid = Some(SecondGrid {
+            columns: 3,
+            bytes_per_row: 3,
+        });
+        assert_eq!(dh.column_rule().as_deref(), Some("Columns: fit|3"));
+        // the second half is narrower, so its last column is further left
+        let second_start = dh.hor_half_width() + MIDDLE_PAD.width();
+        let last = dh.style.nth_column_pos(2);
+        assert_eq!(dh.position_at(second_start + last, 1), Some((2, 0, true)));
+        assert_eq!(
+            dh.position_at(second_start + dh.style.nth_column_pos(3), 1),
+            None
+        );
+        // changing the first half alone keeps the second one
+        dh.inc_columns(CursorActive::First);
+        assert_eq!(dh.style.column_count, ColumnSetting::Fixed(5));
+        assert_eq!(dh.style.second_column_count, Some(ColumnSetting::Fixed(3)));
+        dh.reset_columns();
+        assert_eq!(dh.style.column_count, ColumnSetting::Fit);
+        assert_eq!(dh.style.second_column_count, None);
     }
 }
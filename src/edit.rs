@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::file::FileContent;
+
+/// The bytes of a file that were overwritten in edit mode. They are kept apart from the
+/// bytes of the file, which are never changed, and read in place of them until they are
+/// saved to another file.
+#[derive(Clone, Debug, Default)]
+pub struct Edits {
+    /// the edited bytes at the overwritten addresses of the compared bytes
+    bytes: BTreeMap<usize, u8>,
+    /// the start and the previous edited bytes (None if unedited) of every change,
+    /// for undoing them
+    history: Vec<(usize, Vec<Option<u8>>)>,
+    /// whether there are edits that were not saved yet
+    pub unsaved: bool,
+}
+
+impl Edits {
+    /// Overwrites the byte at the address of the content
+    pub fn write(&mut self, content: &[u8], addr: usize, byte: u8) {
+        self.fill(content, addr..addr + 1, byte)
+    }
+    /// Overwrites the bytes in the range with the same byte, which is undone as one change
+    pub fn fill(&mut self, content: &[u8], range: Range<usize>, byte: u8) {
+        let previous = range.clone().map(|addr| self.get(addr)).collect();
+        self.history.push((range.start, previous));
+        for addr in range {
+            // writing the original byte back makes the byte unedited again
+            self.set(addr, (content[addr] != byte).then_some(byte));
+        }
+    }
+    /// Goes back to the bytes before the last change, returning false if there is none
+    pub fn undo(&mut self) -> bool {
+        let (start, bytes) = match self.history.pop() {
+            Some(change) => change,
+            None => return false,
+        };
+        for (addr, byte) in (start..).zip(bytes) {
+            self.set(addr, byte);
+        }
+        true
+    }
+    /// Sets the edited byte at the address, or makes it unedited
+    fn set(&mut self, addr: usize, byte: Option<u8>) {
+        match byte {
+            Some(byte) => self.bytes.insert(addr, byte),
+            None => self.bytes.remove(&addr),
+        };
+        // nothing is lost without edits, even if they were saved before
+        self.unsaved = !self.bytes.is_empty();
+    }
+    /// The edited byte at the address, if it was overwritten
+    pub fn get(&self, addr: usize) -> Option<u8> {
+        self.bytes.get(&addr).copied()
+    }
+    /// Whether the byte at the address was overwritten
+    pub fn contains(&self, addr: usize) -> bool {
+        self.bytes.contains_key(&addr)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+    /// The content with the edited bytes, for saving and searching it.
+    /// Without edits, this is the content itself, otherwise a copy.
+    pub fn apply(&self, content: &FileContent) -> FileContent {
+        if self.bytes.is_empty() {
+            return content.clone();
+        }
+        let edited = self
+            .bytes
+            .range(..content.len())
+            .map(|(&addr, &byte)| (addr, byte));
+        Arc::new(content.patched(edited))
+    }
+}
+
+/// Whether typed characters are hex digits or the bytes of text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EditInput {
+    #[default]
+    Hex,
+    Text,
+}
+
+/// The state of edit mode, in which typed characters overwrite the bytes at the cursor
+#[derive(Debug, Default)]
+pub struct Editor {
+    /// whether edit mode is on, shared with the thread reading the keys, which sends
+    /// the typed characters instead of looking them up in the keymap while it is
+    pub active: Arc<AtomicBool>,
+    pub input: EditInput,
+    /// whether the second file was edited and the address of the byte
+    /// of which only the high nibble has been typed
+    half: Option<(bool, usize)>,
+}
+
+impl Editor {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+    /// Starts or stops edit mode, forgetting a half typed byte
+    pub fn set_active(&mut self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+        self.half = None;
+    }
+    /// Forgets a half typed byte, for when the byte changed otherwise
+    pub fn forget_half(&mut self) {
+        self.half = None;
+    }
+    /// Switches between typing hex digits and text
+    pub fn switch_input(&mut self) {
+        self.input = match self.input {
+            EditInput::Hex => EditInput::Text,
+            EditInput::Text => EditInput::Hex,
+        };
+        self.half = None;
+    }
+    /// The byte that results from typing the character over the byte at the address,
+    /// and whether it is complete, so that the cursor moves on to the next byte.
+    /// Hex digits first replace the high and then the low nibble of the byte.
+    pub fn type_char(
+        &mut self,
+        c: char,
+        second: bool,
+        addr: usize,
+        byte: u8,
+    ) -> Result<(u8, bool), String> {
+        match self.input {
+            EditInput::Hex => {
+                let digit = c
+                    .to_digit(16)
+                    .ok_or_else(|| format!("'{c}' is not a hex digit (Tab switches to text)"))?
+                    as u8;
+                if self.half == Some((second, addr)) {
+                    self.half = None;
+                    Ok(((byte & 0xf0) | digit, true))
+                } else {
+                    self.half = Some((second, addr));
+                    Ok(((digit << 4) | (byte & 0x0f), false))
+                }
+            }
+            EditInput::Text => u8::try_from(u32::from(c))
+                .map(|byte| (byte, true))
+                .map_err(|_| format!("'{c}' does not fit into a byte")),
+        }
+    }
+    /// The mode shown in the bottom line while editing
+    pub fn describe(&self) -> Option<&'static str> {
+        self.is_active().then_some(match self.input {
+            EditInput::Hex => "Editing hex (Tab: text, Esc: stop)",
+            EditInput::Text => "Editing text (Tab: hex, Esc: stop)",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits() {
+        let content: FileContent = Arc::new(b"abcd".to_vec().into());
+        let mut edits = Edits::default();
+        edits.write(&content, 1, b'x');
+        edits.write(&content, 2, b'y');
+        assert!(edits.contains(1) && edits.unsaved);
+        // writing the original byte back is no edit
+        edits.write(&content, 2, b'c');
+        assert!(!edits.contains(2));
+        assert_eq!(&edits.apply(&content)[..], b"axcd");
+        // the bytes of the file stay the same
+        assert_eq!(&content[..], b"abcd");
+        let grown: FileContent = Arc::new(b"abcdef".to_vec().into());
+        assert_eq!(&edits.apply(&grown)[..], b"axcdef");
+        // a fill is undone at once, and the changes before it one by one
+        edits.fill(&content, 0..3, 0);
+        assert_eq!(&edits.apply(&content)[..], b"\0\0\0d");
+        assert!(edits.undo());
+        assert_eq!(&edits.apply(&content)[..], b"axcd");
+        assert!(edits.undo() && edits.undo());
+        assert_eq!(edits.get(1), Some(b'x'));
+        assert!(edits.undo());
+        assert!(Arc::ptr_eq(&edits.apply(&content), &content));
+        assert!(!edits.contains(1) && !edits.unsaved);
+        assert!(!edits.undo());
+    }
+
+    #[test]
+    fn type_char() {
+        let mut editor = Editor::default();
+        assert_eq!(editor.type_char('4', false, 3, 0x12), Ok((0x42, false)));
+        assert_eq!(editor.type_char('f', false, 3, 0x42), Ok((0x4f, true)));
+        // the low nibble only follows on the same byte
+        assert_eq!(editor.type_char('4', false, 3, 0x12), Ok((0x42, false)));
+        assert_eq!(editor.type_char('5', true, 3, 0x12), Ok((0x52, false)));
+        assert!(editor.type_char('g', true, 3, 0x12).is_err());
+        editor.switch_input();
+        assert_eq!(editor.type_char('g', true, 3, 0x12), Ok((b'g', true)));
+        assert_eq!(editor.type_char('é', true, 3, 0x12), Ok((0xe9, true)));
+        assert!(editor.type_char('€', true, 3, 0x12).is_err());
+    }
+}
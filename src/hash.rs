@@ -0,0 +1,57 @@
+use sha2::{Digest, Sha256};
+
+use crate::selection::Accumulator;
+
+/// The digests of the bytes of a target
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digests {
+    pub crc32: u32,
+    /// the SHA-256 digest as lowercase hex
+    pub sha256: String,
+}
+
+/// Computes the CRC32 and SHA-256 of the bytes at the same time
+#[derive(Default)]
+pub struct Hasher {
+    crc32: crc32fast::Hasher,
+    sha256: Sha256,
+}
+
+impl Accumulator for Hasher {
+    type Output = Digests;
+    fn update(&mut self, bytes: &[u8]) {
+        self.crc32.update(bytes);
+        Digest::update(&mut self.sha256, bytes);
+    }
+    fn finish(self) -> Digests {
+        Digests {
+            crc32: self.crc32.finalize(),
+            sha256: format!("{:x}", self.sha256.finalize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digests_of(bytes: &[u8]) -> Digests {
+        let mut hasher = Hasher::default();
+        hasher.update(bytes);
+        hasher.finish()
+    }
+
+    #[test]
+    fn digests() {
+        let digests = digests_of(b"123456789");
+        assert_eq!(digests.crc32, 0xcbf43926);
+        assert_eq!(
+            digests_of(b"").sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            digests_of(b"abc").sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
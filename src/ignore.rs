@@ -0,0 +1,129 @@
+use std::{fs::read_to_string, ops::Range};
+
+/// The differences between the files that are not counted as ones, like timestamps
+/// and checksums that always differ. The bytes are still shown, but they are colored
+/// as ignored and skipped when jumping to the next difference.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ignore {
+    /// the ignored addresses of both files (in the whole files, so that they
+    /// stay the same when realigning), sorted and without overlaps
+    ranges: [Vec<Range<usize>>; 2],
+    /// the bits in which bytes can differ and still match
+    bits: u8,
+}
+
+impl Ignore {
+    pub fn new(ranges: [Vec<Range<usize>>; 2], bits: u8) -> Self {
+        Ignore {
+            ranges: ranges.map(merge),
+            bits,
+        }
+    }
+    /// Whether the address of the first or second file is in an ignored range
+    fn contains(&self, second: bool, addr: usize) -> bool {
+        let ranges = &self.ranges[second as usize];
+        let after = ranges.partition_point(|range| range.start <= addr);
+        after > 0 && ranges[after - 1].end > addr
+    }
+    /// Whether the bytes at the addresses of both files are different, which they are not
+    /// if they only differ in ignored bits or if a byte that is there is in an ignored range.
+    /// Missing bytes (like inserted ones) are different from the bytes of the other file.
+    pub fn differ(&self, addresses: [Option<usize>; 2], bytes: [Option<u8>; 2]) -> bool {
+        if bytes[0] == bytes[1] {
+            return false;
+        }
+        let in_range = (0..2).any(|i| match (addresses[i], bytes[i]) {
+            (Some(addr), Some(_)) => self.contains(i == 1, addr),
+            _ => false,
+        });
+        match bytes {
+            _ if in_range => false,
+            [Some(x), Some(y)] => (x ^ y) & !self.bits != 0,
+            _ => true,
+        }
+    }
+    /// Exchanges the ranges of the files, for when they are swapped
+    pub fn swap_files(&mut self) {
+        self.ranges.swap(0, 1)
+    }
+}
+
+/// Sorts the ranges and merges the overlapping and adjacent ones
+pub fn merge(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Parses ranges of addresses like `10-1f,40-47`, which are hexadecimal (with or without `0x`)
+/// and include their end, or reads them from the file after an `@`, where they can also
+/// be on separate lines and lines starting with `#` are ignored
+pub fn parse_ranges(value: &str) -> Result<Vec<Range<usize>>, String> {
+    let text = match value.strip_prefix('@') {
+        Some(path) => read_to_string(path).map_err(|e| format!("{path}: {e}"))?,
+        None => value.to_string(),
+    };
+    let address = |field: &str| {
+        let field = field.trim();
+        usize::from_str_radix(field.strip_prefix("0x").unwrap_or(field), 16)
+            .map_err(|_| format!("invalid address '{field}'"))
+    };
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split(','))
+        .filter(|range| !range.trim().is_empty())
+        .map(|range| {
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (address(start)?, address(end)?),
+                None => {
+                    let addr = address(range)?;
+                    (addr, addr)
+                }
+            };
+            if end < start {
+                return Err(format!("the end {end:#x} is before the start {start:#x}"));
+            }
+            Ok(start..end.saturating_add(1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            parse_ranges("10-1f, 0x40 - 0x47,\n# checksum\n80"),
+            Ok(vec![0x10..0x20, 0x40..0x48, 0x80..0x81])
+        );
+        assert!(parse_ranges("20-10").is_err());
+        assert!(parse_ranges("10-").is_err());
+        assert!(parse_ranges("@/nonexistent/ranges").is_err());
+        assert_eq!(merge(vec![8..10, 0..4, 2..6, 6..7]), vec![0..7, 8..10]);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn differ() {
+        let ignore = Ignore::new([vec![0x10..0x20], vec![0x30..0x40]], 0x01);
+        let differ = |addresses, bytes| ignore.differ(addresses, bytes);
+        assert!(differ([Some(0), Some(0)], [Some(2), Some(4)]));
+        assert!(!differ([Some(0), Some(0)], [Some(2), Some(3)]));
+        assert!(!differ([Some(0x1f), Some(0)], [Some(2), Some(4)]));
+        assert!(!differ([Some(0), Some(0x30)], [Some(2), Some(4)]));
+        assert!(differ([Some(0x20), Some(0x20)], [Some(2), Some(4)]));
+        // inserted bytes are only ignored in the ranges
+        assert!(differ([Some(0), Some(0)], [Some(2), None]));
+        assert!(!differ([Some(0x10), Some(0x10)], [Some(2), None]));
+        assert!(differ([Some(0), Some(0x30)], [Some(2), None]));
+        assert!(!differ([None, None], [None, None]));
+    }
+}
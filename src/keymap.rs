@@ -0,0 +1,788 @@
+use std::{collections::HashMap, fmt::Display, fs::read_to_string, path::Path, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::backend::Action;
+
+/// The actions that can be bound to keys, with their name in the keymap file,
+/// a description for the help screen and the default keys
+const ACTIONS: &[(&str, Action, &str, &[&str])] = &[
+    ("up", Action::Up, "Move cursor up", &["up"]),
+    ("down", Action::Down, "Move cursor down", &["down"]),
+    ("left", Action::Left, "Move cursor left", &["left"]),
+    ("right", Action::Right, "Move cursor right", &["right"]),
+    (
+        "view_up",
+        Action::UpAlt,
+        "Move view up without moving cursor",
+        &["k"],
+    ),
+    (
+        "view_down",
+        Action::DownAlt,
+        "Move view down without moving cursor",
+        &["j"],
+    ),
+    (
+        "view_left",
+        Action::LeftAlt,
+        "Move view left without moving cursor",
+        &["h"],
+    ),
+    (
+        "view_right",
+        Action::RightAlt,
+        "Move view right without moving cursor",
+        &["l"],
+    ),
+    (
+        "page_up",
+        Action::PgUp,
+        "Move view up by a screen, keeping page_overlap rows",
+        &["pageup"],
+    ),
+    (
+        "page_down",
+        Action::PgDown,
+        "Move view down by a screen, keeping page_overlap rows",
+        &["pagedown"],
+    ),
+    (
+        "half_page_up",
+        Action::HalfPgUp,
+        "Move view up by half a screen",
+        &["ctrl+u"],
+    ),
+    (
+        "half_page_down",
+        Action::HalfPgDown,
+        "Move view down by half a screen",
+        &["ctrl+d"],
+    ),
+    (
+        "cursor_first",
+        Action::CursorFirst,
+        "Activate the primary view's cursor",
+        &["a"],
+    ),
+    (
+        "cursor_both",
+        Action::CursorBoth,
+        "Activate both cursors",
+        &["s"],
+    ),
+    (
+        "cursor_second",
+        Action::CursorSecond,
+        "Activate the secondary view's cursor",
+        &["d"],
+    ),
+    (
+        "set_offset",
+        Action::SetOffset,
+        "(Unaligned View) Set offset between the files",
+        &["o"],
+    ),
+    (
+        "offset_lock",
+        Action::OffsetLock,
+        "(Unaligned View) Move both cursors together at their offset",
+        &["O"],
+    ),
+    (
+        "auto_offset",
+        Action::AutoOffset,
+        "(Unaligned View) Detect the offset from matching windows",
+        &["alt+o"],
+    ),
+    (
+        "next_difference",
+        Action::NextDifference,
+        "Jump to the next difference",
+        &["space", "J"],
+    ),
+    (
+        "prev_difference",
+        Action::PrevDifference,
+        "Jump to the previous difference",
+        &["K"],
+    ),
+    (
+        "next_insertion",
+        Action::NextInsertion,
+        "Jump to the next inserted/deleted byte",
+        &["ctrl+space", "ctrl+down", "ctrl+j"],
+    ),
+    (
+        "prev_insertion",
+        Action::PrevInsertion,
+        "Jump to the previous inserted/deleted byte",
+        &["ctrl+up", "ctrl+k"],
+    ),
+    (
+        "change_filter",
+        Action::ChangeFilter,
+        "Jump only to changes in ours, in theirs or to conflicts (with a base file)",
+        &["T"],
+    ),
+    ("help", Action::Help, "View Help", &["?", "F1"]),
+    ("unalign", Action::Unalign, "View Unaligned", &["F2"]),
+    ("align", Action::Align, "View Aligned", &["F3"]),
+    (
+        "algorithm",
+        Action::Algorithm,
+        "Alignment Algorithm Settings",
+        &["F4"],
+    ),
+    (
+        "refresh",
+        Action::Refresh,
+        "Redraw the screen",
+        &["r", "F5"],
+    ),
+    (
+        "reload",
+        Action::ReloadFiles,
+        "Read the files again and realign them",
+        &["R"],
+    ),
+    (
+        "follow",
+        Action::Follow,
+        "Follow the files as they grow, moving a cursor at the end along",
+        &["F"],
+    ),
+    (
+        "swap_files",
+        Action::SwapFiles,
+        "Swap the two files",
+        &["w"],
+    ),
+    ("goto", Action::Goto, "Go to address", &["F6"]),
+    (
+        "follow_pointer",
+        Action::FollowPointer,
+        "Read the bytes at the cursor as an offset and go there",
+        &["p"],
+    ),
+    (
+        "set_mark",
+        Action::SetMark,
+        "Set a named mark at the cursor(s)",
+        &["m"],
+    ),
+    ("marks", Action::Marks, "Jump to or delete marks", &["'"]),
+    ("search", Action::Search, "Search", &["F7"]),
+    (
+        "next_search",
+        Action::NextSearch,
+        "Jump to next search result",
+        &["n"],
+    ),
+    (
+        "prev_search",
+        Action::PrevSearch,
+        "Jump to previous search result",
+        &["N"],
+    ),
+    (
+        "drop_search",
+        Action::DropSearch,
+        "Remove the most recent search query at cursor(s)",
+        &["x"],
+    ),
+    (
+        "clear_searches",
+        Action::ClearSearches,
+        "Remove all search queries at cursor(s)",
+        &["X"],
+    ),
+    (
+        "export_search",
+        Action::ExportSearch,
+        "Export the search results to a CSV file",
+        &["e"],
+    ),
+    (
+        "export_html",
+        Action::ExportHtml,
+        "Export the aligned files with their colors to an HTML page",
+        &["P"],
+    ),
+    (
+        "command",
+        Action::Command,
+        "Type a command like set columns=16, goto 0x4000 or algo global",
+        &[":"],
+    ),
+    (
+        "hash",
+        Action::Hash,
+        "Show the CRC32 and SHA-256 of the selection or the whole file(s)",
+        &["#"],
+    ),
+    (
+        "byte_stats",
+        Action::ByteStats,
+        "Show a histogram and statistics of the selection or the whole file(s)",
+        &["H"],
+    ),
+    (
+        "relative_addresses",
+        Action::RelativeAddresses,
+        "Show addresses relative to the cursor(s), or absolute again",
+        &["A"],
+    ),
+    (
+        "fold_identical",
+        Action::FoldIdentical,
+        "Fold long runs of identical bytes in the aligned view, or unfold them",
+        &["Z"],
+    ),
+    (
+        "toggle_fold",
+        Action::ToggleFold,
+        "Unfold the identical bytes at the cursor, or fold them again",
+        &["z"],
+    ),
+    (
+        "diff_map",
+        Action::DiffMap,
+        "Show or hide the map of the differences at the right edge",
+        &["M"],
+    ),
+    (
+        "jump_percent",
+        Action::JumpPercent,
+        "Jump to the count prefix in percent of the files (50% to the middle)",
+        &["%"],
+    ),
+    (
+        "delta_mode",
+        Action::DeltaMode,
+        "Show the second file, the xor or the delta of the bytes in the second half",
+        &["^"],
+    ),
+    (
+        "word_left",
+        Action::WordLeft,
+        "Move cursor left to the next byte group",
+        &["ctrl+left"],
+    ),
+    (
+        "word_right",
+        Action::WordRight,
+        "Move cursor right to the next byte group",
+        &["ctrl+right"],
+    ),
+    (
+        "row_start",
+        Action::RowStart,
+        "Move the cursor to the start of the row",
+        &["home"],
+    ),
+    (
+        "row_end",
+        Action::RowEnd,
+        "Move the cursor to the end of the row",
+        &["end"],
+    ),
+    ("top", Action::Top, "Jump to first address", &["ctrl+home"]),
+    (
+        "bottom",
+        Action::Bottom,
+        "Jump to last address",
+        &["ctrl+end"],
+    ),
+    (
+        "jump_back",
+        Action::JumpBack,
+        "Go back to the position before the last jump",
+        &["ctrl+o"],
+    ),
+    (
+        "jump_forward",
+        Action::JumpForward,
+        "Go forward again after going back",
+        &["tab"],
+    ),
+    (
+        "undo",
+        Action::Undo,
+        "Undo the last change of the columns, layout, offset or selection",
+        &["u"],
+    ),
+    (
+        "edit",
+        Action::Edit,
+        "Overwrite the bytes at the active cursor (Esc stops)",
+        &["i"],
+    ),
+    (
+        "save_as",
+        Action::SaveAs,
+        "Save the edited bytes to a new file",
+        &["W"],
+    ),
+    (
+        "fill",
+        Action::Fill,
+        "Fill the selection with a byte in edit mode",
+        &["ctrl+f"],
+    ),
+    (
+        "undo_edit",
+        Action::UndoEdit,
+        "Undo the last edit of the active file",
+        &["alt+u"],
+    ),
+    (
+        "select_up",
+        Action::SelectUp,
+        "Move cursor up and extend selection",
+        &["shift+up"],
+    ),
+    (
+        "select_down",
+        Action::SelectDown,
+        "Move cursor down and extend selection",
+        &["shift+down"],
+    ),
+    (
+        "select_left",
+        Action::SelectLeft,
+        "Move cursor left and extend selection",
+        &["shift+left"],
+    ),
+    (
+        "select_right",
+        Action::SelectRight,
+        "Move cursor right and extend selection",
+        &["shift+right"],
+    ),
+    (
+        "select_rows",
+        Action::SelectRows,
+        "Start selection of whole rows at cursor(s)",
+        &["V"],
+    ),
+    (
+        "start_selection",
+        Action::StartSelection,
+        "Start selection at cursor(s)",
+        &["v"],
+    ),
+    (
+        "clear_selection",
+        Action::ClearSelection,
+        "Clear selection at cursor(s)",
+        &["c"],
+    ),
+    ("add_column", Action::AddColumn, "Add a hex column", &["]"]),
+    (
+        "remove_column",
+        Action::RemoveColumn,
+        "Remove a hex column",
+        &["["],
+    ),
+    (
+        "reset_column",
+        Action::ResetColumn,
+        "Set columns to fit screen",
+        &["0"],
+    ),
+    (
+        "cycle_group",
+        Action::CycleGroup,
+        "Group the hex columns by 2, 4, 8 or not at all",
+        &["|"],
+    ),
+    (
+        "next_base",
+        Action::NextBase,
+        "Show the bytes in hex, decimal, octal or binary",
+        &["b"],
+    ),
+    (
+        "byte_classes",
+        Action::ByteClasses,
+        "Color the bytes by class instead of by differences",
+        &["C"],
+    ),
+    (
+        "entropy_colors",
+        Action::EntropyColors,
+        "Color the bytes by the entropy around them",
+        &["E"],
+    ),
+    (
+        "toggle_text",
+        Action::ToggleText,
+        "Show or hide the ascii column",
+        &["t"],
+    ),
+    (
+        "toggle_split",
+        Action::ToggleSplit,
+        "Show the files above each other or side by side",
+        &["L"],
+    ),
+    (
+        "auto_column",
+        Action::AutoColumn,
+        "Set columns to the repetitions in the files (or the selected bytes)",
+        &["="],
+    ),
+    (
+        "multiple_column",
+        Action::MultipleColumn,
+        "Keep columns at a multiple of the count prefix or the current columns",
+        &["*"],
+    ),
+    (
+        "suspend",
+        Action::Suspend,
+        "Suspend to the shell (continue with fg)",
+        &["ctrl+z"],
+    ),
+    ("quit", Action::Quit, "Quit", &["q", "esc"]),
+];
+
+/// Named keys, in the way they are written in the keymap file
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("esc", KeyCode::Esc),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+];
+
+const MODIFIER_NAMES: &[(&str, KeyModifiers)] = &[
+    ("ctrl", KeyModifiers::CONTROL),
+    ("alt", KeyModifiers::ALT),
+    ("shift", KeyModifiers::SHIFT),
+];
+
+/// A key along with its modifiers, like `ctrl+j`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // uppercase characters already contain the shift
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeySpec { code, modifiers }
+    }
+}
+
+impl FromStr for KeySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the last part is the key, and since the key may be '+' itself,
+        // we cannot simply split on '+'
+        let (modifier_part, key) = match s.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => {
+                (rest.strip_suffix('+').unwrap_or(rest), "+")
+            }
+            _ => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_part.split('+').filter(|x| !x.is_empty()) {
+            match MODIFIER_NAMES
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                Some((_, modifier)) => modifiers |= *modifier,
+                None => return Err(format!("unknown modifier \"{name}\"")),
+            }
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let function_key = key
+                    .strip_prefix(['f', 'F'])
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .filter(|n| (1..=12).contains(n));
+                match function_key {
+                    Some(n) => KeyCode::F(n),
+                    None => KEY_NAMES
+                        .iter()
+                        .find(|(n, _)| n.eq_ignore_ascii_case(key))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| format!("unknown key \"{key}\""))?,
+                }
+            }
+        };
+        Ok(KeySpec::new(code, modifiers))
+    }
+}
+
+impl Display for KeySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, modifier) in MODIFIER_NAMES {
+            if self.modifiers.contains(*modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.code {
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{name}"),
+                None => match code {
+                    KeyCode::Char(c) => write!(f, "{c}"),
+                    _ => write!(f, "{code:?}"),
+                },
+            },
+        }
+    }
+}
+
+/// Maps keys to actions
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    /// the keys of every action, in the order of `ACTIONS`
+    bindings: Vec<Vec<KeySpec>>,
+    keys: HashMap<KeySpec, Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .map(|(_, _, _, keys)| {
+                keys.iter()
+                    .map(|k| k.parse().expect("invalid default key"))
+                    .collect()
+            })
+            .collect();
+        Self::from_bindings(bindings)
+    }
+}
+
+impl KeyMap {
+    fn from_bindings(bindings: Vec<Vec<KeySpec>>) -> Self {
+        let keys = bindings
+            .iter()
+            .zip(ACTIONS)
+            .flat_map(|(keys, (_, action, _, _))| keys.iter().map(|k| (*k, *action)))
+            .collect();
+        KeyMap { bindings, keys }
+    }
+    /// Parses a keymap where every line looks like `action = key, key`, with
+    /// lines starting with `#` being comments.
+    /// Actions that are not mentioned keep their default keys, but keys bound
+    /// in the keymap are taken away from the defaults.
+    /// Also returns a description of the invalid lines, which are skipped.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut configured: Vec<Option<Vec<KeySpec>>> = vec![None; ACTIONS.len()];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut error = |e: String| errors.push(format!("line {}: \"{line}\": {e}", n + 1));
+            let (name, keys) = match line.split_once('=') {
+                Some(x) => x,
+                None => {
+                    error(String::from("expected \"action = key, key, ...\""));
+                    continue;
+                }
+            };
+            let name = name.trim();
+            let idx = match ACTIONS.iter().position(|(n, _, _, _)| *n == name) {
+                Some(idx) => idx,
+                None => {
+                    error(format!("unknown action \"{name}\""));
+                    continue;
+                }
+            };
+            let keys = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(KeySpec::from_str)
+                .collect::<Result<Vec<_>, _>>();
+            match keys {
+                Ok(keys) => configured[idx].get_or_insert_with(Vec::new).extend(keys),
+                Err(e) => error(e),
+            }
+        }
+        let configured_keys = configured.iter().flatten().flatten().collect::<Vec<_>>();
+        let bindings = KeyMap::default()
+            .bindings
+            .into_iter()
+            .zip(configured.iter())
+            .map(|(default, configured)| match configured {
+                Some(keys) => keys.clone(),
+                None => default
+                    .into_iter()
+                    .filter(|k| !configured_keys.contains(&k))
+                    .collect(),
+            })
+            .collect();
+        (Self::from_bindings(bindings), errors)
+    }
+    /// Reads the keymap from a file, returning the default keymap if it does not exist
+    pub fn from_file(path: &Path) -> (Self, Vec<String>) {
+        match read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => (Self::default(), Vec::new()),
+        }
+    }
+    /// Returns the action of a key event.
+    /// If there is no binding with exactly the same modifiers,
+    /// some of the modifiers are ignored.
+    pub fn action(&self, event: KeyEvent) -> Option<Action> {
+        let key = KeySpec::new(event.code, event.modifiers);
+        [
+            key.modifiers,
+            key.modifiers & KeyModifiers::CONTROL,
+            key.modifiers & KeyModifiers::SHIFT,
+            KeyModifiers::NONE,
+        ]
+        .into_iter()
+        .find_map(|modifiers| self.keys.get(&KeySpec::new(key.code, modifiers)))
+        .copied()
+    }
+    /// Lists every action with its name in the keymap file, its effective keys and its
+    /// description for the help screen, leaving out the actions whose name and description
+    /// do not contain the filter (ignoring case). Actions without keys are listed as well,
+    /// since they can still be bound in the keymap file.
+    pub fn help_text(&self, filter: &str) -> String {
+        let filter = filter.trim().to_lowercase();
+        self.bindings
+            .iter()
+            .zip(ACTIONS)
+            .filter(|(_, (name, _, description, _))| {
+                name.contains(&filter) || description.to_lowercase().contains(&filter)
+            })
+            .map(|(keys, (name, _, description, _))| {
+                let keys = match keys.is_empty() {
+                    true => String::from("not bound"),
+                    false => keys
+                        .iter()
+                        .map(|k| format!("'{k}'"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                format!(" * {name} ({keys}): {description}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+    #[test]
+    fn key_specs() {
+        for spec in [
+            "ctrl+j",
+            "F3",
+            "space",
+            "J",
+            "+",
+            "ctrl++",
+            "alt+shift+pageup",
+        ] {
+            let parsed: KeySpec = spec.parse().unwrap();
+            assert_eq!(parsed.to_string(), spec);
+        }
+        assert_eq!(
+            "Ctrl+Space".parse::<KeySpec>(),
+            Ok(KeySpec::new(KeyCode::Char(' '), KeyModifiers::CONTROL))
+        );
+        assert!("hyper+j".parse::<KeySpec>().is_err());
+        assert!("F13".parse::<KeySpec>().is_err());
+        assert!("jj".parse::<KeySpec>().is_err());
+    }
+    #[test]
+    fn default_keymap() {
+        let keymap = KeyMap::default();
+        let action = |code, modifiers| keymap.action(key(code, modifiers));
+        assert!(matches!(
+            action(KeyCode::Down, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+            Some(Action::NextInsertion)
+        ));
+        assert!(matches!(
+            action(KeyCode::Down, KeyModifiers::SHIFT),
+            Some(Action::SelectDown)
+        ));
+        assert!(matches!(
+            action(KeyCode::Char('V'), KeyModifiers::SHIFT),
+            Some(Action::SelectRows)
+        ));
+        assert!(matches!(
+            action(KeyCode::Down, KeyModifiers::ALT),
+            Some(Action::Down)
+        ));
+        assert!(matches!(
+            action(KeyCode::Home, KeyModifiers::NONE),
+            Some(Action::RowStart)
+        ));
+        assert!(matches!(
+            action(KeyCode::End, KeyModifiers::CONTROL),
+            Some(Action::Bottom)
+        ));
+        assert!(matches!(
+            action(KeyCode::Char('J'), KeyModifiers::SHIFT),
+            Some(Action::NextDifference)
+        ));
+        assert!(action(KeyCode::Char('g'), KeyModifiers::NONE).is_none());
+    }
+    #[test]
+    fn configured_keymap() {
+        let (keymap, errors) = KeyMap::parse(
+            "# comment\n\
+             top = g, home\n\
+             bottom = G\n\
+             view_down = \n\
+             teleport = t\n\
+             quit = hyper+q\n\
+             refresh\n",
+        );
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(errors[0].starts_with("line 5:"));
+        assert!(errors[0].contains("teleport"));
+        assert!(errors[1].contains("hyper"));
+        assert!(errors[2].starts_with("line 7:"));
+        let action = |c| keymap.action(key(KeyCode::Char(c), KeyModifiers::NONE));
+        assert!(matches!(action('g'), Some(Action::Top)));
+        assert!(matches!(action('G'), Some(Action::Bottom)));
+        assert!(action('j').is_none());
+        // unconfigured actions keep their defaults
+        assert!(matches!(action('q'), Some(Action::Quit)));
+        let help = keymap.help_text("");
+        assert!(help.contains(" * top ('g', 'home'): Jump to first address\n"));
+        // actions without keys are still listed
+        assert!(help.contains(" * view_down (not bound): Move view down without"));
+        assert_eq!(
+            keymap.help_text(" First ADDRESS"),
+            " * top ('g', 'home'): Jump to first address"
+        );
+        assert_eq!(keymap.help_text("teleport"), "");
+    }
+}
@@ -1,51 +1,498 @@
-mod align;
+mod annotation;
+mod args;
 mod backend;
+mod cache;
+mod capabilities;
 mod control;
 mod cursor;
 mod datastruct;
 mod dialog;
+mod dirlist;
 mod doublehex;
-mod file;
-mod search;
+mod edit;
+mod hash;
+mod ignore;
+mod keymap;
+mod marks;
+mod notice;
+mod pointer;
+mod prompt;
+mod report;
+mod script;
 mod selection;
+mod session;
+mod sigbus;
+mod stats;
 mod style;
-mod util;
+mod suspend;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod threeway;
 mod view;
+use biodiff_core::{align, file, search, util};
 use std::env;
 use std::ffi::OsString;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::thread::available_parallelism;
 
-use file::FileState;
+use annotation::Annotations;
+use args::Args;
+use file::{FileState, Source};
+use report::batch::{self, BatchOptions};
+use report::html::{self, HtmlOptions, DEFAULT_FOLD_THRESHOLD};
+use report::patch;
+use report::unified::{self, UnifiedOptions, DEFAULT_CONTEXT, DEFAULT_ELIDE_THRESHOLD};
 
-fn print_usage(name: &OsString) -> ! {
-    eprintln!("usage: {} [file1] [file2]", name.to_string_lossy());
-    exit(1)
+/// Prints the differences of the aligned files to stdout in the given format
+/// and exits with 0 if there are none and 1 otherwise
+fn report(x: FileState, y: FileState, format: report::Format, presets: control::Presets) -> ! {
+    let mut settings = control::Settings::from_config().unwrap_or_default();
+    presets.apply(&mut settings);
+    let algo = settings.algo;
+    // like the other errors, a truncated file is told apart from different files in quiet mode
+    let error_status = if matches!(format, report::Format::Quiet) {
+        2
+    } else {
+        1
+    };
+    sigbus::catch_bus_error_in_report(error_status);
+    let alignment = report::Alignment::start(&algo, x.content.clone(), y.content.clone());
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let files = [&x, &y];
+    let result = match format {
+        report::Format::Regions { max_regions } => {
+            report::write_report(&mut stdout, files, alignment, max_regions)
+        }
+        report::Format::Json => report::write_json(&mut stdout, files, &algo, alignment),
+        report::Format::Unified {
+            context,
+            elide_threshold,
+        } => {
+            let options = UnifiedOptions {
+                context,
+                elide_threshold,
+                style: settings.style,
+            };
+            unified::write_unified(&mut stdout, files, alignment, options)
+        }
+        report::Format::Html {
+            path,
+            fold_threshold,
+        } => {
+            let options = HtmlOptions {
+                style: settings.style,
+                fold_threshold,
+            };
+            File::create(path).and_then(|file| {
+                let mut out = std::io::BufWriter::new(file);
+                html::write_html(&mut out, files, alignment, options)
+            })
+        }
+        report::Format::Patch { path, format } => File::create(path).and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            patch::write_patch(&mut out, alignment, format)
+        }),
+        report::Format::Quiet => Ok(alignment.any_difference()),
+    };
+    match result {
+        Ok(different) => exit(different as i32),
+        // the reader of a pipe is not interested in the rest
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => exit(1),
+        Err(e) => {
+            eprintln!("Could not write the report: {e}");
+            exit(1)
+        }
+    }
 }
 
-fn main() {
-    let args: Vec<_> = env::args_os().collect();
-    // we expect exactly two arguments, being the files
-    // might extend this in the future, but for now this is enough
-    let (xfile, yfile) = match &args[1..] {
-        [s] => {
-            if matches!(s.to_str(), Some("-v" | "--version")) {
-                println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
-                exit(0);
-            } else {
-                print_usage(&args[0])
+/// Compares the pairs of files of a manifest on stdin or of two directories and writes a line
+/// for each pair to stdout, then exits with 2 if any pair could not be compared, with 1 if any
+/// pair is different and with 0 otherwise
+fn report_batch(
+    args: &Args,
+    presets: control::Presets,
+    decompress: bool,
+    windows: [file::Window; 2],
+) -> ! {
+    let pairs = match &args.positional[1..] {
+        [] => {
+            let mut manifest = Vec::new();
+            if let Err(e) = std::io::stdin().read_to_end(&mut manifest) {
+                eprintln!("Could not read the manifest: {e}");
+                exit(2);
             }
+            or_exit(batch::parse_manifest(&manifest), 2)
+        }
+        [a, b] => batch::dir_pairs(&[a, b].map(PathBuf::from)).unwrap_or_else(|e| {
+            eprintln!("Could not list the directories: {e}");
+            exit(2)
+        }),
+        _otherwise => print_usage(&args.positional[0], 2),
+    };
+    let mut settings = control::Settings::from_config().unwrap_or_default();
+    presets.apply(&mut settings);
+    let jobs = or_exit(args.number("--jobs"), 2)
+        .unwrap_or_else(|| available_parallelism().map(usize::from).unwrap_or(1));
+    let options = BatchOptions {
+        algo: settings.algo,
+        decompress,
+        windows,
+        json_dir: args.value("--json-dir").map(PathBuf::from),
+        jobs,
+    };
+    sigbus::catch_bus_error_in_report(2);
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    match batch::run(&pairs, &options, &mut stdout) {
+        Ok(counts) if counts.failed > 0 => exit(2),
+        Ok(counts) => exit((counts.different > 0) as i32),
+        // the reader of a pipe is not interested in the rest
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => exit(2),
+        Err(e) => {
+            eprintln!("Could not write the summary: {e}");
+            exit(2)
+        }
+    }
+}
+
+/// Makes the target file from the source file and the patch, then exits with 0,
+/// or with 2 if a file can not be read or written or the patch does not fit the source
+fn apply_patch(args: &Args, patch_path: &str) -> ! {
+    let (source, target) = match &args.positional[1..] {
+        [source, target] => (source, target),
+        _otherwise => print_usage(&args.positional[0], 2),
+    };
+    let read = |path: &Path| {
+        std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {e}", path.display());
+            exit(2)
+        })
+    };
+    let patch = read(Path::new(patch_path));
+    let source = read(Path::new(source));
+    let bytes = or_exit(patch::apply(&patch, &source), 2);
+    if let Err(e) = std::fs::write(target, bytes) {
+        eprintln!("Could not write {}: {e}", Path::new(target).display());
+        exit(2);
+    }
+    exit(0)
+}
+
+/// Returns the value, or prints the error about the command line and exits
+fn or_exit<T>(result: Result<T, String>, status: i32) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(status)
+    })
+}
+
+/// Lists the files of two directories until the list is left, comparing the chosen ones
+/// in the viewer with the options that apply to all of them
+fn compare_dirs(
+    args: &Args,
+    dirs: [&OsString; 2],
+    monochrome: bool,
+    presets: control::Presets,
+    decompress: bool,
+    ignore: &ignore::Ignore,
+    unit: file::Unit,
+) -> ! {
+    let read = |path: PathBuf| {
+        let source = Source {
+            path: path.into_os_string(),
+            label: None,
+            decompress,
+            window: file::Window::default(),
+            unit,
+        };
+        // warnings about the decompression would only be seen after leaving the list
+        match source.read() {
+            Ok((file, _)) => Ok((file, source)),
+            Err(e) => Err(format!(
+                "Could not read {}: {e}",
+                source.path.to_string_lossy()
+            )),
         }
-        [a, b] => (a, b),
-        _otherwise => print_usage(&args[0]),
     };
-    let x = FileState::from_file(xfile).unwrap_or_else(|e| {
-        eprintln!("Could not read {}: {}", xfile.to_string_lossy(), e);
+    let open = |[first, second]: [PathBuf; 2]| -> Result<(), String> {
+        let (x, xsource) = read(first)?;
+        let (y, ysource) = read(second)?;
+        let options = control::Options {
+            monochrome,
+            no_scroll: args.has("--no-scroll"),
+            start_aligned: args.has("--start-aligned"),
+            single: false,
+            self_diff: false,
+            presets,
+            sources: [xsource, ysource].map(|source| source.can_reread().then_some(source)),
+            annotations: Default::default(),
+            ignore: ignore.clone(),
+            no_cache: args.has("--no-cache"),
+            session: args.value("--session").map(PathBuf::from),
+            start_given: false,
+            base: None,
+            record: None,
+            replay: None,
+        };
+        control::run(x, y, options).map_err(|e| format!("Could not draw to the terminal: {e}"))
+    };
+    let dirs = dirs.map(PathBuf::from);
+    if let Err(e) = dirlist::run(dirs, monochrome, open) {
+        eprintln!("Could not list the directories: {e}");
         exit(1);
+    }
+    exit(0)
+}
+
+/// Prints how to call biodiff and exits with the status of errors
+fn print_usage(name: &OsString, status: i32) -> ! {
+    eprintln!(
+        "usage: {} [--color=auto|always|never] [--no-scroll] [--frontend crossterm|cursive|auto] [--decompress auto|off] [--skip[-a|-b] ADDR] [--length[-a|-b] LEN] [--label-a NAME] [--label-b NAME] [--annotations-a FILE] [--annotations-b FILE] [--ignore[-a|-b] RANGES|@FILE] [--ignore-bits MASK] [--unit bytes|nibbles|bits] [--offset-a ADDR] [--offset-b ADDR] [--goto ADDR] [--algorithm global|local|blockwise] [--gap-open N] [--gap-extend N] [--columns N] [--start-aligned] [--no-cache] [--session FILE] [--record FILE] [--replay FILE [--replay-speed N]] [--report [--max-report N] | --json | --unified[=N] [--elide N] | --html FILE [--collapse N] | --emit-patch FILE [--patch-format simple|vcdiff] | --quiet] file1 [file2] | base file1 file2 | --self file | dir1 dir2 | --batch [--jobs N] [--json-dir DIR] [dir1 dir2] | --apply-patch FILE source target",
+        name.to_string_lossy()
+    );
+    exit(status)
+}
+
+fn main() {
+    let args = Args::parse(env::args_os());
+    let name = &args.positional[0];
+    // like cmp, errors are told apart from different files by the exit status in quiet mode
+    let quiet = args.has("--quiet");
+    let error_status = if quiet { 2 } else { 1 };
+    // see https://no-color.org, the flag takes precedence
+    let monochrome = match args.value("--color") {
+        Some("never") => true,
+        Some("always") => false,
+        Some("auto") | None => env::var_os("NO_COLOR").map_or(false, |x| !x.is_empty()),
+        Some(_) => print_usage(name, error_status),
+    };
+    let format = if quiet {
+        Some(report::Format::Quiet)
+    } else if args.has_any("--unified") {
+        Some(report::Format::Unified {
+            context: or_exit(args.number("--unified"), error_status).unwrap_or(DEFAULT_CONTEXT),
+            elide_threshold: or_exit(args.number("--elide"), error_status)
+                .unwrap_or(DEFAULT_ELIDE_THRESHOLD),
+        })
+    } else if let Some(path) = args.value("--html") {
+        Some(report::Format::Html {
+            path: PathBuf::from(path),
+            fold_threshold: or_exit(args.number("--collapse"), error_status)
+                .unwrap_or(DEFAULT_FOLD_THRESHOLD),
+        })
+    } else if let Some(path) = args.value("--emit-patch") {
+        Some(report::Format::Patch {
+            path: PathBuf::from(path),
+            format: or_exit(args.patch_format(), error_status),
+        })
+    } else if args.has("--json") {
+        Some(report::Format::Json)
+    } else if args.has("--report") {
+        Some(report::Format::Regions {
+            max_regions: or_exit(args.number("--max-report"), error_status),
+        })
+    } else {
+        None
+    };
+    let presets = or_exit(args.presets(), error_status);
+    let decompress = or_exit(args.decompress(), error_status);
+    let windows = or_exit(args.windows(), error_status);
+    let ignore = or_exit(args.ignore(), error_status);
+    let unit = or_exit(args.unit(), error_status);
+    if let Some(patch_path) = args.value("--apply-patch") {
+        apply_patch(&args, patch_path)
+    }
+    if args.has("--batch") {
+        if format.is_some() || unit != file::Unit::Byte {
+            eprintln!(
+                "--batch writes its own summary of whole bytes, --json-dir adds the alignments"
+            );
+            exit(2);
+        }
+        report_batch(&args, presets, decompress, windows)
+    }
+    // two directories are listed file by file, and the chosen files are compared
+    if let [a, b] = &args.positional[1..] {
+        if format.is_none() && Path::new(a).is_dir() && Path::new(b).is_dir() {
+            compare_dirs(
+                &args,
+                [a, b],
+                monochrome,
+                presets,
+                decompress,
+                &ignore,
+                unit,
+            )
+        }
+    }
+    // we expect two files, a single one that is viewed without comparing it,
+    // or the common ancestor of two files before them
+    let (basefile, xfile, yfile) = match &args.positional[1..] {
+        [s] if matches!(s.to_str(), Some("-v" | "--version")) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            exit(0);
+        }
+        [a] => (None, a, a),
+        [a, b] => (None, a, b),
+        [base, a, b] => (Some(base), a, b),
+        _otherwise => print_usage(name, error_status),
+    };
+    // a file compared with itself is shown in both halves, at offsets of their own
+    let self_diff = args.has("--self");
+    if self_diff && args.positional.len() != 2 {
+        eprintln!("--self compares a single file with itself");
+        exit(error_status);
+    }
+    if self_diff && windows[0] != windows[1] {
+        eprintln!("--self compares the same bytes in both halves, use --offset-a and --offset-b");
+        exit(error_status);
+    }
+    let single = args.positional.len() == 2 && !self_diff;
+    if single && format.is_some() {
+        eprintln!("Reports need two files to compare");
+        exit(error_status);
+    }
+    if basefile.is_some() && format.is_some() {
+        eprintln!("Reports compare two files, a base file only works in the viewer");
+        exit(error_status);
+    }
+    if unit != file::Unit::Byte && format.is_some() {
+        eprintln!("Reports compare whole bytes, --unit only works in the viewer");
+        exit(error_status);
+    }
+    let stdin_files = [
+        basefile,
+        Some(xfile),
+        Some(yfile).filter(|_| !single && !self_diff),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|&file| file == file::STDIN)
+    .count();
+    if stdin_files > 1 {
+        eprintln!("Only one of the files can be read from stdin");
+        exit(error_status);
+    }
+    let [xwindow, ywindow] = windows;
+    let sources = [
+        (xfile, "--label-a", xwindow, "A"),
+        (yfile, "--label-b", ywindow, "B"),
+    ]
+    .map(|(path, flag, window, half)| {
+        // names to show instead of the paths, like the temporary files git passes to difftools
+        let label = args.value(flag).map(String::from);
+        // the halves of a file compared with itself are told apart by their names
+        let label = if self_diff {
+            let name = label.unwrap_or_else(|| path.to_string_lossy().to_string());
+            Some(format!("{name} @{half}"))
+        } else {
+            label
+        };
+        Source {
+            path: path.clone(),
+            label,
+            decompress,
+            window,
+            unit,
+        }
     });
-    let y = FileState::from_file(yfile).unwrap_or_else(|e| {
-        eprintln!("Could not read {}: {}", yfile.to_string_lossy(), e);
-        exit(1);
+    // a single file is only read once and shown as both files, with the second one hidden
+    let read = |source: &Source| match source.read() {
+        Ok((file, warning)) => {
+            if let Some(warning) = warning {
+                eprintln!("{warning}");
+            }
+            file
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {}", source.path.to_string_lossy(), e);
+            exit(error_status);
+        }
+    };
+    let mut x = read(&sources[0]);
+    // and so is a file compared with itself, with both halves sharing its bytes
+    let mut y = if single {
+        x.shared()
+    } else if self_diff {
+        FileState {
+            name: sources[1].label.clone().unwrap_or_default(),
+            ..x.shared()
+        }
+    } else {
+        read(&sources[1])
+    };
+    // the base is read as a whole, the compared bytes of both files are aligned to all of it
+    let base = basefile.map(|path| {
+        read(&Source {
+            path: path.clone(),
+            label: None,
+            decompress,
+            window: file::Window::default(),
+            unit,
+        })
+        .content
+    });
+    // the flags give addresses of bytes, which are split into units along with the files
+    let per_byte = unit.per_byte();
+    [x.index, y.index] = or_exit(
+        args.start_addresses([&x, &y].map(|file| {
+            let base = file.content.base() / per_byte;
+            base..base + file.content.len() / per_byte
+        })),
+        error_status,
+    )
+    .map(|index| index * per_byte);
+    if single {
+        y.index = x.index;
+    }
+    if let Some(format) = format {
+        report(x, y, format, presets)
+    }
+    let annotations = ["--annotations-a", "--annotations-b"].map(|flag| match args.value(flag) {
+        Some(path) => or_exit(
+            Annotations::load(Path::new(path))
+                .map(|annotations| annotations.split(unit))
+                .map_err(|e| format!("Could not read the annotations {path}: {e}")),
+            error_status,
+        ),
+        None => Annotations::default(),
     });
-    // main control loop
-    control::run(x, y)
+    // the hidden second file of a single one has the same annotations, and so does the second
+    // half of a file compared with itself unless it has annotations of its own
+    let annotations = if single || (self_diff && args.value("--annotations-b").is_none()) {
+        [annotations[0].clone(), annotations[0].clone()]
+    } else {
+        annotations
+    };
+    let [xsource, ysource] = sources.map(|source| source.can_reread().then_some(source));
+    let replay = or_exit(args.replay(), error_status);
+    let options = control::Options {
+        monochrome,
+        no_scroll: args.has("--no-scroll"),
+        start_aligned: args.has("--start-aligned"),
+        single,
+        self_diff,
+        presets,
+        // the hidden second file of a single one is not read again
+        sources: [xsource, ysource.filter(|_| !single)],
+        annotations,
+        ignore,
+        no_cache: args.has("--no-cache"),
+        session: args.value("--session").map(PathBuf::from),
+        start_given: ["--offset-a", "--offset-b", "--goto"]
+            .iter()
+            .any(|flag| args.value(flag).is_some()),
+        base,
+        record: args.value("--record").map(PathBuf::from),
+        replay,
+    };
+    // main control loop, which exits with 0 no matter whether the files are different
+    // since git treats other exit codes of difftools as errors
+    if let Err(e) = control::run(x, y, options) {
+        eprintln!("Could not draw to the terminal: {e}");
+        exit(1);
+    }
 }
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+use crate::file::{Source, Unit};
+
+/// The marks of every file, by the key of the file and the name of the mark
+pub type MarkMap = BTreeMap<String, BTreeMap<String, usize>>;
+
+/// Named addresses like the marks of vim, for jumping back to places.
+/// A mark stores the address of the cursor in each of the shown files under the path
+/// of the file, so that it can also be used when the file is compared to another one.
+#[derive(Clone, Debug, Default)]
+pub struct Marks {
+    /// the keys of the shown files, which are their canonical paths if they have one
+    files: [String; 2],
+    marks: MarkMap,
+}
+
+impl Marks {
+    pub fn new(files: [String; 2], marks: MarkMap) -> Self {
+        Marks { files, marks }
+    }
+    /// The key of a file, which is its canonical path if it can be read again and the shown
+    /// name otherwise (like for stdin). Split bytes have marks of their own, since their
+    /// addresses count the units.
+    pub fn file_key(source: Option<&Source>, name: &str) -> String {
+        let key = source
+            .and_then(|source| std::fs::canonicalize(&source.path).ok())
+            .map_or_else(
+                || name.to_string(),
+                |path| path.to_string_lossy().to_string(),
+            );
+        match source.map(|source| source.unit) {
+            Some(Unit::Nibble) => format!("{key} (nibbles)"),
+            Some(Unit::Bit) => format!("{key} (bits)"),
+            Some(Unit::Byte) | None => key,
+        }
+    }
+    /// Reads the marks of all files written by `save`, which are empty if there is no such file
+    pub fn load(path: &Path) -> Result<MarkMap, String> {
+        match read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MarkMap::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+    /// Writes the marks of all files
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        std::fs::write(path, serde_json::to_string(&self.marks)?)?;
+        Ok(())
+    }
+    /// Exchanges the shown files
+    pub fn swap_files(&mut self) {
+        self.files.swap(0, 1);
+    }
+    /// Sets the mark to the addresses in both files, or removes it from the files
+    /// where the address is None.
+    pub fn set(&mut self, name: &str, addresses: [Option<usize>; 2]) {
+        // if a file is compared to itself, the address of the first one is kept
+        for (file, address) in self.files.iter().zip(addresses).rev() {
+            let marks = self.marks.entry(file.clone()).or_default();
+            match address {
+                Some(address) => marks.insert(name.to_string(), address),
+                None => marks.remove(name),
+            };
+        }
+        self.marks.retain(|_, marks| !marks.is_empty());
+    }
+    /// The addresses of the mark in both files
+    pub fn get(&self, name: &str) -> [Option<usize>; 2] {
+        self.files.clone().map(|file| {
+            self.marks
+                .get(&file)
+                .and_then(|marks| marks.get(name))
+                .copied()
+        })
+    }
+    /// Removes the mark from both files
+    pub fn remove(&mut self, name: &str) {
+        self.set(name, [None, None])
+    }
+    /// The names of the marks of the shown files in order, along with their addresses
+    pub fn list(&self) -> Vec<(String, [Option<usize>; 2])> {
+        let mut names = self
+            .files
+            .iter()
+            .filter_map(|file| self.marks.get(file))
+            .flat_map(|marks| marks.keys())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| (name.clone(), self.get(name)))
+            .collect()
+    }
+    /// The first letter that is not the name of a mark of the shown files yet
+    pub fn free_name(&self) -> String {
+        let used = self.list();
+        ('a'..='z')
+            .map(String::from)
+            .find(|name| used.iter().all(|(used, _)| used != name))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks() {
+        let mut marks = Marks::new([String::from("x"), String::from("y")], MarkMap::new());
+        marks.set("a", [Some(1), Some(2)]);
+        marks.set("b", [Some(3), None]);
+        assert_eq!(marks.free_name(), "c");
+        assert_eq!(
+            marks.list(),
+            [
+                (String::from("a"), [Some(1), Some(2)]),
+                (String::from("b"), [Some(3), None])
+            ]
+        );
+        // the marks of a file are kept when it is compared to another one
+        let mut other = Marks::new([String::from("z"), String::from("x")], marks.marks.clone());
+        assert_eq!(other.get("a"), [None, Some(1)]);
+        other.swap_files();
+        assert_eq!(other.get("b"), [Some(3), None]);
+        marks.remove("a");
+        assert_eq!(marks.get("a"), [None, None]);
+        marks.remove("b");
+        assert!(marks.marks.is_empty());
+        // a file compared to itself keeps the address of the first one
+        let mut same = Marks::new([String::from("x"), String::from("x")], MarkMap::new());
+        same.set("a", [Some(1), Some(5)]);
+        assert_eq!(same.get("a"), [Some(1), Some(1)]);
+    }
+}
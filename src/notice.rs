@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// How long a notice is shown at least, after which the next `Action::Tick` hides it
+pub const NOTICE_DURATION: Duration = Duration::from_secs(4);
+
+/// How important a notice is, which decides its color in the bottom line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// what an action did
+    Info,
+    /// an action that could not do anything, like undoing without changes
+    Warning,
+    /// an action that failed, like going to an address that does not exist
+    Error,
+}
+
+/// A one-line message in the bottom line, which is hidden by the next action
+/// or after it was shown for `NOTICE_DURATION`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notice {
+    pub severity: Severity,
+    pub text: String,
+    since: Instant,
+}
+
+impl Notice {
+    pub fn new(severity: Severity, text: impl Into<String>) -> Self {
+        Notice {
+            severity,
+            text: text.into(),
+            since: Instant::now(),
+        }
+    }
+    /// Whether the notice was shown long enough to be hidden at `now`
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.since) >= NOTICE_DURATION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        let notice = Notice::new(Severity::Info, "Copied the SHA-256");
+        assert!(!notice.is_expired(notice.since));
+        assert!(!notice.is_expired(notice.since + NOTICE_DURATION / 2));
+        assert!(notice.is_expired(notice.since + NOTICE_DURATION));
+    }
+}
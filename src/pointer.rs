@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// How the bytes at the cursor are read as an offset into the file when following it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pointer {
+    /// the number of bytes of the offset, which is 1, 2, 4 or 8
+    pub width: usize,
+    pub big_endian: bool,
+    /// added to the offset, for offsets relative to the start of a header or section
+    pub base: usize,
+}
+
+impl Default for Pointer {
+    fn default() -> Self {
+        Pointer {
+            width: 4,
+            big_endian: false,
+            base: 0,
+        }
+    }
+}
+
+impl Pointer {
+    pub const WIDTHS: [usize; 4] = [1, 2, 4, 8];
+
+    /// Reads the offset from the start of the bytes and adds the base to it
+    pub fn target(&self, bytes: &[u8]) -> Result<usize, String> {
+        let bytes = bytes.get(..self.width).ok_or_else(|| {
+            format!(
+                "There are not {} bytes left at the cursor for the offset",
+                self.width
+            )
+        })?;
+        let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+        let offset = match self.big_endian {
+            true => bytes.iter().fold(0, fold),
+            false => bytes.iter().rev().fold(0, fold),
+        };
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(self.base))
+            .ok_or_else(|| format!("The offset {offset:#x} plus the base is too big"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target() {
+        let bytes = [0x10, 0x20, 0x30, 0x40, 0, 0, 0, 0x01];
+        let pointer = |width, big_endian, base| Pointer {
+            width,
+            big_endian,
+            base,
+        };
+        assert_eq!(pointer(1, false, 0).target(&bytes), Ok(0x10));
+        assert_eq!(pointer(2, true, 0).target(&bytes), Ok(0x1020));
+        assert_eq!(pointer(4, false, 0).target(&bytes), Ok(0x40302010));
+        assert_eq!(pointer(4, true, 0x100).target(&bytes), Ok(0x10203140));
+        assert_eq!(
+            pointer(8, false, 0).target(&bytes),
+            Ok(0x0100_0000_4030_2010)
+        );
+        assert!(pointer(8, false, 0).target(&bytes[1..]).is_err());
+        assert!(pointer(8, true, usize::MAX).target(&bytes).is_err());
+    }
+}
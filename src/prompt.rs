@@ -0,0 +1,198 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The most commands kept in the history of the command line
+const MAX_HISTORY: usize = 100;
+
+/// A key pressed while the command line is open
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PromptKey {
+    Char(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    /// the command run before the shown one in the history
+    Up,
+    /// the command run after the shown one in the history
+    Down,
+    /// runs the command, which is delegated like the dialogs
+    Enter,
+    /// closes the command line without running the command
+    Esc,
+}
+
+/// The vi-style command line opened with ':', which is shown in the bottom line
+#[derive(Debug, Default)]
+pub struct Prompt {
+    /// whether the command line is open, shared with the thread reading the keys, which sends
+    /// them as `PromptKey`s instead of looking them up in the keymap while it is
+    pub active: Arc<AtomicBool>,
+    text: Vec<char>,
+    /// the position of the cursor in the text, in characters
+    cursor: usize,
+    /// the commands that were run, the latest last
+    history: Vec<String>,
+    /// the shown command of the history and the text typed before going through it
+    browsing: Option<(usize, Vec<char>)>,
+}
+
+impl Prompt {
+    pub fn is_open(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+    /// Opens the command line with an empty command
+    pub fn open(&mut self) {
+        self.active.store(true, Ordering::Relaxed);
+        self.set_text(Vec::new());
+        self.browsing = None;
+    }
+    fn close(&mut self) {
+        self.active.store(false, Ordering::Relaxed);
+    }
+    fn set_text(&mut self, text: Vec<char>) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+    /// Edits the command with the key, where backspacing over the empty command closes
+    /// the command line like in vim
+    pub fn key(&mut self, key: PromptKey) {
+        match key {
+            PromptKey::Char(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            PromptKey::Backspace if self.text.is_empty() => self.close(),
+            PromptKey::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.text.remove(self.cursor);
+                }
+            }
+            PromptKey::Delete => {
+                if self.cursor < self.text.len() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            PromptKey::Left => self.cursor = self.cursor.saturating_sub(1),
+            PromptKey::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            PromptKey::Home => self.cursor = 0,
+            PromptKey::End => self.cursor = self.text.len(),
+            PromptKey::Up => self.browse(true),
+            PromptKey::Down => self.browse(false),
+            PromptKey::Enter | PromptKey::Esc => self.close(),
+        }
+    }
+    /// Shows the previous or next command of the history, going back to the typed text
+    /// after the latest one
+    fn browse(&mut self, back: bool) {
+        let current = self.browsing.as_ref().map(|(index, _)| *index);
+        let index = match (current, back) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => return,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|&i| i < self.history.len()),
+        };
+        match index {
+            Some(index) => {
+                let typed = match self.browsing.take() {
+                    Some((_, typed)) => typed,
+                    None => self.text.clone(),
+                };
+                self.set_text(self.history[index].chars().collect());
+                self.browsing = Some((index, typed));
+            }
+            None => {
+                if let Some((_, typed)) = self.browsing.take() {
+                    self.set_text(typed);
+                }
+            }
+        }
+    }
+    /// Closes the command line and returns the command, which is added to the history
+    pub fn submit(&mut self) -> String {
+        self.close();
+        self.browsing = None;
+        let command = self.text.iter().collect::<String>();
+        if !command.trim().is_empty() && self.history.last() != Some(&command) {
+            self.history.push(command.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        command
+    }
+    /// The text before the cursor, the character at the cursor (a space at the end)
+    /// and the text after it, with as much of the start left out as needed for the cursor
+    /// to fit into the width along with the ':' in front
+    pub fn parts(&self, width: usize) -> (String, char, String) {
+        let start = (self.cursor + 2).saturating_sub(width).min(self.cursor);
+        let before = self.text[start..self.cursor].iter().collect();
+        let at = self.text.get(self.cursor).copied().unwrap_or(' ');
+        let after = self.text.iter().skip(self.cursor + 1).collect();
+        (before, at, after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for c in text.chars() {
+            prompt.key(PromptKey::Char(c));
+        }
+    }
+
+    #[test]
+    fn editing() {
+        let mut prompt = Prompt::default();
+        prompt.open();
+        type_text(&mut prompt, "goto 400");
+        for key in [PromptKey::Home, PromptKey::Right, PromptKey::Delete] {
+            prompt.key(key);
+        }
+        type_text(&mut prompt, "O");
+        prompt.key(PromptKey::End);
+        prompt.key(PromptKey::Backspace);
+        assert_eq!(prompt.parts(80), ("gOto 40".into(), ' ', String::new()));
+        prompt.key(PromptKey::Left);
+        // the start is left out when the cursor does not fit
+        assert_eq!(prompt.parts(5), ("o 4".into(), '0', String::new()));
+        assert_eq!(prompt.submit(), "gOto 40");
+        assert!(!prompt.is_open());
+        prompt.open();
+        prompt.key(PromptKey::Backspace);
+        assert!(!prompt.is_open());
+    }
+
+    #[test]
+    fn history() {
+        let mut prompt = Prompt::default();
+        for command in ["set ascii_col", "algo global", "algo global", ""] {
+            prompt.open();
+            type_text(&mut prompt, command);
+            prompt.submit();
+        }
+        prompt.open();
+        type_text(&mut prompt, "go");
+        prompt.key(PromptKey::Up);
+        assert_eq!(prompt.parts(80).0, "algo global");
+        // repeated and empty commands are only kept once
+        prompt.key(PromptKey::Up);
+        assert_eq!(prompt.parts(80).0, "set ascii_col");
+        prompt.key(PromptKey::Up);
+        assert_eq!(prompt.parts(80).0, "set ascii_col");
+        prompt.key(PromptKey::Down);
+        prompt.key(PromptKey::Down);
+        assert_eq!(prompt.parts(80).0, "go");
+        prompt.key(PromptKey::Down);
+        assert_eq!(prompt.parts(80).0, "go");
+    }
+}
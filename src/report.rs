@@ -0,0 +1,550 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+pub mod batch;
+pub mod html;
+pub mod patch;
+pub mod unified;
+
+use crate::{
+    align::{AlignAlgorithm, AlignElement},
+    doublehex::DoubleHexLine,
+    file::{FileContent, FileState},
+    style::ByteData,
+    view::AlignedMessage,
+};
+
+/// The number of bytes per row of the hexdumps if the style does not have a fixed one
+pub const DEFAULT_COLUMNS: usize = 16;
+
+/// The output formats of the report mode
+#[derive(Clone, Debug)]
+pub enum Format {
+    /// a list of the differing regions
+    Regions { max_regions: Option<usize> },
+    /// the whole alignment as JSON
+    Json,
+    /// hexdump hunks of the differences, see `unified::write_unified`
+    Unified {
+        context: usize,
+        elide_threshold: usize,
+    },
+    /// a page with the aligned hex views in a file, see `html::write_html`
+    Html {
+        path: PathBuf,
+        fold_threshold: usize,
+    },
+    /// a patch in a file that makes the second file from the first one, see `patch::write_patch`
+    Patch {
+        path: PathBuf,
+        format: patch::PatchFormat,
+    },
+    /// nothing, only the exit status tells whether the files are different
+    Quiet,
+}
+
+/// How the bytes of a region of the alignment correspond to each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    /// the bytes are the same on both sides
+    Same,
+    /// the bytes on both sides are different
+    Change,
+    /// the bytes are only in the second file
+    Insert,
+    /// the bytes are only in the first file
+    Delete,
+}
+
+impl RegionKind {
+    fn of(elem: &AlignElement) -> Self {
+        match (elem.xbyte, elem.ybyte) {
+            (Some(x), Some(y)) if x == y => RegionKind::Same,
+            (Some(_), Some(_)) => RegionKind::Change,
+            (None, _) => RegionKind::Insert,
+            (Some(_), None) => RegionKind::Delete,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            RegionKind::Same => "same",
+            RegionKind::Change => "change",
+            RegionKind::Insert => "insert",
+            RegionKind::Delete => "delete",
+        }
+    }
+}
+
+/// A maximal run of the alignment with the same kind, with the ranges of bytes
+/// it covers in both files. Ranges of sides without bytes are empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub x: Range<usize>,
+    pub y: Range<usize>,
+}
+
+impl Region {
+    fn new(elem: &AlignElement) -> Self {
+        let side = |addr: usize, byte: Option<u8>| addr..addr + byte.is_some() as usize;
+        Region {
+            kind: RegionKind::of(elem),
+            x: side(elem.xaddr, elem.xbyte),
+            y: side(elem.yaddr, elem.ybyte),
+        }
+    }
+    /// Extends the region by the element if it has the same kind
+    fn extend(&mut self, elem: &AlignElement) -> bool {
+        if RegionKind::of(elem) != self.kind {
+            return false;
+        }
+        self.x.end += elem.xbyte.is_some() as usize;
+        self.y.end += elem.ybyte.is_some() as usize;
+        true
+    }
+}
+
+/// Groups consecutive elements of an alignment into regions
+pub fn regions(alignment: impl IntoIterator<Item = AlignElement>) -> impl Iterator<Item = Region> {
+    let mut elems = alignment.into_iter();
+    let mut current: Option<Region> = None;
+    std::iter::from_fn(move || loop {
+        let elem = match elems.next() {
+            Some(elem) => elem,
+            None => return current.take(),
+        };
+        if current
+            .as_mut()
+            .map_or(false, |region| region.extend(&elem))
+        {
+            continue;
+        }
+        if let Some(region) = current.replace(Region::new(&elem)) {
+            return Some(region);
+        }
+    })
+}
+
+/// The alignment of two whole files, yielding the elements as the alignment threads send them.
+/// Bytes that the algorithm leaves out (as local alignment does at the start and end)
+/// are yielded as only being in one of the files.
+/// The addresses are the ones in the whole files, even if only a window of them is compared.
+/// Dropping it stops the alignment threads before their next block.
+pub struct Alignment {
+    x: FileContent,
+    y: FileContent,
+    receiver: Receiver<AlignedMessage>,
+    /// tells the alignment threads to stop
+    cancel: Arc<AtomicBool>,
+    chunk: std::vec::IntoIter<AlignElement>,
+    /// the addresses after the last yielded element
+    end: (usize, usize),
+    done: bool,
+}
+
+impl Alignment {
+    /// Starts aligning the files from their beginning
+    pub fn start(algo: &AlignAlgorithm, x: FileContent, y: FileContent) -> Self {
+        let (sender, receiver) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        algo.start_align_cancellable(x.clone(), y.clone(), (0, 0), sender, cancel.clone());
+        Alignment {
+            x,
+            y,
+            receiver,
+            cancel,
+            chunk: Vec::new().into_iter(),
+            end: (0, 0),
+            done: false,
+        }
+    }
+    /// Whether the alignment has any bytes that differ or are only in one file.
+    /// This stops at the first difference, after which the alignment threads
+    /// do not align any more blocks.
+    pub fn any_difference(mut self) -> bool {
+        self.any(|e| e.xbyte != e.ybyte)
+    }
+    /// The bytes between the end of the previous chunk and the start of the next one
+    fn gap_until(&self, next: (usize, usize)) -> Vec<AlignElement> {
+        unaligned(&self.x, self.end.0..next.0, &self.y, self.end.1..next.1)
+    }
+}
+
+impl Drop for Alignment {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Iterator for Alignment {
+    type Item = AlignElement;
+    fn next(&mut self) -> Option<AlignElement> {
+        loop {
+            if let Some(elem) = self.chunk.next() {
+                self.end = (
+                    elem.xaddr + elem.xbyte.is_some() as usize,
+                    elem.yaddr + elem.ybyte.is_some() as usize,
+                );
+                return Some(AlignElement {
+                    xaddr: elem.xaddr + self.x.base(),
+                    yaddr: elem.yaddr + self.y.base(),
+                    ..elem
+                });
+            }
+            if self.done {
+                return None;
+            }
+            let chunk = match self.receiver.recv() {
+                Ok(AlignedMessage::Append(elems)) => {
+                    let start = elems.first().map_or(self.end, |e| (e.xaddr, e.yaddr));
+                    let mut chunk = self.gap_until(start);
+                    chunk.extend(elems);
+                    chunk
+                }
+                // since the alignment starts at the beginning of the files,
+                // nothing is ever put in front of it
+                Ok(
+                    AlignedMessage::Prepend(_)
+                    | AlignedMessage::UserEvent(_)
+                    | AlignedMessage::Grown(..)
+                    | AlignedMessage::Modified(_)
+                    | AlignedMessage::Fallback(_)
+                    | AlignedMessage::Done,
+                ) => continue,
+                // the alignment threads drop their senders when they are done
+                Err(_) => {
+                    self.done = true;
+                    self.gap_until((self.x.len(), self.y.len()))
+                }
+            };
+            self.chunk = chunk.into_iter();
+        }
+    }
+}
+
+/// Elements for bytes of both files that are not part of an alignment
+fn unaligned(x: &[u8], xrange: Range<usize>, y: &[u8], yrange: Range<usize>) -> Vec<AlignElement> {
+    let (xend, ystart) = (xrange.end, yrange.start);
+    let xonly = xrange.map(|xaddr| AlignElement {
+        xaddr,
+        xbyte: Some(x[xaddr]),
+        yaddr: ystart,
+        ybyte: None,
+    });
+    let yonly = yrange.map(|yaddr| AlignElement {
+        xaddr: xend,
+        xbyte: None,
+        yaddr,
+        ybyte: Some(y[yaddr]),
+    });
+    xonly.chain(yonly).collect()
+}
+
+/// The line of a row of the alignment in the hexdumps, padded to `columns` bytes so that
+/// the ascii column of the last row stays in place
+pub fn row_line(row: &[AlignElement], columns: usize) -> DoubleHexLine {
+    let mut bytes: Vec<_> = row
+        .iter()
+        .map(|e| {
+            let side = |byte: Option<u8>| ByteData {
+                byte,
+                is_gap: byte.is_none(),
+                ..ByteData::default()
+            };
+            (side(e.xbyte), side(e.ybyte))
+        })
+        .collect();
+    bytes.resize(columns, (ByteData::default(), ByteData::default()));
+    let address = row
+        .first()
+        .map(|e| [Some(e.xaddr), Some(e.yaddr)])
+        .unwrap_or_default();
+    DoubleHexLine {
+        address,
+        bytes,
+        fold: None,
+    }
+}
+
+/// Writes the differing regions of the alignment, listing at most `max_regions` of them.
+/// Returns whether the files are different.
+pub fn write_report(
+    out: &mut impl Write,
+    files: [&FileState; 2],
+    alignment: impl IntoIterator<Item = AlignElement>,
+    max_regions: Option<usize>,
+) -> io::Result<bool> {
+    let [x, y] = files;
+    let digits = x.address_digits().max(y.address_digits()) as usize;
+    let side = |range: &Range<usize>| {
+        let bytes = if range.len() == 1 { "byte" } else { "bytes" };
+        format!("0x{:0digits$x} ({} {bytes})", range.start, range.len())
+    };
+    writeln!(out, "--- {}", x.name)?;
+    writeln!(out, "+++ {}", y.name)?;
+    let mut count = 0;
+    for region in regions(alignment).filter(|r| r.kind != RegionKind::Same) {
+        if max_regions.map_or(true, |max| count < max) {
+            writeln!(
+                out,
+                "{:<6} {} -> {}",
+                region.kind.name(),
+                side(&region.x),
+                side(&region.y)
+            )?;
+        }
+        count += 1;
+    }
+    let plural = if count == 1 { "region" } else { "regions" };
+    match max_regions {
+        Some(max) if count > max => writeln!(
+            out,
+            "{count} differing {plural}, {} not listed",
+            count - max
+        )?,
+        _ => writeln!(out, "{count} differing {plural}")?,
+    }
+    out.flush()?;
+    Ok(count > 0)
+}
+
+/// The kind of a region in the JSON output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonKind {
+    Match,
+    Mismatch,
+    XOnly,
+    YOnly,
+}
+
+/// A region in the JSON output, where `length` is the number of bytes
+/// on the sides the region has bytes on
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonRegion {
+    pub kind: JsonKind,
+    pub xstart: usize,
+    pub ystart: usize,
+    pub length: usize,
+}
+
+impl From<Region> for JsonRegion {
+    fn from(region: Region) -> Self {
+        let (kind, length) = match region.kind {
+            RegionKind::Same => (JsonKind::Match, region.x.len()),
+            RegionKind::Change => (JsonKind::Mismatch, region.x.len()),
+            RegionKind::Delete => (JsonKind::XOnly, region.x.len()),
+            RegionKind::Insert => (JsonKind::YOnly, region.y.len()),
+        };
+        JsonRegion {
+            kind,
+            xstart: region.x.start,
+            ystart: region.y.start,
+            length,
+        }
+    }
+}
+
+/// Writes the whole alignment as a JSON document with the file names, the algorithm parameters
+/// and the list of regions. The regions are written one by one as the alignment progresses.
+/// Returns whether the files are different.
+pub fn write_json(
+    out: &mut impl Write,
+    files: [&FileState; 2],
+    algo: &AlignAlgorithm,
+    alignment: impl IntoIterator<Item = AlignElement>,
+) -> io::Result<bool> {
+    write_json_regions(out, files, algo, regions(alignment))
+}
+
+/// Writes the regions of an alignment like `write_json`
+pub fn write_json_regions(
+    out: &mut impl Write,
+    files: [&FileState; 2],
+    algo: &AlignAlgorithm,
+    regions: impl IntoIterator<Item = Region>,
+) -> io::Result<bool> {
+    let [x, y] = files;
+    write!(out, "{{\"files\":")?;
+    serde_json::to_writer(&mut *out, &[&x.name, &y.name])?;
+    write!(out, ",\"algorithm\":")?;
+    serde_json::to_writer(&mut *out, algo)?;
+    write!(out, ",\"regions\":[")?;
+    let mut different = false;
+    for (i, region) in regions.into_iter().enumerate() {
+        different |= region.kind != RegionKind::Same;
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        serde_json::to_writer(&mut *out, &JsonRegion::from(region))?;
+    }
+    writeln!(out, "]}}")?;
+    out.flush()?;
+    Ok(different)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{align::AlignMode, testing::file};
+
+    fn report(x: &FileState, y: &FileState, max: Option<usize>) -> (String, bool) {
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let alignment = Alignment::start(&algo, x.content.clone(), y.content.clone());
+        let mut out = Vec::new();
+        let different = write_report(&mut out, [x, y], alignment, max).unwrap();
+        (String::from_utf8(out).unwrap(), different)
+    }
+
+    #[test]
+    fn region_kinds() {
+        let x = file("old", b"the quick brown fox jumps over the lazy dog");
+        let y = file("new", b"the quick red fox jumps over the very lazy dog");
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let alignment = Alignment::start(&algo, x.content.clone(), y.content.clone());
+        let found: Vec<_> = regions(alignment)
+            .filter(|r| r.kind != RegionKind::Same)
+            .collect();
+        let covered = |side: fn(&Region) -> &Range<usize>| {
+            regions(Alignment::start(
+                &algo,
+                x.content.clone(),
+                y.content.clone(),
+            ))
+            .map(|r| side(&r).len())
+            .sum::<usize>()
+        };
+        // every byte of both files is part of exactly one region
+        assert_eq!(covered(|r| &r.x), x.content.len());
+        assert_eq!(covered(|r| &r.y), y.content.len());
+        let inserted = found.last().unwrap();
+        assert_eq!(inserted.kind, RegionKind::Insert);
+        assert_eq!((inserted.x.len(), inserted.y.len()), (0, 5));
+    }
+
+    #[test]
+    fn any_difference() {
+        let different = |x: &[u8], y: &[u8], mode| {
+            let algo = AlignAlgorithm {
+                mode,
+                ..AlignAlgorithm::default()
+            };
+            Alignment::start(
+                &algo,
+                Arc::new(x.to_vec().into()),
+                Arc::new(y.to_vec().into()),
+            )
+            .any_difference()
+        };
+        for mode in [
+            AlignMode::Global,
+            AlignMode::Local,
+            AlignMode::Blockwise(64),
+        ] {
+            assert!(!different(b"identical", b"identical", mode));
+            assert!(!different(b"", b"", mode));
+            // pure insertions count as differences
+            assert!(different(b"prefix", b"prefix and more", mode));
+            assert!(different(b"", b"a", mode));
+            assert!(different(b"same length", b"same_length", mode));
+        }
+        // a difference at the start stops the alignment of a large file early
+        let mut x = vec![0u8; 1 << 20];
+        let y = x.clone();
+        x[0] = 1;
+        assert!(different(&x, &y, AlignMode::Blockwise(64)));
+    }
+
+    #[test]
+    fn identical_report() {
+        let x = file("a", b"same bytes");
+        let (text, different) = report(&x, &x, None);
+        assert!(!different);
+        assert_eq!(text, "--- a\n+++ a\n0 differing regions\n");
+    }
+
+    #[test]
+    fn limited_report() {
+        let x = file("a", b"0123456789abcdef");
+        let y = file("b", b"0x23456y89abcdef!");
+        let (text, different) = report(&x, &y, Some(2));
+        assert!(different);
+        assert_eq!(
+            text,
+            "--- a\n\
+             +++ b\n\
+             change 0x01 (1 byte) -> 0x01 (1 byte)\n\
+             change 0x07 (1 byte) -> 0x07 (1 byte)\n\
+             3 differing regions, 1 not listed\n"
+        );
+        let (text, _) = report(&x, &y, None);
+        assert!(text.ends_with("insert 0x10 (0 bytes) -> 0x10 (1 byte)\n3 differing regions\n"));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let x = file(
+            "old",
+            b"\x00\x01\x02\x03 header \x10\x11 body of the file \xff",
+        );
+        let y = file(
+            "new",
+            b"\x00\x01\x02\x04 header body of a file, extended \xff\xfe",
+        );
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let alignment: Vec<_> =
+            Alignment::start(&algo, x.content.clone(), y.content.clone()).collect();
+        let mut out = Vec::new();
+        assert!(write_json(&mut out, [&x, &y], &algo, alignment.clone()).unwrap());
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["files"], serde_json::json!(["old", "new"]));
+        assert_eq!(json["algorithm"]["gap_open"], -5);
+        assert_eq!(json["algorithm"]["aligner"], "rust-bio");
+        let parsed: Vec<JsonRegion> = serde_json::from_value(json["regions"].clone()).unwrap();
+        // expand the regions back into pairs of addresses and compare with the alignment
+        let mut pairs = Vec::new();
+        for region in parsed {
+            for i in 0..region.length {
+                let (xaddr, yaddr) = (region.xstart + i, region.ystart + i);
+                pairs.push(match region.kind {
+                    JsonKind::Match => {
+                        assert_eq!(x.content[xaddr], y.content[yaddr]);
+                        (Some(xaddr), Some(yaddr))
+                    }
+                    JsonKind::Mismatch => {
+                        assert_ne!(x.content[xaddr], y.content[yaddr]);
+                        (Some(xaddr), Some(yaddr))
+                    }
+                    JsonKind::XOnly => (Some(xaddr), None),
+                    JsonKind::YOnly => (None, Some(yaddr)),
+                });
+            }
+        }
+        let expected: Vec<_> = alignment
+            .iter()
+            .map(|e| (e.xbyte.map(|_| e.xaddr), e.ybyte.map(|_| e.yaddr)))
+            .collect();
+        assert_eq!(pairs, expected);
+    }
+}
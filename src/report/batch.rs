@@ -0,0 +1,344 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+};
+
+use crossbeam_utils::thread::scope;
+
+use super::{regions, write_json_regions, Alignment, Region, RegionKind};
+use crate::{
+    align::AlignAlgorithm,
+    dirlist::relative_paths,
+    file::{Source, Unit, Window},
+};
+
+/// Two files to compare in a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pair {
+    pub files: [PathBuf; 2],
+    /// the name of the JSON file of the pair without its extension, which is the relative
+    /// path of files in directories and the number of the pair in a manifest
+    pub name: String,
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> Result<PathBuf, String> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> Result<PathBuf, String> {
+    std::str::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|_| String::from("The paths of the manifest are not UTF-8"))
+}
+
+/// The fields of a line of CSV, which can be quoted with double quotes (doubled inside them),
+/// or None if a quote is not closed
+fn csv_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there is always a field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    (!quoted).then_some(fields)
+}
+
+/// The pairs of a manifest, which has two paths separated by a comma on each line (as CSV),
+/// or all paths separated by NUL bytes if it has any (like the output of `find -print0`)
+pub fn parse_manifest(manifest: &[u8]) -> Result<Vec<Pair>, String> {
+    let name = |i: usize| (i + 1).to_string();
+    if manifest.contains(&0) {
+        let manifest = manifest.strip_suffix(&[0]).unwrap_or(manifest);
+        let paths = manifest
+            .split(|&byte| byte == 0)
+            .map(path_from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        if paths.len() % 2 != 0 {
+            return Err(String::from(
+                "The last path of the manifest has no other one",
+            ));
+        }
+        let pairs = paths.chunks(2).enumerate().map(|(i, files)| Pair {
+            files: [files[0].clone(), files[1].clone()],
+            name: name(i),
+        });
+        return Ok(pairs.collect());
+    }
+    let text = std::str::from_utf8(manifest)
+        .map_err(|_| "The manifest is not UTF-8, separate its paths with NUL bytes instead")?;
+    let mut pairs = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match csv_fields(line).as_deref() {
+            Some([first, second]) => pairs.push(Pair {
+                files: [PathBuf::from(first), PathBuf::from(second)],
+                name: name(pairs.len()),
+            }),
+            _ => {
+                return Err(format!(
+                    "Line {} of the manifest is not two paths separated by a comma",
+                    number + 1
+                ))
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// The pairs of the files with the same relative paths in both directories, including the ones
+/// that are only in one of them, which can not be compared
+pub fn dir_pairs(dirs: &[PathBuf; 2]) -> io::Result<Vec<Pair>> {
+    let [first, second] = relative_paths(dirs)?;
+    let pairs = first.union(&second).map(|path| Pair {
+        files: [dirs[0].join(path), dirs[1].join(path)],
+        name: path.to_string_lossy().to_string(),
+    });
+    Ok(pairs.collect())
+}
+
+/// How much two files differ
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// the number of regions that are not the same on both sides
+    pub regions: usize,
+    /// the number of bytes in these regions, on the side with more of them
+    pub bytes: usize,
+}
+
+impl Summary {
+    fn add(&mut self, region: &Region) {
+        if region.kind != RegionKind::Same {
+            self.regions += 1;
+            self.bytes += region.x.len().max(region.y.len());
+        }
+    }
+}
+
+/// What comparing a pair came to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Compared(Summary),
+    /// the error of a file that could not be read or a JSON file that could not be written
+    Failed(String),
+}
+
+impl Outcome {
+    fn describe(&self) -> String {
+        let plural = |count: usize, what: &str| match count {
+            1 => format!("1 {what}"),
+            _ => format!("{count} {what}s"),
+        };
+        match self {
+            Outcome::Compared(Summary { regions: 0, .. }) => String::from("identical"),
+            Outcome::Compared(summary) => format!(
+                "{}, {} changed",
+                plural(summary.regions, "differing region"),
+                plural(summary.bytes, "byte")
+            ),
+            Outcome::Failed(e) => format!("error: {e}"),
+        }
+    }
+}
+
+/// The settings of all comparisons of a batch
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    pub algo: AlignAlgorithm,
+    pub decompress: bool,
+    pub windows: [Window; 2],
+    /// the directory the alignment of each pair is written to as JSON, from `--json-dir`
+    pub json_dir: Option<PathBuf>,
+    /// how many pairs are compared at the same time, from `--jobs`
+    pub jobs: usize,
+}
+
+/// The numbers of pairs with each outcome
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub identical: usize,
+    pub different: usize,
+    pub failed: usize,
+}
+
+fn compare(pair: &Pair, options: &BatchOptions) -> Outcome {
+    let mut files = Vec::new();
+    for (path, window) in pair.files.iter().zip(options.windows) {
+        let source = Source {
+            path: path.clone().into_os_string(),
+            label: None,
+            decompress: options.decompress,
+            window,
+            unit: Unit::Byte,
+        };
+        // if decompressing fails, the raw bytes are compared like in the other modes
+        match source.read() {
+            Ok((file, _)) => files.push(file),
+            Err(e) => return Outcome::Failed(format!("Could not read {}: {e}", path.display())),
+        }
+    }
+    let (x, y) = (&files[0], &files[1]);
+    let alignment = Alignment::start(&options.algo, x.content.clone(), y.content.clone());
+    let mut summary = Summary::default();
+    let regions = regions(alignment).inspect(|region| summary.add(region));
+    if let Some(dir) = &options.json_dir {
+        let path = dir.join(format!("{}.json", pair.name));
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| File::create(&path))
+            .and_then(|file| {
+                let mut out = BufWriter::new(file);
+                write_json_regions(&mut out, [x, y], &options.algo, regions)
+            });
+        if let Err(e) = written {
+            return Outcome::Failed(format!("Could not write {}: {e}", path.display()));
+        }
+    } else {
+        regions.for_each(drop);
+    }
+    Outcome::Compared(summary)
+}
+
+/// Compares the pairs with `options.jobs` of them at the same time, and writes a line with the
+/// paths and the outcome of each pair in their order.
+/// Pairs that can not be compared do not stop the others.
+pub fn run(pairs: &[Pair], options: &BatchOptions, out: &mut impl Write) -> io::Result<Counts> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = channel();
+    scope(|s| {
+        for _ in 0..options.jobs.max(1).min(pairs.len()) {
+            let sender = sender.clone();
+            let next = &next;
+            s.spawn(move |_| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let pair = match pairs.get(index) {
+                    Some(pair) => pair,
+                    None => return,
+                };
+                // the writer stopped after an error
+                if sender.send((index, compare(pair, options))).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+        let mut counts = Counts::default();
+        // the outcomes arrive in any order, and each is written once all before it are
+        let mut pending = BTreeMap::new();
+        let mut written = 0;
+        for (index, outcome) in receiver {
+            pending.insert(index, outcome);
+            while let Some(outcome) = pending.remove(&written) {
+                match &outcome {
+                    Outcome::Compared(Summary { regions: 0, .. }) => counts.identical += 1,
+                    Outcome::Compared(_) => counts.different += 1,
+                    Outcome::Failed(_) => counts.failed += 1,
+                }
+                let [first, second] = &pairs[written].files;
+                let (first, second) = (first.display(), second.display());
+                writeln!(out, "{first}\t{second}\t{}", outcome.describe())?;
+                written += 1;
+            }
+        }
+        out.flush()?;
+        Ok(counts)
+    })
+    .expect("a comparison of the batch panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{align::AlignMode, testing::temp_path};
+
+    #[test]
+    fn manifests() {
+        let pairs = parse_manifest(b"a,b\n\n\"c,d\",\"e \"\"f\"\"\"\r\n").unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(
+            pairs[1].files,
+            [PathBuf::from("c,d"), PathBuf::from("e \"f\"")]
+        );
+        assert_eq!(pairs[1].name, "2");
+        let pairs = parse_manifest(b"a,b\0c\0").unwrap();
+        assert_eq!(pairs[0].files, [PathBuf::from("a,b"), PathBuf::from("c")]);
+        assert_eq!(pairs.len(), 1);
+        assert!(parse_manifest(b"a\0b\0c").is_err());
+        assert!(parse_manifest(b"a,b\na\n").is_err());
+        assert!(parse_manifest(b"\"a,b\n").is_err());
+    }
+
+    #[test]
+    fn batch() {
+        let dir = temp_path("batch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, content: &[u8]| {
+            std::fs::write(dir.join(name), content).unwrap();
+            dir.join(name)
+        };
+        let pairs = [
+            [file("a1", b"same bytes"), file("b1", b"same bytes")],
+            [file("a2", b"some bytes"), file("b2", b"some other bytes")],
+            [file("a3", b"bytes"), dir.join("missing")],
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, files)| Pair {
+            files,
+            name: (i + 1).to_string(),
+        })
+        .collect::<Vec<_>>();
+        let options = BatchOptions {
+            algo: AlignAlgorithm {
+                mode: AlignMode::Global,
+                ..AlignAlgorithm::default()
+            },
+            decompress: false,
+            windows: [Window::default(); 2],
+            json_dir: Some(dir.join("json")),
+            jobs: 2,
+        };
+        let mut out = Vec::new();
+        let counts = run(&pairs, &options, &mut out).unwrap();
+        let expected = Counts {
+            identical: 1,
+            different: 1,
+            failed: 1,
+        };
+        assert_eq!(counts, expected);
+        let out = String::from_utf8(out).unwrap();
+        let outcomes = out
+            .lines()
+            .map(|line| line.rsplit('\t').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(outcomes[0], "identical");
+        assert_eq!(outcomes[1], "1 differing region, 6 bytes changed");
+        assert!(outcomes[2].starts_with("error: Could not read"));
+        // the JSON files have the numbers of the pairs, and are only written for compared ones
+        let json = std::fs::read_to_string(dir.join("json").join("2.json")).unwrap();
+        assert!(json.contains("\"yonly\""));
+        assert!(!dir.join("json").join("3.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
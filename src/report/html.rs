@@ -0,0 +1,324 @@
+use std::io::{self, Write};
+
+use super::{row_line, DEFAULT_COLUMNS};
+use crate::{
+    align::AlignElement,
+    backend::{Backend, BackgroundColor, Color, Effect},
+    doublehex::DoubleHexLine,
+    file::FileState,
+    style::Style,
+    theme::Palette,
+};
+
+/// Runs of identical rows longer than this are collapsed if no other value is given
+pub const DEFAULT_FOLD_THRESHOLD: usize = 8;
+
+/// Settings for the HTML page
+#[derive(Clone, Copy, Debug)]
+pub struct HtmlOptions {
+    pub style: Style,
+    /// runs of more rows without differences than this are collapsed
+    pub fold_threshold: usize,
+}
+
+/// Replaces the characters that have a meaning in HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A backend that writes the lines as rows of a table, with a cell for each half.
+/// Like `Cross`, errors are kept until they are returned by `refresh`.
+struct Html<W: Write> {
+    out: W,
+    palette: Palette,
+    /// the colors of the page outside of the spans
+    page: [String; 2],
+    /// the style of the open span, whose text is continued while the style stays the same
+    span: Option<String>,
+    cell_open: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Html<W> {
+    fn new(out: W, palette: Palette) -> Self {
+        // the default colors of the terminal are the ones of the dark theme
+        let page = [
+            palette
+                .same
+                .css()
+                .unwrap_or_else(|| String::from("#e5e5e5")),
+            palette
+                .background
+                .css()
+                .unwrap_or_else(|| String::from("#000000")),
+        ];
+        Html {
+            out,
+            palette,
+            page,
+            span: None,
+            cell_open: false,
+            error: None,
+        }
+    }
+    fn write(&mut self, text: &str) {
+        if self.error.is_none() {
+            if let Err(e) = self.out.write_all(text.as_bytes()) {
+                self.error = Some(e);
+            }
+        }
+    }
+    fn close_cell(&mut self) {
+        if self.span.take().is_some() {
+            self.write("</span>");
+        }
+        if std::mem::take(&mut self.cell_open) {
+            self.write("</td>");
+        }
+    }
+    /// Writes the line as a row with both of its halves
+    fn row(&mut self, line: &DoubleHexLine, style: Style) -> io::Result<()> {
+        self.write("<tr>");
+        line.print_halves(self, style);
+        self.close_cell();
+        self.write("</tr>\n");
+        self.refresh()
+    }
+    /// The inline style of text in the colors, which are swapped if it is inverted
+    fn css(&self, color: Color, bg: BackgroundColor, effect: Effect) -> String {
+        let fg = color.theme_color(&self.palette).css();
+        let bg = match bg {
+            BackgroundColor::Blank => None,
+            bg => bg.theme_color(&self.palette).css(),
+        };
+        let (fg, bg) = match effect.inverted {
+            true => (
+                Some(bg.unwrap_or_else(|| self.page[1].clone())),
+                Some(fg.unwrap_or_else(|| self.page[0].clone())),
+            ),
+            false => (fg, bg),
+        };
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            css += &format!("color:{fg};");
+        }
+        if let Some(bg) = bg {
+            css += &format!("background:{bg};");
+        }
+        if effect.bold {
+            css += "font-weight:bold;";
+        }
+        css
+    }
+}
+
+impl<W: Write> Backend for Html<W> {
+    /// Starts the cell of the next half
+    fn set_line(&mut self, _: usize) {
+        self.close_cell();
+        self.write("<td>");
+        self.cell_open = true;
+    }
+
+    fn set_pos(&mut self, _: usize, line: usize) {
+        self.set_line(line)
+    }
+
+    fn append_text(&mut self, text: &str, color: Color, bg: BackgroundColor, effect: Effect) {
+        let css = self.css(color, bg, effect);
+        if self.span.as_ref() != Some(&css) {
+            if self.span.is_some() {
+                self.write("</span>");
+            }
+            self.write(&format!("<span style=\"{css}\">"));
+            self.span = Some(css);
+        }
+        self.write(&escape(text));
+    }
+
+    fn can_scroll(&self) -> bool {
+        false
+    }
+
+    fn scroll(&mut self, _: isize) {}
+
+    fn refresh(&mut self) -> io::Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    fn size(&mut self) -> (usize, usize) {
+        (usize::MAX, 2)
+    }
+
+    fn clear(&mut self) {}
+
+    fn set_title(&mut self, _: &str) {}
+}
+
+/// Writes the lines, collapsing long runs of identical ones into `<details>` elements
+struct PageWriter<W: Write> {
+    html: Html<W>,
+    options: HtmlOptions,
+    /// the identical lines since the last different one, which are only kept until
+    /// there are too many of them
+    run: Vec<DoubleHexLine>,
+    /// whether the current run of identical lines is collapsed
+    collapsed: bool,
+}
+
+impl<W: Write> PageWriter<W> {
+    fn line(&mut self, line: DoubleHexLine) -> io::Result<()> {
+        let style = self.options.style;
+        if line.bytes.iter().any(|(a, b)| a.differs(*b)) {
+            self.end_run()?;
+            return self.html.row(&line, style);
+        }
+        if self.collapsed {
+            return self.html.row(&line, style);
+        }
+        self.run.push(line);
+        if self.run.len() <= self.options.fold_threshold {
+            return Ok(());
+        }
+        let address =
+            |address: Option<usize>| address.map_or(String::from("-"), |a| format!("{a:#x}"));
+        let [x, y] = self.run[0].address.map(address);
+        self.html.write(&format!(
+            "</table>\n<details><summary>\u{2261} identical bytes from {x} and {y}</summary><table>\n"
+        ));
+        for line in std::mem::take(&mut self.run) {
+            self.html.row(&line, style)?;
+        }
+        self.collapsed = true;
+        Ok(())
+    }
+    fn end_run(&mut self) -> io::Result<()> {
+        if std::mem::take(&mut self.collapsed) {
+            self.html.write("</table></details>\n<table>\n");
+        }
+        for line in std::mem::take(&mut self.run) {
+            self.html.row(&line, self.options.style)?;
+        }
+        self.html.refresh()
+    }
+}
+
+/// Writes the lines as a page with a table of both halves in the colors of the theme.
+/// The lines are written as they come, so the whole page is never kept in memory.
+/// Returns whether any line has differences.
+pub fn write_html_lines(
+    out: &mut impl Write,
+    names: [&str; 2],
+    lines: impl IntoIterator<Item = DoubleHexLine>,
+    options: HtmlOptions,
+) -> io::Result<bool> {
+    let mut style = options.style;
+    // differences are shown in their colors, not marked like without colors,
+    // and the page always has the ascii column
+    style.monochrome = false;
+    style.ascii_col = true;
+    let mut html = Html::new(&mut *out, style.theme.palette());
+    let [fg, bg] = html.page.clone();
+    let unimportant = Color::Unimportant.theme_color(&html.palette).css();
+    let [first, second] = names.map(escape);
+    html.write(&format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{first} \u{2194} {second}</title>\n<style>\n\
+         body {{ color: {fg}; background: {bg}; font-family: monospace; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         td {{ white-space: pre; padding: 0 1ch 0 0; }}\n\
+         summary {{ cursor: pointer; color: {}; }}\n\
+         </style>\n</head>\n<body>\n<p>--- {first}<br>+++ {second}</p>\n<table>\n",
+        unimportant.as_deref().unwrap_or(fg.as_str())
+    ));
+    let mut writer = PageWriter {
+        html,
+        options: HtmlOptions { style, ..options },
+        run: Vec::new(),
+        collapsed: false,
+    };
+    let mut different = false;
+    for line in lines {
+        different |= line.bytes.iter().any(|(a, b)| a.differs(*b));
+        writer.line(line)?;
+    }
+    writer.end_run()?;
+    writer.html.write("</table>\n</body>\n</html>\n");
+    writer.html.refresh()?;
+    writer.html.out.flush()?;
+    Ok(different)
+}
+
+/// Writes the alignment as an HTML page like `write_html_lines`, with the bytes per row of
+/// the style, and returns whether the files are different
+pub fn write_html(
+    out: &mut impl Write,
+    files: [&FileState; 2],
+    alignment: impl IntoIterator<Item = AlignElement>,
+    options: HtmlOptions,
+) -> io::Result<bool> {
+    let [x, y] = files;
+    let mut style = options.style;
+    style.addr_width = x.address_digits().max(y.address_digits());
+    let columns = style
+        .column_count
+        .fixed()
+        .map_or(DEFAULT_COLUMNS, |n| n as usize);
+    let mut alignment = alignment.into_iter().peekable();
+    let lines = std::iter::from_fn(|| {
+        alignment.peek()?;
+        let row = alignment.by_ref().take(columns).collect::<Vec<_>>();
+        Some(row_line(&row, columns))
+    });
+    let options = HtmlOptions { style, ..options };
+    write_html_lines(out, [&x.name, &y.name], lines, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        align::{AlignAlgorithm, AlignMode},
+        report::Alignment,
+        testing::file,
+    };
+
+    #[test]
+    fn html() {
+        let mut old = vec![0u8; 0x200];
+        old.extend(b"<old>");
+        let mut new = vec![0u8; 0x200];
+        new.extend(b"<new>");
+        let (x, y) = (file("a&b", &old), file("c", &new));
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let alignment = Alignment::start(&algo, x.content.clone(), y.content.clone());
+        let options = HtmlOptions {
+            style: Style::default(),
+            fold_threshold: DEFAULT_FOLD_THRESHOLD,
+        };
+        let mut out = Vec::new();
+        assert!(write_html(&mut out, [&x, &y], alignment, options).unwrap());
+        let page = String::from_utf8(out).unwrap();
+        assert!(page.contains("<title>a&amp;b \u{2194} c</title>"));
+        // the zeros are collapsed, and the text is escaped
+        assert_eq!(page.matches("<details>").count(), 1);
+        assert!(page.contains("&lt;") && !page.contains("<old"));
+        let diff = Palette::dark().diff.css().unwrap();
+        assert!(page.contains(&format!("color:{diff};")));
+        assert_eq!(page.matches("<tr>").count(), page.matches("</tr>").count());
+        assert!(page.ends_with("</html>\n"));
+    }
+}
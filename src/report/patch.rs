@@ -0,0 +1,412 @@
+use std::io::{self, Write};
+
+use crate::align::AlignElement;
+
+/// The first bytes of a patch in the simple format
+const SIMPLE_MAGIC: &[u8; 8] = b"BDPATCH1";
+/// The first bytes of a VCDIFF patch without secondary compression or custom code table
+const VCDIFF_MAGIC: &[u8; 5] = b"\xd6\xc3\xc4\x00\x00";
+/// The window of a VCDIFF patch copies from the source file
+const VCD_SOURCE: u8 = 0x01;
+/// The indexes of `ADD` and `COPY` (in mode `VCD_SELF`) in the default code table of VCDIFF,
+/// both with their size following the instruction
+const VCD_ADD: u8 = 1;
+const VCD_COPY: u8 = 19;
+
+/// The formats of the patches written by `--emit-patch`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// the format of biodiff, see `write_simple`
+    #[default]
+    Simple,
+    /// RFC 3284, which other tools like xdelta3 can apply
+    Vcdiff,
+}
+
+/// How a part of the second file is made
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// the bytes at the offset of the first file
+    Copy { offset: usize, len: usize },
+    /// bytes that are not in the first file
+    Add(Vec<u8>),
+}
+
+impl Instruction {
+    /// The number of bytes of the second file that the instruction makes
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::Copy { len, .. } => *len,
+            Instruction::Add(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// The instructions that make the second file of the alignment from the first one:
+/// bytes that are the same on both sides are copied, the ones of the second file that are
+/// changed or inserted are added, and the deleted ones are left out
+pub fn instructions(alignment: impl IntoIterator<Item = AlignElement>) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    for elem in alignment {
+        let y = match elem.ybyte {
+            Some(y) => y,
+            None => continue,
+        };
+        let same = elem.xbyte == Some(y);
+        // the bytes continue the last instruction if they are next to its ones
+        match instructions.last_mut() {
+            Some(Instruction::Copy { offset, len }) if same && *offset + *len == elem.xaddr => {
+                *len += 1;
+                continue;
+            }
+            Some(Instruction::Add(bytes)) if !same => {
+                bytes.push(y);
+                continue;
+            }
+            _ => {}
+        }
+        instructions.push(match same {
+            true => Instruction::Copy {
+                offset: elem.xaddr,
+                len: 1,
+            },
+            false => Instruction::Add(vec![y]),
+        });
+    }
+    instructions
+}
+
+/// Writes the instructions in the simple format, which is
+/// - the magic bytes `BDPATCH1`
+/// - the length of the second file as 64 bit little endian number
+/// - the instructions until the end of the patch, which are either `C` followed by the offset
+///   and the length of the copied bytes, or `A` followed by the length and the added bytes,
+///   with all numbers as 64 bit little endian
+pub fn write_simple(out: &mut impl Write, instructions: &[Instruction]) -> io::Result<()> {
+    let number = |n: usize| (n as u64).to_le_bytes();
+    let target_len: usize = instructions.iter().map(Instruction::size).sum();
+    out.write_all(SIMPLE_MAGIC)?;
+    out.write_all(&number(target_len))?;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Copy { offset, len } => {
+                out.write_all(b"C")?;
+                out.write_all(&number(*offset))?;
+                out.write_all(&number(*len))?;
+            }
+            Instruction::Add(bytes) => {
+                out.write_all(b"A")?;
+                out.write_all(&number(bytes.len()))?;
+                out.write_all(bytes)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Appends the number in the variable length integer format of VCDIFF,
+/// 7 bits per byte with the most significant first and the top bit set on all but the last
+fn push_varint(out: &mut Vec<u8>, mut n: usize) {
+    let mut bytes = vec![(n & 0x7f) as u8];
+    n >>= 7;
+    while n > 0 {
+        bytes.push((n & 0x7f) as u8 | 0x80);
+        n >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Writes the instructions as VCDIFF with a single window, whose source segment is the start
+/// of the first file until the last copied byte
+pub fn write_vcdiff(out: &mut impl Write, instructions: &[Instruction]) -> io::Result<()> {
+    out.write_all(VCDIFF_MAGIC)?;
+    if instructions.is_empty() {
+        return Ok(());
+    }
+    let (mut data, mut inst, mut addr) = (Vec::new(), Vec::new(), Vec::new());
+    let mut source_len = 0;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Copy { offset, len } => {
+                inst.push(VCD_COPY);
+                push_varint(&mut inst, *len);
+                // in mode VCD_SELF, addresses in the source segment are its offsets
+                push_varint(&mut addr, *offset);
+                source_len = source_len.max(offset + len);
+            }
+            Instruction::Add(bytes) => {
+                inst.push(VCD_ADD);
+                push_varint(&mut inst, bytes.len());
+                data.extend(bytes);
+            }
+        }
+    }
+    let target_len: usize = instructions.iter().map(Instruction::size).sum();
+    let mut delta = Vec::new();
+    push_varint(&mut delta, target_len);
+    // no section is compressed
+    delta.push(0);
+    for section in [&data, &inst, &addr] {
+        push_varint(&mut delta, section.len());
+    }
+    let mut window = Vec::new();
+    if source_len > 0 {
+        window.push(VCD_SOURCE);
+        push_varint(&mut window, source_len);
+        push_varint(&mut window, 0);
+    } else {
+        window.push(0);
+    }
+    push_varint(
+        &mut window,
+        delta.len() + data.len() + inst.len() + addr.len(),
+    );
+    out.write_all(&window)?;
+    out.write_all(&delta)?;
+    for section in [&data, &inst, &addr] {
+        out.write_all(section)?;
+    }
+    Ok(())
+}
+
+/// Writes the patch that makes the second file of the alignment from the first one,
+/// and returns whether the files are different
+pub fn write_patch(
+    out: &mut impl Write,
+    alignment: impl IntoIterator<Item = AlignElement>,
+    format: PatchFormat,
+) -> io::Result<bool> {
+    let mut different = false;
+    let alignment = alignment
+        .into_iter()
+        .inspect(|e| different |= e.xbyte != e.ybyte);
+    let instructions = instructions(alignment);
+    match format {
+        PatchFormat::Simple => write_simple(out, &instructions)?,
+        PatchFormat::Vcdiff => write_vcdiff(out, &instructions)?,
+    }
+    out.flush()?;
+    Ok(different)
+}
+
+/// Reads the parts of a patch, telling where it ends too early
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err(String::from("The patch ends too early"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    /// A 64 bit little endian number of the simple format
+    fn number(&mut self) -> Result<usize, String> {
+        let bytes: [u8; 8] = self.take(8)?.try_into().expect("eight bytes were taken");
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|e| e.to_string())
+    }
+    /// A variable length integer of VCDIFF, see `push_varint`
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut n: usize = 0;
+        loop {
+            let byte = self.byte()?;
+            n = n
+                .checked_mul(128)
+                .ok_or("A number of the patch is too large")?
+                | (byte & 0x7f) as usize;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+/// The bytes of the source in the range, or an error if it is too short for the patch
+fn source_range(source: &[u8], offset: usize, len: usize) -> Result<&[u8], String> {
+    offset
+        .checked_add(len)
+        .and_then(|end| source.get(offset..end))
+        .ok_or_else(|| String::from("The patch copies bytes after the end of the source file"))
+}
+
+fn apply_simple(mut patch: Reader, source: &[u8]) -> Result<Vec<u8>, String> {
+    let target_len = patch.number()?;
+    let mut target = Vec::new();
+    while !patch.bytes.is_empty() {
+        match patch.byte()? {
+            b'C' => {
+                let (offset, len) = (patch.number()?, patch.number()?);
+                target.extend_from_slice(source_range(source, offset, len)?);
+            }
+            b'A' => {
+                let len = patch.number()?;
+                target.extend_from_slice(patch.take(len)?);
+            }
+            other => return Err(format!("Unknown instruction {other:#04x} in the patch")),
+        }
+    }
+    if target.len() != target_len {
+        return Err(String::from("The patch is incomplete"));
+    }
+    Ok(target)
+}
+
+/// Applies the windows of a VCDIFF patch that only uses the instructions of `write_vcdiff`
+fn apply_vcdiff(mut patch: Reader, source: &[u8]) -> Result<Vec<u8>, String> {
+    let mut target = Vec::new();
+    while !patch.bytes.is_empty() {
+        let segment = match patch.byte()? {
+            0 => &[][..],
+            VCD_SOURCE => {
+                let (len, offset) = (patch.varint()?, patch.varint()?);
+                source_range(source, offset, len)?
+            }
+            _ => {
+                return Err(String::from(
+                    "Only windows of the source file are supported",
+                ))
+            }
+        };
+        let delta_len = patch.varint()?;
+        let mut delta = Reader {
+            bytes: patch.take(delta_len)?,
+        };
+        let window_len = delta.varint()?;
+        if delta.byte()? != 0 {
+            return Err(String::from("Compressed sections are not supported"));
+        }
+        let (data_len, inst_len, addr_len) = (delta.varint()?, delta.varint()?, delta.varint()?);
+        let mut data = Reader {
+            bytes: delta.take(data_len)?,
+        };
+        let mut inst = Reader {
+            bytes: delta.take(inst_len)?,
+        };
+        let mut addr = Reader {
+            bytes: delta.take(addr_len)?,
+        };
+        let start = target.len();
+        while !inst.bytes.is_empty() {
+            match inst.byte()? {
+                VCD_ADD => {
+                    let len = inst.varint()?;
+                    target.extend_from_slice(data.take(len)?)
+                }
+                VCD_COPY => {
+                    let (len, offset) = (inst.varint()?, addr.varint()?);
+                    // addresses after the source segment are in the window before them,
+                    // which the copy can overlap
+                    for i in offset..offset.checked_add(len).ok_or("Too long copy")? {
+                        let byte = match segment.get(i) {
+                            Some(&byte) => byte,
+                            None => *target
+                                .get(start + i - segment.len())
+                                .ok_or("The patch copies bytes that are not made yet")?,
+                        };
+                        target.push(byte);
+                    }
+                }
+                code => {
+                    return Err(format!(
+                        "Instruction {code} of the default code table is not supported"
+                    ))
+                }
+            }
+        }
+        if target.len() - start != window_len {
+            return Err(String::from("The patch is incomplete"));
+        }
+    }
+    Ok(target)
+}
+
+/// Makes the second file from the source (the first file) and a patch in either format
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, String> {
+    if let Some(rest) = patch.strip_prefix(SIMPLE_MAGIC) {
+        apply_simple(Reader { bytes: rest }, source)
+    } else if let Some(rest) = patch.strip_prefix(VCDIFF_MAGIC) {
+        apply_vcdiff(Reader { bytes: rest }, source)
+    } else {
+        Err(String::from(
+            "This is not a patch of biodiff or a VCDIFF patch without compression",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        align::{AlignAlgorithm, AlignMode},
+        report::Alignment,
+    };
+
+    fn round_trip(x: &[u8], y: &[u8]) {
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        for format in [PatchFormat::Simple, PatchFormat::Vcdiff] {
+            let alignment = Alignment::start(
+                &algo,
+                Arc::new(x.to_vec().into()),
+                Arc::new(y.to_vec().into()),
+            );
+            let mut patch = Vec::new();
+            let different = write_patch(&mut patch, alignment, format).unwrap();
+            assert_eq!(different, x != y);
+            assert_eq!(apply(&patch, x).unwrap(), y, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn patches() {
+        let x = b"the quick brown fox jumps over the lazy dog".repeat(4);
+        let mut y = x.clone();
+        y[10..15].copy_from_slice(b"BROWN");
+        y.drain(60..70);
+        round_trip(&x, &y);
+        // insertions at the start and at the end, and deletions there
+        round_trip(&x, &[b"new start, ", &x[..], b", new end"].concat());
+        round_trip(&x, &x[20..x.len() - 20]);
+        round_trip(&x, &x);
+        round_trip(b"", &x);
+        round_trip(&x, b"");
+        round_trip(b"", b"");
+    }
+
+    #[test]
+    fn vcdiff() {
+        let instructions = [
+            Instruction::Add(b"ab".to_vec()),
+            Instruction::Copy { offset: 1, len: 3 },
+        ];
+        let mut patch = Vec::new();
+        write_vcdiff(&mut patch, &instructions).unwrap();
+        // the window copies 4 bytes from the start of the source and makes 5 bytes
+        let expected = [
+            &VCDIFF_MAGIC[..],
+            &[VCD_SOURCE, 4, 0, 12, 5, 0, 2, 4, 1],
+            b"ab",
+            &[VCD_ADD, 2, VCD_COPY, 3],
+            &[1],
+        ]
+        .concat();
+        assert_eq!(patch, expected);
+        assert_eq!(apply(&patch, b"0123").unwrap(), b"ab123");
+        // a window without source that copies its own bytes, overlapping the ones it makes
+        let window = [0, 11, 4, 0, 1, 4, 1, b'z', VCD_ADD, 1, VCD_COPY, 3, 0];
+        let overlapping = [&VCDIFF_MAGIC[..], &window].concat();
+        assert_eq!(apply(&overlapping, b"").unwrap(), b"zzzz");
+        assert!(apply(&patch[..patch.len() - 1], b"0123").is_err());
+        assert!(apply(&patch, b"012").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use super::{row_line, DEFAULT_COLUMNS};
+use crate::{align::AlignElement, file::FileState, style::Style};
+
+/// The number of rows of context around differences if none is given
+pub const DEFAULT_CONTEXT: usize = 3;
+/// Bytes only in one of the files are elided above this amount if no other value is given
+pub const DEFAULT_ELIDE_THRESHOLD: usize = 1024;
+
+/// Settings for the hexdump hunks
+#[derive(Clone, Copy, Debug)]
+pub struct UnifiedOptions {
+    /// rows of unchanged bytes to show before and after each difference
+    pub context: usize,
+    /// runs of bytes only in one file longer than this are shortened to their first
+    /// and last row
+    pub elide_threshold: usize,
+    pub style: Style,
+}
+
+type Row = Vec<AlignElement>;
+
+/// Which file the bytes of a row are in, if they are only in one of them
+fn one_side(row: &[AlignElement]) -> Option<bool> {
+    if row.iter().all(|e| e.ybyte.is_none()) {
+        Some(false)
+    } else if row.iter().all(|e| e.xbyte.is_none()) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The number of bytes of a row in the file(s) it has bytes in
+fn row_bytes(row: &[AlignElement]) -> usize {
+    row.iter()
+        .map(|e| e.xbyte.is_some().max(e.ybyte.is_some()) as usize)
+        .sum()
+}
+
+/// Writes rows of the alignment as stacked hexdump lines, prefixed with `-` for the first
+/// and `+` for the second file
+struct HunkWriter<'a, W: Write> {
+    out: &'a mut W,
+    options: UnifiedOptions,
+    columns: usize,
+    digits: usize,
+    /// rows of the current run of bytes only in one file (on the side given by `run_side`),
+    /// which are kept until the run is longer than the threshold
+    run: Vec<Row>,
+    run_side: bool,
+    run_bytes: usize,
+    /// the last row of a run that is longer than the threshold
+    run_last: Option<Row>,
+}
+
+impl<'a, W: Write> HunkWriter<'a, W> {
+    fn header(&mut self, row: &[AlignElement]) -> io::Result<()> {
+        let digits = self.digits;
+        let (x, y) = row.first().map_or((0, 0), |e| (e.xaddr, e.yaddr));
+        writeln!(self.out, "@@ -{x:0digits$x} +{y:0digits$x} @@")
+    }
+    fn row(&mut self, row: &[AlignElement]) -> io::Result<()> {
+        let [x, y] = row_line(row, self.columns).plain_text(self.options.style);
+        writeln!(self.out, "-{x}")?;
+        writeln!(self.out, "+{y}")
+    }
+    /// Adds a row that is part of a difference, collecting rows with bytes in only one file
+    fn diff_row(&mut self, row: Row) -> io::Result<()> {
+        let side = match one_side(&row) {
+            Some(side) => side,
+            None => {
+                self.end_run()?;
+                return self.row(&row);
+            }
+        };
+        if side != self.run_side {
+            self.end_run()?;
+        }
+        self.run_side = side;
+        self.run_bytes += row_bytes(&row);
+        if self.run.is_empty() || self.run_bytes <= self.options.elide_threshold {
+            self.run.push(row);
+        } else {
+            self.run_last = Some(row);
+        }
+        Ok(())
+    }
+    /// Writes the current run of rows with bytes in only one file,
+    /// with a marker instead of the middle rows if it is too long
+    fn end_run(&mut self) -> io::Result<()> {
+        let run = std::mem::take(&mut self.run);
+        let run_bytes = std::mem::take(&mut self.run_bytes);
+        let last = match self.run_last.take() {
+            Some(last) => last,
+            None => {
+                for row in run {
+                    self.row(&row)?;
+                }
+                return Ok(());
+            }
+        };
+        let first = &run[0];
+        let elided = run_bytes - row_bytes(first) - row_bytes(&last);
+        self.row(first)?;
+        if elided > 0 {
+            let prefix = if self.run_side { '+' } else { '-' };
+            writeln!(self.out, "{prefix} \u{2026} {elided} bytes \u{2026}")?;
+        }
+        self.row(&last)
+    }
+}
+
+/// Writes hunks of stacked hexdump lines for each difference in the alignment, with
+/// rows of context around them, and returns whether the files are different
+pub fn write_unified(
+    out: &mut impl Write,
+    files: [&FileState; 2],
+    alignment: impl IntoIterator<Item = AlignElement>,
+    options: UnifiedOptions,
+) -> io::Result<bool> {
+    let [x, y] = files;
+    let mut style = options.style;
+    // without colors, the rows with differences are marked
+    style.monochrome = true;
+    style.addr_width = x.address_digits().max(y.address_digits());
+    let columns = style
+        .column_count
+        .fixed()
+        .map_or(DEFAULT_COLUMNS, |n| n as usize);
+    writeln!(out, "--- {}", x.name)?;
+    writeln!(out, "+++ {}", y.name)?;
+    let mut writer = HunkWriter {
+        out,
+        options: UnifiedOptions { style, ..options },
+        columns,
+        digits: style.addr_width as usize,
+        run: Vec::new(),
+        run_side: false,
+        run_bytes: 0,
+        run_last: None,
+    };
+    let mut alignment = alignment.into_iter().peekable();
+    let mut before: VecDeque<Row> = VecDeque::new();
+    // rows of context still to be written after the last difference
+    let mut after = 0;
+    let mut in_hunk = false;
+    // whether rows were left out since the last written one, so a new hunk needs a header
+    let mut gap = true;
+    let mut different = false;
+    while alignment.peek().is_some() {
+        let row: Row = alignment.by_ref().take(columns).collect();
+        if row.iter().any(|e| e.xbyte != e.ybyte) {
+            if !in_hunk {
+                if gap {
+                    writer.header(before.front().unwrap_or(&row))?;
+                    gap = false;
+                }
+                for row in before.drain(..) {
+                    writer.row(&row)?;
+                }
+            }
+            different = true;
+            in_hunk = true;
+            after = options.context;
+            writer.diff_row(row)?;
+            continue;
+        }
+        writer.end_run()?;
+        if in_hunk && after > 0 {
+            writer.row(&row)?;
+            after -= 1;
+            continue;
+        }
+        in_hunk = false;
+        before.push_back(row);
+        if before.len() > options.context {
+            before.pop_front();
+            gap = true;
+        }
+    }
+    writer.end_run()?;
+    writer.out.flush()?;
+    Ok(different)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        align::{AlignAlgorithm, AlignMode},
+        report::Alignment,
+        style::ColumnSetting,
+        testing::file,
+    };
+
+    fn unified(x: &[u8], y: &[u8], context: usize, elide_threshold: usize) -> String {
+        let (x, y) = (file("a", x), file("b", y));
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let alignment = Alignment::start(&algo, x.content.clone(), y.content.clone());
+        let options = UnifiedOptions {
+            context,
+            elide_threshold,
+            style: Style {
+                ascii_col: true,
+                column_count: ColumnSetting::Fixed(4),
+                ..Style::default()
+            },
+        };
+        let mut out = Vec::new();
+        write_unified(&mut out, [&x, &y], alignment, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn hunks_with_context() {
+        let x = b"0123456789abcdefghijklmnopqrstuv";
+        let y = b"0123456789abcdEfghijklmnopqrstuv";
+        assert_eq!(
+            unified(x, y, 1, DEFAULT_ELIDE_THRESHOLD),
+            "--- a\n\
+             +++ b\n\
+             @@ -08 +08 @@\n\
+             - 08 38 39 61 62  |89ab\n\
+             + 08 38 39 61 62  |89ab\n\
+             -*0c 63 64 65 66  |cdef\n\
+             +*0c 63 64 45 66  |cdEf\n\
+             - 10 67 68 69 6a  |ghij\n\
+             + 10 67 68 69 6a  |ghij\n"
+        );
+        assert_eq!(unified(x, x, 1, DEFAULT_ELIDE_THRESHOLD), "--- a\n+++ b\n");
+    }
+
+    #[test]
+    fn long_insertions_are_elided() {
+        let x = b"0123456789abcdef";
+        let mut y = b"01234567".to_vec();
+        y.extend(std::iter::repeat(b'x').take(40));
+        y.extend(b"89abcdef");
+        let text = unified(x, &y, 0, 16);
+        assert!(text.contains("+ \u{2026} 32 bytes \u{2026}\n"), "{text}");
+        assert_eq!(
+            text.lines().filter(|l| l.contains("78 78 78 78")).count(),
+            2
+        );
+        // below the threshold, every row is shown
+        let text = unified(x, &y, 0, 64);
+        assert_eq!(
+            text.lines().filter(|l| l.contains("78 78 78 78")).count(),
+            10
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crossterm::terminal;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Action;
+
+/// The first line of a log, with the size of the terminal it was recorded in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    columns: usize,
+    rows: usize,
+}
+
+/// A line of a log after the header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Step {
+    /// the milliseconds since the recording started
+    ms: u64,
+    action: Action,
+}
+
+/// Writes the actions of the views to a log as they are processed, from `--record`.
+/// The log has a line of JSON with the size of the terminal, and then a line of JSON
+/// for each action with the time it was read at.
+#[derive(Debug)]
+pub struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Starts the log at the path with the current size of the terminal
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        let header = Header {
+            columns: columns.into(),
+            rows: rows.into(),
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &header)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(Recorder {
+            out,
+            start: Instant::now(),
+        })
+    }
+    /// Writes the action with its time, flushing it right away so that the log is complete
+    /// even if biodiff crashes right after it
+    fn record(&mut self, action: Action) -> io::Result<()> {
+        let step = Step {
+            ms: self.start.elapsed().as_millis() as u64,
+            action,
+        };
+        serde_json::to_writer(&mut self.out, &step)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+/// The actions of a log of `Recorder` that are replayed instead of reading the keyboard,
+/// from `--replay`
+#[derive(Clone, Debug)]
+pub struct Replay {
+    steps: std::vec::IntoIter<Step>,
+    /// the time of the last replayed action
+    last_ms: u64,
+    /// how many times faster than recorded the actions are replayed, or 0 for not waiting
+    speed: f64,
+    /// the size of the terminal the log was recorded in, which the views are drawn in
+    pub size: (usize, usize),
+}
+
+impl Replay {
+    /// Reads a log, see `Recorder`
+    pub fn parse(log: &str, speed: f64) -> Result<Self, String> {
+        let mut lines = log
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let error =
+            |number: usize, e: serde_json::Error| format!("Line {} of the log: {e}", number + 1);
+        let header: Header = match lines.next() {
+            Some((number, line)) => serde_json::from_str(line).map_err(|e| error(number, e))?,
+            None => return Err(String::from("The log is empty")),
+        };
+        let steps = lines
+            .map(|(number, line)| serde_json::from_str(line).map_err(|e| error(number, e)))
+            .collect::<Result<Vec<Step>, _>>()?;
+        Ok(Replay {
+            steps: steps.into_iter(),
+            last_ms: 0,
+            speed,
+            size: (header.columns, header.rows),
+        })
+    }
+    /// Reads the log at the path
+    pub fn load(path: &Path, speed: f64) -> Result<Self, String> {
+        let log = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        Self::parse(&log, speed)
+    }
+    /// Waits as long as there was between the next action and the one before it
+    /// (divided by the speed) and returns it, or None at the end of the log
+    fn next_action(&mut self) -> Option<Action> {
+        let step = self.steps.next()?;
+        let wait = step.ms.saturating_sub(self.last_ms);
+        self.last_ms = step.ms;
+        if self.speed > 0.0 {
+            sleep(Duration::from_millis(wait).div_f64(self.speed));
+        }
+        Some(step.action)
+    }
+}
+
+/// Where the actions of the views come from besides the keyboard, and where they are written to
+#[derive(Debug, Default)]
+pub struct Script {
+    pub recorder: Option<Recorder>,
+    pub replay: Option<Replay>,
+}
+
+impl Script {
+    /// The next replayed action, or None once the keyboard takes over at the end of the log
+    pub fn replayed(&mut self) -> Option<Action> {
+        let action = self.replay.as_mut()?.next_action();
+        if action.is_none() {
+            self.replay = None;
+        }
+        action
+    }
+    /// Writes the action to the log if one is recorded.
+    /// A log that can not be written anymore is not recorded further.
+    pub fn record(&mut self, action: Action) {
+        // a replayed suspend would stop biodiff in the middle of the replay,
+        // and ticks only say that no key was pressed
+        if matches!(action, Action::Suspend | Action::Tick) {
+            return;
+        }
+        if let Some(Err(_)) = self
+            .recorder
+            .as_mut()
+            .map(|recorder| recorder.record(action))
+        {
+            self.recorder = None;
+        }
+    }
+    /// The size the views are drawn in instead of the size of the terminal while replaying
+    pub fn size(&self) -> Option<(usize, usize)> {
+        self.replay.as_ref().map(|replay| replay.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn logs() {
+        let path = temp_path("record");
+        let mut script = Script {
+            recorder: Some(Recorder::create(&path).unwrap()),
+            replay: None,
+        };
+        let actions = [
+            Action::Down,
+            Action::PendingCount(Some(3)),
+            Action::SetCursorTo(4, 2),
+            Action::Suspend,
+            Action::Tick,
+            Action::Type('x'),
+            Action::Quit,
+        ];
+        for action in actions {
+            script.record(action);
+        }
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut script = Script {
+            recorder: None,
+            replay: Some(Replay::parse(&log, 0.0).unwrap()),
+        };
+        assert!(script.size().is_some());
+        let replayed = std::iter::from_fn(|| script.replayed()).collect::<Vec<_>>();
+        // suspending and ticks are not recorded
+        let expected = actions
+            .into_iter()
+            .filter(|&action| !matches!(action, Action::Suspend | Action::Tick))
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, expected);
+        // the keyboard takes over at the end
+        assert!(script.replay.is_none());
+        assert!(Replay::parse("", 1.0).is_err());
+        assert!(Replay::parse("{\"columns\":80,\"rows\":24}\n{\"ms\":1}", 1.0).is_err());
+    }
+}
@@ -1,6 +1,16 @@
-use std::array::from_fn;
+use std::{
+    array::from_fn,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use crate::cursor::CursorActive;
+use crate::{cursor::CursorActive, file::FileContent};
+
+/// How many bytes are processed between progress reports
+const CHUNK_SIZE: usize = 4 << 20;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum SelectionStatus {
@@ -22,10 +32,14 @@ impl SelectionStatus {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Selections {
     start: [Option<isize>; 2],
     current: [isize; 2],
+    /// the row of the start if whole rows are selected
+    start_row: [Option<Range<isize>>; 2],
+    /// the row of the current offset
+    current_row: [Range<isize>; 2],
 }
 
 impl Selections {
@@ -34,13 +48,41 @@ impl Selections {
     }
 
     pub fn start(&mut self, offset: [isize; 2], cursor_act: CursorActive) {
-        if cursor_act.is_first() {
-            self.start[0] = Some(offset[0]);
-            self.current[0] = offset[0];
+        for (i, active) in cursor_act.is_active().into_iter().enumerate() {
+            if active {
+                self.start[i] = Some(offset[i]);
+                self.current[i] = offset[i];
+                self.start_row[i] = None;
+            }
         }
-        if cursor_act.is_second() {
-            self.start[1] = Some(offset[1]);
-            self.current[1] = offset[1];
+    }
+
+    /// Starts selecting whole rows, beginning with the rows of the offsets
+    pub fn start_rows(
+        &mut self,
+        offset: [isize; 2],
+        rows: [Range<isize>; 2],
+        cursor_act: CursorActive,
+    ) {
+        self.start(offset, cursor_act);
+        let active = cursor_act.is_active();
+        for (i, row) in rows.into_iter().enumerate() {
+            if active[i] {
+                self.start_row[i] = Some(row.clone());
+                self.current_row[i] = row;
+            }
+        }
+    }
+
+    /// Starts the selections at the offsets for the active cursors without one,
+    /// so that they can be extended by moving the cursors
+    pub fn start_missing(&mut self, offset: [isize; 2], cursor_act: CursorActive) {
+        for (i, active) in cursor_act.is_active().into_iter().enumerate() {
+            if active && self.start[i].is_none() {
+                self.start[i] = Some(offset[i]);
+                self.current[i] = offset[i];
+                self.start_row[i] = None;
+            }
         }
     }
 
@@ -57,20 +99,39 @@ impl Selections {
         ret
     }
 
-    pub fn update(&mut self, offset: [isize; 2], cursor_act: CursorActive) {
-        if cursor_act.is_first() {
-            self.current[0] = offset[0];
-        }
-        if cursor_act.is_second() {
-            self.current[1] = offset[1];
+    /// Moves the ends of the selections to the offsets, which are in the given rows
+    pub fn update(
+        &mut self,
+        offset: [isize; 2],
+        rows: [Range<isize>; 2],
+        cursor_act: CursorActive,
+    ) {
+        let active = cursor_act.is_active();
+        for (i, row) in rows.into_iter().enumerate() {
+            if active[i] {
+                self.current[i] = offset[i];
+                self.current_row[i] = row;
+            }
         }
     }
 
+    /// The first and last selected offset, which are extended to the
+    /// start and end of the rows when whole rows are selected
+    fn bounds(&self, i: usize) -> Option<[isize; 2]> {
+        let start = self.start[i]?;
+        let current = self.current[i];
+        Some(match &self.start_row[i] {
+            Some(row) => [
+                row.start.min(self.current_row[i].start),
+                row.end.max(self.current_row[i].end) - 1,
+            ],
+            None => [start.min(current), start.max(current)],
+        })
+    }
+
     pub fn selection_status(&self, offset: [isize; 2]) -> [SelectionStatus; 2] {
-        from_fn(|i| match (self.start[i], self.current[i], offset[i]) {
-            (Some(start), current, offset) => {
-                let begin = start.min(current);
-                let end = start.max(current);
+        from_fn(|i| match (self.bounds(i), offset[i]) {
+            (Some([begin, end]), offset) => {
                 if begin == end && offset == begin {
                     SelectionStatus::Only
                 } else if offset == begin {
@@ -89,17 +150,218 @@ impl Selections {
 
     pub fn ranges(&self, cursor_act: CursorActive) -> [Option<[isize; 2]>; 2] {
         let active = cursor_act.is_active();
-        from_fn(|i| match (self.start[i], self.current[i], active[i]) {
-            (Some(start), current, true) => {
-                let begin = start.min(current);
-                let end = start.max(current);
-                Some([begin, end])
-            }
-            _ => None,
-        })
+        from_fn(|i| self.bounds(i).filter(|_| active[i]))
     }
 
     pub fn is_active(&self) -> bool {
         self.start.iter().any(|x| x.is_some())
     }
+
+    /// Exchanges the selections of both views
+    pub fn swap(&mut self) {
+        self.start.swap(0, 1);
+        self.current.swap(0, 1);
+        self.start_row.swap(0, 1);
+        self.current_row.swap(0, 1);
+    }
+}
+
+/// The bytes of one of the files that an action like hashing works on
+#[derive(Clone)]
+pub struct SelectedBytes {
+    pub name: String,
+    pub content: FileContent,
+    /// the selected bytes, or the whole file if nothing is selected
+    pub range: Range<usize>,
+    /// the address of the first compared byte, which is added to the shown addresses
+    pub base: usize,
+    pub selected: bool,
+}
+
+impl SelectedBytes {
+    /// Describes the bytes, like `first: 0x10-0x1f (16 bytes)`
+    pub fn describe(&self) -> String {
+        let len = self.range.len();
+        let bytes = if len == 1 { "byte" } else { "bytes" };
+        if !self.selected {
+            format!("{}: whole file ({len} {bytes})", self.name)
+        } else if self.range.is_empty() {
+            format!("{}: nothing selected (0 bytes)", self.name)
+        } else {
+            format!(
+                "{}: {:#x}-{:#x} ({len} {bytes})",
+                self.name,
+                self.range.start + self.base,
+                self.range.end - 1 + self.base
+            )
+        }
+    }
+}
+
+/// The bytes of the active cursors, which are the selected ones
+/// or the whole files if nothing is selected. In the aligned view, the
+/// selected bytes of a file leave out the gaps of the alignment.
+pub fn selected_bytes(
+    names: [&str; 2],
+    files: [FileContent; 2],
+    selection: [Option<Range<usize>>; 2],
+    bases: [usize; 2],
+    cursor_act: CursorActive,
+) -> Vec<SelectedBytes> {
+    let whole = selection.iter().all(|x| x.is_none());
+    let active = cursor_act.is_active();
+    files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| active[*i])
+        .filter_map(|(i, content)| {
+            let range = match &selection[i] {
+                Some(range) => range.clone(),
+                None if whole => 0..content.len(),
+                None => return None,
+            };
+            Some(SelectedBytes {
+                name: names[i].to_string(),
+                content,
+                range,
+                base: bases[i],
+                selected: !whole,
+            })
+        })
+        .collect()
+}
+
+/// Something computed from bytes that are fed to it in chunks, like a hash
+pub trait Accumulator: Default {
+    type Output;
+    fn update(&mut self, bytes: &[u8]);
+    fn finish(self) -> Self::Output;
+}
+
+/// The messages sent while processing selected bytes on another thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkMessage<T> {
+    /// this many bytes of all files together were processed
+    Progress(usize),
+    /// the results for every file
+    Done(Vec<T>),
+    Cancelled,
+}
+
+/// Feeds the selected bytes of every file to an accumulator on a new thread,
+/// reporting the progress every few megabytes, until `is_running` is unset
+/// or sending fails
+pub fn process_selected<A, Sender>(
+    selected: Vec<SelectedBytes>,
+    is_running: Arc<AtomicBool>,
+    mut send: Sender,
+) where
+    A: Accumulator + 'static,
+    A::Output: Send + 'static,
+    Sender: FnMut(ChunkMessage<A::Output>) -> bool + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut results = Vec::new();
+        let mut done = 0;
+        for part in selected {
+            let mut accumulator = A::default();
+            for chunk in part.content[part.range].chunks(CHUNK_SIZE) {
+                if !is_running.load(Ordering::Relaxed) {
+                    send(ChunkMessage::Cancelled);
+                    return;
+                }
+                accumulator.update(chunk);
+                done += chunk.len();
+                if !send(ChunkMessage::Progress(done)) {
+                    return;
+                }
+            }
+            results.push(accumulator.finish());
+        }
+        send(ChunkMessage::Done(results));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileBytes;
+    use std::sync::mpsc::channel;
+
+    /// Counts the bytes
+    #[derive(Default)]
+    struct Length(usize);
+
+    impl Accumulator for Length {
+        type Output = usize;
+        fn update(&mut self, bytes: &[u8]) {
+            self.0 += bytes.len();
+        }
+        fn finish(self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn rows() {
+        let mut selections = Selections::new();
+        selections.start_rows([5, 3], [4..8, 2..6], CursorActive::Both);
+        assert_eq!(
+            selections.ranges(CursorActive::Both),
+            [Some([4, 7]), Some([2, 5])]
+        );
+        // the selection grows by whole rows in both directions from the start
+        selections.update([13, 11], [12..16, 10..14], CursorActive::Both);
+        assert_eq!(
+            selections.ranges(CursorActive::First),
+            [Some([4, 15]), None]
+        );
+        selections.update([1, -1], [0..4, -2..2], CursorActive::Second);
+        assert_eq!(
+            selections.ranges(CursorActive::Both),
+            [Some([4, 15]), Some([-2, 5])]
+        );
+        // bytes are selected again after starting a new selection
+        selections.start([9, 9], CursorActive::First);
+        selections.update([7, 7], [4..8, 4..8], CursorActive::First);
+        assert_eq!(selections.ranges(CursorActive::First), [Some([7, 9]), None]);
+        selections.start_missing([0, 0], CursorActive::Both);
+        assert_eq!(
+            selections.ranges(CursorActive::Both),
+            [Some([7, 9]), Some([-2, 5])]
+        );
+    }
+    #[test]
+    fn selected_bytes() {
+        let files = [b"first file".to_vec(), b"second".to_vec()]
+            .map(|content| Arc::new(FileBytes::from(content)));
+        let selected = super::selected_bytes(
+            ["first", "second"],
+            files.clone(),
+            [None, None],
+            [0, 0],
+            CursorActive::Second,
+        );
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].describe(), "second: whole file (6 bytes)");
+        // a file without selected bytes is left out once the other one has some
+        let selected = super::selected_bytes(
+            ["first", "second"],
+            files,
+            [Some(6..10), None],
+            [0x100, 0],
+            CursorActive::Both,
+        );
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].describe(), "first: 0x106-0x109 (4 bytes)");
+        let (send, recv) = channel();
+        process_selected::<Length, _>(selected, Arc::new(AtomicBool::new(true)), move |message| {
+            send.send(message).is_ok()
+        });
+        let messages = recv.iter().collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![ChunkMessage::Progress(4), ChunkMessage::Done(vec![4])]
+        );
+    }
 }
@@ -0,0 +1,241 @@
+use std::{error::Error, fs::read_to_string, path::Path, time::UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    control::Settings,
+    file::Source,
+    search::{Query, QueryType},
+    style::{ColumnSetting, Style},
+};
+
+/// The number of comparisons whose sessions are kept in one file, the ones left the longest
+/// time ago are dropped first
+const MAX_SESSIONS: usize = 100;
+
+/// The size and modification time of a file, for telling whether it changed since its
+/// session was saved
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub len: u64,
+    /// in seconds since the unix epoch
+    pub modified: Option<u64>,
+}
+
+impl Stamp {
+    /// The stamp of the file the source reads, or None if there is none (like for stdin)
+    pub fn of(source: Option<&Source>) -> Option<Stamp> {
+        let metadata = std::fs::metadata(&source?.path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        Some(Stamp {
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// A search query without its compiled form
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub query_type: QueryType,
+    pub text: String,
+    pub mismatches: usize,
+}
+
+impl From<&Query> for SavedQuery {
+    fn from(query: &Query) -> Self {
+        SavedQuery {
+            query_type: query.query_type(),
+            text: query.text().to_string(),
+            mismatches: query.mismatches(),
+        }
+    }
+}
+
+/// Where the comparison of two files was left, which is restored when the same files are
+/// compared again. The alignment is not part of it, it is done again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    /// the keys of the files like the ones of the marks, in the order they were shown
+    pub files: [String; 2],
+    pub stamps: [Option<Stamp>; 2],
+    /// the positions of the cursors in the compared bytes, whose difference is the offset
+    /// between the files in the unaligned view
+    pub positions: [isize; 2],
+    /// the shown addresses of the cursors, which the aligned view goes to once they are aligned
+    pub cursor: [Option<usize>; 2],
+    pub aligned: bool,
+    pub offset_lock: bool,
+    pub style: Style,
+    /// the columns of the second half, which are not saved along with the rest of the style
+    pub second_column_count: Option<ColumnSetting>,
+    /// the marks of both files by their names
+    pub marks: Vec<(String, [Option<usize>; 2])>,
+    /// the last query that was searched
+    pub query: Option<SavedQuery>,
+}
+
+impl Session {
+    /// Reads the session of the files from a file written by `store`, which is None if the files
+    /// have no session there. The session of the files the other way around is swapped.
+    pub fn load(path: &Path, files: &[String; 2]) -> Result<Option<Session>, String> {
+        let reversed = [files[1].clone(), files[0].clone()];
+        Ok(read_sessions(path)?.into_iter().find_map(|mut session| {
+            if &session.files == files {
+                Some(session)
+            } else if session.files == reversed {
+                session.swap();
+                Some(session)
+            } else {
+                None
+            }
+        }))
+    }
+    /// Writes the session to the file, replacing the earlier one of the same files
+    pub fn store(self, path: &Path) -> Result<(), Box<dyn Error + 'static>> {
+        let reversed = [self.files[1].clone(), self.files[0].clone()];
+        let mut sessions = read_sessions(path)?;
+        sessions.retain(|session| session.files != self.files && session.files != reversed);
+        sessions.insert(0, self);
+        sessions.truncate(MAX_SESSIONS);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(&sessions)?)?;
+        Ok(())
+    }
+    /// Exchanges the files of the session
+    fn swap(&mut self) {
+        self.files.swap(0, 1);
+        self.stamps.swap(0, 1);
+        self.positions.swap(0, 1);
+        self.cursor.swap(0, 1);
+        for (_, addresses) in &mut self.marks {
+            addresses.swap(0, 1);
+        }
+    }
+    /// Which of the files changed since the session was saved, going by their stamps now
+    pub fn changed(&self, stamps: &[Option<Stamp>; 2]) -> [bool; 2] {
+        [0, 1]
+            .map(|i| matches!((self.stamps[i], stamps[i]), (Some(then), Some(now)) if then != now))
+    }
+    /// Applies the style, marks and search query of the session to the settings.
+    /// The parts of the style that come from the files and the terminal are kept.
+    pub fn restore(&self, settings: &mut Settings) {
+        let style = settings.style;
+        settings.style = Style {
+            second_column_count: self.second_column_count,
+            addr_width: style.addr_width,
+            short_addr_width: style.short_addr_width,
+            relative_to: style.relative_to,
+            monochrome: style.monochrome,
+            color_depth: style.color_depth,
+            can_scroll: style.can_scroll,
+            single: style.single,
+            unit: style.unit,
+            ..self.style
+        };
+        for (name, addresses) in &self.marks {
+            settings.marks.set(name, *addresses);
+        }
+        settings.last_query = self.query.as_ref().and_then(|query| {
+            Query::new(query.query_type.clone(), &query.text, query.mismatches).ok()
+        });
+    }
+}
+
+/// Reads the sessions of a file, which are empty if there is no such file
+fn read_sessions(path: &Path) -> Result<Vec<Session>, String> {
+    match read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        marks::{MarkMap, Marks},
+        testing::temp_path,
+    };
+
+    fn session(files: [&str; 2]) -> Session {
+        Session {
+            files: files.map(String::from),
+            stamps: [
+                Some(Stamp {
+                    len: 16,
+                    modified: Some(1),
+                }),
+                None,
+            ],
+            positions: [3, -5],
+            cursor: [Some(3), None],
+            aligned: false,
+            offset_lock: true,
+            style: Style {
+                fold: true,
+                ..Style::default()
+            },
+            second_column_count: Some(ColumnSetting::Fixed(8)),
+            marks: vec![(String::from("a"), [Some(1), Some(2)])],
+            query: Some(SavedQuery {
+                query_type: QueryType::HexPattern,
+                text: String::from("12 ?4"),
+                mismatches: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn sessions() {
+        let path = temp_path("session");
+        let files = [String::from("x"), String::from("y")];
+        assert!(Session::load(&path, &files).unwrap().is_none());
+        session(["x", "y"]).store(&path).unwrap();
+        session(["z", "y"]).store(&path).unwrap();
+        let loaded = Session::load(&path, &files).unwrap().unwrap();
+        assert_eq!(loaded.positions, [3, -5]);
+        assert_eq!(loaded.query, session(["x", "y"]).query);
+        // the same files the other way around replace the session, which is swapped when loading
+        let mut reversed = session(["y", "x"]);
+        reversed.positions = [7, 9];
+        reversed.store(&path).unwrap();
+        let loaded = Session::load(&path, &files).unwrap().unwrap();
+        assert_eq!(loaded.positions, [9, 7]);
+        assert_eq!(loaded.marks, [(String::from("a"), [Some(2), Some(1)])]);
+        assert_eq!(read_sessions(&path).unwrap().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+        // only files whose stamps are known on both sides count as changed
+        let stamp = |len| {
+            Some(Stamp {
+                len,
+                modified: Some(1),
+            })
+        };
+        assert_eq!(loaded.changed(&[stamp(16), stamp(16)]), [false, false]);
+        assert_eq!(loaded.changed(&[stamp(20), stamp(20)]), [false, true]);
+    }
+
+    #[test]
+    fn restore() {
+        let mut settings = Settings::default();
+        settings.style.addr_width = 12;
+        settings.marks = Marks::new([String::from("x"), String::from("y")], MarkMap::new());
+        session(["x", "y"]).restore(&mut settings);
+        assert!(settings.style.fold);
+        assert_eq!(settings.style.addr_width, 12);
+        assert_eq!(
+            settings.style.second_column_count,
+            Some(ColumnSetting::Fixed(8))
+        );
+        assert_eq!(settings.marks.get("a"), [Some(1), Some(2)]);
+        assert_eq!(settings.last_query.unwrap().text(), "12 ?4");
+    }
+}
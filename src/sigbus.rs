@@ -0,0 +1,79 @@
+//! Exiting with a message when a memory-mapped file is truncated.
+//!
+//! Reading the bytes of a mapped file past its new end raises SIGBUS, which can not be
+//! turned into a panic. With the default action, the process would die without a word, and
+//! the terminal would be left in raw mode and the alternate screen. The handler restores
+//! what it can of the terminal and exits with a message.
+
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// Whether the terminal is set up for the view, so that the handler has to leave it
+#[cfg(unix)]
+static IN_TERMINAL: AtomicBool = AtomicBool::new(false);
+/// The exit status of the handler, which is the one of errors of the report being written
+#[cfg(unix)]
+static STATUS: AtomicI32 = AtomicI32::new(1);
+
+/// Catches SIGBUS while the terminal is set up
+#[cfg(unix)]
+pub fn catch_bus_error() {
+    IN_TERMINAL.store(true, Ordering::Relaxed);
+    install();
+}
+
+/// Catches SIGBUS while a report is written, exiting with the status of its errors
+#[cfg(unix)]
+pub fn catch_bus_error_in_report(status: i32) {
+    STATUS.store(status, Ordering::Relaxed);
+    install();
+}
+
+#[cfg(unix)]
+fn install() {
+    use std::sync::Once;
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // SAFETY: the action is fully initialized and the handler only uses
+        // async-signal-safe functions
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigbus as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut());
+        }
+    });
+}
+
+/// Leaves the alternate screen and shows the cursor if the view is shown, then explains
+/// what happened. Only async-signal-safe functions are used, so the raw mode of the terminal
+/// is left to the shell.
+#[cfg(unix)]
+extern "C" fn on_sigbus(_: libc::c_int) {
+    const IN_VIEW: &[u8] =
+        b"\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?1015l\x1b[?25h\x1b[?1049l\r\n\
+        biodiff: a file was truncated while it was shown, its bytes can not be read anymore\r\n";
+    const IN_REPORT: &[u8] =
+        b"biodiff: a file was truncated while it was compared, its bytes can not be read anymore\n";
+    let (message, status) = if IN_TERMINAL.load(Ordering::Relaxed) {
+        (IN_VIEW, 1)
+    } else {
+        (IN_REPORT, STATUS.load(Ordering::Relaxed))
+    };
+    // SAFETY: write and _exit are async-signal-safe, and so are the atomic loads
+    unsafe {
+        libc::write(
+            libc::STDERR_FILENO,
+            message.as_ptr() as *const libc::c_void,
+            message.len(),
+        );
+        libc::_exit(status);
+    }
+}
+
+/// Files are not mapped on other systems
+#[cfg(not(unix))]
+pub fn catch_bus_error() {}
+
+#[cfg(not(unix))]
+pub fn catch_bus_error_in_report(_status: i32) {}
@@ -0,0 +1,162 @@
+use crate::selection::Accumulator;
+
+/// The block characters for the eighths of a cell of the chart, from empty to full
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How often every byte value occurs in some bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    pub counts: [u64; 256],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram { counts: [0; 256] }
+    }
+}
+
+impl Accumulator for Histogram {
+    type Output = Histogram;
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.counts[*byte as usize] += 1;
+        }
+    }
+    fn finish(self) -> Histogram {
+        self
+    }
+}
+
+impl Histogram {
+    pub fn len(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+    /// The Shannon entropy in bits per byte, from 0 to 8
+    pub fn entropy(&self) -> f64 {
+        let len = self.len() as f64;
+        self.counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let freq = *count as f64 / len;
+                -freq * freq.log2()
+            })
+            .sum()
+    }
+    /// Summarizes the bytes in a few lines, with a guess what kind of data they are
+    pub fn statistics(&self) -> String {
+        let len = self.len();
+        let values = (0..=255u8).filter(|byte| self.counts[*byte as usize] > 0);
+        let (min, max) = match (values.clone().next(), values.clone().next_back()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return String::from("No bytes"),
+        };
+        let percent = |count: u64| count as f64 * 100.0 / len as f64;
+        let sum = (0..256).map(|i| i as u64 * self.counts[i]).sum::<u64>();
+        let (common, common_count) = (0..=255u8)
+            .map(|byte| (byte, self.counts[byte as usize]))
+            .max_by_key(|(byte, count)| (*count, std::cmp::Reverse(*byte)))
+            .unwrap_or_default();
+        let entropy = self.entropy();
+        let zeros = self.counts[0];
+        let printable = self.counts[0x20..0x7f].iter().sum::<u64>();
+        let guess = if entropy >= 7.5 {
+            "random looking, like compressed or encrypted data"
+        } else if percent(zeros) >= 50.0 {
+            "sparse, mostly zero bytes"
+        } else if percent(printable) >= 90.0 {
+            "mostly printable ASCII text"
+        } else {
+            "structured data"
+        };
+        format!(
+            "Bytes: {len} ({} distinct values)\n\
+             Min: {min:#04x}  Max: {max:#04x}  Mean: {:.2}\n\
+             Most common: {common:#04x} ({common_count} times, {:.1}%)\n\
+             Zero bytes: {:.1}%  Printable ASCII: {:.1}%\n\
+             Entropy: {entropy:.3} bits per byte\n\
+             Looks like: {guess}",
+            values.count(),
+            sum as f64 / len as f64,
+            percent(common_count),
+            percent(zeros),
+            percent(printable),
+        )
+    }
+    /// Draws the histogram as `height` rows of bars with one bar for every
+    /// `256 / width` byte values (`width` has to divide 256), followed by
+    /// a row labelling the byte values
+    pub fn chart(&self, width: usize, height: usize) -> Vec<String> {
+        let per_bar = 256 / width;
+        let bars = self
+            .counts
+            .chunks(per_bar)
+            .map(|chunk| chunk.iter().sum::<u64>())
+            .collect::<Vec<_>>();
+        let highest = bars.iter().copied().max().unwrap_or(0).max(1);
+        // every bar with any bytes is at least an eighth high
+        let eighths = bars
+            .iter()
+            .map(|bar| (bar * height as u64 * 8 / highest) as usize)
+            .zip(&bars)
+            .map(|(eighths, bar)| if *bar > 0 { eighths.max(1) } else { 0 })
+            .collect::<Vec<_>>();
+        let mut rows = (0..height)
+            .rev()
+            .map(|row| {
+                eighths
+                    .iter()
+                    .map(|eighths| BLOCKS[eighths.saturating_sub(row * 8).min(8)])
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        let mut labels = vec![' '; width];
+        for (column, label) in (0..width).step_by(16).map(|x| (x, x * per_bar)) {
+            for (i, c) in format!("{label:02x}").chars().enumerate() {
+                if let Some(place) = labels.get_mut(column + i) {
+                    *place = c;
+                }
+            }
+        }
+        rows.push(labels.into_iter().collect());
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn statistics() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.statistics(), "No bytes");
+        histogram.update(b"\0\0\0\0ab");
+        assert_eq!(histogram.len(), 6);
+        assert!((histogram.entropy() - 1.2516291673878228).abs() < 1e-9);
+        assert_eq!(
+            histogram.statistics(),
+            "Bytes: 6 (3 distinct values)\n\
+             Min: 0x00  Max: 0x62  Mean: 32.50\n\
+             Most common: 0x00 (4 times, 66.7%)\n\
+             Zero bytes: 66.7%  Printable ASCII: 33.3%\n\
+             Entropy: 1.252 bits per byte\n\
+             Looks like: sparse, mostly zero bytes"
+        );
+        let mut uniform = Histogram::default();
+        uniform.update(&(0..=255).collect::<Vec<u8>>());
+        assert_eq!(uniform.entropy(), 8.0);
+    }
+    #[test]
+    fn chart() {
+        let mut histogram = Histogram::default();
+        histogram.update(&[0, 0, 0, 0, 0xff, 0x80, 0x80]);
+        assert_eq!(
+            histogram.chart(32, 2),
+            vec![
+                "█                               ",
+                "█               █              ▄",
+                "00              80              ",
+            ]
+        );
+    }
+}
@@ -1,69 +1,109 @@
-use std::str::FromStr;
+use std::{iter::repeat, str::FromStr};
 
 use serde::{Deserialize, Serialize};
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
     backend::{BackgroundColor, Color, Effect},
+    cursor::MIN_ROWS,
+    file::Unit,
     selection::SelectionStatus,
+    theme::{ColorDepth, Palette, Theme, ThemeColor},
+    threeway::Change,
 };
 pub const FRONT_PAD: &str = " ";
+/// Replaces the front pad of rows with differences in monochrome mode
+pub const DIFF_MARKER: &str = "*";
 pub const MIDDLE_PAD: &str = " |";
-pub const SPACER_PERIOD: usize = 8;
+/// The width of the diff map at the right edge, with a space in front of it
+pub const DIFF_MAP_WIDTH: usize = 2;
+/// The number of colors of the gradient when coloring by entropy
+pub const ENTROPY_LEVELS: u8 = 8;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ByteData {
     pub byte: Option<u8>,
-    pub is_search_result: bool,
+    /// the slot of the first search query that has a result at this byte
+    pub search_slot: Option<u8>,
+    /// whether the byte is part of the search result under the cursor
+    pub is_current_result: bool,
     pub is_selected: SelectionStatus,
+    /// whether the byte is missing because of a gap in the alignment,
+    /// and not because it is outside of the alignment or the file
+    pub is_gap: bool,
+    /// the level of the entropy around the byte when coloring by entropy,
+    /// from 0 to ENTROPY_LEVELS - 1
+    pub entropy_level: u8,
+    /// the background of the innermost annotation containing the byte
+    pub annotation: Option<ThemeColor>,
+    /// whether the byte differs from the one of the other file, but the difference is ignored
+    pub is_ignored: bool,
+    /// whether the byte was overwritten in edit mode
+    pub is_edited: bool,
+    /// which descendant of the base of a three-way comparison changed the difference
+    pub change: Option<Change>,
 }
 
 impl ByteData {
-    pub fn new(byte: Option<u8>, is_search_result: bool, is_selected: SelectionStatus) -> Self {
+    pub fn new(byte: Option<u8>, search_slot: Option<u8>, is_selected: SelectionStatus) -> Self {
         ByteData {
             byte,
-            is_search_result,
+            search_slot,
+            is_current_result: false,
             is_selected,
+            is_gap: false,
+            entropy_level: 0,
+            annotation: None,
+            is_ignored: false,
+            is_edited: false,
+            change: None,
         }
     }
-}
-
-pub fn byte(data: ByteData) -> Option<u8> {
-    data.byte
-}
-
-/// Contains `digits` digits of an address, with a space at the end
-pub fn disp_addr(maddr: Option<usize>, digits: u8) -> String {
-    match maddr {
-        Some(addr) => {
-            format!("{addr:0width$x} ", width = digits as usize)
+    /// marks the byte as being part of the search result under the cursor
+    pub fn current_result(self, is_current_result: bool) -> Self {
+        ByteData {
+            is_current_result,
+            ..self
         }
-        None => {
-            format!("{:width$} ", " ", width = digits as usize)
+    }
+    /// marks the missing byte as a gap in the alignment
+    pub fn gap(self, is_gap: bool) -> Self {
+        ByteData { is_gap, ..self }
+    }
+    /// sets the level of the entropy around the byte
+    pub fn entropy_level(self, entropy_level: u8) -> Self {
+        ByteData {
+            entropy_level,
+            ..self
         }
     }
+    /// tints the byte with the color of its annotation
+    pub fn annotation(self, annotation: Option<ThemeColor>) -> Self {
+        ByteData { annotation, ..self }
+    }
+    /// marks the difference to the byte of the other file as ignored
+    pub fn ignored(self, is_ignored: bool) -> Self {
+        ByteData { is_ignored, ..self }
+    }
+    /// marks the byte as overwritten in edit mode
+    pub fn edited(self, is_edited: bool) -> Self {
+        ByteData { is_edited, ..self }
+    }
+    /// colors the difference by the descendant that changed it
+    pub fn change(self, change: Option<Change>) -> Self {
+        ByteData { change, ..self }
+    }
+    /// whether the byte differs from the byte of the other file in a way that is not ignored
+    pub fn differs(&self, other: ByteData) -> bool {
+        self.byte != other.byte && !self.is_ignored
+    }
+    pub fn is_search_result(&self) -> bool {
+        self.search_slot.is_some()
+    }
 }
 
-/// Formats the addresses that get displayed on the lower right of the screen
-pub fn disp_bottom_addr(addresses: [Option<usize>; 2], digits: u8) -> String {
-    let diff = if let [Some(a), Some(b)] = addresses {
-        let d = (b as isize).wrapping_sub(a as isize);
-        if d < 0 {
-            format!("(-{:0digits$x})", -d, digits = digits as usize)
-        } else {
-            format!("(+{:0digits$x})", d, digits = digits as usize)
-        }
-    } else {
-        format!("  {:digits$} ", " ", digits = digits as usize)
-    };
-    let addr = |a| {
-        if let Some(x) = a {
-            format!("{:0digits$x}", x, digits = digits as usize)
-        } else {
-            format!("{:digits$}", " ", digits = digits as usize)
-        }
-    };
-    format!(" {}|{}{diff}", addr(addresses[0]), addr(addresses[1]))
+pub fn byte(data: ByteData) -> Option<u8> {
+    data.byte
 }
 
 /// Contains two hex digits of a byte and a space behind it, or just three spaces for None
@@ -120,13 +160,106 @@ fn disp_mixed(h: Option<u8>) -> String {
     }
 }
 
-pub fn disp_ascii(h: Option<u8>) -> String {
-    match h {
-        Some(c @ b' '..=b'~') => c as char,
-        Some(_) => '.',
+/// The characters of the EBCDIC code page 037 from 0x40 on, the bytes below are control characters
+const EBCDIC_037: [char; 0xc0] = [
+    ' ', '\u{a0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|', '&', 'é',
+    'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '¬', '-', '/', 'Â', 'Ä', 'À',
+    'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?', 'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï',
+    'Ì', '`', ':', '#', '@', '\'', '=', '"', 'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«',
+    '»', 'ð', 'ý', 'þ', '±', '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸',
+    'Æ', '¤', 'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®', '^',
+    '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', '[', ']', '¯', '¨', '´', '×', '{', 'A', 'B', 'C',
+    'D', 'E', 'F', 'G', 'H', 'I', '\u{ad}', 'ô', 'ö', 'ò', 'ó', 'õ', '}', 'J', 'K', 'L', 'M', 'N',
+    'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ', '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y',
+    'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û',
+    'Ü', 'Ù', 'Ú', '\u{9f}',
+];
+
+/// How the bytes are shown as characters in the text (ascii) column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum TextEncoding {
+    #[default]
+    Ascii = 0,
+    Latin1 = 1,
+    /// code page 037
+    Ebcdic = 2,
+    Utf8 = 3,
+}
+
+impl TextEncoding {
+    /// The characters of the bytes of a row, one for each byte and a space for missing bytes.
+    /// Bytes that are not printable in the encoding are shown as dots, and in UTF-8
+    /// the character is shown at the first byte of its sequence and the rest of the
+    /// sequence as dots.
+    pub fn decode(&self, bytes: &[Option<u8>]) -> Vec<char> {
+        let mut chars: Vec<char> = bytes.iter().map(|b| disp_text(*b, '.')).collect();
+        match self {
+            Self::Utf8 => {
+                let mut i = 0;
+                while i < bytes.len() {
+                    match utf8_sequence(&bytes[i..]) {
+                        Some((c, len)) => {
+                            chars[i] = printable(c);
+                            i += len;
+                        }
+                        None => i += 1,
+                    }
+                }
+            }
+            _ => {
+                for (c, b) in chars.iter_mut().zip(bytes) {
+                    if let Some(b) = b {
+                        *c = printable(self.decode_byte(*b));
+                    }
+                }
+            }
+        }
+        chars
+    }
+    /// The character of a single byte, which is only possible for the single byte encodings
+    fn decode_byte(&self, b: u8) -> char {
+        match self {
+            Self::Ascii | Self::Utf8 if b.is_ascii() => b as char,
+            Self::Ascii | Self::Utf8 => '.',
+            Self::Latin1 => b as char,
+            Self::Ebcdic => b.checked_sub(0x40).map_or('.', |i| EBCDIC_037[i as usize]),
+        }
+    }
+}
+
+/// A space for missing bytes and the given character otherwise
+fn disp_text(b: Option<u8>, c: char) -> char {
+    match b {
+        Some(_) => c,
         None => ' ',
     }
-    .into()
+}
+
+/// The character itself if it can be shown in one column of the text column, and a dot otherwise
+fn printable(c: char) -> char {
+    if !c.is_control() && c.width() == Some(1) {
+        c
+    } else {
+        '.'
+    }
+}
+
+/// The character of a valid UTF-8 sequence at the start of the bytes, with its length
+fn utf8_sequence(bytes: &[Option<u8>]) -> Option<(char, usize)> {
+    let len = match (*bytes.first()?)? {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return None,
+    };
+    let sequence = bytes
+        .get(..len)?
+        .iter()
+        .copied()
+        .collect::<Option<Vec<u8>>>()?;
+    let c = std::str::from_utf8(&sequence).ok()?.chars().next()?;
+    Some((c, len))
 }
 
 pub fn disp_column_blocks(h: Option<u8>) -> String {
@@ -157,18 +290,32 @@ fn disp_roman(h: Option<u8>) -> String {
     format!("{s:>9} ")
 }
 
-pub fn byte_effect(x: ByteData) -> Effect {
+/// Differences are shown in reverse video in monochrome mode, as the colors are missing,
+/// and when coloring by byte class, as the colors show the class instead
+pub fn byte_effect(x: ByteData, other: ByteData, inverted_differences: bool) -> Effect {
     Effect {
-        inverted: false,
-        bold: x.is_search_result,
+        inverted: inverted_differences && x.differs(other),
+        bold: x.is_search_result(),
     }
 }
 
+/// The selection takes precedence over search results, and the result
+/// under the cursor takes precedence over the other results.
+/// Results of the first query are only shown in bold, so that a single search
+/// looks the same as before, the other queries also get a background color.
+/// Annotated bytes are tinted unless they have any of these backgrounds.
 pub fn background_color(x: ByteData) -> BackgroundColor {
     if x.is_selected.is_active() {
         BackgroundColor::Highlight
+    } else if x.is_current_result {
+        BackgroundColor::CurrentResult
     } else {
-        BackgroundColor::Blank
+        match x.search_slot {
+            None | Some(0) => x
+                .annotation
+                .map_or(BackgroundColor::Blank, BackgroundColor::Annotation),
+            Some(slot) => BackgroundColor::Search(slot - 1),
+        }
     }
 }
 
@@ -180,6 +327,19 @@ pub fn spacer_background_color(x: ByteData, rtl: bool) -> BackgroundColor {
     }
 }
 
+/// The color of the class of a byte: null, printable ascii, whitespace and other ascii
+/// control characters, bytes with the high bit set and 0xff
+fn color_from_class(x: ByteData) -> Color {
+    match x.byte {
+        Some(0) => Color::ByteNull,
+        Some(b'!'..=b'~') => Color::BytePrintable,
+        Some(0x01..=0x7f) => Color::ByteControl,
+        Some(0xff) => Color::ByteFf,
+        Some(_) => Color::ByteHighBit,
+        None => Color::Unimportant,
+    }
+}
+
 /// Insertions/Deletions are typically green, mismatches red and same bytes white
 fn color_from_bytes(a: ByteData, b: ByteData) -> Color {
     match (a.byte, b.byte) {
@@ -219,7 +379,7 @@ pub enum DisplayMode {
 }
 
 impl DisplayMode {
-    fn size_per_byte(&self) -> usize {
+    pub fn size_per_byte(&self) -> usize {
         match self {
             Self::Roman => 10,
             Self::Binary => 9,
@@ -259,9 +419,19 @@ impl DisplayMode {
     pub fn can_scroll(&self) -> bool {
         !matches!(self, Self::Braille)
     }
+    /// The next of the numeric bases, going from hex over decimal and octal to binary
+    /// and back to hex. The other modes go to hex.
+    pub fn next_base(&self) -> Self {
+        match self {
+            Self::Hex => Self::Decimal,
+            Self::Decimal => Self::Octal,
+            Self::Octal => Self::Binary,
+            _ => Self::Hex,
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub enum ColumnSetting {
     #[default]
     Fit,
@@ -278,6 +448,55 @@ impl ColumnSetting {
     }
 }
 
+/// How the addresses in front of the rows are shown
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum AddressColumn {
+    /// with the digits of the largest address, rounded up to whole bytes
+    #[default]
+    Full,
+    /// with only as many digits as the largest address needs
+    Short,
+    Hidden,
+}
+
+/// What the second half shows in place of the bytes of the second file
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum DeltaMode {
+    /// the bytes of the second file
+    #[default]
+    Off,
+    /// the xor of the bytes of both files
+    Xor,
+    /// the byte of the second file minus the one of the first file, modulo 256
+    Delta,
+}
+
+impl DeltaMode {
+    /// The next mode, for switching through them with a key
+    pub fn next(self) -> Self {
+        match self {
+            DeltaMode::Off => DeltaMode::Xor,
+            DeltaMode::Xor => DeltaMode::Delta,
+            DeltaMode::Delta => DeltaMode::Off,
+        }
+    }
+    /// The description of the mode for the bottom line, if it is active
+    pub fn describe(self) -> Option<&'static str> {
+        match self {
+            DeltaMode::Off => None,
+            DeltaMode::Xor => Some("Second: XOR"),
+            DeltaMode::Delta => Some("Second: Delta"),
+        }
+    }
+    fn combine(self, first: u8, second: u8) -> u8 {
+        match self {
+            DeltaMode::Off => second,
+            DeltaMode::Xor => first ^ second,
+            DeltaMode::Delta => second.wrapping_sub(first),
+        }
+    }
+}
+
 impl FromStr for ColumnSetting {
     type Err = std::num::ParseIntError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -299,20 +518,277 @@ pub struct Style {
     pub ascii_col: bool,
     pub bars_col: bool,
     pub vertical: bool,
-    pub spacer: bool,
+    /// whether rows with differences get a background tint
+    pub diff_rows: bool,
+    /// whether long runs of identical bytes are folded into single rows in the aligned view
+    pub fold: bool,
+    /// whether a column at the right edge shows where the differences are in the whole files
+    pub diff_map: bool,
+    /// whether the colors of the bytes show their class instead of whether they differ
+    pub byte_classes: bool,
+    /// whether the colors of the bytes show the entropy of the bytes around them,
+    /// which takes precedence over the byte classes
+    pub entropy: bool,
+    /// the number of bytes of which the entropy is computed when coloring by entropy
+    pub entropy_window: u16,
+    /// the number of bytes that the word moves go, or 0 to go by the byte groups
+    pub word_stride: u8,
+    /// whether the word moves continue on the next or previous row at the row ends
+    pub word_wrap: bool,
+    /// the number of rows of the previous page that stay on the screen when paging
+    pub page_overlap: u16,
+    /// how the bytes are shown in the ascii column
+    pub encoding: TextEncoding,
+    /// the character that fills the hex cells of gaps in the alignment
+    pub gap_char: char,
+    /// the character shown for gaps in the alignment in the ascii column
+    pub gap_text_char: char,
+    /// the number of hex columns in a group, which are separated by a space,
+    /// or 1 if they are not grouped
+    pub group: u8,
     pub right_to_left: bool,
     pub column_count: ColumnSetting,
+    /// the column setting of the second half in the unaligned view if it has its own,
+    /// which the aligned view leaves out since its rows have to correspond
+    #[serde(skip)]
+    pub second_column_count: Option<ColumnSetting>,
     pub no_scroll: bool,
+    /// whether the index of the cursor in the alignment is shown in the bottom line
+    pub show_index: bool,
+    /// whether a row with the offsets of the columns is shown below the title line
+    pub ruler: bool,
+    pub address_column: AddressColumn,
+    /// what the second half shows in place of the bytes of the second file
+    pub delta: DeltaMode,
     #[serde(skip)]
     pub addr_width: u8,
+    /// the number of hex digits of the largest address
+    #[serde(skip)]
+    pub short_addr_width: u8,
+    /// the addresses of both files that the shown addresses are relative to, if any
+    #[serde(skip)]
+    pub relative_to: Option<[usize; 2]>,
+    pub theme: Theme,
+    /// set by NO_COLOR or `--color=never`
+    #[serde(skip)]
+    pub monochrome: bool,
+    /// detected from the terminal on startup
+    #[serde(skip)]
+    pub color_depth: ColorDepth,
+    /// detected from the terminal on startup, unless turned off with `--no-scroll`
+    #[serde(skip)]
+    pub can_scroll: bool,
+    /// only the first half is shown, when biodiff is started with a single file
+    #[serde(skip)]
+    pub single: bool,
+    /// the units that the bytes of the files were split into with `--unit`, which are shown
+    /// as single digits grouped by byte, with the unit in the byte after the addresses
+    #[serde(skip)]
+    pub unit: Unit,
 }
 
 impl Style {
-    fn size_per_byte(&self) -> usize {
-        self.mode.size_per_byte() + self.ascii_col as usize + self.bars_col as usize
+    pub fn size_per_byte(&self) -> usize {
+        self.cell_width() + self.ascii_col as usize + self.bars_col as usize
     }
+    /// The width of the hex cell of a byte, which is a single digit for split bytes
+    pub fn cell_width(&self) -> usize {
+        match self.unit {
+            Unit::Byte => self.mode.size_per_byte(),
+            Unit::Nibble | Unit::Bit => 1,
+        }
+    }
+    /// The number of hex columns in a group, which are the units of a byte for split bytes
+    pub fn group_size(&self) -> usize {
+        match self.unit {
+            Unit::Byte => self.group.max(1) as usize,
+            Unit::Nibble | Unit::Bit => self.unit.per_byte(),
+        }
+    }
+    /// The color of a byte compared to the byte of the other file, on the given row.
+    /// Ignored differences and the ones of a three-way comparison have colors of their own
+    /// unless the colors show something else, while edited bytes always stand out.
+    pub fn byte_color(&self, a: ByteData, b: ByteData, row: usize) -> Color {
+        if a.is_gap {
+            Color::Gap
+        } else if a.is_edited {
+            Color::Edited
+        } else if self.entropy {
+            a.byte
+                .map_or(Color::Unimportant, |_| Color::Entropy(a.entropy_level))
+        } else if self.byte_classes {
+            color_from_class(a)
+        } else if a.is_ignored {
+            Color::Ignored
+        } else if let Some(change) = a.change {
+            change.color()
+        } else {
+            self.mode.color(a, b, row)
+        }
+    }
+    /// The byte of a cell in the second half and the byte it is compared to, which are
+    /// the combination of the bytes of both files and zero unless the delta mode is off,
+    /// so that only non-zero combinations are shown as differences
+    pub fn second_half(&self, second: ByteData, first: ByteData) -> (ByteData, ByteData) {
+        if self.delta == DeltaMode::Off {
+            return (second, first);
+        }
+        let byte = match (first.byte, second.byte) {
+            (Some(x), Some(y)) => Some(self.delta.combine(x, y)),
+            _ => None,
+        };
+        let is_gap = byte.is_none() && (first.is_gap || second.is_gap);
+        let zero = ByteData {
+            byte: Some(0),
+            is_gap: false,
+            ..first
+        };
+        (
+            ByteData {
+                byte,
+                is_gap,
+                ..second
+            },
+            zero,
+        )
+    }
+    /// The color of a byte of one of the halves, see `byte_color`.
+    /// Combinations of the bytes in the second half are dim if they are zero.
+    pub fn half_color(&self, a: ByteData, b: ByteData, row: usize, first: bool) -> Color {
+        if first || self.delta == DeltaMode::Off {
+            return self.byte_color(a, b, row);
+        }
+        match a.byte {
+            _ if a.is_gap => Color::Gap,
+            _ if a.is_edited => Color::Edited,
+            _ if a.is_ignored => Color::Ignored,
+            Some(0) => Color::Unimportant,
+            Some(_) => Color::HexDiff,
+            None => Color::HexOneside,
+        }
+    }
+    /// The text of the hex cell of a byte, where gaps in the alignment are filled with the
+    /// gap character. When `short` is set, the space after hex values is left out.
+    pub fn disp_byte(&self, a: ByteData, short: bool) -> String {
+        if self.unit != Unit::Byte {
+            // combinations in the delta mode wrap around at the size of the unit
+            let mask = (1 << (8 / self.unit.per_byte())) - 1;
+            return match a.byte {
+                _ if a.is_gap => self.gap_char.to_string(),
+                Some(unit) => format!("{:x}", unit & mask),
+                None => String::from(" "),
+            };
+        }
+        if !a.is_gap {
+            return self.mode.disp(a.byte, short);
+        }
+        let mut out: String = repeat(self.gap_char)
+            .take(self.mode.size_per_byte() - 1)
+            .chain([' '])
+            .collect();
+        if short && matches!(self.mode, DisplayMode::Hex | DisplayMode::HexAsciiMix) {
+            let _ = out.pop();
+        }
+        out
+    }
+    /// The background of a cell in a row, which is tinted if the row has differences and
+    /// this is enabled. The selection and search results keep their background.
+    pub fn row_background(&self, bg: BackgroundColor, is_different: bool) -> BackgroundColor {
+        match bg {
+            BackgroundColor::Blank if self.diff_rows && is_different => BackgroundColor::DiffRow,
+            bg => bg,
+        }
+    }
+    /// whether differences are shown in reverse video, since the colors do not show them
+    pub fn inverted_differences(&self) -> bool {
+        self.monochrome || self.byte_classes || self.entropy
+    }
+    /// The colors of the theme as supported by the terminal
+    pub fn palette(&self) -> Palette {
+        self.theme.palette().degrade(self.color_depth)
+    }
+    /// The number of characters of an address, including the sign of relative ones
+    /// and the unit in the byte of split bytes
+    pub fn addr_digits(&self) -> usize {
+        let digits = match self.address_column {
+            AddressColumn::Short => self.short_addr_width,
+            AddressColumn::Full | AddressColumn::Hidden => self.addr_width,
+        };
+        digits as usize + self.relative_to.is_some() as usize + self.unit_suffix_width()
+    }
+    /// The width of the `:n` after the byte of an address of split bytes
+    fn unit_suffix_width(&self) -> usize {
+        match self.unit {
+            Unit::Byte => 0,
+            Unit::Nibble | Unit::Bit => 2,
+        }
+    }
+    /// An address in hex with `width` digits, followed by a colon and the unit in the byte
+    /// for split bytes
+    pub fn hex_addr(&self, addr: usize, width: usize) -> String {
+        if self.unit == Unit::Byte {
+            return format!("{addr:0width$x}");
+        }
+        let (byte, unit) = self.unit.split_addr(addr);
+        format!("{byte:0width$x}:{unit}")
+    }
+    /// width of the address column in front of the rows, including the space after it
     pub fn addr_size(&self) -> usize {
-        self.addr_width as usize + 1
+        match self.address_column {
+            AddressColumn::Hidden => 0,
+            AddressColumn::Full | AddressColumn::Short => self.addr_digits() + 1,
+        }
+    }
+    /// The offset of an address of the first or second file from its relative base
+    fn relative_addr(&self, addr: usize, second: bool) -> isize {
+        let base = self.relative_to.map_or(0, |base| base[second as usize]);
+        (addr as isize).wrapping_sub(base as isize)
+    }
+    /// An address of the first or second file as it is shown, which has a sign
+    /// if it is relative, or spaces if there is none
+    pub fn format_addr(&self, addr: Option<usize>, second: bool) -> String {
+        let digits = self.addr_digits();
+        let hex_digits = digits - self.relative_to.is_some() as usize - self.unit_suffix_width();
+        match (addr, self.relative_to) {
+            (None, _) => " ".repeat(digits),
+            (Some(addr), None) => self.hex_addr(addr, hex_digits),
+            (Some(addr), Some(_)) => {
+                let offset = self.relative_addr(addr, second);
+                let sign = if offset < 0 { '-' } else { '+' };
+                format!("{sign}{}", self.hex_addr(offset.unsigned_abs(), hex_digits))
+            }
+        }
+    }
+    /// The address in front of a row with a space after it, or nothing if they are hidden
+    pub fn line_addr(&self, addr: Option<usize>, second: bool) -> String {
+        match self.address_column {
+            AddressColumn::Hidden => String::new(),
+            AddressColumn::Full | AddressColumn::Short => {
+                format!("{} ", self.format_addr(addr, second))
+            }
+        }
+    }
+    /// Formats the addresses that get displayed on the lower right of the screen,
+    /// with the distance between them in hex and decimal (which counts the units
+    /// of split bytes)
+    pub fn bottom_addr(&self, addresses: [Option<usize>; 2]) -> String {
+        let digits = self.addr_digits() - self.relative_to.is_some() as usize;
+        let diff = if let [Some(a), Some(b)] = addresses {
+            let d = self
+                .relative_addr(b, true)
+                .wrapping_sub(self.relative_addr(a, false));
+            let sign = if d < 0 { '-' } else { '+' };
+            let d = d.unsigned_abs();
+            let hex = self.hex_addr(d, digits - self.unit_suffix_width());
+            format!("({sign}{hex} = {sign}{d})")
+        } else {
+            format!("  {:digits$} ", " ")
+        };
+        format!(
+            " {}|{}{diff}",
+            self.format_addr(addresses[0], false),
+            self.format_addr(addresses[1], true)
+        )
     }
     /// width of n columns
     ///
@@ -322,12 +798,19 @@ impl Style {
         if n == 0 {
             return 0;
         }
-        self.mode.size_per_byte() * n
-            + if self.spacer {
-                (n + SPACER_PERIOD - 1) / SPACER_PERIOD - 1
-            } else {
-                0
-            }
+        self.cell_width() * n + self.spacers_before(n - 1)
+    }
+    /// the number of spaces between groups in front of the nth hex column
+    fn spacers_before(&self, n: usize) -> usize {
+        match self.group_size() {
+            1 => 0,
+            group => n / group,
+        }
+    }
+    /// whether the nth hex column on the screen is the last one of its group
+    pub fn ends_group(&self, n: usize) -> bool {
+        let group = self.group_size();
+        group > 1 && n % group == group - 1
     }
     /// width of one ascii column
     pub fn ascii_width(&self, n: usize) -> usize {
@@ -377,8 +860,8 @@ impl Style {
     }
     /// the position of the first character of the nth hex column
     pub fn nth_column_pos(&self, n: usize) -> usize {
-        self.mode.size_per_byte() * n
-            + if self.spacer { n / SPACER_PERIOD } else { 0 }
+        self.cell_width() * n
+            + self.spacers_before(n)
             + if self.right_to_left {
                 0
             } else {
@@ -386,6 +869,24 @@ impl Style {
             }
             + FRONT_PAD.width()
     }
+    /// whether the second half is shown below the first one, which is not the case
+    /// if it is not shown at all
+    pub fn stacked(&self) -> bool {
+        self.vertical && !self.single
+    }
+    /// the number of rows above the bytes of each half, which are the title
+    /// and the column ruler if it is shown
+    pub fn header_height(&self) -> usize {
+        1 + self.ruler as usize
+    }
+    /// the number of halves next to each other on a line
+    fn halves_per_line(&self) -> usize {
+        if self.vertical || self.single {
+            1
+        } else {
+            2
+        }
+    }
     /// the amount of characters that are on a line regardless of column number
     fn const_overhead(&self) -> usize {
         let single_overhead = self.addr_size()
@@ -396,45 +897,78 @@ impl Style {
                 0
             }
             + if self.bars_col { MIDDLE_PAD.width() } else { 0 };
-        if self.vertical {
-            single_overhead
+        let map = if self.diff_map { DIFF_MAP_WIDTH } else { 0 };
+        if self.halves_per_line() == 1 {
+            single_overhead + map
         } else {
-            2 * single_overhead + MIDDLE_PAD.width()
+            2 * single_overhead + MIDDLE_PAD.width() + map
         }
     }
     /// returns the number of columns that are displayed on a given display width
     /// Goes in steps of 8 above 24, steps of 4 for 8 - 24 and steps of 1 for < 8
     /// in case the column_count is not set, otherwise it uses the column_count
     pub fn get_doublehex_dims(&self, columns: usize, rows: usize) -> ((usize, usize), usize) {
-        let y = if self.vertical {
-            rows.saturating_sub(3) / 2
+        // the headers of the halves and the bottom line
+        let y = if self.stacked() {
+            rows.saturating_sub(2 * self.header_height() + 1) / 2
         } else {
-            rows.saturating_sub(2)
+            rows.saturating_sub(self.header_height() + 1)
         };
-        let max_col = if columns <= self.const_overhead() {
-            1
+        let (x, bytes_per_row) = self.fit_columns(self.column_count, self.max_columns(columns));
+        ((x, y), bytes_per_row)
+    }
+    /// The number of shown columns and the bytes per row of the second half if it has
+    /// its own column setting, on a display of the given width.
+    /// Both halves get the same width for their columns, see `get_doublehex_dims`.
+    pub fn second_half_dims(&self, columns: usize) -> Option<(usize, usize)> {
+        let setting = self.second_column_count.filter(|_| !self.single)?;
+        Some(self.fit_columns(setting, self.max_columns(columns)))
+    }
+    /// The smallest display the layout fits on, with a column of bytes in each half and
+    /// the rows that the cursor keeps away from the edges. Smaller displays get a message.
+    pub fn min_size(&self) -> (usize, usize) {
+        let columns = self.const_overhead() + self.size_per_byte() * self.halves_per_line();
+        let rows = if self.stacked() {
+            2 * (self.header_height() + MIN_ROWS) + 1
         } else {
-            let available_col = columns - self.const_overhead();
-            let multiplicity = if self.vertical { 1 } else { 2 };
-            let unit_width = self.size_per_byte() * multiplicity;
-            // take out one space from the available columns for each 8 units
-            let without_spacer = if self.spacer {
-                available_col
-                    - available_col / ((self.size_per_byte() * SPACER_PERIOD + 1) * multiplicity)
-                        * multiplicity
-            } else {
-                available_col
-            };
-            without_spacer / unit_width
+            self.header_height() + MIN_ROWS + 1
+        };
+        (columns, rows)
+    }
+    /// The most hex columns of one half that fit on a display of the given width,
+    /// which is one column even if that does not fit, so that rows are never empty
+    fn max_columns(&self, columns: usize) -> usize {
+        let available_col = columns.saturating_sub(self.const_overhead());
+        let multiplicity = self.halves_per_line();
+        let unit_width = self.size_per_byte() * multiplicity;
+        // take out one space from the available columns for each group
+        let group = self.group_size();
+        let without_spacer = if group > 1 {
+            let group_width = self.size_per_byte() * group + 1;
+            available_col - available_col / (group_width * multiplicity) * multiplicity
+        } else {
+            available_col
         };
-        let x = match self.column_count {
+        (without_spacer / unit_width).max(1)
+    }
+    /// The number of shown columns and the bytes per row for a column setting,
+    /// with at most `max_col` columns being shown
+    fn fit_columns(&self, setting: ColumnSetting, max_col: usize) -> (usize, usize) {
+        let x = match setting {
             ColumnSetting::Fit => {
-                if max_col < 8 {
+                let x = if max_col < 8 {
                     max_col
                 } else if max_col < 24 {
                     max_col / 4 * 4
                 } else {
                     max_col / 8 * 8
+                };
+                // only whole groups, unless not even one fits
+                let group = self.group_size();
+                if x >= group {
+                    x / group * group
+                } else {
+                    x
                 }
             }
             ColumnSetting::Fixed(n) => max_col.min(n as usize),
@@ -446,12 +980,12 @@ impl Style {
                 }
             }
         };
-        let bytes_per_row = match self.column_count {
+        let bytes_per_row = match setting {
             ColumnSetting::Fit => x,
             ColumnSetting::Fixed(n) => n as usize,
             ColumnSetting::Multiple(n) => (x / n as usize * n as usize).max(n as usize),
         };
-        ((x, y), bytes_per_row)
+        (x, bytes_per_row)
     }
 }
 
@@ -462,11 +996,280 @@ impl Default for Style {
             ascii_col: false,
             bars_col: false,
             vertical: false,
-            spacer: false,
+            diff_rows: false,
+            fold: false,
+            diff_map: false,
+            byte_classes: false,
+            entropy: false,
+            entropy_window: 32,
+            word_stride: 0,
+            word_wrap: false,
+            page_overlap: 2,
+            encoding: TextEncoding::Ascii,
+            gap_char: ' ',
+            gap_text_char: ' ',
+            group: 1,
             right_to_left: false,
             no_scroll: false,
+            show_index: false,
+            ruler: false,
             column_count: ColumnSetting::Fit,
+            second_column_count: None,
+            address_column: AddressColumn::Full,
+            delta: DeltaMode::Off,
             addr_width: 0,
+            short_addr_width: 0,
+            relative_to: None,
+            theme: Theme::Dark,
+            monochrome: false,
+            color_depth: ColorDepth::Basic,
+            can_scroll: true,
+            single: false,
+            unit: Unit::Byte,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn decode(encoding: TextEncoding, bytes: &[u8]) -> String {
+        let bytes: Vec<_> = bytes.iter().copied().map(Some).collect();
+        encoding.decode(&bytes).into_iter().collect()
+    }
+    #[test]
+    fn byte_classes() {
+        let style = Style {
+            byte_classes: true,
+            ..Style::default()
+        };
+        let data = |byte| ByteData::new(byte, None, SelectionStatus::default());
+        let colors: Vec<_> = [0x00, b'a', b' ', b'\n', 0x7f, 0x80, 0xfe, 0xff]
+            .map(|x| format!("{:?}", style.byte_color(data(Some(x)), data(Some(x)), 0)))
+            .to_vec();
+        assert_eq!(
+            colors,
+            [
+                "ByteNull",
+                "BytePrintable",
+                "ByteControl",
+                "ByteControl",
+                "ByteControl",
+                "ByteHighBit",
+                "ByteHighBit",
+                "ByteFf"
+            ]
+        );
+        // differences are shown in reverse video instead, and search results stay bold
+        let effect = byte_effect(data(Some(1)), data(Some(2)), style.inverted_differences());
+        assert!(effect.inverted && !effect.bold);
+        let effect = byte_effect(data(Some(1)), data(Some(1)), style.inverted_differences());
+        assert!(!effect.inverted);
+    }
+    #[test]
+    fn delta() {
+        let mut style = Style::default();
+        let byte = |byte, is_gap| ByteData {
+            byte,
+            is_gap,
+            ..ByteData::default()
+        };
+        let pair = style.second_half(byte(Some(0x0f), false), byte(Some(0x10), false));
+        assert_eq!((pair.0.byte, pair.1.byte), (Some(0x0f), Some(0x10)));
+        style.delta = DeltaMode::Xor;
+        let pair = style.second_half(byte(Some(0x0f), false), byte(Some(0x10), false));
+        assert_eq!((pair.0.byte, pair.1.byte), (Some(0x1f), Some(0)));
+        style.delta = DeltaMode::Delta;
+        let pair = style.second_half(byte(Some(0x0f), false), byte(Some(0x10), false));
+        assert_eq!(pair.0.byte, Some(0xff));
+        assert!(matches!(
+            style.half_color(pair.0, pair.1, 0, false),
+            Color::HexDiff
+        ));
+        // gaps stay gaps
+        let pair = style.second_half(byte(Some(0x0f), false), byte(None, true));
+        assert!(pair.0.is_gap && pair.0.byte.is_none());
+    }
+    #[test]
+    fn addresses() {
+        let mut style = Style {
+            addr_width: 4,
+            short_addr_width: 3,
+            ..Style::default()
+        };
+        assert_eq!(style.line_addr(Some(0x1a0), false), "01a0 ");
+        assert_eq!(style.line_addr(None, false), "     ");
+        assert_eq!(
+            style.bottom_addr([Some(0x10), Some(0x8)]),
+            " 0010|0008(-0008 = -8)"
+        );
+        style.address_column = AddressColumn::Short;
+        assert_eq!(style.line_addr(Some(0x1a0), false), "1a0 ");
+        assert_eq!(style.addr_size(), 4);
+        style.address_column = AddressColumn::Hidden;
+        assert_eq!(style.line_addr(Some(0x1a0), false), "");
+        assert_eq!(style.addr_size(), 0);
+        // the bottom line still shows the addresses
+        assert_eq!(
+            style.bottom_addr([Some(0x10), None]),
+            format!(" 0010|{}", " ".repeat(11))
+        );
+        style.address_column = AddressColumn::Full;
+        style.relative_to = Some([0x100, 0x200]);
+        assert_eq!(style.line_addr(Some(0x1a0), false), "+00a0 ");
+        assert_eq!(style.line_addr(Some(0x1a0), true), "-0060 ");
+        assert_eq!(
+            style.bottom_addr([Some(0x100), Some(0x210)]),
+            " +0000|+0010(+0010 = +16)"
+        );
+    }
+    #[test]
+    fn split_units() {
+        let style = Style {
+            addr_width: 2,
+            unit: Unit::Bit,
+            gap_char: '-',
+            ..Style::default()
+        };
+        assert_eq!(style.line_addr(Some(0x95), false), "12:5 ");
+        assert_eq!(
+            style.bottom_addr([Some(0x95), Some(0x98)]),
+            " 12:5|13:0(+00:3 = +3)"
+        );
+        let unit = |byte| ByteData {
+            byte,
+            ..ByteData::default()
+        };
+        assert_eq!(style.disp_byte(unit(Some(1)), false), "1");
+        assert_eq!(style.disp_byte(unit(None).gap(true), true), "-");
+        // the bits of each byte are a group, with a space after it
+        assert_eq!(style.nth_column_pos(8), 15);
+        assert!(style.ends_group(7) && !style.ends_group(8));
+        let nibbles = Style {
+            unit: Unit::Nibble,
+            delta: DeltaMode::Delta,
+            ..style
+        };
+        let (delta, _) = nibbles.second_half(unit(Some(0x1)), unit(Some(0x2)));
+        assert_eq!(nibbles.disp_byte(delta, false), "f");
+    }
+    #[test]
+    fn row_background() {
+        let mut style = Style::default();
+        let tint = |style: &Style, bg, is_different| {
+            format!("{:?}", style.row_background(bg, is_different))
+        };
+        assert_eq!(tint(&style, BackgroundColor::Blank, true), "Blank");
+        style.diff_rows = true;
+        assert_eq!(tint(&style, BackgroundColor::Blank, true), "DiffRow");
+        assert_eq!(tint(&style, BackgroundColor::Blank, false), "Blank");
+        // the selection and search results are shown over the tint
+        assert_eq!(tint(&style, BackgroundColor::Highlight, true), "Highlight");
+        assert_eq!(tint(&style, BackgroundColor::Search(1), true), "Search(1)");
+    }
+    #[test]
+    fn gaps() {
+        let style = Style {
+            gap_char: '-',
+            ..Style::default()
+        };
+        let gap = ByteData::default().gap(true);
+        assert_eq!(style.disp_byte(gap, false), "-- ");
+        assert_eq!(style.disp_byte(gap, true), "--");
+        assert_eq!(style.disp_byte(ByteData::default(), false), "   ");
+        let binary = Style {
+            mode: DisplayMode::Binary,
+            ..style
+        };
+        assert_eq!(binary.disp_byte(gap, false), "-------- ");
+        assert!(matches!(style.byte_color(gap, gap, 0), Color::Gap));
+    }
+    #[test]
+    fn next_base() {
+        let mut mode = DisplayMode::Braille;
+        let mut widths = Vec::new();
+        for _ in 0..5 {
+            mode = mode.next_base();
+            widths.push(mode.disp(Some(5), false).len());
+        }
+        assert_eq!(widths, [3, 4, 4, 9, 3]);
+        assert_eq!(DisplayMode::Octal.disp(Some(8), false), "010 ");
+    }
+    #[test]
+    fn control_characters() {
+        let bytes = b"a\0\n\t\x7f\x85\x9f\xa0z";
+        assert_eq!(decode(TextEncoding::Ascii, bytes), "a.......z");
+        assert_eq!(decode(TextEncoding::Latin1, bytes), "a......\u{a0}z");
+        assert_eq!(decode(TextEncoding::Utf8, bytes), "a.......z");
+        assert_eq!(TextEncoding::Ascii.decode(&[Some(b'a'), None]), ['a', ' ']);
+    }
+    #[test]
+    fn latin1() {
+        assert_eq!(
+            decode(TextEncoding::Latin1, b"\xe4\xf6\xfc\xdf\xff"),
+            "äöüßÿ"
+        );
+    }
+    #[test]
+    fn ebcdic() {
+        // "Hello, World!" in code page 037
+        let hello = b"\xc8\x85\x93\x93\x96\x6b\x40\xe6\x96\x99\x93\x84\x5a";
+        assert_eq!(decode(TextEncoding::Ebcdic, hello), "Hello, World!");
+        assert_eq!(
+            decode(TextEncoding::Ebcdic, b"\xf0\xf9\x81\xa9\xc1\xe9"),
+            "09azAZ"
+        );
+        // the characters that differ between the EBCDIC code pages
+        assert_eq!(
+            decode(TextEncoding::Ebcdic, b"\x4a\x4f\x5f\xad\xba\xbd\xe0"),
+            "¢|¬Ý[¨\\"
+        );
+        // control characters, including the newline 0x25 and the last byte
+        assert_eq!(
+            decode(TextEncoding::Ebcdic, b"\x00\x15\x25\x3f\xff"),
+            "....."
+        );
+    }
+    #[test]
+    fn utf8() {
+        assert_eq!(decode(TextEncoding::Utf8, "aä€b".as_bytes()), "aä.€..b");
+        // an emoji takes two columns and is not shown
+        assert_eq!(decode(TextEncoding::Utf8, "😀".as_bytes()), "....");
+        // cut off sequences, stray continuation bytes and overlong encodings
+        assert_eq!(
+            decode(TextEncoding::Utf8, b"\xe2\x82a\x80\xc0\xafb"),
+            "..a...b"
+        );
+        let cut = [Some(0xc3), None, Some(b'a')];
+        assert_eq!(TextEncoding::Utf8.decode(&cut), ['.', ' ', 'a']);
+    }
+    #[test]
+    fn tiny_displays() {
+        let style = Style {
+            addr_width: 8,
+            ascii_col: true,
+            ..Style::default()
+        };
+        let stacked = Style {
+            vertical: true,
+            ..style
+        };
+        for style in [style, stacked] {
+            for columns in [0, 1, 5] {
+                // at least one byte per row, and no rows instead of underflowing ones
+                assert_eq!(style.get_doublehex_dims(columns, 0), ((1, 0), 1));
+                let fixed = Style {
+                    column_count: ColumnSetting::Fixed(16),
+                    ..style
+                };
+                assert_eq!(fixed.get_doublehex_dims(columns, 0), ((1, 0), 16));
+            }
+            // the smallest size has room for a column and the cursor
+            let (columns, rows) = style.min_size();
+            let ((x, y), _) = style.get_doublehex_dims(columns, rows);
+            assert_eq!((x, y), (1, MIN_ROWS));
+            let ((_, y), _) = style.get_doublehex_dims(columns - 1, rows - 1);
+            assert_eq!(y, MIN_ROWS - 1);
         }
     }
 }
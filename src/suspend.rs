@@ -0,0 +1,70 @@
+//! Suspending to the shell with Ctrl-Z like other programs in a shell with job control.
+//!
+//! In raw mode the terminal does not turn Ctrl-Z into SIGTSTP, so the key arrives as the
+//! `Suspend` action instead, and the frontend stops the process itself after restoring
+//! the terminal, setting it up again once the process is continued. A SIGTSTP sent from
+//! outside (like with `kill -TSTP`) is caught and turned into the same action.
+//! Without job control on Windows, nothing is stopped and the view is just redrawn.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether a caught SIGTSTP asked to suspend, which the event reader has not handled yet
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a SIGTSTP was caught since the last call, so the next event is a `Suspend` action
+pub fn take_request() -> bool {
+    REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Catches SIGTSTP sent while the terminal is set up, which would otherwise stop the
+/// process with the terminal still in raw mode and the alternate screen
+#[cfg(unix)]
+pub fn catch_stop_signal() {
+    use std::sync::Once;
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // SAFETY: the action is fully initialized and the handler only uses
+        // async-signal-safe functions
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigtstp as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGTSTP, &action, std::ptr::null_mut());
+        }
+    });
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigtstp(_: libc::c_int) {
+    REQUESTED.store(true, Ordering::Relaxed);
+    // wakes up the event reader with a resize event, which then sees the request
+    // SAFETY: raise is async-signal-safe
+    unsafe {
+        libc::raise(libc::SIGWINCH);
+    }
+}
+
+/// Stops the process like the default action of SIGTSTP, returning once it is continued
+/// (by `fg` in the shell). The terminal has to be restored before and set up again after.
+#[cfg(unix)]
+pub fn stop() {
+    // SAFETY: the actions are fully initialized or written by sigaction before being used
+    unsafe {
+        let mut default: libc::sigaction = std::mem::zeroed();
+        default.sa_sigaction = libc::SIG_DFL;
+        libc::sigemptyset(&mut default.sa_mask);
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        libc::sigaction(libc::SIGTSTP, &default, &mut previous);
+        // the default action stops the whole process, so this returns after SIGCONT
+        libc::raise(libc::SIGTSTP);
+        libc::sigaction(libc::SIGTSTP, &previous, std::ptr::null_mut());
+    }
+}
+
+/// There is no SIGTSTP to catch without job control
+#[cfg(not(unix))]
+pub fn catch_stop_signal() {}
+
+/// Nothing is stopped without job control, the view is just set up again
+#[cfg(not(unix))]
+pub fn stop() {}
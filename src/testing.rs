@@ -0,0 +1,16 @@
+//! Fixtures shared by the tests of the modules.
+
+use std::path::PathBuf;
+
+use crate::file::FileState;
+
+/// A file with the name and the bytes, see `FileState::from_bytes`
+pub fn file(name: &str, content: &[u8]) -> FileState {
+    FileState::from_bytes(name, content.to_vec())
+}
+
+/// A path in the temporary directory for the test with the name,
+/// which is unique to the process running the tests
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("biodiff-{name}-{}", std::process::id()))
+}
@@ -0,0 +1,475 @@
+use std::{env, fmt::Display, str::FromStr};
+
+use crossterm::style::Color as CrossColor;
+use cursive::theme::{BaseColor, Color as CursiveColor};
+use serde::{Deserialize, Serialize};
+
+/// The names of the 16 basic terminal colors, in the order of their ANSI index
+const BASIC_NAMES: [&str; 16] = [
+    "black",
+    "darkred",
+    "darkgreen",
+    "darkyellow",
+    "darkblue",
+    "darkmagenta",
+    "darkcyan",
+    "grey",
+    "darkgrey",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+];
+
+/// The RGB values of the basic colors (as in xterm), used for finding
+/// the nearest basic color
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The intensities of the 6x6x6 color cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// A color of a theme, written as "default", a basic color name like "darkred",
+/// a 256-color index like "208" or an RGB value like "#ff8700"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ThemeColor {
+    /// the default color of the terminal
+    Default,
+    /// one of the 16 basic colors, by their ANSI index
+    Basic(u8),
+    /// one of the 256 colors
+    Fixed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl ThemeColor {
+    /// Returns the approximate RGB value, if it is not the default color
+    fn rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            ThemeColor::Default => None,
+            ThemeColor::Basic(n) => Some(BASIC_RGB[n as usize % 16]),
+            ThemeColor::Fixed(n @ 0..=15) => Some(BASIC_RGB[n as usize]),
+            ThemeColor::Fixed(n @ 16..=231) => {
+                let n = n - 16;
+                let level = |x: u8| CUBE_LEVELS[x as usize % 6];
+                Some((level(n / 36), level(n / 6), level(n)))
+            }
+            ThemeColor::Fixed(n) => {
+                let grey = 8 + 10 * (n - 232);
+                Some((grey, grey, grey))
+            }
+            ThemeColor::Rgb(r, g, b) => Some((r, g, b)),
+        }
+    }
+    /// The color in CSS notation, or None for the default color
+    pub fn css(self) -> Option<String> {
+        let (r, g, b) = self.rgb()?;
+        Some(format!("#{r:02x}{g:02x}{b:02x}"))
+    }
+    /// Converts the color to one that the terminal can display
+    pub fn degrade(self, depth: ColorDepth) -> Self {
+        let rgb = match (self, depth) {
+            (ThemeColor::Default | ThemeColor::Basic(_), _)
+            | (ThemeColor::Fixed(_), ColorDepth::Fixed | ColorDepth::TrueColor)
+            | (ThemeColor::Rgb(..), ColorDepth::TrueColor) => return self,
+            (ThemeColor::Fixed(n @ 0..=15), ColorDepth::Basic) => return ThemeColor::Basic(n),
+            (_, _) => self.rgb().unwrap_or_default(),
+        };
+        match depth {
+            // the basic colors of the 256 colors are skipped since they are often changed
+            ColorDepth::Fixed => ThemeColor::Fixed(nearest(16..=255, rgb, |n| {
+                ThemeColor::Fixed(n).rgb().unwrap_or_default()
+            })),
+            _ => ThemeColor::Basic(nearest(0..=15, rgb, |n| BASIC_RGB[n as usize])),
+        }
+    }
+    pub fn to_cross(self) -> CrossColor {
+        match self {
+            ThemeColor::Default => CrossColor::Reset,
+            ThemeColor::Basic(n) => match n % 16 {
+                0 => CrossColor::Black,
+                1 => CrossColor::DarkRed,
+                2 => CrossColor::DarkGreen,
+                3 => CrossColor::DarkYellow,
+                4 => CrossColor::DarkBlue,
+                5 => CrossColor::DarkMagenta,
+                6 => CrossColor::DarkCyan,
+                7 => CrossColor::Grey,
+                8 => CrossColor::DarkGrey,
+                9 => CrossColor::Red,
+                10 => CrossColor::Green,
+                11 => CrossColor::Yellow,
+                12 => CrossColor::Blue,
+                13 => CrossColor::Magenta,
+                14 => CrossColor::Cyan,
+                _ => CrossColor::White,
+            },
+            ThemeColor::Fixed(n) => CrossColor::AnsiValue(n),
+            ThemeColor::Rgb(r, g, b) => CrossColor::Rgb { r, g, b },
+        }
+    }
+    pub fn to_cursiv(self) -> CursiveColor {
+        match self {
+            ThemeColor::Default => CursiveColor::TerminalDefault,
+            ThemeColor::Basic(n) => {
+                let base = BaseColor::from(n % 8);
+                if n % 16 < 8 {
+                    CursiveColor::Dark(base)
+                } else {
+                    CursiveColor::Light(base)
+                }
+            }
+            ThemeColor::Fixed(n) => CursiveColor::from_256colors(n),
+            ThemeColor::Rgb(r, g, b) => CursiveColor::Rgb(r, g, b),
+        }
+    }
+}
+
+/// Returns the candidate whose color has the smallest distance to `rgb`
+fn nearest(
+    candidates: impl Iterator<Item = u8>,
+    (r, g, b): (u8, u8, u8),
+    color: impl Fn(u8) -> (u8, u8, u8),
+) -> u8 {
+    let dist = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+    candidates
+        .min_by_key(|n| {
+            let (cr, cg, cb) = color(*n);
+            dist(r, cr) + dist(g, cg) + dist(b, cb)
+        })
+        .unwrap_or_default()
+}
+
+impl FromStr for ThemeColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        if s == "default" {
+            return Ok(ThemeColor::Default);
+        }
+        if let Some(n) = BASIC_NAMES.iter().position(|name| *name == s) {
+            return Ok(ThemeColor::Basic(n as u8));
+        }
+        if let Ok(n) = s.parse::<u8>() {
+            return Ok(ThemeColor::Fixed(n));
+        }
+        match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.is_ascii() => {
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+                match (channel(0), channel(2), channel(4)) {
+                    (Ok(r), Ok(g), Ok(b)) => Ok(ThemeColor::Rgb(r, g, b)),
+                    _ => Err(format!("invalid RGB color \"{s}\"")),
+                }
+            }
+            _ => Err(format!(
+                "unknown color \"{s}\", expected a color name, a number up to 255 or #rrggbb"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ThemeColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for ThemeColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThemeColor::Default => write!(f, "default"),
+            ThemeColor::Basic(n) => write!(f, "{}", BASIC_NAMES[*n as usize % 16]),
+            ThemeColor::Fixed(n) => write!(f, "{n}"),
+            ThemeColor::Rgb(r, g, b) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+impl From<ThemeColor> for String {
+    fn from(value: ThemeColor) -> Self {
+        value.to_string()
+    }
+}
+
+/// How many colors the terminal supports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorDepth {
+    #[default]
+    Basic,
+    Fixed,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Guesses the color support from the COLORTERM and TERM environment variables
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Fixed
+        } else {
+            ColorDepth::Basic
+        }
+    }
+}
+
+/// The colors used for each kind of byte and background
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub unimportant: ThemeColor,
+    pub same: ThemeColor,
+    pub same_secondary: ThemeColor,
+    pub diff: ThemeColor,
+    pub diff_secondary: ThemeColor,
+    pub oneside: ThemeColor,
+    pub oneside_secondary: ThemeColor,
+    /// gaps in the alignment
+    pub gap: ThemeColor,
+    /// differences that are ignored
+    pub ignored: ThemeColor,
+    /// bytes that were overwritten in edit mode
+    pub edited: ThemeColor,
+    /// differences of a three-way comparison that only ours, only theirs or both changed
+    pub ours: ThemeColor,
+    pub theirs: ThemeColor,
+    pub conflict: ThemeColor,
+    /// the colors of the byte classes when coloring by class
+    pub null: ThemeColor,
+    pub printable: ThemeColor,
+    pub control: ThemeColor,
+    pub high_bit: ThemeColor,
+    pub ff: ThemeColor,
+    /// the gradient when coloring by entropy, from low to high entropy
+    pub entropy: [ThemeColor; 8],
+    pub background: ThemeColor,
+    pub highlight: ThemeColor,
+    pub current_result: ThemeColor,
+    /// the tint of rows with differences
+    pub diff_row: ThemeColor,
+    /// backgrounds of the additional search queries
+    pub search: [ThemeColor; 3],
+    /// the background of annotated bytes without a color of their own
+    pub annotation: ThemeColor,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::dark()
+    }
+}
+
+impl Palette {
+    /// The original colors, for terminals with a dark background
+    pub fn dark() -> Self {
+        use ThemeColor::Basic;
+        Palette {
+            unimportant: Basic(8),
+            same: Basic(15),
+            same_secondary: Basic(11),
+            diff: Basic(9),
+            diff_secondary: Basic(1),
+            oneside: Basic(10),
+            oneside_secondary: Basic(2),
+            gap: Basic(8),
+            ignored: Basic(5),
+            edited: Basic(12),
+            ours: Basic(14),
+            theirs: Basic(13),
+            conflict: Basic(9),
+            null: Basic(8),
+            printable: Basic(14),
+            control: Basic(10),
+            high_bit: Basic(11),
+            ff: Basic(13),
+            entropy: [
+                Basic(8),
+                Basic(4),
+                Basic(12),
+                Basic(6),
+                Basic(14),
+                Basic(10),
+                Basic(11),
+                Basic(9),
+            ],
+            background: Basic(0),
+            highlight: Basic(8),
+            current_result: Basic(3),
+            diff_row: Basic(4),
+            search: [Basic(4), Basic(5), Basic(6)],
+            annotation: Basic(2),
+        }
+    }
+    /// Colors for terminals with a light background
+    pub fn light() -> Self {
+        use ThemeColor::Rgb;
+        Palette {
+            unimportant: Rgb(0x8a, 0x8a, 0x8a),
+            same: Rgb(0x1c, 0x1c, 0x1c),
+            same_secondary: Rgb(0x87, 0x5f, 0x00),
+            diff: Rgb(0xd7, 0x00, 0x00),
+            diff_secondary: Rgb(0x87, 0x00, 0x00),
+            oneside: Rgb(0x00, 0x87, 0x00),
+            oneside_secondary: Rgb(0x00, 0x5f, 0x00),
+            gap: Rgb(0x8a, 0x8a, 0x8a),
+            ignored: Rgb(0x87, 0x5f, 0xaf),
+            edited: Rgb(0x00, 0x5f, 0xd7),
+            ours: Rgb(0x00, 0x87, 0x87),
+            theirs: Rgb(0x87, 0x00, 0x87),
+            conflict: Rgb(0xd7, 0x00, 0x00),
+            null: Rgb(0x8a, 0x8a, 0x8a),
+            printable: Rgb(0x00, 0x87, 0x87),
+            control: Rgb(0x00, 0x87, 0x00),
+            high_bit: Rgb(0x87, 0x5f, 0x00),
+            ff: Rgb(0x87, 0x00, 0x87),
+            entropy: [
+                Rgb(0x8a, 0x8a, 0x8a),
+                Rgb(0x00, 0x00, 0xaf),
+                Rgb(0x00, 0x5f, 0xd7),
+                Rgb(0x00, 0x87, 0x87),
+                Rgb(0x00, 0x87, 0x5f),
+                Rgb(0x00, 0x87, 0x00),
+                Rgb(0xaf, 0x87, 0x00),
+                Rgb(0xd7, 0x00, 0x00),
+            ],
+            background: Rgb(0xff, 0xff, 0xff),
+            highlight: Rgb(0xd0, 0xd0, 0xd0),
+            current_result: Rgb(0xff, 0xd7, 0x5f),
+            diff_row: Rgb(0xff, 0xeb, 0xeb),
+            search: [
+                Rgb(0xaf, 0xd7, 0xff),
+                Rgb(0xff, 0xaf, 0xff),
+                Rgb(0xaf, 0xff, 0xff),
+            ],
+            annotation: Rgb(0xd7, 0xff, 0xd7),
+        }
+    }
+    /// Converts all colors to ones the terminal can display
+    pub fn degrade(self, depth: ColorDepth) -> Self {
+        let d = |c: ThemeColor| c.degrade(depth);
+        Palette {
+            unimportant: d(self.unimportant),
+            same: d(self.same),
+            same_secondary: d(self.same_secondary),
+            diff: d(self.diff),
+            diff_secondary: d(self.diff_secondary),
+            oneside: d(self.oneside),
+            oneside_secondary: d(self.oneside_secondary),
+            gap: d(self.gap),
+            ignored: d(self.ignored),
+            edited: d(self.edited),
+            ours: d(self.ours),
+            theirs: d(self.theirs),
+            conflict: d(self.conflict),
+            null: d(self.null),
+            printable: d(self.printable),
+            control: d(self.control),
+            high_bit: d(self.high_bit),
+            ff: d(self.ff),
+            entropy: self.entropy.map(d),
+            background: d(self.background),
+            highlight: d(self.highlight),
+            current_result: d(self.current_result),
+            diff_row: d(self.diff_row),
+            search: self.search.map(d),
+            annotation: d(self.annotation),
+        }
+    }
+}
+
+/// The theme selected in the config file, either `"dark"`, `"light"` or
+/// `{"custom": {...}}` with the colors of a palette (missing colors are taken from the dark theme)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    Custom(Palette),
+}
+
+impl Theme {
+    pub fn palette(&self) -> Palette {
+        match self {
+            Theme::Dark => Palette::dark(),
+            Theme::Light => Palette::light(),
+            Theme::Custom(palette) => *palette,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_colors() {
+        for color in ["default", "darkred", "white", "208", "#ff8700"] {
+            let parsed: ThemeColor = color.parse().unwrap();
+            assert_eq!(parsed.to_string(), color);
+        }
+        assert_eq!("DarkGrey".parse(), Ok(ThemeColor::Basic(8)));
+        assert!("256".parse::<ThemeColor>().is_err());
+        assert!("#ff87".parse::<ThemeColor>().is_err());
+        assert!("#gg8700".parse::<ThemeColor>().is_err());
+        assert!("orange".parse::<ThemeColor>().is_err());
+    }
+    #[test]
+    fn degrade_colors() {
+        let orange = ThemeColor::Rgb(0xff, 0x87, 0x00);
+        assert_eq!(orange.degrade(ColorDepth::TrueColor), orange);
+        assert_eq!(orange.degrade(ColorDepth::Fixed), ThemeColor::Fixed(208));
+        assert_eq!(orange.degrade(ColorDepth::Basic), ThemeColor::Basic(3));
+        assert_eq!(
+            ThemeColor::Fixed(232).degrade(ColorDepth::Basic),
+            ThemeColor::Basic(0)
+        );
+        assert_eq!(
+            ThemeColor::Fixed(9).degrade(ColorDepth::Basic),
+            ThemeColor::Basic(9)
+        );
+        // the dark theme is unchanged on all terminals
+        for depth in [ColorDepth::Basic, ColorDepth::Fixed, ColorDepth::TrueColor] {
+            assert_eq!(Palette::dark().degrade(depth), Palette::dark());
+        }
+    }
+    #[test]
+    fn theme_config() {
+        let theme: Theme = serde_json::from_str("\"light\"").unwrap();
+        assert_eq!(theme, Theme::Light);
+        let theme: Theme =
+            serde_json::from_str(r##"{"custom": {"diff": "#ff0000", "search": ["1", "2", "3"]}}"##)
+                .unwrap();
+        let palette = theme.palette();
+        assert_eq!(palette.diff, ThemeColor::Rgb(255, 0, 0));
+        assert_eq!(palette.search[2], ThemeColor::Fixed(3));
+        assert_eq!(palette.same, Palette::dark().same);
+        assert!(serde_json::from_str::<Theme>(r#"{"custom": {"diff": "nope"}}"#).is_err());
+        let json = serde_json::to_string(&theme).unwrap();
+        assert_eq!(serde_json::from_str::<Theme>(&json).unwrap(), theme);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{ops::Range, sync::mpsc::channel};
+
+use crate::{
+    align::{AlignAlgorithm, AlignElement, AlignedBlock},
+    backend::Color,
+    file::FileContent,
+    ignore::merge,
+};
+
+/// Which of the descendants of a common ancestor changed a difference between them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// only the first file given ("ours") differs from the base there
+    Ours,
+    /// only the second file given ("theirs") differs from the base there
+    Theirs,
+    /// both files differ from the base there, and from each other
+    Conflict,
+}
+
+impl Change {
+    pub fn color(self) -> Color {
+        match self {
+            Change::Ours => Color::Ours,
+            Change::Theirs => Color::Theirs,
+            Change::Conflict => Color::Conflict,
+        }
+    }
+    /// The text of the status bar when the jumps to differences stop at these changes
+    pub fn describe(self) -> &'static str {
+        match self {
+            Change::Ours => "Jumping to changes in ours",
+            Change::Theirs => "Jumping to changes in theirs",
+            Change::Conflict => "Jumping to conflicts",
+        }
+    }
+}
+
+/// The bytes of two files that differ from their common ancestor (the base), which tell
+/// for each difference between the files which of them changed it.
+/// Both files are aligned to the base as a whole once, so the changes stay the same when the
+/// files are realigned to each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreeWay {
+    /// the changed addresses of both files (in the whole files, like the ignored ones),
+    /// sorted and without overlaps. A byte that was deleted from the base is a change at the
+    /// address of the byte after it.
+    changed: [Vec<Range<usize>>; 2],
+    /// which of ours and theirs the shown files are, which are swapped along with them
+    sides: [Change; 2],
+    /// the kind of differences the jumps to the next and previous difference stop at,
+    /// or None for all of them
+    pub filter: Option<Change>,
+}
+
+impl ThreeWay {
+    /// Aligns both files to the base with the algorithm, which takes until both are aligned
+    pub fn new(algo: &AlignAlgorithm, base: FileContent, files: [FileContent; 2]) -> Self {
+        // both alignments run at the same time
+        let receivers = files.map(|file| {
+            let (sender, receiver) = channel::<AlignedBlock>();
+            let offset = file.base();
+            algo.start_align(base.clone(), file, (0, 0), sender);
+            (receiver, offset)
+        });
+        let changed = receivers.map(|(receiver, offset)| {
+            let mut changed = Vec::new();
+            // the channel closes when all alignment threads are done
+            for block in receiver {
+                if let AlignedBlock::Append(elements) | AlignedBlock::Prepend(elements) = block {
+                    changed.extend(changed_ranges(&elements, offset));
+                }
+            }
+            merge(changed)
+        });
+        ThreeWay {
+            changed,
+            sides: [Change::Ours, Change::Theirs],
+            filter: None,
+        }
+    }
+    /// Whether the address of the first or second file is in a changed range
+    fn contains(&self, second: bool, addr: usize) -> bool {
+        let ranges = &self.changed[second as usize];
+        let after = ranges.partition_point(|range| range.start <= addr);
+        after > 0 && ranges[after - 1].end > addr
+    }
+    /// Which of the files changed the bytes at the (shown) addresses, or None if neither did.
+    /// Gaps have the address of the byte after them, so that deletions count as changes.
+    pub fn change(&self, addresses: [Option<usize>; 2]) -> Option<Change> {
+        let changed =
+            [0, 1].map(|i| addresses[i].map_or(false, |addr| self.contains(i == 1, addr)));
+        match changed {
+            [true, true] => Some(Change::Conflict),
+            [true, false] => Some(self.sides[0]),
+            [false, true] => Some(self.sides[1]),
+            [false, false] => None,
+        }
+    }
+    /// Whether the jumps to differences stop at a difference that the change is
+    pub fn is_jump_target(&self, change: Option<Change>) -> bool {
+        self.filter.map_or(true, |filter| change == Some(filter))
+    }
+    /// Goes on to the next filter of the jumps, after all differences come the changes of
+    /// ours, then the ones of theirs, then the conflicts and then all differences again
+    pub fn cycle_filter(&mut self) {
+        self.filter = match self.filter {
+            None => Some(Change::Ours),
+            Some(Change::Ours) => Some(Change::Theirs),
+            Some(Change::Theirs) => Some(Change::Conflict),
+            Some(Change::Conflict) => None,
+        }
+    }
+    /// Exchanges the changes of the files, for when they are swapped
+    pub fn swap_files(&mut self) {
+        self.changed.swap(0, 1);
+        self.sides.swap(0, 1);
+    }
+}
+
+/// The addresses (plus the offset) of the bytes of the second file of the alignment that
+/// differ from the ones of the first file, with adjacent ones in the same range
+fn changed_ranges(elements: &[AlignElement], offset: usize) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for element in elements.iter().filter(|e| e.xbyte != e.ybyte) {
+        let addr = element.yaddr + offset;
+        match ranges.last_mut() {
+            Some(last) if last.end >= addr => last.end = last.end.max(addr + 1),
+            _ => ranges.push(addr..addr + 1),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::file::FileBytes;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn changes() {
+        let [base, ours, theirs] = [&b"0123456789"[..], &b"01x3456789"[..], &b"01234569"[..]]
+            .map(|bytes| Arc::new(FileBytes::from(bytes.to_vec())));
+        let mut three_way = ThreeWay::new(&AlignAlgorithm::default(), base, [ours, theirs]);
+        // the deleted 78 of theirs is a change at the address of the 9 after it
+        assert_eq!(three_way.changed, [vec![2..3], vec![7..8]]);
+        assert_eq!(three_way.change([Some(2), Some(2)]), Some(Change::Ours));
+        assert_eq!(three_way.change([Some(7), Some(7)]), Some(Change::Theirs));
+        assert_eq!(three_way.change([Some(2), Some(7)]), Some(Change::Conflict));
+        assert_eq!(three_way.change([Some(0), None]), None);
+        three_way.swap_files();
+        assert_eq!(three_way.change([Some(7), Some(7)]), Some(Change::Theirs));
+        assert_eq!(three_way.change([None, Some(2)]), Some(Change::Ours));
+        // the filter goes through all changes and back to all differences
+        assert!(three_way.is_jump_target(None));
+        three_way.cycle_filter();
+        assert!(three_way.is_jump_target(Some(Change::Ours)));
+        assert!(!three_way.is_jump_target(Some(Change::Conflict)));
+        for _ in 0..3 {
+            three_way.cycle_filter();
+        }
+        assert_eq!(three_way.filter, None);
+    }
+}
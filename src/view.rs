@@ -1,10 +1,52 @@
 mod aligned;
+mod diffmap;
+mod fold;
 mod unaligned;
 
-use std::ops::Range;
+use std::{collections::VecDeque, ops::Range};
 
 pub use aligned::{Aligned, AlignedMessage};
-pub use unaligned::Unaligned;
+pub use unaligned::{Unaligned, UnalignedMessage};
+
+/// How many changes of a view can be undone
+const UNDO_LEN: usize = 100;
+
+/// The states of a view before changes to its style, offset or selection, for going back
+/// to them. Cursor movements are not recorded, they are gone back over with the jump history.
+struct UndoStack<T> {
+    states: VecDeque<T>,
+}
+
+impl<T> Default for UndoStack<T> {
+    fn default() -> Self {
+        UndoStack {
+            states: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> UndoStack<T> {
+    /// Remembers the state before a change, forgetting the oldest one if there are too many
+    fn push(&mut self, state: T) {
+        self.states.push_back(state);
+        if self.states.len() > UNDO_LEN {
+            self.states.pop_front();
+        }
+    }
+    /// The state before the last change
+    fn pop(&mut self) -> Option<T> {
+        self.states.pop_back()
+    }
+    /// Forgets all states, for when they do not fit the view anymore
+    fn clear(&mut self) {
+        self.states.clear()
+    }
+}
+
+/// The title of the terminal window when comparing the two files
+fn window_title((first, second): &(String, String)) -> String {
+    format!("biodiff: {first} \u{2194} {second}")
+}
 
 fn next_difference(
     address: isize,
@@ -12,6 +54,10 @@ fn next_difference(
     forward: bool,
     is_different: impl Fn(isize) -> bool,
 ) -> isize {
+    // there is nothing to jump to if both files are empty
+    if range.is_empty() {
+        return address;
+    }
     let sign = if forward { 1 } else { -1 };
     let mut i = address;
     while range.contains(&i) && is_different(i) {
@@ -1,26 +1,52 @@
-use std::{array::from_fn, ops::Range, sync::mpsc::Sender};
+use std::{
+    array::from_fn,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    ops::Range,
+    path::Path,
+    sync::mpsc::Sender,
+    time::Instant,
+};
 
 use cursive::{Vec2, View};
 
 use crate::{
-    align::{AlignAlgorithm, AlignElement},
-    backend::{Action, Backend, Cursiv},
+    align::{AlignAlgorithm, AlignElement, AlignMode, AlignedBlock, Banded, DEFAULT_BLOCKSIZE},
+    backend::{Action, Backend, Cursiv, Dummy},
     cursor::{CursorActive, Move},
     datastruct::{DoubleVec, SignedArray},
-    doublehex::{DoubleHexContext, DoubleHexLine},
+    doublehex::{DoubleHexContext, DoubleHexLine, FileTitle},
     file::{FileContent, FileState},
-    search::{Query, SearchContext, SearchPair, SearchResults},
-    selection::Selections,
-    style::{ByteData, ColumnSetting},
+    notice::Severity,
+    report::html::{write_html_lines, HtmlOptions, DEFAULT_FOLD_THRESHOLD},
+    search::{
+        write_results_csv, CurrentResults, Query, SearchContext, SearchMessage, SearchOptions,
+        SearchPair, SearchResults,
+    },
+    selection::{selected_bytes, SelectedBytes, SelectionStatus, Selections},
+    style::{ByteData, Style},
+    theme::ThemeColor,
 };
 
-use super::next_difference;
+use super::{
+    diffmap::{map_index, DiffMap},
+    fold::Folds,
+    next_difference, window_title, UndoStack,
+};
 /// Enum that containts events but also allows
 /// messages for appending/prepending data to the Aligned view.
 pub enum AlignedMessage {
     UserEvent(Action),
     Append(Vec<AlignElement>),
     Prepend(Vec<AlignElement>),
+    /// the files have grown in follow mode, with a sender for the continued alignment
+    Grown([FileContent; 2], Sender<AlignedMessage>),
+    /// whether the files changed on disk since they were read
+    Modified([bool; 2]),
+    /// the chosen aligner failed on a block, with the message about it
+    Fallback(String),
+    /// the threads of the alignment are finished
+    Done,
 }
 
 impl From<Action> for AlignedMessage {
@@ -29,6 +55,19 @@ impl From<Action> for AlignedMessage {
     }
 }
 
+impl From<AlignedBlock> for AlignedMessage {
+    fn from(block: AlignedBlock) -> Self {
+        match block {
+            AlignedBlock::Append(elements) => AlignedMessage::Append(elements),
+            AlignedBlock::Prepend(elements) => AlignedMessage::Prepend(elements),
+            AlignedBlock::Fallback { aligner, error } => AlignedMessage::Fallback(format!(
+                "{aligner} failed ({error}), aligned with rust-bio instead"
+            )),
+            AlignedBlock::Done => AlignedMessage::Done,
+        }
+    }
+}
+
 /// A view that dynamically displays aligned files
 pub struct Aligned {
     data: DoubleVec<AlignElement>,
@@ -36,8 +75,37 @@ pub struct Aligned {
     searches: SearchPair,
     original: [FileContent; 2],
     selection: Selections,
+    /// the index in the alignment of the first byte in view
     index: isize,
+    /// the runs of identical bytes that are folded if the style says so
+    folds: Folds,
+    /// where the differences are, for the diff map
+    diff_map: DiffMap,
     pub dh: DoubleHexContext,
+    /// the parameters the alignment was started with, which are shown in the title line
+    /// and used for continuing the alignment when the files grow
+    algo: AlignAlgorithm,
+    /// whether the threads of the alignment are still sending blocks
+    aligning: bool,
+    /// only set once the files grow, as the channel is closed when the alignment is done otherwise
+    sender: Option<Sender<AlignedMessage>>,
+    /// the lengths of the files up to which the alignment is done (or being done)
+    aligned_lens: [usize; 2],
+    /// whether the files were swapped since the alignment was started, so that
+    /// the blocks of the alignment threads have to be mirrored
+    mirrored: bool,
+    /// the states before changes of the style or selection
+    undo_stack: UndoStack<Snapshot>,
+    /// the addresses the alignment started at, for leaving the view before anything is aligned
+    start: [usize; 2],
+    /// the shown addresses of a restored session, which the cursor goes to once they are aligned
+    restore: Option<[Option<usize>; 2]>,
+}
+
+/// What undo goes back to in the aligned view
+struct Snapshot {
+    style: Style,
+    selection: Selections,
 }
 
 impl Aligned {
@@ -48,21 +116,29 @@ impl Aligned {
     pub fn new(
         first: FileState,
         second: FileState,
-        dh: DoubleHexContext,
+        mut dh: DoubleHexContext,
         algo: &AlignAlgorithm,
         sel: [Option<Range<usize>>; 2],
         sender: Sender<AlignedMessage>,
     ) -> Self {
+        dh.bases = [first.content.base(), second.content.base()];
+        // the aligned view can not be edited
+        dh.editor.set_active(false);
+        // the rows of both halves have to correspond, so they share their columns
+        dh.second_grid = None;
+        // the alignment decides which bytes are next to each other
+        dh.offset_lock = false;
         let index = -(dh.cursor.get_index() as isize);
         let data = DoubleVec::new();
         let first_arc = first.content.clone();
         let second_arc = second.content.clone();
-        algo.start_align_with_selection(
-            [first_arc, second_arc],
-            sel,
-            [first.index, second.index],
-            sender,
-        );
+        let aligned_lens = [first_arc.len(), second_arc.len()];
+        let files = [first_arc, second_arc];
+        let start = [first.index, second.index];
+        match &dh.align_cache {
+            Some(cache) => cache.start_align(algo, files, sel, start, sender),
+            None => algo.start_align_with_selection(files, sel, start, sender),
+        }
         Aligned {
             data,
             filenames: (first.name, second.name),
@@ -70,34 +146,194 @@ impl Aligned {
             searches: SearchPair(first.search, second.search),
             selection: Selections::new(),
             index,
+            folds: Folds::default(),
+            diff_map: DiffMap::default(),
             dh,
+            algo: *algo,
+            aligning: true,
+            sender: None,
+            aligned_lens,
+            mirrored: false,
+            undo_stack: UndoStack::default(),
+            start: [first.index, second.index],
+            restore: None,
+        }
+    }
+    /// Shows the files the other way around by mirroring the alignment, without aligning again
+    pub fn swap_files(&mut self) {
+        self.data
+            .for_each_mut(|alignel| *alignel = alignel.mirror());
+        let (first, second) = std::mem::take(&mut self.filenames);
+        self.filenames = (second, first);
+        self.original.swap(0, 1);
+        self.searches.swap();
+        self.selection.swap();
+        self.aligned_lens.swap(0, 1);
+        self.start.swap(0, 1);
+        self.mirrored = !self.mirrored;
+        self.dh.swap_files();
+        // the selections that were remembered are the other way around
+        self.undo_stack.clear();
+    }
+    /// The names of both files
+    pub fn filenames(&self) -> [&str; 2] {
+        [&self.filenames.0, &self.filenames.1]
+    }
+    /// The contents of both files
+    pub fn files(&self) -> [FileContent; 2] {
+        self.original.clone()
+    }
+    /// The contents of both files with their edits
+    pub fn edited_files(&self) -> [FileContent; 2] {
+        self.dh.edited_files(self.files())
+    }
+    /// Sets whether the files changed on disk and redraws the title line
+    pub fn set_modified<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        modified: [bool; 2],
+    ) -> std::io::Result<()> {
+        self.dh.modified = modified;
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// What is running in the background, for the busy indicator in the title line
+    fn busy(&self) -> Vec<&'static str> {
+        [
+            (self.aligning, "aligning"),
+            (self.searches.is_running(), "searching"),
+        ]
+        .into_iter()
+        .filter_map(|(running, task)| running.then_some(task))
+        .collect()
+    }
+    /// Turns the busy indicator and hides the notice if it was shown long enough,
+    /// redrawing the bars if anything changed
+    pub fn tick<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let busy = !self.busy().is_empty();
+        if !self.dh.tick(Instant::now(), busy) {
+            return Ok(());
+        }
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// Continues the alignment over the bytes the files have grown by, once the
+    /// alignment has reached their previous ends.
+    /// Local alignments do not reach the ends and are therefore not continued.
+    fn extend_alignment(&mut self) {
+        let lens = [self.original[0].len(), self.original[1].len()];
+        let sender = match &self.sender {
+            Some(sender) if self.aligned_lens != lens => sender.clone(),
+            _ => return,
+        };
+        let mut end = match self.data.last() {
+            Some(el) => [
+                el.xaddr + el.xbyte.is_some() as usize,
+                el.yaddr + el.ybyte.is_some() as usize,
+            ],
+            None => return,
+        };
+        if end != self.aligned_lens {
+            return;
         }
+        self.aligned_lens = lens;
+        let block_size = match self.algo.mode {
+            AlignMode::Blockwise(block_size) => block_size,
+            AlignMode::Global | AlignMode::Local => DEFAULT_BLOCKSIZE,
+        };
+        // the new blocks are mirrored like the ones of the running alignment
+        let [mut x, mut y] = self.original.clone();
+        if self.mirrored {
+            std::mem::swap(&mut x, &mut y);
+            end.swap(0, 1);
+        }
+        let algo = self.algo;
+        std::thread::spawn(move || algo.align_end(x, y, (end[0], end[1]), block_size, sender));
     }
     /// Checks whether a given range of indexes overlaps with the indexes currently visible.
     fn is_in_view(&self, range: Range<isize>) -> bool {
-        let self_range = self.index..self.index + (self.dh.cursor.get_size()) as isize;
+        let self_range = self.view_range();
         !(self_range.start >= range.end || self_range.end <= range.start)
     }
+    /// The indexes from the first to the last byte in view
+    fn view_range(&self) -> Range<isize> {
+        let end = self.unfolded(self.folded(self.index) + self.dh.cursor.get_size() as isize);
+        self.index..end
+    }
+    /// The position of an index of the alignment in the rows, which is
+    /// the same unless identical bytes are folded (see `Folds`)
+    fn folded(&self, index: isize) -> isize {
+        if !self.dh.style.fold {
+            return index;
+        }
+        self.folds
+            .folded(index, self.dh.cursor.bytes_per_row(), self.index)
+    }
+    /// The index in the alignment at a position in the rows, see `folded`
+    fn unfolded(&self, pos: isize) -> isize {
+        if !self.dh.style.fold {
+            return pos;
+        }
+        self.folds
+            .unfolded(pos, self.dh.cursor.bytes_per_row(), self.index)
+    }
+    /// The indexes of the row in view that contains the index
+    fn row_range(&self, index: isize) -> Range<isize> {
+        let bytes_per_row = self.dh.cursor.bytes_per_row() as isize;
+        let view_pos = self.folded(self.index);
+        let row = (self.folded(index) - view_pos).div_euclid(bytes_per_row);
+        let start = view_pos + row * bytes_per_row;
+        self.unfolded(start)..self.unfolded(start + bytes_per_row - 1) + 1
+    }
+    /// The alignment element at an index with the bytes as they are shown,
+    /// which are the edited ones where they were overwritten
+    fn edited_element(&self, index: isize) -> Option<AlignElement> {
+        self.data.get(index).map(|alignel| {
+            let bytes = [alignel.xbyte, alignel.ybyte];
+            let [xbyte, ybyte] = self.dh.edited_bytes(alignel.byte_addresses(), bytes);
+            AlignElement {
+                xbyte,
+                ybyte,
+                ..alignel
+            }
+        })
+    }
+    /// The alignment element at a position in the rows, see `edited_element`
+    fn element(&self, pos: isize) -> Option<AlignElement> {
+        self.edited_element(self.unfolded(pos))
+    }
+    /// The alignment elements at the positions in the rows
+    fn elements(&self, range: Range<isize>) -> Vec<Option<AlignElement>> {
+        range.map(|pos| self.element(pos)).collect()
+    }
     /// changes the active cursor to be cursor_act and moves back into bounds if the active cursor is outside bounds
-    fn change_active_cursor<B: Backend>(&mut self, printer: &mut B, cursor_act: CursorActive) {
+    fn change_active_cursor<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        cursor_act: CursorActive,
+    ) -> std::io::Result<()> {
         self.dh.cursor_act = cursor_act;
         self.set_cursor(printer, cursor_act);
         if self.selection.is_active() {
             self.redraw(printer, false)
         } else {
+            self.print_bars(printer);
             printer.refresh()
         }
     }
     /// Gets a useful form of the information contained in the alignement data for printing.
     fn get_content(&self) -> Vec<DoubleHexLine> {
         let mut content = Vec::new();
+        let current = self.current_results();
+        let entropy = self.entropy_levels();
+        let tint = self.dh.style.palette().annotation;
+        let view_pos = self.folded(self.index);
         for x in 0..self.dh.cursor.get_size_y() {
-            // address of current line to be converted
-            let base_addr = (x * self.dh.cursor.bytes_per_row()) as isize + self.index;
+            // position of current line to be converted
+            let row_pos = (x * self.dh.cursor.bytes_per_row()) as isize + view_pos;
             let mut bytes = Vec::new();
             for (i, alignel) in self
-                .data
-                .get_range(base_addr..base_addr + self.dh.cursor.get_size_x() as isize)
+                .elements(row_pos..row_pos + self.dh.cursor.get_size_x() as isize)
                 .into_iter()
                 .enumerate()
             {
@@ -108,51 +344,104 @@ impl Aligned {
                         continue;
                     }
                 };
-                let addresses = [malignel.xaddr, malignel.yaddr].map(Some);
-                let [is_first_result, is_second_result] = self.searches.is_in_result(addresses);
-                let idx = base_addr + i as isize;
-                let [is_first_selected, is_second_selected] =
-                    self.selection.selection_status([idx, idx]);
-                let first = ByteData::new(malignel.xbyte, is_first_result, is_first_selected);
-                let second = ByteData::new(malignel.ybyte, is_second_result, is_second_selected);
+                let [is_first_current, is_second_current] =
+                    current.contains(malignel.byte_addresses());
+                let pos = row_pos + i as isize;
+                let idx = self.unfolded(pos);
+                let selected = self.selection.selection_status([idx, idx]);
+                let level = |side: usize| {
+                    let offset = (pos - view_pos) as usize;
+                    entropy[side].get(offset).copied().unwrap_or_default()
+                };
+                let (first, second) = self.byte_data(&malignel, selected, tint);
+                let first = first
+                    .current_result(is_first_current)
+                    .entropy_level(level(0));
+                let second = second
+                    .current_result(is_second_current)
+                    .entropy_level(level(1));
                 bytes.push((first, second));
             }
-            let address = self
-                .data
-                .get(base_addr)
-                .map(|alignel| [Some(alignel.xaddr), Some(alignel.yaddr)])
-                .unwrap_or_default();
-            content.push(DoubleHexLine { address, bytes });
+            let address = self.dh.shown_addresses(
+                self.element(row_pos)
+                    .map(|alignel| [Some(alignel.xaddr), Some(alignel.yaddr)])
+                    .unwrap_or_default(),
+            );
+            let fold = match self.dh.style.fold {
+                true => self
+                    .folds
+                    .fold_at(row_pos, self.dh.cursor.bytes_per_row(), self.index),
+                false => None,
+            };
+            content.push(DoubleHexLine {
+                address,
+                bytes,
+                fold,
+            });
         }
         content
     }
-    fn bytes_in_view(&self) -> [Vec<u8>; 2] {
-        let mut ret = [vec![], vec![]];
-        for alignel in self
-            .data
-            .get_range(self.index..self.index + self.dh.cursor.get_size() as isize)
-            .into_iter()
-            .flatten()
-        {
-            if let Some(xbyte) = alignel.xbyte {
-                ret[0].push(xbyte);
-            }
-            if let Some(ybyte) = alignel.ybyte {
-                ret[1].push(ybyte);
-            }
-        }
-        ret
+    /// The bytes of both files of the element with their search results, annotations (tinted
+    /// with the color), edits and whether their difference is ignored or changed
+    fn byte_data(
+        &self,
+        alignel: &AlignElement,
+        selected: [SelectionStatus; 2],
+        tint: ThemeColor,
+    ) -> (ByteData, ByteData) {
+        let addresses = [alignel.xaddr, alignel.yaddr].map(Some);
+        let is_ignored = self
+            .dh
+            .is_ignored(addresses, [alignel.xbyte, alignel.ybyte]);
+        let change = self.dh.change(addresses, [alignel.xbyte, alignel.ybyte]);
+        // gaps are not part of the search results, annotations or edits around them,
+        // even though they have the address of the next byte of their file
+        let byte_addresses = alignel.byte_addresses();
+        let [is_first_result, is_second_result] = self.searches.is_in_result(byte_addresses);
+        let [first_tint, second_tint] = self.dh.annotation_tints(byte_addresses, tint);
+        let [is_first_edited, is_second_edited] = self.dh.is_edited(byte_addresses);
+        let first = ByteData::new(alignel.xbyte, is_first_result, selected[0])
+            .gap(alignel.xbyte.is_none())
+            .annotation(first_tint)
+            .ignored(is_ignored)
+            .change(change)
+            .edited(is_first_edited);
+        let second = ByteData::new(alignel.ybyte, is_second_result, selected[1])
+            .gap(alignel.ybyte.is_none())
+            .annotation(second_tint)
+            .ignored(is_ignored)
+            .change(change)
+            .edited(is_second_edited);
+        (first, second)
+    }
+    /// The entropy levels of the bytes in view, see `DoubleHexContext::entropy_levels`
+    fn entropy_levels(&self) -> [Vec<u8>; 2] {
+        let len = self.dh.cursor.get_size();
+        self.dh
+            .entropy_levels(self.folded(self.index), len, |range| {
+                self.elements(range)
+                    .into_iter()
+                    .map(|alignel| {
+                        alignel
+                            .map(|alignel| (alignel.xbyte, alignel.ybyte))
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
     }
     /// returns the current index of the cursor into the data
     fn cursor_index(&self) -> isize {
-        self.index + self.dh.cursor.get_index() as isize
+        self.unfolded(self.cursor_pos())
+    }
+    /// returns the position of the cursor in the rows, see `folded`
+    fn cursor_pos(&self) -> isize {
+        self.folded(self.index) + self.dh.cursor.get_index() as isize
     }
     /// Paints the cursor at the current position
     fn set_cursor<B: Backend>(&self, printer: &mut B, cursor_act: CursorActive) {
         let idx = self.cursor_index();
-        let (a, b) = self
-            .data
-            .get(idx)
+        let alignel = self.edited_element(idx);
+        let (a, b) = alignel
             .map(|alignel| (alignel.xbyte, alignel.ybyte))
             .unwrap_or_default();
         let addresses = self
@@ -160,105 +449,261 @@ impl Aligned {
             .map(|x| x.map(Some))
             .unwrap_or_default();
         let [sel0, sel1] = self.selection.selection_status([idx, idx]);
+        let [level0, level1] = self
+            .entropy_levels()
+            .map(|levels| levels.get(self.dh.cursor.get_index()).copied());
+        let [tint0, tint1] = self
+            .dh
+            .annotation_tints(addresses, self.dh.style.palette().annotation);
+        let is_ignored = self.dh.is_ignored(addresses, [a, b]);
+        let change = self.dh.change(addresses, [a, b]);
         let [a, b] = [
-            (&self.searches.0, addresses[0], sel0, a),
-            (&self.searches.1, addresses[1], sel1, b),
+            (&self.searches.0, addresses[0], sel0, a, level0, tint0),
+            (&self.searches.1, addresses[1], sel1, b, level1, tint1),
         ]
-        .map(|(search, addr, sel, byte)| {
-            let is_search_result = search.as_ref().map_or(false, |s| s.is_in_result(addr));
-            ByteData::new(byte, is_search_result, sel)
+        .map(|(search, addr, sel, byte, level, tint)| {
+            let search_slot = search
+                .iter()
+                .position(|s| s.is_in_result(byte.and(addr)))
+                .map(|slot| slot as u8);
+            ByteData::new(byte, search_slot, sel)
+                .current_result(search_slot.is_some())
+                .gap(alignel.is_some() && byte.is_none())
+                .entropy_level(level.unwrap_or_default())
+                .annotation(byte.and(tint))
+                .ignored(is_ignored)
+                .change(change)
+        });
+        let [edited0, edited1] = self.dh.is_edited(addresses);
+        let (a, b) = (
+            a.edited(a.byte.is_some() && edited0),
+            b.edited(b.byte.is_some() && edited1),
+        );
+        let row_start = self.cursor_pos() - self.dh.cursor.get_x() as isize;
+        let row = self.dh.cursor_row(|col| {
+            self.element(row_start + col as isize)
+                .map(|alignel| (alignel.xbyte, alignel.ybyte, differ(&self.dh, &alignel)))
+                .unwrap_or_default()
         });
         self.dh
-            .set_doublehex_cursor(printer, cursor_act, (a, b), addresses);
+            .set_doublehex_cursor(printer, cursor_act, (a, b), row);
     }
 
-    /// Prints the top and bottom bar.
+    /// The title of the terminal window for this view
+    pub fn window_title(&self) -> String {
+        window_title(&self.filenames)
+    }
+    /// Prints the top and bottom bar and the diff map.
     fn print_bars<B: Backend>(&self, printer: &mut B) {
+        // they would be drawn over the message of a screen that is too small
+        if self.dh.too_small.is_some() {
+            return;
+        }
+        self.print_diff_map(printer);
+        let [first, second] =
+            [(&self.filenames.0, 0), (&self.filenames.1, 1)].map(|(name, i)| FileTitle {
+                name,
+                len: self.original[i].len(),
+                modified: self.dh.modified[i],
+                edited: self.dh.edits[i].unsaved,
+            });
+        // the busy indicator says that it is aligning
+        let title = if self.aligning { "" } else { " aligned" };
+        let detail = self.algorithm_detail();
         self.dh
-            .print_title_line(printer, " aligned", &self.filenames.0, &self.filenames.1);
+            .print_title_line(printer, title, &self.busy(), Some(&detail), first, second);
         let addresses = self
             .current_cursor_addresses()
             .map(|x| x.map(Some))
             .unwrap_or_default();
-        self.dh.print_bottom_line(printer, addresses);
+        let status = self.searches.status_text(self.dh.cursor_act);
+        let lens = [0, 1].map(|i| self.original[i].len());
+        self.dh.print_bottom_line(
+            printer,
+            addresses,
+            Some(self.alignment_index()),
+            lens,
+            self.selection_file_ranges(),
+            status.as_deref(),
+        );
+    }
+    /// The parameters of the alignment for the title line, which are the ones it was started
+    /// with even if the settings were changed since then
+    fn algorithm_detail(&self) -> String {
+        let algo = &self.algo;
+        let mode = match algo.mode {
+            AlignMode::Global => String::from("global"),
+            AlignMode::Local => String::from("local"),
+            AlignMode::Blockwise(block_size) => format!("blockwise {block_size:#x}"),
+        };
+        let band = match algo.band {
+            Banded::Normal => String::new(),
+            Banded::Banded { kmer, window } => format!(", banded {kmer}/{window}"),
+        };
+        format!(
+            ": {mode}, gap {}/{}{band}, {}",
+            algo.gap_open,
+            algo.gap_extend,
+            algo.aligner.0.name()
+        )
+    }
+    /// Prints the diff map with the differences in the whole alignment
+    fn print_diff_map<B: Backend>(&self, printer: &mut B) {
+        let cells = self
+            .diff_map
+            .cells(self.data.bounds(), self.dh.map_cells(), self.view_range());
+        self.dh.print_diff_map(printer, &cells);
     }
 
     /// Moves the cursor xdiff down and ydiff to the right,
     /// redrawing/scrolling if necessary.
-    pub fn move_around<B: Backend>(&mut self, printer: &mut B, movement: Move) {
+    pub fn move_around<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        movement: Move,
+    ) -> std::io::Result<()> {
         self.set_cursor(printer, CursorActive::None);
+        let previous_results = self.current_results();
+        let view_pos = self.folded(self.index);
+        let bounds = self.data.bounds();
         let relative_bounds =
-            (self.data.bounds().start - self.index)..(self.data.bounds().end - self.index);
+            (self.folded(bounds.start) - view_pos)..(self.folded(bounds.end) - view_pos);
         let movement = if self.dh.style.right_to_left {
             movement.reflect_rtl()
         } else {
             movement
         };
         let index_diff = self.dh.cursor.mov(movement, relative_bounds);
-        self.index += index_diff;
-        if self.selection.is_active() {
+        self.index = self.unfolded(view_pos + index_diff);
+        if self.selection.is_active() || previous_results != self.current_results() {
             let idx = self.cursor_index();
-            self.selection.update([idx, idx], self.dh.cursor_act);
-            self.redraw(printer, false);
+            let row = self.row_range(idx);
+            self.selection
+                .update([idx, idx], [row.clone(), row], self.dh.cursor_act);
+            self.redraw(printer, false)
         } else if let Some(scroll_amount) = self.dh.cursor.full_row_move(index_diff) {
             let content = self.get_content();
             self.dh
                 .print_doublehex_scrolled(&content, printer, scroll_amount, |p| self.print_bars(p));
             self.set_cursor(printer, self.dh.cursor_act);
-            printer.refresh();
+            printer.refresh()
         } else {
-            self.redraw(printer, false);
+            self.redraw(printer, false)
+        }
+    }
+    /// Moves the cursor to the byte displayed at the screen position, if there is one
+    pub fn set_cursor_to<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        column: usize,
+        row: usize,
+    ) -> std::io::Result<()> {
+        if let Some(cell) = self.dh.map_cell_at(column, row) {
+            let before = self.cursor_addresses();
+            self.jump_map(printer, cell, self.dh.map_cells())?;
+            self.remember_jump(before);
+            return Ok(());
+        }
+        let (x, y, _) = match self.dh.position_at(column, row) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        let pos = self.folded(self.index) + (y * self.dh.cursor.bytes_per_row() + x) as isize;
+        let index = self.unfolded(pos);
+        if self.data.bounds().contains(&index) {
+            self.goto_index(printer, index)?;
+        }
+        Ok(())
+    }
+    /// Mirrors the blocks coming from the alignment threads if the files were swapped since
+    fn mirror_blocks(&self, vec: &mut [AlignElement]) {
+        if self.mirrored {
+            vec.iter_mut()
+                .for_each(|alignel| *alignel = alignel.mirror());
         }
     }
     /// Appends alignment data to the underlying DoubleVec.
     /// Returns true if something in view changed.
-    pub fn append(&mut self, vec: Vec<AlignElement>) -> bool {
+    pub fn append(&mut self, mut vec: Vec<AlignElement>) -> bool {
+        self.mirror_blocks(&mut vec);
         let extend_range = self.data.bounds().end..self.data.bounds().end + vec.len() as isize;
+        self.folds.extend_end(extend_range.start, &vec);
+        self.diff_map
+            .extend_end(extend_range.start, &vec, |alignel| {
+                differ(&self.dh, alignel)
+            });
         self.data.extend_end(&vec);
+        self.filter_pending_results(&vec);
         self.is_in_view(extend_range)
     }
     /// Prepends alignment data to the underlying DoubleVec.
     /// Returns true if something in view changed.
-    pub fn prepend(&mut self, vec: Vec<AlignElement>) -> bool {
+    pub fn prepend(&mut self, mut vec: Vec<AlignElement>) -> bool {
+        self.mirror_blocks(&mut vec);
         let extend_range = self.data.bounds().start - vec.len() as isize..self.data.bounds().start;
+        self.folds.extend_front(extend_range.end, &vec);
+        self.diff_map
+            .extend_front(extend_range.end, &vec, |alignel| differ(&self.dh, alignel));
         self.data.extend_front(&vec);
+        self.filter_pending_results(&vec);
         self.is_in_view(extend_range)
     }
     /// Resize the view without printing it, returns whether redrawing is necessary.
     pub fn resize(&mut self, dimensions: (usize, usize)) -> bool {
         let (columns, rows) = dimensions;
+        // a backend that is not shown (or cursive before its first layout) has no size,
+        // which keeps the layout of the last screen
+        if columns == 0 || rows == 0 {
+            return false;
+        }
+        let too_small_changed = self.dh.check_size(dimensions);
         let old_dimensions = (self.dh.cursor.get_size_x(), self.dh.cursor.get_size_y());
         let (new_dimensions, bytes_per_row) = self.dh.style.get_doublehex_dims(columns, rows);
-        self.index += self.dh.cursor.resize(new_dimensions, bytes_per_row);
-        old_dimensions != new_dimensions
+        let cursor = self.cursor_index();
+        self.dh.cursor.resize(new_dimensions, bytes_per_row);
+        // the cursor stays at the same byte, and the folds are laid out for rows that
+        // start at its column (since the rows or folds may have changed)
+        self.index = cursor - self.dh.cursor.get_x() as isize;
+        self.index = self.unfolded(self.folded(cursor) - self.dh.cursor.get_index() as isize);
+        old_dimensions != new_dimensions || too_small_changed
     }
     /// Redraws the current view without checking and updating the view for changes.
-    pub fn redraw<B: Backend>(&self, printer: &mut B, clear: bool) {
+    pub fn redraw<B: Backend>(&self, printer: &mut B, clear: bool) -> std::io::Result<()> {
         if clear {
             printer.clear();
         }
+        if self.dh.too_small.is_some() {
+            self.dh.print_too_small(printer);
+            return printer.refresh();
+        }
         let content = self.get_content();
         self.dh.print_doublehex_screen(&content, printer);
         self.set_cursor(printer, self.dh.cursor_act);
         self.print_bars(printer);
-        printer.refresh();
+        printer.refresh()
     }
     /// Updates the view and draws it.
-    pub fn refresh<B: Backend>(&mut self, printer: &mut B) {
+    pub fn refresh<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let changed = self.resize(printer.size());
-        self.redraw(printer, changed);
+        self.redraw(printer, changed)
     }
     /// jump to a given index with the currently active cursor
-    pub fn goto_index<B: Backend>(&mut self, printer: &mut B, index: isize) {
-        let address_diff = index - self.cursor_index();
+    pub fn goto_index<B: Backend>(&mut self, printer: &mut B, index: isize) -> std::io::Result<()> {
+        // targets in folded rows are unfolded so that the cursor can be placed on them
+        if self.dh.style.fold
+            && self
+                .folds
+                .is_hidden(index, self.dh.cursor.bytes_per_row(), self.index)
+        {
+            self.folds.expand(index);
+        }
+        let address_diff = self.folded(index) - self.cursor_pos();
         let (col, row) = self.dh.cursor.jump(address_diff);
-        self.move_around(printer, Move::Unbounded(col, row));
+        self.move_around(printer, Move::Unbounded(col, row))
     }
     /// get the index of the current file address with the side given by `right`
     fn index_address(&self, right: bool, pos: usize) -> Result<isize, isize> {
-        self.data.binary_search(&pos, |pos, el| {
-            Some(*pos).cmp(&el.map(|a| if right { a.yaddr } else { a.xaddr }))
-        })
+        index_address(&self.data, right, pos)
     }
     /// Go to the address in `pos`, right is true if on the second view, else the first view is used.
     /// Returns true if the address exists.
@@ -268,6 +713,27 @@ impl Aligned {
         first: usize,
         second: Option<usize>,
     ) -> Result<(), String> {
+        let before = self.cursor_addresses();
+        self.goto_addresses(printer, first, second)?;
+        self.remember_jump(before);
+        Ok(())
+    }
+    /// Go to the addresses like `goto`, without remembering the jump
+    fn goto_addresses<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        first: usize,
+        second: Option<usize>,
+    ) -> Result<(), String> {
+        let shown = first;
+        // a single address is one of the second file only if its cursor is the only active one
+        let first = self.dh.file_address(
+            second.is_none() && self.dh.cursor_act == CursorActive::Second,
+            first,
+        )?;
+        let second = second
+            .map(|second| self.dh.file_address(true, second))
+            .transpose()?;
         let address_index = match (self.dh.cursor_act, second) {
             (CursorActive::First | CursorActive::Second, None) => {
                 self.index_address(self.dh.cursor_act.is_second(), first)
@@ -295,16 +761,143 @@ impl Aligned {
             (CursorActive::None, _) => return Ok(()),
         };
         let address_index =
-            address_index.map_err(|_| format!("Address 0x{first:08x} is not aligned (yet)"))?;
-        self.goto_index(printer, address_index);
+            address_index.map_err(|_| format!("Address 0x{shown:08x} is not aligned (yet)"))?;
+        self.goto_index(printer, address_index)
+            .map_err(|e| format!("Could not draw the view: {e}"))
+    }
+    /// Goes to the shown addresses of a restored session once they are aligned
+    pub fn restore_cursor(&mut self, addresses: [Option<usize>; 2]) {
+        self.restore = Some(addresses);
+    }
+    /// Tries to go to the addresses of a restored session, which are kept until they are aligned.
+    /// If they are not aligned to each other once the alignment is done (like when the files
+    /// changed), the cursor goes to the one in the first file.
+    fn goto_restored<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let [first, second] = match self.restore {
+            Some(addresses) => addresses,
+            None => return Ok(()),
+        };
+        let result = match [first, second] {
+            [Some(first), Some(second)] => self.goto_addresses(printer, first, Some(second)),
+            [Some(addr), None] | [None, Some(addr)] => {
+                let old_active = self.dh.cursor_act;
+                self.dh.cursor_act = if first.is_some() {
+                    CursorActive::First
+                } else {
+                    CursorActive::Second
+                };
+                let result = self.goto_addresses(printer, addr, None);
+                self.dh.cursor_act = old_active;
+                result
+            }
+            [None, None] => Ok(()),
+        };
+        match result {
+            Ok(()) => self.restore = None,
+            // the addresses may still be aligned by the running alignment
+            Err(_) if self.aligning => (),
+            Err(_) if first.is_some() && second.is_some() => {
+                self.restore = Some([first, None]);
+                return self.goto_restored(printer);
+            }
+            Err(e) => {
+                self.restore = None;
+                self.dh.set_notice(
+                    Severity::Warning,
+                    format!("Could not restore the cursor: {e}"),
+                );
+                self.print_bars(printer);
+                return printer.refresh();
+            }
+        }
         Ok(())
     }
+    /// The index of the cursor in the alignment, along with the indexes of all aligned bytes
+    pub fn alignment_index(&self) -> (isize, Range<isize>) {
+        (self.cursor_index(), self.data.bounds())
+    }
+    /// Goes to an index in the alignment, like one shown in the bottom line
+    pub fn goto_alignment_index<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        index: isize,
+    ) -> Result<(), String> {
+        let before = self.cursor_addresses();
+        self.goto_index(printer, index)
+            .map_err(|e| format!("Could not draw the view: {e}"))?;
+        self.remember_jump(before);
+        Ok(())
+    }
+    /// Goes to a position of the jump history, which is the place of the first file's
+    /// address in the alignment unless the first cursor was on a gap
+    fn goto_position<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        position: [Option<usize>; 2],
+    ) -> Result<(), String> {
+        let (right, shown) = match position {
+            [Some(first), _] => (false, first),
+            [None, Some(second)] => (true, second),
+            [None, None] => return Ok(()),
+        };
+        let address = self.dh.file_address(right, shown)?;
+        let index = self
+            .index_address(right, address)
+            .map_err(|_| format!("Address 0x{shown:08x} is not aligned (yet)"))?;
+        self.goto_index(printer, index)
+            .map_err(|e| format!("Could not draw the view: {e}"))
+    }
+    /// Remembers the position of the cursors before a jump if they moved
+    fn remember_jump(&mut self, before: [Option<usize>; 2]) {
+        if self.cursor_addresses() != before {
+            self.dh.history.push(before);
+        }
+    }
+    /// Goes back to the position before the last jump, or forward again after going back
+    fn jump_history<B: Backend>(&mut self, printer: &mut B, forward: bool) -> std::io::Result<()> {
+        let position = match forward {
+            true => self.dh.history.forward(),
+            false => self.dh.history.back(self.cursor_addresses()),
+        };
+        match position.map(|position| self.goto_position(printer, position)) {
+            Some(Err(e)) => {
+                self.dh.set_notice(Severity::Error, e);
+                self.set_pending_count(printer, None)
+            }
+            _ => Ok(()),
+        }
+    }
+    /// The shown addresses of the cursors, or None for a cursor on a gap
+    pub fn cursor_addresses(&self) -> [Option<usize>; 2] {
+        let addresses = self
+            .data
+            .get(self.cursor_index())
+            .map(|x| x.byte_addresses())
+            .unwrap_or_default();
+        self.dh.shown_addresses(addresses)
+    }
+    /// The addresses of the cursors in the compared bytes, or the ones the alignment started
+    /// at if nothing is aligned at the cursor
+    pub fn positions(&self) -> [isize; 2] {
+        self.current_cursor_addresses()
+            .unwrap_or(self.start)
+            .map(|addr| addr as isize)
+    }
     /// get the file addresses of the current cursors
     fn current_cursor_addresses(&self) -> Option<[usize; 2]> {
         self.data
             .get(self.cursor_index())
             .map(|x| [x.xaddr, x.yaddr])
     }
+    /// returns the search results the cursor is currently in, which are none on a gap
+    fn current_results(&self) -> CurrentResults {
+        let addresses = self
+            .data
+            .get(self.cursor_index())
+            .map(|x| x.byte_addresses())
+            .unwrap_or_default();
+        self.searches.current_results(addresses)
+    }
 
     pub fn selection_file_ranges(&self) -> [Option<Range<usize>>; 2] {
         let ranges = self.selection.ranges(self.dh.cursor_act);
@@ -336,8 +929,19 @@ impl Aligned {
         })
     }
 
+    /// The selected bytes of the active files, see `selected_bytes`
+    pub fn selected_bytes(&self) -> Vec<SelectedBytes> {
+        selected_bytes(
+            [&self.filenames.0, &self.filenames.1],
+            self.edited_files(),
+            self.selection_file_ranges(),
+            self.dh.bases,
+            self.dh.cursor_act,
+        )
+    }
+
     /// get the search results and positions of all active cursors
-    fn search_data(&self, forward: bool) -> Vec<(&Option<SearchResults>, usize, bool)> {
+    fn search_data(&self, forward: bool) -> Vec<(&SearchResults, usize, bool)> {
         let [first, second] = self
             .current_cursor_addresses()
             .or_else(|| {
@@ -349,252 +953,992 @@ impl Aligned {
                 .map(|x| [x.xaddr, x.yaddr])
             })
             .unwrap_or([0, 0]);
-        Some((&self.searches.0, first, false))
-            .filter(|_| self.dh.cursor_act.is_first())
-            .iter()
-            .chain(
-                Some((&self.searches.1, second, true))
-                    .filter(|_| self.dh.cursor_act.is_second())
-                    .iter(),
-            )
-            .copied()
+        self.searches
+            .active(self.dh.cursor_act)
+            .into_iter()
+            .map(|(search, right)| (search, if right { second } else { first }, right))
             .collect()
     }
     /// Jump to the next search result on either active cursor after the current index
-    pub fn jump_next_search_result<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_next_search_result<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let search_data = self.search_data(true);
-        let next = match SearchResults::nearest_next_result(&search_data, |addr, right| {
+        let next = SearchResults::nearest_next_result(&search_data, |addr, right| {
             self.index_address(right, addr).ok()
-        }) {
+        });
+        let (next, wrapped) = match next {
             Some(x) => x,
-            None => return,
+            None => return Ok(()),
         };
+        // jumping back to the other end of the file is easily mistaken for going on
+        if wrapped {
+            self.dh
+                .set_notice(Severity::Warning, "Search wrapped around to the start");
+        }
         self.goto_index(printer, next)
     }
     /// Jump to the previous search reult on either active cursor before the current index
-    pub fn jump_prev_search_result<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_prev_search_result<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let search_data = self.search_data(false);
-        let next = match SearchResults::nearest_prev_result(&search_data, |addr, right| {
+        let next = SearchResults::nearest_prev_result(&search_data, |addr, right| {
             self.index_address(right, addr).ok()
-        }) {
+        });
+        let (next, wrapped) = match next {
             Some(x) => x,
-            None => return,
+            None => return Ok(()),
         };
+        if wrapped {
+            self.dh
+                .set_notice(Severity::Warning, "Search wrapped around to the end");
+        }
         self.goto_index(printer, next)
     }
-    /// Jump to the index where the next bytes are different
+    /// Jumps `count` search results forward or backward, only drawing the final position
+    fn jump_search_results<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        forward: bool,
+        count: usize,
+    ) -> std::io::Result<()> {
+        if count <= 1 {
+            return match forward {
+                true => self.jump_next_search_result(printer),
+                false => self.jump_prev_search_result(printer),
+            };
+        }
+        // the intermediate jumps are not drawn
+        for _ in 0..count {
+            match forward {
+                true => self.jump_next_search_result(&mut Dummy)?,
+                false => self.jump_prev_search_result(&mut Dummy)?,
+            }
+        }
+        self.redraw(printer, false)
+    }
+    /// Jump to the index where the `count`th next bytes are different
     pub fn jump_next_difference<B: Backend>(
         &mut self,
         printer: &mut B,
         forward: bool,
         insertion: bool,
-    ) {
-        let target_address = next_difference(
-            self.cursor_index(),
-            self.data.bounds(),
-            forward,
-            |i| match self.data.get(i).map(|x| (x.xbyte, x.ybyte)) {
-                None | Some((Some(_), None)) | Some((None, Some(_))) => true,
-                Some((x, y)) => x != y && !insertion,
-            },
-        );
-        self.goto_index(printer, target_address);
+        count: usize,
+    ) -> std::io::Result<()> {
+        let target_address = (0..count).fold(self.cursor_index(), |address, _| {
+            next_difference(address, self.data.bounds(), forward, |i| {
+                match self.edited_element(i) {
+                    None => true,
+                    Some(alignel) => match (alignel.xbyte, alignel.ybyte) {
+                        (Some(_), Some(_)) if insertion => false,
+                        (a, b) => self
+                            .dh
+                            .is_jump_target([Some(alignel.xaddr), Some(alignel.yaddr)], [a, b]),
+                    },
+                }
+            })
+        });
+        self.goto_index(printer, target_address)
     }
     /// Go to the first position of the file
-    pub fn jump_start<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_start<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         self.goto_index(printer, self.data.bounds().start)
     }
     /// Go to the last position of the file
-    pub fn jump_end<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_end<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         self.goto_index(printer, self.data.bounds().end - 1)
     }
-    /// Adds a batch of search results to the current ones if they are of the same query.
-    pub fn add_search_results(
+    /// Go to the start of the `cell`th of `cells` equal slices of the alignment,
+    /// like the cells of the diff map
+    fn jump_map<B: Backend>(
         &mut self,
-        query: Query,
-        results: Vec<Option<Range<usize>>>,
-        first: bool,
-    ) {
-        let search = if first {
-            &mut self.searches.0
+        printer: &mut B,
+        cell: usize,
+        cells: usize,
+    ) -> std::io::Result<()> {
+        self.goto_index(printer, map_index(self.data.bounds(), cell, cells))
+    }
+    /// Adds a batch of search results to the current ones if they are of the same query.
+    /// Tells how many results were found when the search finishes.
+    pub fn add_search_results(&mut self, query: Query, results: Vec<SearchMessage>, first: bool) {
+        let search = match self.searches.results_mut(first, &query) {
+            Some(s) => s,
+            None => return,
+        };
+        let running = search.progress().is_some();
+        // the addresses spanned by the new matches
+        let mut found: Option<Range<usize>> = None;
+        for message in results {
+            if let SearchMessage::Match(m) = &message {
+                found = Some(match found {
+                    Some(found) => found.start.min(m.start)..found.end.max(m.end),
+                    None => m.clone(),
+                });
+            }
+            search.update(message)
+        }
+        if let Some(found) = found {
+            search.filter_pending(found, |range| has_difference(&self.data, !first, range));
+        }
+        // the ones outside of the finished alignment can not be decided anymore
+        if !self.aligning {
+            search.drop_pending();
+        }
+        let name = if first {
+            &self.filenames.0
         } else {
-            &mut self.searches.1
+            &self.filenames.1
         };
-        let search = match search {
-            Some(s) if s.query() == &query => s,
+        let summary = self
+            .searches
+            .results_mut(first, &query)
+            .and_then(|search| search.finished_summary(name));
+        if let (true, Some(summary)) = (running, summary) {
+            self.dh.set_notice(Severity::Info, summary);
+        }
+    }
+    /// Decides for the pending matches of difference-only searches in the addresses of
+    /// a newly aligned block whether they are kept, which is possible as soon as the
+    /// alignment covers them
+    fn filter_pending_results(&mut self, block: &[AlignElement]) {
+        let (first, last) = match (block.first(), block.last()) {
+            (Some(first), Some(last)) => (first, last),
             _ => return,
         };
-        for result in results.iter().flatten() {
-            search.add_match(result.clone())
+        let data = &self.data;
+        let searches = (self.searches.0.iter_mut().map(|s| (false, s)))
+            .chain(self.searches.1.iter_mut().map(|s| (true, s)));
+        for (right, search) in searches {
+            let addr = |el: &AlignElement| if right { el.yaddr } else { el.xaddr };
+            search.filter_pending(addr(first)..addr(last) + 1, |range| {
+                has_difference(data, right, range)
+            });
         }
     }
     /// Clears the search results of both cursors
     pub fn clear_search(&mut self) {
         self.searches.clear(self.dh.cursor_act)
     }
+    /// Removes either the most recent or all search queries of the active cursors and redraws
+    pub fn remove_search<B: Backend>(&mut self, printer: &mut B, all: bool) -> std::io::Result<()> {
+        if all {
+            self.searches.clear(self.dh.cursor_act)
+        } else {
+            self.searches.drop_last(self.dh.cursor_act)
+        }
+        self.redraw(printer, false)
+    }
     /// Initializes the empty search results for the search query
-    /// on the currently active cursors
+    /// on the currently active cursors.
     pub fn setup_search(
         &mut self,
         query: Query,
+        options: SearchOptions,
     ) -> (
         (SearchContext, FileContent),
         Option<(SearchContext, FileContent)>,
     ) {
-        let files = [self.original[0].clone(), self.original[1].clone()];
-        self.searches.setup_search(query, self.dh.cursor_act, files)
+        let files = self.edited_files();
+        self.searches
+            .setup_search(query, self.dh.cursor_act, files, options)
     }
     /// Inreases the column count by one and refreshes the view
-    pub fn add_column<B: Backend>(&mut self, printer: &mut B) {
-        self.dh.inc_columns();
-        self.refresh(printer);
+    pub fn add_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        self.dh.inc_columns(CursorActive::Both);
+        self.refresh(printer)
     }
     /// Decreases the column count by one and refreshes the view
-    pub fn remove_column<B: Backend>(&mut self, printer: &mut B) {
-        self.dh.dec_columns();
-        self.refresh(printer);
+    pub fn remove_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        self.dh.dec_columns(CursorActive::Both);
+        self.refresh(printer)
     }
     /// Sets the column count to the peak of the autocorrelation of
-    /// the bytes in the current view and refreshes the view
-    pub fn auto_column<B: Backend>(&mut self, printer: &mut B) {
+    /// the selected bytes, or of samples of the whole files without
+    /// a selection, and refreshes the view
+    pub fn auto_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let selection = self.selection_file_ranges();
         let data = self.original.clone();
-        let [mut first, mut second] = if selection.iter().any(|x| x.is_some()) {
-            self.clear_selection(printer);
-            from_fn(|i| Some(data[i][selection[i].clone()?].to_vec()))
-                .map(|x| x.unwrap_or_default())
-        } else {
-            self.bytes_in_view()
-        };
-        // set vectors to be empty if the cursor is not active
-        if !self.dh.cursor_act.is_first() {
-            first = Vec::new();
+        let whole = selection.iter().all(|x| x.is_none());
+        if !whole {
+            self.selection.clear(self.dh.cursor_act);
         }
-        if !self.dh.cursor_act.is_second() {
-            second = Vec::new();
-        }
-        self.dh.auto_columns([&first, &second]);
-        self.refresh(printer);
+        let active = [
+            self.dh.cursor_act.is_first(),
+            self.dh.cursor_act.is_second(),
+        ];
+        // files without an active cursor are left out
+        let bytes: [&[u8]; 2] = from_fn(|i| match &selection[i] {
+            _ if !active[i] => &[][..],
+            Some(range) => &data[i][range.clone()],
+            None if whole => &data[i][..],
+            None => &[][..],
+        });
+        self.dh.auto_columns(bytes, CursorActive::Both);
+        self.refresh(printer)
     }
-    pub fn start_selection<B: Backend>(&mut self, printer: &mut B) {
+    pub fn start_selection<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let idx = self.cursor_index();
         self.selection.start([idx, idx], self.dh.cursor_act);
-        self.redraw(printer, false);
+        self.redraw(printer, false)
+    }
+    /// starts selecting whole rows at the row of the cursor
+    pub fn select_rows<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let idx = self.cursor_index();
+        let row = self.row_range(idx);
+        self.selection
+            .start_rows([idx, idx], [row.clone(), row], self.dh.cursor_act);
+        self.redraw(printer, false)
+    }
+    /// moves the cursor while extending the selection, which is started
+    /// at the cursor if there is none yet
+    pub fn extend_selection<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        movement: Move,
+    ) -> std::io::Result<()> {
+        let idx = self.cursor_index();
+        self.selection.start_missing([idx, idx], self.dh.cursor_act);
+        self.move_around(printer, movement)
     }
     /// clears the selection with the currently active cursors
-    pub fn clear_selection<B: Backend>(&mut self, printer: &mut B) -> bool {
+    pub fn clear_selection<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<bool> {
+        let before = self.snapshot();
         let ret = self.selection.clear(self.dh.cursor_act);
-        self.redraw(printer, false);
-        ret
+        if ret {
+            self.undo_stack.push(before);
+        }
+        self.redraw(printer, false)?;
+        Ok(ret)
+    }
+    /// The current style and selection, for going back to them
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            style: self.dh.style,
+            selection: self.selection.clone(),
+        }
+    }
+    /// Goes back to the style and selection before their last change,
+    /// with the cursor staying where it is
+    pub fn undo<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let Snapshot { style, selection } = match self.undo_stack.pop() {
+            Some(snapshot) => snapshot,
+            None => {
+                self.dh.set_notice(Severity::Warning, "Nothing to undo");
+                return self.set_pending_count(printer, None);
+            }
+        };
+        self.dh.style = style;
+        self.selection = selection;
+        // the screen is cleared, since the diff map might have been toggled
+        self.resize(printer.size());
+        self.redraw(printer, true)
     }
     /// Executes an action corresponding to an escape and returns true
     /// if anything was done
-    pub fn process_escape<B: Backend>(&mut self, printer: &mut B) -> bool {
+    pub fn process_escape<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<bool> {
         self.clear_selection(printer)
     }
     /// Process move events
-    pub fn process_move<B: Backend>(&mut self, printer: &mut B, action: Action) {
-        match action {
-            Action::Down => self.move_around(printer, Move::CursorY(1)),
-            Action::DownAlt => self.move_around(printer, Move::ViewY(1)),
-            Action::Up => self.move_around(printer, Move::CursorY(-1)),
-            Action::UpAlt => self.move_around(printer, Move::ViewY(-1)),
-            Action::Left => self.move_around(printer, Move::CursorX(-1)),
-            Action::LeftAlt => self.move_around(printer, Move::ViewX(-1)),
-            Action::Right => self.move_around(printer, Move::CursorX(1)),
-            Action::RightAlt => self.move_around(printer, Move::ViewX(1)),
-            Action::PgDown => self.move_around(
-                printer,
-                Move::ViewY(self.dh.cursor.get_size_y() as isize / 2),
-            ),
-            Action::PgUp => self.move_around(
-                printer,
-                Move::ViewY(-(self.dh.cursor.get_size_y() as isize) / 2),
-            ),
-            Action::NextDifference => self.jump_next_difference(printer, true, false),
-            Action::NextInsertion => self.jump_next_difference(printer, true, true),
-            Action::PrevDifference => self.jump_next_difference(printer, false, false),
-            Action::PrevInsertion => self.jump_next_difference(printer, false, true),
+    /// (the movements and jumps are repeated `count` times)
+    pub fn process_move<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        action: Action,
+        count: usize,
+    ) -> std::io::Result<()> {
+        let n = count as isize;
+        let before = action.is_jump().then(|| self.cursor_addresses());
+        let before_change = action.can_be_undone().then(|| self.snapshot());
+        let result = match action {
+            Action::Down => self.move_around(printer, Move::CursorY(n)),
+            Action::DownAlt => self.move_around(printer, Move::ViewY(n)),
+            Action::Up => self.move_around(printer, Move::CursorY(-n)),
+            Action::UpAlt => self.move_around(printer, Move::ViewY(-n)),
+            Action::Left => self.move_around(printer, Move::CursorX(-n)),
+            Action::LeftAlt => self.move_around(printer, Move::ViewX(-n)),
+            Action::Right => self.move_around(printer, Move::CursorX(n)),
+            Action::RightAlt => self.move_around(printer, Move::ViewX(n)),
+            Action::PgDown => self.move_around(printer, Move::Page(self.dh.page_rows())),
+            Action::PgUp => self.move_around(printer, Move::Page(-self.dh.page_rows())),
+            Action::HalfPgDown => self.move_around(printer, Move::Page(self.dh.half_page_rows())),
+            Action::HalfPgUp => self.move_around(printer, Move::Page(-self.dh.half_page_rows())),
+            Action::NextDifference => self.jump_next_difference(printer, true, false, count),
+            Action::NextInsertion => self.jump_next_difference(printer, true, true, count),
+            Action::PrevDifference => self.jump_next_difference(printer, false, false, count),
+            Action::PrevInsertion => self.jump_next_difference(printer, false, true, count),
+            Action::WordLeft => self.move_around(printer, self.dh.word_move(false)),
+            Action::WordRight => self.move_around(printer, self.dh.word_move(true)),
+            Action::RowStart => self.move_around(printer, Move::RowStart),
+            Action::RowEnd => self.move_around(printer, Move::RowEnd),
             Action::Top => self.jump_start(printer),
             Action::Bottom => self.jump_end(printer),
-            Action::NextSearch => self.jump_next_search_result(printer),
-            Action::PrevSearch => self.jump_prev_search_result(printer),
+            Action::JumpPercent => self.jump_map(printer, count.min(100), 100),
+            Action::NextSearch => self.jump_search_results(printer, true, count),
+            Action::PrevSearch => self.jump_search_results(printer, false, count),
+            Action::SetCursorTo(column, row) => self.set_cursor_to(printer, column, row),
             Action::CursorFirst => self.change_active_cursor(printer, CursorActive::First),
             Action::CursorBoth => self.change_active_cursor(printer, CursorActive::Both),
             Action::CursorSecond => self.change_active_cursor(printer, CursorActive::Second),
             Action::AddColumn => self.add_column(printer),
             Action::RemoveColumn => self.remove_column(printer),
             Action::AutoColumn => self.auto_column(printer),
-            Action::StartSelection => self.start_selection(printer),
-            Action::ClearSelection => {
-                self.clear_selection(printer);
+            Action::RelativeAddresses => {
+                let addresses = self
+                    .current_cursor_addresses()
+                    .map_or([None; 2], |addresses| addresses.map(Some));
+                let addresses = self.dh.shown_addresses(addresses);
+                self.dh.toggle_relative_addresses(addresses);
+                self.refresh(printer)
+            }
+            Action::MultipleColumn => {
+                self.dh.multiple_columns(count);
+                self.refresh(printer)
             }
+            Action::SelectDown => self.extend_selection(printer, Move::CursorY(n)),
+            Action::SelectUp => self.extend_selection(printer, Move::CursorY(-n)),
+            Action::SelectLeft => self.extend_selection(printer, Move::CursorX(-n)),
+            Action::SelectRight => self.extend_selection(printer, Move::CursorX(n)),
+            Action::SelectRows => self.select_rows(printer),
+            Action::StartSelection => self.start_selection(printer),
+            Action::ClearSelection => self.clear_selection(printer).map(|_| ()),
+            Action::DropSearch => self.remove_search(printer, false),
+            Action::ClearSearches => self.remove_search(printer, true),
             Action::ResetColumn => {
-                self.dh.style.column_count = ColumnSetting::Fit;
-                self.refresh(printer);
+                self.dh.reset_columns();
+                self.refresh(printer)
+            }
+            Action::CycleGroup => {
+                self.dh.cycle_group();
+                self.refresh(printer)
+            }
+            Action::NextBase => {
+                self.dh.style.mode = self.dh.style.mode.next_base();
+                self.refresh(printer)
+            }
+            Action::ByteClasses => {
+                self.dh.style.byte_classes = !self.dh.style.byte_classes;
+                self.refresh(printer)
+            }
+            Action::EntropyColors => {
+                self.dh.style.entropy = !self.dh.style.entropy;
+                self.refresh(printer)
+            }
+            Action::FoldIdentical => {
+                self.dh.style.fold = !self.dh.style.fold;
+                self.refresh(printer)
+            }
+            Action::ToggleFold => {
+                if !self.dh.style.fold {
+                    self.dh
+                        .set_notice(Severity::Warning, "Identical bytes are not folded (Z)");
+                } else if self.folds.toggle(self.cursor_index()).is_none() {
+                    self.dh.set_notice(
+                        Severity::Warning,
+                        "No identical bytes to fold at the cursor",
+                    );
+                }
+                self.refresh(printer)
+            }
+            Action::DiffMap => {
+                // the screen is cleared, since the columns can stay the same
+                self.dh.style.diff_map = !self.dh.style.diff_map;
+                self.resize(printer.size());
+                self.redraw(printer, true)
+            }
+            Action::DeltaMode => {
+                self.dh.style.delta = self.dh.style.delta.next();
+                self.refresh(printer)
+            }
+            Action::ToggleSplit => {
+                self.dh.style.vertical = !self.dh.style.vertical;
+                self.refresh(printer)
+            }
+            Action::ToggleText => {
+                self.dh.style.ascii_col = !self.dh.style.ascii_col;
+                self.refresh(printer)
             }
-            _ => (),
+            Action::Follow => {
+                self.dh.toggle_follow();
+                self.print_bars(printer);
+                printer.refresh()
+            }
+            Action::ChangeFilter => {
+                self.dh.cycle_change_filter();
+                self.print_bars(printer);
+                printer.refresh()
+            }
+            Action::JumpBack => self.jump_history(printer, false),
+            Action::JumpForward => self.jump_history(printer, true),
+            Action::Undo => self.undo(printer),
+            Action::UndoEdit => {
+                self.dh.set_notice(
+                    Severity::Warning,
+                    "Editing is only possible in the unaligned view (F2)",
+                );
+                self.print_bars(printer);
+                printer.refresh()
+            }
+            Action::OffsetLock => {
+                self.dh.set_notice(
+                    Severity::Warning,
+                    "Locking the offset is only possible in the unaligned view (F2)",
+                );
+                self.print_bars(printer);
+                printer.refresh()
+            }
+            Action::Edit | Action::StopEditing => {
+                // the reader already started edit mode, which only exists in the unaligned view
+                if self.dh.editor.is_active() {
+                    self.dh.set_notice(
+                        Severity::Warning,
+                        "Editing is only possible in the unaligned view (F2)",
+                    );
+                }
+                self.dh.editor.set_active(false);
+                self.print_bars(printer);
+                printer.refresh()
+            }
+            Action::Command => {
+                self.dh.prompt.open();
+                self.set_pending_count(printer, None)
+            }
+            Action::Prompt(key) => {
+                self.dh.prompt.key(key);
+                self.set_pending_count(printer, None)
+            }
+            _ => Ok(()),
+        };
+        if let Some(before) = before {
+            self.remember_jump(before);
+        }
+        if let Some(before) = before_change {
+            self.undo_stack.push(before);
         }
+        result
+    }
+    /// Returns whether a search is still running on one of the files
+    pub fn is_search_running(&self) -> bool {
+        self.searches.is_running()
+    }
+    /// Marks the searches that are still running as cancelled
+    pub fn cancel_running_searches(&mut self) {
+        self.searches.cancel_running()
+    }
+    /// Writes the search results of both files into a new CSV file at `path`,
+    /// which fails if there already is one
+    pub fn export_search(&self, path: &Path, with_bytes: bool) -> std::io::Result<()> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut out = BufWriter::new(file);
+        let [first, second] = &self.edited_files();
+        write_results_csv(
+            &mut out,
+            [
+                (&self.filenames.0, first, &self.searches.0),
+                (&self.filenames.1, second, &self.searches.1),
+            ],
+            with_bytes,
+        )?;
+        out.flush()
+    }
+    /// Writes the whole alignment so far as an HTML page with the colors and the number of
+    /// columns of the view, see `html::write_html_lines`
+    pub fn export_html(&self, path: &Path) -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let bounds = self.data.bounds();
+        let columns = self.dh.cursor.bytes_per_row();
+        let tint = self.dh.style.palette().annotation;
+        let lines = (bounds.start..bounds.end).step_by(columns).map(|start| {
+            let row = (start..(start + columns as isize).min(bounds.end))
+                .map(|index| self.edited_element(index))
+                .collect::<Vec<_>>();
+            let address = row
+                .iter()
+                .flatten()
+                .next()
+                .map(|alignel| {
+                    self.dh
+                        .shown_addresses([Some(alignel.xaddr), Some(alignel.yaddr)])
+                })
+                .unwrap_or_default();
+            // missing elements are left blank, like in the view
+            let bytes = row
+                .iter()
+                .map(|alignel| match alignel {
+                    Some(alignel) => self.byte_data(alignel, Default::default(), tint),
+                    None => (ByteData::default(), ByteData::default()),
+                })
+                .collect();
+            DoubleHexLine {
+                address,
+                bytes,
+                fold: None,
+            }
+        });
+        let options = HtmlOptions {
+            style: self.dh.style,
+            fold_threshold: DEFAULT_FOLD_THRESHOLD,
+        };
+        write_html_lines(&mut out, self.filenames(), lines, options)?;
+        Ok(())
     }
     /// Returns the active search query for one of the currently cursors
     pub fn current_search_query(&self) -> Option<&Query> {
         self.searches.current_search_query(self.dh.cursor_act)
     }
     /// Process events
-    pub fn process_action<B: Backend>(&mut self, printer: &mut B, action: AlignedMessage) {
+    pub fn process_action<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        action: AlignedMessage,
+    ) -> std::io::Result<()> {
+        // only the message fits on a screen that is too small, so the action is done without
+        // drawing it, and the screen is drawn again in case the view fits on it now
+        if self.dh.too_small.is_some()
+            && !matches!(
+                action,
+                AlignedMessage::UserEvent(Action::Refresh | Action::Tick)
+            )
+        {
+            let too_small = self.dh.too_small.take();
+            let result = self.process_action(&mut Dummy, action);
+            self.dh.too_small = too_small;
+            result?;
+            return self.refresh(printer);
+        }
         match match action {
             AlignedMessage::UserEvent(ev) => ev,
             AlignedMessage::Append(vec) => {
-                if self.append(vec) {
-                    self.refresh(printer);
+                // in follow mode, the cursor stays at the end
+                let pinned =
+                    self.dh.following() && self.cursor_index() == self.data.bounds().end - 1;
+                let changed = self.append(vec);
+                self.extend_alignment();
+                if pinned {
+                    self.jump_end(printer)?;
+                } else if changed {
+                    self.refresh(printer)?;
+                } else {
+                    // the percentages and the diff map change with the alignment
+                    self.print_bars(printer);
+                    printer.refresh()?;
+                }
+                return self.goto_restored(printer);
+            }
+            AlignedMessage::Grown(files, sender) => {
+                self.original = files;
+                self.sender = Some(sender);
+                self.extend_alignment();
+                return Ok(());
+            }
+            AlignedMessage::Modified(modified) => return self.set_modified(printer, modified),
+            AlignedMessage::Done => {
+                self.aligning = false;
+                for search in self.searches.0.iter_mut().chain(self.searches.1.iter_mut()) {
+                    search.drop_pending();
                 }
-                return;
+                self.print_bars(printer);
+                printer.refresh()?;
+                return self.goto_restored(printer);
+            }
+            AlignedMessage::Fallback(text) => {
+                self.dh.set_notice(Severity::Warning, text);
+                self.print_bars(printer);
+                return printer.refresh();
             }
             AlignedMessage::Prepend(vec) => {
                 if self.prepend(vec) {
-                    self.refresh(printer);
+                    self.refresh(printer)?;
+                } else {
+                    // the percentages and the diff map change with the alignment
+                    self.print_bars(printer);
+                    printer.refresh()?;
                 }
-                return;
+                return self.goto_restored(printer);
             }
         } {
-            Action::Refresh => self.refresh(printer),
-            otherwise => self.process_move(printer, otherwise),
+            Action::PendingCount(count) => self.set_pending_count(printer, count),
+            Action::Tick => self.tick(printer),
+            otherwise => {
+                // every other action uses up the count prefix and hides the notice
+                let count = self.dh.pending_count.take();
+                let notice = self.dh.notice.take();
+                // the cursor of a restored session stays where the user moved it
+                if !matches!(otherwise, Action::Refresh) {
+                    self.restore = None;
+                }
+                match otherwise {
+                    Action::Refresh => self.refresh(printer),
+                    otherwise => self.process_move(printer, otherwise, count.unwrap_or(1)),
+                }?;
+                if count.is_some() || notice.is_some() {
+                    self.set_pending_count(printer, None)?;
+                }
+                Ok(())
+            }
         }
     }
+    /// Sets the count prefix shown in the bottom line
+    fn set_pending_count<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        count: Option<usize>,
+    ) -> std::io::Result<()> {
+        self.dh.pending_count = count;
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// The addresses of the alignment element at the cursor, or if the cursor is outside
+    /// of the alignment, of the nearest element (preferring the ones before it) and true.
+    /// Before anything is aligned, these are the addresses the alignment started at.
+    fn nearest_cursor_addresses(&self) -> ([usize; 2], bool) {
+        let index = self.cursor_index();
+        if let Some(a) = self.data.get(index) {
+            return ([a.xaddr, a.yaddr], false);
+        }
+        let nearest = if index >= self.data.bounds().end {
+            self.data.last()
+        } else {
+            self.data.first()
+        };
+        (nearest.map_or(self.start, |a| [a.xaddr, a.yaddr]), true)
+    }
     /// Turn an Aligned view into its part, including information on where it points
-    pub fn destruct(self) -> Result<(FileState, FileState, DoubleHexContext), Self> {
-        // we return the original view in case the cursor is outside the files
-        match (self.data.get(self.cursor_index())).map(|a| (a.xaddr, a.yaddr)) {
-            Some((xaddr, yaddr)) => {
-                let [original0, original1] = self.original;
-                Ok((
-                    FileState {
-                        name: self.filenames.0,
-                        content: original0,
-                        index: xaddr,
-                        search: self.searches.0,
-                    },
-                    FileState {
-                        name: self.filenames.1,
-                        content: original1,
-                        index: yaddr,
-                        search: self.searches.1,
-                    },
-                    self.dh,
-                ))
-            }
-            None => Err(self),
+    #[allow(clippy::result_large_err)]
+    pub fn destruct(mut self) -> Result<(FileState, FileState, DoubleHexContext), Self> {
+        // the cursor can be outside of the alignment, like while it is still being
+        // calculated, in which case it is moved to the nearest aligned byte
+        let ([xaddr, yaddr], moved) = self.nearest_cursor_addresses();
+        if moved {
+            self.dh.set_notice(
+                Severity::Info,
+                "Moved the cursor to the nearest aligned byte",
+            );
         }
+        let [original0, original1] = self.original;
+        Ok((
+            FileState {
+                name: self.filenames.0,
+                content: original0,
+                index: xaddr,
+                search: self.searches.0,
+            },
+            FileState {
+                name: self.filenames.1,
+                content: original1,
+                index: yaddr,
+                search: self.searches.1,
+            },
+            self.dh,
+        ))
     }
 }
 
 // view implementations for cursive
+/// Returns the index of the alignment element at the address of either the first or second file
+/// (`right` is true for the second file)
+fn index_address(data: &DoubleVec<AlignElement>, right: bool, pos: usize) -> Result<isize, isize> {
+    data.binary_search(&pos, |pos, el| {
+        Some(*pos).cmp(&el.map(|a| if right { a.yaddr } else { a.xaddr }))
+    })
+}
+
+/// Returns whether the two files differ anywhere in the alignment of the given range of one file,
+/// or None if the range is not completely aligned yet
+fn has_difference(
+    data: &DoubleVec<AlignElement>,
+    right: bool,
+    range: &Range<usize>,
+) -> Option<bool> {
+    if range.is_empty() {
+        return Some(false);
+    }
+    let start = index_address(data, right, range.start).ok()?;
+    let end = index_address(data, right, range.end - 1).ok()?;
+    Some(
+        data.get_range(start..end + 1)
+            .into_iter()
+            .flatten()
+            .any(|el| el.xbyte != el.ybyte),
+    )
+}
+
+/// Whether the bytes of an element of the alignment count as different, see `Ignore::differ`
+fn differ(dh: &DoubleHexContext, alignel: &AlignElement) -> bool {
+    dh.differ(
+        [Some(alignel.xaddr), Some(alignel.yaddr)],
+        [alignel.xbyte, alignel.ybyte],
+    )
+}
+
 impl View for Aligned {
     fn draw(&self, printer: &cursive::Printer) {
-        let mut backend = Cursiv::from_printer(printer);
-        self.redraw(&mut backend, true);
+        let mut backend = Cursiv::from_printer(printer, self.dh.style.palette());
+        // drawing with cursive can not fail
+        let _ = self.redraw(&mut backend, true);
     }
     fn layout(&mut self, size: Vec2) {
         self.resize((size.x, size.y));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use crate::{
+        align::AlignMode,
+        backend::capture::{assert_snapshot, Capture},
+        search::QueryType,
+        testing::file,
+    };
+
+    /// Creates an aligned view of the two files, using the whole global alignment
+    fn aligned(first: &[u8], second: &[u8]) -> Aligned {
+        let mut dh = DoubleHexContext::new((8, 8));
+        dh.style.addr_width = 2;
+        dh.style.ascii_col = true;
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let (send, recv) = channel();
+        let mut view = Aligned::new(
+            file("first", first),
+            file("second", second),
+            dh,
+            &algo,
+            [None, None],
+            send,
+        );
+        // the channel is closed once the alignment thread is done
+        for msg in recv.iter() {
+            view.process_action(&mut Dummy, msg).unwrap();
+        }
+        view
+    }
+
+    const FIRST: &[u8] = b"Hello, World! This is the first file.";
+    const SECOND: &[u8] = b"Hello, world! This is the second file.";
+
+    #[test]
+    fn snapshot_aligned() {
+        let mut view = aligned(FIRST, SECOND);
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        assert_snapshot("aligned", &capture.annotated());
+        assert_eq!(view.window_title(), "biodiff: first \u{2194} second");
+    }
+
+    #[test]
+    fn title_parameters() {
+        let mut view = aligned(FIRST, SECOND);
+        let title = |view: &mut Aligned, columns| {
+            let mut capture = Capture::new(columns, 16);
+            view.refresh(&mut capture).unwrap();
+            capture.text().lines().next().unwrap().to_string()
+        };
+        assert!(title(&mut view, 160).starts_with(" aligned: global, gap -5/-1, rust-bio "));
+        // the parameters are the ones the alignment started with, and they make room
+        // for the names on narrow screens (with enough rows for both stacked halves)
+        view.dh.style.vertical = true;
+        view.aligning = true;
+        assert!(title(&mut view, 40).starts_with(" | aligning "));
+        // the indicator turns with every tick while aligning
+        view.tick(&mut Capture::new(40, 16)).unwrap();
+        assert!(title(&mut view, 40).starts_with(" / aligning "));
+    }
+
+    #[test]
+    fn restore_cursor() {
+        let algo = AlignAlgorithm {
+            mode: AlignMode::Global,
+            ..AlignAlgorithm::default()
+        };
+        let restored = |addresses| {
+            let (send, recv) = channel();
+            let mut view = Aligned::new(
+                file("first", FIRST),
+                file("second", SECOND),
+                DoubleHexContext::new((8, 8)),
+                &algo,
+                [None, None],
+                send,
+            );
+            view.restore_cursor(addresses);
+            for msg in recv.iter() {
+                view.process_action(&mut Dummy, msg).unwrap();
+            }
+            view
+        };
+        let view = restored([Some(14), Some(14)]);
+        assert_eq!(view.cursor_addresses(), [Some(14), Some(14)]);
+        // addresses that are not aligned to each other fall back to the one in the first file
+        let view = restored([Some(14), Some(20)]);
+        assert_eq!(view.cursor_addresses(), [Some(14), Some(14)]);
+        assert!(view.restore.is_none());
+    }
+
+    #[test]
+    fn search_results_skip_gaps() {
+        // the second file lacks a long part of the first one, so the alignment
+        // has a long gap in it, which has the address of the match after it
+        let first = b"header--0123456789abcdefghijklmnneedle--tail";
+        let second = b"header--needle--tail";
+        let mut view = aligned(first, second);
+        let results = |start, len| {
+            let query = Query::new(QueryType::Text, "needle", 0).unwrap();
+            let mut results = SearchResults::new(query, len);
+            results.add_match(start..start + 6);
+            results
+        };
+        view.searches.0.push(results(32, first.len()));
+        view.searches.1.push(results(8, second.len()));
+        let bytes = view
+            .get_content()
+            .into_iter()
+            .flat_map(|line| line.bytes)
+            .collect::<Vec<_>>();
+        for side in [0, 1] {
+            let found = bytes
+                .iter()
+                .map(|(a, b)| [a, b][side])
+                .filter(|byte| byte.search_slot.is_some())
+                .collect::<Vec<_>>();
+            assert_eq!(found.len(), 6);
+            assert!(found.iter().all(|byte| byte.byte.is_some()));
+        }
+        // on the gap, the cursor is not in the match either
+        let gap = view
+            .data
+            .bounds()
+            .find(|&i| view.data.get(i).map_or(false, |x| x.ybyte.is_none()))
+            .unwrap();
+        view.goto_index(&mut Dummy, gap).unwrap();
+        assert_eq!(view.current_results(), CurrentResults::default());
+    }
+
+    #[test]
+    fn destruct_outside_alignment() {
+        // right after starting, nothing is aligned yet
+        let mut first = file("first", FIRST);
+        first.index = 7;
+        let mut second = file("second", SECOND);
+        second.index = 9;
+        let (send, recv) = channel();
+        let view = Aligned::new(
+            first,
+            second,
+            DoubleHexContext::new((8, 8)),
+            &AlignAlgorithm::default(),
+            [None, None],
+            send,
+        );
+        let (first, second, dh) = view.destruct().ok().unwrap();
+        drop(recv);
+        assert_eq!([first.index, second.index], [7, 9]);
+        assert_eq!(
+            dh.notice_text(),
+            Some("Moved the cursor to the nearest aligned byte")
+        );
+        // past the end of the alignment, the cursor goes back to the last byte
+        let mut view = aligned(FIRST, SECOND);
+        view.index = 1000;
+        let (first, second, _) = view.destruct().ok().unwrap();
+        assert_eq!(
+            [first.index, second.index],
+            [FIRST.len() - 1, SECOND.len() - 1]
+        );
+        // on the alignment, nothing is moved
+        let (_, _, dh) = aligned(FIRST, SECOND).destruct().ok().unwrap();
+        assert_eq!(dh.notice_text(), None);
+    }
+
+    #[test]
+    fn snapshot_aligned_selection() {
+        let mut view = aligned(FIRST, SECOND);
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::Right, 2).unwrap();
+        view.start_selection(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::Down, 1).unwrap();
+        assert_snapshot("aligned_selection", &capture.annotated());
+    }
+
+    #[test]
+    fn select_rows() {
+        let mut view = aligned(FIRST, SECOND);
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::Down, 3).unwrap();
+        view.process_move(&mut capture, Action::SelectRows, 1)
+            .unwrap();
+        // the gap in the first file leaves out one byte of the row
+        assert_eq!(
+            view.selection_file_ranges(),
+            [Some(0x18..0x1f), Some(0x18..0x20)]
+        );
+        view.process_move(&mut capture, Action::SelectUp, 1)
+            .unwrap();
+        assert_eq!(
+            view.selection_file_ranges(),
+            [Some(0x10..0x1f), Some(0x10..0x20)]
+        );
+        view.clear_selection(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::SelectLeft, 2)
+            .unwrap();
+        assert_eq!(
+            view.selection_file_ranges(),
+            [Some(0x0e..0x11), Some(0x0e..0x11)]
+        );
+    }
+
+    #[test]
+    fn snapshot_aligned_vertical() {
+        let mut view = aligned(FIRST, SECOND);
+        view.dh.style.vertical = true;
+        let mut capture = Capture::new(40, 16);
+        view.refresh(&mut capture).unwrap();
+        assert_snapshot("aligned_vertical", &capture.text());
+    }
+
+    #[test]
+    fn swap_files() {
+        let mut view = aligned(FIRST, SECOND);
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        let before = capture.text();
+        view.swap_files();
+        assert_eq!(view.window_title(), "biodiff: second \u{2194} first");
+        view.refresh(&mut capture).unwrap();
+        assert_ne!(capture.text(), before);
+        view.swap_files();
+        view.refresh(&mut capture).unwrap();
+        assert_eq!(capture.text(), before);
+        // blocks that are still sent by the alignment are mirrored as well
+        view.swap_files();
+        view.append(vec![AlignElement {
+            xaddr: FIRST.len(),
+            xbyte: Some(b'!'),
+            yaddr: SECOND.len(),
+            ybyte: None,
+        }]);
+        let last = view.data.last().unwrap();
+        assert_eq!((last.xaddr, last.xbyte), (SECOND.len(), None));
+        assert_eq!((last.yaddr, last.ybyte), (FIRST.len(), Some(b'!')));
+    }
+
+    #[test]
+    fn scrolling_matches_redraw() {
+        let first: Vec<u8> = (0..=255).collect();
+        let second: Vec<u8> = (0..=255).map(|x: u8| x ^ u8::from(x % 7 == 0)).collect();
+        let mut view = aligned(&first, &second);
+        let mut scrolled = Capture::new(80, 10);
+        let mut redrawn = Capture::new(80, 10).without_scroll();
+        view.refresh(&mut scrolled).unwrap();
+        view.refresh(&mut redrawn).unwrap();
+        for action in [Action::DownAlt, Action::PgDown, Action::UpAlt] {
+            // only the scrolled capture sees the movement, the other one is redrawn completely
+            view.process_move(&mut scrolled, action, 3).unwrap();
+            view.redraw(&mut redrawn, false).unwrap();
+            assert_eq!(scrolled.annotated(), redrawn.annotated());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{align::AlignElement, doublehex::MapCell};
+
+/// The number of indexes summarized by a bucket at the start
+const FIRST_BUCKET_SIZE: isize = 256;
+/// The number of buckets after which they are merged into buckets of twice the size,
+/// which is plenty for the height of a screen
+const MAX_BUCKETS: usize = 4096;
+
+/// Whether there are differences in a bucket
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Bucket {
+    different: bool,
+    inserted: bool,
+}
+
+impl Bucket {
+    fn merge(self, other: Bucket) -> Bucket {
+        Bucket {
+            different: self.different || other.different,
+            inserted: self.inserted || other.inserted,
+        }
+    }
+}
+
+/// A coarse summary of where the differences in the alignment are, for the diff map.
+/// The indexes of the alignment are put into buckets of `bucket_size` indexes,
+/// which get larger as the alignment grows.
+pub struct DiffMap {
+    bucket_size: isize,
+    /// the number of the first bucket, where bucket n starts at n * bucket_size
+    first: isize,
+    buckets: VecDeque<Bucket>,
+}
+
+impl Default for DiffMap {
+    fn default() -> Self {
+        DiffMap {
+            bucket_size: FIRST_BUCKET_SIZE,
+            first: 0,
+            buckets: VecDeque::new(),
+        }
+    }
+}
+
+impl DiffMap {
+    /// Adds the elements appended to the alignment at `start`,
+    /// where `differ` tells whether the bytes of an element count as different
+    pub fn extend_end(
+        &mut self,
+        start: isize,
+        elements: &[AlignElement],
+        differ: impl Fn(&AlignElement) -> bool,
+    ) {
+        for (i, alignel) in elements.iter().enumerate() {
+            self.add(start + i as isize, alignel, differ(alignel));
+        }
+        self.coarsen();
+    }
+    /// Adds the elements prepended to the alignment in front of `end`, see `extend_end`
+    pub fn extend_front(
+        &mut self,
+        end: isize,
+        elements: &[AlignElement],
+        differ: impl Fn(&AlignElement) -> bool,
+    ) {
+        for (i, alignel) in elements.iter().rev().enumerate() {
+            self.add(end - 1 - i as isize, alignel, differ(alignel));
+        }
+        self.coarsen();
+    }
+    fn add(&mut self, index: isize, alignel: &AlignElement, different: bool) {
+        let bucket = index.div_euclid(self.bucket_size);
+        if self.buckets.is_empty() {
+            self.first = bucket;
+        }
+        while bucket < self.first {
+            self.buckets.push_front(Bucket::default());
+            self.first -= 1;
+        }
+        while bucket >= self.first + self.buckets.len() as isize {
+            self.buckets.push_back(Bucket::default());
+        }
+        let entry = &mut self.buckets[(bucket - self.first) as usize];
+        match (alignel.xbyte, alignel.ybyte) {
+            (Some(_), Some(_)) => entry.different |= different,
+            _ => entry.inserted |= different,
+        }
+    }
+    /// Merges pairs of buckets while there are too many of them
+    fn coarsen(&mut self) {
+        while self.buckets.len() > MAX_BUCKETS {
+            let first = self.first.div_euclid(2);
+            let mut buckets = VecDeque::with_capacity(self.buckets.len() / 2 + 1);
+            for (i, bucket) in self.buckets.iter().enumerate() {
+                let merged = (self.first + i as isize).div_euclid(2) - first;
+                match buckets.get_mut(merged as usize) {
+                    Some(entry) => *entry = bucket.merge(*entry),
+                    None => buckets.push_back(*bucket),
+                }
+            }
+            self.first = first;
+            self.buckets = buckets;
+            self.bucket_size *= 2;
+        }
+    }
+    /// Summarizes the indexes in `range`, which includes whole buckets
+    fn summary(&self, range: Range<isize>) -> Bucket {
+        if range.is_empty() {
+            return Bucket::default();
+        }
+        let start = range.start.div_euclid(self.bucket_size) - self.first;
+        let end = (range.end - 1).div_euclid(self.bucket_size) - self.first + 1;
+        let start = start.clamp(0, self.buckets.len() as isize) as usize;
+        let end = end.clamp(0, self.buckets.len() as isize) as usize;
+        self.buckets
+            .range(start..end.max(start))
+            .fold(Bucket::default(), |acc, bucket| acc.merge(*bucket))
+    }
+    /// The cells of a diff map with `cells` cells for the indexes in `bounds`,
+    /// where the indexes in `view` are marked as being in view
+    pub fn cells(&self, bounds: Range<isize>, cells: usize, view: Range<isize>) -> Vec<MapCell> {
+        viewport_cells(bounds.clone(), cells, view)
+            .into_iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let summary = self.summary(map_slice(bounds.clone(), i, cells));
+                MapCell {
+                    different: summary.different,
+                    inserted: summary.inserted,
+                    ..cell
+                }
+            })
+            .collect()
+    }
+}
+
+/// The indexes summarized by a cell of a diff map with `cells` cells
+/// for the indexes in `bounds`, which are spread evenly over the cells
+pub fn map_slice(bounds: Range<isize>, cell: usize, cells: usize) -> Range<isize> {
+    let len = (bounds.end - bounds.start).max(0) as i128;
+    let at = |cell: usize| bounds.start + (len * cell as i128 / cells.max(1) as i128) as isize;
+    at(cell)..at(cell + 1)
+}
+
+/// The index to jump to for a cell of the diff map, which is the start of its slice
+/// (or the last index for the cell after the end)
+pub fn map_index(bounds: Range<isize>, cell: usize, cells: usize) -> isize {
+    let start = map_slice(bounds.clone(), cell, cells).start;
+    start.min(bounds.end - 1).max(bounds.start)
+}
+
+/// The cells of a diff map without differences, which only shows where the view is
+pub fn viewport_cells(bounds: Range<isize>, cells: usize, view: Range<isize>) -> Vec<MapCell> {
+    (0..cells)
+        .map(|i| {
+            let slice = map_slice(bounds.clone(), i, cells);
+            MapCell {
+                in_view: !slice.is_empty() && slice.start < view.end && view.start < slice.end,
+                ..MapCell::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn element(x: Option<u8>, y: Option<u8>) -> AlignElement {
+        AlignElement {
+            xaddr: 0,
+            xbyte: x,
+            yaddr: 0,
+            ybyte: y,
+        }
+    }
+    #[test]
+    fn cells() {
+        let mut map = DiffMap::default();
+        let mut elements = vec![element(Some(0), Some(0)); 4096];
+        elements[1500] = element(Some(0), Some(1));
+        elements[3500] = element(None, Some(1));
+        let differ = |alignel: &AlignElement| alignel.xbyte != alignel.ybyte;
+        map.extend_end(0, &elements, differ);
+        let cells = map.cells(0..4096, 4, 0..100);
+        let flags = cells
+            .iter()
+            .map(|cell| (cell.different, cell.inserted, cell.in_view))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            vec![
+                (false, false, true),
+                (true, false, false),
+                (false, false, false),
+                (false, true, false)
+            ]
+        );
+        map.extend_front(0, &[element(Some(0), Some(2))], differ);
+        assert!(map.cells(-1..0, 1, 0..0)[0].different);
+        // the buckets are merged, but the differences stay
+        map.extend_end(
+            4096,
+            &vec![element(Some(0), Some(0)); MAX_BUCKETS * 256],
+            differ,
+        );
+        assert!(map.buckets.len() <= MAX_BUCKETS);
+        let bounds = -1..4096 + MAX_BUCKETS as isize * 256;
+        assert!(map.cells(bounds.clone(), 1, 0..1)[0].inserted);
+        assert!(!map.cells(bounds, 2, 0..1)[1].different);
+        // ignored differences are left out
+        let mut map = DiffMap::default();
+        map.extend_end(0, &[element(Some(0), Some(1))], |_| false);
+        assert!(!map.cells(0..1, 1, 0..0)[0].different);
+    }
+    #[test]
+    fn slices() {
+        assert_eq!(map_slice(0..100, 0, 3), 0..33);
+        assert_eq!(map_slice(0..100, 2, 3), 66..100);
+        assert_eq!(map_index(0..100, 3, 3), 99);
+        assert_eq!(map_index(0..100, 50, 100), 50);
+        let cells = viewport_cells(0..2, 4, 1..2);
+        let in_view = cells.iter().map(|cell| cell.in_view).collect::<Vec<_>>();
+        assert_eq!(in_view, vec![false, false, false, true]);
+    }
+}
@@ -0,0 +1,321 @@
+use std::{cell::RefCell, ops::Range};
+
+use crate::align::AlignElement;
+
+/// The number of identical bytes in a row that are folded, if they cover enough rows
+const FOLD_MIN_BYTES: isize = 256;
+/// The number of whole rows a fold has to cover, so that folding saves some space
+const FOLD_MIN_ROWS: usize = 2;
+
+/// A fold of whole rows of identical bytes into a single row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fold {
+    /// the index of the first folded byte in the alignment
+    start: isize,
+    rows: usize,
+    /// the position of the fold's row, counted like an index without the folded rows
+    pos: isize,
+}
+
+/// Where the folds are for a row width and a position of the rows
+struct Layout {
+    bytes_per_row: usize,
+    phase: isize,
+    folds: Vec<Fold>,
+}
+
+/// The runs of identical bytes in the alignment, which can be folded into single rows.
+///
+/// Positions in the folded rows are counted like indexes in the alignment, except that
+/// a fold only takes up one row. Since the folds consist of whole rows, the rows
+/// start at the same column in both, so the folds depend on where the rows start.
+#[derive(Default)]
+pub struct Folds {
+    /// the runs of at least FOLD_MIN_BYTES identical bytes, in order
+    runs: Vec<Range<isize>>,
+    /// the shorter identical bytes at the start and the end of the alignment,
+    /// which can still become a run when the alignment grows
+    open_front: Range<isize>,
+    open_end: Range<isize>,
+    /// indexes in runs which are not folded
+    expanded: Vec<isize>,
+    layout: RefCell<Option<Layout>>,
+}
+
+fn is_identical(alignel: &AlignElement) -> bool {
+    alignel.xbyte.is_some() && alignel.xbyte == alignel.ybyte
+}
+
+impl Folds {
+    /// Adds the elements appended to the alignment at `start`
+    pub fn extend_end(&mut self, start: isize, elements: &[AlignElement]) {
+        for (i, alignel) in elements.iter().enumerate() {
+            if is_identical(alignel) {
+                self.push_end(start + i as isize);
+            }
+        }
+        *self.layout.get_mut() = None;
+    }
+    /// Adds the elements prepended to the alignment in front of `end`
+    pub fn extend_front(&mut self, end: isize, elements: &[AlignElement]) {
+        for (i, alignel) in elements.iter().rev().enumerate() {
+            if is_identical(alignel) {
+                self.push_front(end - 1 - i as isize);
+            }
+        }
+        *self.layout.get_mut() = None;
+    }
+    fn push_end(&mut self, idx: isize) {
+        if let Some(run) = self.runs.last_mut().filter(|run| run.end == idx) {
+            run.end += 1;
+            return;
+        }
+        if self.open_end.is_empty() || self.open_end.end != idx {
+            // the identical bytes at the start may reach up to here
+            self.open_end = if !self.open_front.is_empty() && self.open_front.end == idx {
+                self.open_front.clone()
+            } else {
+                idx..idx
+            };
+        }
+        let shared = !self.open_front.is_empty() && self.open_front == self.open_end;
+        self.open_end.end += 1;
+        if shared {
+            self.open_front.end += 1;
+        }
+        self.promote(self.open_end.clone(), true);
+    }
+    fn push_front(&mut self, idx: isize) {
+        if let Some(run) = self.runs.first_mut().filter(|run| run.start == idx + 1) {
+            run.start -= 1;
+            return;
+        }
+        if self.open_front.is_empty() || self.open_front.start != idx + 1 {
+            // the identical bytes at the end may reach down to here
+            self.open_front = if !self.open_end.is_empty() && self.open_end.start == idx + 1 {
+                self.open_end.clone()
+            } else {
+                idx + 1..idx + 1
+            };
+        }
+        let shared = !self.open_end.is_empty() && self.open_front == self.open_end;
+        self.open_front.start -= 1;
+        if shared {
+            self.open_end.start -= 1;
+        }
+        self.promote(self.open_front.clone(), false);
+    }
+    /// Turns identical bytes at one end into a run once they are long enough
+    fn promote(&mut self, open: Range<isize>, at_end: bool) {
+        if open.end - open.start < FOLD_MIN_BYTES {
+            return;
+        }
+        for other in [&mut self.open_front, &mut self.open_end] {
+            if *other == open {
+                *other = 0..0;
+            }
+        }
+        if at_end {
+            self.runs.push(open);
+        } else {
+            self.runs.insert(0, open);
+        }
+    }
+    /// The run containing the index, if there is one
+    fn run_at(&self, index: isize) -> Option<Range<isize>> {
+        let i = self.runs.partition_point(|run| run.start <= index);
+        let run = self.runs.get(i.checked_sub(1)?)?;
+        run.contains(&index).then(|| run.clone())
+    }
+    /// Unfolds the run at the index if it is folded and folds it otherwise.
+    /// Returns whether it is folded now, or None if there is no run at the index.
+    pub fn toggle(&mut self, index: isize) -> Option<bool> {
+        let run = self.run_at(index)?;
+        let expanded = self.expanded.len();
+        self.expanded.retain(|i| !run.contains(i));
+        let folded = self.expanded.len() != expanded;
+        if !folded {
+            self.expanded.push(index);
+        }
+        *self.layout.get_mut() = None;
+        Some(folded)
+    }
+    /// Unfolds the run at the index, if there is one
+    pub fn expand(&mut self, index: isize) {
+        if self.run_at(index).is_some() && !self.expanded.contains(&index) {
+            self.expanded.push(index);
+            *self.layout.get_mut() = None;
+        }
+    }
+    /// The folds for rows that start at `row_start` (or any multiple of
+    /// `bytes_per_row` away from it), which are computed again only if these change
+    fn with_folds<T>(
+        &self,
+        bytes_per_row: usize,
+        row_start: isize,
+        f: impl FnOnce(&[Fold]) -> T,
+    ) -> T {
+        let bytes_per_row = bytes_per_row.max(1);
+        let phase = row_start.rem_euclid(bytes_per_row as isize);
+        let mut layout = self.layout.borrow_mut();
+        if !matches!(&*layout, Some(l) if l.bytes_per_row == bytes_per_row && l.phase == phase) {
+            *layout = None;
+        }
+        let layout = layout.get_or_insert_with(|| Layout {
+            bytes_per_row,
+            phase,
+            folds: self.layout_folds(bytes_per_row, phase),
+        });
+        f(&layout.folds)
+    }
+    fn layout_folds(&self, bytes_per_row: usize, phase: isize) -> Vec<Fold> {
+        let width = bytes_per_row as isize;
+        let mut hidden = 0;
+        self.runs
+            .iter()
+            .filter(|run| !self.expanded.iter().any(|i| run.contains(i)))
+            .filter_map(|run| {
+                // only the whole rows in the run are folded
+                let start = run.start + (phase - run.start).rem_euclid(width);
+                let rows = (run.end - start).max(0) as usize / bytes_per_row;
+                (rows >= FOLD_MIN_ROWS).then_some((start, rows))
+            })
+            .map(|(start, rows)| {
+                let fold = Fold {
+                    start,
+                    rows,
+                    pos: start - hidden,
+                };
+                hidden += (rows as isize - 1) * width;
+                fold
+            })
+            .collect()
+    }
+    /// The position of an index of the alignment in the folded rows,
+    /// where the bytes of a fold are at the columns of their row
+    pub fn folded(&self, index: isize, bytes_per_row: usize, row_start: isize) -> isize {
+        self.with_folds(bytes_per_row, row_start, |folds| {
+            let width = bytes_per_row.max(1) as isize;
+            let i = folds.partition_point(|fold| fold.start <= index);
+            let fold = match i.checked_sub(1) {
+                Some(i) => folds[i],
+                None => return index,
+            };
+            let len = fold.rows as isize * width;
+            if index < fold.start + len {
+                fold.pos + (index - fold.start).rem_euclid(width)
+            } else {
+                index - (fold.start - fold.pos) - len + width
+            }
+        })
+    }
+    /// The index in the alignment at a position in the folded rows,
+    /// which is in the first folded row for the row of a fold
+    pub fn unfolded(&self, pos: isize, bytes_per_row: usize, row_start: isize) -> isize {
+        self.with_folds(bytes_per_row, row_start, |folds| {
+            let width = bytes_per_row.max(1) as isize;
+            let i = folds.partition_point(|fold| fold.pos <= pos);
+            let fold = match i.checked_sub(1) {
+                Some(i) => folds[i],
+                None => return pos,
+            };
+            let len = fold.rows as isize * width;
+            if pos < fold.pos + width {
+                fold.start + (pos - fold.pos)
+            } else {
+                pos + (fold.start - fold.pos) + len - width
+            }
+        })
+    }
+    /// The number of folded bytes if the position is in the row of a fold
+    pub fn fold_at(&self, pos: isize, bytes_per_row: usize, row_start: isize) -> Option<usize> {
+        self.with_folds(bytes_per_row, row_start, |folds| {
+            let i = folds.partition_point(|fold| fold.pos <= pos);
+            let fold = folds.get(i.checked_sub(1)?)?;
+            (pos < fold.pos + bytes_per_row as isize).then_some(fold.rows * bytes_per_row)
+        })
+    }
+    /// Whether the index is folded away, which is not the case for the
+    /// first folded row that is shown in the fold's row
+    pub fn is_hidden(&self, index: isize, bytes_per_row: usize, row_start: isize) -> bool {
+        self.with_folds(bytes_per_row, row_start, |folds| {
+            let width = bytes_per_row as isize;
+            let i = folds.partition_point(|fold| fold.start <= index);
+            match i.checked_sub(1) {
+                Some(i) => {
+                    let fold = folds[i];
+                    (fold.start + width..fold.start + fold.rows as isize * width).contains(&index)
+                }
+                None => false,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// alignment elements that are identical where `same` is true
+    fn elements(same: &[bool]) -> Vec<AlignElement> {
+        same.iter()
+            .enumerate()
+            .map(|(i, same)| AlignElement {
+                xaddr: i,
+                xbyte: Some(0),
+                yaddr: i,
+                ybyte: Some(!same as u8),
+            })
+            .collect()
+    }
+    #[test]
+    fn runs() {
+        let mut folds = Folds::default();
+        folds.extend_end(0, &elements(&[false; 10]));
+        folds.extend_end(10, &elements(&[true; 200]));
+        assert!(folds.runs.is_empty());
+        // the run grows over the end of the appended block
+        folds.extend_end(210, &elements(&[true; 100]));
+        assert_eq!(folds.runs, vec![10..310]);
+        folds.extend_end(310, &elements(&[true; 10]));
+        assert_eq!(folds.runs, vec![10..320]);
+        // and also over blocks in front of it
+        folds.extend_front(0, &elements(&[true; 300]));
+        assert_eq!(folds.runs, vec![-300..0, 10..320]);
+        let mut folds = Folds::default();
+        folds.extend_end(0, &elements(&[true; 200]));
+        folds.extend_front(0, &elements(&[true; 100]));
+        assert_eq!(folds.runs, vec![-100..200]);
+    }
+    #[test]
+    fn positions() {
+        let mut folds = Folds::default();
+        folds.extend_end(0, &elements(&[false; 10]));
+        folds.extend_end(10, &elements(&[true; 300]));
+        folds.extend_end(310, &elements(&[false; 100]));
+        // with rows starting at 0, the rows from 16 to 304 are folded
+        assert_eq!(folds.folded(15, 16, 0), 15);
+        assert_eq!(folds.folded(16, 16, 0), 16);
+        assert_eq!(folds.folded(40, 16, 0), 24);
+        assert_eq!(folds.folded(304, 16, 0), 32);
+        assert_eq!(folds.unfolded(32, 16, 0), 304);
+        assert_eq!(folds.unfolded(20, 16, 0), 20);
+        assert_eq!(folds.fold_at(16, 16, 0), Some(288));
+        assert_eq!(folds.fold_at(32, 16, 0), None);
+        assert!(folds.is_hidden(40, 16, 0));
+        assert!(!folds.is_hidden(20, 16, 0));
+        // with rows starting at 8, the fold starts at 24
+        assert_eq!(folds.folded(24, 16, 8), 24);
+        assert_eq!(folds.unfolded(40, 16, 8), 296);
+        for index in 0..410 {
+            let pos = folds.folded(index, 16, 8);
+            if !folds.is_hidden(index, 16, 8) {
+                assert_eq!(folds.unfolded(pos, 16, 8), index);
+            }
+        }
+        assert_eq!(folds.toggle(100), Some(false));
+        assert_eq!(folds.folded(304, 16, 0), 304);
+        assert_eq!(folds.toggle(200), Some(true));
+        assert_eq!(folds.folded(304, 16, 0), 32);
+        assert_eq!(folds.toggle(5), None);
+    }
+}
@@ -1,19 +1,50 @@
-use std::{array::from_fn, ops::Range};
+use std::{
+    array::from_fn,
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::Path,
+    time::Instant,
+};
 
 use cursive::{Vec2, View};
 
 use crate::{
-    backend::{Action, Backend, Cursiv},
+    backend::{Action, Backend, Cursiv, Dummy},
     cursor::{CursorActive, Move},
     datastruct::{CompVec, SignedArray},
-    doublehex::{DoubleHexContext, DoubleHexLine},
-    file::{FileContent, FileState},
-    search::{Query, SearchContext, SearchPair, SearchResults},
-    selection::Selections,
-    style::{ByteData, ColumnSetting},
+    doublehex::{DoubleHexContext, DoubleHexLine, FileTitle, SecondGrid},
+    file::{FileContent, FileState, Unit},
+    notice::Severity,
+    search::{
+        write_results_csv, Query, SearchContext, SearchMessage, SearchOptions, SearchPair,
+        SearchResults,
+    },
+    selection::{selected_bytes, SelectedBytes, Selections},
+    style::{ByteData, Style},
+};
+
+use super::{
+    diffmap::{map_index, viewport_cells},
+    next_difference, window_title, UndoStack,
 };
 
-use super::next_difference;
+/// Events for the unaligned view, which are either from the user
+/// or about the files changing on disk
+pub enum UnalignedMessage {
+    UserEvent(Action),
+    /// the files have grown in follow mode
+    Grown([FileContent; 2]),
+    /// whether the files changed on disk since they were read
+    Modified([bool; 2]),
+}
+
+impl From<Action> for UnalignedMessage {
+    fn from(action: Action) -> Self {
+        UnalignedMessage::UserEvent(action)
+    }
+}
+
 /// An unaligned view that is just two files next to each other
 pub struct Unaligned {
     pub data: CompVec,
@@ -22,11 +53,21 @@ pub struct Unaligned {
     selection: Selections,
     index: isize,
     pub dh: DoubleHexContext,
+    /// the states before changes of the style, offset or selection
+    undo_stack: UndoStack<Snapshot>,
+}
+
+/// What undo goes back to in the unaligned view
+struct Snapshot {
+    style: Style,
+    shift: isize,
+    selection: Selections,
 }
 
 impl Unaligned {
     /// Creates a new view, with the indexes in the files at the cursor
-    pub fn new(first: FileState, second: FileState, dh: DoubleHexContext) -> Self {
+    pub fn new(first: FileState, second: FileState, mut dh: DoubleHexContext) -> Self {
+        dh.bases = [first.content.base(), second.content.base()];
         let mut index = -(dh.cursor.get_index() as isize);
         let mut data = CompVec::new(first.content, second.content);
         index += data.add_first_shift(-(first.index as isize));
@@ -38,140 +79,329 @@ impl Unaligned {
             selection: Selections::new(),
             index,
             dh,
+            undo_stack: UndoStack::default(),
         }
     }
+    /// Shows the files the other way around, with the cursor staying at the same bytes
+    pub fn swap_files(&mut self) {
+        self.index += self.data.swap();
+        let (first, second) = std::mem::take(&mut self.filenames);
+        self.filenames = (second, first);
+        self.searches.swap();
+        self.selection.swap();
+        self.dh.swap_files();
+        // the offsets and selections that were remembered are the other way around
+        self.undo_stack.clear();
+    }
     /// Resizes the view without drawing it, returning if anything changed
     pub fn resize(&mut self, dimensions: (usize, usize)) -> bool {
         let (columns, rows) = dimensions;
+        // a backend that is not shown (or cursive before its first layout) has no size,
+        // which keeps the layout of the last screen
+        if columns == 0 || rows == 0 {
+            return false;
+        }
+        let too_small_changed = self.dh.check_size(dimensions);
         let old_dimensions = (self.dh.cursor.get_size_x(), self.dh.cursor.get_size_y());
         let (new_dimensions, bytes_per_row) = self.dh.style.get_doublehex_dims(columns, rows);
         self.index += self.dh.cursor.resize(new_dimensions, bytes_per_row);
-        old_dimensions != new_dimensions
+        // the second half only gets its own grid if it differs from the first one
+        let old_grid = self.dh.second_grid;
+        self.dh.second_grid = self
+            .dh
+            .style
+            .second_half_dims(columns)
+            .filter(|&grid| grid != (new_dimensions.0, bytes_per_row))
+            .map(|(columns, bytes_per_row)| SecondGrid {
+                columns,
+                bytes_per_row,
+            });
+        old_dimensions != new_dimensions || old_grid != self.dh.second_grid || too_small_changed
     }
     /// Redraws without checking for resize.
     /// clear indicates whether the screen should be cleared before.
-    pub fn redraw<B: Backend>(&self, printer: &mut B, clear: bool) {
+    pub fn redraw<B: Backend>(&self, printer: &mut B, clear: bool) -> std::io::Result<()> {
         if clear {
             printer.clear();
         }
+        if self.dh.too_small.is_some() {
+            self.dh.print_too_small(printer);
+            return printer.refresh();
+        }
         let content = self.get_content();
-        self.dh.print_doublehex_screen(&content, printer);
+        match self.dh.second_grid {
+            Some(grid) => {
+                let second = self.lines(self.second_start(grid), grid.columns, grid.bytes_per_row);
+                self.dh.print_split_screen(&content, &second, printer);
+            }
+            None => self.dh.print_doublehex_screen(&content, printer),
+        }
         self.set_cursor(printer, self.dh.cursor_act);
         self.print_bars(printer);
-        printer.refresh();
+        printer.refresh()
     }
     /// Resizes and redraws.
-    pub fn refresh<B: Backend>(&mut self, printer: &mut B) {
+    pub fn refresh<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let changed = self.resize(printer.size());
-        self.redraw(printer, changed);
+        self.redraw(printer, changed)
     }
     /// Paints the cursor at the current position
     fn set_cursor<B: Backend>(&self, printer: &mut B, cursor_act: CursorActive) {
         let idx = self.cursor_index();
-        let addrs @ [addr0, addr1] = self.current_cursor_addresses();
+        let [addr0, addr1] = self.current_cursor_addresses();
         let [sel0, sel1] = self
             .selection
             .selection_status([idx, idx - self.data.shift]);
 
-        let (a, b) = self.data.get(idx);
+        let (a, b) = self.bytes_at(idx);
+        let [level0, level1] = self
+            .entropy_levels()
+            .map(|levels| levels.get(self.dh.cursor.get_index()).copied());
+        let [tint0, tint1] = self
+            .dh
+            .annotation_tints([addr0, addr1], self.dh.style.palette().annotation);
+        let is_ignored = self.dh.is_ignored([addr0, addr1], [a, b]);
+        let change = self.dh.change([addr0, addr1], [a, b]);
+        let [edited0, edited1] = self.dh.is_edited([addr0, addr1]);
         let [a, b] = [
-            (&self.searches.0, addr0, sel0, a),
-            (&self.searches.1, addr1, sel1, b),
+            (&self.searches.0, addr0, sel0, a, level0, tint0, edited0),
+            (&self.searches.1, addr1, sel1, b, level1, tint1, edited1),
         ]
-        .map(|(search, addr, sel, byte)| {
-            let is_search_result = search.as_ref().map_or(false, |s| s.is_in_result(addr));
-            ByteData::new(byte, is_search_result, sel)
+        .map(|(search, addr, sel, byte, level, tint, edited)| {
+            let search_slot = search
+                .iter()
+                .position(|s| s.is_in_result(addr))
+                .map(|slot| slot as u8);
+            ByteData::new(byte, search_slot, sel)
+                .current_result(search_slot.is_some())
+                .entropy_level(level.unwrap_or_default())
+                .annotation(tint)
+                .ignored(is_ignored)
+                .change(change)
+                .edited(edited)
+        });
+        let row_start = idx - self.dh.cursor.get_x() as isize;
+        let mut row = self.dh.cursor_row(|col| {
+            let index = row_start + col as isize;
+            let (a, b) = self.bytes_at(index);
+            (a, b, self.dh.differ(self.addresses_at(index), [a, b]))
         });
+        if let Some(grid) = self.dh.second_grid {
+            // the row of the second cursor has other bytes if the second half has its own columns
+            let x = self.second_column(grid);
+            let bytes = (0..grid.columns as isize)
+                .map(|col| self.bytes_at(idx - x as isize + col).1)
+                .collect::<Vec<_>>();
+            let text = self.dh.style.encoding.decode(&bytes);
+            row.text[1] = text.get(x).copied().unwrap_or(' ');
+            row.second_column = (x < grid.columns).then_some(x);
+        }
         self.dh
-            .set_doublehex_cursor(printer, cursor_act, (a, b), addrs);
+            .set_doublehex_cursor(printer, cursor_act, (a, b), row);
     }
     /// changes the active cursor to be cursor_act and moves back into bounds if the active cursor is outside bounds
-    fn change_active_cursor<B: Backend>(&mut self, printer: &mut B, cursor_act: CursorActive) {
+    fn change_active_cursor<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        cursor_act: CursorActive,
+    ) -> std::io::Result<()> {
+        if cursor_act != CursorActive::Both && self.dh.offset_lock {
+            self.dh.offset_lock = false;
+            self.dh
+                .set_notice(Severity::Info, "Unlocked the offset between the cursors");
+        }
         self.dh.cursor_act = cursor_act;
-        self.move_back_into_bounds(printer);
+        self.move_back_into_bounds(printer)?;
         self.set_cursor(printer, cursor_act);
         if self.selection.is_active() {
             self.redraw(printer, false)
         } else {
+            self.print_bars(printer);
             printer.refresh()
         }
     }
     /// Converts the content of the CompVec into DoubleHexLines so they can be displayed
     fn get_content(&self) -> Vec<DoubleHexLine> {
+        self.lines(
+            self.index,
+            self.dh.cursor.get_size_x(),
+            self.dh.cursor.bytes_per_row(),
+        )
+    }
+    /// The DoubleHexLines of the rows in view of a grid with the given columns and
+    /// bytes per row, starting at the index `start`
+    fn lines(&self, start: isize, columns: usize, bytes_per_row: usize) -> Vec<DoubleHexLine> {
         let mut content = Vec::new();
-        for x in 0..self.dh.cursor.get_size_y() {
+        let current = self
+            .searches
+            .current_results(self.current_cursor_addresses());
+        let rows = self.dh.cursor.get_size_y();
+        let entropy = self
+            .dh
+            .entropy_levels(start, rows * bytes_per_row, |range| self.bytes_in(range));
+        let tint = self.dh.style.palette().annotation;
+        for x in 0..rows {
             // address of the nth line
-            let base_addr = (x * self.dh.cursor.bytes_per_row()) as isize + self.index;
-            let address = [
+            let base_addr = (x * bytes_per_row) as isize + start;
+            let address = self.dh.shown_addresses([
                 self.data.get_first_addr(base_addr),
                 self.data.get_second_addr(base_addr),
-            ];
+            ]);
             let mut bytes = Vec::new();
             for (i, (byte_a, byte_b)) in self
-                .data
-                .get_range(base_addr..base_addr + self.dh.cursor.get_size_x() as isize)
+                .bytes_in(base_addr..base_addr + columns as isize)
                 .into_iter()
                 .enumerate()
             {
                 let current_index = base_addr + i as isize;
-                let addresses = [
-                    self.data.get_first_addr(current_index),
-                    self.data.get_second_addr(current_index),
-                ];
+                let addresses = self.addresses_at(current_index);
+                let is_ignored = self.dh.is_ignored(addresses, [byte_a, byte_b]);
+                let change = self.dh.change(addresses, [byte_a, byte_b]);
                 let [is_first_result, is_second_result] = self.searches.is_in_result(addresses);
+                let [is_first_current, is_second_current] = current.contains(addresses);
                 let [is_first_selected, is_second_selected] = self
                     .selection
                     .selection_status([current_index, current_index - self.data.shift]);
+                let level = |side: usize| {
+                    let offset = (current_index - start) as usize;
+                    entropy[side].get(offset).copied().unwrap_or_default()
+                };
+                let [first_tint, second_tint] = self.dh.annotation_tints(addresses, tint);
+                let [is_first_edited, is_second_edited] = self.dh.is_edited(addresses);
                 bytes.push((
-                    ByteData::new(byte_a, is_first_result, is_first_selected),
-                    ByteData::new(byte_b, is_second_result, is_second_selected),
+                    ByteData::new(byte_a, is_first_result, is_first_selected)
+                        .current_result(is_first_current)
+                        .entropy_level(level(0))
+                        .annotation(first_tint)
+                        .ignored(is_ignored)
+                        .change(change)
+                        .edited(is_first_edited),
+                    ByteData::new(byte_b, is_second_result, is_second_selected)
+                        .current_result(is_second_current)
+                        .entropy_level(level(1))
+                        .annotation(second_tint)
+                        .ignored(is_ignored)
+                        .change(change)
+                        .edited(is_second_edited),
                 ));
             }
 
-            content.push(DoubleHexLine { address, bytes });
+            content.push(DoubleHexLine {
+                address,
+                bytes,
+                fold: None,
+            });
         }
         content
     }
-    fn bytes_in_view(&self) -> [Vec<u8>; 2] {
-        let mut ret = [vec![], vec![]];
-        for (first, second) in self
-            .data
-            .get_range(self.index..self.index + self.dh.cursor.get_size() as isize)
-        {
-            if let Some(f) = first {
-                ret[0].push(f);
-            }
-            if let Some(s) = second {
-                ret[1].push(s);
-            }
-        }
-        ret
+    /// The entropy levels of the bytes in view, see `DoubleHexContext::entropy_levels`
+    fn entropy_levels(&self) -> [Vec<u8>; 2] {
+        self.dh
+            .entropy_levels(self.index, self.dh.cursor.get_size(), |range| {
+                self.bytes_in(range)
+            })
+    }
+    /// The column of the cursor in the second half with its own grid,
+    /// whose rows start at multiples of its bytes per row in the second file
+    fn second_column(&self, grid: SecondGrid) -> usize {
+        (self.cursor_index() - self.data.shift).rem_euclid(grid.bytes_per_row as isize) as usize
+    }
+    /// The index of the first byte in view of the second half with its own grid,
+    /// which has the cursor in the same row as the first half
+    fn second_start(&self, grid: SecondGrid) -> isize {
+        let row = self.dh.cursor.get_y() * grid.bytes_per_row;
+        self.cursor_index() - (self.second_column(grid) + row) as isize
     }
     pub fn set_shift(&mut self, shift: isize) {
         self.data.shift = shift;
     }
-    /// Prints the top and bottom bar
+    /// The title of the terminal window for this view
+    pub fn window_title(&self) -> String {
+        if self.dh.style.single {
+            format!("biodiff: {}", self.filenames.0)
+        } else {
+            window_title(&self.filenames)
+        }
+    }
+    /// Prints the top and bottom bar and the diff map, which only shows where the view is
     fn print_bars<B: Backend>(&self, printer: &mut B) {
+        // they would be drawn over the message of a screen that is too small
+        if self.dh.too_small.is_some() {
+            return;
+        }
+        let view = self.index..self.index + self.dh.cursor.get_size() as isize;
+        let cells = viewport_cells(self.data.bounds(), self.dh.map_cells(), view);
+        self.dh.print_diff_map(printer, &cells);
+        let lens = self.data.get_data().map(|content| content.len());
+        let [first, second] =
+            [(&self.filenames.0, 0), (&self.filenames.1, 1)].map(|(name, i)| FileTitle {
+                name,
+                len: lens[i],
+                modified: self.dh.modified[i],
+                edited: self.dh.edits[i].unsaved,
+            });
+        let busy: &[&str] = match self.searches.is_running() {
+            true => &["searching"],
+            false => &[],
+        };
         self.dh
-            .print_title_line(printer, " unaligned", &self.filenames.0, &self.filenames.1);
+            .print_title_line(printer, " unaligned", busy, None, first, second);
         let addr = self.current_cursor_addresses();
-        self.dh.print_bottom_line(printer, addr);
+        let status = self.searches.status_text(self.dh.cursor_act);
+        self.dh.print_bottom_line(
+            printer,
+            addr,
+            None,
+            lens,
+            self.selection_file_ranges(),
+            status.as_deref(),
+        );
     }
     /// returns the bound of the index of the currently active cursor(s)
     fn active_data_bounds(&self) -> Range<isize> {
         match self.dh.cursor_act {
+            CursorActive::Both if self.dh.offset_lock => self.locked_bounds(),
             CursorActive::Both | CursorActive::None => self.data.bounds(),
             CursorActive::First => self.data.first_bound(),
             CursorActive::Second => self.data.second_bound(),
         }
     }
+    /// The indexes where both files have bytes, which the cursors do not leave
+    /// while their offset is locked
+    fn locked_bounds(&self) -> Range<isize> {
+        let [first, second] = [self.data.first_bound(), self.data.second_bound()];
+        first.start.max(second.start)..first.end.min(second.end)
+    }
+    /// Locks the offset between the cursors so that both move together without leaving
+    /// the bytes where the files overlap, or unlocks it again
+    pub fn toggle_offset_lock<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        if self.dh.offset_lock {
+            self.dh.offset_lock = false;
+            self.dh
+                .set_notice(Severity::Info, "Unlocked the offset between the cursors");
+            self.print_bars(printer);
+            return printer.refresh();
+        }
+        if self.locked_bounds().is_empty() {
+            self.dh.set_notice(
+                Severity::Warning,
+                "The files do not overlap at the offset between the cursors",
+            );
+            self.print_bars(printer);
+            return printer.refresh();
+        }
+        self.dh.offset_lock = true;
+        self.dh
+            .set_notice(Severity::Info, "Locked the offset between the cursors");
+        self.change_active_cursor(printer, CursorActive::Both)
+    }
     /// returns the current index of the cursor into the data
     fn cursor_index(&self) -> isize {
         self.index + self.dh.cursor.get_index() as isize
     }
     /// when the currently active cursor is outside of bounds, move it back
     /// into bounds
-    pub fn move_back_into_bounds<B: Backend>(&mut self, printer: &mut B) {
+    pub fn move_back_into_bounds<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let old_active = self.dh.cursor_act;
         let bounds = self.active_data_bounds();
         self.dh.cursor_act = CursorActive::Both;
@@ -182,40 +412,76 @@ impl Unaligned {
             bounds.end - 1
         } else {
             self.dh.cursor_act = old_active;
-            return;
+            return Ok(());
         };
-        self.goto_index(printer, new_index);
+        let result = self.goto_index(printer, new_index);
         self.dh.cursor_act = old_active;
+        result
     }
     /// go to an index on both sides, regardless of currently active cursor
-    pub fn goto_index_both<B: Backend>(&mut self, printer: &mut B, index: isize) {
+    pub fn goto_index_both<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        index: isize,
+    ) -> std::io::Result<()> {
         let old_active = self.dh.cursor_act;
         self.dh.cursor_act = CursorActive::Both;
-        self.goto_index(printer, index);
+        let result = self.goto_index(printer, index);
         self.dh.cursor_act = old_active;
+        result
     }
     /// set the current shift in the data to `shift` and jump to the common
     /// sequence of highest entropy length
-    pub fn align_custom<B: Backend>(&mut self, printer: &mut B, shift: isize) {
+    pub fn align_custom<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        shift: isize,
+    ) -> std::io::Result<()> {
+        self.remember_state();
         self.set_shift(shift);
         let hi_idx = self.data.highest_common_entropy();
-        self.goto_index_both(printer, hi_idx);
+        self.goto_index_both(printer, hi_idx)
+    }
+    /// The offset from the shown address of the first cursor to the one of the second cursor,
+    /// which is the same for all bytes until the offset is set again
+    pub fn offset_between_cursors(&self) -> isize {
+        self.offset_at_shift(self.data.shift)
+    }
+    /// The offset between the cursors with the given shift of the second file
+    fn offset_at_shift(&self, shift: isize) -> isize {
+        let [first_base, second_base] = self.dh.bases.map(|base| base as isize);
+        second_base - first_base - shift
+    }
+    /// The offsets between the cursors now and after aligning the starts or the ends
+    /// of the files, for choosing how to set the offset
+    pub fn offset_choices(&self) -> [isize; 3] {
+        let end_shift = self.data.xvec.len() as isize - self.data.yvec.len() as isize;
+        [self.data.shift, 0, end_shift].map(|shift| self.offset_at_shift(shift))
     }
     /// align the starts of the data and jump to them
-    pub fn align_start<B: Backend>(&mut self, printer: &mut B) {
+    pub fn align_start<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        self.remember_state();
         self.set_shift(0);
-        self.goto_index_both(printer, 0);
+        self.goto_index_both(printer, 0)
     }
     /// align the ends of the data and jump to them
-    pub fn align_end<B: Backend>(&mut self, printer: &mut B) {
+    pub fn align_end<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let diff = self.data.xvec.len() as isize - self.data.yvec.len() as isize;
+        self.remember_state();
         self.set_shift(diff);
-        self.goto_index_both(printer, self.data.xvec.len() as isize - 1);
+        self.goto_index_both(printer, self.data.xvec.len() as isize - 1)
     }
     /// moves the cursor xdiff down and ydiff to the right,
     /// redrawing/scrolling if necessary
-    pub fn move_around<B: Backend>(&mut self, printer: &mut B, movement: Move) {
+    pub fn move_around<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        movement: Move,
+    ) -> std::io::Result<()> {
         self.set_cursor(printer, CursorActive::None);
+        let previous_results = self
+            .searches
+            .current_results(self.current_cursor_addresses());
         let bounds = self.active_data_bounds();
         let movement = if self.dh.style.right_to_left {
             movement.reflect_rtl()
@@ -233,127 +499,500 @@ impl Unaligned {
         };
         self.index += index_diff;
         let idx = self.cursor_index();
-        self.selection
-            .update([idx, idx - self.data.shift], self.dh.cursor_act);
-        // if they are moved independently, we cannot scroll
-        if !matches!(self.dh.cursor_act, CursorActive::Both) || self.selection.is_active() {
-            self.redraw(printer, false);
+        self.selection.update(
+            [idx, idx - self.data.shift],
+            self.row_ranges(idx),
+            self.dh.cursor_act,
+        );
+        let results_changed = previous_results
+            != self
+                .searches
+                .current_results(self.current_cursor_addresses());
+        // if they are moved independently or the second half has its own rows, we cannot scroll
+        if !matches!(self.dh.cursor_act, CursorActive::Both)
+            || self.dh.second_grid.is_some()
+            || self.selection.is_active()
+            || results_changed
+        {
+            self.redraw(printer, false)
         } else if let Some(scroll_amount) = self.dh.cursor.full_row_move(index_diff) {
             // scroll if we can
             let content = self.get_content();
             self.dh
                 .print_doublehex_scrolled(&content, printer, scroll_amount, |p| self.print_bars(p));
             self.set_cursor(printer, self.dh.cursor_act);
-            printer.refresh();
+            printer.refresh()
         } else {
-            self.redraw(printer, false);
+            self.redraw(printer, false)
         }
     }
     /// Function that processes only the move events
-    pub fn process_move<B: Backend>(&mut self, printer: &mut B, action: Action) {
-        match action {
-            Action::Down => self.move_around(printer, Move::CursorY(1)),
-            Action::DownAlt => self.move_around(printer, Move::ViewY(1)),
-            Action::Up => self.move_around(printer, Move::CursorY(-1)),
-            Action::UpAlt => self.move_around(printer, Move::ViewY(-1)),
-            Action::Left => self.move_around(printer, Move::CursorX(-1)),
-            Action::LeftAlt => self.move_around(printer, Move::ViewX(-1)),
-            Action::Right => self.move_around(printer, Move::CursorX(1)),
-            Action::RightAlt => self.move_around(printer, Move::ViewX(1)),
-            Action::PgDown => self.move_around(
-                printer,
-                Move::ViewY(self.dh.cursor.get_size_y() as isize / 2),
-            ),
-            Action::PgUp => self.move_around(
-                printer,
-                Move::ViewY(-(self.dh.cursor.get_size_y() as isize) / 2),
-            ),
-            Action::NextDifference => self.jump_next_difference(printer, true, false),
-            Action::NextInsertion => self.jump_next_difference(printer, true, true),
-            Action::PrevDifference => self.jump_next_difference(printer, false, false),
-            Action::PrevInsertion => self.jump_next_difference(printer, false, true),
+    /// (the movements and jumps are repeated `count` times)
+    pub fn process_move<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        action: Action,
+        count: usize,
+    ) -> std::io::Result<()> {
+        let n = count as isize;
+        let before = action.is_jump().then(|| self.cursor_addresses());
+        let result = match action {
+            Action::Down => self.move_around(printer, Move::CursorY(n)),
+            Action::DownAlt => self.move_around(printer, Move::ViewY(n)),
+            Action::Up => self.move_around(printer, Move::CursorY(-n)),
+            Action::UpAlt => self.move_around(printer, Move::ViewY(-n)),
+            Action::Left => self.move_around(printer, Move::CursorX(-n)),
+            Action::LeftAlt => self.move_around(printer, Move::ViewX(-n)),
+            Action::Right => self.move_around(printer, Move::CursorX(n)),
+            Action::RightAlt => self.move_around(printer, Move::ViewX(n)),
+            Action::PgDown => self.move_around(printer, Move::Page(self.dh.page_rows())),
+            Action::PgUp => self.move_around(printer, Move::Page(-self.dh.page_rows())),
+            Action::HalfPgDown => self.move_around(printer, Move::Page(self.dh.half_page_rows())),
+            Action::HalfPgUp => self.move_around(printer, Move::Page(-self.dh.half_page_rows())),
+            Action::SelectDown => self.extend_selection(printer, Move::CursorY(n)),
+            Action::SelectUp => self.extend_selection(printer, Move::CursorY(-n)),
+            Action::SelectLeft => self.extend_selection(printer, Move::CursorX(-n)),
+            Action::SelectRight => self.extend_selection(printer, Move::CursorX(n)),
+            Action::NextDifference => self.jump_next_difference(printer, true, false, count),
+            Action::NextInsertion => self.jump_next_difference(printer, true, true, count),
+            Action::PrevDifference => self.jump_next_difference(printer, false, false, count),
+            Action::PrevInsertion => self.jump_next_difference(printer, false, true, count),
+            Action::WordLeft => self.move_around(printer, self.dh.word_move(false)),
+            Action::WordRight => self.move_around(printer, self.dh.word_move(true)),
+            Action::RowStart => self.move_around(printer, Move::RowStart),
+            Action::RowEnd => self.move_around(printer, Move::RowEnd),
             Action::Top => self.jump_start(printer),
             Action::Bottom => self.jump_end(printer),
-            Action::NextSearch => self.jump_next_search_result(printer),
-            Action::PrevSearch => self.jump_prev_search_result(printer),
-            _ => (),
+            Action::JumpPercent => self.jump_map(printer, count.min(100), 100),
+            Action::NextSearch => self.jump_search_results(printer, true, count),
+            Action::PrevSearch => self.jump_search_results(printer, false, count),
+            Action::SetCursorTo(column, row) => self.set_cursor_to(printer, column, row),
+            Action::JumpBack => self.jump_history(printer, false),
+            Action::JumpForward => self.jump_history(printer, true),
+            _ => Ok(()),
+        };
+        if let Some(before) = before {
+            self.remember_jump(before);
         }
+        result
     }
-    /// Inreases the column count by one and refreshes the view
-    pub fn add_column<B: Backend>(&mut self, printer: &mut B) {
-        self.dh.inc_columns();
-        self.refresh(printer);
+    /// Inreases the column count of the active halves by one and refreshes the view
+    pub fn add_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        self.dh.inc_columns(self.dh.cursor_act);
+        self.refresh(printer)
     }
-    /// Decreases the column count by one and refreshes the view
-    pub fn remove_column<B: Backend>(&mut self, printer: &mut B) {
-        self.dh.dec_columns();
-        self.refresh(printer);
+    /// Decreases the column count of the active halves by one and refreshes the view
+    pub fn remove_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        self.dh.dec_columns(self.dh.cursor_act);
+        self.refresh(printer)
     }
     /// Sets the column count to the peak of the autocorrelation of
-    /// the bytes in the current view and refreshes the view
-    pub fn auto_column<B: Backend>(&mut self, printer: &mut B) {
+    /// the selected bytes, or of samples of the whole files without
+    /// a selection, and refreshes the view
+    pub fn auto_column<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let selection = self.selection_file_ranges();
         let data = self.data.get_data();
-        let [mut first, mut second] = if selection.iter().any(|x| x.is_some()) {
-            self.clear_selection(printer);
-            from_fn(|i| Some(data[i][selection[i].clone()?].to_vec()))
-                .map(|x| x.unwrap_or_default())
-        } else {
-            self.bytes_in_view()
-        };
-        // set vectors to be empty if the cursor is not active
-        if !self.dh.cursor_act.is_first() {
-            first = Vec::new();
-        }
-        if !self.dh.cursor_act.is_second() {
-            second = Vec::new();
+        let whole = selection.iter().all(|x| x.is_none());
+        if !whole {
+            self.selection.clear(self.dh.cursor_act);
         }
-        self.dh.auto_columns([&first, &second]);
-        self.refresh(printer);
+        let active = [
+            self.dh.cursor_act.is_first(),
+            self.dh.cursor_act.is_second(),
+        ];
+        // files without an active cursor are left out
+        let bytes: [&[u8]; 2] = from_fn(|i| match &selection[i] {
+            _ if !active[i] => &[][..],
+            Some(range) => &data[i][range.clone()],
+            None if whole => &data[i][..],
+            None => &[][..],
+        });
+        self.dh.auto_columns(bytes, self.dh.cursor_act);
+        self.refresh(printer)
     }
-    pub fn start_selection<B: Backend>(&mut self, printer: &mut B) {
+    pub fn start_selection<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let idx = self.cursor_index();
         self.selection
             .start([idx, idx - self.data.shift], self.dh.cursor_act);
-        self.redraw(printer, false);
+        self.redraw(printer, false)
+    }
+    /// The offsets of the row in view that contains the index in both files
+    fn row_ranges(&self, index: isize) -> [Range<isize>; 2] {
+        let bytes_per_row = self.dh.cursor.bytes_per_row() as isize;
+        let row = (index - self.index).div_euclid(bytes_per_row);
+        let start = self.index + row * bytes_per_row;
+        [0, self.data.shift].map(|shift| (start - shift)..(start - shift + bytes_per_row))
+    }
+    /// starts selecting whole rows at the row of the cursor
+    pub fn select_rows<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let idx = self.cursor_index();
+        self.selection.start_rows(
+            [idx, idx - self.data.shift],
+            self.row_ranges(idx),
+            self.dh.cursor_act,
+        );
+        self.redraw(printer, false)
+    }
+    /// moves the cursor while extending the selection, which is started
+    /// at the cursor if there is none yet
+    pub fn extend_selection<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        movement: Move,
+    ) -> std::io::Result<()> {
+        let idx = self.cursor_index();
+        self.selection
+            .start_missing([idx, idx - self.data.shift], self.dh.cursor_act);
+        self.move_around(printer, movement)
     }
     /// clears the selection with the currently active cursors
-    pub fn clear_selection<B: Backend>(&mut self, printer: &mut B) -> bool {
+    pub fn clear_selection<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<bool> {
+        let before = self.snapshot();
         let ret = self.selection.clear(self.dh.cursor_act);
-        self.redraw(printer, false);
-        ret
+        if ret {
+            self.undo_stack.push(before);
+        }
+        self.redraw(printer, false)?;
+        Ok(ret)
+    }
+    /// The current style, offset and selection, for going back to them
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            style: self.dh.style,
+            shift: self.data.shift,
+            selection: self.selection.clone(),
+        }
+    }
+    /// Remembers the current state before changing it
+    fn remember_state(&mut self) {
+        let snapshot = self.snapshot();
+        self.undo_stack.push(snapshot);
+    }
+    /// Goes back to the style, offset and selection before their last change,
+    /// with the cursor staying where it is
+    pub fn undo<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let Snapshot {
+            style,
+            shift,
+            selection,
+        } = match self.undo_stack.pop() {
+            Some(snapshot) => snapshot,
+            None => {
+                self.dh.set_notice(Severity::Warning, "Nothing to undo");
+                return self.set_pending_count(printer, None);
+            }
+        };
+        self.dh.style = style;
+        self.set_shift(shift);
+        self.selection = selection;
+        // the screen is cleared, since the diff map might have been toggled
+        self.resize(printer.size());
+        self.redraw(printer, true)
+    }
+    /// Starts edit mode for the file of the active cursor
+    fn start_editing<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let split = self.dh.style.unit != Unit::Byte;
+        let editing = self.dh.cursor_act != CursorActive::Both && !split;
+        if split {
+            self.dh.set_notice(
+                Severity::Warning,
+                "Editing is only possible when the bytes are not split with --unit",
+            );
+        } else if !editing {
+            self.dh.set_notice(
+                Severity::Warning,
+                "Choose the file to edit by activating its cursor (a or d)",
+            );
+        }
+        self.dh.editor.set_active(editing);
+        self.set_pending_count(printer, None)
+    }
+    /// Overwrites the byte at the active cursor with the typed character,
+    /// moving on to the next byte when it is complete
+    fn type_char<B: Backend>(&mut self, printer: &mut B, c: char) -> std::io::Result<()> {
+        // characters typed just before leaving edit mode can still arrive
+        if !self.dh.editor.is_active() {
+            return Ok(());
+        }
+        let second = self.dh.cursor_act == CursorActive::Second;
+        let addr = match self.current_cursor_addresses()[second as usize] {
+            Some(addr) => addr,
+            None => {
+                self.dh
+                    .set_notice(Severity::Warning, "The cursor is not inside of the file");
+                return self.set_pending_count(printer, None);
+            }
+        };
+        let content = if second {
+            &self.data.yvec
+        } else {
+            &self.data.xvec
+        };
+        let shown = self.dh.edits[second as usize]
+            .get(addr)
+            .unwrap_or(content[addr]);
+        match self.dh.editor.type_char(c, second, addr, shown) {
+            Ok((byte, complete)) => {
+                self.dh.edits[second as usize].write(content, addr, byte);
+                let next = self.cursor_index() + 1;
+                if complete && self.active_data_bounds().contains(&next) {
+                    self.goto_index(printer, next)?;
+                }
+                self.redraw(printer, false)
+            }
+            Err(e) => {
+                self.dh.set_notice(Severity::Error, e);
+                self.set_pending_count(printer, None)
+            }
+        }
+    }
+    /// The active file and the range of its selected bytes for filling them in edit mode,
+    /// or why they can not be filled
+    pub fn fill_range(&self) -> Result<(bool, Range<usize>), String> {
+        if !self.dh.editor.is_active() {
+            return Err(String::from("Filling works in edit mode, which 'i' starts"));
+        }
+        let second = self.dh.cursor_act == CursorActive::Second;
+        let [start, end] = self.selection.ranges(self.dh.cursor_act)[second as usize]
+            .ok_or_else(|| String::from("Select the bytes to fill first ('v')"))?;
+        let len = self.data.get_data()[second as usize].len() as isize;
+        if start < 0 || end >= len {
+            return Err(String::from("The selection reaches beyond the file"));
+        }
+        Ok((second, start as usize..end as usize + 1))
+    }
+    /// Overwrites the bytes in the range of one of the files with the same byte
+    pub fn fill(&mut self, second: bool, range: Range<usize>, byte: u8) {
+        let content = if second {
+            &self.data.yvec
+        } else {
+            &self.data.xvec
+        };
+        self.dh.edits[second as usize].fill(content, range, byte);
+        self.dh.editor.forget_half();
+    }
+    /// Undoes the last edit of the file of the active cursor, or of the first file
+    /// if both are active
+    fn undo_edit<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        let second = self.dh.cursor_act == CursorActive::Second;
+        if !self.dh.edits[second as usize].undo() {
+            self.dh.set_notice(Severity::Warning, "No edits to undo");
+            return self.set_pending_count(printer, None);
+        }
+        self.dh.editor.forget_half();
+        self.redraw(printer, false)
     }
     /// Executes an action corresponding to an escape and returns true
     /// if anything was done
-    pub fn process_escape<B: Backend>(&mut self, printer: &mut B) -> bool {
+    pub fn process_escape<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<bool> {
         self.clear_selection(printer)
     }
     /// Process a single action/event
-    pub fn process_action<B: Backend>(&mut self, printer: &mut B, action: Action) {
-        match action {
+    pub fn process_action<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        action: Action,
+    ) -> std::io::Result<()> {
+        // only the message fits on a screen that is too small, so the action is done without
+        // drawing it, and the screen is drawn again in case the view fits on it now
+        if self.dh.too_small.is_some() && !matches!(action, Action::Refresh | Action::Tick) {
+            let too_small = self.dh.too_small.take();
+            let result = self.process_action(&mut Dummy, action);
+            self.dh.too_small = too_small;
+            result?;
+            return self.refresh(printer);
+        }
+        // every other action uses up the count prefix and hides the notice
+        let count = match action {
+            Action::PendingCount(count) => return self.set_pending_count(printer, count),
+            Action::Tick => return self.tick(printer),
+            _ => self.dh.pending_count.take(),
+        };
+        let notice = self.dh.notice.take();
+        if self.dh.style.single && action.compares_files() {
+            self.dh.set_notice(
+                Severity::Warning,
+                "Not available when viewing a single file",
+            );
+            return self.set_pending_count(printer, None);
+        }
+        let before = action.can_be_undone().then(|| self.snapshot());
+        let result = match action {
             Action::Refresh => self.refresh(printer),
             Action::CursorFirst => self.change_active_cursor(printer, CursorActive::First),
+            Action::OffsetLock => self.toggle_offset_lock(printer),
             Action::CursorBoth => self.change_active_cursor(printer, CursorActive::Both),
             Action::CursorSecond => self.change_active_cursor(printer, CursorActive::Second),
             Action::AddColumn => self.add_column(printer),
             Action::RemoveColumn => self.remove_column(printer),
             Action::AutoColumn => self.auto_column(printer),
-            Action::StartSelection => self.start_selection(printer),
-            Action::ClearSelection => {
-                self.clear_selection(printer);
+            Action::RelativeAddresses => {
+                let addresses = self.current_cursor_addresses_clamped().map(Some);
+                let addresses = self.dh.shown_addresses(addresses);
+                self.dh.toggle_relative_addresses(addresses);
+                self.refresh(printer)
             }
+            Action::MultipleColumn => {
+                self.dh.multiple_columns(count.unwrap_or(1));
+                self.refresh(printer)
+            }
+            Action::SelectRows => self.select_rows(printer),
+            Action::StartSelection => self.start_selection(printer),
+            Action::ClearSelection => self.clear_selection(printer).map(|_| ()),
+            Action::DropSearch => self.remove_search(printer, false),
+            Action::ClearSearches => self.remove_search(printer, true),
             Action::ResetColumn => {
-                self.dh.style.column_count = ColumnSetting::Fit;
-                self.refresh(printer);
+                self.dh.reset_columns();
+                self.refresh(printer)
+            }
+            Action::CycleGroup => {
+                self.dh.cycle_group();
+                self.refresh(printer)
+            }
+            Action::NextBase => {
+                self.dh.style.mode = self.dh.style.mode.next_base();
+                self.refresh(printer)
+            }
+            Action::ByteClasses => {
+                self.dh.style.byte_classes = !self.dh.style.byte_classes;
+                self.refresh(printer)
+            }
+            Action::EntropyColors => {
+                self.dh.style.entropy = !self.dh.style.entropy;
+                self.refresh(printer)
+            }
+            Action::FoldIdentical | Action::ToggleFold => {
+                self.dh.set_notice(
+                    Severity::Warning,
+                    "Folding is only done in the aligned view",
+                );
+                self.set_pending_count(printer, None)
+            }
+            Action::DiffMap => {
+                // the screen is cleared, since the columns can stay the same
+                self.dh.style.diff_map = !self.dh.style.diff_map;
+                self.resize(printer.size());
+                self.redraw(printer, true)
+            }
+            Action::DeltaMode => {
+                self.dh.style.delta = self.dh.style.delta.next();
+                self.refresh(printer)
+            }
+            Action::ToggleSplit => {
+                self.dh.style.vertical = !self.dh.style.vertical;
+                self.refresh(printer)
             }
-            otherwise => self.process_move(printer, otherwise),
+            Action::ToggleText => {
+                self.dh.style.ascii_col = !self.dh.style.ascii_col;
+                self.refresh(printer)
+            }
+            Action::Follow => {
+                self.dh.toggle_follow();
+                self.set_pending_count(printer, None)
+            }
+            Action::ChangeFilter => {
+                self.dh.cycle_change_filter();
+                self.set_pending_count(printer, None)
+            }
+            Action::Undo => self.undo(printer),
+            Action::Edit => self.start_editing(printer),
+            Action::Type(c) => self.type_char(printer, c),
+            Action::SwitchInput => {
+                self.dh.editor.switch_input();
+                self.set_pending_count(printer, None)
+            }
+            Action::StopEditing => {
+                self.dh.editor.set_active(false);
+                self.set_pending_count(printer, None)
+            }
+            Action::UndoEdit => self.undo_edit(printer),
+            Action::Command => {
+                self.dh.prompt.open();
+                self.set_pending_count(printer, None)
+            }
+            Action::Prompt(key) => {
+                self.dh.prompt.key(key);
+                self.set_pending_count(printer, None)
+            }
+            otherwise => self.process_move(printer, otherwise, count.unwrap_or(1)),
+        };
+        if let Some(before) = before {
+            self.undo_stack.push(before);
         }
+        result?;
+        if count.is_some() || notice.is_some() {
+            self.set_pending_count(printer, None)?;
+        }
+        Ok(())
+    }
+    /// Sets the count prefix shown in the bottom line
+    fn set_pending_count<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        count: Option<usize>,
+    ) -> std::io::Result<()> {
+        self.dh.pending_count = count;
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// Moves the cursor to the byte displayed at the screen position, if there is one.
+    /// If the cursors are moved independently, the cursor of the clicked side becomes active.
+    pub fn set_cursor_to<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        column: usize,
+        row: usize,
+    ) -> std::io::Result<()> {
+        if let Some(cell) = self.dh.map_cell_at(column, row) {
+            let before = self.cursor_addresses();
+            self.jump_map(printer, cell, self.dh.map_cells())?;
+            self.remember_jump(before);
+            return Ok(());
+        }
+        let (x, y, second) = match self.dh.position_at(column, row) {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if !matches!(self.dh.cursor_act, CursorActive::Both) {
+            let clicked = if second {
+                CursorActive::Second
+            } else {
+                CursorActive::First
+            };
+            self.change_active_cursor(printer, clicked)?;
+        }
+        let index = match self.dh.second_grid {
+            Some(grid) if second => self.second_start(grid) + (y * grid.bytes_per_row + x) as isize,
+            _ => self.index + (y * self.dh.cursor.bytes_per_row() + x) as isize,
+        };
+        if self.active_data_bounds().contains(&index) {
+            self.goto_index(printer, index)?;
+        }
+        Ok(())
     }
     /// jump to a given index with the currently active cursor
-    pub fn goto_index<B: Backend>(&mut self, printer: &mut B, index: isize) {
+    pub fn goto_index<B: Backend>(&mut self, printer: &mut B, index: isize) -> std::io::Result<()> {
+        let index = self.clamp_locked(index);
         let address_diff = index - self.cursor_index();
         let (col, row) = self.dh.cursor.jump(address_diff);
-        self.move_around(printer, Move::Unbounded(col, row));
+        self.move_around(printer, Move::Unbounded(col, row))
+    }
+    /// Keeps the index where both files have bytes while the offset is locked,
+    /// unlocking it if the files no longer overlap (like after setting another offset)
+    fn clamp_locked(&mut self, index: isize) -> isize {
+        if !self.dh.offset_lock || self.dh.cursor_act != CursorActive::Both {
+            return index;
+        }
+        let bounds = self.locked_bounds();
+        if bounds.is_empty() {
+            self.dh.offset_lock = false;
+            self.dh.set_notice(
+                Severity::Warning,
+                "Unlocked the offset, the files do not overlap at it",
+            );
+            return index;
+        }
+        index.clamp(bounds.start, bounds.end - 1)
     }
     /// Go to the address in `pos`, right is true if on the second view, else the first view is used.
     /// Returns true if the address exists.
@@ -363,52 +1002,169 @@ impl Unaligned {
         first: usize,
         second: Option<usize>,
     ) -> Result<(), String> {
-        let error_on_bound = |bound: usize, addr: usize| {
+        let before = self.cursor_addresses();
+        self.goto_addresses(printer, first, second)?;
+        self.remember_jump(before);
+        Ok(())
+    }
+    /// Go to the addresses like `goto`, without remembering the jump
+    fn goto_addresses<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        first: usize,
+        second: Option<usize>,
+    ) -> Result<(), String> {
+        let bases = self.dh.bases;
+        let error_on_bound = |bound: usize, addr: usize, second: bool| {
+            let base = bases[second as usize];
             if bound > addr {
                 Ok(())
             } else {
                 Err(format!(
-                    "Target address {addr:#x} is not in bounds (< {bound:#x})",
+                    "Target address {:#x} is not in bounds (< {:#x})",
+                    addr + base,
+                    bound + base
                 ))
             }
         };
+        let draw_error = |e: std::io::Error| format!("Could not draw the view: {e}");
+        // a single address is one of the second file only if its cursor is the only active one
+        let first = self.dh.file_address(
+            second.is_none() && self.dh.cursor_act == CursorActive::Second,
+            first,
+        )?;
+        let second = second
+            .map(|second| self.dh.file_address(true, second))
+            .transpose()?;
         let [first_len, second_len] = self.data.get_data().map(|x| x.len());
         let (target_idx, shift) = match (self.dh.cursor_act, second) {
             (CursorActive::None, _) => return Ok(()),
             (CursorActive::First, None) => {
-                error_on_bound(first_len, first)?;
-                self.goto_index(printer, first as isize);
-                return Ok(());
+                error_on_bound(first_len, first, false)?;
+                return self.goto_index(printer, first as isize).map_err(draw_error);
             }
             (CursorActive::Second, None) => {
-                error_on_bound(second_len, first)?;
-                self.goto_index(printer, first as isize + self.data.shift);
-                return Ok(());
+                error_on_bound(second_len, first, true)?;
+                return self
+                    .goto_index(printer, first as isize + self.data.shift)
+                    .map_err(draw_error);
+            }
+            // the other cursor keeps its offset if it is locked
+            (CursorActive::Both, None) if self.dh.offset_lock => {
+                error_on_bound(first_len, first, false)?;
+                let other = first as isize - self.data.shift;
+                if !(0..second_len as isize).contains(&other) {
+                    return Err(format!(
+                        "The locked offset puts the other cursor outside of its file at {:#x}",
+                        first + bases[0]
+                    ));
+                }
+                (first as isize, self.data.shift)
             }
             (CursorActive::Both, None) => {
-                error_on_bound(first_len.max(second_len), first)?;
+                error_on_bound(first_len.max(second_len), first, false)?;
                 (first as isize, 0)
             }
             (_, Some(second)) => {
-                error_on_bound(first_len, first)?;
-                error_on_bound(second_len, second)?;
+                error_on_bound(first_len, first, false)?;
+                error_on_bound(second_len, second, true)?;
                 (first as isize, first as isize - second as isize)
             }
         };
         let old_cursor_act = self.dh.cursor_act;
         self.dh.cursor_act = CursorActive::Both;
         self.data.shift = shift;
-        self.goto_index(printer, target_idx);
+        let result = self.goto_index(printer, target_idx);
         self.dh.cursor_act = old_cursor_act;
-        Ok(())
+        result.map_err(draw_error)
+    }
+    /// Goes to a position of the jump history, keeping the offset between the files
+    /// if only one of the cursors was inside of its file
+    fn goto_position<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        position: [Option<usize>; 2],
+    ) -> Result<(), String> {
+        let (right, shown) = match position {
+            [Some(first), Some(second)] => {
+                return self.goto_addresses(printer, first, Some(second))
+            }
+            [Some(first), None] => (false, first),
+            [None, Some(second)] => (true, second),
+            [None, None] => return Ok(()),
+        };
+        let address = self.dh.file_address(right, shown)?;
+        let len = self.data.get_data()[right as usize].len();
+        if address >= len {
+            return Err(format!(
+                "Target address {shown:#x} is not in bounds (< {:#x})",
+                len + self.dh.bases[right as usize]
+            ));
+        }
+        self.goto_index_both(printer, self.index_address(right, address))
+            .map_err(|e| format!("Could not draw the view: {e}"))
+    }
+    /// Remembers the position of the cursors before a jump if they moved
+    fn remember_jump(&mut self, before: [Option<usize>; 2]) {
+        if self.cursor_addresses() != before {
+            self.dh.history.push(before);
+        }
+    }
+    /// Goes back to the position before the last jump, or forward again after going back
+    fn jump_history<B: Backend>(&mut self, printer: &mut B, forward: bool) -> std::io::Result<()> {
+        let position = match forward {
+            true => self.dh.history.forward(),
+            false => self.dh.history.back(self.cursor_addresses()),
+        };
+        match position.map(|position| self.goto_position(printer, position)) {
+            Some(Err(e)) => {
+                self.dh.set_notice(Severity::Error, e);
+                self.set_pending_count(printer, None)
+            }
+            _ => Ok(()),
+        }
+    }
+    /// The shown addresses of the cursors, or None for a cursor outside of its file
+    pub fn cursor_addresses(&self) -> [Option<usize>; 2] {
+        self.dh.shown_addresses(self.current_cursor_addresses())
+    }
+    /// The positions of the cursors in the compared bytes, which may be outside of the files.
+    /// Their difference is the offset between the files.
+    pub fn positions(&self) -> [isize; 2] {
+        let index = self.cursor_index();
+        [index, index - self.data.shift]
+    }
+    /// Moves the cursors to the positions without drawing the view, like the ones of `positions`
+    /// when restoring a session
+    pub fn set_positions(&mut self, positions: [isize; 2]) {
+        // the shift between the files may be clamped to keep them overlapping
+        let [first, _] = self.positions();
+        self.index += self.data.add_first_shift(first - positions[0]);
+        let [_, second] = self.positions();
+        self.index += self.data.add_second_shift(second - positions[1]);
     }
     /// get the file addresses of the current cursors
     fn current_cursor_addresses(&self) -> [Option<usize>; 2] {
+        self.addresses_at(self.cursor_index())
+    }
+    /// The addresses of the bytes of both files at an index
+    fn addresses_at(&self, index: isize) -> [Option<usize>; 2] {
         [
-            self.data.get_first_addr(self.cursor_index()),
-            self.data.get_second_addr(self.cursor_index()),
+            self.data.get_first_addr(index),
+            self.data.get_second_addr(index),
         ]
     }
+    /// The bytes of both files at the index as they are shown, which are the edited ones
+    /// where they were overwritten
+    fn bytes_at(&self, index: isize) -> (Option<u8>, Option<u8>) {
+        let (a, b) = self.data.get(index);
+        let [a, b] = self.dh.edited_bytes(self.addresses_at(index), [a, b]);
+        (a, b)
+    }
+    /// The bytes of both files at the indexes of the range, see `bytes_at`
+    fn bytes_in(&self, range: Range<isize>) -> Vec<(Option<u8>, Option<u8>)> {
+        range.map(|index| self.bytes_at(index)).collect()
+    }
 
     fn current_cursor_addresses_clamped(&self) -> [usize; 2] {
         let idx = [self.cursor_index(), self.cursor_index() - self.data.shift];
@@ -427,26 +1183,32 @@ impl Unaligned {
         from_fn(|i| {
             let len = self.data.get_data().map(|x| x.len())[i];
             ranges[i].map(|[start, end]| {
-                let [start, end] = [start, end + 1].map(|x| (x as usize).clamp(0, len));
+                let [start, end] = [start, end + 1].map(|x| x.clamp(0, len as isize) as usize);
                 start..end
             })
         })
     }
 
+    /// The selected bytes of the active files, see `selected_bytes`
+    pub fn selected_bytes(&self) -> Vec<SelectedBytes> {
+        selected_bytes(
+            [&self.filenames.0, &self.filenames.1],
+            self.edited_files(),
+            self.selection_file_ranges(),
+            self.dh.bases,
+            self.dh.cursor_act,
+        )
+    }
+
     /// get the search results and positions of all active cursors
-    fn search_data(&self) -> Vec<(&Option<SearchResults>, usize, bool)> {
+    fn search_data(&self) -> Vec<(&SearchResults, usize, bool)> {
         let [first, second] = self.current_cursor_addresses();
-        first
-            .map(|x| (&self.searches.0, x, false))
-            .filter(|_| self.dh.cursor_act.is_first())
-            .iter()
-            .chain(
-                second
-                    .map(|x| (&self.searches.1, x, true))
-                    .filter(|_| self.dh.cursor_act.is_second())
-                    .iter(),
-            )
-            .copied()
+        self.searches
+            .active(self.dh.cursor_act)
+            .into_iter()
+            .filter_map(|(search, right)| {
+                Some((search, if right { second } else { first }?, right))
+            })
             .collect()
     }
     /// get the file address of the current index, with the side given by `right`
@@ -458,97 +1220,241 @@ impl Unaligned {
         }
     }
     /// Jump to the next search result on either active cursor after the current index
-    pub fn jump_next_search_result<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_next_search_result<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let search_data = self.search_data();
-        let next = match SearchResults::nearest_next_result(&search_data, |addr, right| {
+        let next = SearchResults::nearest_next_result(&search_data, |addr, right| {
             Some(self.index_address(right, addr))
-        }) {
+        });
+        let (next, wrapped) = match next {
             Some(x) => x,
-            None => return,
+            None => return Ok(()),
         };
+        // jumping back to the other end of the file is easily mistaken for going on
+        if wrapped {
+            self.dh
+                .set_notice(Severity::Warning, "Search wrapped around to the start");
+        }
         self.goto_index(printer, next)
     }
     /// Jump to the previous search reult on either active cursor before the current index
-    pub fn jump_prev_search_result<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_prev_search_result<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let search_data = self.search_data();
-        let next = match SearchResults::nearest_prev_result(&search_data, |addr, right| {
+        let next = SearchResults::nearest_prev_result(&search_data, |addr, right| {
             Some(self.index_address(right, addr))
-        }) {
+        });
+        let (next, wrapped) = match next {
             Some(x) => x,
-            None => return,
+            None => return Ok(()),
         };
+        if wrapped {
+            self.dh
+                .set_notice(Severity::Warning, "Search wrapped around to the end");
+        }
         self.goto_index(printer, next)
     }
-    /// Jump to the index where the next bytes are different
+    /// Jumps `count` search results forward or backward, only drawing the final position
+    fn jump_search_results<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        forward: bool,
+        count: usize,
+    ) -> std::io::Result<()> {
+        if count <= 1 {
+            return match forward {
+                true => self.jump_next_search_result(printer),
+                false => self.jump_prev_search_result(printer),
+            };
+        }
+        // the intermediate jumps are not drawn
+        for _ in 0..count {
+            match forward {
+                true => self.jump_next_search_result(&mut Dummy)?,
+                false => self.jump_prev_search_result(&mut Dummy)?,
+            }
+        }
+        self.redraw(printer, false)
+    }
+    /// Jump to the index where the `count`th next bytes are different
     pub fn jump_next_difference<B: Backend>(
         &mut self,
         printer: &mut B,
         forward: bool,
         insertion: bool,
-    ) {
-        let target_address = next_difference(
-            self.cursor_index(),
-            self.data.bounds(),
-            forward,
-            |i| match self.data.get(i) {
-                (None | Some(_), None) | (None, Some(_)) => true,
-                (Some(a), Some(b)) => a != b && !insertion,
-            },
-        );
-        self.goto_index(printer, target_address);
+        count: usize,
+    ) -> std::io::Result<()> {
+        let target_address = (0..count).fold(self.cursor_index(), |address, _| {
+            next_difference(address, self.data.bounds(), forward, |i| {
+                match self.bytes_at(i) {
+                    (None, None) => true,
+                    (Some(_), Some(_)) if insertion => false,
+                    (a, b) => self.dh.is_jump_target(self.addresses_at(i), [a, b]),
+                }
+            })
+        });
+        self.goto_index(printer, target_address)
     }
     /// Go to the first position of the file
-    pub fn jump_start<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_start<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let index = self.active_data_bounds().start;
         self.goto_index(printer, index)
     }
     /// Go to the last position of the file
-    pub fn jump_end<B: Backend>(&mut self, printer: &mut B) {
+    pub fn jump_end<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
         let index = self.active_data_bounds().end - 1;
         self.goto_index(printer, index)
     }
-    /// Adds a batch of search results to the current ones if they are of the same query.
-    pub fn add_search_results(
+    /// Go to the start of the `cell`th of `cells` equal slices of the bytes,
+    /// like the cells of the diff map
+    fn jump_map<B: Backend>(
         &mut self,
-        query: Query,
-        results: Vec<Option<Range<usize>>>,
-        first: bool,
-    ) {
-        let search = if first {
-            &mut self.searches.0
-        } else {
-            &mut self.searches.1
+        printer: &mut B,
+        cell: usize,
+        cells: usize,
+    ) -> std::io::Result<()> {
+        self.goto_index(printer, map_index(self.data.bounds(), cell, cells))
+    }
+    /// Adds a batch of search results to the current ones if they are of the same query.
+    /// Tells how many results were found when the search finishes.
+    pub fn add_search_results(&mut self, query: Query, results: Vec<SearchMessage>, first: bool) {
+        let search = match self.searches.results_mut(first, &query) {
+            Some(s) => s,
+            None => return,
         };
-        let search = match search {
-            Some(s) if s.query() == &query => s,
-            _ => return,
+        let running = search.progress().is_some();
+        for message in results {
+            search.update(message)
+        }
+        let name = if first {
+            &self.filenames.0
+        } else {
+            &self.filenames.1
         };
-        for result in results.iter().flatten() {
-            search.add_match(result.clone())
+        if let (true, Some(summary)) = (running, search.finished_summary(name)) {
+            self.dh.set_notice(Severity::Info, summary);
+        }
+    }
+    /// The names of both files
+    pub fn filenames(&self) -> [&str; 2] {
+        [&self.filenames.0, &self.filenames.1]
+    }
+    /// The contents of both files
+    pub fn files(&self) -> [FileContent; 2] {
+        [self.data.xvec.clone(), self.data.yvec.clone()]
+    }
+    /// The contents of both files with their edits
+    pub fn edited_files(&self) -> [FileContent; 2] {
+        self.dh.edited_files(self.files())
+    }
+    /// Sets whether the files changed on disk and redraws the title line
+    pub fn set_modified<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        modified: [bool; 2],
+    ) -> std::io::Result<()> {
+        self.dh.modified = modified;
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// Turns the busy indicator while searching and hides the notice if it was shown
+    /// long enough, redrawing the bars if anything changed
+    pub fn tick<B: Backend>(&mut self, printer: &mut B) -> std::io::Result<()> {
+        if !self.dh.tick(Instant::now(), self.searches.is_running()) {
+            return Ok(());
+        }
+        self.print_bars(printer);
+        printer.refresh()
+    }
+    /// Replaces the files by their grown versions, keeping the edits, and redraws.
+    /// If the cursor was at the end, it moves to the new end.
+    pub fn grow<B: Backend>(
+        &mut self,
+        printer: &mut B,
+        files: [FileContent; 2],
+    ) -> std::io::Result<()> {
+        let at_end = self.cursor_index() == self.active_data_bounds().end - 1;
+        [self.data.xvec, self.data.yvec] = files;
+        match (at_end, self.dh.too_small) {
+            (true, None) => self.jump_end(printer),
+            // the jump is not drawn over the message of a screen that is too small
+            (true, Some(_)) => {
+                self.jump_end(&mut Dummy)?;
+                self.redraw(printer, false)
+            }
+            (false, _) => self.redraw(printer, false),
         }
     }
     /// Clears the search results of the currently active cursors
     pub fn clear_search(&mut self) {
         self.searches.clear(self.dh.cursor_act)
     }
+    /// Removes either the most recent or all search queries of the active cursors and redraws
+    pub fn remove_search<B: Backend>(&mut self, printer: &mut B, all: bool) -> std::io::Result<()> {
+        if all {
+            self.searches.clear(self.dh.cursor_act)
+        } else {
+            self.searches.drop_last(self.dh.cursor_act)
+        }
+        self.redraw(printer, false)
+    }
     /// Initializes the empty search results for the search query
-    /// on the currently active cursors
+    /// on the currently active cursors.
+    /// Difference-only searches are not supported here, since the files are not aligned.
     pub fn setup_search(
         &mut self,
         query: Query,
+        options: SearchOptions,
     ) -> (
         (SearchContext, FileContent),
         Option<(SearchContext, FileContent)>,
     ) {
-        let [first, second] = self.data.get_data();
-        self.searches
-            .setup_search(query, self.dh.cursor_act, [first, second])
+        let [first, second] = self.edited_files();
+        self.searches.setup_search(
+            query,
+            self.dh.cursor_act,
+            [first, second],
+            SearchOptions {
+                only_differences: false,
+                ..options
+            },
+        )
+    }
+    /// Returns whether a search is still running on one of the files
+    pub fn is_search_running(&self) -> bool {
+        self.searches.is_running()
+    }
+    /// Marks the searches that are still running as cancelled
+    pub fn cancel_running_searches(&mut self) {
+        self.searches.cancel_running()
+    }
+    /// The unaligned view has no alignment to write as HTML, so this is always an error
+    pub fn export_html(&self, _path: &Path) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "only aligned files can be exported, press F3 to align them first",
+        ))
+    }
+    /// Writes the search results of both files into a new CSV file at `path`,
+    /// which fails if there already is one
+    pub fn export_search(&self, path: &Path, with_bytes: bool) -> std::io::Result<()> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut out = BufWriter::new(file);
+        let [first, second] = self.edited_files();
+        write_results_csv(
+            &mut out,
+            [
+                (&self.filenames.0, &first, &self.searches.0),
+                (&self.filenames.1, &second, &self.searches.1),
+            ],
+            with_bytes,
+        )?;
+        out.flush()
     }
     /// Returns the active search query for one of the currently cursors
     pub fn current_search_query(&self) -> Option<&Query> {
         self.searches.current_search_query(self.dh.cursor_act)
     }
     /// Turns the view into most of its parts
+    #[allow(clippy::result_large_err)]
     pub fn destruct(self) -> Result<(FileState, FileState, DoubleHexContext), Self> {
         // for now we only return if the cursor is at a positions where both indexes are actually
         // inside the file
@@ -574,10 +1480,375 @@ impl Unaligned {
 
 impl View for Unaligned {
     fn draw(&self, printer: &cursive::Printer) {
-        let mut backend = Cursiv::from_printer(printer);
-        self.redraw(&mut backend, true);
+        let mut backend = Cursiv::from_printer(printer, self.dh.style.palette());
+        // drawing with cursive can not fail
+        let _ = self.redraw(&mut backend, true);
     }
     fn layout(&mut self, size: Vec2) {
         self.resize((size.x, size.y));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::capture::Capture,
+        style::ColumnSetting,
+        testing::{file, temp_path},
+    };
+
+    fn unaligned(first: FileState, second: FileState) -> Unaligned {
+        let mut dh = DoubleHexContext::new((8, 8));
+        dh.style.addr_width = 2;
+        dh.style.ascii_col = true;
+        let mut view = Unaligned::new(first, second, dh);
+        view.resize((80, 10));
+        view
+    }
+
+    #[test]
+    fn empty_file() {
+        let mut view = unaligned(file("added", b"new content"), file("/dev/null", b""));
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        let text = capture.text();
+        assert!(text.contains("6e 65 77 20 63 6f 6e 74"), "{text}");
+        assert!(text.contains("/dev/null"), "{text}");
+        // moving around does not run into the end of the empty file
+        let both_empty = unaligned(file("a", b""), file("b", b""));
+        for mut view in [view, both_empty] {
+            for action in [
+                Action::Down,
+                Action::Right,
+                Action::NextDifference,
+                Action::PgDown,
+            ] {
+                view.process_move(&mut capture, action, 3).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn swap_files() {
+        let mut view = unaligned(file("first", b"0123456789"), file("second", b"abcdef"));
+        let mut capture = Capture::new(80, 10);
+        view.goto(&mut capture, 3, Some(5)).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(3), Some(5)]);
+        view.process_action(&mut capture, Action::CursorFirst)
+            .unwrap();
+        view.swap_files();
+        assert_eq!(view.window_title(), "biodiff: second \u{2194} first");
+        assert_eq!(view.current_cursor_addresses(), [Some(5), Some(3)]);
+        assert_eq!(view.dh.cursor_act, CursorActive::Second);
+        view.refresh(&mut capture).unwrap();
+        let text = capture.text();
+        assert!(
+            text.find("second").unwrap() < text.find("first").unwrap(),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn tiny_screen() {
+        let mut view = unaligned(file("first", b"0123456789"), file("second", b"abcdef"));
+        view.refresh(&mut Capture::new(1, 1)).unwrap();
+        for (columns, rows) in [(20, 20), (80, 3)] {
+            let mut capture = Capture::new(columns, rows);
+            view.refresh(&mut capture).unwrap();
+            let text = capture.text();
+            assert!(text.contains("terminal too small ("), "{text}");
+            // actions are done without drawing over the message
+            view.process_action(&mut capture, Action::Right).unwrap();
+            assert!(!capture.text().contains("30 31"));
+        }
+        assert_eq!(view.current_cursor_addresses(), [Some(2), Some(2)]);
+        // an unknown size keeps the layout
+        assert!(!view.resize((0, 0)));
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        assert!(view.dh.too_small.is_none());
+        // the bytes are shown again, with the view having moved along with the cursor
+        let text = capture.text();
+        assert!(text.contains(" 02 32 33 34"), "{text}");
+    }
+
+    #[test]
+    fn ticks() {
+        let mut view = unaligned(file("first", b"0123456789"), file("second", b"abcdef"));
+        let mut capture = Capture::new(80, 10);
+        view.process_action(&mut capture, Action::PendingCount(Some(3)))
+            .unwrap();
+        view.dh.set_notice(Severity::Info, "Copied the SHA-256");
+        // a tick neither uses up the count prefix nor hides a notice that was just shown
+        view.process_action(&mut capture, Action::Tick).unwrap();
+        assert_eq!(view.dh.pending_count, Some(3));
+        assert_eq!(view.dh.notice_text(), Some("Copied the SHA-256"));
+        view.process_action(&mut capture, Action::Down).unwrap();
+        assert_eq!(view.dh.pending_count, None);
+        assert_eq!(view.dh.notice_text(), None);
+    }
+
+    #[test]
+    fn jump_history() {
+        let mut view = unaligned(
+            file("first", b"0123456789"),
+            file("second", b"abcdefghijkl"),
+        );
+        let mut capture = Capture::new(80, 10);
+        view.goto(&mut capture, 3, Some(1)).unwrap();
+        view.process_action(&mut capture, Action::Bottom).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [None, Some(11)]);
+        view.process_action(&mut capture, Action::JumpBack).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(3), Some(1)]);
+        // moving around is not a jump
+        view.process_action(&mut capture, Action::Left).unwrap();
+        view.process_action(&mut capture, Action::JumpBack).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(0), Some(0)]);
+        view.process_action(&mut capture, Action::JumpForward)
+            .unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(3), Some(1)]);
+        view.process_action(&mut capture, Action::JumpForward)
+            .unwrap();
+        assert_eq!(view.current_cursor_addresses(), [None, Some(11)]);
+        assert_eq!(view.dh.notice_text(), None);
+    }
+
+    #[test]
+    fn single_file() {
+        let only = file("only", b"a single file, shown once");
+        let second = only.shared();
+        let mut view = unaligned(only, second);
+        view.dh.style.single = true;
+        view.dh.cursor_act = CursorActive::First;
+        view.resize((80, 10));
+        let mut capture = Capture::new(80, 10);
+        view.refresh(&mut capture).unwrap();
+        // the one half gets all of the width
+        assert_eq!(view.dh.cursor.get_size_x(), 16);
+        let text = capture.text();
+        assert_eq!(text.matches("61 20 73 69 6e 67").count(), 1, "{text}");
+        assert_eq!(text.matches("only").count(), 1, "{text}");
+        view.process_action(&mut capture, Action::NextDifference)
+            .unwrap();
+        assert!(capture.text().contains("Not available"));
+        view.process_action(&mut capture, Action::CursorBoth)
+            .unwrap();
+        assert_eq!(view.dh.cursor_act, CursorActive::First);
+    }
+
+    #[test]
+    fn undo() {
+        let mut view = unaligned(file("first", b"0123456789"), file("second", b"abcdef"));
+        let mut capture = Capture::new(80, 10);
+        view.start_selection(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::Right, 2).unwrap();
+        view.process_action(&mut capture, Action::ClearSelection)
+            .unwrap();
+        view.align_end(&mut capture).unwrap();
+        view.process_action(&mut capture, Action::AddColumn)
+            .unwrap();
+        view.process_action(&mut capture, Action::ToggleText)
+            .unwrap();
+        assert!(!view.dh.style.ascii_col);
+        assert_eq!(view.data.shift, 4);
+        view.process_action(&mut capture, Action::Undo).unwrap();
+        assert!(view.dh.style.ascii_col);
+        assert!(matches!(
+            view.dh.style.column_count,
+            ColumnSetting::Fixed(_)
+        ));
+        view.process_action(&mut capture, Action::Undo).unwrap();
+        assert!(matches!(view.dh.style.column_count, ColumnSetting::Fit));
+        assert_eq!(view.data.shift, 4);
+        view.process_action(&mut capture, Action::Undo).unwrap();
+        assert_eq!(view.data.shift, 0);
+        // the cursor movements are left alone
+        let cursor = view.current_cursor_addresses();
+        assert_eq!(view.selection_file_ranges(), [None, None]);
+        view.process_action(&mut capture, Action::Undo).unwrap();
+        assert_eq!(view.selection_file_ranges(), [Some(0..3), Some(0..3)]);
+        assert_eq!(view.current_cursor_addresses(), cursor);
+        view.process_action(&mut capture, Action::Undo).unwrap();
+        assert_eq!(view.dh.notice_text(), Some("Nothing to undo"));
+    }
+    #[test]
+    fn edit() {
+        let mut view = unaligned(file("first", b"0123"), file("second", b"0123"));
+        let mut capture = Capture::new(80, 10);
+        // with both cursors active, it is not clear which file to edit
+        view.process_action(&mut capture, Action::Edit).unwrap();
+        assert!(!view.dh.editor.is_active());
+        view.process_action(&mut capture, Action::CursorSecond)
+            .unwrap();
+        view.process_action(&mut capture, Action::Edit).unwrap();
+        for c in "4142".chars() {
+            view.process_action(&mut capture, Action::Type(c)).unwrap();
+        }
+        view.process_action(&mut capture, Action::SwitchInput)
+            .unwrap();
+        view.process_action(&mut capture, Action::Type('z'))
+            .unwrap();
+        view.process_action(&mut capture, Action::StopEditing)
+            .unwrap();
+        let [first, second] = view.edited_files();
+        assert_eq!((&first[..], &second[..]), (&b"0123"[..], &b"ABz3"[..]));
+        // the edits are only read in place of the bytes of the file
+        assert_eq!(&view.files()[1][..], b"0123");
+        assert_eq!(view.current_cursor_addresses()[1], Some(3));
+        assert!(view.dh.has_unsaved_edits());
+        let text = capture.text();
+        assert!(text.contains("[4 bytes, edited]"), "{text}");
+        // the edited bytes are shown
+        assert!(text.contains("41 42 7a 33"), "{text}");
+        let annotated = capture.annotated();
+        assert!(
+            annotated
+                .lines()
+                .any(|line| line.starts_with("fg") && line.contains('e')),
+            "{annotated}"
+        );
+    }
+    #[test]
+    fn export_search() {
+        let view = unaligned(file("first", b"0123"), file("second", b"01"));
+        let path = temp_path("export");
+        view.export_search(&path, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"file,query,start,end\n");
+        // existing files are not overwritten
+        let err = view.export_search(&path, false).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn fill() {
+        let mut view = unaligned(file("first", b"0123"), file("second", b"01"));
+        let mut capture = Capture::new(80, 10);
+        view.process_move(&mut capture, Action::Right, 3).unwrap();
+        view.start_selection(&mut capture).unwrap();
+        view.process_move(&mut capture, Action::Left, 2).unwrap();
+        view.process_action(&mut capture, Action::CursorFirst)
+            .unwrap();
+        assert!(view.fill_range().is_err());
+        view.process_action(&mut capture, Action::Edit).unwrap();
+        let (second, range) = view.fill_range().unwrap();
+        assert_eq!((second, range.clone()), (false, 1..4));
+        view.fill(second, range, 0x90);
+        assert_eq!(&view.edited_files()[0][..], b"0\x90\x90\x90");
+        view.process_action(&mut capture, Action::UndoEdit).unwrap();
+        assert_eq!(&view.edited_files()[0][..], b"0123");
+        assert!(!view.dh.has_unsaved_edits());
+        // the selection of the second file reaches beyond its end
+        view.process_action(&mut capture, Action::CursorSecond)
+            .unwrap();
+        assert!(view.fill_range().is_err());
+    }
+    #[test]
+    fn second_columns() {
+        let content = (0..=255).collect::<Vec<u8>>();
+        let mut view = unaligned(file("first", &content), file("second", &content));
+        let mut capture = Capture::new(80, 10);
+        let bytes_per_row = view.dh.cursor.bytes_per_row();
+        view.process_action(&mut capture, Action::CursorSecond)
+            .unwrap();
+        view.process_action(&mut capture, Action::RemoveColumn)
+            .unwrap();
+        assert!(matches!(view.dh.style.column_count, ColumnSetting::Fit));
+        assert_eq!(view.dh.cursor.bytes_per_row(), bytes_per_row);
+        let grid = view.dh.second_grid.unwrap();
+        assert_eq!(grid.bytes_per_row, bytes_per_row - 1);
+        assert!(capture
+            .text()
+            .contains(&format!("Columns: fit|{}", bytes_per_row - 1)));
+        // the second cursor stays in the row of the first one, at the column of its address
+        view.process_action(&mut capture, Action::CursorBoth)
+            .unwrap();
+        view.process_move(&mut capture, Action::Down, 1).unwrap();
+        assert_eq!(
+            view.current_cursor_addresses(),
+            [Some(bytes_per_row), Some(bytes_per_row)]
+        );
+        assert_eq!(view.second_column(grid), 1);
+        // the rows above the cursor row are counted back from its start
+        let rows_above = view.dh.cursor.get_y() * grid.bytes_per_row;
+        assert_eq!(
+            view.second_start(grid) + rows_above as isize,
+            grid.bytes_per_row as isize
+        );
+        // changing both keeps the difference
+        view.process_action(&mut capture, Action::AddColumn)
+            .unwrap();
+        assert_eq!(
+            view.dh.style.second_column_count,
+            Some(ColumnSetting::Fixed(bytes_per_row as u16))
+        );
+        view.process_action(&mut capture, Action::ResetColumn)
+            .unwrap();
+        assert_eq!(view.dh.style.second_column_count, None);
+        assert_eq!(view.dh.second_grid, None);
+    }
+    #[test]
+    fn offset_lock() {
+        let mut view = unaligned(file("first", &[0; 16]), file("second", &[0; 32]));
+        let mut capture = Capture::new(80, 10);
+        view.goto(&mut capture, 3, Some(5)).unwrap();
+        view.process_action(&mut capture, Action::CursorFirst)
+            .unwrap();
+        view.process_action(&mut capture, Action::OffsetLock)
+            .unwrap();
+        assert_eq!(view.dh.cursor_act, CursorActive::Both);
+        // both cursors stay inside of their files
+        view.process_action(&mut capture, Action::Bottom).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(15), Some(17)]);
+        let text = capture.text();
+        assert!(text.contains("Offset locked: +0x2"), "{text}");
+        view.process_move(&mut capture, Action::Down, 3).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(15), Some(17)]);
+        view.goto(&mut capture, 8, None).unwrap();
+        assert_eq!(view.current_cursor_addresses(), [Some(8), Some(10)]);
+        // setting another offset changes the locked one
+        view.align_end(&mut capture).unwrap();
+        assert!(view.dh.offset_lock);
+        assert_eq!(view.current_cursor_addresses(), [Some(15), Some(31)]);
+        view.process_action(&mut capture, Action::CursorSecond)
+            .unwrap();
+        assert!(!view.dh.offset_lock);
+    }
+    #[test]
+    fn restore_positions() {
+        let mut view = unaligned(file("first", &[0; 16]), file("second", &[0; 32]));
+        let mut capture = Capture::new(80, 10);
+        view.goto(&mut capture, 3, Some(20)).unwrap();
+        let positions = view.positions();
+        assert_eq!(positions, [3, 20]);
+        let mut restored = unaligned(file("first", &[0; 16]), file("second", &[0; 32]));
+        restored.set_positions(positions);
+        assert_eq!(restored.current_cursor_addresses(), [Some(3), Some(20)]);
+        // a cursor outside of its file keeps the offset
+        restored.set_positions([3, -5]);
+        assert_eq!(restored.positions(), [3, -5]);
+        assert_eq!(restored.current_cursor_addresses(), [Some(3), None]);
+    }
+
+    #[test]
+    fn paging() {
+        let mut view = unaligned(file("first", &[0; 0x1000]), file("second", &[0; 0x1000]));
+        let mut capture = Capture::new(80, 10);
+        view.goto(&mut capture, 0x800, None).unwrap();
+        let row = view.dh.cursor.bytes_per_row();
+        let rows = view.dh.cursor.get_size_y();
+        assert_eq!(view.dh.page_rows(), rows as isize - 2);
+        view.process_move(&mut capture, Action::PgDown, 1).unwrap();
+        let paged = 0x800 + (rows - 2) * row;
+        assert_eq!(view.current_cursor_addresses(), [Some(paged), Some(paged)]);
+        view.process_move(&mut capture, Action::HalfPgUp, 1)
+            .unwrap();
+        let half = paged - rows / 2 * row;
+        assert_eq!(view.current_cursor_addresses(), [Some(half), Some(half)]);
+        // without overlap a page moves by the whole screen
+        view.dh.style.page_overlap = 0;
+        view.process_move(&mut capture, Action::PgUp, 1).unwrap();
+        let whole = half - rows * row;
+        assert_eq!(view.current_cursor_addresses(), [Some(whole), Some(whole)]);
+    }
+}